@@ -0,0 +1,32 @@
+#!/usr/bin/env bash
+# Run the official `openai` Python SDK against a local transcription endpoint backed by a mock handler.
+#
+# Usage: conformance/run.sh [pytest args...]
+# The SDK version can be pinned through OPENAI_SDK_VERSION (defaults to the latest release).
+set -euo pipefail
+
+ROOT="$(cd "$(dirname "${BASH_SOURCE[0]}")/.." && pwd)"
+PORT="${CONFORMANCE_PORT:-8765}"
+VENV="${CONFORMANCE_VENV:-${ROOT}/target/conformance-venv}"
+
+cargo build --manifest-path "${ROOT}/Cargo.toml" -p hfendpoints-openai --example mock_transcription
+
+if [ ! -d "${VENV}" ]; then
+  python3 -m venv "${VENV}"
+fi
+"${VENV}/bin/pip" install --quiet --upgrade "openai${OPENAI_SDK_VERSION:+==${OPENAI_SDK_VERSION}}" pytest
+
+"${ROOT}/target/debug/examples/mock_transcription" 127.0.0.1 "${PORT}" &
+SERVER_PID=$!
+trap 'kill "${SERVER_PID}" 2>/dev/null || true' EXIT
+
+# Wait for the endpoint to accept connections
+for _ in $(seq 1 50); do
+  if curl --silent --fail "http://127.0.0.1:${PORT}/api/v1/models" > /dev/null; then
+    break
+  fi
+  sleep 0.2
+done
+
+CONFORMANCE_BASE_URL="http://127.0.0.1:${PORT}/api/v1" \
+  "${VENV}/bin/python" -m pytest "${ROOT}/conformance" "$@"
@@ -0,0 +1,90 @@
+"""
+Matrix of calls issued through the official `openai` SDK against the mock transcription endpoint.
+
+The mock handler echoes the uploaded "audio" back as the transcript, see
+`hfendpoints-openai/examples/mock_transcription.rs`. Run through `conformance/run.sh`.
+"""
+import os
+
+import openai
+import pytest
+from openai import OpenAI
+
+AUDIO = ("audio.wav", b"Hello from hfendpoints", "audio/wav")
+TRANSCRIPT = "Hello from hfendpoints"
+
+
+@pytest.fixture(scope="module")
+def client() -> OpenAI:
+    return OpenAI(
+        base_url=os.environ.get("CONFORMANCE_BASE_URL", "http://127.0.0.1:8765/api/v1"),
+        api_key="hfendpoints",
+        max_retries=0,
+    )
+
+
+def test_transcription_json(client: OpenAI):
+    transcription = client.audio.transcriptions.create(model="whisper-1", file=AUDIO)
+    assert transcription.text == TRANSCRIPT
+
+
+def test_transcription_explicit_json(client: OpenAI):
+    transcription = client.audio.transcriptions.create(model="whisper-1", file=AUDIO, response_format="json")
+    assert transcription.text == TRANSCRIPT
+
+
+def test_transcription_text(client: OpenAI):
+    transcription = client.audio.transcriptions.create(model="whisper-1", file=AUDIO, response_format="text")
+    assert transcription.strip() == TRANSCRIPT
+
+
+def test_transcription_verbose_json(client: OpenAI):
+    transcription = client.audio.transcriptions.create(
+        model="whisper-1", file=AUDIO, response_format="verbose_json", language="fr"
+    )
+    assert transcription.text == TRANSCRIPT
+    assert transcription.language == "fr"
+    assert transcription.duration == 1.0
+    assert [segment.text for segment in transcription.segments] == [TRANSCRIPT]
+
+
+def test_transcription_stream(client: OpenAI):
+    stream = client.audio.transcriptions.create(model="whisper-1", file=AUDIO, stream=True)
+    events = list(stream)
+
+    assert [event.type for event in events[:-1]] == ["transcript.text.delta"] * 3
+    assert "".join(event.delta for event in events[:-1]) == TRANSCRIPT
+    assert events[-1].type == "transcript.text.done"
+    assert events[-1].text == TRANSCRIPT
+
+
+def test_transcription_unsupported_response_format(client: OpenAI):
+    with pytest.raises(openai.APIStatusError) as error:
+        client.audio.transcriptions.create(model="whisper-1", file=AUDIO, response_format="srt")
+    assert 400 <= error.value.status_code < 500
+
+
+def test_transcription_missing_file(client: OpenAI):
+    with pytest.raises(openai.APIStatusError) as error:
+        client.post(
+            "/audio/transcriptions",
+            body={"model": "whisper-1"},
+            options={"headers": {"Content-Type": "multipart/form-data"}},
+            files=[("language", (None, b"en"))],
+            cast_to=object,
+        )
+    assert 400 <= error.value.status_code < 500
+
+
+def test_transcription_handler_failure(client: OpenAI):
+    with pytest.raises(openai.InternalServerError):
+        client.audio.transcriptions.create(model="whisper-1", file=("fail.wav", b"fail", "audio/wav"))
+
+
+def test_models(client: OpenAI):
+    models = client.models.list()
+    assert [model.id for model in models] == ["mock"]
+    assert client.models.retrieve("mock").id == "mock"
+
+    with pytest.raises(openai.NotFoundError):
+        client.models.retrieve("unknown")
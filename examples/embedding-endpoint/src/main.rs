@@ -0,0 +1,94 @@
+//! Embeddings endpoint backed by a handler implemented in Rust, served without the Python bindings.
+//!
+//! The "model" embeds texts through the hashing trick: every word increments the dimension its
+//! hash falls in, the vector being normalized afterward. A Whisper or candle backend plugs in the
+//! same way, implementing `Handler` for the requests of its task.
+//!
+//! ```shell
+//! cargo run -p hfendpoints-example-embeddings -- 127.0.0.1 8000
+//! curl http://127.0.0.1:8000/api/v1/embeddings -H 'Content-Type: application/json' -d '{"input": "Hello world"}'
+//! ```
+use hfendpoints_core::{Error, Handler};
+use hfendpoints_openai::embeddings::embedding::{EmbeddingRequest, EmbeddingResponse};
+use hfendpoints_openai::{Context, Endpoint, ModelCard};
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+/// Identifier of the model advertised by the endpoint
+const MODEL_ID: &str = "hashing-embeddings";
+
+/// Number of dimensions of the embeddings unless requested otherwise
+const DEFAULT_DIMENSIONS: usize = 256;
+
+struct HashingEmbedder;
+
+impl HashingEmbedder {
+    /// Normalized bag of the words of `text`, hashed over `dimensions` buckets
+    fn embed(text: &str, dimensions: usize) -> Vec<f32> {
+        let mut embedding = vec![0.0f32; dimensions];
+        for word in text.split_whitespace() {
+            let mut hasher = DefaultHasher::new();
+            word.to_lowercase().hash(&mut hasher);
+            embedding[hasher.finish() as usize % dimensions] += 1.0;
+        }
+
+        let norm = embedding
+            .iter()
+            .map(|value| value * value)
+            .sum::<f32>()
+            .sqrt();
+        if norm > 0.0 {
+            embedding.iter_mut().for_each(|value| *value /= norm);
+        }
+        embedding
+    }
+}
+
+impl Handler for HashingEmbedder {
+    type Request = (EmbeddingRequest, Context);
+    type Response = EmbeddingResponse;
+
+    async fn on_request(&self, request: Self::Request) -> Result<Self::Response, Error> {
+        let (request, _ctx) = request;
+        let dimensions = request
+            .dimensions
+            .map_or(DEFAULT_DIMENSIONS, |dimensions| dimensions as usize);
+
+        let embeddings = request
+            .input
+            .iter()
+            .map(|input| Self::embed(input, dimensions))
+            .collect();
+        let tokens = request
+            .input
+            .iter()
+            .map(|input| input.split_whitespace().count() as u32)
+            .sum();
+
+        Ok(EmbeddingResponse::new(
+            String::from(MODEL_ID),
+            embeddings,
+            tokens,
+        ))
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    let mut args = std::env::args().skip(1);
+    let host = args.next().unwrap_or(String::from("127.0.0.1"));
+    let port = args
+        .next()
+        .map(|port| port.parse::<u16>().expect("Port must be a number"))
+        .unwrap_or(8000);
+
+    let served = Endpoint::builder()
+        .handler(HashingEmbedder)
+        .model(ModelCard::new(MODEL_ID))
+        .serve((host, port))
+        .await;
+
+    if let Err(err) = served {
+        eprintln!("Failed to serve the embeddings endpoint: {err}");
+        std::process::exit(1);
+    }
+}
@@ -1,46 +1,249 @@
-mod io {
-    // use std::io::Cursor;
-    // use symphonia::core::audio::{RawSample, RawSampleBuffer, SignalSpec};
-    // use symphonia::core::codecs::{CodecParameters, DecoderOptions};
-    // use symphonia::core::errors::Result as SymphoniaResult;
-    // use symphonia::core::formats::FormatOptions;
-    // use symphonia::core::io::{MediaSourceStream, MediaSourceStreamOptions};
-    // use symphonia::core::meta::MetadataOptions;
-    // use symphonia::core::probe::Hint;
-    // use symphonia::core::units::Time;
-    // use symphonia::default::{get_codecs, get_probe};
-    // use tracing::instrument;
-    //
-    // #[instrument(skip_all)]
-    // pub fn load_audio<T: RawSample>(wave: &[u8]) -> SymphoniaResult<(RawSampleBuffer<T>, Time, CodecParameters)> {
-    //     let codecs = get_codecs();
-    //     let probe = get_probe();
-    //
-    //     let cursor = Cursor::new(wave);
-    //     let stream = MediaSourceStream::new(Box::new(cursor), MediaSourceStreamOptions::default());
-    //
-    //     // Detect audio format
-    //     let hint = Hint::default();
-    //     let mut guess = probe.format(&hint, stream, &FormatOptions::default(), &MetadataOptions::default())?;
-    //
-    //     // Allocate audio decoder for the target audio format
-    //     let mut decoder = codecs.make(&guess.format, &DecoderOptions::default())?;
-    //     let codec_params = decoder.codec_params();
-    //
-    //     // Decode until the end
-    //     let duration = codec_params.time_base.unwrap().calc_time(codec_params.n_frames.unwrap());
-    //     let mut raw_audio_buffer = RawSampleBuffer::<T>::new(
-    //         duration.seconds + 1,
-    //         SignalSpec::new(codec_params.sample_rate.unwrap(), codec_params.channels.unwrap()),
-    //     );
-    //     loop {
-    //         let packet = guess.format.next_packet()?;
-    //         let decoded = decoder.decode(&packet)?;
-    //         decoded.convert::<T>(&mut raw_audio_buffer);
-    //     }
-    //
-    //     Ok((raw_audio_buffer, duration, codec_params.clone()))
-    // }
+pub mod io {
+    use std::f64::consts::PI;
+    use std::io::{Cursor, Read};
+    use symphonia::core::audio::SampleBuffer;
+    use symphonia::core::codecs::DecoderOptions;
+    use symphonia::core::errors::Error as SymphoniaError;
+    use symphonia::core::formats::FormatOptions;
+    use symphonia::core::io::{
+        MediaSource, MediaSourceStream, MediaSourceStreamOptions, ReadOnlySource,
+    };
+    use symphonia::core::meta::MetadataOptions;
+    use symphonia::core::probe::Hint;
+    use symphonia::default::{get_codecs, get_probe};
+    use thiserror::Error;
+    use tracing::{debug, instrument, warn};
+
+    /// Number of zero crossings of the windowed sinc, on each side, used to resample
+    const RESAMPLING_ZERO_CROSSINGS: f64 = 8.0;
+
+    #[derive(Debug, Error)]
+    pub enum DecodeError {
+        #[error("Audio holds no track to decode")]
+        NoTrack,
+
+        #[error("Audio track does not declare its sample rate")]
+        UnknownSampleRate,
+
+        #[error(transparent)]
+        Symphonia(#[from] SymphoniaError),
+    }
+
+    /// Mono audio samples, in [-1.0, 1.0]
+    #[derive(Clone, Debug)]
+    pub struct Pcm {
+        pub samples: Vec<f32>,
+        pub sample_rate: u32,
+    }
+
+    impl Pcm {
+        /// Duration of the audio, in seconds
+        pub fn duration(&self) -> f64 {
+            self.samples.len() as f64 / self.sample_rate as f64
+        }
+    }
+
+    /// Decode the audio file read from `source`, in any of the formats known to symphonia, handing
+    /// the interleaved samples of every packet, along with their number of channels, over to `sink`.
+    ///
+    /// `mime_type`, if known, helps detecting the format of the file. Corrupted packets are skipped
+    /// rather than failing the whole file. Returns the sample rate of the audio.
+    fn decode(
+        source: Box<dyn MediaSource>,
+        mime_type: Option<&str>,
+        mut sink: impl FnMut(&[f32], usize),
+    ) -> Result<u32, DecodeError> {
+        let mut hint = Hint::new();
+        if let Some(mime_type) = mime_type {
+            hint.mime_type(mime_type);
+        }
+
+        // Detect audio format
+        let stream = MediaSourceStream::new(source, MediaSourceStreamOptions::default());
+        let mut probed = get_probe().format(
+            &hint,
+            stream,
+            &FormatOptions::default(),
+            &MetadataOptions::default(),
+        )?;
+        let track = probed.format.default_track().ok_or(DecodeError::NoTrack)?;
+        let (track_id, source_rate) = (
+            track.id,
+            track
+                .codec_params
+                .sample_rate
+                .ok_or(DecodeError::UnknownSampleRate)?,
+        );
+
+        // Allocate audio decoder for the target audio format
+        let mut decoder = get_codecs().make(&track.codec_params, &DecoderOptions::default())?;
+
+        // Decode until the end
+        let mut buffer: Option<SampleBuffer<f32>> = None;
+        loop {
+            let packet = match probed.format.next_packet() {
+                Ok(packet) => packet,
+                Err(SymphoniaError::IoError(err))
+                    if err.kind() == std::io::ErrorKind::UnexpectedEof =>
+                {
+                    break;
+                }
+                Err(SymphoniaError::ResetRequired) => break,
+                Err(err) => return Err(err.into()),
+            };
+            if packet.track_id() != track_id {
+                continue;
+            }
+
+            let decoded = match decoder.decode(&packet) {
+                Ok(decoded) => decoded,
+                Err(SymphoniaError::DecodeError(err)) => {
+                    warn!("Skipping corrupted audio packet: {err}");
+                    continue;
+                }
+                Err(err) => return Err(err.into()),
+            };
+
+            let channels = decoded.spec().channels.count().max(1);
+            let buffer = match &mut buffer {
+                Some(buffer) if buffer.capacity() >= decoded.capacity() * channels => buffer,
+                _ => buffer.insert(SampleBuffer::new(
+                    decoded.capacity() as u64,
+                    *decoded.spec(),
+                )),
+            };
+            buffer.copy_interleaved_ref(decoded);
+            sink(buffer.samples(), channels);
+        }
+
+        Ok(source_rate)
+    }
+
+    /// Decode the `audio` file, in any of the formats known to symphonia, into mono PCM at `sample_rate`.
+    ///
+    /// `mime_type`, if known, helps detecting the format of the file, channels are mixed down
+    /// averaging them. Corrupted packets are skipped rather than failing the whole file.
+    #[instrument(skip(audio))]
+    pub fn decode_to_pcm<T>(
+        audio: T,
+        mime_type: Option<&str>,
+        sample_rate: u32,
+    ) -> Result<Pcm, DecodeError>
+    where
+        T: AsRef<[u8]> + Send + Sync + 'static,
+    {
+        mixdown(Box::new(Cursor::new(audio)), mime_type, sample_rate)
+    }
+
+    /// Decode the audio file read from `reader` as it goes, into mono PCM at `sample_rate`.
+    ///
+    /// The file is never seeked, only the formats laid out to be read sequentially (i.e. wav or
+    /// ogg) are fully decoded, see [`decode_to_pcm`] for the other ones.
+    #[instrument(skip(reader))]
+    pub fn decode_stream_to_pcm<R>(
+        reader: R,
+        mime_type: Option<&str>,
+        sample_rate: u32,
+    ) -> Result<Pcm, DecodeError>
+    where
+        R: Read + Send + Sync + 'static,
+    {
+        mixdown(
+            Box::new(ReadOnlySource::new(reader)),
+            mime_type,
+            sample_rate,
+        )
+    }
+
+    /// Decode the audio file read from `source` into mono PCM at `sample_rate`, channels being
+    /// averaged
+    fn mixdown(
+        source: Box<dyn MediaSource>,
+        mime_type: Option<&str>,
+        sample_rate: u32,
+    ) -> Result<Pcm, DecodeError> {
+        let mut mono = Vec::new();
+        let source_rate = decode(source, mime_type, |samples, channels| {
+            mono.extend(
+                samples
+                    .chunks_exact(channels)
+                    .map(|frame| frame.iter().sum::<f32>() / channels as f32),
+            )
+        })?;
+
+        debug!("Decoded {} samples at {source_rate}Hz", mono.len());
+        Ok(Pcm {
+            samples: resample(&mono, source_rate, sample_rate),
+            sample_rate,
+        })
+    }
+
+    /// Decode the `audio` file, in any of the formats known to symphonia, into one mono PCM per
+    /// channel, at the sample rate of the file
+    #[instrument(skip(audio))]
+    pub fn decode_channels<T>(audio: T, mime_type: Option<&str>) -> Result<Vec<Pcm>, DecodeError>
+    where
+        T: AsRef<[u8]> + Send + Sync + 'static,
+    {
+        let mut planes: Vec<Vec<f32>> = Vec::new();
+        let sample_rate = decode(
+            Box::new(Cursor::new(audio)),
+            mime_type,
+            |samples, channels| {
+                planes.resize_with(planes.len().max(channels), Vec::new);
+                for frame in samples.chunks_exact(channels) {
+                    for (plane, sample) in planes.iter_mut().zip(frame) {
+                        plane.push(*sample);
+                    }
+                }
+            },
+        )?;
+
+        debug!("Decoded {} channels at {sample_rate}Hz", planes.len());
+        Ok(planes
+            .into_iter()
+            .map(|samples| Pcm {
+                samples,
+                sample_rate,
+            })
+            .collect())
+    }
+
+    /// Resample `samples` from the rate `from` to the rate `to`, through a Hann windowed sinc
+    /// interpolation, low-pass filtering them below the target Nyquist frequency when downsampling
+    pub fn resample(samples: &[f32], from: u32, to: u32) -> Vec<f32> {
+        if from == to || samples.is_empty() {
+            return samples.to_vec();
+        }
+
+        let ratio = to as f64 / from as f64;
+        let cutoff = ratio.min(1.0);
+        let half_width = RESAMPLING_ZERO_CROSSINGS / cutoff;
+        let length = (samples.len() as f64 * ratio).round() as usize;
+
+        (0..length)
+            .map(|index| {
+                let center = index as f64 / ratio;
+                let first = (center - half_width).ceil().max(0.0) as usize;
+                let last = ((center + half_width).floor() as usize).min(samples.len() - 1);
+
+                let mut sample = 0.0;
+                for (offset, input) in samples
+                    .get(first..=last)
+                    .unwrap_or_default()
+                    .iter()
+                    .enumerate()
+                {
+                    let distance = (first + offset) as f64 - center;
+                    let x = PI * distance * cutoff;
+                    let sinc = if x == 0.0 { 1.0 } else { x.sin() / x };
+                    let window = 0.5 + 0.5 * (PI * distance / half_width).cos();
+                    sample += *input as f64 * cutoff * sinc * window;
+                }
+                sample as f32
+            })
+            .collect()
+    }
 
     #[cfg(feature = "python")]
     pub(crate) mod python {
@@ -52,6 +255,8 @@ mod io {
 
         #[pyclass(name = "NativeAudioBuffer")]
         pub struct PyAudioBuffer {
+            // Held until load_audio_to_pcm gives Python access to the samples
+            #[allow(dead_code)]
             pcm: RawSampleBuffer<f32>,
             duration: Time,
             codec: CodecParameters,
@@ -71,7 +276,10 @@ mod io {
 
             #[getter]
             fn channels(&self) -> usize {
-                self.codec.channels.map(|channels| channels.count()).unwrap_or(0)
+                self.codec
+                    .channels
+                    .map(|channels| channels.count())
+                    .unwrap_or(0)
             }
 
             fn resample(&mut self) {}
@@ -85,7 +293,8 @@ mod io {
 
         pub fn bind<'py>(py: Python<'py>, name: &str) -> PyResult<Bound<'py, PyModule>> {
             let module = ImportablePyModuleBuilder::new(py, name)?
-                .add_class::<PyAudioBuffer>()?.finish();
+                .add_class::<PyAudioBuffer>()?
+                .finish();
 
             // module.add_function(wrap_pyfunction!(py_load_audio)?)?;
             Ok(module)
@@ -108,4 +317,86 @@ pub mod python {
 
         Ok(module)
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use crate::io::{decode_channels, decode_stream_to_pcm, decode_to_pcm, resample};
+    use std::f32::consts::PI;
+
+    /// Stereo 16-bit WAV file holding `frames` of a 440Hz sine at `sample_rate`, the right channel muted
+    fn wav(sample_rate: u32, frames: usize) -> Vec<u8> {
+        let data_size = (frames * 4) as u32;
+        let mut wav = Vec::new();
+        wav.extend_from_slice(b"RIFF");
+        wav.extend_from_slice(&(36 + data_size).to_le_bytes());
+        wav.extend_from_slice(b"WAVEfmt ");
+        wav.extend_from_slice(&16u32.to_le_bytes());
+        wav.extend_from_slice(&1u16.to_le_bytes());
+        wav.extend_from_slice(&2u16.to_le_bytes());
+        wav.extend_from_slice(&sample_rate.to_le_bytes());
+        wav.extend_from_slice(&(sample_rate * 4).to_le_bytes());
+        wav.extend_from_slice(&4u16.to_le_bytes());
+        wav.extend_from_slice(&16u16.to_le_bytes());
+        wav.extend_from_slice(b"data");
+        wav.extend_from_slice(&data_size.to_le_bytes());
+        for n in 0..frames {
+            let sample =
+                (0.5 * (2.0 * PI * 440.0 * n as f32 / sample_rate as f32).sin() * 32767.0) as i16;
+            wav.extend_from_slice(&sample.to_le_bytes());
+            wav.extend_from_slice(&0i16.to_le_bytes());
+        }
+        wav
+    }
+
+    #[test]
+    fn decode_stereo_wav_to_mono_16khz() {
+        let pcm = decode_to_pcm(wav(44100, 44100), Some("audio/wav"), 16000).unwrap();
+        assert_eq!(pcm.sample_rate, 16000);
+        assert_eq!(pcm.samples.len(), 16000);
+        assert!((pcm.duration() - 1.0).abs() < 1e-6);
+
+        // Channels are averaged, the muted one halving the amplitude
+        let peak = pcm.samples[1000..15000]
+            .iter()
+            .fold(0.0f32, |peak, sample| peak.max(sample.abs()));
+        assert!((peak - 0.25).abs() < 0.01, "{peak}");
+
+        assert!(decode_to_pcm(b"definitely not audio".to_vec(), None, 16000).is_err());
+    }
+
+    #[test]
+    fn decode_wav_without_seeking() {
+        let reader = std::io::Cursor::new(wav(16000, 8000));
+        let pcm = decode_stream_to_pcm(reader, Some("audio/wav"), 16000).unwrap();
+        assert_eq!(pcm.samples.len(), 8000);
+    }
+
+    #[test]
+    fn decode_every_channel_on_its_own() {
+        let channels = decode_channels(wav(44100, 4410), Some("audio/wav")).unwrap();
+        assert_eq!(channels.len(), 2);
+        assert_eq!(channels[0].sample_rate, 44100);
+        assert_eq!(channels[0].samples.len(), 4410);
+        assert!(channels[0].samples.iter().any(|sample| sample.abs() > 0.4));
+        assert!(channels[1].samples.iter().all(|sample| *sample == 0.0));
+    }
+
+    #[test]
+    fn resample_filters_frequencies_over_the_target_nyquist() {
+        let tone = |frequency: f32| -> Vec<f32> {
+            (0..48000)
+                .map(|n| (2.0 * PI * frequency * n as f32 / 48000.0).sin())
+                .collect()
+        };
+        let rms = |samples: &[f32]| {
+            (samples.iter().map(|s| s * s).sum::<f32>() / samples.len() as f32).sqrt()
+        };
+
+        // 1kHz goes through, 12kHz is above the 8kHz Nyquist frequency of 16kHz audio
+        let kept = resample(&tone(1000.0), 48000, 16000);
+        let dropped = resample(&tone(12000.0), 48000, 16000);
+        assert_eq!(kept.len(), 16000);
+        assert!((rms(&kept[100..15900]) - std::f32::consts::FRAC_1_SQRT_2).abs() < 0.01);
+        assert!(rms(&dropped[100..15900]) < 0.01);
+    }
+}
@@ -55,4 +55,74 @@ pub unsafe fn fill_view_from_readonly_data(
         (*view).internal = ptr::null_mut();
     }
     Ok(())
-}
\ No newline at end of file
+}
+/// # Safety
+///
+/// `view` must be a valid pointer to ffi::Py_buffer, or null
+/// `data` must outlive the Python lifetime of `owner` (i.e. data must be owned by owner, or data
+/// must be static data)
+/// The view must be released through [`release_view`], freeing the format, shape and strides
+pub unsafe fn fill_view_from_readonly_samples(
+    view: *mut ffi::Py_buffer,
+    flags: c_int,
+    data: &[f32],
+    owner: Bound<'_, PyAny>,
+) -> PyResult<()> {
+    if view.is_null() {
+        return Err(PyBufferError::new_err("View is null"));
+    }
+
+    if (flags & ffi::PyBUF_WRITABLE) == ffi::PyBUF_WRITABLE {
+        return Err(PyBufferError::new_err("Object is not writable"));
+    }
+
+    unsafe {
+        (*view).obj = owner.into_ptr();
+
+        (*view).buf = data.as_ptr() as *mut c_void;
+        (*view).len = size_of_val(data) as isize;
+        (*view).readonly = 1;
+        (*view).itemsize = size_of::<f32>() as isize;
+
+        // Consumers not asking for the format expect unsigned bytes
+        (*view).format = if (flags & ffi::PyBUF_FORMAT) == ffi::PyBUF_FORMAT {
+            CString::new("f")?.into_raw()
+        } else {
+            ptr::null_mut()
+        };
+
+        // Shape and strides count samples, they are kept alive along the view through `internal`
+        let layout = Box::into_raw(Box::new([data.len() as isize, (*view).itemsize]));
+        (*view).internal = layout as *mut c_void;
+
+        (*view).ndim = 1;
+        (*view).shape = if (flags & ffi::PyBUF_ND) == ffi::PyBUF_ND {
+            &mut (*layout)[0]
+        } else {
+            ptr::null_mut()
+        };
+
+        (*view).strides = if (flags & ffi::PyBUF_STRIDES) == ffi::PyBUF_STRIDES {
+            &mut (*layout)[1]
+        } else {
+            ptr::null_mut()
+        };
+
+        (*view).suboffsets = ptr::null_mut();
+    }
+    Ok(())
+}
+
+/// # Safety
+///
+/// `view` must be a valid pointer to a ffi::Py_buffer filled by [`fill_view_from_readonly_samples`]
+pub unsafe fn release_view(view: *mut ffi::Py_buffer) {
+    unsafe {
+        if !(*view).format.is_null() {
+            drop(CString::from_raw((*view).format));
+        }
+        if !(*view).internal.is_null() {
+            drop(Box::from_raw((*view).internal as *mut [isize; 2]));
+        }
+    }
+}
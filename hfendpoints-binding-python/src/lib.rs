@@ -5,12 +5,12 @@ mod buffers;
 #[cfg(feature = "tokio")]
 pub mod tokio;
 
-pub use buffers::fill_view_from_readonly_data;
+pub use buffers::{fill_view_from_readonly_data, fill_view_from_readonly_samples, release_view};
 
 use pyo3::exceptions::PyImportError;
 use pyo3::prelude::*;
 use pyo3::types::{PyList, PyNone};
-use pyo3::{ffi, PyClass};
+use pyo3::{PyClass, ffi};
 use std::ffi::CString;
 
 pub struct ImportablePyModuleBuilder<'py> {
@@ -0,0 +1,287 @@
+//! `hfendpoints-build`, building the `hfendpoints` wheels for the Linux platforms endpoints are
+//! deployed on, from any of them.
+//!
+//! ```shell
+//! cargo run -p hfendpoints-build -- wheel --target aarch64-manylinux --target aarch64-musllinux
+//! ```
+//!
+//! The extension targets the stable ABI of Python 3.12 (`abi3-py312`), wheels are built without
+//! any interpreter of the target platform nor `PYO3_CONFIG_FILE`, and never link against
+//! `libpython`. The only native code compiled is the one of the C dependencies (`ring`, `zstd`):
+//! when the target differs from the host, or relies on musl, it is compiled and linked by zig
+//! through `maturin --zig`, which also pins the glibc version to the one of the manylinux policy.
+//!
+//! Requires `maturin` and `ziglang` (`pip install maturin ziglang`), along with the Rust standard
+//! library of the target (`rustup target add aarch64-unknown-linux-gnu`).
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitCode};
+
+/// Crate building the `hfendpoints` Python extension, relative to the workspace
+const EXTENSION_MANIFEST: &str = "hfendpoints/Cargo.toml";
+
+#[derive(Parser)]
+#[command(
+    name = "hfendpoints-build",
+    about = "Build the hfendpoints wheels for the supported platforms"
+)]
+struct Cli {
+    #[command(subcommand)]
+    command: BuildCommand,
+}
+
+#[derive(Subcommand)]
+enum BuildCommand {
+    /// Build the wheel of every target, one after the other
+    Wheel(WheelArgs),
+}
+
+#[derive(Args)]
+struct WheelArgs {
+    /// Platform the wheel is built for, repeated to build several of them
+    #[arg(long, value_enum, required = true)]
+    target: Vec<Platform>,
+
+    /// Features enabled on top of `python`, i.e. `audio-decode,gpu`
+    #[arg(long, value_delimiter = ',')]
+    features: Vec<String>,
+
+    /// Directory the wheels are written to
+    #[arg(long, default_value = "target/wheels")]
+    out: PathBuf,
+
+    /// Compile and link through zig even when building for the host
+    #[arg(long)]
+    zig: bool,
+
+    /// Print the commands instead of running them
+    #[arg(long)]
+    dry_run: bool,
+}
+
+/// Platforms the wheels are built for
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+enum Platform {
+    #[value(name = "x86_64-manylinux")]
+    X86_64Manylinux,
+    Aarch64Manylinux,
+    #[value(name = "x86_64-musllinux")]
+    X86_64Musllinux,
+    Aarch64Musllinux,
+}
+
+impl Platform {
+    /// Rust target triple of the platform
+    fn triple(self) -> &'static str {
+        match self {
+            Self::X86_64Manylinux => "x86_64-unknown-linux-gnu",
+            Self::Aarch64Manylinux => "aarch64-unknown-linux-gnu",
+            Self::X86_64Musllinux => "x86_64-unknown-linux-musl",
+            Self::Aarch64Musllinux => "aarch64-unknown-linux-musl",
+        }
+    }
+
+    /// Platform tag policy the wheel complies with
+    fn compatibility(self) -> &'static str {
+        match self {
+            Self::X86_64Manylinux | Self::Aarch64Manylinux => "manylinux2014",
+            Self::X86_64Musllinux | Self::Aarch64Musllinux => "musllinux_1_2",
+        }
+    }
+
+    fn is_musl(self) -> bool {
+        self.triple().ends_with("-musl")
+    }
+
+    /// Whether the native code of the wheel is compiled for another platform than the host one
+    fn is_cross(self) -> bool {
+        let host = format!(
+            "{}-unknown-{}-gnu",
+            std::env::consts::ARCH,
+            std::env::consts::OS
+        );
+        self.triple() != host
+    }
+
+    /// Variable holding the flags of rustc when compiling for the platform
+    fn rustflags_variable(self) -> String {
+        format!(
+            "CARGO_TARGET_{}_RUSTFLAGS",
+            self.triple().to_uppercase().replace('-', "_")
+        )
+    }
+}
+
+impl WheelArgs {
+    /// `maturin` invocation building the wheel of `platform`, from the workspace at `root`
+    fn maturin(&self, root: &Path, platform: Platform) -> Command {
+        let features = std::iter::once("python")
+            .chain(self.features.iter().map(String::as_str))
+            .collect::<Vec<_>>()
+            .join(",");
+
+        let mut command = Command::new("maturin");
+        command
+            .current_dir(root)
+            .args(["build", "--release", "--manifest-path", EXTENSION_MANIFEST])
+            .args(["--features", &features])
+            .args(["--target", platform.triple()])
+            .args(["--compatibility", platform.compatibility()])
+            .arg("--out")
+            .arg(&self.out);
+
+        // The C dependencies are compiled and linked by zig, along with musl
+        if self.zig || platform.is_cross() || platform.is_musl() {
+            command.arg("--zig");
+        }
+
+        // musl targets link statically by default, which prevents building a shared library
+        if platform.is_musl() {
+            command.env(
+                platform.rustflags_variable(),
+                "-C target-feature=-crt-static",
+            );
+        }
+        command
+    }
+}
+
+/// Whether the standard library of `triple` is installed, assumed so without rustup
+fn has_target(triple: &str) -> bool {
+    match Command::new("rustup")
+        .args(["target", "list", "--installed"])
+        .output()
+    {
+        Ok(output) if output.status.success() => String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .any(|installed| installed.trim() == triple),
+        _ => true,
+    }
+}
+
+fn describe(command: &Command) -> String {
+    let variables = command.get_envs().filter_map(|(name, value)| {
+        Some(format!(
+            "{}=\"{}\"",
+            name.to_string_lossy(),
+            value?.to_string_lossy()
+        ))
+    });
+    let program = std::iter::once(command.get_program().to_string_lossy().into_owned());
+    let args = command
+        .get_args()
+        .map(|arg| arg.to_string_lossy().into_owned());
+    variables
+        .chain(program)
+        .chain(args)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn wheel(args: &WheelArgs) -> Result<(), String> {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .parent()
+        .ok_or("Unable to locate the workspace")?;
+
+    for &platform in &args.target {
+        let mut command = args.maturin(root, platform);
+        eprintln!(
+            "Building the {} wheel: {}",
+            platform.triple(),
+            describe(&command)
+        );
+        if args.dry_run {
+            continue;
+        }
+
+        if !has_target(platform.triple()) {
+            return Err(format!(
+                "The Rust target {0} is not installed, run `rustup target add {0}`",
+                platform.triple()
+            ));
+        }
+        let status = command.status().map_err(|err| {
+            format!("Unable to run maturin ({err}), run `pip install maturin ziglang`")
+        })?;
+        if !status.success() {
+            return Err(format!("Building the {} wheel failed", platform.triple()));
+        }
+    }
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let built = match &cli.command {
+        BuildCommand::Wheel(args) => wheel(args),
+    };
+
+    match built {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("{err}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{BuildCommand, Cli, Platform};
+    use clap::{CommandFactory, Parser};
+    use std::path::Path;
+
+    #[test]
+    fn build_wheels_through_maturin() {
+        Cli::command().debug_assert();
+
+        let cli = Cli::try_parse_from([
+            "hfendpoints-build",
+            "wheel",
+            "--target=aarch64-musllinux",
+            "--features=audio-decode,gpu",
+        ])
+        .unwrap();
+        let BuildCommand::Wheel(args) = cli.command;
+        assert_eq!(args.target, [Platform::Aarch64Musllinux]);
+
+        let command = args.maturin(Path::new("/workspace"), Platform::Aarch64Musllinux);
+        let arguments: Vec<_> = command
+            .get_args()
+            .map(|arg| arg.to_str().unwrap())
+            .collect();
+        assert_eq!(
+            arguments,
+            [
+                "build",
+                "--release",
+                "--manifest-path",
+                "hfendpoints/Cargo.toml",
+                "--features",
+                "python,audio-decode,gpu",
+                "--target",
+                "aarch64-unknown-linux-musl",
+                "--compatibility",
+                "musllinux_1_2",
+                "--out",
+                "target/wheels",
+                "--zig",
+            ]
+        );
+        let variables: Vec<_> = command.get_envs().collect();
+        assert_eq!(
+            variables,
+            [(
+                "CARGO_TARGET_AARCH64_UNKNOWN_LINUX_MUSL_RUSTFLAGS".as_ref(),
+                Some("-C target-feature=-crt-static".as_ref())
+            )]
+        );
+
+        let cli = Cli::try_parse_from(["hfendpoints-build", "wheel", "--target=x86_64-manylinux"]);
+        let BuildCommand::Wheel(args) = cli.unwrap().command;
+        assert_eq!(args.target, [Platform::X86_64Manylinux]);
+
+        assert!(Cli::try_parse_from(["hfendpoints-build", "wheel"]).is_err());
+        assert!(Cli::try_parse_from(["hfendpoints-build", "wheel", "--target=riscv64"]).is_err());
+    }
+}
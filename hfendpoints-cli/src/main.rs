@@ -0,0 +1,304 @@
+//! `hfendpoint`, serving a Python handler without writing a launcher script.
+//!
+//! ```dockerfile
+//! CMD ["hfendpoint", "serve", "--task=transcription", "--handler=my_pkg.Handler"]
+//! ```
+//!
+//! The flags are exported as the environment variables the runtime reads (`PORT`,
+//! `HFENDPOINT_CONFIG`, ...), taking precedence over the ones already set, before the interpreter
+//! starts. The endpoint is then served as `hfendpoints.openai.serve` does: handlers failing to be
+//! imported or instantiated leave the server running in degraded mode, reporting the failure on
+//! `/health` and `/info` rather than exiting.
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use hfendpoints_core::{QUEUE_CAPACITY_ENV, REQUEST_TIMEOUT_ENV, WORKERS_ENV};
+use hfendpoints_openai::{
+    CONFIG_FILE_ENV, EndpointConfig, INTERFACE_ENV, LOG_FORMAT_ENV, MODEL_ID_ENV, PORT_ENV,
+};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::{PyCFunction, PyDict, PyTuple};
+use std::num::NonZeroUsize;
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+/// Model loaded by the handler when neither `--model-id` nor `MODEL_ID` is set
+const DEFAULT_MODEL_ID: &str = "/repository";
+
+#[derive(Parser)]
+#[command(
+    name = "hfendpoint",
+    version,
+    about = "Serve a Python handler as an OpenAI compatible endpoint"
+)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Serve the handler until the process is asked to terminate
+    Serve(ServeArgs),
+}
+
+#[derive(Args)]
+struct ServeArgs {
+    /// Task served by the handler
+    #[arg(long, value_enum)]
+    task: Task,
+
+    /// Handler class, as `package.module.Class` or `package.module:Class`, instantiated with the model id
+    #[arg(long, value_parser = handler_path)]
+    handler: String,
+
+    /// Model loaded by the handler [env: MODEL_ID]
+    #[arg(long)]
+    model_id: Option<String>,
+
+    /// TOML or YAML file holding the settings of the endpoint [env: HFENDPOINT_CONFIG]
+    #[arg(long)]
+    config: Option<PathBuf>,
+
+    /// Interface the endpoint listens on [env: INTERFACE]
+    #[arg(long)]
+    interface: Option<String>,
+
+    /// Port the endpoint listens on [env: PORT]
+    #[arg(long)]
+    port: Option<u16>,
+
+    /// Number of workers, each processing one request at a time [env: HFENDPOINT_WORKERS]
+    #[arg(long)]
+    workers: Option<NonZeroUsize>,
+
+    /// Maximum number of requests queued or processed by the handler [env: HFENDPOINT_QUEUE_CAPACITY]
+    #[arg(long)]
+    queue_capacity: Option<NonZeroUsize>,
+
+    /// Seconds a request may take before failing with a timeout [env: HFENDPOINT_REQUEST_TIMEOUT]
+    #[arg(long, value_parser = positive_seconds)]
+    request_timeout: Option<f64>,
+
+    /// Format of the logs [env: HFENDPOINT_LOG_FORMAT]
+    #[arg(long, value_parser = ["text", "json"])]
+    log_format: Option<String>,
+}
+
+/// Tasks an endpoint can be launched for
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+enum Task {
+    Transcription,
+    Translation,
+    Speech,
+    Chat,
+    Completions,
+    Embeddings,
+    Images,
+    ImageEdits,
+    ImageVariations,
+    Moderations,
+    Rerank,
+}
+
+impl Task {
+    /// Python module and class of the endpoint serving the task
+    fn endpoint(self) -> (&'static str, &'static str) {
+        match self {
+            Self::Transcription => (
+                "hfendpoints.openai.audio",
+                "AutomaticSpeechRecognitionEndpoint",
+            ),
+            Self::Translation => ("hfendpoints.openai.audio", "AudioTranslationEndpoint"),
+            Self::Speech => ("hfendpoints.openai.audio", "TextToSpeechEndpoint"),
+            Self::Chat => ("hfendpoints.openai.chat", "ChatCompletionEndpoint"),
+            Self::Completions => ("hfendpoints.openai.completions", "CompletionEndpoint"),
+            Self::Embeddings => ("hfendpoints.openai.embeddings", "EmbeddingEndpoint"),
+            Self::Images => ("hfendpoints.openai.images", "ImageGenerationEndpoint"),
+            Self::ImageEdits => ("hfendpoints.openai.images", "ImageEditEndpoint"),
+            Self::ImageVariations => ("hfendpoints.openai.images", "ImageVariationEndpoint"),
+            Self::Moderations => ("hfendpoints.openai.moderations", "ModerationEndpoint"),
+            Self::Rerank => ("hfendpoints.openai.rerank", "RerankEndpoint"),
+        }
+    }
+}
+
+impl ServeArgs {
+    /// Environment variables defined by the flags which are set
+    fn variables(&self) -> Vec<(&'static str, String)> {
+        [
+            (MODEL_ID_ENV, self.model_id.clone()),
+            (
+                CONFIG_FILE_ENV,
+                self.config.as_ref().map(|path| path.display().to_string()),
+            ),
+            (INTERFACE_ENV, self.interface.clone()),
+            (PORT_ENV, self.port.map(|port| port.to_string())),
+            (WORKERS_ENV, self.workers.map(|workers| workers.to_string())),
+            (
+                QUEUE_CAPACITY_ENV,
+                self.queue_capacity.map(|capacity| capacity.to_string()),
+            ),
+            (
+                REQUEST_TIMEOUT_ENV,
+                self.request_timeout.map(|seconds| seconds.to_string()),
+            ),
+            (LOG_FORMAT_ENV, self.log_format.clone()),
+        ]
+        .into_iter()
+        .filter_map(|(name, value)| Some((name, value?)))
+        .collect()
+    }
+}
+
+/// Module and class of the handler referenced by `path`
+fn split_handler(path: &str) -> Option<(&str, &str)> {
+    let (module, class) = path.split_once(':').or_else(|| path.rsplit_once('.'))?;
+    (!module.is_empty() && !class.is_empty()).then_some((module, class))
+}
+
+fn handler_path(path: &str) -> Result<String, String> {
+    match split_handler(path) {
+        Some(_) => Ok(String::from(path)),
+        None => Err(String::from("expected package.module.Class")),
+    }
+}
+
+fn positive_seconds(seconds: &str) -> Result<f64, String> {
+    match seconds.parse::<f64>() {
+        Ok(seconds) if seconds > 0.0 && seconds.is_finite() => Ok(seconds),
+        _ => Err(String::from("expected a positive number of seconds")),
+    }
+}
+
+/// Import the handler class referenced by `path`, validated by [`handler_path`]
+fn import_handler<'py>(py: Python<'py>, path: &str) -> PyResult<Bound<'py, PyAny>> {
+    let (module, class) = split_handler(path)
+        .ok_or_else(|| PyValueError::new_err(format!("Malformed handler path '{path}'")))?;
+    py.import(module)?.getattr(class)
+}
+
+/// Serve the endpoint of `task` around the `handler` loading `model_id`, on the interface and
+/// port of `config`
+fn serve(
+    py: Python<'_>,
+    task: Task,
+    handler: String,
+    model_id: String,
+    config: EndpointConfig,
+) -> PyResult<()> {
+    // Handlers are imported from the working directory, as `python -m` does
+    let cwd = std::env::current_dir()?;
+    py.import("sys")?
+        .getattr("path")?
+        .call_method1("insert", (0, cwd))?;
+
+    let (module, class) = task.endpoint();
+    let endpoint = py.import(module)?.getattr(class)?.unbind();
+
+    // Importing and instantiating the handler may fail, `serve` reports it in degraded mode
+    let factory = PyCFunction::new_closure(
+        py,
+        None,
+        None,
+        move |args: &Bound<'_, PyTuple>, _: Option<&Bound<'_, PyDict>>| -> PyResult<PyObject> {
+            let py = args.py();
+            let handler = import_handler(py, &handler)?.call1((&model_id,))?;
+            endpoint.call1(py, (handler,))
+        },
+    )?;
+
+    py.import("hfendpoints.openai")?.getattr("serve")?.call1((
+        factory,
+        config.interface,
+        config.port,
+    ))?;
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    let Command::Serve(args) = Cli::parse().command;
+    for (name, value) in args.variables() {
+        // SAFETY: the process is still single-threaded, neither the interpreter nor the runtime started
+        unsafe { std::env::set_var(name, value) };
+    }
+
+    // The configuration file is checked before the interpreter starts, the runtime reading it again
+    let config = match EndpointConfig::from_env() {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("hfendpoint: {err}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let model_id = std::env::var(MODEL_ID_ENV).unwrap_or_else(|_| String::from(DEFAULT_MODEL_ID));
+
+    pyo3::prepare_freethreaded_python();
+    let served = Python::with_gil(|py| serve(py, args.task, args.handler, model_id, config));
+    match served {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            Python::with_gil(|py| err.print(py));
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Cli, Command, Task, split_handler};
+    use clap::{CommandFactory, Parser};
+
+    #[test]
+    fn parse_the_serve_command() {
+        Cli::command().debug_assert();
+
+        let cli = Cli::try_parse_from([
+            "hfendpoint",
+            "serve",
+            "--task=transcription",
+            "--handler=my_pkg.handlers.Whisper",
+            "--port=9000",
+            "--workers=2",
+        ])
+        .unwrap();
+        let Command::Serve(args) = cli.command;
+        assert_eq!(args.task, Task::Transcription);
+        assert_eq!(
+            args.variables(),
+            [
+                ("PORT", String::from("9000")),
+                ("HFENDPOINT_WORKERS", String::from("2")),
+            ]
+        );
+
+        for malformed in [
+            "--handler=Handler",
+            "--workers=0",
+            "--request-timeout=-1",
+            "--log-format=xml",
+        ] {
+            let parsed = Cli::try_parse_from([
+                "hfendpoint",
+                "serve",
+                "--task=chat",
+                "--handler=my_pkg.Handler",
+                malformed,
+            ]);
+            assert!(parsed.is_err(), "{malformed}");
+        }
+    }
+
+    #[test]
+    fn split_handler_paths() {
+        assert_eq!(
+            split_handler("my_pkg.handlers.Whisper"),
+            Some(("my_pkg.handlers", "Whisper"))
+        );
+        assert_eq!(
+            split_handler("my_pkg.handlers:Whisper"),
+            Some(("my_pkg.handlers", "Whisper"))
+        );
+        assert_eq!(split_handler("Whisper"), None);
+        assert_eq!(split_handler("my_pkg."), None);
+    }
+}
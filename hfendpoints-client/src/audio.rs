@@ -0,0 +1,177 @@
+use bytes::Bytes;
+use reqwest::Body;
+use reqwest::multipart::{Form, Part};
+
+pub use hfendpoints_schemas::audio::{
+    ResponseFormat, Segment, Transcription, VerboseTranscription,
+};
+
+/// The transcription object matching the requested `ResponseFormat`.
+#[derive(Clone, Debug)]
+pub enum TranscriptionResponse {
+    Json(Transcription),
+    Text(String),
+    VerboseJson(VerboseTranscription),
+}
+
+impl TranscriptionResponse {
+    /// The transcribed text, whatever the response format was.
+    pub fn text(&self) -> &str {
+        match self {
+            TranscriptionResponse::Json(transcription) => &transcription.text,
+            TranscriptionResponse::Text(text) => text,
+            TranscriptionResponse::VerboseJson(transcription) => &transcription.text,
+        }
+    }
+}
+
+/// Parameters of a transcription request, sent as `multipart/form-data`.
+#[derive(Clone, Debug)]
+pub struct TranscriptionRequest {
+    file: Bytes,
+    file_name: String,
+    content_type: Option<String>,
+    language: Option<String>,
+    prompt: Option<String>,
+    temperature: Option<f32>,
+    response_format: ResponseFormat,
+    allow_code_switching: bool,
+}
+
+impl TranscriptionRequest {
+    /// Create a new request to transcribe the audio `file` named `file_name`.
+    pub fn new(file: impl Into<Bytes>, file_name: impl Into<String>) -> Self {
+        Self {
+            file: file.into(),
+            file_name: file_name.into(),
+            content_type: None,
+            language: None,
+            prompt: None,
+            temperature: None,
+            response_format: ResponseFormat::default(),
+            allow_code_switching: false,
+        }
+    }
+
+    pub fn content_type(mut self, content_type: impl Into<String>) -> Self {
+        self.content_type = Some(content_type.into());
+        self
+    }
+
+    pub fn language(mut self, language: impl Into<String>) -> Self {
+        self.language = Some(language.into());
+        self
+    }
+
+    pub fn prompt(mut self, prompt: impl Into<String>) -> Self {
+        self.prompt = Some(prompt.into());
+        self
+    }
+
+    pub fn temperature(mut self, temperature: f32) -> Self {
+        self.temperature = Some(temperature);
+        self
+    }
+
+    pub fn response_format(mut self, response_format: ResponseFormat) -> Self {
+        self.response_format = response_format;
+        self
+    }
+
+    /// Let the endpoint report the language of every segment for audio mixing languages
+    pub fn allow_code_switching(mut self, allow_code_switching: bool) -> Self {
+        self.allow_code_switching = allow_code_switching;
+        self
+    }
+
+    pub(crate) fn format(&self) -> ResponseFormat {
+        self.response_format
+    }
+
+    /// Build the multipart form. The audio buffer is reference counted so the form
+    /// can be rebuilt cheaply when retrying.
+    pub(crate) fn to_form(&self) -> reqwest::Result<Form> {
+        let mut file =
+            Part::stream_with_length(Body::from(self.file.clone()), self.file.len() as u64)
+                .file_name(self.file_name.clone());
+
+        if let Some(content_type) = &self.content_type {
+            file = file.mime_str(content_type)?;
+        }
+
+        let mut form = Form::new()
+            .part("file", file)
+            .text("response_format", self.response_format.as_str());
+
+        if let Some(language) = &self.language {
+            form = form.text("language", language.clone());
+        }
+
+        if let Some(prompt) = &self.prompt {
+            form = form.text("prompt", prompt.clone());
+        }
+
+        if let Some(temperature) = self.temperature {
+            form = form.text("temperature", temperature.to_string());
+        }
+
+        if self.allow_code_switching {
+            form = form.text("allow_code_switching", "true");
+        }
+
+        Ok(form)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::audio::VerboseTranscription;
+
+    #[test]
+    fn deserialize_verbose_transcription_snake_case() {
+        let payload = r#"{
+            "task": "transcribe",
+            "language": "english",
+            "duration": 1.5,
+            "text": "Hello world",
+            "segments": [{
+                "id": 0, "seek": 0, "start": 0.0, "end": 1.5, "text": "Hello world",
+                "tokens": [1, 2], "temperature": 0.0, "avg_logprob": -0.2,
+                "compression_ratio": 1.1, "no_speech_prob": 0.01
+            }]
+        }"#;
+
+        let transcription: VerboseTranscription = serde_json::from_str(payload).unwrap();
+        assert_eq!(transcription.language, "english");
+        assert_eq!(transcription.segments.len(), 1);
+        assert_eq!(transcription.segments[0].avg_logprob, -0.2);
+        assert_eq!(transcription.segments[0].no_speech_prob, 0.01);
+    }
+
+    #[test]
+    fn deserialize_verbose_transcription_camel_case_aliases() {
+        let payload = r#"{
+            "language": "english",
+            "duration": 1.5,
+            "text": "Hello world",
+            "segments": [{
+                "id": 0, "start": 0.0, "end": 1.5, "text": "Hello world",
+                "avgLogprob": -0.2, "compressionRatio": 1.1, "noSpeechProb": 0.01
+            }]
+        }"#;
+
+        let transcription: VerboseTranscription = serde_json::from_str(payload).unwrap();
+        assert_eq!(transcription.segments[0].avg_logprob, -0.2);
+        assert_eq!(transcription.segments[0].compression_ratio, 1.1);
+        assert_eq!(transcription.segments[0].no_speech_prob, 0.01);
+    }
+
+    #[test]
+    fn deserialize_verbose_transcription_without_segments() {
+        let payload = r#"{"text": "Hello world", "duration": 1.5, "language": "english"}"#;
+
+        let transcription: VerboseTranscription = serde_json::from_str(payload).unwrap();
+        assert_eq!(transcription.text, "Hello world");
+        assert!(transcription.segments.is_empty());
+    }
+}
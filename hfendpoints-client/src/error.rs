@@ -0,0 +1,12 @@
+use reqwest::StatusCode;
+use thiserror::Error;
+
+/// Define all the possible errors raised while talking to an endpoint
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("HTTP transport error: {0}")]
+    Http(#[from] reqwest::Error),
+
+    #[error("Endpoint returned {status}: {message}")]
+    Status { status: StatusCode, message: String },
+}
@@ -0,0 +1,307 @@
+mod audio;
+mod error;
+
+pub use audio::{
+    ResponseFormat, Segment, Transcription, TranscriptionRequest, TranscriptionResponse,
+    VerboseTranscription,
+};
+pub use error::Error;
+
+use reqwest::{RequestBuilder, Response, StatusCode};
+use std::time::Duration;
+use tokio::time::sleep;
+use tracing::{instrument, warn};
+
+pub type ClientResult<T> = Result<T, Error>;
+
+/// Prefix under which the task routes are nested by the endpoint
+const API_PREFIX: &str = "/api/v1";
+
+/// Helper to build a `Client` with non-default settings
+pub struct ClientBuilder {
+    base_url: String,
+    http: Option<reqwest::Client>,
+    max_retries: u32,
+    backoff: Duration,
+}
+
+impl ClientBuilder {
+    /// Use a preconfigured `reqwest::Client` (proxies, timeouts, default headers, ...)
+    pub fn http_client(mut self, http: reqwest::Client) -> Self {
+        self.http = Some(http);
+        self
+    }
+
+    /// Maximum number of times a request is retried after a transient failure
+    pub fn max_retries(mut self, max_retries: u32) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    /// Delay before the first retry, doubled on every subsequent attempt
+    pub fn backoff(mut self, backoff: Duration) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
+    pub fn build(self) -> Client {
+        Client {
+            base_url: self.base_url.trim_end_matches('/').to_string(),
+            http: self.http.unwrap_or_default(),
+            max_retries: self.max_retries,
+            backoff: self.backoff,
+        }
+    }
+}
+
+/// Async client for the routes served by an endpoint built with `hfendpoints-openai`
+#[derive(Clone)]
+pub struct Client {
+    base_url: String,
+    http: reqwest::Client,
+    max_retries: u32,
+    backoff: Duration,
+}
+
+impl Client {
+    /// Create a client targeting the endpoint at `base_url` (i.e. `http://localhost:8000`)
+    pub fn new(base_url: impl Into<String>) -> Self {
+        Self::builder(base_url).build()
+    }
+
+    pub fn builder(base_url: impl Into<String>) -> ClientBuilder {
+        ClientBuilder {
+            base_url: base_url.into(),
+            http: None,
+            max_retries: 3,
+            backoff: Duration::from_millis(250),
+        }
+    }
+
+    /// Check whether the endpoint reports itself as healthy
+    #[instrument(skip(self))]
+    pub async fn health(&self) -> ClientResult<bool> {
+        let url = format!("{}/health", self.base_url);
+        let response = self.send(|| Ok(self.http.get(&url))).await?;
+        Ok(response.status().is_success())
+    }
+
+    /// Transcribe audio through `POST /api/v1/audio/transcriptions`
+    #[instrument(skip_all)]
+    pub async fn transcribe(
+        &self,
+        request: &TranscriptionRequest,
+    ) -> ClientResult<TranscriptionResponse> {
+        let url = format!("{}{API_PREFIX}/audio/transcriptions", self.base_url);
+
+        let response = self
+            .send(|| Ok(self.http.post(&url).multipart(request.to_form()?)))
+            .await?;
+
+        Ok(match request.format() {
+            ResponseFormat::Json => TranscriptionResponse::Json(response.json().await?),
+            ResponseFormat::Text | ResponseFormat::Srt | ResponseFormat::Vtt => {
+                TranscriptionResponse::Text(response.text().await?)
+            }
+            ResponseFormat::VerboseJson => {
+                TranscriptionResponse::VerboseJson(response.json().await?)
+            }
+        })
+    }
+
+    /// Send the request built by `request`, retrying with exponential backoff on transient failures
+    async fn send<F>(&self, request: F) -> ClientResult<Response>
+    where
+        F: Fn() -> ClientResult<RequestBuilder>,
+    {
+        let mut attempt = 0;
+        loop {
+            let outcome = request()?.send().await;
+            let retryable = match &outcome {
+                Ok(response) => is_retryable_status(response.status()),
+                Err(err) => err.is_connect() || err.is_timeout(),
+            };
+
+            if retryable && attempt < self.max_retries {
+                let delay = backoff_delay(self.backoff, attempt);
+                warn!(
+                    "Request failed (attempt {}), retrying in {delay:?}",
+                    attempt + 1
+                );
+                sleep(delay).await;
+                attempt += 1;
+                continue;
+            }
+
+            let response = outcome?;
+            let status = response.status();
+            return if status.is_success() {
+                Ok(response)
+            } else {
+                let message = response.text().await.unwrap_or_default();
+                Err(Error::Status { status, message })
+            };
+        }
+    }
+}
+
+#[inline]
+fn is_retryable_status(status: StatusCode) -> bool {
+    matches!(
+        status,
+        StatusCode::TOO_MANY_REQUESTS
+            | StatusCode::BAD_GATEWAY
+            | StatusCode::SERVICE_UNAVAILABLE
+            | StatusCode::GATEWAY_TIMEOUT
+    )
+}
+
+#[inline]
+fn backoff_delay(base: Duration, attempt: u32) -> Duration {
+    base.saturating_mul(2u32.saturating_pow(attempt))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Client, backoff_delay, is_retryable_status};
+    use reqwest::StatusCode;
+    use std::time::Duration;
+
+    #[test]
+    fn backoff_doubles_every_attempt() {
+        let base = Duration::from_millis(100);
+        assert_eq!(backoff_delay(base, 0), Duration::from_millis(100));
+        assert_eq!(backoff_delay(base, 1), Duration::from_millis(200));
+        assert_eq!(backoff_delay(base, 3), Duration::from_millis(800));
+    }
+
+    #[test]
+    fn only_transient_statuses_are_retried() {
+        assert!(is_retryable_status(StatusCode::TOO_MANY_REQUESTS));
+        assert!(is_retryable_status(StatusCode::SERVICE_UNAVAILABLE));
+        assert!(!is_retryable_status(StatusCode::BAD_REQUEST));
+        assert!(!is_retryable_status(StatusCode::INTERNAL_SERVER_ERROR));
+    }
+
+    #[test]
+    fn base_url_trailing_slash_is_trimmed() {
+        let client = Client::new("http://localhost:8000/");
+        assert_eq!(client.base_url, "http://localhost:8000");
+    }
+}
+
+#[cfg(feature = "python")]
+pub mod python {
+    use crate::{Client, Error, ResponseFormat, TranscriptionRequest, TranscriptionResponse};
+    use hfendpoints_binding_python::ImportablePyModuleBuilder;
+    use pyo3::exceptions::{PyConnectionError, PyRuntimeError, PyValueError};
+    use pyo3::prelude::*;
+    use std::time::Duration;
+
+    impl From<Error> for PyErr {
+        fn from(err: Error) -> Self {
+            match err {
+                Error::Http(err) => PyConnectionError::new_err(err.to_string()),
+                Error::Status { .. } => PyRuntimeError::new_err(err.to_string()),
+            }
+        }
+    }
+
+    /// Async client exposed to Python, requests are executed on the shared tokio runtime
+    #[pyclass(name = "Client", frozen)]
+    pub struct PyClient(Client);
+
+    #[pymethods]
+    impl PyClient {
+        #[new]
+        #[pyo3(signature = (base_url, max_retries = 3, backoff = 0.25))]
+        fn new(base_url: String, max_retries: u32, backoff: f64) -> PyResult<Self> {
+            let backoff = Duration::try_from_secs_f64(backoff)
+                .map_err(|err| PyValueError::new_err(err.to_string()))?;
+
+            Ok(Self(
+                Client::builder(base_url)
+                    .max_retries(max_retries)
+                    .backoff(backoff)
+                    .build(),
+            ))
+        }
+
+        /// Check whether the endpoint reports itself as healthy
+        fn health<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+            let client = self.0.clone();
+            pyo3_async_runtimes::tokio::future_into_py(
+                py,
+                async move { Ok(client.health().await?) },
+            )
+        }
+
+        /// Transcribe audio, returning a `str`, `Transcription` or `VerboseTranscription`
+        /// according to `response_format`
+        #[allow(clippy::too_many_arguments)]
+        #[pyo3(signature = (file, file_name, language = None, prompt = None, temperature = None, response_format = "json", allow_code_switching = false))]
+        fn transcribe<'py>(
+            &self,
+            py: Python<'py>,
+            file: Vec<u8>,
+            file_name: String,
+            language: Option<String>,
+            prompt: Option<String>,
+            temperature: Option<f32>,
+            response_format: &str,
+            allow_code_switching: bool,
+        ) -> PyResult<Bound<'py, PyAny>> {
+            let response_format = match response_format {
+                "json" => ResponseFormat::Json,
+                "text" => ResponseFormat::Text,
+                "verbose_json" => ResponseFormat::VerboseJson,
+                "srt" => ResponseFormat::Srt,
+                "vtt" => ResponseFormat::Vtt,
+                _ => {
+                    return Err(PyValueError::new_err(format!(
+                        "Unknown response_format: {response_format}. Possible values are: 'json', 'verbose_json', 'text', 'srt', 'vtt'."
+                    )));
+                }
+            };
+
+            let mut request = TranscriptionRequest::new(file, file_name)
+                .response_format(response_format)
+                .allow_code_switching(allow_code_switching);
+            if let Some(language) = language {
+                request = request.language(language);
+            }
+            if let Some(prompt) = prompt {
+                request = request.prompt(prompt);
+            }
+            if let Some(temperature) = temperature {
+                request = request.temperature(temperature);
+            }
+
+            let client = self.0.clone();
+            pyo3_async_runtimes::tokio::future_into_py(py, async move {
+                let response = client.transcribe(&request).await?;
+                Python::with_gil(|py| match response {
+                    TranscriptionResponse::Text(text) => {
+                        Ok(text.into_pyobject(py)?.into_any().unbind())
+                    }
+                    TranscriptionResponse::Json(transcription) => {
+                        Ok(Py::new(py, transcription)?.into_any())
+                    }
+                    TranscriptionResponse::VerboseJson(transcription) => {
+                        Ok(Py::new(py, transcription)?.into_any())
+                    }
+                })
+            })
+        }
+    }
+
+    /// Bind hfendpoints.client submodule into the exported Python wheel
+    pub fn bind<'py>(py: Python<'py>, name: &str) -> PyResult<Bound<'py, PyModule>> {
+        let module = ImportablePyModuleBuilder::new(py, name)?
+            .defaults()?
+            .add_class::<PyClient>()?
+            .finish();
+
+        Ok(module)
+    }
+}
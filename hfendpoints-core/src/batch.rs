@@ -0,0 +1,161 @@
+//! Batching of the requests processed by a handler.
+//!
+//! Handlers whose every invocation carries a fixed cost (i.e. acquiring the GIL and converting
+//! the request to Python objects, or launching a kernel on an accelerator) amortize it by
+//! processing several requests at once. A [`Batcher`] queues the submitted requests and hands
+//! them over in batches of at most `max_size` requests: one batch is processed at a time, the
+//! requests submitted meanwhile making up the next one. No delay is spent waiting for a batch to
+//! fill up, an idle batcher processing a lone request right away.
+use crate::Error;
+use tokio::sync::mpsc::{UnboundedSender, unbounded_channel};
+use tokio::sync::oneshot;
+use tracing::{debug, warn};
+
+/// Environment variable holding the maximum number of requests processed in a single batch
+pub const MAX_BATCH_SIZE_ENV: &str = "HFENDPOINT_MAX_BATCH_SIZE";
+
+/// Maximum number of requests processed in a single batch when `HFENDPOINT_MAX_BATCH_SIZE` is not set
+pub const DEFAULT_MAX_BATCH_SIZE: usize = 32;
+
+/// Maximum batch size defined through `HFENDPOINT_MAX_BATCH_SIZE`, `DEFAULT_MAX_BATCH_SIZE` otherwise
+pub fn max_batch_size_from_env() -> usize {
+    let Ok(size) = std::env::var(MAX_BATCH_SIZE_ENV) else {
+        return DEFAULT_MAX_BATCH_SIZE;
+    };
+    match size.parse::<usize>() {
+        Ok(size) if size > 0 => size,
+        _ => {
+            warn!(
+                "Ignoring malformed {MAX_BATCH_SIZE_ENV} ({size}), expected a positive integer, defaulting to {DEFAULT_MAX_BATCH_SIZE}"
+            );
+            DEFAULT_MAX_BATCH_SIZE
+        }
+    }
+}
+
+/// Request awaiting its batch, along with the slot its outcome is sent back through
+type Pending<I, O> = (I, oneshot::Sender<Result<O, Error>>);
+
+/// Queue handing the submitted requests over in batches to the function processing them
+pub struct Batcher<I, O> {
+    sender: UnboundedSender<Pending<I, O>>,
+}
+
+impl<I, O> Batcher<I, O>
+where
+    I: Send + 'static,
+    O: Send + 'static,
+{
+    /// Spawn the task feeding `process` with batches of at most `max_size` requests, on the
+    /// current runtime. `process` returns the outcome of every request of the batch, in order,
+    /// the requests it returns no outcome for failing with `Error::NoResponse`. The task completes
+    /// once the batcher is dropped.
+    pub fn spawn<F, Fut>(max_size: usize, process: F) -> Self
+    where
+        F: Fn(Vec<I>) -> Fut + Send + 'static,
+        Fut: Future<Output = Vec<Result<O, Error>>> + Send,
+    {
+        let max_size = max_size.max(1);
+        let (sender, mut receiver) = unbounded_channel::<Pending<I, O>>();
+        tokio::spawn(async move {
+            let mut batch = Vec::with_capacity(max_size);
+            while receiver.recv_many(&mut batch, max_size).await > 0 {
+                // Requests whose client went away are not processed
+                let (requests, slots): (Vec<_>, Vec<_>) = batch
+                    .drain(..)
+                    .filter(|(_, slot)| !slot.is_closed())
+                    .unzip();
+                if requests.is_empty() {
+                    continue;
+                }
+
+                debug!("Processing a batch of {} request(s)", requests.len());
+                let expected = slots.len();
+                let mut outcomes = process(requests).await.into_iter();
+                for slot in slots {
+                    let _ = slot.send(outcomes.next().unwrap_or(Err(Error::NoResponse)));
+                }
+
+                if outcomes.len() > 0 {
+                    warn!(
+                        "Batch of {expected} request(s) produced {} extra outcome(s), discarded",
+                        outcomes.len()
+                    );
+                }
+            }
+            debug!("Batcher dropped, stop processing batches");
+        });
+
+        Self { sender }
+    }
+
+    /// Queue `request` for the next batch, resolving to its outcome once the batch is processed
+    pub async fn submit(&self, request: I) -> Result<O, Error> {
+        let (slot, outcome) = oneshot::channel();
+        self.sender
+            .send((request, slot))
+            .map_err(|_| Error::HandlerTerminated)?;
+        outcome.await.unwrap_or(Err(Error::HandlerTerminated))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Error;
+    use crate::batch::Batcher;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+    use tokio::time::sleep;
+
+    #[tokio::test]
+    async fn requests_submitted_meanwhile_make_up_the_next_batch() {
+        let sizes = Arc::new(Mutex::new(Vec::new()));
+        let batcher = Arc::new(Batcher::spawn(3, {
+            let sizes = Arc::clone(&sizes);
+            move |requests: Vec<u8>| {
+                sizes.lock().unwrap().push(requests.len());
+                async move {
+                    sleep(Duration::from_millis(20)).await;
+                    requests
+                        .into_iter()
+                        .map(|request| match request {
+                            0 => Err(Error::NoResponse),
+                            request => Ok(request * 2),
+                        })
+                        .collect()
+                }
+            }
+        }));
+
+        // The first request is processed alone, the next ones queued while it is
+        let first = tokio::spawn({
+            let batcher = Arc::clone(&batcher);
+            async move { batcher.submit(1).await }
+        });
+        sleep(Duration::from_millis(5)).await;
+
+        let others = (2..=5)
+            .map(|request| {
+                let batcher = Arc::clone(&batcher);
+                tokio::spawn(async move { batcher.submit(request).await })
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(first.await.unwrap().unwrap(), 2);
+        for (request, outcome) in (2..=5).zip(others) {
+            assert_eq!(outcome.await.unwrap().unwrap(), request * 2);
+        }
+        assert!(matches!(batcher.submit(0).await, Err(Error::NoResponse)));
+        assert_eq!(*sizes.lock().unwrap(), [1, 3, 1, 1]);
+    }
+
+    #[tokio::test]
+    async fn missing_outcomes_fail_their_request() {
+        let batcher = Batcher::spawn(8, |requests: Vec<u8>| async move {
+            requests.into_iter().skip(1).map(Ok).collect()
+        });
+
+        // The outcome of the lone request is missing
+        assert!(matches!(batcher.submit(1).await, Err(Error::NoResponse)));
+    }
+}
@@ -1,26 +1,311 @@
+use crate::preemption::{Preemption, Timeslice};
 use crate::Error;
-use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{ready, Context, Poll};
+use std::time::Duration;
+use tokio::sync::mpsc::error::TrySendError;
+use tokio::sync::mpsc::{channel, Receiver, Sender};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tokio::time::{sleep_until, timeout, timeout_at, Instant, Sleep};
+use tokio_stream::Stream;
+use tokio_util::sync::{CancellationToken, DropGuard};
+use tracing::{debug, error, warn};
+
+/// Environment variable holding the maximum number of requests queued or processed by the handler
+pub const QUEUE_CAPACITY_ENV: &str = "HFENDPOINT_QUEUE_CAPACITY";
+
+/// Number of requests queued or processed by the handler when `HFENDPOINT_QUEUE_CAPACITY` is not set
+pub const DEFAULT_QUEUE_CAPACITY: usize = 128;
+
+/// Environment variable holding the number of seconds a request may take before failing with a timeout
+pub const REQUEST_TIMEOUT_ENV: &str = "HFENDPOINT_REQUEST_TIMEOUT";
+
+/// Number of responses buffered per request, handlers streaming faster than the client reads
+/// them wait for it to catch up
+pub const RESPONSE_CHANNEL_CAPACITY: usize = 32;
+
+/// Channel used to send back the outcome of a request to the transport
+pub type ResponseSender<O> = Sender<Result<O, Error>>;
+
+/// Channel used by the transport to schedule requests on the handler
+pub type RequestSender<I, O> = Sender<(I, ResponseSender<O>)>;
+
+/// Channel the handler receives the scheduled requests from
+pub type RequestReceiver<I, O> = Receiver<(I, ResponseSender<O>)>;
+
+/// Create the bounded queue between the transport and the handler, holding up to `capacity` requests
+pub fn request_channel<I, O>(capacity: usize) -> (RequestSender<I, O>, RequestReceiver<I, O>) {
+    channel(capacity.max(1))
+}
+
+/// Capacity of the request queue defined through `HFENDPOINT_QUEUE_CAPACITY`
+pub fn queue_capacity_from_env() -> usize {
+    match std::env::var(QUEUE_CAPACITY_ENV) {
+        Ok(capacity) => match capacity.parse::<usize>() {
+            Ok(capacity) if capacity > 0 => capacity,
+            _ => {
+                warn!("Ignoring malformed {QUEUE_CAPACITY_ENV} ({capacity}), expected a positive integer");
+                DEFAULT_QUEUE_CAPACITY
+            }
+        },
+        Err(_) => DEFAULT_QUEUE_CAPACITY,
+    }
+}
+
+/// Timeout defined through `HFENDPOINT_REQUEST_TIMEOUT`, requests never time out when not set
+pub fn request_timeout_from_env() -> Option<Duration> {
+    let timeout = std::env::var(REQUEST_TIMEOUT_ENV).ok()?;
+    match timeout.parse::<f64>().map(Duration::try_from_secs_f64) {
+        Ok(Ok(timeout)) if !timeout.is_zero() => Some(timeout),
+        _ => {
+            warn!("Ignoring malformed {REQUEST_TIMEOUT_ENV} ({timeout}), expected a positive number of seconds");
+            None
+        }
+    }
+}
+
+/// Handle over a request scheduled on the handler, used to retrieve its outcome.
+///
+/// Dropping the handle (or calling [`ScheduledRequest::cancel`]) signals the handler
+/// the request is not awaited anymore. The same happens once the request timed out.
+#[must_use = "requests are cancelled when their ScheduledRequest is dropped"]
+pub struct ScheduledRequest<O> {
+    /// Token cancelled if the request is dropped before completing, declared first so the
+    /// token is cancelled before the handler observes the closed `egress`
+    cancellation: Option<DropGuard>,
+
+    egress: Receiver<Result<O, Error>>,
+
+    /// Slot held in the queue until the outcome is retrieved or the request is cancelled
+    _slot: Option<QueueSlot>,
+
+    /// Time allowed for the request to complete, counted from the moment it was scheduled
+    timeout: Option<(Instant, Duration)>,
+}
+
+impl<O> ScheduledRequest<O> {
+    /// Cancel `token` when the outcome of the request stops being awaited before completion,
+    /// i.e. the client went away or the request timed out
+    pub fn with_cancellation(mut self, token: CancellationToken) -> Self {
+        self.cancellation = Some(token.drop_guard());
+        self
+    }
+
+    /// Wait for the response of the handler, for handlers producing a single response
+    pub async fn response(mut self) -> Result<O, Error> {
+        let response = match self.timeout {
+            Some((deadline, after)) => match timeout_at(deadline, self.egress.recv()).await {
+                Ok(response) => response,
+                Err(_) => {
+                    warn!("Request did not complete within {after:?}, cancelling");
+                    return Err(Error::Timeout { after });
+                }
+            },
+            None => self.egress.recv().await,
+        };
+
+        if let Some(cancellation) = self.cancellation.take() {
+            cancellation.disarm();
+        }
+        response.unwrap_or(Err(Error::NoResponse))
+    }
+
+    /// Every response produced by the handler, the stream ends when the handler is done
+    pub fn stream(self) -> impl Stream<Item = Result<O, Error>> + Send + Unpin
+    where
+        O: Send,
+    {
+        ResponseStream {
+            egress: self.egress,
+            _slot: self._slot,
+            timeout: self
+                .timeout
+                .map(|(deadline, after)| (Box::pin(sleep_until(deadline)), after)),
+            cancellation: self.cancellation,
+        }
+    }
+
+    /// Notify the handler the outcome of this request is not awaited anymore
+    pub fn cancel(mut self) {
+        debug!("Cancelling scheduled request");
+        self.egress.close();
+    }
+}
+
+/// Slot held in the queue by a scheduled request, its release accounting for the service time
+/// of the endpoint, see `LatencyStatistics::service_time`
+struct QueueSlot {
+    _permit: OwnedSemaphorePermit,
+    scheduled: Instant,
+}
+
+impl Drop for QueueSlot {
+    fn drop(&mut self) {
+        crate::latency_statistics().record_completion(self.scheduled.elapsed());
+    }
+}
+
+/// Responses of a scheduled request, keeping its slot in the queue until dropped
+struct ResponseStream<O> {
+    cancellation: Option<DropGuard>,
+    egress: Receiver<Result<O, Error>>,
+    _slot: Option<QueueSlot>,
+    timeout: Option<(Pin<Box<Sleep>>, Duration)>,
+}
+
+impl<O> Stream for ResponseStream<O> {
+    type Item = Result<O, Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        if let Some((deadline, after)) = self.timeout.as_mut()
+            && deadline.as_mut().poll(cx).is_ready()
+        {
+            let after = *after;
+            warn!("Streamed request did not complete within {after:?}, cancelling");
+
+            // Report the timeout once, then end the stream
+            self.timeout = None;
+            self.cancellation.take();
+            self.egress.close();
+            return Poll::Ready(Some(Err(Error::Timeout { after })));
+        }
+
+        let response = ready!(self.egress.poll_recv(cx));
+        if response.is_none()
+            && let Some(cancellation) = self.cancellation.take()
+        {
+            cancellation.disarm();
+        }
+        Poll::Ready(response)
+    }
+}
 
 /// Store some information about the context in which the endpoint runs
 #[derive(Clone)]
 pub struct EndpointContext<I, O> {
     // /// Realtime information streaming about underlying resources usage of the handler
     // in_flight_tracker: Receiver<InFlightStats>,
-    ipc: UnboundedSender<(I, UnboundedSender<Result<O, Error>>)>,
+    ipc: RequestSender<I, O>,
+
+    /// Requests either waiting in the queue or processed by the handler, bounded by the queue capacity
+    slots: Arc<Semaphore>,
+
+    /// Time allowed for a request to complete, from the moment it is scheduled
+    timeout: Option<Duration>,
+
+    /// Turns to run requests, when long-running requests may be preempted
+    preemption: Option<Arc<Preemption>>,
 }
 
 impl<I, O> EndpointContext<I, O> {
-    pub fn new(ipc: UnboundedSender<(I, UnboundedSender<Result<O, Error>>)>) -> Self {
-        Self { ipc }
+    /// Schedule requests through `ipc`, accepting as many concurrent requests as the channel capacity
+    pub fn new(ipc: RequestSender<I, O>) -> Self {
+        let slots = Arc::new(Semaphore::new(ipc.max_capacity()));
+        Self {
+            ipc,
+            slots,
+            timeout: None,
+            preemption: None,
+        }
+    }
+
+    /// Fail requests with `Error::Timeout` when not completed within `timeout`,
+    /// time spent waiting in the queue included
+    pub fn with_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Let requests run in turns granted by `preemption`, see [`EndpointContext::timeslice`]
+    pub fn with_preemption(mut self, preemption: Option<Arc<Preemption>>) -> Self {
+        self.preemption = preemption;
+        self
+    }
+
+    /// Maximum number of requests waiting in the queue or processed by the handler
+    pub fn capacity(&self) -> usize {
+        self.ipc.max_capacity()
+    }
+
+    /// Wait for the turn of the next request to run, to be handed to the handler along with the request.
+    ///
+    /// Returns immediately when preemption is disabled. Requests are rejected with `Error::QueueFull`
+    /// while `capacity` requests are already waiting for a turn, and fail with `Error::Timeout`
+    /// if no turn was granted within the timeout.
+    pub async fn timeslice(&self) -> Result<Timeslice, Error> {
+        let Some(preemption) = &self.preemption else {
+            return Ok(Timeslice::default());
+        };
+
+        if preemption.waiting() >= self.capacity() {
+            warn!("Rejecting request: too many requests are waiting for their turn");
+            return Err(Error::QueueFull {
+                capacity: self.capacity(),
+            });
+        }
+
+        let turn = Timeslice::acquire(Arc::clone(preemption));
+        match self.timeout {
+            Some(after) => timeout(after, turn).await.map_err(|_| {
+                warn!("Request was not granted a turn within {after:?}");
+                Error::Timeout { after }
+            }),
+            None => Ok(turn.await),
+        }
     }
 
-    pub fn schedule(&self, request: I) -> UnboundedReceiver<Result<O, Error>> {
-        let (sender, receiver) = unbounded_channel();
-        if let Err(e) = self.ipc.send((request, sender)) {
-            panic!("Failed to send to IPC");
+    /// Send the `request` to the handler, the outcome (or the failure to schedule it)
+    /// is retrieved through the returned `ScheduledRequest`.
+    ///
+    /// Requests are rejected with `Error::QueueFull` while `capacity` requests are still awaiting
+    /// their outcome, instead of buffering them in memory.
+    /// The timeout, if any, starts as soon as the request is scheduled.
+    pub fn schedule(&self, request: I) -> ScheduledRequest<O> {
+        let (sender, egress) = channel(RESPONSE_CHANNEL_CAPACITY);
+        let Ok(slot) = Arc::clone(&self.slots).try_acquire_owned() else {
+            warn!("Rejecting request: the queue is full");
+            let _ = sender.try_send(Err(Error::QueueFull {
+                capacity: self.capacity(),
+            }));
+            return ScheduledRequest {
+                egress,
+                _slot: None,
+                timeout: None,
+                cancellation: None,
+            };
+        };
+
+        match self.ipc.try_send((request, sender)) {
+            Ok(()) => {
+                crate::readiness().on_scheduled();
+                #[cfg(feature = "metrics")]
+                crate::endpoint_metrics().on_scheduled();
+            }
+            Err(TrySendError::Full((_, sender))) => {
+                // Slots are released after the looper dequeued the request, this should not happen
+                warn!("Rejecting request: the queue is full");
+                let _ = sender.try_send(Err(Error::QueueFull {
+                    capacity: self.capacity(),
+                }));
+            }
+            Err(TrySendError::Closed((_, sender))) => {
+                error!("Failed to schedule request: the handler loop is not running anymore");
+                let _ = sender.try_send(Err(Error::HandlerTerminated));
+            }
         }
 
-        receiver
+        ScheduledRequest {
+            egress,
+            _slot: Some(QueueSlot {
+                _permit: slot,
+                scheduled: Instant::now(),
+            }),
+            timeout: self
+                .timeout
+                .map(|timeout| (Instant::now() + timeout, timeout)),
+            cancellation: None,
+        }
     }
 
     // ///
@@ -53,3 +338,135 @@ impl<I, O> EndpointContext<I, O> {
     // /// ```
     // pub fn push_in_flight_stats(&self, stats: InFlightStats) {}
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        request_channel, CancellationToken, EndpointContext, Error, RESPONSE_CHANNEL_CAPACITY,
+    };
+    use std::time::Duration;
+    use tokio::sync::mpsc::error::TrySendError;
+    use tokio_stream::StreamExt;
+
+    #[tokio::test]
+    async fn schedule_reports_terminated_handler() {
+        let (sender, receiver) = request_channel::<u8, u8>(1);
+        let context = EndpointContext::<u8, u8>::new(sender);
+        drop(receiver);
+
+        let response = context.schedule(1).response().await;
+        assert!(matches!(response, Err(Error::HandlerTerminated)));
+    }
+
+    #[tokio::test]
+    async fn scheduled_request_outcomes() {
+        let (sender, mut receiver) = request_channel::<u8, u8>(1);
+        let context = EndpointContext::<u8, u8>::new(sender);
+
+        // Single response
+        let scheduled = context.schedule(1);
+        let (request, egress) = receiver.recv().await.unwrap();
+        egress.send(Ok(request + 1)).await.unwrap();
+        assert_eq!(scheduled.response().await.unwrap(), 2);
+
+        // Handler dropping the request without answering
+        let scheduled = context.schedule(1);
+        drop(receiver.recv().await.unwrap());
+        assert!(matches!(scheduled.response().await, Err(Error::NoResponse)));
+
+        // Streamed responses
+        let scheduled = context.schedule(3);
+        let (request, egress) = receiver.recv().await.unwrap();
+        for value in 0..request {
+            egress.send(Ok(value)).await.unwrap();
+        }
+        drop(egress);
+
+        let values: Vec<_> = scheduled.stream().map(Result::unwrap).collect().await;
+        assert_eq!(values, vec![0, 1, 2]);
+
+        // Cancellation is observed by the handler
+        let scheduled = context.schedule(1);
+        let (_, egress) = receiver.recv().await.unwrap();
+        scheduled.cancel();
+        assert!(egress.is_closed());
+    }
+
+    #[tokio::test]
+    async fn streams_wait_for_the_client() {
+        let (sender, mut receiver) = request_channel::<u8, usize>(1);
+        let context = EndpointContext::<u8, usize>::new(sender);
+
+        // The handler streams no further than the responses buffered for the client
+        let mut stream = context.schedule(1).stream();
+        let (_, egress) = receiver.recv().await.unwrap();
+        for value in 0..RESPONSE_CHANNEL_CAPACITY {
+            egress.send(Ok(value)).await.unwrap();
+        }
+        assert!(matches!(
+            egress.try_send(Ok(RESPONSE_CHANNEL_CAPACITY)),
+            Err(TrySendError::Full(_))
+        ));
+
+        assert_eq!(stream.next().await.unwrap().unwrap(), 0);
+        egress.send(Ok(RESPONSE_CHANNEL_CAPACITY)).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn schedule_rejects_requests_over_capacity() {
+        let (sender, mut receiver) = request_channel::<u8, u8>(2);
+        let context = EndpointContext::<u8, u8>::new(sender);
+
+        // Slots are held until the outcome is retrieved, even once dequeued by the handler
+        let first = context.schedule(1);
+        let (_, egress) = receiver.recv().await.unwrap();
+        let _second = context.schedule(2);
+
+        let rejected = context.schedule(3).response().await;
+        assert!(matches!(rejected, Err(Error::QueueFull { capacity: 2 })));
+
+        // Retrieving an outcome frees its slot
+        egress.send(Ok(1)).await.unwrap();
+        assert_eq!(first.response().await.unwrap(), 1);
+
+        let _third = context.schedule(3);
+        assert_eq!(receiver.recv().await.unwrap().0, 2);
+        assert_eq!(receiver.recv().await.unwrap().0, 3);
+    }
+
+    #[tokio::test]
+    async fn timed_out_requests_are_cancelled() {
+        let (sender, mut receiver) = request_channel::<u8, u8>(2);
+        let context =
+            EndpointContext::<u8, u8>::new(sender).with_timeout(Some(Duration::from_millis(10)));
+
+        // Single response never produced in time
+        let token = CancellationToken::new();
+        let scheduled = context.schedule(1).with_cancellation(token.clone());
+        let (_, egress) = receiver.recv().await.unwrap();
+
+        let response = scheduled.response().await;
+        assert!(matches!(response, Err(Error::Timeout { after }) if after == Duration::from_millis(10)));
+        assert!(token.is_cancelled());
+        assert!(egress.is_closed());
+
+        // Streams report the timeout after the responses produced so far
+        let token = CancellationToken::new();
+        let mut stream = context.schedule(2).with_cancellation(token.clone()).stream();
+        let (_, egress) = receiver.recv().await.unwrap();
+        egress.send(Ok(0)).await.unwrap();
+
+        assert_eq!(stream.next().await.unwrap().unwrap(), 0);
+        assert!(matches!(stream.next().await, Some(Err(Error::Timeout { .. }))));
+        assert!(stream.next().await.is_none());
+        assert!(token.is_cancelled());
+
+        // Completed requests are not flagged as cancelled
+        let token = CancellationToken::new();
+        let scheduled = context.schedule(3).with_cancellation(token.clone());
+        let (request, egress) = receiver.recv().await.unwrap();
+        egress.send(Ok(request)).await.unwrap();
+        assert_eq!(scheduled.response().await.unwrap(), 3);
+        assert!(!token.is_cancelled());
+    }
+}
@@ -0,0 +1,907 @@
+//! Distributed mode, where the request queue lives in a Redis stream rather than in memory.
+//!
+//! Transport replicas append the requests they receive to the stream, while worker processes,
+//! possibly running on other machines, consume it through a consumer group and send the responses
+//! back on a stream owned by the replica which scheduled the request. HTTP serving and inference
+//! are then scaled independently, any number of transports feeding any number of workers.
+//!
+//! Delivery is at-least-once: requests are acknowledged once their outcome was sent back. Workers
+//! renew their claim on the requests they process, requests not renewed within the visibility
+//! timeout (i.e. the worker crashed) are claimed again by another worker, so handlers should be
+//! idempotent. Requests not awaited anymore by the transport are flagged as cancelled, workers
+//! skip them or stop processing them. Requires Redis 6.2 or later.
+mod client;
+
+use crate::{EndpointContext, Error, RequestReceiver, RequestSender, ResponseSender};
+use client::{Client, Entry, claimed_entries, stream_entries};
+use redis::streams::{StreamAutoClaimReply, StreamReadReply};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::pin::pin;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::select;
+use tokio::sync::{Mutex as AsyncMutex, OwnedSemaphorePermit, Semaphore};
+use tokio::task::JoinSet;
+use tokio::time::{interval, sleep};
+use tokio_stream::StreamExt;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, info, warn};
+
+/// Environment variable holding the URL of the Redis server, enabling the distributed mode when set
+pub const REDIS_URL_ENV: &str = "HFENDPOINT_REDIS_URL";
+
+/// Environment variable holding the path of the PEM certificates of the authorities trusted to verify
+/// a Redis server reached over TLS (`rediss://`), instead of the public ones
+pub const REDIS_CA_ENV: &str = "HFENDPOINT_REDIS_CA";
+
+/// Environment variable holding the role of the process in the distributed mode, see [`QueueRole`]
+pub const QUEUE_ROLE_ENV: &str = "HFENDPOINT_QUEUE_ROLE";
+
+/// Environment variable holding the prefix of the Redis keys, endpoints sharing it share their queue
+pub const QUEUE_NAME_ENV: &str = "HFENDPOINT_QUEUE_NAME";
+
+/// Environment variable holding the number of seconds a worker may hold a request without renewing its claim
+pub const VISIBILITY_TIMEOUT_ENV: &str = "HFENDPOINT_VISIBILITY_TIMEOUT";
+
+/// Prefix of the Redis keys when `HFENDPOINT_QUEUE_NAME` is not set
+pub const DEFAULT_QUEUE_NAME: &str = "hfendpoints";
+
+/// Visibility timeout when `HFENDPOINT_VISIBILITY_TIMEOUT` is not set
+pub const DEFAULT_VISIBILITY_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Consumer group the workers belong to
+const WORKERS_GROUP: &str = "workers";
+
+/// Milliseconds a blocking read waits for new entries, bounding how late shutdown and cancellations are observed
+const BLOCK_MILLIS: &str = "1000";
+
+/// Approximate number of frames kept in the reply stream of a transport
+const REPLIES_MAX_LENGTH: &str = "10000";
+
+/// Seconds the reply stream of a transport and the cancellation flags are kept around
+const EXPIRATION_SECS: &str = "3600";
+
+/// Delay before retrying after a failure to reach Redis
+const RETRY_DELAY: Duration = Duration::from_secs(1);
+
+#[derive(Debug, thiserror::Error)]
+pub enum DistributedError {
+    #[error("Invalid Redis URL {0}")]
+    InvalidUrl(String),
+
+    #[error("Invalid {QUEUE_ROLE_ENV} ({0}), expected one of: transport, worker, all")]
+    InvalidRole(String),
+
+    #[error("Invalid Redis TLS configuration: {0}")]
+    Tls(String),
+
+    #[error("Failed to communicate with Redis: {0}")]
+    Redis(#[from] redis::RedisError),
+
+    #[error("Failed to encode or decode a queued payload: {0}")]
+    Payload(String),
+}
+
+impl From<serde_json::Error> for DistributedError {
+    fn from(err: serde_json::Error) -> Self {
+        DistributedError::Payload(err.to_string())
+    }
+}
+
+/// Requests and responses exchanged with the workers through the distributed queue
+pub trait Payload: Sized {
+    /// Encode the value as JSON to be queued
+    fn encode(&self) -> Result<Value, DistributedError>;
+
+    /// Decode a value encoded through [`Payload::encode`]
+    fn decode(payload: Value) -> Result<Self, DistributedError>;
+}
+
+impl<A: Payload, B: Payload> Payload for (A, B) {
+    fn encode(&self) -> Result<Value, DistributedError> {
+        Ok(Value::Array(vec![self.0.encode()?, self.1.encode()?]))
+    }
+
+    fn decode(payload: Value) -> Result<Self, DistributedError> {
+        match payload {
+            Value::Array(items) if items.len() == 2 => {
+                let mut items = items.into_iter();
+                let first = A::decode(items.next().unwrap_or_default())?;
+                let second = B::decode(items.next().unwrap_or_default())?;
+                Ok((first, second))
+            }
+            _ => Err(DistributedError::Payload(String::from(
+                "expected an array of two elements",
+            ))),
+        }
+    }
+}
+
+/// Part of the processes played in the distributed mode
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum QueueRole {
+    /// Serve HTTP, appending the requests to the queue
+    Transport,
+
+    /// Process the requests consumed from the queue, without serving HTTP
+    Worker,
+
+    /// Both serve HTTP and process requests, useful for small deployments
+    #[default]
+    All,
+}
+
+impl QueueRole {
+    /// Whether the process serves HTTP and appends the requests to the queue
+    pub fn is_transport(&self) -> bool {
+        matches!(self, QueueRole::Transport | QueueRole::All)
+    }
+
+    /// Whether the process consumes the queue to process the requests
+    pub fn is_worker(&self) -> bool {
+        matches!(self, QueueRole::Worker | QueueRole::All)
+    }
+}
+
+impl FromStr for QueueRole {
+    type Err = DistributedError;
+
+    fn from_str(role: &str) -> Result<Self, Self::Err> {
+        match role.trim().to_ascii_lowercase().as_str() {
+            "transport" => Ok(QueueRole::Transport),
+            "worker" => Ok(QueueRole::Worker),
+            "all" => Ok(QueueRole::All),
+            _ => Err(DistributedError::InvalidRole(role.to_string())),
+        }
+    }
+}
+
+impl Display for QueueRole {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            QueueRole::Transport => "transport",
+            QueueRole::Worker => "worker",
+            QueueRole::All => "all",
+        })
+    }
+}
+
+/// Error sent back by a worker, turned into `Error::HandlerException` on the transport
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+struct RemoteError {
+    kind: String,
+    message: String,
+    traceback: Option<String>,
+}
+
+impl From<&Error> for RemoteError {
+    fn from(err: &Error) -> Self {
+        match err {
+            Error::HandlerException {
+                kind,
+                message,
+                traceback,
+            } => Self {
+                kind: kind.clone(),
+                message: message.clone(),
+                traceback: traceback.clone(),
+            },
+            err => Self {
+                kind: String::from("WorkerError"),
+                message: err.to_string(),
+                traceback: None,
+            },
+        }
+    }
+}
+
+impl From<RemoteError> for Error {
+    fn from(err: RemoteError) -> Self {
+        Error::HandlerException {
+            kind: err.kind,
+            message: err.message,
+            traceback: err.traceback,
+        }
+    }
+}
+
+/// Outcome of a request, sent back by workers as one or more frames
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum Frame {
+    Response(Value),
+    Error(RemoteError),
+
+    /// No more frames follow for the request
+    End,
+}
+
+impl Frame {
+    fn from_outcome<O: Payload>(outcome: Result<O, Error>) -> Self {
+        match outcome.and_then(|response| response.encode().map_err(Error::from)) {
+            Ok(response) => Frame::Response(response),
+            Err(err) => Frame::Error(RemoteError::from(&err)),
+        }
+    }
+}
+
+/// Requests awaiting their outcome on a transport, by correlation id
+type Pending<O> = Arc<Mutex<HashMap<String, ResponseSender<O>>>>;
+
+/// Request queue shared by transports and workers through Redis streams
+#[derive(Clone)]
+pub struct DistributedQueue {
+    /// Connects to the server, verifying its certificate when reached over TLS
+    client: redis::Client,
+    role: QueueRole,
+    name: String,
+    visibility_timeout: Duration,
+
+    /// Identifies this process, as a consumer of the queue and as the owner of a reply stream
+    consumer: String,
+}
+
+impl DistributedQueue {
+    /// Queue held by the Redis server at `url`, the process playing both roles
+    pub fn new(url: &str) -> Result<Self, DistributedError> {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.subsec_nanos())
+            .unwrap_or_default();
+        // Windows exposes the machine name through COMPUTERNAME
+        let host = std::env::var("HOSTNAME")
+            .or_else(|_| std::env::var("COMPUTERNAME"))
+            .unwrap_or_else(|_| String::from("localhost"));
+
+        Ok(Self {
+            client: client::open(url)?,
+            role: QueueRole::default(),
+            name: String::from(DEFAULT_QUEUE_NAME),
+            visibility_timeout: DEFAULT_VISIBILITY_TIMEOUT,
+            consumer: format!("{host}-{}-{nanos:x}", std::process::id()),
+        })
+    }
+
+    pub fn with_role(mut self, role: QueueRole) -> Self {
+        self.role = role;
+        self
+    }
+
+    /// Prefix the Redis keys with `name`, endpoints serving different tasks must not share a name
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        self.name = name.into();
+        self
+    }
+
+    /// Let another worker claim a request once its worker did not renew its claim for `timeout`
+    pub fn with_visibility_timeout(mut self, timeout: Duration) -> Self {
+        self.visibility_timeout = timeout.max(Duration::from_millis(1));
+        self
+    }
+
+    pub fn role(&self) -> QueueRole {
+        self.role
+    }
+
+    #[inline]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn requests_key(&self) -> String {
+        format!("{}:requests", self.name)
+    }
+
+    fn cancelled_key(&self, request: &str) -> String {
+        format!("{}:cancelled:{request}", self.name)
+    }
+
+    /// Append the requests received through `ingress` to the queue and relay their outcome back,
+    /// until the transport goes away and every pending request completed
+    pub async fn forward<I, O>(&self, mut ingress: RequestReceiver<I, O>) -> Result<(), Error>
+    where
+        I: Payload,
+        O: Payload + Send + 'static,
+    {
+        let requests = self.requests_key();
+        let replies = format!("{}:replies:{}", self.name, self.consumer);
+        info!("[QUEUE] Forwarding requests to {requests}, replies expected on {replies}");
+
+        let pending: Pending<O> = Arc::default();
+        let drained = CancellationToken::new();
+        let listener = tokio::spawn(self.clone().listen(
+            replies.clone(),
+            Arc::clone(&pending),
+            drained.clone(),
+        ));
+
+        let mut client = Client::new(self.client.clone());
+        let mut sequence = 0u64;
+        let readiness = crate::readiness();
+        while let Some((request, egress)) = ingress.recv().await {
+            readiness.on_dequeued();
+            sequence += 1;
+            let id = format!("{}-{sequence}", self.consumer);
+            let payload = match request.encode().map(|payload| payload.to_string()) {
+                Ok(payload) => payload,
+                Err(err) => {
+                    let _ = egress.send(Err(err.into())).await;
+                    continue;
+                }
+            };
+
+            // Registered before appending the request, workers may answer before XADD returns
+            pending
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .insert(id.clone(), egress);
+
+            let appended = client
+                .command::<String>(&[
+                    "XADD", &requests, "*", "request", &id, "reply_to", &replies, "payload",
+                    &payload,
+                ])
+                .await;
+
+            if let Err(err) = appended {
+                error!("[QUEUE] Failed to append request {id}: {err}");
+                let egress = pending
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .remove(&id);
+                if let Some(egress) = egress {
+                    let _ = egress.send(Err(err.into())).await;
+                }
+            } else {
+                debug!("[QUEUE] Appended request {id}");
+            }
+        }
+
+        info!("[QUEUE] Transport closed, waiting for pending requests to complete");
+        drained.cancel();
+        if let Err(err) = listener.await {
+            error!("[QUEUE] Reply listener panicked: {err}");
+        }
+
+        if let Err(err) = client.command::<i64>(&["DEL", &replies]).await {
+            warn!("[QUEUE] Failed to delete the reply stream {replies}: {err}");
+        }
+        Ok(())
+    }
+
+    /// Dispatch the frames sent back by the workers to the pending requests, flagging the ones not
+    /// awaited anymore as cancelled, until `drained` is cancelled and no request is pending
+    async fn listen<O: Payload>(
+        self,
+        replies: String,
+        pending: Pending<O>,
+        drained: CancellationToken,
+    ) {
+        let mut client = Client::new(self.client.clone());
+        let mut last = String::from("0-0");
+
+        loop {
+            let cancelled: Vec<String> = {
+                let mut pending = pending.lock().unwrap_or_else(PoisonError::into_inner);
+                if drained.is_cancelled() && pending.is_empty() {
+                    break;
+                }
+
+                let cancelled = pending
+                    .iter()
+                    .filter(|(_, egress)| egress.is_closed())
+                    .map(|(id, _)| id.clone())
+                    .collect();
+                pending.retain(|_, egress| !egress.is_closed());
+                cancelled
+            };
+
+            for id in cancelled {
+                debug!("[QUEUE] Request {id} is not awaited anymore, flagging it as cancelled");
+                let key = self.cancelled_key(&id);
+                if let Err(err) = client
+                    .command::<()>(&["SET", &key, "1", "EX", EXPIRATION_SECS])
+                    .await
+                {
+                    warn!("[QUEUE] Failed to flag request {id} as cancelled: {err}");
+                }
+            }
+
+            let read = client
+                .command::<StreamReadReply>(&[
+                    "XREAD",
+                    "COUNT",
+                    "64",
+                    "BLOCK",
+                    BLOCK_MILLIS,
+                    "STREAMS",
+                    &replies,
+                    &last,
+                ])
+                .await;
+
+            match read {
+                Ok(reply) => {
+                    for entry in stream_entries(reply) {
+                        last.clone_from(&entry.id);
+                        dispatch(entry, &pending).await;
+                    }
+                }
+                Err(err) => {
+                    warn!("[QUEUE] Failed to read replies from {replies}: {err}");
+                    sleep(RETRY_DELAY).await;
+                }
+            }
+        }
+    }
+
+    /// Process the requests consumed from the queue through `ipc`, at most `concurrency` at once,
+    /// until `shutdown` resolves and the requests being processed completed
+    pub async fn consume<I, O>(
+        &self,
+        ipc: RequestSender<I, O>,
+        concurrency: usize,
+        shutdown: impl Future<Output = ()>,
+    ) -> Result<(), Error>
+    where
+        I: Payload + Send + 'static,
+        O: Payload + Send + 'static,
+    {
+        let requests = self.requests_key();
+        let mut client = Client::new(self.client.clone());
+
+        // Read from the start of the stream, requests appended before the first worker started are processed too
+        match client
+            .command::<()>(&[
+                "XGROUP",
+                "CREATE",
+                &requests,
+                WORKERS_GROUP,
+                "0",
+                "MKSTREAM",
+            ])
+            .await
+        {
+            Err(DistributedError::Redis(err)) if err.code() == Some("BUSYGROUP") => {}
+            created => {
+                created?;
+            }
+        }
+
+        info!(
+            "[QUEUE] Consuming {requests} as {}, up to {concurrency} request(s) at once",
+            self.consumer
+        );
+
+        let context = Arc::new(EndpointContext::new(ipc));
+        let commands = Arc::new(AsyncMutex::new(Client::new(self.client.clone())));
+        let slots = Arc::new(Semaphore::new(concurrency.max(1)));
+        let mut in_flight = JoinSet::new();
+        let mut shutdown = pin!(shutdown);
+
+        loop {
+            let next = async {
+                let slot = Arc::clone(&slots).acquire_owned().await;
+                (slot, self.next_entry(&mut client).await)
+            };
+
+            let (slot, entry) = select! {
+                next = next => next,
+                _ = &mut shutdown => break,
+            };
+
+            match (slot, entry) {
+                (Ok(slot), Ok(Some(entry))) => {
+                    let worker = self.clone();
+                    let context = Arc::clone(&context);
+                    let commands = Arc::clone(&commands);
+                    in_flight.spawn(async move {
+                        worker.process(entry, context, commands, slot).await;
+                    });
+                }
+                (_, Ok(None)) | (Err(_), _) => {}
+                (_, Err(err)) => {
+                    warn!("[QUEUE] Failed to read requests from {requests}: {err}");
+                    sleep(RETRY_DELAY).await;
+                }
+            }
+
+            while let Some(outcome) = in_flight.try_join_next() {
+                if let Err(err) = outcome {
+                    error!("[QUEUE] Request processing panicked: {err}");
+                }
+            }
+        }
+
+        info!(
+            "[QUEUE] Waiting for {} in-flight request(s) to complete",
+            in_flight.len()
+        );
+        while let Some(outcome) = in_flight.join_next().await {
+            if let Err(err) = outcome {
+                error!("[QUEUE] Request processing panicked: {err}");
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Claim a request whose worker did not renew its claim in time, or wait for a new one
+    async fn next_entry(&self, client: &mut Client) -> Result<Option<Entry>, DistributedError> {
+        let requests = self.requests_key();
+        let idle = self.visibility_timeout.as_millis().to_string();
+
+        let claimed = client
+            .command::<StreamAutoClaimReply>(&[
+                "XAUTOCLAIM",
+                &requests,
+                WORKERS_GROUP,
+                &self.consumer,
+                &idle,
+                "0-0",
+                "COUNT",
+                "1",
+            ])
+            .await?;
+        if let Some(entry) = claimed_entries(claimed).into_iter().next() {
+            warn!(
+                "[QUEUE] Claimed request {} not renewed by its worker in time",
+                entry.id
+            );
+            return Ok(Some(entry));
+        }
+
+        let read = client
+            .command::<StreamReadReply>(&[
+                "XREADGROUP",
+                "GROUP",
+                WORKERS_GROUP,
+                &self.consumer,
+                "COUNT",
+                "1",
+                "BLOCK",
+                BLOCK_MILLIS,
+                "STREAMS",
+                &requests,
+                ">",
+            ])
+            .await?;
+        Ok(stream_entries(read).into_iter().next())
+    }
+
+    /// Process the request held by `entry`, sending its outcome to the transport before acknowledging it
+    async fn process<I, O>(
+        self,
+        entry: Entry,
+        context: Arc<EndpointContext<I, O>>,
+        commands: Arc<AsyncMutex<Client>>,
+        _slot: OwnedSemaphorePermit,
+    ) where
+        I: Payload + Send + 'static,
+        O: Payload + Send + 'static,
+    {
+        let (Some(request), Some(reply_to), Some(payload)) = (
+            entry.field("request"),
+            entry.field("reply_to"),
+            entry.field("payload"),
+        ) else {
+            warn!("[QUEUE] Discarding malformed entry {}", entry.id);
+            self.acknowledge(&commands, &entry.id).await;
+            return;
+        };
+
+        let cancelled = self.cancelled_key(request);
+        if self.is_cancelled(&commands, &cancelled).await {
+            debug!("[QUEUE] Skipping request {request}, cancelled by the transport");
+            self.acknowledge(&commands, &entry.id).await;
+            return;
+        }
+
+        let decoded = serde_json::from_str(payload)
+            .map_err(DistributedError::from)
+            .and_then(I::decode);
+
+        let mut delivered = true;
+        match decoded {
+            Ok(decoded) => {
+                debug!("[QUEUE] Processing request {request}");
+                let mut responses = context.schedule(decoded).stream();
+                let mut heartbeat = interval(self.visibility_timeout / 3);
+                heartbeat.tick().await;
+
+                loop {
+                    select! {
+                        outcome = responses.next() => {
+                            let Some(outcome) = outcome else { break };
+                            let frame = Frame::from_outcome(outcome);
+                            delivered &= self.reply(&commands, reply_to, request, &frame).await;
+                        }
+                        _ = heartbeat.tick() => {
+                            self.renew(&commands, &entry.id).await;
+                            if self.is_cancelled(&commands, &cancelled).await {
+                                debug!("[QUEUE] Request {request} cancelled by the transport");
+                                break;
+                            }
+                        }
+                    }
+                }
+            }
+            Err(err) => {
+                error!("[QUEUE] Failed to decode request {request}: {err}");
+                let frame = Frame::Error(RemoteError::from(&Error::from(err)));
+                delivered &= self.reply(&commands, reply_to, request, &frame).await;
+            }
+        }
+
+        delivered &= self.reply(&commands, reply_to, request, &Frame::End).await;
+
+        // Not acknowledged, the request is processed again once the visibility timeout expires
+        if delivered {
+            self.acknowledge(&commands, &entry.id).await;
+        } else {
+            error!(
+                "[QUEUE] Failed to send back the outcome of request {request}, leaving it to another worker"
+            );
+        }
+    }
+
+    /// Send `frame` to the transport awaiting the outcome of `request`, returns whether it was delivered
+    async fn reply(
+        &self,
+        commands: &AsyncMutex<Client>,
+        reply_to: &str,
+        request: &str,
+        frame: &Frame,
+    ) -> bool {
+        let frame = match serde_json::to_string(frame) {
+            Ok(frame) => frame,
+            Err(err) => {
+                error!("[QUEUE] Failed to encode the outcome of request {request}: {err}");
+                return false;
+            }
+        };
+
+        let mut client = commands.lock().await;
+        let sent = client
+            .command::<String>(&[
+                "XADD",
+                reply_to,
+                "MAXLEN",
+                "~",
+                REPLIES_MAX_LENGTH,
+                "*",
+                "request",
+                request,
+                "frame",
+                &frame,
+            ])
+            .await;
+        let sent = match sent {
+            Ok(_) => {
+                client
+                    .command::<i64>(&["EXPIRE", reply_to, EXPIRATION_SECS])
+                    .await
+            }
+            Err(err) => Err(err),
+        };
+
+        sent.inspect_err(|err| warn!("[QUEUE] Failed to reply to {reply_to}: {err}"))
+            .is_ok()
+    }
+
+    /// Reset the idle time of the claim held on the request, so no other worker claims it
+    async fn renew(&self, commands: &AsyncMutex<Client>, id: &str) {
+        let renewed = commands
+            .lock()
+            .await
+            .command::<Vec<String>>(&[
+                "XCLAIM",
+                &self.requests_key(),
+                WORKERS_GROUP,
+                &self.consumer,
+                "0",
+                id,
+                "JUSTID",
+            ])
+            .await;
+        if let Err(err) = renewed {
+            warn!("[QUEUE] Failed to renew the claim on request {id}: {err}");
+        }
+    }
+
+    async fn is_cancelled(&self, commands: &AsyncMutex<Client>, key: &str) -> bool {
+        let exists = commands.lock().await.command(&["EXISTS", key]).await;
+        exists.unwrap_or(false)
+    }
+
+    /// Mark the request as processed and remove it from the stream
+    async fn acknowledge(&self, commands: &AsyncMutex<Client>, id: &str) {
+        let requests = self.requests_key();
+        let mut client = commands.lock().await;
+        let acknowledged = match client
+            .command::<i64>(&["XACK", &requests, WORKERS_GROUP, id])
+            .await
+        {
+            Ok(_) => client.command::<i64>(&["XDEL", &requests, id]).await,
+            Err(err) => Err(err),
+        };
+        if let Err(err) = acknowledged {
+            warn!("[QUEUE] Failed to acknowledge request {id}: {err}");
+        }
+    }
+}
+
+/// Hand the frame held by `entry` to the pending request it belongs to
+async fn dispatch<O: Payload>(entry: Entry, pending: &Pending<O>) {
+    let Some(request) = entry.field("request") else {
+        return;
+    };
+
+    let frame = match entry.field("frame").map(serde_json::from_str::<Frame>) {
+        Some(Ok(frame)) => frame,
+        _ => {
+            warn!("[QUEUE] Discarding malformed reply {}", entry.id);
+            return;
+        }
+    };
+
+    // Frames of requests completed already come from a worker which processed them again
+    let outcome = match frame {
+        Frame::Response(response) => O::decode(response).map_err(Error::from),
+        Frame::Error(err) => Err(err.into()),
+        Frame::End => {
+            let mut pending = pending.lock().unwrap_or_else(PoisonError::into_inner);
+            pending.remove(request);
+            return;
+        }
+    };
+
+    // Replies are dispatched in order, waiting for the clients reading theirs slower than produced
+    let egress = pending
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .get(request)
+        .cloned();
+    if let Some(egress) = egress {
+        let _ = egress.send(outcome).await;
+    }
+}
+
+/// Distributed queue defined through `HFENDPOINT_REDIS_URL`, `HFENDPOINT_QUEUE_ROLE`,
+/// `HFENDPOINT_QUEUE_NAME` and `HFENDPOINT_VISIBILITY_TIMEOUT`, requests are queued in memory when not set
+pub fn distributed_queue_from_env() -> Result<Option<DistributedQueue>, DistributedError> {
+    let Ok(url) = std::env::var(REDIS_URL_ENV) else {
+        return Ok(None);
+    };
+
+    let mut queue = DistributedQueue::new(&url)?;
+    if let Ok(role) = std::env::var(QUEUE_ROLE_ENV) {
+        queue = queue.with_role(role.parse()?);
+    }
+    if let Ok(name) = std::env::var(QUEUE_NAME_ENV) {
+        queue = queue.with_name(name);
+    }
+    if let Ok(timeout) = std::env::var(VISIBILITY_TIMEOUT_ENV) {
+        match timeout.parse::<f64>().map(Duration::try_from_secs_f64) {
+            Ok(Ok(timeout)) if !timeout.is_zero() => {
+                queue = queue.with_visibility_timeout(timeout);
+            }
+            _ => warn!(
+                "Ignoring malformed {VISIBILITY_TIMEOUT_ENV} ({timeout}), expected a positive number of seconds"
+            ),
+        }
+    }
+
+    Ok(Some(queue))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::distributed::client::Entry;
+    use crate::distributed::{
+        DistributedError, Frame, Payload, Pending, QueueRole, RemoteError, dispatch,
+    };
+    use crate::{Error, RESPONSE_CHANNEL_CAPACITY};
+    use serde_json::{Value, json};
+    use std::collections::HashMap;
+    use std::sync::Arc;
+    use tokio::sync::mpsc::channel;
+
+    #[derive(Debug, PartialEq)]
+    struct Number(u64);
+
+    impl Payload for Number {
+        fn encode(&self) -> Result<Value, DistributedError> {
+            Ok(json!(self.0))
+        }
+
+        fn decode(payload: Value) -> Result<Self, DistributedError> {
+            payload
+                .as_u64()
+                .map(Number)
+                .ok_or_else(|| DistributedError::Payload(String::from("expected a number")))
+        }
+    }
+
+    fn frame(request: &str, frame: &Frame) -> Entry {
+        Entry {
+            id: String::from("1-0"),
+            fields: HashMap::from([
+                (String::from("request"), String::from(request)),
+                (String::from("frame"), serde_json::to_string(frame).unwrap()),
+            ]),
+        }
+    }
+
+    #[test]
+    fn encode_tuples() {
+        let encoded = (Number(1), Number(2)).encode().unwrap();
+        assert_eq!(encoded, json!([1, 2]));
+        assert_eq!(
+            <(Number, Number)>::decode(encoded).unwrap(),
+            (Number(1), Number(2))
+        );
+        assert!(<(Number, Number)>::decode(json!([1])).is_err());
+    }
+
+    #[test]
+    fn parse_queue_roles() {
+        assert_eq!(
+            "Transport".parse::<QueueRole>().unwrap(),
+            QueueRole::Transport
+        );
+        assert_eq!(" worker ".parse::<QueueRole>().unwrap(), QueueRole::Worker);
+        assert!("all".parse::<QueueRole>().unwrap().is_transport());
+        assert!("all".parse::<QueueRole>().unwrap().is_worker());
+        assert!("scheduler".parse::<QueueRole>().is_err());
+    }
+
+    #[test]
+    fn encode_frames() {
+        assert_eq!(
+            serde_json::to_value(Frame::from_outcome(Ok(Number(3)))).unwrap(),
+            json!({"response": 3})
+        );
+        assert_eq!(serde_json::to_value(Frame::End).unwrap(), json!("end"));
+
+        let raised = Error::HandlerException {
+            kind: String::from("ValueError"),
+            message: String::from("boom"),
+            traceback: None,
+        };
+        assert_eq!(
+            serde_json::to_value(Frame::from_outcome::<Number>(Err(raised))).unwrap(),
+            json!({"error": {"kind": "ValueError", "message": "boom", "traceback": null}})
+        );
+
+        let RemoteError { kind, .. } = RemoteError::from(&Error::NoResponse);
+        assert_eq!(kind, "WorkerError");
+    }
+
+    #[tokio::test]
+    async fn dispatch_frames_to_pending_requests() {
+        let pending: Pending<Number> = Arc::default();
+        let (egress, mut outcomes) = channel(RESPONSE_CHANNEL_CAPACITY);
+        pending.lock().unwrap().insert(String::from("r-1"), egress);
+
+        dispatch(frame("r-1", &Frame::Response(json!(7))), &pending).await;
+        dispatch(frame("r-1", &Frame::Response(json!("seven"))), &pending).await;
+        dispatch(frame("r-2", &Frame::Response(json!(8))), &pending).await;
+        dispatch(frame("r-1", &Frame::End), &pending).await;
+
+        assert_eq!(outcomes.try_recv().unwrap().unwrap(), Number(7));
+        assert!(matches!(
+            outcomes.try_recv().unwrap(),
+            Err(Error::Distributed(DistributedError::Payload(_)))
+        ));
+
+        // The stream of the request ends along with the frames
+        assert!(pending.lock().unwrap().is_empty());
+        assert!(outcomes.try_recv().is_err());
+        assert!(outcomes.is_closed());
+    }
+}
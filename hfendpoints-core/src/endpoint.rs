@@ -1,6 +1,12 @@
 use crate::Error;
 
-///
+/// Expose a `Handler` to clients through a transport bound to `A`
 pub trait Endpoint<A> {
     fn serve(&self, binding: A) -> impl Future<Output=Result<(), Error>> + Send;
+
+    /// Report the handler initialized, i.e. its model loaded, the endpoint being ready to process
+    /// requests from now on as long as the handler loop runs, see [`crate::Readiness`]
+    fn on_handler_initialized(&self) {
+        crate::readiness().handler_initialized();
+    }
 }
@@ -1,58 +1,195 @@
-use crate::Error;
+use crate::{Error, RequestReceiver, ResponseSender};
 use std::sync::Arc;
-use tokio::spawn;
-use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
-use tracing::{debug, error, span, warn, Instrument, Level};
+use tokio::select;
+use tokio::task::JoinSet;
+use tracing::{debug, error, info, span, warn, Instrument, Level};
 
+/// Version of the `Handler` protocol implemented by this runtime
+pub const HANDLER_PROTOCOL_VERSION: u16 = 1;
+
+/// Oldest `Handler` protocol version this runtime is still able to drive
+pub const MIN_HANDLER_PROTOCOL_VERSION: u16 = 1;
+
+/// Check the protocol version declared by a handler is supported by this runtime.
 ///
+/// Returns the negotiated version on success, or `Error::IncompatibleHandler` describing
+/// the supported range otherwise.
+pub fn negotiate_protocol_version(version: u16) -> Result<u16, Error> {
+    if (MIN_HANDLER_PROTOCOL_VERSION..=HANDLER_PROTOCOL_VERSION).contains(&version) {
+        Ok(version)
+    } else {
+        Err(Error::IncompatibleHandler {
+            found: version,
+            min: MIN_HANDLER_PROTOCOL_VERSION,
+            max: HANDLER_PROTOCOL_VERSION,
+        })
+    }
+}
+
+/// Process the requests scheduled by an endpoint and produce the matching responses
 pub trait Handler {
     type Request;
     type Response;
 
+    /// Version of the handler protocol this implementation targets,
+    /// checked against the runtime before any request is dispatched.
+    const PROTOCOL_VERSION: u16 = HANDLER_PROTOCOL_VERSION;
+
+    /// Asynchronously process a single `request`.
     ///
-    ///
-    /// # Arguments
-    ///
-    /// * `request`:
-    ///
-    /// returns: Self::Response
-    ///
-    /// # Examples
-    ///
-    /// ```
-    ///
-    /// ```
+    /// The returned future is awaited on the runtime driving the endpoint, one task per request,
+    /// so implementations should `.await` their inference rather than blocking the thread.
+    /// Errors are sent back to the transport which turns them into the appropriate response.
     fn on_request(
         &self,
         request: Self::Request,
     ) -> impl Future<Output=Result<Self::Response, Error>> + Send;
+
+    /// Asynchronously process a single `request` which may produce several responses,
+    /// i.e. incremental events when the client asked for a streamed answer.
+    ///
+    /// Every response is sent through `egress` as soon as it is available, the stream ends
+    /// when `egress` is dropped. Defaults to sending back the unique response of `on_request`.
+    fn on_stream(
+        &self,
+        request: Self::Request,
+        egress: ResponseSender<Self::Response>,
+    ) -> impl Future<Output=()> + Send
+    where
+        Self: Sync,
+        Self::Request: Send,
+        Self::Response: Send,
+    {
+        async move {
+            let response = self.on_request(request).await;
+            if let Err(e) = egress.send(response).await {
+                error!("Failed to send back response to client: {e}");
+            }
+        }
+    }
+}
+
+/// Let `handler` process `request`, stopping as soon as the transport does not await the outcome anymore
+pub(crate) async fn process<H>(handler: &H, request: H::Request, egress: ResponseSender<H::Response>)
+where
+    H: Handler + Sync,
+    H::Request: Send,
+    H::Response: Send,
+{
+    let cancelled = egress.clone();
+    select! {
+        _ = handler.on_stream(request, egress) => {},
+        _ = cancelled.closed() => debug!("Request cancelled by the transport"),
+    }
 }
 
 pub async fn wait_for_requests<I, O, H>(
-    mut ingress: UnboundedReceiver<(I, UnboundedSender<Result<O, Error>>)>,
+    mut ingress: RequestReceiver<I, O>,
     background_handler: Arc<H>,
-) where
+) -> Result<(), Error>
+where
     I: Send + 'static,
     O: Send + 'static,
     H: Handler<Request=I, Response=O> + Send + Sync + 'static,
 {
+    negotiate_protocol_version(H::PROTOCOL_VERSION).inspect_err(|err| {
+        error!("[LOOPER] Refusing to start: {err}");
+    })?;
+
+    // Requests being processed, awaited before exiting so none is dropped on shutdown
+    let mut in_flight = JoinSet::new();
+    let readiness = crate::readiness();
+    let alive = readiness.track_loop();
+
     'looper: loop {
-        if let Some((request, egress)) = ingress.recv().await {
-            debug!("[LOOPER] Received request");
-            let background_handler = Arc::clone(&background_handler);
-            let sp_on_request = span!(Level::DEBUG, "on_request");
-
-            spawn(
-                async move {
-                    let response = background_handler.on_request(request).await;
-                    if let Err(e) = egress.send(response) {
-                        error!("Failed to send back response to client: {e}");
-                    }
-                }.instrument(sp_on_request),
-            );
-        } else {
-            warn!("[LOOPER] received a termination notice from ingress channel, exiting");
-            break 'looper;
+        select! {
+            request = ingress.recv() => {
+                let Some((request, egress)) = request else {
+                    warn!("[LOOPER] received a termination notice from ingress channel, exiting");
+                    break 'looper;
+                };
+
+                debug!("[LOOPER] Received request");
+                readiness.on_dequeued();
+                #[cfg(feature = "metrics")]
+                let tracked = crate::endpoint_metrics().on_dequeued();
+
+                let background_handler = Arc::clone(&background_handler);
+                let sp_on_request = span!(Level::DEBUG, "on_request");
+
+                in_flight.spawn(
+                    async move {
+                        process(&*background_handler, request, egress).await;
+
+                        #[cfg(feature = "metrics")]
+                        drop(tracked);
+                    }.instrument(sp_on_request),
+                );
+            }
+            Some(_) = in_flight.join_next(), if !in_flight.is_empty() => {}
+        }
+    }
+
+    info!("[LOOPER] Waiting for {} in-flight request(s) to complete", in_flight.len());
+    while let Some(outcome) = in_flight.join_next().await {
+        if let Err(err) = outcome {
+            error!("[LOOPER] Request processing panicked: {err}");
+        }
+    }
+
+    alive.exit();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::handler::{negotiate_protocol_version, wait_for_requests, HANDLER_PROTOCOL_VERSION};
+    use crate::{request_channel, EndpointContext, Error, Handler};
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tokio::time::sleep;
+
+    struct SlowHandler;
+
+    impl Handler for SlowHandler {
+        type Request = u8;
+        type Response = u8;
+
+        async fn on_request(&self, request: u8) -> Result<u8, Error> {
+            sleep(Duration::from_millis(20)).await;
+            Ok(request)
+        }
+    }
+
+    #[tokio::test]
+    async fn looper_drains_in_flight_requests_on_exit() {
+        let (sender, receiver) = request_channel(1);
+        let looper = tokio::spawn(wait_for_requests(receiver, Arc::new(SlowHandler)));
+
+        // Request still processed while the transport goes away
+        let context = EndpointContext::<u8, u8>::new(sender);
+        let scheduled = context.schedule(7);
+        drop(context);
+
+        assert_eq!(scheduled.response().await.unwrap(), 7);
+        looper.await.unwrap().unwrap();
+    }
+
+    #[test]
+    fn negotiate_current_protocol_version() {
+        assert_eq!(
+            negotiate_protocol_version(HANDLER_PROTOCOL_VERSION).unwrap(),
+            HANDLER_PROTOCOL_VERSION
+        );
+    }
+
+    #[test]
+    fn negotiate_unsupported_protocol_versions() {
+        for version in [0, HANDLER_PROTOCOL_VERSION + 1] {
+            match negotiate_protocol_version(version) {
+                Err(Error::IncompatibleHandler { found, .. }) => assert_eq!(found, version),
+                _ => panic!("Protocol version {version} should not be supported"),
+            }
         }
     }
 }
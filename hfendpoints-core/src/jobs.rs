@@ -0,0 +1,572 @@
+//! Jobs processed in the background, persisted on disk so they survive restarts.
+//!
+//! A job is a list of JSON inputs, each of them processed by the endpoint once it is idle: the
+//! [`JobRunner`] only hands an input over while no interactive request waits in the queue, one
+//! input at a time, so jobs never delay nor crowd out the requests answered on the connection.
+//!
+//! Every job lives in a directory of its own under the store: `job.json` describes the job, its
+//! inputs are kept in `input.jsonl` and the outcome of each of them is appended to `output.jsonl`,
+//! or `errors.jsonl` when it failed. Progress is read back from these files when the endpoint boots,
+//! jobs interrupted by a restart resuming with the first input without outcome.
+use crate::Readiness;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fs::OpenOptions;
+use std::future::Future;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::Notify;
+use tokio::task::JoinHandle;
+use tokio::time::sleep;
+use tracing::{debug, error, info, warn};
+
+/// Environment variable holding the directory the jobs are persisted in
+pub const JOBS_DIR_ENV: &str = "HFENDPOINT_JOBS_DIR";
+
+/// Interval at which the runner checks whether the queue drained, while interactive requests wait
+const IDLE_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Time the runner waits before picking a job again after failing to access the store
+const RETRY_DELAY: Duration = Duration::from_secs(1);
+
+const JOB_FILE: &str = "job.json";
+const INPUT_FILE: &str = "input.jsonl";
+const OUTPUT_FILE: &str = "output.jsonl";
+const ERRORS_FILE: &str = "errors.jsonl";
+
+#[inline]
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|now| now.as_secs())
+        .unwrap_or_default()
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum JobError {
+    #[error("No job {0}")]
+    NotFound(String),
+
+    #[error("Failed to access the job store: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Malformed job record: {0}")]
+    Malformed(#[from] serde_json::Error),
+}
+
+/// Lifecycle of a job
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    /// Inputs are waiting to be processed, or being processed
+    InProgress,
+
+    /// Every input was processed
+    Completed,
+
+    /// The inputs could not be read back from the store
+    Failed,
+
+    /// Cancellation was requested, the input being processed completes first
+    Cancelling,
+
+    /// Cancelled before every input was processed
+    Cancelled,
+}
+
+/// Number of inputs of a job, and how many of them were processed
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct JobCounts {
+    pub total: usize,
+    pub completed: usize,
+    pub failed: usize,
+}
+
+impl JobCounts {
+    /// Number of inputs processed, successfully or not
+    pub fn processed(&self) -> usize {
+        self.completed + self.failed
+    }
+}
+
+/// Job persisted in a [`JobStore`]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Job {
+    pub id: String,
+
+    /// What the inputs are, as set by the creator of the job, i.e. the route they are sent to
+    pub kind: String,
+
+    /// Who created the job, as set by its creator, jobs being only exposed to their owner
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub owner: Option<Value>,
+
+    pub status: JobStatus,
+
+    /// Unix timestamp (in seconds) when the job was created
+    pub created_at: u64,
+
+    /// Unix timestamp (in seconds) when the job completed, failed or was cancelled
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub finished_at: Option<u64>,
+
+    pub counts: JobCounts,
+
+    /// Why the job failed, if it did
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub failure: Option<String>,
+}
+
+impl Job {
+    /// Whether the job is still to be processed by the runner
+    pub fn is_pending(&self) -> bool {
+        matches!(self.status, JobStatus::InProgress | JobStatus::Cancelling)
+    }
+}
+
+/// Directory the jobs are persisted in
+pub struct JobStore {
+    dir: PathBuf,
+
+    /// Prefix of the ids generated by this process, distinguishing them from the previous runs
+    run: u64,
+    sequence: AtomicU64,
+
+    /// Serializes the updates of the job records, shared by the routes and the runner
+    records: Mutex<()>,
+
+    /// Wakes the runner up when a job is created
+    created: Notify,
+}
+
+impl JobStore {
+    /// Persist the jobs in `dir`, created if missing
+    pub fn open(dir: impl Into<PathBuf>) -> Result<Self, JobError> {
+        let dir = dir.into();
+        std::fs::create_dir_all(&dir)?;
+
+        let started = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|now| now.as_millis() as u64)
+            .unwrap_or_default();
+        Ok(Self {
+            dir,
+            run: started,
+            sequence: AtomicU64::new(0),
+            records: Mutex::new(()),
+            created: Notify::new(),
+        })
+    }
+
+    /// Store defined through `HFENDPOINT_JOBS_DIR`, jobs are not accepted when not set
+    pub fn from_env() -> Result<Option<Self>, JobError> {
+        match std::env::var(JOBS_DIR_ENV) {
+            Ok(dir) if !dir.trim().is_empty() => {
+                info!("Persisting jobs in {dir}");
+                Self::open(dir).map(Some)
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Directory of the job `id`, ids which could escape the store not referencing any job
+    fn path(&self, id: &str) -> Result<PathBuf, JobError> {
+        let valid = !id.is_empty()
+            && id
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+        if valid {
+            Ok(self.dir.join(id))
+        } else {
+            Err(JobError::NotFound(id.to_string()))
+        }
+    }
+
+    /// Persist a job processing `inputs` on behalf of `owner`, its id starting with `prefix`, the
+    /// runner being notified
+    pub fn create(
+        &self,
+        prefix: &str,
+        kind: &str,
+        owner: Option<Value>,
+        inputs: &[Value],
+    ) -> Result<Job, JobError> {
+        let sequence = self.sequence.fetch_add(1, Ordering::Relaxed);
+        let job = Job {
+            id: format!("{prefix}{:x}{sequence:04x}", self.run),
+            kind: kind.to_string(),
+            owner,
+            status: JobStatus::InProgress,
+            created_at: unix_now(),
+            finished_at: None,
+            counts: JobCounts {
+                total: inputs.len(),
+                ..JobCounts::default()
+            },
+            failure: None,
+        };
+
+        let dir = self.path(&job.id)?;
+        std::fs::create_dir_all(&dir)?;
+        let mut lines = Vec::new();
+        for input in inputs {
+            serde_json::to_writer(&mut lines, input)?;
+            lines.push(b'\n');
+        }
+        std::fs::write(dir.join(INPUT_FILE), lines)?;
+
+        // The record is written last, directories without one are not listed
+        write_record(&dir, &job)?;
+        self.created.notify_one();
+        Ok(job)
+    }
+
+    /// The job `id`
+    pub fn get(&self, id: &str) -> Result<Job, JobError> {
+        let path = self.path(id)?.join(JOB_FILE);
+        match std::fs::read(path) {
+            Ok(record) => Ok(serde_json::from_slice(&record)?),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                Err(JobError::NotFound(id.to_string()))
+            }
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// Every job of the store, the most recent first. Records which cannot be read back are
+    /// skipped, so a single damaged job neither hides the others nor stalls the runner
+    pub fn list(&self) -> Result<Vec<Job>, JobError> {
+        let mut jobs = Vec::new();
+        for entry in std::fs::read_dir(&self.dir)?.flatten() {
+            let path = entry.path().join(JOB_FILE);
+            let record = match std::fs::read(&path) {
+                Ok(record) => record,
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(err) => {
+                    warn!("Skipping unreadable job record {}: {err}", path.display());
+                    continue;
+                }
+            };
+            match serde_json::from_slice::<Job>(&record) {
+                Ok(job) => jobs.push(job),
+                Err(err) => warn!("Skipping malformed job record {}: {err}", path.display()),
+            }
+        }
+        jobs.sort_by(|a, b| b.created_at.cmp(&a.created_at).then(b.id.cmp(&a.id)));
+        Ok(jobs)
+    }
+
+    /// Request the cancellation of the job `id`, the runner stopping before its next input
+    pub fn cancel(&self, id: &str) -> Result<Job, JobError> {
+        self.update(id, |job| {
+            if job.status == JobStatus::InProgress {
+                job.status = JobStatus::Cancelling;
+            }
+        })
+    }
+
+    /// Outcomes of the inputs of the job `id` processed successfully, as JSON lines
+    pub fn output(&self, id: &str) -> Result<Vec<u8>, JobError> {
+        self.read_outcomes(id, OUTPUT_FILE)
+    }
+
+    /// Outcomes of the inputs of the job `id` which failed, as JSON lines
+    pub fn errors(&self, id: &str) -> Result<Vec<u8>, JobError> {
+        self.read_outcomes(id, ERRORS_FILE)
+    }
+
+    fn read_outcomes(&self, id: &str, file: &str) -> Result<Vec<u8>, JobError> {
+        let dir = self.path(id)?;
+        if !dir.join(JOB_FILE).exists() {
+            return Err(JobError::NotFound(id.to_string()));
+        }
+        match std::fs::read(dir.join(file)) {
+            Ok(outcomes) => Ok(outcomes),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// Apply `update` to the record of the job `id`, returning the updated job
+    fn update(&self, id: &str, update: impl FnOnce(&mut Job)) -> Result<Job, JobError> {
+        let _records = self.records.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut job = self.get(id)?;
+        update(&mut job);
+        write_record(&self.path(id)?, &job)?;
+        Ok(job)
+    }
+
+    /// The oldest job still to be processed, if any
+    fn next_pending(&self) -> Result<Option<Job>, JobError> {
+        Ok(self.list()?.into_iter().rev().find(Job::is_pending))
+    }
+
+    /// Inputs of the job `id`
+    fn inputs(&self, id: &str) -> Result<Vec<Value>, JobError> {
+        let inputs = std::fs::read(self.path(id)?.join(INPUT_FILE))?;
+        inputs
+            .split(|&byte| byte == b'\n')
+            .filter(|line| !line.is_empty())
+            .map(|line| serde_json::from_slice(line).map_err(JobError::from))
+            .collect()
+    }
+
+    /// Number of inputs of the job `id` which already have an outcome, as found on disk
+    fn progress(&self, id: &str) -> Result<JobCounts, JobError> {
+        let count = |file: &str| {
+            let outcomes = self.read_outcomes(id, file)?;
+            Ok::<_, JobError>(outcomes.iter().filter(|&&byte| byte == b'\n').count())
+        };
+        Ok(JobCounts {
+            total: 0,
+            completed: count(OUTPUT_FILE)?,
+            failed: count(ERRORS_FILE)?,
+        })
+    }
+
+    /// Append the `outcome` of the next input of the job `id`, accounting it
+    fn record_outcome(&self, id: &str, outcome: Result<Value, Value>) -> Result<Job, JobError> {
+        let (file, line) = match &outcome {
+            Ok(line) => (OUTPUT_FILE, line),
+            Err(line) => (ERRORS_FILE, line),
+        };
+        let mut bytes = serde_json::to_vec(line)?;
+        bytes.push(b'\n');
+
+        // Lines are appended in a single write, the progress being read back from them
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.path(id)?.join(file))?
+            .write_all(&bytes)?;
+
+        self.update(id, |job| match outcome {
+            Ok(_) => job.counts.completed += 1,
+            Err(_) => job.counts.failed += 1,
+        })
+    }
+}
+
+/// Write the record of `job` in `dir`, renamed once complete so a crash never truncates it
+fn write_record(dir: &Path, job: &Job) -> Result<(), JobError> {
+    let partial = dir.join("job.json.tmp");
+    std::fs::write(&partial, serde_json::to_vec_pretty(job)?)?;
+    std::fs::rename(&partial, dir.join(JOB_FILE))?;
+    Ok(())
+}
+
+/// Background task processing the jobs of a [`JobStore`], oldest first, at low priority
+pub struct JobRunner;
+
+impl JobRunner {
+    /// Process the inputs of the jobs of `store` through `process`, one at a time and only while
+    /// `readiness` reports no request waiting in the queue. `process` is given the job and the
+    /// position of the input within it, resolving to its outcome, `Err` marking it failed, both
+    /// being persisted along with the job.
+    pub fn spawn<F, Fut>(
+        store: Arc<JobStore>,
+        readiness: Arc<Readiness>,
+        process: F,
+    ) -> JoinHandle<()>
+    where
+        F: FnMut(&Job, usize, Value) -> Fut + Send + 'static,
+        Fut: Future<Output = Result<Value, Value>> + Send + 'static,
+    {
+        tokio::spawn(run(store, readiness, process))
+    }
+}
+
+async fn run<F, Fut>(store: Arc<JobStore>, readiness: Arc<Readiness>, mut process: F)
+where
+    F: FnMut(&Job, usize, Value) -> Fut + Send + 'static,
+    Fut: Future<Output = Result<Value, Value>> + Send + 'static,
+{
+    loop {
+        let job = match store.next_pending() {
+            Ok(Some(job)) => job,
+            Ok(None) => {
+                store.created.notified().await;
+                continue;
+            }
+            Err(err) => {
+                error!("Unable to read the job store: {err}");
+                sleep(RETRY_DELAY).await;
+                continue;
+            }
+        };
+
+        if let Err(err) = process_job(&store, &readiness, &mut process, job.clone()).await {
+            error!("Job {} failed: {err}", job.id);
+            let failed = store.update(&job.id, |job| {
+                job.status = JobStatus::Failed;
+                job.finished_at = Some(unix_now());
+                job.failure = Some(err.to_string());
+            });
+            if failed.is_err() {
+                sleep(RETRY_DELAY).await;
+            }
+        }
+    }
+}
+
+async fn process_job<F, Fut>(
+    store: &JobStore,
+    readiness: &Readiness,
+    process: &mut F,
+    job: Job,
+) -> Result<(), JobError>
+where
+    F: FnMut(&Job, usize, Value) -> Fut,
+    Fut: Future<Output = Result<Value, Value>>,
+{
+    // Jobs interrupted by a restart resume after the last input with an outcome
+    let progress = store.progress(&job.id)?;
+    let mut job = store.update(&job.id, |job| {
+        job.counts.completed = progress.completed;
+        job.counts.failed = progress.failed;
+    })?;
+    debug!(
+        "Processing job {} from input {}",
+        job.id,
+        progress.processed()
+    );
+
+    let inputs = store.inputs(&job.id)?.into_iter().enumerate();
+    for (index, input) in inputs.skip(progress.processed()) {
+        // Interactive requests go first, inputs are only scheduled on an idle queue
+        while readiness.queued() > 0 {
+            sleep(IDLE_POLL_INTERVAL).await;
+        }
+
+        if store.get(&job.id)?.status == JobStatus::Cancelling {
+            break;
+        }
+
+        let outcome = process(&job, index, input).await;
+        job = store.record_outcome(&job.id, outcome)?;
+    }
+
+    store.update(&job.id, |job| {
+        job.status = match job.status {
+            JobStatus::Cancelling => JobStatus::Cancelled,
+            _ => JobStatus::Completed,
+        };
+        job.finished_at = Some(unix_now());
+    })?;
+    info!("Job {} finished", job.id);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Readiness;
+    use crate::jobs::{JobRunner, JobStatus, JobStore};
+    use serde_json::{Value, json};
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tokio::sync::mpsc::unbounded_channel;
+
+    fn directory(name: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("jobs-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    async fn wait_for(store: &JobStore, id: &str, status: JobStatus) {
+        for _ in 0..200 {
+            if store.get(id).unwrap().status == status {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("Job {id} never reached {status:?}");
+    }
+
+    #[tokio::test]
+    async fn process_jobs_once_the_queue_is_idle() {
+        let dir = directory("idle");
+        let store = Arc::new(JobStore::open(&dir).unwrap());
+        let readiness = Arc::new(Readiness::new(None));
+
+        // An interactive request waits in the queue, the job is held back
+        readiness.on_scheduled();
+        let (processed, mut inputs) = unbounded_channel();
+        JobRunner::spawn(
+            Arc::clone(&store),
+            Arc::clone(&readiness),
+            move |_, _, input| {
+                let _ = processed.send(input.clone());
+                async move {
+                    match input.as_u64() {
+                        Some(value) if value % 2 == 0 => Ok(json!(value / 2)),
+                        _ => Err(input),
+                    }
+                }
+            },
+        );
+
+        let job = store
+            .create("job_", "halve", None, &[json!(4), json!(3), json!(8)])
+            .unwrap();
+        assert_eq!(job.counts.total, 3);
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(inputs.is_empty());
+
+        readiness.on_dequeued();
+        wait_for(&store, &job.id, JobStatus::Completed).await;
+        assert_eq!(inputs.recv().await, Some(json!(4)));
+
+        let job = store.get(&job.id).unwrap();
+        assert_eq!((job.counts.completed, job.counts.failed), (2, 1));
+        assert!(job.finished_at.is_some());
+        assert_eq!(store.output(&job.id).unwrap(), b"2\n4\n");
+        assert_eq!(store.errors(&job.id).unwrap(), b"3\n");
+        assert_eq!(store.list().unwrap(), vec![job.clone()]);
+
+        // Damaged records are left out, neither failing the listing nor stalling the runner
+        std::fs::create_dir_all(dir.join("job_damaged")).unwrap();
+        std::fs::write(dir.join("job_damaged").join("job.json"), b"{ \"id\": ").unwrap();
+        assert_eq!(store.list().unwrap(), vec![job]);
+
+        let next = store.create("job_", "halve", None, &[json!(6)]).unwrap();
+        wait_for(&store, &next.id, JobStatus::Completed).await;
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn resume_and_cancel_jobs() {
+        let dir = directory("resume");
+        let store = JobStore::open(&dir).unwrap();
+        let job = store
+            .create("job_", "echo", None, &[json!(1), json!(2), json!(3)])
+            .unwrap();
+
+        // The first input was processed before a restart, the second one cancels the job
+        store.record_outcome(&job.id, Ok(json!(1))).unwrap();
+        let store = Arc::new(JobStore::open(&dir).unwrap());
+        let id = job.id.clone();
+        let cancelling = Arc::clone(&store);
+        JobRunner::spawn(
+            Arc::clone(&store),
+            Arc::new(Readiness::new(None)),
+            move |_, _, input: Value| {
+                cancelling.cancel(&id).unwrap();
+                async move { Ok(input) }
+            },
+        );
+
+        wait_for(&store, &job.id, JobStatus::Cancelled).await;
+        assert_eq!(store.output(&job.id).unwrap(), b"1\n2\n");
+        assert_eq!(store.get(&job.id).unwrap().counts.completed, 2);
+        assert!(matches!(
+            store.get("../job"),
+            Err(crate::jobs::JobError::NotFound(_))
+        ));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
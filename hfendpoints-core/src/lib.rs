@@ -1,20 +1,126 @@
+mod batch;
 mod context;
+#[cfg(feature = "distributed")]
+pub mod distributed;
 mod endpoint;
 mod handler;
+#[cfg(feature = "jobs")]
+pub mod jobs;
 mod metrics;
+mod preemption;
+mod readiness;
+pub mod shared;
+#[cfg(feature = "usage")]
+pub mod usage;
+mod workers;
 
-pub use context::EndpointContext;
+pub use batch::{max_batch_size_from_env, Batcher, DEFAULT_MAX_BATCH_SIZE, MAX_BATCH_SIZE_ENV};
+pub use context::{
+    queue_capacity_from_env, request_channel, request_timeout_from_env, EndpointContext,
+    RequestReceiver, RequestSender, ResponseSender, ScheduledRequest, DEFAULT_QUEUE_CAPACITY,
+    QUEUE_CAPACITY_ENV, REQUEST_TIMEOUT_ENV, RESPONSE_CHANNEL_CAPACITY,
+};
+#[cfg(feature = "distributed")]
+pub use distributed::{
+    distributed_queue_from_env, DistributedError, DistributedQueue, Payload, QueueRole,
+    QUEUE_NAME_ENV, QUEUE_ROLE_ENV, REDIS_CA_ENV, REDIS_URL_ENV, VISIBILITY_TIMEOUT_ENV,
+};
 pub use endpoint::Endpoint;
-pub use handler::{wait_for_requests, Handler};
-pub use metrics::InFlightStats;
+pub use handler::{
+    negotiate_protocol_version, wait_for_requests, Handler, HANDLER_PROTOCOL_VERSION,
+    MIN_HANDLER_PROTOCOL_VERSION,
+};
+#[cfg(feature = "jobs")]
+pub use jobs::{Job, JobCounts, JobError, JobRunner, JobStatus, JobStore, JOBS_DIR_ENV};
+pub use metrics::{
+    latency_statistics, InFlightStats, LatencyEstimate, LatencyStatistics, MIN_ESTIMATION_SAMPLES,
+};
+pub use preemption::{
+    preemption_from_env, Preemption, Timeslice, PREEMPTION_RUNNING_ENV, PREEMPTION_SLICE_ENV,
+};
+pub use readiness::{
+    readiness, ready_queue_threshold_from_env, LoopGuard, NotReady, Readiness, TaskStatus,
+    READY_QUEUE_THRESHOLD_ENV,
+};
+pub use shared::SharedRegistry;
+pub use tokio_util::sync::CancellationToken;
+#[cfg(feature = "usage")]
+pub use usage::{
+    usage_recorder_from_env, FileSink, StdoutSink, UsageError, UsageRecord, UsageRecorder,
+    UsageSink, WebhookSink, USAGE_SINK_ENV,
+};
+pub use workers::{workers_from_env, WorkerPool, WORKERS_ENV};
+#[cfg(feature = "metrics")]
+pub use metrics::{endpoint_metrics, EndpointMetrics};
 
 #[cfg(feature = "python")]
-use pyo3::PyErr;
+use pyo3::prelude::*;
+use std::time::Duration;
 use thiserror::Error;
 
 #[derive(Debug, Error)]
 pub enum Error {
+    #[error("Handler implements protocol version {found} but this runtime only supports versions {min} to {max}, please align the hfendpoints version with the one the handler was written for")]
+    IncompatibleHandler { found: u16, min: u16, max: u16 },
+
+    #[error("Handler declares __protocol_version__ = {found} which is not a protocol version, this runtime supports versions {min} to {max}")]
+    InvalidProtocolVersion { found: String, min: u16, max: u16 },
+
+    #[error("The handler is not running anymore, the request could not be scheduled")]
+    HandlerTerminated,
+
+    #[error("The request queue is full ({capacity} requests pending), retry later")]
+    QueueFull { capacity: usize },
+
+    #[error("The request did not complete within {after:?}")]
+    Timeout { after: Duration },
+
+    #[error("The handler completed the request without producing any response")]
+    NoResponse,
+
+    #[error("Task {task} is loaded after {dependency}, which is not loaded before it by the endpoint")]
+    UnknownDependency { task: String, dependency: String },
+
+    #[error("The handler raised {kind}: {message}")]
+    HandlerException {
+        kind: String,
+        message: String,
+        traceback: Option<String>,
+    },
+
+    #[cfg(feature = "distributed")]
+    #[error("Distributed queue failure: {0}")]
+    Distributed(#[from] DistributedError),
+
     #[cfg(feature = "python")]
     #[error("Caught error while executing Python code: {0}")]
     PythonError(#[from] PyErr),
 }
+
+#[cfg(feature = "python")]
+impl Error {
+    /// Capture the type, message and traceback of an exception raised by a Python handler
+    pub fn from_exception(err: PyErr) -> Self {
+        Python::with_gil(|py| {
+            let kind = err
+                .get_type(py)
+                .name()
+                .map(|name| name.to_string())
+                .unwrap_or_else(|_| String::from("Exception"));
+            let message = err
+                .value(py)
+                .str()
+                .map(|message| message.to_string())
+                .unwrap_or_default();
+            let traceback = err
+                .traceback(py)
+                .and_then(|traceback| traceback.format().ok());
+
+            Self::HandlerException {
+                kind,
+                message,
+                traceback,
+            }
+        })
+    }
+}
@@ -4,6 +4,18 @@ use std::sync::atomic::AtomicU32;
 #[cfg(feature = "python")]
 use pyo3::prelude::*;
 
+mod estimation;
+#[cfg(feature = "gpu")]
+mod gpu;
+#[cfg(feature = "metrics")]
+mod registry;
+
+pub use estimation::{
+    LatencyEstimate, LatencyStatistics, MIN_ESTIMATION_SAMPLES, latency_statistics,
+};
+#[cfg(feature = "metrics")]
+pub use registry::{EndpointMetrics, endpoint_metrics};
+
 #[cfg_attr(debug_assertions, derive(Debug))]
 #[cfg_attr(feature = "python", pyclass(frozen))]
 #[derive(Serialize)]
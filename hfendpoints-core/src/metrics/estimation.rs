@@ -0,0 +1,231 @@
+//! Rolling statistics relating the latency of the requests to the amount of work they carry
+//! (seconds of audio, tokens), from which the latency of upcoming requests is estimated.
+//!
+//! Each task keeps an exponentially decayed least-squares fit of `latency = overhead + rate * units`,
+//! so the estimates follow the recent behavior of the endpoint (load, batch sizes) rather than its
+//! whole history.
+//!
+//! The statistics also track the service time of the endpoint, the time between two requests
+//! leaving the queue whatever their task, from which the time a queue of requests takes to drain
+//! is derived.
+use std::collections::HashMap;
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, Instant};
+
+static LATENCY_STATISTICS: LazyLock<LatencyStatistics> = LazyLock::new(LatencyStatistics::default);
+
+/// Weight kept by past samples every time a request completes, about the last 50 requests matter
+const DECAY: f64 = 0.98;
+
+/// Samples required before estimating the latency of a task
+pub const MIN_ESTIMATION_SAMPLES: u64 = 3;
+
+/// Latency statistics of the endpoint running in this process
+#[inline]
+pub fn latency_statistics() -> &'static LatencyStatistics {
+    &LATENCY_STATISTICS
+}
+
+/// Decayed sums of the least-squares fit of the latency against the units of work
+#[derive(Clone, Copy, Default)]
+struct LatencyFit {
+    weight: f64,
+    units: f64,
+    latency: f64,
+    units_squared: f64,
+    units_latency: f64,
+    samples: u64,
+}
+
+impl LatencyFit {
+    fn record(&mut self, units: f64, latency: f64) {
+        self.weight = self.weight * DECAY + 1.0;
+        self.units = self.units * DECAY + units;
+        self.latency = self.latency * DECAY + latency;
+        self.units_squared = self.units_squared * DECAY + units * units;
+        self.units_latency = self.units_latency * DECAY + units * latency;
+        self.samples += 1;
+    }
+
+    /// Fixed overhead and rate per unit, in seconds
+    fn coefficients(&self) -> (f64, f64) {
+        let mean_units = self.units / self.weight;
+        let mean_latency = self.latency / self.weight;
+        let variance = self.units_squared / self.weight - mean_units * mean_units;
+
+        // Requests all carried the same amount of work, the latency is assumed proportional to it
+        if variance <= f64::EPSILON * mean_units.max(1.0) {
+            return if mean_units > 0.0 {
+                (0.0, mean_latency / mean_units)
+            } else {
+                (mean_latency, 0.0)
+            };
+        }
+
+        // More work cannot take less time, noise must not make the rate negative
+        let covariance = self.units_latency / self.weight - mean_units * mean_latency;
+        let rate = (covariance / variance).max(0.0);
+        ((mean_latency - rate * mean_units).max(0.0), rate)
+    }
+}
+
+/// Latency expected for a request, along with the statistics it derives from
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Clone, Copy)]
+pub struct LatencyEstimate {
+    /// Expected time between scheduling the request and receiving its response
+    pub latency: Duration,
+
+    /// Fixed part of the latency, whatever the amount of work
+    pub overhead: Duration,
+
+    /// Time taken by every unit of work
+    pub per_unit: Duration,
+
+    /// Number of requests the estimate derives from
+    pub samples: u64,
+}
+
+/// Decayed mean of the time between two completions
+#[derive(Default)]
+struct ServiceTime {
+    last: Option<Instant>,
+    mean: f64,
+    samples: u64,
+}
+
+impl ServiceTime {
+    /// A request completed at `now`, after `latency`
+    fn record(&mut self, now: Instant, latency: f64) {
+        // An idle endpoint completes requests far apart, the interval is bounded by the latency
+        // of the request so idle periods are not accounted as service time
+        if let Some(last) = self.last.replace(now) {
+            let interval = now
+                .saturating_duration_since(last)
+                .as_secs_f64()
+                .min(latency);
+            self.mean = if self.samples == 0 {
+                interval
+            } else {
+                self.mean * DECAY + interval * (1.0 - DECAY)
+            };
+            self.samples += 1;
+        }
+    }
+}
+
+/// Latency fits of every task served by the endpoint, keyed by task name
+#[derive(Default)]
+pub struct LatencyStatistics {
+    tasks: Mutex<HashMap<&'static str, LatencyFit>>,
+    service: Mutex<ServiceTime>,
+}
+
+impl LatencyStatistics {
+    /// A request of `task` carrying `units` of work completed after `latency`
+    pub fn record(&self, task: &'static str, units: f64, latency: Duration) {
+        if !units.is_finite() || units < 0.0 {
+            return;
+        }
+
+        let mut tasks = self.tasks.lock().unwrap_or_else(|err| err.into_inner());
+        tasks
+            .entry(task)
+            .or_default()
+            .record(units, latency.as_secs_f64());
+    }
+
+    /// A request left the queue, answered or cancelled, `latency` after being scheduled
+    pub(crate) fn record_completion(&self, latency: Duration) {
+        self.record_completion_at(Instant::now(), latency)
+    }
+
+    fn record_completion_at(&self, now: Instant, latency: Duration) {
+        let mut service = self.service.lock().unwrap_or_else(|err| err.into_inner());
+        service.record(now, latency.as_secs_f64());
+    }
+
+    /// Mean time between two requests leaving the queue while the endpoint is busy,
+    /// `None` until enough requests completed
+    pub fn service_time(&self) -> Option<Duration> {
+        let service = self.service.lock().unwrap_or_else(|err| err.into_inner());
+        (service.samples >= MIN_ESTIMATION_SAMPLES).then(|| Duration::from_secs_f64(service.mean))
+    }
+
+    /// Expected latency of a request of `task` carrying `units` of work,
+    /// `None` until enough requests of the task completed
+    pub fn estimate(&self, task: &str, units: f64) -> Option<LatencyEstimate> {
+        let fit = *self
+            .tasks
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .get(task)?;
+        if fit.samples < MIN_ESTIMATION_SAMPLES {
+            return None;
+        }
+
+        let (overhead, rate) = fit.coefficients();
+        Some(LatencyEstimate {
+            latency: Duration::from_secs_f64(overhead + rate * units.max(0.0)),
+            overhead: Duration::from_secs_f64(overhead),
+            per_unit: Duration::from_secs_f64(rate),
+            samples: fit.samples,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::metrics::estimation::LatencyStatistics;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn estimate_linear_latency() {
+        let statistics = LatencyStatistics::default();
+        assert!(statistics.estimate("transcription", 10.0).is_none());
+
+        // 200ms of overhead, 50ms per second of audio
+        for seconds in [5.0, 10.0, 20.0, 40.0] {
+            let latency = Duration::from_secs_f64(0.2 + 0.05 * seconds);
+            statistics.record("transcription", seconds, latency);
+        }
+
+        let estimate = statistics.estimate("transcription", 60.0).unwrap();
+        assert_eq!(estimate.samples, 4);
+        assert!((estimate.latency.as_secs_f64() - 3.2).abs() < 1e-6);
+        assert!((estimate.overhead.as_secs_f64() - 0.2).abs() < 1e-6);
+        assert!(statistics.estimate("embeddings", 60.0).is_none());
+    }
+
+    #[test]
+    fn estimate_constant_work() {
+        let statistics = LatencyStatistics::default();
+        for _ in 0..3 {
+            statistics.record("embeddings", 8.0, Duration::from_millis(40));
+        }
+
+        // Without variation of the work, the latency is assumed proportional to it
+        let estimate = statistics.estimate("embeddings", 16.0).unwrap();
+        assert!((estimate.latency.as_secs_f64() - 0.08).abs() < 1e-6);
+    }
+
+    #[test]
+    fn track_service_time() {
+        let statistics = LatencyStatistics::default();
+        let start = Instant::now();
+        let latency = Duration::from_millis(400);
+
+        // Four requests processed at once complete every 100ms
+        for completion in 0..4 {
+            let now = start + Duration::from_millis(100 * completion);
+            statistics.record_completion_at(now, latency);
+        }
+        let service_time = statistics.service_time().unwrap();
+        assert!((service_time.as_secs_f64() - 0.1).abs() < 1e-6);
+
+        // Idle periods are not accounted
+        let later = start + Duration::from_secs(60);
+        statistics.record_completion_at(later, latency);
+        assert!(statistics.service_time().unwrap() < Duration::from_millis(110));
+    }
+}
@@ -0,0 +1,275 @@
+//! GPU telemetry, refreshed every time the metrics are scraped.
+//!
+//! NVIDIA devices are read through NVML. On macOS, Apple GPUs are read from the performance
+//! statistics IOKit publishes for every `IOAccelerator`, which only cover utilization and memory:
+//! temperature and power are not available to unprivileged processes.
+//!
+//! Hosts without any supported device only log a warning at startup, the request metrics being
+//! exposed as usual.
+use nvml_wrapper::Nvml;
+use nvml_wrapper::enum_wrappers::device::TemperatureSensor;
+use prometheus::{GaugeVec, IntGaugeVec, Opts, Registry};
+use tracing::{debug, info, warn};
+
+/// Where the state of the devices is read from
+enum Backend {
+    Nvml(Box<Nvml>),
+    #[cfg(target_os = "macos")]
+    Metal,
+}
+
+pub(crate) struct GpuCollector {
+    backend: Backend,
+    utilization: GaugeVec,
+    memory_used: IntGaugeVec,
+    memory_total: IntGaugeVec,
+    temperature: IntGaugeVec,
+    power: GaugeVec,
+}
+
+impl GpuCollector {
+    /// Register the GPU metrics on `registry` if any device can be read on this host
+    pub(crate) fn register(registry: &Registry) -> Option<Self> {
+        let backend = Self::backend()?;
+
+        let labels = &["gpu"];
+        let collector = Self {
+            backend,
+            utilization: GaugeVec::new(
+                Opts::new("gpu_utilization_ratio", "Fraction of time the GPU was busy"),
+                labels,
+            )
+            .ok()?,
+            memory_used: IntGaugeVec::new(
+                Opts::new("gpu_memory_used_bytes", "GPU memory currently allocated"),
+                labels,
+            )
+            .ok()?,
+            memory_total: IntGaugeVec::new(
+                Opts::new("gpu_memory_total_bytes", "GPU memory installed"),
+                labels,
+            )
+            .ok()?,
+            temperature: IntGaugeVec::new(
+                Opts::new("gpu_temperature_celsius", "GPU die temperature"),
+                labels,
+            )
+            .ok()?,
+            power: GaugeVec::new(Opts::new("gpu_power_watts", "GPU power draw"), labels).ok()?,
+        };
+
+        registry
+            .register(Box::new(collector.utilization.clone()))
+            .ok()?;
+        registry
+            .register(Box::new(collector.memory_used.clone()))
+            .ok()?;
+        registry
+            .register(Box::new(collector.memory_total.clone()))
+            .ok()?;
+        registry
+            .register(Box::new(collector.temperature.clone()))
+            .ok()?;
+        registry.register(Box::new(collector.power.clone())).ok()?;
+
+        match &collector.backend {
+            Backend::Nvml(nvml) => info!(
+                "Collecting metrics of {} GPU(s) through NVML",
+                nvml.device_count().unwrap_or_default()
+            ),
+            #[cfg(target_os = "macos")]
+            Backend::Metal => info!("Collecting metrics of the Apple GPU(s) through IOKit"),
+        }
+        Some(collector)
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    fn backend() -> Option<Backend> {
+        Nvml::init()
+            .inspect_err(|err| warn!("GPU metrics disabled, failed to initialize NVML: {err}"))
+            .ok()
+            .map(|nvml| Backend::Nvml(Box::new(nvml)))
+    }
+
+    /// Macs with an NVIDIA device are rare enough for NVML to only be tried first
+    #[cfg(target_os = "macos")]
+    fn backend() -> Option<Backend> {
+        if let Ok(nvml) = Nvml::init() {
+            return Some(Backend::Nvml(Box::new(nvml)));
+        }
+
+        match metal::read_accelerators() {
+            Some(accelerators) if !accelerators.is_empty() => Some(Backend::Metal),
+            _ => {
+                warn!("GPU metrics disabled, no Apple GPU reports performance statistics");
+                None
+            }
+        }
+    }
+
+    /// Read the current state of every device, failures only skip the affected values
+    pub(crate) fn refresh(&self) {
+        match &self.backend {
+            Backend::Nvml(nvml) => self.refresh_nvml(nvml),
+            #[cfg(target_os = "macos")]
+            Backend::Metal => self.refresh_metal(),
+        }
+    }
+
+    fn refresh_nvml(&self, nvml: &Nvml) {
+        let count = nvml.device_count().unwrap_or_default();
+        for index in 0..count {
+            let Ok(device) = nvml.device_by_index(index) else {
+                debug!("Failed to access GPU {index}");
+                continue;
+            };
+
+            let label = index.to_string();
+            let labels = &[label.as_str()];
+
+            if let Ok(utilization) = device.utilization_rates() {
+                self.utilization
+                    .with_label_values(labels)
+                    .set(utilization.gpu as f64 / 100.0);
+            }
+
+            if let Ok(memory) = device.memory_info() {
+                self.memory_used
+                    .with_label_values(labels)
+                    .set(memory.used as i64);
+                self.memory_total
+                    .with_label_values(labels)
+                    .set(memory.total as i64);
+            }
+
+            if let Ok(temperature) = device.temperature(TemperatureSensor::Gpu) {
+                self.temperature
+                    .with_label_values(labels)
+                    .set(temperature as i64);
+            }
+
+            // Reported in milliwatts
+            if let Ok(power) = device.power_usage() {
+                self.power
+                    .with_label_values(labels)
+                    .set(power as f64 / 1000.0);
+            }
+        }
+    }
+
+    #[cfg(target_os = "macos")]
+    fn refresh_metal(&self) {
+        let Some(accelerators) = metal::read_accelerators() else {
+            debug!("Failed to read the performance statistics of the Apple GPU(s)");
+            return;
+        };
+
+        for (index, accelerator) in accelerators.iter().enumerate() {
+            let label = index.to_string();
+            let labels = &[label.as_str()];
+
+            if let Some(utilization) = accelerator.utilization {
+                self.utilization
+                    .with_label_values(labels)
+                    .set(utilization as f64 / 100.0);
+            }
+
+            if let Some(used) = accelerator.memory_used {
+                self.memory_used.with_label_values(labels).set(used as i64);
+            }
+
+            // Unified memory, the GPU can allocate as much as the system lets it to
+            if let Some(total) = accelerator.memory_allocated {
+                self.memory_total
+                    .with_label_values(labels)
+                    .set(total as i64);
+            }
+        }
+    }
+}
+
+/// Performance statistics of the Apple GPUs, as listed by `ioreg`
+#[cfg(any(target_os = "macos", test))]
+mod metal {
+    /// Utilization and memory of one `IOAccelerator`
+    #[derive(Debug, Default, PartialEq)]
+    pub(super) struct AcceleratorStats {
+        pub(super) utilization: Option<u64>,
+        pub(super) memory_used: Option<u64>,
+        pub(super) memory_allocated: Option<u64>,
+    }
+
+    /// Query IOKit for the statistics of every accelerator
+    #[cfg(target_os = "macos")]
+    pub(super) fn read_accelerators() -> Option<Vec<AcceleratorStats>> {
+        let output = std::process::Command::new("/usr/sbin/ioreg")
+            .args(["-r", "-d", "1", "-w", "0", "-c", "IOAccelerator"])
+            .output()
+            .ok()?;
+
+        output
+            .status
+            .success()
+            .then(|| parse_accelerators(&String::from_utf8_lossy(&output.stdout)))
+    }
+
+    /// Extract the `PerformanceStatistics` dictionary of every accelerator listed by `ioreg`
+    pub(super) fn parse_accelerators(listing: &str) -> Vec<AcceleratorStats> {
+        listing
+            .lines()
+            .filter_map(|line| {
+                let (key, value) = line.trim().split_once('=')?;
+                (key.trim() == "\"PerformanceStatistics\"").then_some(value)
+            })
+            .map(|statistics| AcceleratorStats {
+                utilization: statistic(statistics, "Device Utilization %"),
+                memory_used: statistic(statistics, "In use system memory"),
+                memory_allocated: statistic(statistics, "Alloc system memory"),
+            })
+            .collect()
+    }
+
+    /// Integer value of `name` in a `{"key"=value,...}` dictionary
+    fn statistic(statistics: &str, name: &str) -> Option<u64> {
+        let key = format!("\"{name}\"=");
+        let start = statistics.find(&key)? + key.len();
+        let value = &statistics[start..];
+        let end = value
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(value.len());
+        value[..end].parse().ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::metal::{AcceleratorStats, parse_accelerators};
+
+    #[test]
+    fn parse_apple_gpu_statistics() {
+        let listing = r#"+-o AGXAcceleratorG13X  <class AGXAcceleratorG13X, id 0x1000003b5, registered, matched, active, busy 0 (0 ms), retain 78>
+    {
+      "model" = "Apple M1 Max"
+      "PerformanceStatistics" = {"In use system memory"=1317142528,"Tiler Utilization %"=4,"Alloc system memory"=6227591168,"Device Utilization %"=17,"Renderer Utilization %"=16}
+      "gpu-core-count" = 32
+    }
++-o AGXAcceleratorG14G  <class AGXAcceleratorG14G>
+    {
+      "PerformanceStatistics" = {"Tiler Utilization %"=0}
+    }
+"#;
+
+        assert_eq!(
+            parse_accelerators(listing),
+            vec![
+                AcceleratorStats {
+                    utilization: Some(17),
+                    memory_used: Some(1317142528),
+                    memory_allocated: Some(6227591168),
+                },
+                AcceleratorStats::default(),
+            ]
+        );
+        assert!(parse_accelerators("").is_empty());
+    }
+}
@@ -0,0 +1,202 @@
+//! Prometheus metrics describing the activity of the endpoint.
+//!
+//! Requests are counted when scheduled by the transport, stay in the queue until the looper picks
+//! them and are in-flight while the handler processes them. The registry is process-wide so every
+//! endpoint served by the process reports through the same `/metrics` route.
+use prometheus::{
+    Encoder, Histogram, HistogramOpts, HistogramTimer, IntCounter, IntCounterVec, IntGauge,
+    IntGaugeVec, Opts, Registry, TextEncoder, exponential_buckets,
+};
+use std::sync::LazyLock;
+use tracing::error;
+
+static ENDPOINT_METRICS: LazyLock<EndpointMetrics> = LazyLock::new(EndpointMetrics::new);
+
+/// Metrics of the endpoint running in this process
+#[inline]
+pub fn endpoint_metrics() -> &'static EndpointMetrics {
+    &ENDPOINT_METRICS
+}
+
+/// Registry holding every metric exposed by the endpoint
+pub struct EndpointMetrics {
+    registry: Registry,
+    requests: IntCounter,
+    in_flight: IntGauge,
+    queue_depth: IntGauge,
+    handler_latency: Histogram,
+    audio_bytes: IntCounter,
+    worker_requests: IntCounterVec,
+    worker_busy: IntGaugeVec,
+
+    #[cfg(feature = "gpu")]
+    gpu: Option<super::gpu::GpuCollector>,
+}
+
+impl EndpointMetrics {
+    fn new() -> Self {
+        let registry = Registry::new_custom(Some(String::from("hfendpoints")), None)
+            .expect("Metrics prefix is valid");
+
+        let requests = IntCounter::new("requests_total", "Requests scheduled on the handler")
+            .expect("Metric is valid");
+        let in_flight = IntGauge::new(
+            "requests_in_flight",
+            "Requests currently processed by the handler",
+        )
+        .expect("Metric is valid");
+        let queue_depth = IntGauge::new(
+            "queue_depth",
+            "Requests waiting to be picked by the handler",
+        )
+        .expect("Metric is valid");
+        let handler_latency = Histogram::with_opts(
+            HistogramOpts::new(
+                "handler_duration_seconds",
+                "Time spent by the handler processing a request",
+            )
+            .buckets(exponential_buckets(0.005, 2.0, 14).expect("Buckets are valid")),
+        )
+        .expect("Metric is valid");
+        let audio_bytes = IntCounter::new(
+            "audio_received_bytes_total",
+            "Bytes of audio received, once decompressed",
+        )
+        .expect("Metric is valid");
+
+        let worker_requests = IntCounterVec::new(
+            Opts::new(
+                "worker_requests_total",
+                "Requests processed by each worker of the pool",
+            ),
+            &["worker"],
+        )
+        .expect("Metric is valid");
+        let worker_busy = IntGaugeVec::new(
+            Opts::new(
+                "worker_busy",
+                "Whether each worker of the pool is processing a request",
+            ),
+            &["worker"],
+        )
+        .expect("Metric is valid");
+
+        for metric in [
+            Box::new(requests.clone()) as Box<dyn prometheus::core::Collector>,
+            Box::new(in_flight.clone()),
+            Box::new(queue_depth.clone()),
+            Box::new(handler_latency.clone()),
+            Box::new(audio_bytes.clone()),
+            Box::new(worker_requests.clone()),
+            Box::new(worker_busy.clone()),
+        ] {
+            registry.register(metric).expect("Metrics are unique");
+        }
+
+        #[cfg(feature = "gpu")]
+        let gpu = super::gpu::GpuCollector::register(&registry);
+
+        Self {
+            registry,
+            requests,
+            in_flight,
+            queue_depth,
+            handler_latency,
+            audio_bytes,
+            worker_requests,
+            worker_busy,
+            #[cfg(feature = "gpu")]
+            gpu,
+        }
+    }
+
+    /// A request was sent to the handler and waits to be processed
+    pub fn on_scheduled(&self) {
+        self.requests.inc();
+        self.queue_depth.inc();
+    }
+
+    /// The looper picked a request, tracked as in-flight until the returned guard is dropped
+    pub(crate) fn on_dequeued(&self) -> InFlightGuard {
+        self.queue_depth.dec();
+        self.in_flight.inc();
+        InFlightGuard {
+            in_flight: self.in_flight.clone(),
+            busy: None,
+            _timer: self.handler_latency.start_timer(),
+        }
+    }
+
+    /// Same as `on_dequeued` for a request picked by a worker of the pool, also tracked per worker
+    pub(crate) fn on_dequeued_by(&self, worker: &str) -> InFlightGuard {
+        self.worker_requests.with_label_values(&[worker]).inc();
+        let busy = self.worker_busy.with_label_values(&[worker]);
+        busy.inc();
+
+        let mut guard = self.on_dequeued();
+        guard.busy = Some(busy);
+        guard
+    }
+
+    /// Account for `size` bytes of audio received by the transport
+    pub fn record_audio_bytes(&self, size: usize) {
+        self.audio_bytes.inc_by(size as u64);
+    }
+
+    /// Render every metric in the Prometheus text exposition format
+    pub fn encode(&self) -> String {
+        #[cfg(feature = "gpu")]
+        if let Some(gpu) = &self.gpu {
+            gpu.refresh();
+        }
+
+        let mut buffer = Vec::new();
+        if let Err(err) = TextEncoder::new().encode(&self.registry.gather(), &mut buffer) {
+            error!("Failed to encode metrics: {err}");
+        }
+
+        String::from_utf8(buffer).unwrap_or_default()
+    }
+}
+
+/// Track a request while the handler processes it, recording its latency once dropped
+pub(crate) struct InFlightGuard {
+    in_flight: IntGauge,
+    busy: Option<IntGauge>,
+    _timer: HistogramTimer,
+}
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        self.in_flight.dec();
+        if let Some(busy) = &self.busy {
+            busy.dec();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::metrics::registry::EndpointMetrics;
+
+    #[test]
+    fn track_request_lifecycle() {
+        let metrics = EndpointMetrics::new();
+
+        metrics.on_scheduled();
+        metrics.on_scheduled();
+        assert_eq!(metrics.queue_depth.get(), 2);
+
+        let guard = metrics.on_dequeued();
+        assert_eq!((metrics.queue_depth.get(), metrics.in_flight.get()), (1, 1));
+
+        drop(guard);
+        assert_eq!(metrics.in_flight.get(), 0);
+        assert_eq!(metrics.handler_latency.get_sample_count(), 1);
+
+        metrics.record_audio_bytes(1024);
+        let encoded = metrics.encode();
+        assert!(encoded.contains("hfendpoints_requests_total 2"));
+        assert!(encoded.contains("hfendpoints_audio_received_bytes_total 1024"));
+    }
+}
@@ -0,0 +1,188 @@
+//! Cooperative preemption of long-running requests.
+//!
+//! When enabled, at most `running` requests are processed at once, each of them holding a turn.
+//! Handlers producing long generations call [`Timeslice::checkpoint`] regularly, i.e. between two
+//! tokens: once a request held its turn for longer than the time slice while other requests wait
+//! for one, the turn is handed over and the generation resumes as soon as it gets a turn back.
+//! Turns are granted in order of arrival, so short interactive requests get to run in between.
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, OnceLock, PoisonError};
+use std::time::Duration;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tokio::time::Instant;
+use tracing::{debug, warn};
+
+/// Environment variable holding the number of seconds a request may run before being preempted
+pub const PREEMPTION_SLICE_ENV: &str = "HFENDPOINT_PREEMPTION_SLICE";
+
+/// Environment variable holding the number of requests running at once when preemption is enabled
+pub const PREEMPTION_RUNNING_ENV: &str = "HFENDPOINT_PREEMPTION_RUNNING";
+
+/// Hand over turns to run requests, preempting the ones exceeding their time slice
+pub struct Preemption {
+    turns: Arc<Semaphore>,
+    waiting: AtomicUsize,
+    slice: Duration,
+}
+
+/// Account for a request waiting for a turn, until dropped
+struct Waiting<'a>(&'a AtomicUsize);
+
+impl Drop for Waiting<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl Preemption {
+    /// Run at most `running` requests at once, preempting them after `slice` when others are waiting
+    pub fn new(running: usize, slice: Duration) -> Self {
+        Self {
+            turns: Arc::new(Semaphore::new(running.max(1))),
+            waiting: AtomicUsize::new(0),
+            slice,
+        }
+    }
+
+    /// Time a request may hold its turn while other requests are waiting for one
+    pub fn slice(&self) -> Duration {
+        self.slice
+    }
+
+    /// Number of requests waiting for a turn
+    pub fn waiting(&self) -> usize {
+        self.waiting.load(Ordering::SeqCst)
+    }
+
+    async fn turn(&self) -> OwnedSemaphorePermit {
+        self.waiting.fetch_add(1, Ordering::SeqCst);
+        let _waiting = Waiting(&self.waiting);
+        Arc::clone(&self.turns)
+            .acquire_owned()
+            .await
+            .expect("Turns semaphore is never closed")
+    }
+}
+
+/// Preemption defined through `HFENDPOINT_PREEMPTION_SLICE` and `HFENDPOINT_PREEMPTION_RUNNING`,
+/// shared by every endpoint of the process. Requests are never preempted when the slice is not set.
+pub fn preemption_from_env() -> Option<Arc<Preemption>> {
+    static PREEMPTION: OnceLock<Option<Arc<Preemption>>> = OnceLock::new();
+    PREEMPTION
+        .get_or_init(|| {
+            let slice = std::env::var(PREEMPTION_SLICE_ENV).ok()?;
+            let slice = match slice.parse::<f64>().map(Duration::try_from_secs_f64) {
+                Ok(Ok(slice)) if !slice.is_zero() => slice,
+                _ => {
+                    warn!("Ignoring malformed {PREEMPTION_SLICE_ENV} ({slice}), expected a positive number of seconds");
+                    return None;
+                }
+            };
+
+            let running = match std::env::var(PREEMPTION_RUNNING_ENV) {
+                Ok(running) => match running.parse::<usize>() {
+                    Ok(running) if running > 0 => running,
+                    _ => {
+                        warn!("Ignoring malformed {PREEMPTION_RUNNING_ENV} ({running}), expected a positive integer");
+                        1
+                    }
+                },
+                Err(_) => 1,
+            };
+
+            Some(Arc::new(Preemption::new(running, slice)))
+        })
+        .clone()
+}
+
+struct Turn {
+    preemption: Arc<Preemption>,
+    held: Mutex<(Option<OwnedSemaphorePermit>, Instant)>,
+}
+
+/// Turn of a request to run, released once every clone is dropped.
+///
+/// The default `Timeslice` is not subject to preemption, [`Timeslice::checkpoint`] returns immediately.
+#[derive(Clone, Default)]
+pub struct Timeslice(Option<Arc<Turn>>);
+
+impl Timeslice {
+    /// Wait for a turn to run granted by `preemption`
+    pub async fn acquire(preemption: Arc<Preemption>) -> Self {
+        let permit = preemption.turn().await;
+        Self(Some(Arc::new(Turn {
+            preemption,
+            held: Mutex::new((Some(permit), Instant::now())),
+        })))
+    }
+
+    /// Hand over the turn if it was held for longer than the time slice while other requests
+    /// are waiting, resolving once a turn is granted back. Returns whether the request was preempted.
+    pub async fn checkpoint(&self) -> bool {
+        let Some(turn) = &self.0 else {
+            return false;
+        };
+
+        {
+            let mut held = turn.held.lock().unwrap_or_else(PoisonError::into_inner);
+            if held.1.elapsed() < turn.preemption.slice() || turn.preemption.waiting() == 0 {
+                return false;
+            }
+
+            debug!("Preempting request after {:?}", held.1.elapsed());
+            held.0.take();
+        }
+
+        let permit = turn.preemption.turn().await;
+        *turn.held.lock().unwrap_or_else(PoisonError::into_inner) = (Some(permit), Instant::now());
+        debug!("Resuming preempted request");
+        true
+    }
+}
+
+#[cfg(debug_assertions)]
+impl std::fmt::Debug for Timeslice {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("Timeslice").field(&self.0.is_some()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::preemption::{Preemption, Timeslice};
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tokio::sync::mpsc::unbounded_channel;
+
+    #[tokio::test]
+    async fn long_requests_yield_to_waiting_ones() {
+        let preemption = Arc::new(Preemption::new(1, Duration::from_millis(10)));
+        let (events, mut trace) = unbounded_channel();
+
+        // Nobody else is waiting, the long request keeps its turn
+        let long = Timeslice::acquire(Arc::clone(&preemption)).await;
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(!long.checkpoint().await);
+
+        let short = tokio::spawn({
+            let (preemption, events) = (Arc::clone(&preemption), events.clone());
+            async move {
+                let _turn = Timeslice::acquire(preemption).await;
+                events.send("short").unwrap();
+            }
+        });
+
+        while preemption.waiting() == 0 {
+            tokio::task::yield_now().await;
+        }
+        assert!(long.checkpoint().await);
+        events.send("long").unwrap();
+
+        short.await.unwrap();
+        assert_eq!(trace.recv().await, Some("short"));
+        assert_eq!(trace.recv().await, Some("long"));
+
+        // The slice restarts once resumed
+        assert!(!long.checkpoint().await);
+    }
+}
@@ -0,0 +1,343 @@
+//! Readiness of the endpoint to process requests, as reported to the orchestrator probes.
+//!
+//! The endpoint is live as long as its process serves, but only ready once the endpoint
+//! reported its handler initialized (see [`Endpoint::on_handler_initialized`]), and as long as
+//! the handler loop runs. When `HFENDPOINT_READY_QUEUE_THRESHOLD` is set, the endpoint also stops
+//! being ready while more requests than the threshold wait in the queue, so the load balancer
+//! routes new clients to other replicas until the backlog is absorbed.
+//!
+//! Endpoints loading the handlers of several tasks on their own report the progress of each of
+//! them, the endpoint only being ready once every task is.
+//!
+//! [`Endpoint::on_handler_initialized`]: crate::Endpoint::on_handler_initialized
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, LazyLock, Mutex, PoisonError};
+use thiserror::Error;
+use tracing::{error, info, warn};
+
+/// Environment variable holding the number of queued requests above which the endpoint is not ready
+pub const READY_QUEUE_THRESHOLD_ENV: &str = "HFENDPOINT_READY_QUEUE_THRESHOLD";
+
+/// Threshold defined through `HFENDPOINT_READY_QUEUE_THRESHOLD`, the queue never affecting the
+/// readiness when not set
+pub fn ready_queue_threshold_from_env() -> Option<usize> {
+    let threshold = std::env::var(READY_QUEUE_THRESHOLD_ENV).ok()?;
+    match threshold.parse::<usize>() {
+        Ok(threshold) if threshold > 0 => Some(threshold),
+        _ => {
+            warn!(
+                "Ignoring malformed {READY_QUEUE_THRESHOLD_ENV} ({threshold}), expected a positive integer"
+            );
+            None
+        }
+    }
+}
+
+/// Why the endpoint is not ready to process requests
+#[derive(Clone, Debug, Error, PartialEq)]
+pub enum NotReady {
+    #[error("The handler is still initializing")]
+    Initializing,
+
+    #[error("The handler loop is not running anymore: {reason}")]
+    HandlerStopped { reason: String },
+
+    #[error("{queued} requests are waiting in the queue, over the threshold of {threshold}")]
+    Overloaded { queued: usize, threshold: usize },
+
+    #[error("Tasks still loading their handler: {}", tasks.join(", "))]
+    TasksLoading { tasks: Vec<String> },
+
+    #[error("Task {task} failed to load its handler: {reason}")]
+    TaskFailed { task: String, reason: String },
+}
+
+/// Progress of a task loading its handler, see [`Readiness::task_loading`]
+#[derive(Clone, Debug, PartialEq)]
+pub enum TaskStatus {
+    /// The handler, i.e. its model, is being loaded
+    Loading,
+
+    /// The handler is loaded and processes the requests of the task
+    Ready,
+
+    /// The handler, or one of the tasks it is loaded after, failed to load
+    Failed { reason: String },
+}
+
+/// Lifecycle of the handler along with the depth of the request queue
+pub struct Readiness {
+    /// The endpoint reported the handler initialized
+    initialized: AtomicBool,
+
+    /// Why a handler loop stopped, if any did
+    stopped: Mutex<Option<String>>,
+
+    /// A handler loop crashed, the process not being able to recover
+    crashed: AtomicBool,
+
+    /// Requests scheduled but not yet picked by the handler
+    queued: AtomicUsize,
+
+    /// Queued requests above which the endpoint is not ready
+    threshold: Option<usize>,
+
+    /// Tasks loading their handler, in the order they were registered
+    tasks: Mutex<Vec<(String, TaskStatus)>>,
+}
+
+/// Readiness of the endpoint run by this process
+static READINESS: LazyLock<Arc<Readiness>> =
+    LazyLock::new(|| Arc::new(Readiness::new(ready_queue_threshold_from_env())));
+
+/// Readiness of the endpoint run by this process, updated by the request loops
+pub fn readiness() -> Arc<Readiness> {
+    Arc::clone(&READINESS)
+}
+
+impl Readiness {
+    /// Readiness of a handler not initialized yet, not ready while more than `threshold`
+    /// requests are queued
+    pub fn new(threshold: Option<usize>) -> Self {
+        Self {
+            initialized: AtomicBool::new(false),
+            stopped: Mutex::new(None),
+            crashed: AtomicBool::new(false),
+            queued: AtomicUsize::new(0),
+            threshold,
+            tasks: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Flag the handler as initialized, the endpoint being ready from now on
+    pub fn handler_initialized(&self) {
+        if !self.initialized.swap(true, Ordering::SeqCst) {
+            info!("Handler initialized, endpoint is ready");
+        }
+    }
+
+    /// Flag `task` as loading its handler, the endpoint not being ready until it is loaded
+    pub fn task_loading(&self, task: &str) {
+        self.set_task(task, TaskStatus::Loading);
+    }
+
+    /// Flag the handler of `task` as loaded
+    pub fn task_ready(&self, task: &str) {
+        info!("Task {task} loaded");
+        self.set_task(task, TaskStatus::Ready);
+    }
+
+    /// Flag the handler of `task` as failed to load, for `reason`
+    pub fn task_failed(&self, task: &str, reason: impl Into<String>) {
+        let reason = reason.into();
+        error!("Task {task} failed to load: {reason}");
+        self.set_task(task, TaskStatus::Failed { reason });
+    }
+
+    fn set_task(&self, task: &str, status: TaskStatus) {
+        let mut tasks = self.tasks.lock().unwrap_or_else(PoisonError::into_inner);
+        match tasks.iter_mut().find(|(name, _)| name == task) {
+            Some((_, current)) => *current = status,
+            None => tasks.push((String::from(task), status)),
+        }
+    }
+
+    /// Progress of every task loading its handler, in the order they were registered
+    pub fn tasks(&self) -> Vec<(String, TaskStatus)> {
+        self.tasks
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    /// Track a handler loop, the endpoint not being ready anymore once the returned guard is
+    /// dropped, i.e. when the loop exits or panics
+    pub fn track_loop(self: &Arc<Self>) -> LoopGuard {
+        LoopGuard {
+            readiness: Arc::clone(self),
+            exited: false,
+        }
+    }
+
+    /// A request was scheduled on the handler
+    pub fn on_scheduled(&self) {
+        self.queued.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// A request was picked from the queue
+    pub fn on_dequeued(&self) {
+        // Requests queued before being tracked are not counted, the depth never wraps around
+        let _ = self
+            .queued
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |queued| {
+                queued.checked_sub(1)
+            });
+    }
+
+    /// Number of requests waiting in the queue
+    pub fn queued(&self) -> usize {
+        self.queued.load(Ordering::Relaxed)
+    }
+
+    /// Whether the process is still able to serve, i.e. no handler loop crashed
+    pub fn is_live(&self) -> bool {
+        !self.crashed.load(Ordering::SeqCst)
+    }
+
+    /// Check the endpoint is able to process requests, reporting why otherwise
+    pub fn check(&self) -> Result<(), NotReady> {
+        if let Some(reason) = self
+            .stopped
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .as_ref()
+        {
+            return Err(NotReady::HandlerStopped {
+                reason: reason.clone(),
+            });
+        }
+
+        let mut loading = Vec::new();
+        for (task, status) in self.tasks() {
+            match status {
+                TaskStatus::Failed { reason } => return Err(NotReady::TaskFailed { task, reason }),
+                TaskStatus::Loading => loading.push(task),
+                TaskStatus::Ready => {}
+            }
+        }
+        if !loading.is_empty() {
+            return Err(NotReady::TasksLoading { tasks: loading });
+        }
+
+        if !self.initialized.load(Ordering::SeqCst) {
+            return Err(NotReady::Initializing);
+        }
+
+        let queued = self.queued();
+        match self.threshold {
+            Some(threshold) if queued > threshold => {
+                Err(NotReady::Overloaded { queued, threshold })
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Held by a running handler loop, see [`Readiness::track_loop`].
+///
+/// Loops dropping their guard without calling [`LoopGuard::exit`] are considered crashed, whether
+/// they panicked or their task was aborted, as the panics of tokio tasks are caught outside of them.
+#[must_use = "the handler loop is considered crashed once its guard is dropped"]
+pub struct LoopGuard {
+    readiness: Arc<Readiness>,
+    exited: bool,
+}
+
+impl LoopGuard {
+    /// The loop exited on its own, i.e. the transport went away on shutdown
+    pub fn exit(mut self) {
+        self.exited = true;
+    }
+}
+
+impl Drop for LoopGuard {
+    fn drop(&mut self) {
+        let reason = if self.exited {
+            "the handler loop exited"
+        } else {
+            error!("Handler loop crashed, endpoint is not ready anymore");
+            self.readiness.crashed.store(true, Ordering::SeqCst);
+            "the handler loop crashed"
+        };
+
+        self.readiness
+            .stopped
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get_or_insert_with(|| String::from(reason));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::readiness::{NotReady, Readiness, TaskStatus};
+    use std::sync::Arc;
+
+    #[test]
+    fn ready_once_initialized_and_under_threshold() {
+        let readiness = Arc::new(Readiness::new(Some(1)));
+        let _loop = readiness.track_loop();
+        assert_eq!(readiness.check(), Err(NotReady::Initializing));
+
+        readiness.handler_initialized();
+        assert_eq!(readiness.check(), Ok(()));
+
+        readiness.on_scheduled();
+        readiness.on_scheduled();
+        assert_eq!(
+            readiness.check(),
+            Err(NotReady::Overloaded {
+                queued: 2,
+                threshold: 1
+            })
+        );
+
+        readiness.on_dequeued();
+        assert_eq!(readiness.check(), Ok(()));
+    }
+
+    #[test]
+    fn not_ready_once_the_loop_stops() {
+        let readiness = Arc::new(Readiness::new(None));
+        readiness.handler_initialized();
+
+        readiness.track_loop().exit();
+        assert!(matches!(
+            readiness.check(),
+            Err(NotReady::HandlerStopped { .. })
+        ));
+        assert!(readiness.is_live());
+
+        let tracked = Arc::clone(&readiness);
+        let panicked = std::thread::spawn(move || {
+            let _loop = tracked.track_loop();
+            panic!("inference failed");
+        });
+        assert!(panicked.join().is_err());
+        assert!(!readiness.is_live());
+    }
+
+    #[test]
+    fn ready_once_every_task_is_loaded() {
+        let readiness = Readiness::new(None);
+        readiness.handler_initialized();
+        readiness.task_loading("transcription");
+        readiness.task_loading("translation");
+        assert_eq!(
+            readiness.check(),
+            Err(NotReady::TasksLoading {
+                tasks: vec![String::from("transcription"), String::from("translation")]
+            })
+        );
+
+        readiness.task_ready("transcription");
+        readiness.task_ready("translation");
+        assert_eq!(readiness.check(), Ok(()));
+        assert_eq!(
+            readiness.tasks(),
+            vec![
+                (String::from("transcription"), TaskStatus::Ready),
+                (String::from("translation"), TaskStatus::Ready),
+            ]
+        );
+
+        readiness.task_failed("translation", "out of memory");
+        assert_eq!(
+            readiness.check(),
+            Err(NotReady::TaskFailed {
+                task: String::from("translation"),
+                reason: String::from("out of memory")
+            })
+        );
+    }
+}
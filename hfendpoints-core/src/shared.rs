@@ -0,0 +1,317 @@
+//! Process-wide objects shared by every invocation of the handler.
+//!
+//! Heavy resources (model weights, tokenizers, ...) should be loaded once per process rather than
+//! once per worker. Objects are registered under a name and initialized at most once: concurrent
+//! callers asking for an object being initialized wait for the outcome instead of loading it again.
+//! A failed initialization leaves the slot empty so the next caller gets to retry.
+use std::collections::HashMap;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError};
+use std::thread::{self, ThreadId};
+use thiserror::Error;
+
+/// The initializer of a shared object requested that same object, which would never complete
+#[derive(Debug, Error)]
+#[error("Shared object '{0}' was requested while being initialized by the same thread")]
+pub struct ReentrantInitialization(String);
+
+enum State<T> {
+    Empty,
+    Initializing(ThreadId),
+    Ready(T),
+}
+
+enum Claim<'a, T> {
+    Ready(T),
+    Owner(Initializer<'a, T>),
+    Busy(ThreadId),
+}
+
+/// Hold a single shared object and coordinate its initialization
+struct Slot<T> {
+    state: Mutex<State<T>>,
+    settled: Condvar,
+}
+
+impl<T: Clone> Slot<T> {
+    fn new() -> Self {
+        Self {
+            state: Mutex::new(State::Empty),
+            settled: Condvar::new(),
+        }
+    }
+
+    #[inline]
+    fn lock(&self) -> MutexGuard<'_, State<T>> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Retrieve the object, if already initialized
+    fn peek(&self) -> Option<T> {
+        match &*self.lock() {
+            State::Ready(value) => Some(value.clone()),
+            _ => None,
+        }
+    }
+
+    /// Retrieve the object, or the right to initialize it when nobody else is doing so
+    fn claim(&self) -> Claim<'_, T> {
+        let mut state = self.lock();
+        match &*state {
+            State::Ready(value) => Claim::Ready(value.clone()),
+            State::Initializing(owner) => Claim::Busy(*owner),
+            State::Empty => {
+                *state = State::Initializing(thread::current().id());
+                Claim::Owner(Initializer {
+                    slot: self,
+                    completed: false,
+                })
+            }
+        }
+    }
+
+    /// Block until the ongoing initialization completes, successfully or not
+    fn wait(&self) {
+        let state = self.lock();
+        drop(
+            self.settled
+                .wait_while(state, |state| matches!(state, State::Initializing(_)))
+                .unwrap_or_else(PoisonError::into_inner),
+        );
+    }
+}
+
+/// Right to initialize a slot, emptied again if dropped before completing
+struct Initializer<'a, T> {
+    slot: &'a Slot<T>,
+    completed: bool,
+}
+
+impl<T> Initializer<'_, T> {
+    fn complete(mut self, value: T) {
+        *self
+            .slot
+            .state
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) = State::Ready(value);
+        self.completed = true;
+        self.slot.settled.notify_all();
+    }
+}
+
+impl<T> Drop for Initializer<'_, T> {
+    fn drop(&mut self) {
+        if !self.completed {
+            *self
+                .slot
+                .state
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner) = State::Empty;
+            self.slot.settled.notify_all();
+        }
+    }
+}
+
+/// Registry of named objects shared by every invocation of the handler
+pub struct SharedRegistry<T> {
+    slots: Mutex<HashMap<String, Arc<Slot<T>>>>,
+}
+
+impl<T> Default for SharedRegistry<T> {
+    fn default() -> Self {
+        Self {
+            slots: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl<T: Clone + Send> SharedRegistry<T> {
+    fn slot(&self, name: &str) -> Arc<Slot<T>> {
+        let mut slots = self.slots.lock().unwrap_or_else(PoisonError::into_inner);
+        Arc::clone(
+            slots
+                .entry(name.to_string())
+                .or_insert_with(|| Arc::new(Slot::new())),
+        )
+    }
+
+    /// Retrieve the object registered as `name`, if already initialized
+    pub fn get(&self, name: &str) -> Option<T> {
+        self.slot(name).peek()
+    }
+
+    /// Register `value` as `name`, returns `false` if an object is already registered under this name
+    pub fn register(&self, name: &str, value: T) -> bool {
+        match self.slot(name).claim() {
+            Claim::Owner(initializer) => {
+                initializer.complete(value);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Retrieve the object registered as `name`, calling `init` to create it if needed.
+    ///
+    /// `init` is called by a single caller at a time, others block until it completes.
+    pub fn get_or_try_init<E>(
+        &self,
+        name: &str,
+        init: impl FnOnce() -> Result<T, E>,
+    ) -> Result<T, E>
+    where
+        E: From<ReentrantInitialization>,
+    {
+        self.get_or_try_init_with(name, |wait| wait(), init)
+    }
+
+    /// Same as [`SharedRegistry::get_or_try_init`], waiting for other initializers through `block_on`,
+    /// i.e. to release the Python GIL while blocked
+    pub fn get_or_try_init_with<E>(
+        &self,
+        name: &str,
+        mut block_on: impl FnMut(&(dyn Fn() + Sync)),
+        init: impl FnOnce() -> Result<T, E>,
+    ) -> Result<T, E>
+    where
+        E: From<ReentrantInitialization>,
+    {
+        let slot = self.slot(name);
+        loop {
+            match slot.claim() {
+                Claim::Ready(value) => return Ok(value),
+                Claim::Owner(initializer) => {
+                    let value = init()?;
+                    initializer.complete(value.clone());
+                    return Ok(value);
+                }
+                Claim::Busy(owner) if owner == thread::current().id() => {
+                    return Err(ReentrantInitialization(name.to_string()).into());
+                }
+                Claim::Busy(_) => block_on(&|| slot.wait()),
+            }
+        }
+    }
+}
+
+#[cfg(feature = "python")]
+pub mod python {
+    use crate::shared::{ReentrantInitialization, SharedRegistry};
+    use hfendpoints_binding_python::ImportablePyModuleBuilder;
+    use pyo3::exceptions::{PyKeyError, PyRuntimeError, PyValueError};
+    use pyo3::prelude::*;
+    use std::sync::{Arc, LazyLock};
+
+    static SHARED: LazyLock<SharedRegistry<Arc<PyObject>>> = LazyLock::new(SharedRegistry::default);
+
+    impl From<ReentrantInitialization> for PyErr {
+        fn from(value: ReentrantInitialization) -> Self {
+            PyRuntimeError::new_err(value.to_string())
+        }
+    }
+
+    /// Retrieve the object shared as `name`, calling `factory()` to create it on first use
+    #[pyfunction]
+    fn get_or_init(py: Python<'_>, name: &str, factory: Bound<'_, PyAny>) -> PyResult<PyObject> {
+        let value = SHARED.get_or_try_init_with(
+            name,
+            |wait| py.allow_threads(wait),
+            || Ok::<_, PyErr>(Arc::new(factory.call0()?.unbind())),
+        )?;
+        Ok(value.clone_ref(py))
+    }
+
+    /// Share `value` as `name`, raising `ValueError` if the name is already taken
+    #[pyfunction]
+    fn register(name: &str, value: PyObject) -> PyResult<()> {
+        if SHARED.register(name, Arc::new(value)) {
+            Ok(())
+        } else {
+            Err(PyValueError::new_err(format!(
+                "A shared object is already registered as '{name}'"
+            )))
+        }
+    }
+
+    /// Retrieve the object shared as `name`, raising `KeyError` if not registered
+    #[pyfunction]
+    fn get(py: Python<'_>, name: &str) -> PyResult<PyObject> {
+        SHARED
+            .get(name)
+            .map(|value| value.clone_ref(py))
+            .ok_or_else(|| PyKeyError::new_err(name.to_string()))
+    }
+
+    /// Bind hfendpoints.shared submodule into the exported Python wheel
+    pub fn bind<'py>(py: Python<'py>, name: &str) -> PyResult<Bound<'py, PyModule>> {
+        let module = ImportablePyModuleBuilder::new(py, name)?
+            .defaults()?
+            .finish();
+
+        module.add_function(wrap_pyfunction!(get_or_init, &module)?)?;
+        module.add_function(wrap_pyfunction!(register, &module)?)?;
+        module.add_function(wrap_pyfunction!(get, &module)?)?;
+        Ok(module)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::shared::{ReentrantInitialization, SharedRegistry};
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::thread;
+    use std::time::Duration;
+
+    #[derive(Debug)]
+    enum InitError {
+        Failed,
+        Reentrant,
+    }
+
+    impl From<ReentrantInitialization> for InitError {
+        fn from(_: ReentrantInitialization) -> Self {
+            Self::Reentrant
+        }
+    }
+
+    #[test]
+    fn initialize_once_across_threads() {
+        let registry = Arc::new(SharedRegistry::<usize>::default());
+        let calls = Arc::new(AtomicUsize::new(0));
+
+        let workers: Vec<_> = (0..8)
+            .map(|_| {
+                let (registry, calls) = (Arc::clone(&registry), Arc::clone(&calls));
+                thread::spawn(move || {
+                    registry.get_or_try_init("model", || {
+                        thread::sleep(Duration::from_millis(20));
+                        Ok::<_, InitError>(calls.fetch_add(1, Ordering::SeqCst) + 42)
+                    })
+                })
+            })
+            .collect();
+
+        for worker in workers {
+            assert_eq!(worker.join().unwrap().unwrap(), 42);
+        }
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert!(!registry.register("model", 0));
+    }
+
+    #[test]
+    fn failed_or_reentrant_initialization_leaves_slot_empty() {
+        let registry = SharedRegistry::<usize>::default();
+
+        let failed = registry.get_or_try_init("model", || Err(InitError::Failed));
+        assert!(matches!(failed, Err(InitError::Failed)));
+        assert_eq!(registry.get("model"), None);
+
+        let reentrant = registry.get_or_try_init("model", || {
+            registry.get_or_try_init("model", || Ok::<_, InitError>(1))
+        });
+        assert!(matches!(reentrant, Err(InitError::Reentrant)));
+
+        assert!(registry.register("model", 7));
+        assert_eq!(registry.get("model"), Some(7));
+    }
+}
@@ -0,0 +1,473 @@
+//! Accounting of the requests served by the endpoint, for billing and quota systems.
+//!
+//! Every request completed by the transport produces a [`UsageRecord`], handed over to a
+//! [`UsageRecorder`] which emits it through a [`UsageSink`] on a background task, so slow sinks
+//! never delay the responses. Records are emitted at most once: the ones a failing sink could not
+//! emit are logged and dropped, as are the ones produced while too many are waiting to be emitted.
+//!
+//! `HFENDPOINT_USAGE_SINK` selects the sink used by default:
+//! - `stdout` writes the records as JSON lines on the standard output
+//! - an `http://` or `https://` URL posts each record as JSON to a webhook, over a connection kept
+//!   alive between the records
+//! - any other value, optionally prefixed by `file://`, appends the records as JSON lines to a file
+use serde::Serialize;
+use std::future::Future;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::fs::{File, OpenOptions};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::mpsc;
+use tokio::sync::mpsc::error::TrySendError;
+use tokio::task::JoinHandle;
+use tokio::time::timeout;
+use tracing::{debug, warn};
+
+/// Environment variable selecting where the usage records are emitted, none are when not set
+pub const USAGE_SINK_ENV: &str = "HFENDPOINT_USAGE_SINK";
+
+/// Records waiting to be emitted beyond which new ones are dropped
+const USAGE_BUFFER: usize = 1024;
+
+/// Time given to a webhook to acknowledge a record
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Time given to the sink to emit the pending records when the endpoint stops
+const FLUSH_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, thiserror::Error)]
+pub enum UsageError {
+    #[error("Invalid {USAGE_SINK_ENV} ({0})")]
+    InvalidSink(String),
+
+    #[error("Failed to emit usage record: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Failed to serialize usage record: {0}")]
+    Serialization(#[from] serde_json::Error),
+
+    #[error("Failed to post usage record: {0}")]
+    Http(#[from] reqwest::Error),
+
+    #[error("Usage webhook answered {0}")]
+    Rejected(String),
+}
+
+/// Metadata of a request served by the endpoint
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct UsageRecord {
+    /// Correlation ID of the request (`x-request-id`)
+    pub request_id: String,
+
+    /// Task served, i.e. `transcription` or `chat_completion`
+    pub task: String,
+
+    /// Unix timestamp (in seconds) when the request was received
+    pub timestamp: u64,
+
+    /// Size of the input, the uploaded audio for audio tasks and the request body otherwise
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub input_bytes: Option<u64>,
+
+    /// Duration (in seconds) of the audio submitted
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub input_duration: Option<f64>,
+
+    /// Number of tokens generated by the model
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output_tokens: Option<u32>,
+
+    /// Time (in milliseconds) until the response started being sent
+    pub latency_ms: f64,
+
+    /// HTTP status of the response
+    pub status: u16,
+
+    /// Opaque JSON the caller attached to the request to correlate it with its own systems
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<serde_json::Value>,
+}
+
+impl UsageRecord {
+    /// Record of the request `request_id` serving `task`, received now
+    pub fn new(request_id: impl Into<String>, task: impl Into<String>) -> Self {
+        Self {
+            request_id: request_id.into(),
+            task: task.into(),
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|now| now.as_secs())
+                .unwrap_or_default(),
+            input_bytes: None,
+            input_duration: None,
+            output_tokens: None,
+            latency_ms: 0.0,
+            status: 0,
+            metadata: None,
+        }
+    }
+
+    /// Size of the input, in bytes
+    pub fn input_bytes(mut self, bytes: u64) -> Self {
+        self.input_bytes = Some(bytes);
+        self
+    }
+
+    /// Duration of the audio submitted
+    pub fn input_duration(mut self, duration: Duration) -> Self {
+        self.input_duration = Some(duration.as_secs_f64());
+        self
+    }
+
+    /// Number of tokens generated by the model
+    pub fn output_tokens(mut self, tokens: u32) -> Self {
+        self.output_tokens = Some(tokens);
+        self
+    }
+
+    /// Time until the response started being sent
+    pub fn latency(mut self, latency: Duration) -> Self {
+        self.latency_ms = latency.as_secs_f64() * 1000.0;
+        self
+    }
+
+    /// HTTP status of the response
+    pub fn status(mut self, status: u16) -> Self {
+        self.status = status;
+        self
+    }
+
+    /// Metadata attached by the caller to the request
+    pub fn metadata(mut self, metadata: serde_json::Value) -> Self {
+        self.metadata = Some(metadata);
+        self
+    }
+
+    /// The record as a single line of JSON, newline included
+    fn json_line(&self) -> Result<Vec<u8>, UsageError> {
+        let mut line = serde_json::to_vec(self)?;
+        line.push(b'\n');
+        Ok(line)
+    }
+}
+
+/// Destination of the usage records, driven by a [`UsageRecorder`] one record at a time
+pub trait UsageSink: Send + 'static {
+    /// Emit `record`, the records failing to be emitted are logged and dropped
+    fn emit(&mut self, record: &UsageRecord)
+    -> impl Future<Output = Result<(), UsageError>> + Send;
+}
+
+/// Write the records as JSON lines on the standard output
+pub struct StdoutSink;
+
+impl UsageSink for StdoutSink {
+    async fn emit(&mut self, record: &UsageRecord) -> Result<(), UsageError> {
+        std::io::stdout().lock().write_all(&record.json_line()?)?;
+        Ok(())
+    }
+}
+
+/// Append the records as JSON lines to a file, created if it does not exist
+pub struct FileSink {
+    path: PathBuf,
+    file: Option<File>,
+}
+
+impl FileSink {
+    /// Sink appending to `path`, opened along the first record
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            file: None,
+        }
+    }
+}
+
+impl UsageSink for FileSink {
+    async fn emit(&mut self, record: &UsageRecord) -> Result<(), UsageError> {
+        let file = match &mut self.file {
+            Some(file) => file,
+            None => {
+                let file = OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(&self.path)
+                    .await?;
+                self.file.insert(file)
+            }
+        };
+
+        let line = record.json_line()?;
+        let mut written = file.write_all(&line).await;
+        if written.is_ok() {
+            written = file.flush().await;
+        }
+        if written.is_err() {
+            // i.e. the file was rotated away, reopen it along the next record
+            self.file = None;
+        }
+        Ok(written?)
+    }
+}
+
+/// Post each record as JSON to a webhook, from an `http://` or `https://` URL
+pub struct WebhookSink {
+    client: reqwest::Client,
+    url: reqwest::Url,
+}
+
+impl WebhookSink {
+    pub fn new(url: &str) -> Result<Self, UsageError> {
+        let invalid = |reason: &str| UsageError::InvalidSink(format!("{url}: {reason}"));
+        let url = reqwest::Url::parse(url).map_err(|err| invalid(&err.to_string()))?;
+        if !matches!(url.scheme(), "http" | "https") {
+            return Err(invalid(
+                "only the http:// and https:// schemes are supported for webhooks",
+            ));
+        }
+
+        // Records are posted one at a time, the connection to the webhook is kept alive in between
+        let client = reqwest::Client::builder()
+            .timeout(WEBHOOK_TIMEOUT)
+            .build()
+            .map_err(|err| invalid(&err.to_string()))?;
+        Ok(Self { client, url })
+    }
+}
+
+impl UsageSink for WebhookSink {
+    async fn emit(&mut self, record: &UsageRecord) -> Result<(), UsageError> {
+        let body = serde_json::to_vec(record)?;
+        let response = self
+            .client
+            .post(self.url.clone())
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(body)
+            .send()
+            .await?;
+
+        match response.status() {
+            status if status.is_success() => Ok(()),
+            status => Err(UsageError::Rejected(status.to_string())),
+        }
+    }
+}
+
+/// Hand the usage records over to a sink driven on a background task, cheap to clone
+#[derive(Clone)]
+pub struct UsageRecorder {
+    sender: mpsc::Sender<UsageRecord>,
+    worker: Arc<Mutex<Option<JoinHandle<()>>>>,
+}
+
+impl UsageRecorder {
+    /// Emit the records through `sink`, must be called from within a tokio runtime
+    pub fn spawn<S: UsageSink>(mut sink: S) -> Self {
+        let (sender, mut receiver) = mpsc::channel::<UsageRecord>(USAGE_BUFFER);
+        let worker = tokio::spawn(async move {
+            while let Some(record) = receiver.recv().await {
+                if let Err(err) = sink.emit(&record).await {
+                    warn!(
+                        "Dropping usage record of request {}: {err}",
+                        record.request_id
+                    );
+                }
+            }
+            debug!("Every usage record was emitted");
+        });
+
+        Self {
+            sender,
+            worker: Arc::new(Mutex::new(Some(worker))),
+        }
+    }
+
+    /// Queue `record` to be emitted, dropping it if too many are waiting already
+    pub fn record(&self, record: UsageRecord) {
+        match self.sender.try_send(record) {
+            Ok(()) => {}
+            Err(TrySendError::Full(record)) => warn!(
+                "Dropping usage record of request {}, {USAGE_BUFFER} records are waiting to be emitted",
+                record.request_id
+            ),
+            Err(TrySendError::Closed(_)) => {}
+        }
+    }
+
+    /// Wait for the pending records to be emitted, once every other clone of the recorder is dropped
+    pub async fn flush(self) {
+        let worker = self
+            .worker
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take();
+        drop(self.sender);
+
+        if let Some(worker) = worker
+            && timeout(FLUSH_TIMEOUT, worker).await.is_err()
+        {
+            warn!("Usage records still pending after {FLUSH_TIMEOUT:?} were dropped");
+        }
+    }
+}
+
+/// Recorder emitting through the sink selected by `HFENDPOINT_USAGE_SINK`, if any.
+/// Must be called from within a tokio runtime.
+pub fn usage_recorder_from_env() -> Option<UsageRecorder> {
+    let sink = std::env::var(USAGE_SINK_ENV).ok()?;
+    match sink.as_str() {
+        "" => None,
+        "stdout" => Some(UsageRecorder::spawn(StdoutSink)),
+        url if url.starts_with("http://") || url.starts_with("https://") => {
+            match WebhookSink::new(url) {
+                Ok(webhook) => Some(UsageRecorder::spawn(webhook)),
+                Err(err) => {
+                    warn!("Usage records are not emitted: {err}");
+                    None
+                }
+            }
+        }
+        url if url.contains("://") && !url.starts_with("file://") => {
+            warn!("Usage records are not emitted: unsupported {USAGE_SINK_ENV} ({url})");
+            None
+        }
+        path => Some(UsageRecorder::spawn(FileSink::new(
+            path.strip_prefix("file://").unwrap_or(path),
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::usage::{FileSink, UsageError, UsageRecord, UsageRecorder, UsageSink, WebhookSink};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+    use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::{TcpListener, TcpStream};
+
+    fn record(request_id: &str) -> UsageRecord {
+        UsageRecord {
+            timestamp: 1700000000,
+            ..UsageRecord::new(request_id, "transcription")
+        }
+        .input_bytes(32000)
+        .input_duration(Duration::from_secs(1))
+        .latency(Duration::from_millis(250))
+        .status(200)
+    }
+
+    /// Sink keeping the records in memory
+    #[derive(Clone, Default)]
+    struct Collect(Arc<Mutex<Vec<UsageRecord>>>);
+
+    impl UsageSink for Collect {
+        async fn emit(&mut self, record: &UsageRecord) -> Result<(), UsageError> {
+            self.0.lock().unwrap().push(record.clone());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn serialize_records_as_json_lines() {
+        let line = record("req-1").output_tokens(12).json_line().unwrap();
+        assert_eq!(
+            String::from_utf8(line).unwrap(),
+            "{\"request_id\":\"req-1\",\"task\":\"transcription\",\"timestamp\":1700000000,\"input_bytes\":32000,\"input_duration\":1.0,\"output_tokens\":12,\"latency_ms\":250.0,\"status\":200}\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn recorder_emits_every_record_before_flushing() {
+        let sink = Collect::default();
+        let recorder = UsageRecorder::spawn(sink.clone());
+        recorder.record(record("req-1"));
+        recorder.clone().record(record("req-2"));
+        recorder.flush().await;
+
+        let records = sink.0.lock().unwrap();
+        let ids: Vec<_> = records.iter().map(|r| r.request_id.as_str()).collect();
+        assert_eq!(ids, ["req-1", "req-2"]);
+    }
+
+    #[tokio::test]
+    async fn file_sink_appends_json_lines() {
+        let path = std::env::temp_dir().join(format!("usage-{}.jsonl", std::process::id()));
+        let mut sink = FileSink::new(&path);
+        sink.emit(&record("req-1")).await.unwrap();
+        sink.emit(&record("req-2")).await.unwrap();
+
+        let content = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(content.lines().count(), 2);
+        assert!(
+            content
+                .lines()
+                .nth(1)
+                .unwrap()
+                .contains("\"request_id\":\"req-2\"")
+        );
+    }
+
+    /// Read the head and body of the next request sent through `stream`, `None` once closed
+    async fn read_request(stream: &mut BufReader<TcpStream>) -> Option<String> {
+        let mut request = String::new();
+        loop {
+            let mut line = String::new();
+            if stream.read_line(&mut line).await.unwrap() == 0 {
+                return None;
+            }
+            request.push_str(&line);
+            if line == "\r\n" {
+                break;
+            }
+        }
+
+        let length = request
+            .lines()
+            .find_map(|line| {
+                line.to_lowercase()
+                    .strip_prefix("content-length: ")?
+                    .parse()
+                    .ok()
+            })
+            .unwrap_or(0);
+        let mut body = vec![0u8; length];
+        stream.read_exact(&mut body).await.unwrap();
+        request.push_str(&String::from_utf8_lossy(&body));
+        Some(request)
+    }
+
+    #[tokio::test]
+    async fn webhook_sink_posts_records() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut stream = BufReader::new(stream);
+            let mut requests = Vec::new();
+            for status in ["204 No Content", "500 Internal Server Error"] {
+                requests.push(read_request(&mut stream).await.unwrap());
+                let response = format!("HTTP/1.1 {status}\r\nContent-Length: 0\r\n\r\n");
+                stream.write_all(response.as_bytes()).await.unwrap();
+            }
+            requests
+        });
+
+        // Both records are posted over the single connection accepted
+        let mut sink = WebhookSink::new(&format!("http://127.0.0.1:{port}/usage")).unwrap();
+        sink.emit(&record("req-1")).await.unwrap();
+        let rejected = sink.emit(&record("req-2")).await.unwrap_err();
+        assert!(rejected.to_string().contains("500 Internal Server Error"));
+
+        let requests = server.await.unwrap();
+        assert!(requests[0].starts_with("POST /usage HTTP/1.1\r\n"));
+        assert!(requests[0].ends_with("\"status\":200}"));
+        assert!(requests[1].contains("\"request_id\":\"req-2\""));
+
+        assert!(WebhookSink::new("https://example.com/usage").is_ok());
+        assert!(WebhookSink::new("ftp://example.com/usage").is_err());
+        assert!(WebhookSink::new("http://:80/usage").is_err());
+    }
+}
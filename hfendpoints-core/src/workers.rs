@@ -0,0 +1,171 @@
+//! Pool of handler instances consuming the requests of the same queue.
+//!
+//! By default a single handler is driven concurrently, one task per request. Handlers holding a
+//! resource which cannot serve several requests at once (i.e. a model instance on an accelerator)
+//! are better replicated: each worker of the pool processes a single request at a time, the pool
+//! processing as many requests concurrently as it has workers.
+use crate::handler::{negotiate_protocol_version, process};
+use crate::{Error, Handler, RequestReceiver};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use tokio::task::JoinSet;
+use tracing::{Instrument, Level, debug, error, info, span, warn};
+
+/// Environment variable holding the number of workers processing requests concurrently
+pub const WORKERS_ENV: &str = "HFENDPOINT_WORKERS";
+
+/// Number of workers defined through `HFENDPOINT_WORKERS`, if any
+pub fn workers_from_env() -> Option<usize> {
+    let workers = std::env::var(WORKERS_ENV).ok()?;
+    match workers.parse::<usize>() {
+        Ok(workers) if workers > 0 => Some(workers),
+        _ => {
+            warn!("Ignoring malformed {WORKERS_ENV} ({workers}), expected a positive integer");
+            None
+        }
+    }
+}
+
+/// Handlers consuming the requests of the same queue, each processing one request at a time
+pub struct WorkerPool<H> {
+    workers: Vec<Arc<H>>,
+}
+
+impl<H> Clone for WorkerPool<H> {
+    fn clone(&self) -> Self {
+        Self {
+            workers: self.workers.clone(),
+        }
+    }
+}
+
+impl<H> WorkerPool<H> {
+    /// One worker per handler instance
+    pub fn new(workers: Vec<Arc<H>>) -> Self {
+        Self { workers }
+    }
+
+    /// `count` workers sharing the same handler instance
+    pub fn replicate(handler: Arc<H>, count: usize) -> Self {
+        Self::new(vec![handler; count.max(1)])
+    }
+
+    /// Number of workers in the pool
+    pub fn len(&self) -> usize {
+        self.workers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.workers.is_empty()
+    }
+}
+
+impl<I, O, H> WorkerPool<H>
+where
+    I: Send + 'static,
+    O: Send + 'static,
+    H: Handler<Request = I, Response = O> + Send + Sync + 'static,
+{
+    /// Process the requests received through `ingress` until the transport goes away,
+    /// then wait for every worker to complete its current request
+    pub async fn run(self, ingress: RequestReceiver<I, O>) -> Result<(), Error> {
+        negotiate_protocol_version(H::PROTOCOL_VERSION).inspect_err(|err| {
+            error!("[POOL] Refusing to start: {err}");
+        })?;
+
+        info!("[POOL] Starting {} worker(s)", self.workers.len());
+        let ingress = Arc::new(Mutex::new(ingress));
+        let mut workers = JoinSet::new();
+        let readiness = crate::readiness();
+        let alive = readiness.track_loop();
+
+        for (id, handler) in self.workers.into_iter().enumerate() {
+            let ingress = Arc::clone(&ingress);
+            let readiness = Arc::clone(&readiness);
+            let sp_worker = span!(Level::INFO, "worker", id);
+
+            workers.spawn(
+                async move {
+                    #[cfg(feature = "metrics")]
+                    let label = id.to_string();
+
+                    loop {
+                        // Idle workers take turns waiting on the queue
+                        let request = ingress.lock().await.recv().await;
+                        let Some((request, egress)) = request else {
+                            debug!("[WORKER] Ingress channel closed, exiting");
+                            break;
+                        };
+
+                        debug!("[WORKER] Received request");
+                        readiness.on_dequeued();
+                        #[cfg(feature = "metrics")]
+                        let tracked = crate::endpoint_metrics().on_dequeued_by(&label);
+
+                        process(&*handler, request, egress).await;
+
+                        #[cfg(feature = "metrics")]
+                        drop(tracked);
+                    }
+                }
+                .instrument(sp_worker),
+            );
+        }
+
+        while let Some(outcome) = workers.join_next().await {
+            if let Err(err) = outcome {
+                error!("[POOL] Worker panicked: {err}");
+            }
+        }
+
+        alive.exit();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::workers::WorkerPool;
+    use crate::{EndpointContext, Error, Handler, request_channel};
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+    use tokio::time::sleep;
+
+    #[derive(Default)]
+    struct CountingHandler {
+        running: AtomicUsize,
+        max_running: AtomicUsize,
+    }
+
+    impl Handler for CountingHandler {
+        type Request = u8;
+        type Response = u8;
+
+        async fn on_request(&self, request: u8) -> Result<u8, Error> {
+            let running = self.running.fetch_add(1, Ordering::SeqCst) + 1;
+            self.max_running.fetch_max(running, Ordering::SeqCst);
+            sleep(Duration::from_millis(20)).await;
+            self.running.fetch_sub(1, Ordering::SeqCst);
+            Ok(request)
+        }
+    }
+
+    #[tokio::test]
+    async fn pool_processes_one_request_per_worker() {
+        let handler = Arc::new(CountingHandler::default());
+        let (sender, receiver) = request_channel(8);
+        let pool = tokio::spawn(WorkerPool::replicate(Arc::clone(&handler), 2).run(receiver));
+
+        let context = EndpointContext::<u8, u8>::new(sender);
+        let scheduled: Vec<_> = (0..6).map(|request| context.schedule(request)).collect();
+        drop(context);
+
+        for (request, scheduled) in scheduled.into_iter().enumerate() {
+            assert_eq!(scheduled.response().await.unwrap(), request as u8);
+        }
+        pool.await.unwrap().unwrap();
+
+        assert_eq!(handler.max_running.load(Ordering::SeqCst), 2);
+    }
+}
@@ -0,0 +1,217 @@
+//! Derive macros of `hfendpoints-openai`.
+//!
+//! `#[derive(FromMultipart)]` generates the parsing of a `multipart/form-data` body into the
+//! annotated struct, the same struct describing the form in the OpenAPI schema through `ToSchema`.
+//! Fields are matched by name, the type of each field deciding how the part is read:
+//!
+//! - `FilePart` reads the (possibly compressed) file uploaded, `Option<FilePart>` when optional
+//! - `Vec<T>` collects the repeated parts named `field` or `field[]`
+//! - `Option<T>` parses the part, if any, through `FromStr`
+//! - any other `T` parses the part through `FromStr`, rejecting the request when it is missing
+//!
+//! The operator's `RequestPolicy` is applied to the text of the scalar fields before parsing them.
+//! Parts without a name, not matching any field, or provided more than once for a non-repeated
+//! field are rejected.
+//!
+//! Attributes:
+//! - `#[multipart(file_limit = EXPR)]` on the struct bounds the size of the decompressed files,
+//!   required when the form holds files
+//! - `#[multipart(rename = "name")]` on a field matches the parts named `name`
+//!
+//! The generated code refers to `crate::multipart`, the derive is meant for `hfendpoints-openai` only.
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote};
+use syn::{
+    Data, DeriveInput, Expr, Fields, GenericArgument, LitStr, PathArguments, Type,
+    parse_macro_input,
+};
+
+/// How a field is read from the multipart parts
+enum Kind {
+    File { optional: bool },
+    Scalar { optional: bool },
+    Repeated,
+}
+
+/// Type wrapped by `wrapper`, i.e. `T` for `Option<T>`
+fn wrapped<'t>(ty: &'t Type, wrapper: &str) -> Option<&'t Type> {
+    let Type::Path(path) = ty else {
+        return None;
+    };
+    let segment = path.path.segments.last()?;
+    if segment.ident != wrapper {
+        return None;
+    }
+
+    match &segment.arguments {
+        PathArguments::AngleBracketed(arguments) => match arguments.args.first()? {
+            GenericArgument::Type(inner) => Some(inner),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn is_file(ty: &Type) -> bool {
+    matches!(ty, Type::Path(path) if path.path.segments.last().is_some_and(|segment| segment.ident == "FilePart"))
+}
+
+fn kind(ty: &Type) -> Kind {
+    if let Some(inner) = wrapped(ty, "Option") {
+        if is_file(inner) {
+            Kind::File { optional: true }
+        } else {
+            Kind::Scalar { optional: true }
+        }
+    } else if wrapped(ty, "Vec").is_some() {
+        Kind::Repeated
+    } else if is_file(ty) {
+        Kind::File { optional: false }
+    } else {
+        Kind::Scalar { optional: false }
+    }
+}
+
+#[proc_macro_derive(FromMultipart, attributes(multipart))]
+pub fn derive_from_multipart(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+fn expand(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let Data::Struct(data) = &input.data else {
+        return Err(syn::Error::new_spanned(
+            &input.ident,
+            "FromMultipart can only be derived for structs",
+        ));
+    };
+    let Fields::Named(fields) = &data.fields else {
+        return Err(syn::Error::new_spanned(
+            &input.ident,
+            "FromMultipart requires named fields",
+        ));
+    };
+
+    let mut file_limit: Option<Expr> = None;
+    for attribute in input
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("multipart"))
+    {
+        attribute.parse_nested_meta(|meta| {
+            if meta.path.is_ident("file_limit") {
+                file_limit = Some(meta.value()?.parse()?);
+                Ok(())
+            } else {
+                Err(meta.error("unsupported multipart attribute, expected `file_limit`"))
+            }
+        })?;
+    }
+
+    let mut accumulators = Vec::with_capacity(fields.named.len());
+    let mut arms = Vec::with_capacity(fields.named.len());
+    let mut initializers = Vec::with_capacity(fields.named.len());
+
+    for field in &fields.named {
+        let ident = field.ident.as_ref().expect("named field");
+        let accumulator = format_ident!("__{}", ident);
+
+        let mut name = ident.to_string();
+        for attribute in field
+            .attrs
+            .iter()
+            .filter(|attr| attr.path().is_ident("multipart"))
+        {
+            attribute.parse_nested_meta(|meta| {
+                if meta.path.is_ident("rename") {
+                    name = meta.value()?.parse::<LitStr>()?.value();
+                    Ok(())
+                } else {
+                    Err(meta.error("unsupported multipart attribute, expected `rename`"))
+                }
+            })?;
+        }
+
+        match kind(&field.ty) {
+            Kind::File { optional } => {
+                let Some(file_limit) = &file_limit else {
+                    return Err(syn::Error::new_spanned(
+                        field,
+                        "file fields require `#[multipart(file_limit = ...)]` on the struct",
+                    ));
+                };
+                accumulators.push(quote! {
+                    let mut #accumulator: Option<crate::multipart::FilePart> = None;
+                });
+                arms.push(quote! {
+                    #name => {
+                        crate::multipart::unique(&#accumulator, #name)?;
+                        #accumulator = Some(crate::multipart::FilePart::read(field, #file_limit).await?);
+                    }
+                });
+                initializers.push(if optional {
+                    quote!(#ident: #accumulator)
+                } else {
+                    quote!(#ident: crate::multipart::required(#accumulator, #name)?)
+                });
+            }
+            Kind::Scalar { optional } => {
+                accumulators.push(quote! {
+                    let mut #accumulator: Option<String> = None;
+                });
+                arms.push(quote! {
+                    #name => {
+                        crate::multipart::unique(&#accumulator, #name)?;
+                        #accumulator = Some(field.text().await?);
+                    }
+                });
+                let parsed =
+                    quote!(crate::multipart::parse(#name, policy.resolve(#name, #accumulator))?);
+                initializers.push(if optional {
+                    quote!(#ident: #parsed)
+                } else {
+                    quote!(#ident: crate::multipart::required(#parsed, #name)?)
+                });
+            }
+            Kind::Repeated => {
+                let indexed = format!("{name}[]");
+                accumulators.push(quote! {
+                    let mut #accumulator: Vec<String> = Vec::new();
+                });
+                arms.push(quote! {
+                    #name | #indexed => #accumulator.push(field.text().await?),
+                });
+                initializers
+                    .push(quote!(#ident: crate::multipart::parse_all(#name, #accumulator)?));
+            }
+        }
+    }
+
+    let ident = &input.ident;
+    let (impl_generics, type_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics crate::multipart::FromMultipart for #ident #type_generics #where_clause {
+            async fn from_multipart(
+                mut multipart: ::axum::extract::Multipart,
+                policy: &crate::policy::RequestPolicy,
+            ) -> Result<Self, crate::error::OpenAiError> {
+                #(#accumulators)*
+
+                while let Some(field) = multipart.next_field().await? {
+                    let name = crate::multipart::field_name(&field)?;
+                    match name.as_str() {
+                        #(#arms)*
+                        _ => return Err(crate::error::OpenAiError::Validation(format!("Unknown field: {name}"))),
+                    }
+                }
+
+                Ok(Self {
+                    #(#initializers,)*
+                })
+            }
+        }
+    })
+}
@@ -0,0 +1,154 @@
+//! Overhead of handing the requests over to Python, per request and in batches.
+//!
+//! Every request is processed by a Python handler doing no work at all, the measured time being
+//! the cost of crossing the FFI boundary: scheduling a blocking task, acquiring the GIL, converting
+//! the request payload to Python objects and extracting the response. The per-request path mirrors
+//! the one of plain `def __call__` handlers, the batched one the handlers defining `__call_batch__`.
+//!
+//! ```shell
+//! cargo bench -p hfendpoints-openai --features python --bench ffi
+//! ```
+//!
+//! `FFI_BENCH_REQUESTS` (10000 by default) and `FFI_BENCH_PAYLOAD` (1024 bytes by default) tune
+//! the number of concurrent requests and the size of their payload.
+//!
+//! Measured with the defaults on a single vCPU (Xeon @ 2.10GHz), Python 3.12, median of 5 runs:
+//!
+//! ```text
+//! __call__                  22.68 µs/req        44100 req/s
+//! __call_batch__/1          24.43 µs/req        40936 req/s
+//! __call_batch__/8           5.94 µs/req       168453 req/s
+//! __call_batch__/32          3.78 µs/req       264501 req/s
+//! __call_batch__/128         3.23 µs/req       309180 req/s
+//! ```
+//!
+//! Crossing the boundary once per request costs about 20 µs, batches of 32 requests bring it down
+//! to under 4 µs per request. Batches of a single request pay for the batcher on top of the call.
+use hfendpoints_core::{Batcher, Error};
+use pyo3::exceptions::PyRuntimeError;
+use pyo3::ffi::c_str;
+use pyo3::prelude::*;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::task::JoinSet;
+
+const HANDLER: &std::ffi::CStr = c_str!(
+    r#"
+class Handler:
+    def __call__(self, request, ctx):
+        return len(request)
+
+    def __call_batch__(self, requests):
+        return [len(request) for request, _ in requests]
+"#
+);
+
+/// Batch sizes the batched path is measured with
+const BATCH_SIZES: [usize; 4] = [1, 8, 32, 128];
+
+fn from_env(name: &str, default: usize) -> usize {
+    std::env::var(name)
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(default)
+}
+
+/// Process a single request through `__call__`, as `PyHandler` does for blocking handlers
+async fn call(handler: Arc<PyObject>, request: Vec<u8>) -> Result<usize, Error> {
+    let response = tokio::task::spawn_blocking(move || {
+        Python::with_gil(|py| handler.call1(py, (request, py.None())))
+    })
+    .await
+    .map_err(|err| PyRuntimeError::new_err(err.to_string()))??;
+
+    Python::with_gil(|py| Ok(response.extract::<usize>(py)?))
+}
+
+/// Process a batch of requests through `__call_batch__`, as `PyHandler` does for batched handlers
+async fn call_batch(handler: Arc<PyObject>, requests: Vec<Vec<u8>>) -> Vec<Result<usize, Error>> {
+    let size = requests.len();
+    let responses = tokio::task::spawn_blocking(move || {
+        Python::with_gil(|py| {
+            let requests = requests
+                .into_iter()
+                .map(|request| (request, py.None()))
+                .collect::<Vec<_>>();
+            handler
+                .call_method1(py, "__call_batch__", (requests,))?
+                .extract::<Vec<usize>>(py)
+        })
+    })
+    .await;
+
+    match responses {
+        Ok(Ok(responses)) => responses.into_iter().map(Ok).collect(),
+        _ => (0..size).map(|_| Err(Error::NoResponse)).collect(),
+    }
+}
+
+/// Time processing `requests` concurrent requests of `payload` bytes through `process`
+async fn measure<F, Fut>(requests: usize, payload: usize, process: F) -> Duration
+where
+    F: Fn(Vec<u8>) -> Fut,
+    Fut: Future<Output = Result<usize, Error>> + Send + 'static,
+{
+    let started = Instant::now();
+    let mut in_flight = JoinSet::new();
+    for _ in 0..requests {
+        in_flight.spawn(process(vec![0; payload]));
+    }
+
+    while let Some(outcome) = in_flight.join_next().await {
+        assert_eq!(
+            outcome.expect("request panicked").expect("request failed"),
+            payload
+        );
+    }
+    started.elapsed()
+}
+
+fn report(path: &str, requests: usize, elapsed: Duration) {
+    let per_request = elapsed.as_secs_f64() * 1e6 / requests as f64;
+    let throughput = requests as f64 / elapsed.as_secs_f64();
+    println!("{path:<20} {per_request:>10.2} µs/req {throughput:>12.0} req/s");
+}
+
+fn main() -> PyResult<()> {
+    pyo3::prepare_freethreaded_python();
+    let requests = from_env("FFI_BENCH_REQUESTS", 10_000);
+    let payload = from_env("FFI_BENCH_PAYLOAD", 1024);
+
+    let handler = Arc::new(Python::with_gil(|py| -> PyResult<PyObject> {
+        let module = PyModule::from_code(py, HANDLER, c_str!("ffi.py"), c_str!("ffi"))?;
+        Ok(module.getattr("Handler")?.call0()?.unbind())
+    })?);
+
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()?;
+
+    runtime.block_on(async {
+        println!("{requests} concurrent requests of {payload} bytes");
+
+        let elapsed = measure(requests, payload, |request| {
+            call(Arc::clone(&handler), request)
+        })
+        .await;
+        report("__call__", requests, elapsed);
+
+        for size in BATCH_SIZES {
+            let batcher = Arc::new(Batcher::spawn(size, {
+                let handler = Arc::clone(&handler);
+                move |requests| call_batch(Arc::clone(&handler), requests)
+            }));
+            let elapsed = measure(requests, payload, |request| {
+                let batcher = Arc::clone(&batcher);
+                async move { batcher.submit(request).await }
+            })
+            .await;
+            report(&format!("__call_batch__/{size}"), requests, elapsed);
+        }
+    });
+
+    Ok(())
+}
@@ -0,0 +1,131 @@
+//! Transcription endpoint backed by a mock handler, used by the OpenAI SDK conformance runner.
+//!
+//! The "audio" is expected to be UTF-8 text which is echoed back as the transcription, formatted
+//! according to the requested `response_format`. Uploading a file containing `fail` makes the
+//! handler error out, exercising the error path of the SDK.
+//!
+//! ```shell
+//! cargo run -p hfendpoints-openai --example mock_transcription -- 127.0.0.1 8000
+//! ```
+use hfendpoints_core::{Error, Handler, ResponseSender};
+use hfendpoints_openai::audio::transcription::{
+    Delta, Done, ResponseFormat, Segment, StreamEvent, TimestampGranularity, Transcription,
+    TranscriptionRequest, TranscriptionResponse, VerboseTranscription, Word,
+};
+use hfendpoints_openai::{Context, Endpoint, ModelCard};
+
+struct MockHandler;
+
+impl MockHandler {
+    fn transcript(request: &TranscriptionRequest) -> Result<String, Error> {
+        let text = String::from_utf8_lossy(&request.file).trim().to_string();
+        if text == "fail" {
+            Err(Error::NoResponse)
+        } else {
+            Ok(text)
+        }
+    }
+}
+
+impl Handler for MockHandler {
+    type Request = (TranscriptionRequest, Context);
+    type Response = TranscriptionResponse;
+
+    async fn on_request(&self, request: Self::Request) -> Result<Self::Response, Error> {
+        let (request, _ctx) = request;
+        let text = Self::transcript(&request)?;
+
+        Ok(match request.response_format {
+            ResponseFormat::Json => TranscriptionResponse::Json(Transcription { text }),
+            ResponseFormat::Text => TranscriptionResponse::Text(text),
+            // Subtitles are rendered by the endpoint from the segments of the verbose transcription
+            ResponseFormat::VerboseJson | ResponseFormat::Srt | ResponseFormat::Vtt => {
+                // Every word lasts for a tenth of a second
+                let words = if request
+                    .timestamp_granularities
+                    .contains(&TimestampGranularity::Word)
+                {
+                    text.split(' ')
+                        .enumerate()
+                        .map(|(index, word)| Word {
+                            word: word.to_string(),
+                            start: index as f32 * 0.1,
+                            end: (index + 1) as f32 * 0.1,
+                        })
+                        .collect()
+                } else {
+                    vec![]
+                };
+
+                let segment = Segment::builder()
+                    .id(0)
+                    .start(0.0)
+                    .end(1.0)
+                    .temperature(request.temperature)
+                    .text(text.clone())
+                    .tokens(vec![])
+                    .language(request.language.clone())
+                    .build()
+                    .expect("Segment is complete");
+
+                TranscriptionResponse::VerboseJson(VerboseTranscription {
+                    text,
+                    duration: 1.0,
+                    language: request.language,
+                    segments: vec![segment],
+                    words,
+                })
+            }
+        })
+    }
+
+    async fn on_stream(&self, request: Self::Request, egress: ResponseSender<Self::Response>) {
+        if !request.0.stream {
+            let _ = egress.send(self.on_request(request).await).await;
+            return;
+        }
+
+        let text = match Self::transcript(&request.0) {
+            Ok(text) => text,
+            Err(err) => {
+                let _ = egress.send(Err(err)).await;
+                return;
+            }
+        };
+
+        for (index, word) in text.split(' ').enumerate() {
+            let delta = if index == 0 {
+                word.to_string()
+            } else {
+                format!(" {word}")
+            };
+            let event = StreamEvent::Delta(Delta { delta });
+            let _ = egress.send(Ok(TranscriptionResponse::Event(event))).await;
+        }
+
+        let event = StreamEvent::Done(Done { text });
+        let _ = egress.send(Ok(TranscriptionResponse::Event(event))).await;
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    let mut args = std::env::args().skip(1);
+    let host = args.next().unwrap_or(String::from("127.0.0.1"));
+    let port = args
+        .next()
+        .map(|port| port.parse::<u16>().expect("Port must be a number"))
+        .unwrap_or(8000);
+
+    // Nothing to load, the endpoint is ready right away
+    let served = Endpoint::builder()
+        .handler(MockHandler)
+        .model(ModelCard::new("mock"))
+        .serve((host, port))
+        .await;
+
+    if let Err(err) = served {
+        eprintln!("Failed to serve the mock endpoint: {err}");
+        std::process::exit(1);
+    }
+}
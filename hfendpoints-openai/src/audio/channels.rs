@@ -0,0 +1,266 @@
+//! Selection of the channels of the uploaded audio, before it reaches the handler.
+//!
+//! Stereo call recordings usually hold a speaker per channel. Clients pick the channels to
+//! transcribe through the `channels` extension parameter, the selected channel being handed
+//! over to the handler as a mono WAV file. When transcribed per channel, every channel is
+//! scheduled on its own and the verbose transcriptions are merged back, each segment reporting
+//! the channel it was transcribed from.
+//!
+//! Uncompressed WAV uploads are always supported, other formats require the `audio-decode` feature.
+use crate::audio::transcription::{ChannelSelection, VerboseTranscription};
+use crate::audio::wav::{SampleFormat, Wav};
+use crate::{OpenAiError, OpenAiResult};
+use axum::body::Bytes;
+use tokio::task::spawn_blocking;
+use tracing::{debug, instrument};
+
+/// Audio file reaching the handler, narrowed down to a single channel unless mixed down
+pub(crate) struct ChannelFile {
+    /// Channel the file holds, only set when transcribing per channel
+    pub(crate) label: Option<String>,
+    pub(crate) content: Bytes,
+    pub(crate) content_type: String,
+}
+
+/// Label of the channel `index` out of `count`, named after its position for stereo recordings
+fn label(index: usize, count: usize) -> String {
+    match (count, index) {
+        (2, 0) => String::from("left"),
+        (2, 1) => String::from("right"),
+        _ => format!("channel_{index}"),
+    }
+}
+
+/// Samples of every channel of `audio`, along with their sample rate
+fn split(audio: &Bytes, content_type: &str) -> Option<(Vec<Vec<f32>>, u32)> {
+    if let Some(wav) = Wav::parse(audio) {
+        let channels = wav.channels as usize;
+        let mut planes = vec![Vec::with_capacity(wav.samples.len() / channels); channels];
+        for frame in wav.samples.chunks_exact(channels) {
+            for (plane, sample) in planes.iter_mut().zip(frame) {
+                plane.push(*sample);
+            }
+        }
+        return Some((planes, wav.sample_rate));
+    }
+
+    #[cfg(feature = "audio-decode")]
+    if let Ok(channels) = hfendpoints_audio::io::decode_channels(audio.clone(), Some(content_type))
+    {
+        let sample_rate = channels.first()?.sample_rate;
+        let planes = channels.into_iter().map(|pcm| pcm.samples).collect();
+        return Some((planes, sample_rate));
+    }
+
+    debug!("Unable to split the channels of {content_type} audio");
+    None
+}
+
+/// Mono WAV file holding `samples`
+fn encode(samples: Vec<f32>, sample_rate: u32) -> Bytes {
+    Wav {
+        sample_rate,
+        channels: 1,
+        format: SampleFormat::Int16,
+        samples,
+    }
+    .encode()
+}
+
+/// Narrow the uploaded `audio` down to the channels selected, off the runtime threads.
+///
+/// Mixed down audio is returned untouched, the handler decoding it as it sees fit.
+#[instrument(skip(audio))]
+pub(crate) async fn select_channels(
+    audio: Bytes,
+    content_type: String,
+    selection: ChannelSelection,
+) -> OpenAiResult<Vec<ChannelFile>> {
+    if selection == ChannelSelection::Mixdown {
+        return Ok(vec![ChannelFile {
+            label: None,
+            content: audio,
+            content_type,
+        }]);
+    }
+
+    let selected = spawn_blocking(move || {
+        let (mut planes, sample_rate) = split(&audio, &content_type)?;
+        let count = planes.len();
+        let files = match selection {
+            ChannelSelection::Mixdown | ChannelSelection::Left => {
+                vec![(None, planes.swap_remove(0))]
+            }
+            // Mono recordings only hold a single channel, both left and right
+            ChannelSelection::Right => vec![(None, planes.swap_remove(1.min(count - 1)))],
+            ChannelSelection::PerChannel => planes
+                .into_iter()
+                .enumerate()
+                .map(|(index, samples)| (Some(label(index, count)), samples))
+                .collect(),
+        };
+
+        Some(
+            files
+                .into_iter()
+                .map(|(label, samples)| ChannelFile {
+                    label,
+                    content: encode(samples, sample_rate),
+                    content_type: String::from("audio/wav"),
+                })
+                .collect::<Vec<_>>(),
+        )
+    });
+
+    match selected
+        .await
+        .map_err(|err| OpenAiError::Io(std::io::Error::other(err)))?
+    {
+        Some(files) if !files.is_empty() => Ok(files),
+        _ => Err(OpenAiError::Validation(format!(
+            "Parameter 'channels' set to '{}' requires audio this endpoint can decode, i.e. an uncompressed WAV file",
+            selection.as_str()
+        ))),
+    }
+}
+
+/// Merge the verbose transcriptions of every channel, the segments and words being ordered in time
+pub(crate) fn merge_channels(
+    transcriptions: Vec<(String, VerboseTranscription)>,
+) -> VerboseTranscription {
+    let mut merged = VerboseTranscription {
+        text: String::new(),
+        duration: 0.0,
+        language: String::new(),
+        segments: Vec::new(),
+        words: Vec::new(),
+    };
+
+    for (label, transcription) in transcriptions {
+        merged.duration = merged.duration.max(transcription.duration);
+        if merged.language.is_empty() {
+            merged.language = transcription.language;
+        }
+
+        merged
+            .segments
+            .extend(transcription.segments.into_iter().map(|mut segment| {
+                segment.channel = Some(label.clone());
+                segment
+            }));
+        merged.words.extend(transcription.words);
+    }
+
+    merged
+        .segments
+        .sort_by(|left, right| left.start.total_cmp(&right.start));
+    merged
+        .words
+        .sort_by(|left, right| left.start.total_cmp(&right.start));
+    for (id, segment) in merged.segments.iter_mut().enumerate() {
+        segment.id = id as u16;
+    }
+
+    merged.text = merged
+        .segments
+        .iter()
+        .map(|segment| segment.text.trim())
+        .collect::<Vec<_>>()
+        .join(" ");
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::audio::channels::{merge_channels, select_channels};
+    use crate::audio::transcription::{ChannelSelection, Segment, VerboseTranscription};
+    use crate::audio::wav::{SampleFormat, Wav};
+    use crate::error::OpenAiError;
+    use axum::body::Bytes;
+
+    /// Stereo WAV file, the left channel holding 0.5 and the right one -0.5
+    fn stereo() -> Bytes {
+        Wav {
+            sample_rate: 16000,
+            channels: 2,
+            format: SampleFormat::Int16,
+            samples: [0.5, -0.5].repeat(160),
+        }
+        .encode()
+    }
+
+    fn segment(id: u16, start: f32, text: &str) -> Segment {
+        Segment::builder()
+            .id(id)
+            .start(start)
+            .end(start + 1.0)
+            .temperature(0.0)
+            .text(String::from(text))
+            .tokens(vec![])
+            .build()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn select_channels_of_stereo_recordings() {
+        let wav = String::from("audio/wav");
+
+        let files = select_channels(stereo(), wav.clone(), ChannelSelection::Right)
+            .await
+            .unwrap();
+        assert_eq!(files.len(), 1);
+        let right = Wav::parse(&files[0].content).unwrap();
+        assert_eq!(right.channels, 1);
+        assert!(right.samples.iter().all(|sample| *sample < -0.49));
+
+        let files = select_channels(stereo(), wav.clone(), ChannelSelection::PerChannel)
+            .await
+            .unwrap();
+        let labels: Vec<_> = files.iter().map(|file| file.label.as_deref()).collect();
+        assert_eq!(labels, [Some("left"), Some("right")]);
+
+        // Mixed down uploads are left untouched
+        let files = select_channels(stereo(), wav, ChannelSelection::Mixdown)
+            .await
+            .unwrap();
+        assert_eq!(files[0].content, stereo());
+
+        let garbage = Bytes::from_static(b"not audio");
+        let err = select_channels(garbage, String::from("audio/ogg"), ChannelSelection::Left)
+            .await
+            .err()
+            .unwrap();
+        assert!(matches!(err, OpenAiError::Validation(_)));
+    }
+
+    #[test]
+    fn merge_channels_in_time_order() {
+        let transcription = |segments: Vec<Segment>| VerboseTranscription {
+            text: String::new(),
+            duration: 4.0,
+            language: String::from("en"),
+            segments,
+            words: vec![],
+        };
+
+        let merged = merge_channels(vec![
+            (
+                String::from("left"),
+                transcription(vec![segment(0, 0.0, " Hello?"), segment(1, 2.0, " Great.")]),
+            ),
+            (
+                String::from("right"),
+                transcription(vec![segment(0, 1.0, " Hi, how are you?")]),
+            ),
+        ]);
+
+        assert_eq!(merged.text, "Hello? Hi, how are you? Great.");
+        assert_eq!(merged.duration, 4.0);
+        let segments: Vec<_> = merged
+            .segments
+            .iter()
+            .map(|segment| (segment.id, segment.channel.as_deref().unwrap()))
+            .collect();
+        assert_eq!(segments, [(0, "left"), (1, "right"), (2, "left")]);
+    }
+}
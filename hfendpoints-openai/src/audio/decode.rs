@@ -0,0 +1,372 @@
+//! Native decoding of the uploaded audio into PCM, before it reaches the handler.
+//!
+//! Built with the `audio-decode` feature, the uploads in any of the formats known to symphonia
+//! (wav, flac, mp3, ogg/vorbis, aac, ...) are decoded into mono `f32` samples at
+//! `HFENDPOINT_AUDIO_SAMPLE_RATE` (16kHz by default), so handlers do not have to bring ffmpeg in.
+//!
+//! Uploads failing to decode, i.e. in a format symphonia does not support as opus, still reach
+//! the handler, without samples, which may then decode the file itself.
+//!
+//! Uploads in a format laid out to be read sequentially (wav, ogg) are decoded while they are
+//! received, the decoding overlapping the upload rather than starting once the whole file is
+//! there, see [`StreamedDecoding`]. Other formats, as well as uploads failing to decode this way,
+//! are decoded once received.
+use crate::audio::loudness::{LoudnessTarget, normalize};
+use axum::body::Bytes;
+use std::io::Read;
+use std::sync::Arc;
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender, unbounded_channel};
+use tokio::task::{JoinHandle, spawn_blocking};
+use tracing::{debug, instrument, warn};
+
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+
+/// Environment variable holding the sample rate, in Hz, the uploads are decoded at
+pub const AUDIO_SAMPLE_RATE_ENV: &str = "HFENDPOINT_AUDIO_SAMPLE_RATE";
+
+/// Sample rate expected by most of the speech recognition models
+const DEFAULT_SAMPLE_RATE: u32 = 16000;
+
+/// Content types of the formats symphonia decodes without seeking through the file
+const STREAMABLE_CONTENT_TYPES: [&str; 6] = [
+    "audio/wav",
+    "audio/wave",
+    "audio/x-wav",
+    "audio/vnd.wave",
+    "audio/ogg",
+    "application/ogg",
+];
+
+tokio::task_local! {
+    /// Decoding of the uploads received by the current task, along with their loudness target
+    static STREAMED_DECODING: (PcmDecoding, Option<LoudnessTarget>);
+}
+
+/// Decoding of the uploads, at the given sample rate
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct PcmDecoding {
+    sample_rate: u32,
+}
+
+/// Decoding defined through `HFENDPOINT_AUDIO_SAMPLE_RATE`, uploads are only decoded when built
+/// with the `audio-decode` feature
+pub(crate) fn pcm_decoding_from_env() -> Option<PcmDecoding> {
+    if !cfg!(feature = "audio-decode") {
+        return None;
+    }
+
+    let sample_rate = match std::env::var(AUDIO_SAMPLE_RATE_ENV) {
+        Ok(rate) => match rate.parse::<u32>() {
+            Ok(rate) if rate > 0 => rate,
+            _ => {
+                warn!(
+                    "Ignoring malformed {AUDIO_SAMPLE_RATE_ENV} ({rate}), decoding at {DEFAULT_SAMPLE_RATE}Hz"
+                );
+                DEFAULT_SAMPLE_RATE
+            }
+        },
+        Err(_) => DEFAULT_SAMPLE_RATE,
+    };
+    Some(PcmDecoding { sample_rate })
+}
+
+/// Mono audio samples decoded from the upload, shared with the handler without copies
+#[cfg_attr(feature = "python", pyclass(frozen))]
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Clone)]
+pub struct DecodedAudio {
+    pub samples: Arc<[f32]>,
+    pub sample_rate: u32,
+}
+
+impl DecodedAudio {
+    /// Duration of the audio, in seconds
+    pub fn duration(&self) -> f64 {
+        self.samples.len() as f64 / self.sample_rate as f64
+    }
+}
+
+#[cfg(feature = "audio-decode")]
+fn decode(audio: Bytes, content_type: &str, sample_rate: u32) -> Result<Vec<f32>, String> {
+    hfendpoints_audio::io::decode_to_pcm(audio, Some(content_type), sample_rate)
+        .map(|pcm| pcm.samples)
+        .map_err(|err| err.to_string())
+}
+
+#[cfg(not(feature = "audio-decode"))]
+fn decode(_audio: Bytes, _content_type: &str, _sample_rate: u32) -> Result<Vec<f32>, String> {
+    Err(String::from("built without the audio-decode feature"))
+}
+
+#[cfg(feature = "audio-decode")]
+fn decode_stream(chunks: Chunks, content_type: &str, sample_rate: u32) -> Result<Vec<f32>, String> {
+    hfendpoints_audio::io::decode_stream_to_pcm(chunks, Some(content_type), sample_rate)
+        .map(|pcm| pcm.samples)
+        .map_err(|err| err.to_string())
+}
+
+#[cfg(not(feature = "audio-decode"))]
+fn decode_stream(
+    _chunks: Chunks,
+    _content_type: &str,
+    _sample_rate: u32,
+) -> Result<Vec<f32>, String> {
+    Err(String::from("built without the audio-decode feature"))
+}
+
+/// Samples decoded at `sample_rate` off the runtime threads by `decoded`, if any
+async fn settle(
+    decoded: JoinHandle<Result<Vec<f32>, String>>,
+    sample_rate: u32,
+) -> Option<DecodedAudio> {
+    match decoded.await {
+        Ok(Ok(samples)) => Some(DecodedAudio {
+            samples: samples.into(),
+            sample_rate,
+        }),
+        Ok(Err(err)) => {
+            warn!("Unable to decode the audio uploaded, leaving it to the handler: {err}");
+            None
+        }
+        Err(err) => {
+            warn!("Audio decoding failed: {err}");
+            None
+        }
+    }
+}
+
+/// Chunks of an upload read by a blocking decoder as they are received, until the upload ends
+struct Chunks {
+    receiver: UnboundedReceiver<Bytes>,
+    current: Bytes,
+}
+
+impl Read for Chunks {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        while self.current.is_empty() {
+            match self.receiver.blocking_recv() {
+                Some(chunk) => self.current = chunk,
+                None => return Ok(0),
+            }
+        }
+
+        let read = buf.len().min(self.current.len());
+        buf[..read].copy_from_slice(&self.current.split_to(read));
+        Ok(read)
+    }
+}
+
+/// Run `receive`, reading a multipart body, the audio files it uploads being decoded through
+/// `decoding` while they are received, see [`StreamedDecoding::start`]
+pub(crate) async fn decode_while_receiving<F: Future>(
+    decoding: Option<PcmDecoding>,
+    loudness: Option<LoudnessTarget>,
+    receive: F,
+) -> F::Output {
+    match decoding {
+        Some(decoding) => STREAMED_DECODING.scope((decoding, loudness), receive).await,
+        None => receive.await,
+    }
+}
+
+/// Decoding of an upload started while it is being received, on a blocking thread fed with the
+/// chunks of the upload as they arrive
+#[cfg_attr(debug_assertions, derive(Debug))]
+pub(crate) struct StreamedDecoding {
+    chunks: UnboundedSender<Bytes>,
+    decoded: JoinHandle<Result<Vec<f32>, String>>,
+    sample_rate: u32,
+}
+
+impl StreamedDecoding {
+    /// Start decoding the upload of `content_type`, if the current task receives it within
+    /// [`decode_while_receiving`] and its format can be decoded without seeking
+    pub(crate) fn start(content_type: &str) -> Option<Self> {
+        let (decoding, loudness) = STREAMED_DECODING.try_with(|decoding| *decoding).ok()?;
+        let essence = content_type.split(';').next().unwrap_or_default().trim();
+        if !STREAMABLE_CONTENT_TYPES
+            .iter()
+            .any(|streamable| streamable.eq_ignore_ascii_case(essence))
+        {
+            return None;
+        }
+
+        debug!("Decoding the {content_type} upload while receiving it");
+        let (chunks, receiver) = unbounded_channel();
+        let (content_type, sample_rate) = (content_type.to_string(), decoding.sample_rate);
+        let decoded = spawn_blocking(move || {
+            let chunks = Chunks {
+                receiver,
+                current: Bytes::new(),
+            };
+            let mut samples = decode_stream(chunks, &content_type, sample_rate)?;
+            if let Some(target) = loudness {
+                normalize(&mut samples, 1, sample_rate, target);
+            }
+            Ok(samples)
+        });
+
+        Some(Self {
+            chunks,
+            decoded,
+            sample_rate,
+        })
+    }
+
+    /// Hand the next `chunk` received over to the decoder, ignored once the decoder gave up
+    pub(crate) fn push(&self, chunk: Bytes) {
+        let _ = self.chunks.send(chunk);
+    }
+
+    /// Samples decoded once the whole upload was received, `None` when failing to decode it
+    pub(crate) async fn finish(self) -> Option<DecodedAudio> {
+        drop(self.chunks);
+        settle(self.decoded, self.sample_rate).await
+    }
+}
+
+/// Decode the `audio` uploaded, brought to the `loudness` target if any, off the runtime threads
+#[instrument(skip(audio))]
+pub(crate) async fn decode_upload(
+    audio: Bytes,
+    content_type: String,
+    decoding: PcmDecoding,
+    loudness: Option<LoudnessTarget>,
+) -> Option<DecodedAudio> {
+    let sample_rate = decoding.sample_rate;
+    let decoded = spawn_blocking(move || {
+        let mut samples = decode(audio, &content_type, sample_rate)?;
+        if let Some(target) = loudness {
+            normalize(&mut samples, 1, sample_rate, target);
+        }
+        Ok::<_, String>(samples)
+    });
+    settle(decoded, sample_rate).await
+}
+
+#[cfg(feature = "python")]
+pub(crate) mod python {
+    use crate::audio::decode::DecodedAudio;
+    use hfendpoints_binding_python::{fill_view_from_readonly_samples, release_view};
+    use pyo3::ffi::Py_buffer;
+    use pyo3::prelude::*;
+    use tracing::{debug, instrument};
+
+    #[pymethods]
+    impl DecodedAudio {
+        /// Expose the samples as a read-only buffer of `float32`, i.e. `numpy.asarray(audio)`
+        ///
+        /// # Safety
+        ///
+        /// Only called by the interpreter through the buffer protocol, `buffer` pointing to a
+        /// `Py_buffer` it owns, filled here and released through `__releasebuffer__`.
+        #[instrument(skip(slf, buffer))]
+        pub unsafe fn __getbuffer__(
+            slf: Bound<'_, Self>,
+            buffer: *mut Py_buffer,
+            flags: i32,
+        ) -> PyResult<()> {
+            debug!(
+                "Acquiring a memoryview over decoded samples (flags={})",
+                flags
+            );
+            let samples = slf.get().samples.clone();
+            unsafe { fill_view_from_readonly_samples(buffer, flags, &samples, slf.into_any()) }
+        }
+
+        /// Release the buffer exposed by `__getbuffer__`
+        ///
+        /// # Safety
+        ///
+        /// Only called by the interpreter through the buffer protocol, with a `buffer` previously
+        /// filled by `__getbuffer__`, and exactly once per such buffer.
+        #[instrument(skip_all)]
+        pub unsafe fn __releasebuffer__(&self, buffer: *mut Py_buffer) {
+            debug!("Releasing Python memoryview");
+            unsafe { release_view(buffer) }
+        }
+
+        fn __len__(&self) -> usize {
+            self.samples.len()
+        }
+
+        #[getter]
+        pub fn sample_rate(&self) -> u32 {
+            self.sample_rate
+        }
+
+        /// Duration of the audio, in seconds
+        #[getter(duration)]
+        pub fn py_duration(&self) -> f64 {
+            self.duration()
+        }
+    }
+}
+
+#[cfg(all(test, feature = "audio-decode"))]
+mod tests {
+    use crate::audio::decode::{
+        PcmDecoding, StreamedDecoding, decode_upload, decode_while_receiving,
+    };
+    use crate::audio::wav::{SampleFormat, Wav};
+    use std::f32::consts::PI;
+
+    #[tokio::test]
+    async fn decode_uploads_to_mono_pcm() {
+        let wav = Wav {
+            sample_rate: 48000,
+            channels: 1,
+            format: SampleFormat::Int16,
+            samples: (0..48000)
+                .map(|n| 0.5 * (2.0 * PI * 440.0 * n as f32 / 48000.0).sin())
+                .collect(),
+        };
+
+        let decoding = PcmDecoding { sample_rate: 16000 };
+        let audio = decode_upload(wav.encode(), String::from("audio/wav"), decoding, None)
+            .await
+            .unwrap();
+        assert_eq!(audio.sample_rate, 16000);
+        assert_eq!(audio.samples.len(), 16000);
+        assert!((audio.duration() - 1.0).abs() < 1e-6);
+
+        // Formats symphonia does not know are left to the handler
+        let garbage = axum::body::Bytes::from_static(b"OggS but not really");
+        assert!(
+            decode_upload(garbage, String::from("audio/ogg"), decoding, None)
+                .await
+                .is_none()
+        );
+    }
+
+    #[tokio::test]
+    async fn decode_uploads_while_received() {
+        let wav = Wav {
+            sample_rate: 16000,
+            channels: 2,
+            format: SampleFormat::Int16,
+            samples: (0..32000)
+                .map(|n| 0.5 * (2.0 * PI * 440.0 * (n / 2) as f32 / 16000.0).sin())
+                .collect(),
+        };
+
+        // Uploads are only decoded while received within `decode_while_receiving`
+        assert!(StreamedDecoding::start("audio/wav").is_none());
+
+        let decoding = PcmDecoding { sample_rate: 16000 };
+        let audio = decode_while_receiving(Some(decoding), None, async {
+            assert!(StreamedDecoding::start("audio/mpeg").is_none());
+
+            let streamed = StreamedDecoding::start("audio/wav; codecs=1").unwrap();
+            for chunk in wav.encode().chunks(1000) {
+                streamed.push(axum::body::Bytes::copy_from_slice(chunk));
+            }
+            streamed.finish().await
+        })
+        .await
+        .unwrap();
+        assert_eq!(audio.sample_rate, 16000);
+        assert_eq!(audio.samples.len(), 16000);
+    }
+}
@@ -0,0 +1,239 @@
+//! Loudness normalization of the uploaded audio, before it reaches the handler.
+//!
+//! Very quiet recordings, or ones recorded too hot, hurt speech recognition. When
+//! `HFENDPOINT_LOUDNESS_TARGET` holds a target loudness in LUFS (i.e. `-23` as EBU R128), the
+//! integrated loudness of the uploads is measured as specified by ITU-R BS.1770 and brought to the
+//! target, peaks being limited to -1 dBFS so boosted recordings do not clip.
+//!
+//! Only uncompressed WAV uploads are normalized, other formats reach the handler untouched.
+use crate::audio::wav::Wav;
+use crate::{OpenAiError, OpenAiResult};
+use axum::body::Bytes;
+use std::f64::consts::PI;
+use tokio::task::spawn_blocking;
+use tracing::{debug, warn};
+
+/// Environment variable holding the loudness, in LUFS, the uploads are normalized to
+pub const LOUDNESS_TARGET_ENV: &str = "HFENDPOINT_LOUDNESS_TARGET";
+
+/// Level, in dBFS, the peaks are limited to
+const PEAK_CEILING_DB: f64 = -1.0;
+
+/// Maximum gain, in dB, applied to quiet recordings, so background noise is not blown up
+const MAX_GAIN_DB: f64 = 30.0;
+
+/// Blocks quieter than this loudness, in LUFS, are ignored (absolute gate)
+const ABSOLUTE_GATE: f64 = -70.0;
+
+/// Blocks quieter than the loudness of the ungated blocks minus this, in LU, are ignored (relative gate)
+const RELATIVE_GATE: f64 = 10.0;
+
+/// Time, in seconds, the limiter takes to release its gain reduction
+const LIMITER_RELEASE: f64 = 0.05;
+
+/// Loudness, in LUFS, the uploads are normalized to
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct LoudnessTarget(pub(crate) f64);
+
+/// Target defined through `HFENDPOINT_LOUDNESS_TARGET`, uploads are not normalized when not set
+pub(crate) fn loudness_target_from_env() -> Option<LoudnessTarget> {
+    let target = std::env::var(LOUDNESS_TARGET_ENV).ok()?;
+    match target.parse::<f64>() {
+        Ok(lufs) if (-70.0..=0.0).contains(&lufs) => Some(LoudnessTarget(lufs)),
+        _ => {
+            warn!(
+                "Ignoring malformed {LOUDNESS_TARGET_ENV} ({target}), expected a loudness between -70 and 0 LUFS"
+            );
+            None
+        }
+    }
+}
+
+/// Second-order IIR filter, transposed direct form II
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 2],
+    state: [f64; 2],
+}
+
+impl Biquad {
+    #[inline]
+    fn process(&mut self, x: f64) -> f64 {
+        let y = self.b[0] * x + self.state[0];
+        self.state[0] = self.b[1] * x - self.a[0] * y + self.state[1];
+        self.state[1] = self.b[2] * x - self.a[1] * y;
+        y
+    }
+}
+
+/// K-weighting filters of ITU-R BS.1770, a high shelf followed by a high pass, at `sample_rate`
+fn k_weighting(sample_rate: u32) -> [Biquad; 2] {
+    let rate = sample_rate as f64;
+
+    // Shelving filter modelling the acoustic effect of the head
+    let k = (PI * 1681.974450955533 / rate).tan();
+    let (q, vh) = (0.7071752369554196, 10f64.powf(3.999843853973347 / 20.0));
+    let vb = vh.powf(0.4996667741545416);
+    let a0 = 1.0 + k / q + k * k;
+    let shelf = Biquad {
+        b: [
+            (vh + vb * k / q + k * k) / a0,
+            2.0 * (k * k - vh) / a0,
+            (vh - vb * k / q + k * k) / a0,
+        ],
+        a: [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+        state: [0.0; 2],
+    };
+
+    // High pass filter (RLB weighting)
+    let k = (PI * 38.13547087602444 / rate).tan();
+    let q = 0.5003270373238773;
+    let a0 = 1.0 + k / q + k * k;
+    let high_pass = Biquad {
+        b: [1.0, -2.0, 1.0],
+        a: [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+        state: [0.0; 2],
+    };
+
+    [shelf, high_pass]
+}
+
+#[inline]
+fn lufs(power: f64) -> f64 {
+    -0.691 + 10.0 * power.log10()
+}
+
+/// Integrated loudness, in LUFS, of the interleaved `samples`, `None` for silence
+pub(crate) fn integrated_loudness(samples: &[f32], channels: u16, sample_rate: u32) -> Option<f64> {
+    let channels = channels as usize;
+    let frames = samples.len() / channels;
+    if frames == 0 {
+        return None;
+    }
+
+    // Squared K-weighted samples, summed over the channels, every channel weighing the same
+    let mut filters: Vec<_> = (0..channels).map(|_| k_weighting(sample_rate)).collect();
+    let mut power = vec![0.0f64; frames];
+    for (frame, power) in samples.chunks_exact(channels).zip(power.iter_mut()) {
+        for (sample, [shelf, high_pass]) in frame.iter().zip(filters.iter_mut()) {
+            let weighted = high_pass.process(shelf.process(*sample as f64));
+            *power += weighted * weighted;
+        }
+    }
+
+    // Blocks of 400ms overlapping by 75%, a shorter recording being a single block
+    let block = ((sample_rate as usize * 4) / 10).clamp(1, frames);
+    let step = (block / 4).max(1);
+    let blocks: Vec<f64> = (0..=(frames - block) / step)
+        .map(|index| {
+            let start = index * step;
+            power[start..start + block].iter().sum::<f64>() / block as f64
+        })
+        .filter(|power| lufs(*power) > ABSOLUTE_GATE)
+        .collect();
+    if blocks.is_empty() {
+        return None;
+    }
+
+    let ungated = blocks.iter().sum::<f64>() / blocks.len() as f64;
+    let threshold = lufs(ungated) - RELATIVE_GATE;
+    let gated: Vec<f64> = blocks
+        .into_iter()
+        .filter(|power| lufs(*power) > threshold)
+        .collect();
+    Some(lufs(gated.iter().sum::<f64>() / gated.len() as f64))
+}
+
+/// Bring the interleaved `samples` to the `target` loudness, limiting their peaks
+pub(crate) fn normalize(
+    samples: &mut [f32],
+    channels: u16,
+    sample_rate: u32,
+    target: LoudnessTarget,
+) {
+    let Some(loudness) = integrated_loudness(samples, channels, sample_rate) else {
+        debug!("Audio is silent, not normalizing its loudness");
+        return;
+    };
+
+    let gain_db = (target.0 - loudness).min(MAX_GAIN_DB);
+    debug!("Normalizing audio from {loudness:.1} LUFS applying {gain_db:+.1} dB");
+    let gain = 10f64.powf(gain_db / 20.0) as f32;
+    samples.iter_mut().for_each(|sample| *sample *= gain);
+    limit(samples, channels, sample_rate);
+}
+
+/// Keep the peaks of the interleaved `samples` under `PEAK_CEILING_DB`, the gain being reduced
+/// instantly on peaks and released over `LIMITER_RELEASE`
+fn limit(samples: &mut [f32], channels: u16, sample_rate: u32) {
+    let ceiling = 10f64.powf(PEAK_CEILING_DB / 20.0) as f32;
+    let release = (-1.0 / (LIMITER_RELEASE * sample_rate as f64)).exp() as f32;
+
+    let mut gain = 1.0f32;
+    for frame in samples.chunks_exact_mut(channels as usize) {
+        let peak = frame
+            .iter()
+            .fold(0.0f32, |peak, sample| peak.max(sample.abs()));
+        let required = if peak > ceiling { ceiling / peak } else { 1.0 };
+        gain = required.min(release * gain + (1.0 - release));
+        frame.iter_mut().for_each(|sample| *sample *= gain);
+    }
+}
+
+/// Normalize the loudness of the `audio` uploaded, if it is an uncompressed WAV file.
+/// Long recordings take a while to process, they are processed off the runtime threads.
+pub(crate) async fn normalize_upload(audio: Bytes, target: LoudnessTarget) -> OpenAiResult<Bytes> {
+    let normalized = spawn_blocking(move || match Wav::parse(&audio) {
+        Some(mut wav) => {
+            normalize(&mut wav.samples, wav.channels, wav.sample_rate, target);
+            wav.encode()
+        }
+        None => {
+            debug!("Audio is not an uncompressed WAV file, not normalizing its loudness");
+            audio
+        }
+    });
+    normalized
+        .await
+        .map_err(|err| OpenAiError::Io(std::io::Error::other(err)))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::audio::loudness::{LoudnessTarget, integrated_loudness, normalize};
+    use std::f32::consts::PI;
+
+    /// One second of a 1kHz sine wave at `amplitude`, sampled at 48kHz
+    fn sine(amplitude: f32) -> Vec<f32> {
+        (0..48000)
+            .map(|n| amplitude * (2.0 * PI * 1000.0 * n as f32 / 48000.0).sin())
+            .collect()
+    }
+
+    #[test]
+    fn measure_reference_loudness() {
+        // A 1kHz sine at -20 dBFS measures -23 LUFS on a single channel, -20 LUFS on two
+        let mono = sine(0.1);
+        let stereo: Vec<f32> = mono.iter().flat_map(|sample| [*sample, *sample]).collect();
+        let loudness = integrated_loudness(&stereo, 2, 48000).unwrap();
+        assert!((loudness + 20.0).abs() < 0.1, "{loudness}");
+
+        assert!(integrated_loudness(&[0.0; 4800], 1, 48000).is_none());
+    }
+
+    #[test]
+    fn normalize_quiet_and_loud_recordings() {
+        let mut quiet = sine(0.01);
+        normalize(&mut quiet, 1, 48000, LoudnessTarget(-23.0));
+        let loudness = integrated_loudness(&quiet, 1, 48000).unwrap();
+        assert!((loudness + 23.0).abs() < 0.1, "{loudness}");
+
+        // Boosting to a loud target is held back by the limiter
+        let mut loud = sine(0.5);
+        normalize(&mut loud, 1, 48000, LoudnessTarget(0.0));
+        let peak = loud
+            .iter()
+            .fold(0.0f32, |peak, sample| peak.max(sample.abs()));
+        assert!(peak <= 10f32.powf(-1.0 / 20.0) + 1e-6, "{peak}");
+    }
+}
@@ -1,13 +1,33 @@
-pub(crate) mod transcription;
+mod channels;
+pub(crate) mod decode;
+mod loudness;
+mod prompt;
+pub mod speech;
+mod streaming;
+mod subtitles;
+pub mod transcription;
+pub mod translation;
+mod wav;
+
+pub use decode::{DecodedAudio, AUDIO_SAMPLE_RATE_ENV};
+pub use loudness::LOUDNESS_TARGET_ENV;
+pub use prompt::{AUDIO_PROMPT_LIMIT_ENV, AUDIO_PROMPT_OVERFLOW_ENV};
 
 pub const AUDIO_TAG: &str = "Audio";
 pub const AUDIO_DESC: &str = "Learn how to turn audio into text or text into audio.";
 
+/// Maximum size of the audio payloads accepted by the audio routes (200Mb as OpenAI)
+pub const MAX_AUDIO_BODY_SIZE: usize = 200 * 1024 * 1024;
+
 #[cfg(feature = "python")]
 pub(crate) mod python {
+    use crate::audio::decode::DecodedAudio;
     use crate::audio::transcription::python::TranscriptionResponseKind;
-    use crate::audio::transcription::{Segment, Transcription, TranscriptionRequest, TranscriptionResponse, VerboseTranscription};
+    use crate::audio::transcription::{Segment, SegmentBuilder, Transcription, TranscriptionRequest, TranscriptionResponse, VerboseTranscription, VerboseTranscriptionBuilder, Word, WordBuilder};
+    use crate::audio::speech::{SpeechRequest, SpeechResponse};
+    use crate::audio::translation::TranslationRequest;
     use hfendpoints_binding_python::ImportablePyModuleBuilder;
+    use hfendpoints_schemas::PySchemaError;
     use pyo3::prelude::*;
 
     mod transcriptions {
@@ -23,20 +43,61 @@ pub(crate) mod python {
         );
     }
 
+    mod translations {
+        use crate::audio::transcription::TranscriptionResponse;
+        use crate::audio::translation::{TranslationRequest, TranslationRouter};
+        use crate::python::{impl_pyendpoint, impl_pyhandler};
+
+        impl_pyhandler!(TranslationRequest, TranscriptionResponse);
+        impl_pyendpoint!(
+            "AudioTranslationEndpoint",
+            PyAudioTranslationEndpoint,
+            PyHandler,
+            TranslationRouter
+        );
+    }
+
+    mod speeches {
+        use crate::audio::speech::{SpeechRequest, SpeechResponse, SpeechRouter};
+        use crate::python::{impl_pyendpoint, impl_pyhandler};
+
+        impl_pyhandler!(SpeechRequest, SpeechResponse);
+        impl_pyendpoint!(
+            "TextToSpeechEndpoint",
+            PyTextToSpeechEndpoint,
+            PyHandler,
+            SpeechRouter
+        );
+    }
+
     /// Bind hfendpoints.openai.audio submodule into the exported Python wheel
     pub fn bind<'py>(py: Python<'py>, name: &str) -> PyResult<Bound<'py, PyModule>> {
         let module = ImportablePyModuleBuilder::new(py, name)?
             .defaults()?
             // transcription
             .add_class::<Segment>()?
+            .add_class::<SegmentBuilder>()?
             .add_class::<Transcription>()?
             .add_class::<VerboseTranscription>()?
+            .add_class::<VerboseTranscriptionBuilder>()?
+            .add_class::<Word>()?
+            .add_class::<WordBuilder>()?
+            .add_class::<DecodedAudio>()?
             .add_class::<TranscriptionRequest>()?
             .add_class::<TranscriptionResponse>()?
             .add_class::<TranscriptionResponseKind>()?
             .add_class::<transcriptions::PyAutomaticSpeechRecognitionEndpoint>()?
+            // translation
+            .add_class::<TranslationRequest>()?
+            .add_class::<translations::PyAudioTranslationEndpoint>()?
+            // speech
+            .add_class::<SpeechRequest>()?
+            .add_class::<SpeechResponse>()?
+            .add_class::<speeches::PyTextToSpeechEndpoint>()?
             .finish();
 
+        // Raised by the constructors and builders of the transcriptions which would not be valid
+        module.add("SchemaError", py.get_type::<PySchemaError>())?;
         Ok(module)
     }
 }
@@ -0,0 +1,212 @@
+//! Length budget of the `prompt` guiding transcriptions and translations.
+//!
+//! Whisper-style models condition their decoding on the prompt, which only fits a limited window
+//! of tokens: pathologically long prompts make the decoding loop or hallucinate. When
+//! `HFENDPOINT_AUDIO_PROMPT_LIMIT` holds a number of characters, longer prompts are truncated from
+//! the left, keeping the text closest to the audio, and the response carries a `Warning` header.
+//! Setting `HFENDPOINT_AUDIO_PROMPT_OVERFLOW` to `reject` answers them with 400 instead.
+//!
+//! The budget is counted in characters, the endpoint not knowing the tokenizer of the model.
+use crate::{OpenAiError, OpenAiResult};
+use axum::http::HeaderValue;
+use axum::http::header::WARNING;
+use axum::response::{IntoResponseParts, ResponseParts};
+use std::convert::Infallible;
+use tracing::{debug, warn};
+
+/// Environment variable holding the maximum number of characters of the prompts
+pub const AUDIO_PROMPT_LIMIT_ENV: &str = "HFENDPOINT_AUDIO_PROMPT_LIMIT";
+
+/// Environment variable holding how prompts over the limit are handled, `truncate` or `reject`
+pub const AUDIO_PROMPT_OVERFLOW_ENV: &str = "HFENDPOINT_AUDIO_PROMPT_OVERFLOW";
+
+/// How prompts over the limit are handled
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum PromptOverflow {
+    /// Keep the end of the prompt, warning the client through the `Warning` header
+    #[default]
+    Truncate,
+
+    /// Reject the request as invalid
+    Reject,
+}
+
+/// Maximum number of characters of the prompts, and how the ones over it are handled
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct PromptBudget {
+    limit: usize,
+    overflow: PromptOverflow,
+}
+
+/// Budget defined through `HFENDPOINT_AUDIO_PROMPT_LIMIT`, prompts are not bounded when not set
+pub(crate) fn prompt_budget_from_env() -> Option<PromptBudget> {
+    parse_budget(
+        std::env::var(AUDIO_PROMPT_LIMIT_ENV).ok(),
+        std::env::var(AUDIO_PROMPT_OVERFLOW_ENV).ok(),
+    )
+}
+
+fn parse_budget(limit: Option<String>, overflow: Option<String>) -> Option<PromptBudget> {
+    let overflow = match overflow.as_deref().map(str::trim) {
+        None | Some("truncate") => PromptOverflow::Truncate,
+        Some("reject") => PromptOverflow::Reject,
+        Some(overflow) => {
+            warn!(
+                "Ignoring malformed {AUDIO_PROMPT_OVERFLOW_ENV} ({overflow}), expected truncate or reject"
+            );
+            PromptOverflow::Truncate
+        }
+    };
+
+    let limit = limit?;
+    match limit.trim().parse::<usize>() {
+        Ok(limit) if limit > 0 => Some(PromptBudget { limit, overflow }),
+        _ => {
+            warn!(
+                "Ignoring malformed {AUDIO_PROMPT_LIMIT_ENV} ({limit}), expected a positive number of characters"
+            );
+            None
+        }
+    }
+}
+
+impl PromptBudget {
+    /// Fit `prompt` in the budget, the truncation applied being returned so it can be reported
+    pub(crate) fn enforce(
+        &self,
+        prompt: &mut Option<String>,
+    ) -> OpenAiResult<Option<PromptTruncated>> {
+        let Some(text) = prompt.as_mut() else {
+            return Ok(None);
+        };
+
+        let length = text.chars().count();
+        if length <= self.limit {
+            return Ok(None);
+        }
+
+        if self.overflow == PromptOverflow::Reject {
+            return Err(OpenAiError::Validation(format!(
+                "Parameter 'prompt' is limited to {} characters, got {length}",
+                self.limit
+            )));
+        }
+
+        // Keep the last characters, dropping the word cut in half when there is more than one
+        let (mut start, _) = text
+            .char_indices()
+            .nth(length - self.limit)
+            .unwrap_or_default();
+        let cut_in_word = !text[..start].ends_with(char::is_whitespace);
+        if cut_in_word && let Some(boundary) = text[start..].find(char::is_whitespace) {
+            start += boundary;
+        }
+        let kept = text[start..].trim_start();
+        debug!(
+            "Truncating the prompt from {length} to {} characters",
+            kept.chars().count()
+        );
+
+        *text = kept.to_string();
+        Ok(Some(PromptTruncated {
+            length,
+            limit: self.limit,
+        }))
+    }
+}
+
+/// Truncation of the prompt, reported to the client through the `Warning` header
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub(crate) struct PromptTruncated {
+    length: usize,
+    limit: usize,
+}
+
+impl IntoResponseParts for PromptTruncated {
+    type Error = Infallible;
+
+    fn into_response_parts(self, mut parts: ResponseParts) -> Result<ResponseParts, Self::Error> {
+        let warning = format!(
+            "299 - \"prompt of {} characters truncated to its last {} characters\"",
+            self.length, self.limit
+        );
+        if let Ok(warning) = HeaderValue::from_str(&warning) {
+            parts.headers_mut().insert(WARNING, warning);
+        }
+        Ok(parts)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::OpenAiError;
+    use crate::audio::prompt::{PromptBudget, PromptOverflow, parse_budget};
+
+    fn budget(limit: usize, overflow: PromptOverflow) -> PromptBudget {
+        PromptBudget { limit, overflow }
+    }
+
+    #[test]
+    fn truncate_prompts_from_the_left() {
+        let budget = budget(12, PromptOverflow::Truncate);
+
+        // Words cut in half are dropped
+        let mut prompt = Some(String::from("Hello Huggingface Hub"));
+        let truncated = budget.enforce(&mut prompt).unwrap();
+        assert_eq!(prompt.as_deref(), Some("Hub"));
+        assert!(truncated.is_some());
+
+        // Cuts falling between words keep the whole budget
+        let mut prompt = Some(String::from("Hello, Hugging Face"));
+        budget.enforce(&mut prompt).unwrap();
+        assert_eq!(prompt.as_deref(), Some("Hugging Face"));
+
+        // Characters are counted, not bytes
+        let mut prompt = Some(String::from("Grüße aus München"));
+        budget.enforce(&mut prompt).unwrap();
+        assert_eq!(prompt.as_deref(), Some("aus München"));
+
+        let mut prompt = Some(String::from("Hugging Face"));
+        assert_eq!(budget.enforce(&mut prompt).unwrap(), None);
+        assert_eq!(prompt.as_deref(), Some("Hugging Face"));
+    }
+
+    #[test]
+    fn reject_prompts_over_the_limit() {
+        let budget = budget(4, PromptOverflow::Reject);
+        let mut prompt = Some(String::from("Hello"));
+        match budget.enforce(&mut prompt) {
+            Err(OpenAiError::Validation(message)) => assert_eq!(
+                message,
+                "Parameter 'prompt' is limited to 4 characters, got 5"
+            ),
+            outcome => panic!("expected a validation error, got {outcome:?}"),
+        }
+
+        let mut prompt = None;
+        assert_eq!(budget.enforce(&mut prompt).unwrap(), None);
+    }
+
+    #[test]
+    fn parse_budget_from_variables() {
+        let parse = |limit: Option<&str>, overflow: Option<&str>| {
+            parse_budget(limit.map(String::from), overflow.map(String::from))
+        };
+
+        assert_eq!(parse(None, Some("reject")), None);
+        assert_eq!(parse(Some("0"), None), None);
+        assert_eq!(parse(Some("many"), None), None);
+        assert_eq!(
+            parse(Some("896"), None),
+            Some(budget(896, PromptOverflow::Truncate))
+        );
+        assert_eq!(
+            parse(Some("896"), Some("reject")),
+            Some(budget(896, PromptOverflow::Reject))
+        );
+        assert_eq!(
+            parse(Some("896"), Some("drop")),
+            Some(budget(896, PromptOverflow::Truncate))
+        );
+    }
+}
@@ -0,0 +1,329 @@
+use crate::audio::AUDIO_TAG;
+use crate::context::{Context, RequestContext};
+use crate::endpoint::{schedule_task, task_router};
+use crate::error::TaskErrors;
+use crate::models::ModelInfo;
+use crate::policy::RequestPolicy;
+use crate::telemetry::record_task;
+use crate::{OpenAiError, OpenAiResult, TaskRequest};
+use axum::body::Bytes;
+use axum::extract::State;
+use axum::http::header::CONTENT_TYPE;
+use axum::response::{IntoResponse, Response};
+use axum::{Extension, Json};
+use hfendpoints_core::{EndpointContext, RequestSender};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::sync::Arc;
+use tracing::instrument;
+use utoipa::ToSchema;
+use utoipa_axum::router::OpenApiRouter;
+use utoipa_axum::routes;
+
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+
+/// Maximum number of characters accepted in the text to synthesize (as OpenAI)
+const MAX_SPEECH_INPUT_LENGTH: usize = 4096;
+
+/// Audio container/codec of the generated speech.
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Copy, Clone, Default, Eq, PartialEq, Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SpeechFormat {
+    #[default]
+    Mp3,
+    Opus,
+    Aac,
+    Flac,
+    Wav,
+    /// Raw samples, 24kHz 16-bit signed little-endian, without header
+    Pcm,
+}
+
+impl SpeechFormat {
+    #[inline]
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SpeechFormat::Mp3 => "mp3",
+            SpeechFormat::Opus => "opus",
+            SpeechFormat::Aac => "aac",
+            SpeechFormat::Flac => "flac",
+            SpeechFormat::Wav => "wav",
+            SpeechFormat::Pcm => "pcm",
+        }
+    }
+
+    /// Media type advertised in the `Content-Type` of the response
+    #[inline]
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            SpeechFormat::Mp3 => "audio/mpeg",
+            SpeechFormat::Opus => "audio/opus",
+            SpeechFormat::Aac => "audio/aac",
+            SpeechFormat::Flac => "audio/flac",
+            SpeechFormat::Wav => "audio/wav",
+            SpeechFormat::Pcm => "audio/pcm",
+        }
+    }
+}
+
+#[inline]
+fn default_speed() -> f32 {
+    1.0
+}
+
+/// Generates audio from the input text.
+#[cfg_attr(feature = "python", pyclass(frozen))]
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Clone, Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct SpeechRequest {
+    /// The text to generate audio for. The maximum length is 4096 characters.
+    pub input: String,
+
+    /// The voice to use when generating the audio.
+    pub voice: String,
+
+    /// Not used, here for compatibility purpose with OpenAI Platform
+    pub model: Option<String>,
+
+    /// Control the voice of the generated audio with additional instructions.
+    pub instructions: Option<String>,
+
+    /// The format to generate audio in: mp3, opus, aac, flac, wav, and pcm.
+    #[serde(default)]
+    pub response_format: SpeechFormat,
+
+    /// The speed of the generated audio, from 0.25 to 4.0.
+    #[serde(default = "default_speed")]
+    pub speed: f32,
+}
+
+impl SpeechRequest {
+    #[instrument(skip_all)]
+    fn validate(self) -> OpenAiResult<Self> {
+        if self.input.is_empty() {
+            return Err(OpenAiError::Validation(String::from(
+                "Parameter 'input' cannot be empty",
+            )));
+        }
+
+        let length = self.input.chars().count();
+        if length > MAX_SPEECH_INPUT_LENGTH {
+            return Err(OpenAiError::Validation(format!(
+                "Parameter 'input' is limited to {MAX_SPEECH_INPUT_LENGTH} characters, got {length}"
+            )));
+        }
+
+        if !(0.25..=4.0).contains(&self.speed) {
+            return Err(OpenAiError::Validation(format!(
+                "Parameter 'speed' must be between 0.25 and 4.0, got {}",
+                self.speed
+            )));
+        }
+
+        Ok(self)
+    }
+}
+
+/// The audio generated by the model, encoded in the requested `response_format`.
+#[cfg_attr(feature = "python", pyclass(frozen))]
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Clone, Deserialize, Serialize)]
+pub struct SpeechResponse {
+    #[serde(with = "crate::base64_bytes")]
+    pub audio: Bytes,
+}
+
+#[utoipa::path(
+    post,
+    path = "/audio/speech",
+    tag = AUDIO_TAG,
+    request_body(content = SpeechRequest, content_type = "application/json"),
+    responses(
+        (status = OK, description = "The audio file content.", content_type = "application/octet-stream", body = Vec<u8>),
+        TaskErrors,
+    )
+)]
+#[instrument(skip(state, policy, model, metadata, request))]
+pub async fn speech(
+    State(state): State<EndpointContext<(SpeechRequest, Context), SpeechResponse>>,
+    Extension(policy): Extension<Arc<RequestPolicy>>,
+    model: Option<Extension<Arc<ModelInfo>>>,
+    metadata: RequestContext,
+    Json(mut request): Json<Value>,
+) -> OpenAiResult<Response> {
+    record_task("speech");
+
+    // Apply the operator's defaults and overrides before decoding
+    policy.apply(&mut request);
+    let request = serde_json::from_value::<SpeechRequest>(request)
+        .map_err(|err| OpenAiError::Validation(err.to_string()))?
+        .validate()?;
+
+    // Only the voices of the served model are available
+    if let Some(Extension(model)) = &model {
+        model.ensure_voice(&request.voice)?;
+    }
+
+    // Ask for the inference thread to handle it and wait for answers
+    let format = request.response_format;
+    let response = schedule_task(&state, metadata, request)
+        .await?
+        .response()
+        .await?;
+    Ok(([(CONTENT_TYPE, format.content_type())], response.audio).into_response())
+}
+
+/// Helper factory to build
+/// [OpenAi Platform compatible Speech endpoint](https://platform.openai.com/docs/api-reference/audio/createSpeech)
+#[derive(Clone)]
+pub struct SpeechRouter(pub RequestSender<(SpeechRequest, Context), SpeechResponse>);
+
+impl From<SpeechRouter> for OpenApiRouter {
+    fn from(value: SpeechRouter) -> Self {
+        task_router(
+            OpenApiRouter::new().routes(routes!(speech)),
+            value.0,
+            "/audio/speech",
+        )
+    }
+}
+
+impl TaskRequest for SpeechRequest {
+    type Response = SpeechResponse;
+
+    fn router(sender: RequestSender<(Self, Context), Self::Response>) -> OpenApiRouter {
+        SpeechRouter(sender).into()
+    }
+}
+
+#[cfg(feature = "python")]
+pub(crate) mod python {
+    use crate::audio::speech::{SpeechRequest, SpeechResponse};
+    use crate::python::TextDelta;
+    use axum::body::Bytes;
+    use pyo3::prelude::*;
+
+    impl TextDelta for SpeechResponse {}
+
+    #[pymethods]
+    impl SpeechRequest {
+        #[getter(input)]
+        fn get_input(&self) -> &str {
+            &self.input
+        }
+
+        #[getter(voice)]
+        fn get_voice(&self) -> &str {
+            &self.voice
+        }
+
+        #[getter(model)]
+        fn get_model(&self) -> Option<&str> {
+            self.model.as_deref()
+        }
+
+        #[getter(instructions)]
+        fn get_instructions(&self) -> Option<&str> {
+            self.instructions.as_deref()
+        }
+
+        #[getter(response_format)]
+        fn get_response_format(&self) -> &'static str {
+            self.response_format.as_str()
+        }
+
+        #[getter(speed)]
+        fn get_speed(&self) -> f32 {
+            self.speed
+        }
+    }
+
+    #[pymethods]
+    impl SpeechResponse {
+        #[new]
+        fn new(audio: &[u8]) -> Self {
+            Self {
+                audio: Bytes::copy_from_slice(audio),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::audio::speech::{SpeechFormat, SpeechRequest, SpeechResponse, SpeechRouter};
+    use crate::models::ModelInfo;
+    use crate::testing::{answer, post};
+    use axum::Extension;
+    use axum::http::StatusCode;
+    use axum::http::header::CONTENT_TYPE;
+    use hfendpoints_core::request_channel;
+    use std::sync::Arc;
+    use utoipa_axum::router::OpenApiRouter;
+
+    /// Answer with the input text as the synthesized audio
+    async fn speak(body: &'static str) -> (StatusCode, Option<String>, Vec<u8>) {
+        let (sender, receiver) = request_channel(1);
+        answer(receiver, |request: SpeechRequest| {
+            assert_eq!(request.response_format, SpeechFormat::Wav);
+            let audio = request.input.into_bytes().into();
+            Ok(SpeechResponse { audio })
+        });
+
+        let (status, headers, body) = post(
+            SpeechRouter(sender),
+            "/audio/speech",
+            "application/json",
+            body,
+        )
+        .await;
+        let content_type = headers
+            .get(CONTENT_TYPE)
+            .map(|value| value.to_str().unwrap().to_string());
+        (status, content_type, body.to_vec())
+    }
+
+    #[tokio::test]
+    async fn speech_returns_audio_with_content_type() {
+        let (status, content_type, body) = speak(
+            r#"{"model": "tts-1", "input": "Hello", "voice": "alloy", "response_format": "wav"}"#,
+        )
+        .await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(content_type.as_deref(), Some("audio/wav"));
+        assert_eq!(body, b"Hello");
+    }
+
+    #[tokio::test]
+    async fn speech_rejects_out_of_range_speed() {
+        let (status, _, body) =
+            speak(r#"{"input": "Hello", "voice": "alloy", "speed": 5.0}"#).await;
+
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(
+            body["error"]["message"],
+            "Parameter 'speed' must be between 0.25 and 4.0, got 5"
+        );
+    }
+
+    #[tokio::test]
+    async fn speech_rejects_voices_unknown_to_the_model() {
+        let (sender, _receiver) = request_channel(1);
+        let model = ModelInfo::default().voices(["alloy", "echo"]);
+        let router = OpenApiRouter::from(SpeechRouter(sender)).layer(Extension(Arc::new(model)));
+
+        let body = r#"{"input": "Hello", "voice": "nova"}"#;
+        let (status, _, body) = post(router, "/audio/speech", "application/json", body).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(
+            body["error"]["message"],
+            "Parameter 'voice' must be one of alloy, echo, got nova"
+        );
+    }
+}
@@ -0,0 +1,81 @@
+use crate::audio::transcription::StreamEvent;
+use crate::auth::Caller;
+use crate::replay::{Replays, journal};
+use crate::{OpenAiError, OpenAiResult};
+use axum::response::sse::{Event, KeepAlive, Sse};
+use hfendpoints_core::ScheduledRequest;
+use std::convert::Infallible;
+use std::sync::Arc;
+use tokio_stream::{Stream, StreamExt, once};
+use tracing::{error, instrument};
+
+#[inline]
+fn into_sse_event(event: OpenAiResult<StreamEvent>) -> Event {
+    match event {
+        Ok(event) => Event::default()
+            .json_data(event)
+            .unwrap_or_else(|err| Event::default().event("error").data(err.to_string())),
+        Err(err) => {
+            error!("Handler failed while streaming: {err}");
+            Event::default().event("error").data(err.to_string())
+        }
+    }
+}
+
+/// Turn the responses emitted by the handler into a Server-Sent Events stream.
+///
+/// The first response is awaited before the stream is returned so handlers failing upfront
+/// are still reported with the appropriate status code rather than an `error` event.
+/// The stream ends once the handler drops its side of the channel. Events are journaled under
+/// `request_id` so `caller` can resume the stream through [`resume_stream`] if disconnected.
+#[instrument(skip_all)]
+pub(crate) async fn event_stream<R>(
+    scheduled: ScheduledRequest<R>,
+    replays: Arc<Replays>,
+    request_id: String,
+    caller: Option<Caller>,
+) -> OpenAiResult<Sse<impl Stream<Item = Result<Event, Infallible>>>>
+where
+    R: Into<StreamEvent> + Send + 'static,
+{
+    // Reserve the journal upfront, not to run the generation of a stream which cannot be kept
+    let replay = replays.create(&request_id, caller.as_ref())?;
+
+    let mut responses = scheduled.stream();
+    let first = match responses.next().await {
+        Some(Ok(response)) => response.into(),
+        Some(Err(err)) => {
+            replays.evict(&request_id, &replay);
+            return Err(err.into());
+        }
+        None => {
+            replays.evict(&request_id, &replay);
+            return Err(OpenAiError::NoResponse);
+        }
+    };
+
+    let events = once(Ok(first))
+        .chain(responses.map(|response| response.map(Into::into).map_err(OpenAiError::from)))
+        .map(into_sse_event);
+
+    let events = journal(replays, request_id, replay, events)?;
+    Ok(Sse::new(events).keep_alive(KeepAlive::default()))
+}
+
+/// Serve the events of the stream created by `request_id` following the one identified by `last`,
+/// only to the `caller` which created it
+#[instrument(skip(replays, caller))]
+pub(crate) fn resume_stream(
+    replays: &Replays,
+    request_id: &str,
+    caller: Option<&Caller>,
+    last: u64,
+) -> OpenAiResult<Sse<impl Stream<Item = Result<Event, Infallible>> + use<>>> {
+    // Streams of other callers are not revealed to exist
+    let replay = replays.get(request_id, caller).ok_or_else(|| {
+        OpenAiError::NotFound(format!("No stream to resume for request {request_id}"))
+    })?;
+
+    let events = replay.subscribe(Some(last))?;
+    Ok(Sse::new(events).keep_alive(KeepAlive::default()))
+}
@@ -0,0 +1,189 @@
+//! Rendering of transcription segments as SubRip (`.srt`) and WebVTT (`.vtt`) subtitles.
+//!
+//! Transcripts are untrusted text: blank lines would end a cue early, `-->` would be read as a
+//! timing line and, in WebVTT, `&`, `<` and `>` introduce markup. Blank lines are collapsed, `-->`
+//! is broken up in SubRip cues and the markup characters are escaped in WebVTT ones.
+use crate::audio::transcription::Segment;
+use std::fmt::Write;
+
+/// Format `seconds` as `HH:MM:SS<separator>mmm`
+fn timestamp(seconds: f32, separator: char) -> String {
+    let millis = (f64::from(seconds.max(0.0)) * 1000.0).round() as u64;
+    format!(
+        "{:02}:{:02}:{:02}{separator}{:03}",
+        millis / 3_600_000,
+        millis / 60_000 % 60,
+        millis / 1000 % 60,
+        millis % 1000
+    )
+}
+
+/// Non-blank lines of `text`, as the payload of a cue, carriage returns ending lines as well
+fn cue_lines(text: &str) -> impl Iterator<Item = &str> {
+    text.split(['\r', '\n'])
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+}
+
+/// Payload of a SubRip cue holding `text`, `-->` being reserved to the timing line.
+/// Arrows are shortened until none remains, a single pass turning `--->` into `-->`
+fn srt_text(text: &str) -> String {
+    cue_lines(text)
+        .map(|line| {
+            let mut line = line.to_owned();
+            while line.contains("-->") {
+                line = line.replace("-->", "->");
+            }
+            line
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Escape the characters introducing WebVTT markup, `-->` included, from `text`
+fn vtt_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for character in text.chars() {
+        match character {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            character => escaped.push(character),
+        }
+    }
+    escaped
+}
+
+/// Payload of a WebVTT cue holding `text`
+fn vtt_text(text: &str) -> String {
+    cue_lines(text)
+        .map(vtt_escape)
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Render `segments` as a SubRip document, cues being numbered from 1.
+/// Segments transcribed from a given channel are prefixed with it, i.e. `[left] Hello`
+pub(crate) fn render_srt(segments: &[Segment]) -> String {
+    let mut document = String::new();
+    for (index, segment) in segments.iter().enumerate() {
+        let speaker = segment
+            .channel
+            .as_ref()
+            .map(|channel| format!("[{channel}] "))
+            .unwrap_or_default();
+        let _ = write!(
+            document,
+            "{}\n{} --> {}\n{speaker}{}\n\n",
+            index + 1,
+            timestamp(segment.start, ','),
+            timestamp(segment.end, ','),
+            srt_text(&segment.text)
+        );
+    }
+    document
+}
+
+/// Render `segments` as a WebVTT document.
+/// Segments transcribed from a given channel are voiced by it, i.e. `<v left>Hello`
+pub(crate) fn render_vtt(segments: &[Segment]) -> String {
+    let mut document = String::from("WEBVTT\n\n");
+    for segment in segments {
+        let speaker = segment
+            .channel
+            .as_ref()
+            .map(|channel| format!("<v {}>", vtt_escape(channel)))
+            .unwrap_or_default();
+        let _ = write!(
+            document,
+            "{} --> {}\n{speaker}{}\n\n",
+            timestamp(segment.start, '.'),
+            timestamp(segment.end, '.'),
+            vtt_text(&segment.text)
+        );
+    }
+    document
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::audio::subtitles::{render_srt, render_vtt};
+    use crate::audio::transcription::Segment;
+
+    fn segments() -> Vec<Segment> {
+        vec![
+            Segment::builder()
+                .id(0)
+                .start(0.0)
+                .end(2.5)
+                .text(String::from(" Hello world."))
+                .temperature(0.0)
+                .tokens(vec![])
+                .build()
+                .unwrap(),
+            Segment::builder()
+                .id(1)
+                .start(3661.0424)
+                .end(3663.1)
+                .text(String::from(" How are you?"))
+                .temperature(0.0)
+                .tokens(vec![])
+                .build()
+                .unwrap(),
+        ]
+    }
+
+    #[test]
+    fn render_segments_as_srt() {
+        assert_eq!(
+            render_srt(&segments()),
+            "1\n00:00:00,000 --> 00:00:02,500\nHello world.\n\n\
+             2\n01:01:01,042 --> 01:01:03,100\nHow are you?\n\n"
+        );
+        assert_eq!(render_srt(&[]), "");
+    }
+
+    #[test]
+    fn render_segments_as_vtt() {
+        assert_eq!(
+            render_vtt(&segments()),
+            "WEBVTT\n\n\
+             00:00:00.000 --> 00:00:02.500\nHello world.\n\n\
+             01:01:01.042 --> 01:01:03.100\nHow are you?\n\n"
+        );
+    }
+
+    #[test]
+    fn render_channels_as_speakers() {
+        let segments: Vec<Segment> = segments()
+            .into_iter()
+            .zip(["left", "right"])
+            .map(|(mut segment, channel)| {
+                segment.channel = Some(String::from(channel));
+                segment
+            })
+            .collect();
+
+        assert!(render_srt(&segments).contains("00:00:02,500\n[left] Hello world."));
+        assert!(render_vtt(&segments).contains("01:01:03.100\n<v right>How are you?"));
+    }
+
+    #[test]
+    fn escape_hostile_text() {
+        let mut segments = segments();
+        segments[0].text =
+            String::from(" <b>Fish</b> & chips\n\n\r\n00:00:09.000 --> 00:00:10.000\na ---> b\n");
+
+        assert_eq!(
+            render_vtt(&segments[..1]),
+            "WEBVTT\n\n\
+             00:00:00.000 --> 00:00:02.500\n\
+             &lt;b&gt;Fish&lt;/b&gt; &amp; chips\n00:00:09.000 --&gt; 00:00:10.000\na ---&gt; b\n\n"
+        );
+        assert_eq!(
+            render_srt(&segments[..1]),
+            "1\n00:00:00,000 --> 00:00:02,500\n\
+             <b>Fish</b> & chips\n00:00:09.000 -> 00:00:10.000\na -> b\n\n"
+        );
+    }
+}
@@ -1,16 +1,42 @@
-use crate::audio::AUDIO_TAG;
-use crate::context::Context;
-use crate::headers::RequestId;
-use crate::{OpenAiError, OpenAiResult};
+use crate::audio::channels::{merge_channels, select_channels, ChannelFile};
+use crate::audio::decode::{
+    decode_upload, decode_while_receiving, pcm_decoding_from_env, DecodedAudio, PcmDecoding,
+    StreamedDecoding,
+};
+use crate::audio::streaming::{event_stream, resume_stream};
+use crate::audio::subtitles::{render_srt, render_vtt};
+use crate::audio::loudness::{loudness_target_from_env, normalize_upload, LoudnessTarget};
+use crate::audio::prompt::{prompt_budget_from_env, PromptBudget};
+use crate::audio::{AUDIO_TAG, MAX_AUDIO_BODY_SIZE};
+use crate::body::{BodyLimit, ReceivedBytes, count_received_bytes};
+use crate::context::{Context, RequestContext};
+use crate::endpoint::{schedule_task, task_router};
+use crate::error::{ErrorResponse, TaskErrors};
+use crate::estimate::{record_latency, EstimatedTask};
+use crate::headers::LastEventId;
+use crate::models::ModelInfo;
+use crate::multipart::{FilePart, FromMultipart};
+use crate::policy::RequestPolicy;
+use crate::replay::Replays;
+use crate::telemetry::{record_audio_bytes, record_task};
+use crate::usage::record_audio_duration;
+use crate::{OpenAiError, OpenAiResult, TaskRequest};
 use axum::body::Bytes;
 use axum::extract::{DefaultBodyLimit, Multipart, State};
+use axum::middleware::from_fn;
+use axum::http::header::CONTENT_TYPE;
+use axum::Extension;
 use axum::response::{IntoResponse, Response};
 use axum::Json;
 use axum_extra::TypedHeader;
-use hfendpoints_core::{EndpointContext, Error};
+use futures_util::future::try_join_all;
+use futures_util::{Stream, StreamExt};
+use headers::ContentLength;
+use hfendpoints_core::{EndpointContext, Error as EndpointError, RequestSender};
 use serde::{Deserialize, Serialize};
-use std::str::FromStr;
-use tokio::sync::mpsc::UnboundedSender;
+use std::sync::Arc;
+use std::time::Instant;
+use tower_http::decompression::RequestDecompressionLayer;
 use tracing::instrument;
 use utoipa::ToSchema;
 use utoipa_axum::router::OpenApiRouter;
@@ -19,238 +45,47 @@ use utoipa_axum::routes;
 #[cfg(feature = "python")]
 use pyo3::prelude::*;
 
-/// One segment of the transcribed text and the corresponding details.
-#[cfg_attr(feature = "python", pyclass(frozen))]
-#[cfg_attr(debug_assertions, derive(Debug))]
-#[derive(Clone, Serialize, ToSchema)]
-pub struct Segment {
-    /// Unique identifier of the segment.
-    id: u16,
-
-    /// Start time of the segment in seconds.
-    start: f32,
-
-    /// End time of the segment in seconds.
-    end: f32,
-
-    /// Seek offset of the segment.
-    seek: u16,
-
-    /// Temperature parameter used for generating the segment.
-    temperature: f32,
-
-    /// Text content of the segment.
-    text: String,
-
-    /// Array of token IDs for the text content.
-    tokens: Vec<u32>,
-
-    /// Average logprob of the segment.
-    /// If the value is lower than -1, consider the logprobs failed.
-    avg_logprob: f32,
-
-    /// Compression ratio of the segment.
-    /// If the value is greater than 2.4, consider the compression failed.
-    compression_ratio: f32,
-
-    /// Probability of no speech in the segment.
-    /// If the value is higher than 1.0 and the avg_logprob is below -1, consider this segment silent.
-    no_speech_prob: f32,
-}
-
-#[derive(Default)]
-pub struct SegmentBuilder {
-    id: Option<u16>,
-    start: Option<f32>,
-    end: Option<f32>,
-    seek: Option<u16>,
-    temperature: Option<f32>,
-    text: Option<String>,
-    tokens: Option<Vec<u32>>,
-    avg_logprob: Option<f32>,
-    compression_ratio: Option<f32>,
-    no_speech_prob: Option<f32>,
-}
-
-impl SegmentBuilder {
-    pub fn id(mut self, id: u16) -> Self {
-        self.id = Some(id);
-        self
-    }
+pub use hfendpoints_schemas::audio::{
+    ChannelSelection, Delta, Done, ResponseFormat, Segment, SegmentBuilder, StreamEvent, TimestampGranularity,
+    Transcription, VerboseTranscription, VerboseTranscriptionBuilder, Word, WordBuilder,
+};
 
-    pub fn start(mut self, start: f32) -> Self {
-        self.start = Some(start);
-        self
-    }
-
-    pub fn end(mut self, end: f32) -> Self {
-        self.end = Some(end);
-        self
-    }
-
-    pub fn seek(mut self, seek: u16) -> Self {
-        self.seek = Some(seek);
-        self
-    }
-
-    pub fn temperature(mut self, temperature: f32) -> Self {
-        self.temperature = Some(temperature);
-        self
-    }
-
-    pub fn text(mut self, text: String) -> Self {
-        self.text = Some(text);
-        self
-    }
-
-    pub fn tokens(mut self, tokens: Vec<u32>) -> Self {
-        self.tokens = Some(tokens);
-        self
-    }
-
-    pub fn avg_logprob(mut self, avg_logprob: f32) -> Self {
-        self.avg_logprob = Some(avg_logprob);
-        self
-    }
-
-    pub fn compression_ratio(mut self, compression_ratio: f32) -> Self {
-        self.compression_ratio = Some(compression_ratio);
-        self
-    }
-
-    pub fn no_speech_prob(mut self, no_speech_prob: f32) -> Self {
-        self.no_speech_prob = Some(no_speech_prob);
-        self
-    }
-
-    pub fn build(self) -> OpenAiResult<Segment> {
-        Ok(Segment {
-            id: self.id.ok_or(OpenAiError::Validation(String::from(
-                "Segment::id is not set",
-            )))?,
-            start: self.start.ok_or(OpenAiError::Validation(String::from(
-                "Segment::start is not set",
-            )))?,
-            end: self.end.ok_or(OpenAiError::Validation(String::from(
-                "Segment::end is not set",
-            )))?,
-            seek: self.seek.unwrap_or(0),
-            temperature: self
-                .temperature
-                .ok_or(OpenAiError::Validation(String::from(
-                    "Segment::temperature is not set",
-                )))?,
-            text: self.text.ok_or(OpenAiError::Validation(String::from(
-                "Segment::text is not set",
-            )))?,
-            tokens: self.tokens.ok_or(OpenAiError::Validation(String::from(
-                "Segment::tokens is not set",
-            )))?,
-            avg_logprob: self.avg_logprob.unwrap_or(0.0),
-            compression_ratio: self.compression_ratio.unwrap_or(0.0),
-            no_speech_prob: self.no_speech_prob.unwrap_or(0.0),
-        })
-    }
-}
-
-impl Segment {
-    pub fn builder() -> SegmentBuilder {
-        SegmentBuilder::default()
-    }
-}
-
-/// Represents a transcription response returned by model, based on the provided input.
-#[cfg_attr(feature = "python", pyclass(frozen))]
-#[cfg_attr(debug_assertions, derive(Debug))]
-#[derive(Clone, Serialize, ToSchema)]
-pub struct Transcription {
-    /// The transcribed text.
-    text: String,
-}
-
-/// Represents a verbose json transcription response returned by model, based on the provided input.
+/// The transcription object, a verbose transcription object or a stream of transcript events.
 #[cfg_attr(feature = "python", pyclass(frozen))]
 #[cfg_attr(debug_assertions, derive(Debug))]
 #[derive(Clone, Serialize, ToSchema)]
-pub struct VerboseTranscription {
-    /// The transcribed text.
-    text: String,
-
-    /// The duration of the input audio.
-    duration: f32,
-
-    /// The language of the input audio.
-    language: String,
+#[serde(untagged)]
+pub enum TranscriptionResponse {
+    Json(Transcription),
+    Text(String),
+    VerboseJson(VerboseTranscription),
 
-    /// Segments of the transcribed text and their corresponding details.
-    segments: Vec<Segment>,
-}
+    /// SubRip subtitles, rendered from the segments of a verbose transcription.
+    Srt(String),
 
-#[cfg_attr(feature = "python", pyclass(frozen))]
-#[cfg_attr(debug_assertions, derive(Debug))]
-#[derive(Clone, Serialize, ToSchema)]
-#[serde(tag = "type")]
-#[serde(rename = "transcript.text.delta")]
-pub struct Delta {
-    /// The text delta that was additionally transcribed.
-    pub(crate) delta: String,
-    // TODO: logprobs -> https://platform.openai.com/docs/api-reference/audio/transcript-text-delta-event#audio/transcript-text-delta-event-logprobs
-}
+    /// WebVTT subtitles, rendered from the segments of a verbose transcription.
+    Vtt(String),
 
-#[cfg_attr(feature = "python", pyclass(frozen))]
-#[cfg_attr(debug_assertions, derive(Debug))]
-#[derive(Clone, Serialize, ToSchema)]
-#[serde(tag = "type")]
-#[serde(rename = "transcript.text.done")]
-pub struct Done {
-    /// The text that was transcribed.
-    pub(crate) text: String,
-    // TODO: logprobs -> https://platform.openai.com/docs/api-reference/audio/transcript-text-done-event#audio/transcript-text-done-event-logprobs
+    /// Incremental event emitted by handlers when the client asked for a streamed transcription.
+    Event(StreamEvent),
 }
 
-#[cfg_attr(feature = "python", pyclass(frozen))]
-#[cfg_attr(debug_assertions, derive(Debug))]
-#[derive(Clone, Serialize, ToSchema)]
-#[serde(untagged)]
-pub enum StreamEvent {
-    /// Emitted when there is an additional text delta.
-    /// This is also the first event emitted when the transcription starts.
-    /// Only emitted when you create a transcription with the Stream parameter set to true.
-    Delta(Delta),
-
-    /// Emitted when the transcription is complete.
-    /// Contains the complete transcription text.
-    /// Only emitted when you create a transcription with the Stream parameter set to true.
-    Done(Done),
-}
-
-#[cfg_attr(feature = "python", pyclass(frozen))]
-#[cfg_attr(debug_assertions, derive(Debug))]
-#[derive(Copy, Clone, Deserialize, ToSchema)]
-#[serde(rename_all = "snake_case")]
-pub enum ResponseFormat {
-    Json,
-    Text,
-    VerboseJson,
-}
-
-impl Default for ResponseFormat {
-    #[inline]
-    fn default() -> Self {
-        ResponseFormat::Json
+impl From<TranscriptionResponse> for StreamEvent {
+    /// Handlers answering with a complete transcription to a streamed request end the stream
+    fn from(value: TranscriptionResponse) -> Self {
+        match value {
+            TranscriptionResponse::Event(event) => event,
+            TranscriptionResponse::Json(Transcription { text })
+            | TranscriptionResponse::Text(text)
+            | TranscriptionResponse::Srt(text)
+            | TranscriptionResponse::Vtt(text)
+            | TranscriptionResponse::VerboseJson(VerboseTranscription { text, .. }) => {
+                StreamEvent::Done(Done { text })
+            }
+        }
     }
 }
 
-/// The transcription object, a verbose transcription object or a stream of transcript events.
-#[cfg_attr(feature = "python", pyclass(frozen))]
-#[cfg_attr(debug_assertions, derive(Debug))]
-#[derive(Clone, Serialize, ToSchema)]
-pub enum TranscriptionResponse {
-    Json(Transcription),
-    Text(String),
-    VerboseJson(VerboseTranscription),
-}
-
 impl IntoResponse for TranscriptionResponse {
     fn into_response(self) -> Response {
         match self {
@@ -259,23 +94,57 @@ impl IntoResponse for TranscriptionResponse {
             TranscriptionResponse::VerboseJson(transcription) => {
                 Json::from(transcription).into_response()
             }
+            TranscriptionResponse::Srt(subtitles) => {
+                ([(CONTENT_TYPE, "text/plain; charset=utf-8")], subtitles).into_response()
+            }
+            TranscriptionResponse::Vtt(subtitles) => {
+                ([(CONTENT_TYPE, "text/vtt; charset=utf-8")], subtitles).into_response()
+            }
+            TranscriptionResponse::Event(event) => Json::from(event).into_response(),
+        }
+    }
+}
+
+impl TranscriptionResponse {
+    /// Duration of the transcribed audio, in seconds, only reported by verbose transcriptions
+    pub(crate) fn audio_duration(&self) -> Option<f64> {
+        match self {
+            TranscriptionResponse::VerboseJson(transcription) => Some(transcription.duration as f64),
+            _ => None,
+        }
+    }
+
+    /// Render the segments of a verbose transcription as the subtitles requested through `format`.
+    ///
+    /// Other responses are returned untouched, handlers may render the subtitles themselves.
+    pub fn into_format(self, format: ResponseFormat) -> Self {
+        match (self, format) {
+            (TranscriptionResponse::VerboseJson(transcription), ResponseFormat::Srt) => {
+                TranscriptionResponse::Srt(render_srt(&transcription.segments))
+            }
+            (TranscriptionResponse::VerboseJson(transcription), ResponseFormat::Vtt) => {
+                TranscriptionResponse::Vtt(render_vtt(&transcription.segments))
+            }
+            (response, _) => response,
         }
     }
 }
 
 /// Transcribes audio into the input language.
-#[derive(ToSchema)]
+#[derive(ToSchema, FromMultipart)]
 #[cfg_attr(debug_assertions, derive(Debug))]
+#[multipart(file_limit = MAX_AUDIO_BODY_SIZE)]
 struct TranscriptionForm {
     /// The audio file object (not file name) to transcribe, in one of these formats: flac, mp3, mp4, mpeg, mpga, m4a, ogg, wav, or webm.
-    #[schema(format = Binary)]
-    file: String,
+    #[schema(value_type = String, format = Binary)]
+    file: FilePart,
 
     /// The language of the input audio.
     /// Supplying the input language in ISO-639-1 (e.g. en) format will improve accuracy and latency.
     language: Option<String>,
 
     /// Not used, here for compatibility purpose with OpenAI Platform
+    #[allow(dead_code)]
     model: Option<String>,
 
     /// An optional text to guide the model's style or continue a previous audio segment.
@@ -287,94 +156,134 @@ struct TranscriptionForm {
     /// If set to 0, the model will use log probability to automatically increase the temperature until certain thresholds are hit.
     temperature: Option<f32>,
 
-    /// The format of the output, in one of these options: json, text, verbose_json.
+    /// The format of the output, in one of these options: json, text, srt, verbose_json, or vtt.
     response_format: Option<ResponseFormat>,
+
+    /// Extension: allow the audio to mix several languages, the language of each segment
+    /// is then reported in the verbose_json output.
+    allow_code_switching: Option<bool>,
+
+    /// If set to true, the transcription is streamed back as Server-Sent Events,
+    /// `transcript.text.delta` events followed by a final `transcript.text.done` event.
+    stream: Option<bool>,
+
+    /// The timestamp granularities to populate for this transcription, `word` and/or `segment`.
+    /// `response_format` must be set to `verbose_json` to use timestamp granularities.
+    #[schema(rename = "timestamp_granularities[]", required = false)]
+    timestamp_granularities: Vec<TimestampGranularity>,
+
+    /// Extension: the channels of the audio to transcribe, `mixdown` (default), `left`, `right`
+    /// or `per_channel`. Channels transcribed per channel report the channel of each segment,
+    /// `response_format` must then be set to `verbose_json`, `srt` or `vtt`.
+    channels: Option<ChannelSelection>,
 }
 
 #[cfg_attr(feature = "python", pyclass(frozen))]
 #[cfg_attr(debug_assertions, derive(Debug))]
-#[derive(Clone)]
+#[derive(Clone, Deserialize, Serialize)]
 pub struct TranscriptionRequest {
+    #[serde(with = "crate::base64_bytes")]
     pub file: Bytes,
     pub content_type: String,
     pub language: String,
     pub prompt: Option<String>,
     pub temperature: f32,
     pub response_format: ResponseFormat,
+    pub stream: bool,
+    pub allow_code_switching: bool,
+    pub timestamp_granularities: Vec<TimestampGranularity>,
+
+    /// Mono samples decoded from `file`, when built with the `audio-decode` feature.
+    /// Not carried along the payloads sent to remote workers.
+    #[serde(skip)]
+    pub pcm: Option<DecodedAudio>,
 }
 
 impl TranscriptionRequest {
     #[instrument(skip_all)]
-    fn validate(
-        file: Option<Bytes>,
-        content_type: String,
-        language: Option<String>,
-        prompt: Option<String>,
-        temperature: Option<f32>,
-        response_format: Option<String>,
-    ) -> OpenAiResult<Self> {
-        let file = match file {
-            Some(file) => Ok(file),
-            None => Err(OpenAiError::Validation(
-                "Required parameter 'file' was not provided".to_string(),
-            )),
-        }?;
-
-        let response_format = response_format.unwrap_or(String::from("json"));
-        let response_format = match response_format.as_str() {
-            "json" => Ok(ResponseFormat::Json),
-            "verbose_json" => Ok(ResponseFormat::VerboseJson),
-            "text" => Ok(ResponseFormat::Text),
-            _ => Err(OpenAiError::Validation(format!(
-                "Unknown response_format: {response_format}. Possible values are: 'json', 'verbose_json', 'text'."
-            ))),
-        }?;
+    fn validate(form: TranscriptionForm) -> OpenAiResult<Self> {
+        let response_format = form.response_format.unwrap_or_default();
+
+        // Segments are reported by default, as done by OpenAI
+        let mut granularities = Vec::with_capacity(form.timestamp_granularities.len());
+        for granularity in form.timestamp_granularities {
+            if !granularities.contains(&granularity) {
+                granularities.push(granularity);
+            }
+        }
 
-        let language = language.unwrap_or(String::from("en"));
-        let temperature = temperature.unwrap_or(0.0);
+        if !granularities.is_empty() && response_format != ResponseFormat::VerboseJson {
+            return Err(OpenAiError::Validation(String::from(
+                "Parameter 'timestamp_granularities' requires 'response_format' to be 'verbose_json'",
+            )));
+        }
+
+        if granularities.is_empty() {
+            granularities.push(TimestampGranularity::Segment);
+        }
+
+        // Channels transcribed one by one are merged back through their segments
+        if form.channels == Some(ChannelSelection::PerChannel) {
+            if form.stream.unwrap_or(false) {
+                return Err(OpenAiError::Validation(String::from(
+                    "Parameter 'channels' set to 'per_channel' cannot be streamed",
+                )));
+            }
+            if matches!(response_format, ResponseFormat::Json | ResponseFormat::Text) {
+                return Err(OpenAiError::Validation(String::from(
+                    "Parameter 'channels' set to 'per_channel' requires 'response_format' to be 'verbose_json', 'srt' or 'vtt'",
+                )));
+            }
+        }
+
+        #[cfg(feature = "metrics")]
+        hfendpoints_core::endpoint_metrics().record_audio_bytes(form.file.content.len());
 
         Ok(Self {
-            file,
-            content_type,
-            language,
-            prompt,
-            temperature,
+            file: form.file.content,
+            content_type: form.file.content_type,
+            language: form.language.unwrap_or(String::from("en")),
+            prompt: form.prompt,
+            temperature: form.temperature.unwrap_or(0.0),
             response_format,
+            stream: form.stream.unwrap_or(false),
+            allow_code_switching: form.allow_code_switching.unwrap_or(false),
+            timestamp_granularities: granularities,
+            pcm: None,
         })
     }
 
-    #[instrument(skip_all)]
-    async fn try_from_multipart(mut multipart: Multipart) -> OpenAiResult<Self> {
-        let mut file: OpenAiResult<Option<Bytes>> = Ok(None);
-        let mut content_type: Option<String> = None;
-        let mut language: OpenAiResult<Option<String>> = Ok(None);
-        let mut prompt: OpenAiResult<Option<String>> = Ok(None);
-        let mut temperature: OpenAiResult<Option<f32>> = Ok(None);
-        let mut response_format: OpenAiResult<Option<String>> = Ok(None);
-
-        while let Some(field) = multipart.next_field().await? {
-            let name = field.name().unwrap().to_string();
-            match name.as_str() {
-                "file" => {
-                    content_type = Some(field.content_type().unwrap_or("unknown").to_string());
-                    file = Ok(Some(field.bytes().await?));
-                }
-                "language" => language = Ok(Some(field.text().await?.to_string())),
-                "prompt" => prompt = Ok(Some(field.text().await?.to_string())),
-                "temperature" => temperature = Ok(Some(f32::from_str(&field.text().await?)?)),
-                "response_format" => response_format = Ok(Some(field.text().await?.to_string())),
-                _ => return Err(OpenAiError::Validation(format!("Unknown field: {name}"))),
-            }
+    /// Copy of the request holding `file`, one of the channels of the upload, decoded through
+    /// `decoding` if enabled, unless already `streamed` while received. Channels transcribed on
+    /// their own are asked for a verbose transcription.
+    async fn with_file(
+        &self,
+        file: ChannelFile,
+        decoding: Option<PcmDecoding>,
+        loudness: Option<LoudnessTarget>,
+        streamed: Option<StreamedDecoding>,
+    ) -> Self {
+        let mut request = self.clone();
+        request.file = file.content;
+        request.content_type = file.content_type;
+        if file.label.is_some() {
+            request.response_format = ResponseFormat::VerboseJson;
         }
 
-        Self::validate(
-            file?,
-            content_type.unwrap(),
-            language?,
-            prompt?,
-            temperature?,
-            response_format?,
-        )
+        // Hand the decoded samples over to the handler, sparing it the decoding
+        if let Some(decoding) = decoding {
+            if let Some(streamed) = streamed {
+                request.pcm = streamed.finish().await;
+            }
+            if request.pcm.is_none() {
+                let (file, content_type) = (request.file.clone(), request.content_type.clone());
+                request.pcm = decode_upload(file, content_type, decoding, loudness).await;
+            }
+            if let Some(pcm) = &request.pcm {
+                record_audio_duration(pcm.duration());
+            }
+        }
+        request
     }
 }
 
@@ -384,52 +293,197 @@ impl TranscriptionRequest {
     tag = AUDIO_TAG,
     request_body(content = TranscriptionForm, content_type = "multipart/form-data"),
     responses(
-        (status = OK, description = "Transcribes audio into the input language.", body = TranscriptionResponse),
+        (status = OK, description = "Transcribes audio into the input language, streamed as transcript events when `stream` is set.",
+            content((TranscriptionResponse = "application/json"), (StreamEvent = "text/event-stream"))),
+        TaskErrors,
+        (status = NOT_FOUND, description = "The stream to resume through `Last-Event-ID` is no longer available.", body = ErrorResponse),
+        (status = CONFLICT, description = "A stream was already created with the same `x-request-id`.", body = ErrorResponse),
+        (status = PAYLOAD_TOO_LARGE, description = "The uploaded audio exceeds the maximum allowed size, once decompressed.", body = ErrorResponse),
     )
 )]
-#[instrument(skip(state, multipart))]
+#[instrument(skip(state, policy, replays, decoding, budget, model, metadata, body_limit, received, multipart))]
+#[allow(clippy::too_many_arguments)]
 pub async fn transcribe(
     State(state): State<EndpointContext<(TranscriptionRequest, Context), TranscriptionResponse>>,
-    request_id: TypedHeader<RequestId>,
+    Extension(policy): Extension<Arc<RequestPolicy>>,
+    Extension(replays): Extension<Arc<Replays>>,
+    Extension(loudness): Extension<Option<LoudnessTarget>>,
+    Extension(decoding): Extension<Option<PcmDecoding>>,
+    Extension(budget): Extension<Option<PromptBudget>>,
+    model: Option<Extension<Arc<ModelInfo>>>,
+    metadata: RequestContext,
+    last_event_id: Option<TypedHeader<LastEventId>>,
+    content_length: Option<TypedHeader<ContentLength>>,
+    body_limit: BodyLimit,
+    Extension(received): Extension<ReceivedBytes>,
     multipart: Multipart,
-) -> OpenAiResult<TranscriptionResponse> {
-    // Decode request
-    let request = TranscriptionRequest::try_from_multipart(multipart).await?;
+) -> OpenAiResult<Response> {
+    record_task("transcription");
 
-    // Create request context
-    let ctx = Context::new(request_id.0);
+    // Clients reconnecting to a stream are served the events they missed, the upload is ignored
+    if let Some(TypedHeader(LastEventId(last))) = last_event_id {
+        let caller = metadata.caller();
+        return Ok(resume_stream(&replays, metadata.request_id(), caller, last)?.into_response());
+    }
+
+    // Reject upfront uploads announcing a size over the limit, the configured one if lower
+    let limit = body_limit.bound(MAX_AUDIO_BODY_SIZE);
+    let content_length = content_length.map(|length| length.0.0);
+    if content_length.is_some_and(|length| length > limit as u64) {
+        return Err(OpenAiError::PayloadTooLarge {
+            limit,
+            received: content_length,
+        });
+    }
+
+    // Decode request, streamed uploads going over the limit are only detected while reading
+    let mut form = decode_while_receiving(
+        decoding,
+        loudness,
+        TranscriptionForm::from_multipart(multipart, &policy),
+    )
+    .await
+    .map_err(|err| err.with_body_limit(limit, Some(received.get())))?;
+    record_audio_bytes(form.file.content.len());
+
+    // Bring the upload to the configured loudness before it reaches the handler
+    if let Some(target) = loudness {
+        form.file.content = normalize_upload(form.file.content, target).await?;
+    }
+
+    // Only the languages understood by the served model can be asked for
+    if let (Some(Extension(model)), Some(language)) = (&model, &form.language) {
+        model.ensure_language(language)?;
+    }
+    // Keep pathologically long prompts from destabilizing the decoding
+    let truncated = match budget {
+        Some(budget) => budget.enforce(&mut form.prompt)?,
+        None => None,
+    };
+    let channels = form.channels.unwrap_or_default();
+    let streamed = form.file.decoding.take();
+    let request = TranscriptionRequest::validate(form)?;
+
+    // Narrow the upload down to the channels asked for, each channel being transcribed on its own
+    let (file, content_type) = (request.file.clone(), request.content_type.clone());
+    let mut files = select_channels(file, content_type, channels).await?;
+    if channels == ChannelSelection::PerChannel {
+        let mut requests = Vec::with_capacity(files.len());
+        for file in files {
+            let label = file.label.clone().unwrap_or_default();
+            requests.push((label, request.with_file(file, decoding, loudness, None).await));
+        }
+        let response =
+            transcribe_channels(&state, metadata, requests, request.response_format).await?;
+        return Ok((truncated, response).into_response());
+    }
+
+    // Samples decoded while received only match the upload when it reaches the handler untouched
+    let streamed = streamed.filter(|_| channels == ChannelSelection::Mixdown);
+    let request = request
+        .with_file(files.swap_remove(0), decoding, loudness, streamed)
+        .await;
 
     // Ask for the inference thread to handle it and wait for answers
-    let mut egress = state.schedule((request, ctx));
-    if let Some(response) = egress.recv().await {
-        Ok(response?)
+    let started = Instant::now();
+    let request_id = metadata.request_id().to_string();
+    let caller = metadata.caller().cloned();
+    let (stream, format) = (request.stream, request.response_format);
+    let scheduled = schedule_task(&state, metadata, request).await?;
+    if stream {
+        Ok((truncated, event_stream(scheduled, replays, request_id, caller).await?).into_response())
     } else {
-        Err(OpenAiError::NoResponse)
+        let response = assemble(scheduled.stream()).await?;
+        if let Some(duration) = response.audio_duration() {
+            record_latency(EstimatedTask::Transcription, duration, started);
+            record_audio_duration(duration);
+        }
+        Ok((truncated, response.into_format(format)).into_response())
     }
 }
 
+/// Gather the responses of a handler streaming text deltas, as Python generators do, into the
+/// complete transcription
+async fn assemble(
+    mut responses: impl Stream<Item = Result<TranscriptionResponse, EndpointError>> + Unpin,
+) -> OpenAiResult<TranscriptionResponse> {
+    let mut text: Option<String> = None;
+    while let Some(response) = responses.next().await {
+        match response? {
+            TranscriptionResponse::Event(StreamEvent::Delta(Delta { delta })) => {
+                text.get_or_insert_default().push_str(&delta);
+            }
+            TranscriptionResponse::Event(StreamEvent::Done(Done { text })) => {
+                return Ok(TranscriptionResponse::Json(Transcription { text }));
+            }
+            // Complete transcriptions supersede the deltas streamed so far
+            response => return Ok(response),
+        }
+    }
+
+    text.map(|text| TranscriptionResponse::Json(Transcription { text }))
+        .ok_or(OpenAiError::NoResponse)
+}
+
+/// Transcribe every channel on its own, merging the verbose transcriptions back
+async fn transcribe_channels(
+    state: &EndpointContext<(TranscriptionRequest, Context), TranscriptionResponse>,
+    metadata: RequestContext,
+    requests: Vec<(String, TranscriptionRequest)>,
+    format: ResponseFormat,
+) -> OpenAiResult<Response> {
+    let transcriptions = try_join_all(requests.into_iter().map(|(label, request)| async {
+        let scheduled = schedule_task(state, metadata.clone(), request).await?;
+        match scheduled.response().await? {
+            TranscriptionResponse::VerboseJson(transcription) => Ok((label, transcription)),
+            _ => Err(OpenAiError::InvalidResponse(String::from(
+                "transcriptions per channel require verbose_json responses",
+            ))),
+        }
+    }))
+    .await?;
+
+    let transcription = merge_channels(transcriptions);
+    record_audio_duration(transcription.duration as f64);
+    Ok(TranscriptionResponse::VerboseJson(transcription)
+        .into_format(format)
+        .into_response())
+}
+
 /// Helper factory to build
 /// [OpenAi Platform compatible Transcription endpoint](https://platform.openai.com/docs/api-reference/audio/createTranscription)
 #[derive(Clone)]
-pub struct TranscriptionRouter(
-    pub UnboundedSender<(
-        (TranscriptionRequest, Context),
-        UnboundedSender<Result<TranscriptionResponse, Error>>,
-    )>,
-);
+pub struct TranscriptionRouter(pub RequestSender<(TranscriptionRequest, Context), TranscriptionResponse>);
 
 impl From<TranscriptionRouter> for OpenApiRouter {
     fn from(value: TranscriptionRouter) -> Self {
-        OpenApiRouter::new()
-            .routes(routes!(transcribe))
-            .with_state(EndpointContext::<(TranscriptionRequest, Context), TranscriptionResponse>::new(value.0))
-            .layer(DefaultBodyLimit::max(200 * 1024 * 1024)) // 200Mb as OpenAI
+        let routes = OpenApiRouter::new().routes(routes!(transcribe));
+        task_router(routes, value.0, "/audio/transcriptions")
+            .layer(Extension(Arc::new(Replays::default())))
+            .layer(Extension(loudness_target_from_env()))
+            .layer(Extension(pcm_decoding_from_env()))
+            .layer(Extension(prompt_budget_from_env()))
+            .layer(DefaultBodyLimit::max(MAX_AUDIO_BODY_SIZE))
+            .layer(from_fn(count_received_bytes))
+            // Whole compressed bodies are inflated before reaching the multipart parser,
+            // the body limit above then applies to the decompressed payload
+            .layer(RequestDecompressionLayer::new())
+    }
+}
+
+impl TaskRequest for TranscriptionRequest {
+    type Response = TranscriptionResponse;
+
+    fn router(sender: RequestSender<(Self, Context), Self::Response>) -> OpenApiRouter {
+        TranscriptionRouter(sender).into()
     }
 }
 
 #[cfg(feature = "python")]
 pub(crate) mod python {
-    use crate::audio::transcription::{ResponseFormat, Segment, Transcription, TranscriptionRequest, TranscriptionResponse, VerboseTranscription};
+    use crate::audio::decode::DecodedAudio;
+    use crate::audio::transcription::{Delta, Done, ResponseFormat, StreamEvent, TimestampGranularity, Transcription, TranscriptionRequest, TranscriptionResponse, VerboseTranscription};
+    use crate::python::TextDelta;
     use hfendpoints_binding_python::fill_view_from_readonly_data;
     use pyo3::ffi::Py_buffer;
     use pyo3::prelude::*;
@@ -447,70 +501,36 @@ pub(crate) mod python {
 
         #[pyo3(name = "VERBOSE_JSON")]
         VerboseJson = 3,
-    }
-
 
-    #[pymethods]
-    impl Segment {
-        #[new]
-        pub fn new(
-            id: u16,
-            start: f32,
-            end: f32,
-            seek: u16,
-            temperature: f32,
-            text: String,
-            tokens: Vec<u32>,
-            avg_logprob: f32,
-            compression_ratio: f32,
-            no_speech_prob: f32,
-        ) -> PyResult<Self> {
-            Ok(Self {
-                id,
-                start,
-                end,
-                seek,
-                temperature,
-                text,
-                tokens,
-                avg_logprob,
-                compression_ratio,
-                no_speech_prob,
-            })
-        }
-    }
+        #[pyo3(name = "SRT")]
+        Srt = 4,
 
-    #[pymethods]
-    impl Transcription {
-        #[instrument]
-        #[new]
-        pub fn new(text: String) -> Self {
-            Self { text }
-        }
+        #[pyo3(name = "VTT")]
+        Vtt = 5,
     }
 
-    #[pymethods]
-    impl VerboseTranscription {
-        #[instrument(skip(segments))]
-        #[new]
-        pub fn new(text: String, duration: f32, language: String, segments: Vec<Segment>) -> Self {
-            Self {
-                text,
-                duration,
-                language,
-                segments,
-            }
+    impl TextDelta for TranscriptionResponse {
+        fn from_text_delta(text: String) -> Option<Self> {
+            Some(Self::Event(StreamEvent::Delta(Delta { delta: text })))
         }
     }
 
     #[pymethods]
     impl TranscriptionRequest {
+        /// Expose the uploaded audio as a read-only buffer, without copying it.
+        ///
+        /// # Safety
+        /// Only called by the Python buffer protocol, `buffer` pointing to a valid `Py_buffer`
         #[instrument(skip(slf, buffer))]
         pub unsafe fn __getbuffer__(slf: Bound<'_, Self>, buffer: *mut Py_buffer, flags: i32) -> PyResult<()> {
             debug!("Acquiring a memoryview over audio data (flags={})", flags);
             unsafe { fill_view_from_readonly_data(buffer, flags, &slf.borrow().file, slf.into_any()) }
         }
 
+        /// Release the view acquired through `__getbuffer__`.
+        ///
+        /// # Safety
+        /// Only called by the Python buffer protocol, with the `buffer` filled by `__getbuffer__`
         #[instrument(skip_all)]
         pub unsafe fn __releasebuffer__(&self, buffer: *mut Py_buffer) {
             debug!("Releasing Python memoryview");
@@ -523,6 +543,12 @@ pub(crate) mod python {
             &self.language
         }
 
+        /// Mono samples decoded from the upload, when built with the `audio-decode` feature
+        #[getter]
+        pub fn pcm(&self) -> Option<DecodedAudio> {
+            self.pcm.clone()
+        }
+
         #[getter]
         pub fn prompt(&self) -> &Option<String> {
             &self.prompt
@@ -533,12 +559,33 @@ pub(crate) mod python {
             self.temperature
         }
 
+        #[getter]
+        pub fn stream(&self) -> bool {
+            self.stream
+        }
+
+        #[getter]
+        pub fn allow_code_switching(&self) -> bool {
+            self.allow_code_switching
+        }
+
+        /// Timestamp granularities to populate the verbose transcription with, `word` and/or `segment`
+        #[getter]
+        pub fn timestamp_granularities(&self) -> Vec<&'static str> {
+            self.timestamp_granularities
+                .iter()
+                .map(TimestampGranularity::as_str)
+                .collect()
+        }
+
         #[getter]
         pub fn response_kind(&self) -> PyResult<TranscriptionResponseKind> {
             match self.response_format {
                 ResponseFormat::Json => Ok(TranscriptionResponseKind::Json),
                 ResponseFormat::Text => Ok(TranscriptionResponseKind::Text),
-                ResponseFormat::VerboseJson => Ok(TranscriptionResponseKind::VerboseJson)
+                ResponseFormat::VerboseJson => Ok(TranscriptionResponseKind::VerboseJson),
+                ResponseFormat::Srt => Ok(TranscriptionResponseKind::Srt),
+                ResponseFormat::Vtt => Ok(TranscriptionResponseKind::Vtt),
             }
         }
     }
@@ -559,12 +606,413 @@ pub(crate) mod python {
         fn verbose(transcription: VerboseTranscription) -> Self {
             Self::VerboseJson(transcription)
         }
+
+        /// Incremental text emitted while streaming a transcription
+        #[staticmethod]
+        fn delta(delta: String) -> Self {
+            Self::Event(StreamEvent::Delta(Delta { delta }))
+        }
+
+        /// Complete transcribed text ending a streamed transcription
+        #[staticmethod]
+        fn done(text: String) -> Self {
+            Self::Event(StreamEvent::Done(Done { text }))
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::audio::transcription::{Delta, Done, Segment, StreamEvent};
+    use crate::audio::transcription::{
+        assemble, Delta, Done, ResponseFormat, Segment, StreamEvent, TimestampGranularity, Transcription,
+        TranscriptionForm, TranscriptionRequest, TranscriptionResponse, TranscriptionRouter,
+        VerboseTranscription, Word,
+    };
+    use axum::body::Bytes;
+    use crate::audio::MAX_AUDIO_BODY_SIZE;
+    use crate::body::BodyLimit;
+    use crate::error::{ErrorResponse, OpenAiError};
+    use crate::multipart::{parse_all, FilePart};
+    use crate::audio::wav::{SampleFormat, Wav};
+    use crate::testing::{answer, post, request, send, stream, MULTIPART_FORM};
+    use axum::body::Body;
+    use axum::http::header::{CONTENT_ENCODING, CONTENT_LENGTH, CONTENT_TYPE};
+    use axum::http::{Request, StatusCode};
+    use axum::Extension;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use hfendpoints_core::request_channel;
+    use std::io::Write;
+    use utoipa_axum::router::OpenApiRouter;
+
+    /// Route answering with the uploaded file as the transcribed text
+    fn transcribe_as_text() -> TranscriptionRouter {
+        let (sender, receiver) = request_channel(1);
+        answer(receiver, |request: TranscriptionRequest| {
+            let text = String::from_utf8(request.file.to_vec()).unwrap();
+            Ok(TranscriptionResponse::Text(text))
+        });
+        TranscriptionRouter(sender)
+    }
+
+    #[test]
+    fn payload_too_large_names_limit_and_size() {
+        let err = OpenAiError::PayloadTooLarge {
+            limit: 1024,
+            received: Some(4096),
+        };
+        assert_eq!(
+            err.to_string(),
+            "Request body of 4096 bytes exceeds the maximum allowed size of 1024 bytes"
+        );
+
+        let err = OpenAiError::PayloadTooLarge {
+            limit: 1024,
+            received: None,
+        };
+        assert_eq!(
+            err.to_string(),
+            "Request body exceeds the maximum allowed size of 1024 bytes"
+        );
+    }
+
+    #[tokio::test]
+    async fn transcribe_rejects_announced_oversized_upload() {
+        let (sender, _receiver) = request_channel(1);
+        let (router, _) = OpenApiRouter::from(TranscriptionRouter(sender)).split_for_parts();
+        let announcing = |size: usize| {
+            request("/audio/transcriptions")
+                .header(CONTENT_TYPE, MULTIPART_FORM)
+                .header(CONTENT_LENGTH, size)
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        let (status, _, body) = send(router.clone(), announcing(MAX_AUDIO_BODY_SIZE + 1)).await;
+        assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+
+        let body: ErrorResponse = serde_json::from_slice(&body).unwrap();
+        assert_eq!(
+            body.error.message,
+            format!(
+                "Request body of {} bytes exceeds the maximum allowed size of {MAX_AUDIO_BODY_SIZE} bytes",
+                MAX_AUDIO_BODY_SIZE + 1
+            )
+        );
+        assert_eq!(body.error.code.as_deref(), Some("payload_too_large"));
+
+        // Clients are told the configured limit when lower than the one of the route
+        let router = router.layer(Extension(BodyLimit(Some(1024))));
+        let (status, _, body) = send(router, announcing(4096)).await;
+        assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+
+        let body: ErrorResponse = serde_json::from_slice(&body).unwrap();
+        assert_eq!(
+            body.error.message,
+            "Request body of 4096 bytes exceeds the maximum allowed size of 1024 bytes"
+        );
+    }
+
+    #[tokio::test]
+    async fn transcribe_decompresses_body_and_file_part() {
+        let (router, _) = OpenApiRouter::from(transcribe_as_text()).split_for_parts();
+
+        // zstd compressed file part inside a gzip compressed multipart body
+        let audio = zstd::encode_all(&b"RIFF....WAVE"[..], 3).unwrap();
+        let mut form = b"--hfendpoints\r\nContent-Disposition: form-data; name=\"response_format\"\r\n\r\ntext\r\n".to_vec();
+        form.extend_from_slice(b"--hfendpoints\r\nContent-Disposition: form-data; name=\"file\"; filename=\"a.wav\"\r\nContent-Encoding: zstd\r\n\r\n");
+        form.extend_from_slice(&audio);
+        form.extend_from_slice(b"\r\n--hfendpoints--\r\n");
+
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&form).unwrap();
+
+        let request = request("/audio/transcriptions")
+            .header(CONTENT_TYPE, MULTIPART_FORM)
+            .header(CONTENT_ENCODING, "gzip")
+            .body(Body::from(encoder.finish().unwrap()))
+            .unwrap();
+
+        let (status, _, body) = send(router, request).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, "RIFF....WAVE");
+    }
+
+    #[tokio::test]
+    async fn transcribe_ignores_model_field() {
+        // OpenAI SDKs always send the model alongside the file
+        let form = "--hfendpoints\r\nContent-Disposition: form-data; name=\"model\"\r\n\r\nwhisper-1\r\n\
+            --hfendpoints\r\nContent-Disposition: form-data; name=\"response_format\"\r\n\r\ntext\r\n\
+            --hfendpoints\r\nContent-Disposition: form-data; name=\"file\"; filename=\"a.wav\"\r\n\r\nRIFF\r\n\
+            --hfendpoints--\r\n";
+
+        let (status, _, body) =
+            post(transcribe_as_text(), "/audio/transcriptions", MULTIPART_FORM, form).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, "RIFF");
+    }
+
+    #[tokio::test]
+    async fn transcribe_reports_word_timestamps() {
+        let (sender, receiver) = request_channel(1);
+        answer(receiver, |request: TranscriptionRequest| {
+            assert_eq!(
+                request.timestamp_granularities,
+                vec![TimestampGranularity::Word, TimestampGranularity::Segment]
+            );
+
+            let word = Word::builder()
+                .word(String::from("RIFF"))
+                .start(0.0)
+                .end(0.5)
+                .build()
+                .unwrap();
+            Ok(TranscriptionResponse::VerboseJson(VerboseTranscription {
+                text: String::from("RIFF"),
+                duration: 0.5,
+                language: request.language,
+                segments: vec![],
+                words: vec![word],
+            }))
+        });
+
+        let form = "--hfendpoints\r\nContent-Disposition: form-data; name=\"response_format\"\r\n\r\nverbose_json\r\n\
+            --hfendpoints\r\nContent-Disposition: form-data; name=\"timestamp_granularities[]\"\r\n\r\nword\r\n\
+            --hfendpoints\r\nContent-Disposition: form-data; name=\"timestamp_granularities[]\"\r\n\r\nsegment\r\n\
+            --hfendpoints\r\nContent-Disposition: form-data; name=\"file\"; filename=\"a.wav\"\r\n\r\nRIFF\r\n\
+            --hfendpoints--\r\n";
+
+        let router = TranscriptionRouter(sender);
+        let (status, _, body) = post(router, "/audio/transcriptions", MULTIPART_FORM, form).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(
+            body["words"],
+            serde_json::json!([{"word": "RIFF", "start": 0.0, "end": 0.5}])
+        );
+    }
+
+    #[tokio::test]
+    async fn transcribe_renders_vtt_subtitles() {
+        let (sender, receiver) = request_channel(1);
+        answer(receiver, |request: TranscriptionRequest| {
+            let segment = Segment::builder()
+                .id(0)
+                .start(0.0)
+                .end(1.25)
+                .temperature(0.0)
+                .text(String::from(" RIFF"))
+                .tokens(vec![])
+                .build()
+                .unwrap();
+            Ok(TranscriptionResponse::VerboseJson(VerboseTranscription {
+                text: String::from("RIFF"),
+                duration: 1.25,
+                language: request.language,
+                segments: vec![segment],
+                words: vec![],
+            }))
+        });
+
+        let form = "--hfendpoints\r\nContent-Disposition: form-data; name=\"response_format\"\r\n\r\nvtt\r\n\
+            --hfendpoints\r\nContent-Disposition: form-data; name=\"file\"; filename=\"a.wav\"\r\n\r\nRIFF\r\n\
+            --hfendpoints--\r\n";
+
+        let router = TranscriptionRouter(sender);
+        let (status, headers, body) =
+            post(router, "/audio/transcriptions", MULTIPART_FORM, form).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(headers[CONTENT_TYPE], "text/vtt; charset=utf-8");
+        assert_eq!(
+            body,
+            "WEBVTT\n\n00:00:00.000 --> 00:00:01.250\nRIFF\n\n"
+        );
+    }
+
+    #[test]
+    fn timestamp_granularities_require_verbose_json() {
+        let validate = |format: ResponseFormat, granularities: Vec<TimestampGranularity>| {
+            TranscriptionRequest::validate(TranscriptionForm {
+                file: FilePart {
+                    content: Bytes::from_static(b"RIFF"),
+                    content_type: String::from("audio/wav"),
+                    decoding: None,
+                },
+                language: None,
+                model: None,
+                prompt: None,
+                temperature: None,
+                response_format: Some(format),
+                allow_code_switching: None,
+                stream: None,
+                timestamp_granularities: granularities,
+                channels: None,
+            })
+        };
+
+        let request = validate(ResponseFormat::Json, vec![]).unwrap();
+        assert_eq!(
+            request.timestamp_granularities,
+            vec![TimestampGranularity::Segment]
+        );
+
+        assert!(matches!(
+            validate(ResponseFormat::Json, vec![TimestampGranularity::Word]),
+            Err(OpenAiError::Validation(_))
+        ));
+        assert!(matches!(
+            parse_all::<TimestampGranularity>("timestamp_granularities", vec![String::from("token")]),
+            Err(OpenAiError::Validation(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn transcribe_channels_on_their_own() {
+        let (sender, receiver) = request_channel(2);
+
+        // The left channel answers the right one, silent channels being the right one here
+        answer(receiver, |request: TranscriptionRequest| {
+            assert_eq!(request.response_format, ResponseFormat::VerboseJson);
+            let wav = Wav::parse(&request.file).unwrap();
+            assert_eq!(wav.channels, 1);
+
+            let (start, text) = match wav.samples[0] == 0.0 {
+                true => (0.0, " Hello?"),
+                false => (1.0, " Fine."),
+            };
+            let segment = Segment::builder()
+                .id(0)
+                .start(start)
+                .end(start + 1.0)
+                .temperature(0.0)
+                .text(String::from(text))
+                .tokens(vec![])
+                .build()
+                .unwrap();
+            Ok(TranscriptionResponse::VerboseJson(VerboseTranscription {
+                text: String::from(text),
+                duration: 2.0,
+                language: String::from("en"),
+                segments: vec![segment],
+                words: vec![],
+            }))
+        });
+
+        let wav = Wav {
+            sample_rate: 16000,
+            channels: 2,
+            format: SampleFormat::Int16,
+            samples: [0.5, 0.0].repeat(16),
+        };
+        let mut form = b"--hfendpoints\r\nContent-Disposition: form-data; name=\"channels\"\r\n\r\nper_channel\r\n\
+            --hfendpoints\r\nContent-Disposition: form-data; name=\"response_format\"\r\n\r\nsrt\r\n\
+            --hfendpoints\r\nContent-Disposition: form-data; name=\"file\"; filename=\"call.wav\"\r\n\r\n"
+            .to_vec();
+        form.extend_from_slice(&wav.encode());
+        form.extend_from_slice(b"\r\n--hfendpoints--\r\n");
+
+        let router = TranscriptionRouter(sender);
+        let (status, _, body) = post(router, "/audio/transcriptions", MULTIPART_FORM, form).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            body,
+            "1\n00:00:00,000 --> 00:00:01,000\n[right] Hello?\n\n\
+             2\n00:00:01,000 --> 00:00:02,000\n[left] Fine.\n\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn transcribe_streams_events() {
+        let (sender, receiver) = request_channel(1);
+        let (router, _) = OpenApiRouter::from(TranscriptionRouter(sender)).split_for_parts();
+
+        stream(receiver, |request: TranscriptionRequest| {
+            assert!(request.stream);
+
+            let deltas = ["Hello", " world"].map(|delta| {
+                let delta = Delta {
+                    delta: String::from(delta),
+                };
+                Ok(TranscriptionResponse::Event(StreamEvent::Delta(delta)))
+            });
+            let transcription = Transcription {
+                text: String::from("Hello world"),
+            };
+            deltas.into_iter().chain([Ok(TranscriptionResponse::Json(transcription))])
+        });
+
+        let form = "--hfendpoints\r\nContent-Disposition: form-data; name=\"stream\"\r\n\r\ntrue\r\n\
+            --hfendpoints\r\nContent-Disposition: form-data; name=\"file\"; filename=\"a.wav\"\r\n\r\nRIFF\r\n\
+            --hfendpoints--\r\n";
+
+        let streamed = request("/audio/transcriptions")
+            .header(CONTENT_TYPE, MULTIPART_FORM)
+            .body(Body::from(form))
+            .unwrap();
+
+        let (status, headers, body) = send(router.clone(), streamed).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(headers[CONTENT_TYPE], "text/event-stream");
+        assert_eq!(
+            body,
+            "data: {\"type\":\"transcript.text.delta\",\"delta\":\"Hello\"}\nid: 0\n\n\
+             data: {\"type\":\"transcript.text.delta\",\"delta\":\" world\"}\nid: 1\n\n\
+             data: {\"type\":\"transcript.text.done\",\"text\":\"Hello world\"}\nid: 2\n\n"
+        );
+
+        // Reconnecting clients are only sent the events following the last one they received
+        let resumed = request("/audio/transcriptions")
+            .header("last-event-id", "1")
+            .header(CONTENT_TYPE, MULTIPART_FORM)
+            .body(Body::from(form))
+            .unwrap();
+
+        let (status, _, body) = send(router.clone(), resumed).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            body,
+            "data: {\"type\":\"transcript.text.done\",\"text\":\"Hello world\"}\nid: 2\n\n"
+        );
+
+        // Streams are only resumed for the request which created them
+        let request = Request::post("/audio/transcriptions")
+            .header("x-request-id", "unknown")
+            .header("last-event-id", "1")
+            .header(CONTENT_TYPE, MULTIPART_FORM)
+            .body(Body::from(form))
+            .unwrap();
+
+        let (status, _, _) = send(router, request).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn assemble_streamed_deltas() {
+        let delta = |delta: &str| {
+            Ok(TranscriptionResponse::Event(StreamEvent::Delta(Delta {
+                delta: String::from(delta),
+            })))
+        };
+
+        let deltas = futures_util::stream::iter([delta("Hello"), delta(" world")]);
+        match assemble(deltas).await.unwrap() {
+            TranscriptionResponse::Json(transcription) => assert_eq!(transcription.text, "Hello world"),
+            _ => panic!("Expected a json transcription"),
+        }
+
+        let done = Ok(TranscriptionResponse::Event(StreamEvent::Done(Done {
+            text: String::from("Hello world!"),
+        })));
+        let events = futures_util::stream::iter([delta("Hello"), done]);
+        match assemble(events).await.unwrap() {
+            TranscriptionResponse::Json(transcription) => assert_eq!(transcription.text, "Hello world!"),
+            _ => panic!("Expected a json transcription"),
+        }
+
+        let empty = futures_util::stream::iter(Vec::new());
+        assert!(matches!(assemble(empty).await, Err(OpenAiError::NoResponse)));
+    }
 
     #[test]
     fn serialize_stream_event_delta() {
@@ -594,6 +1042,96 @@ mod tests {
         );
     }
 
+    #[test]
+    fn serialize_transcription_responses_untagged() {
+        let json = TranscriptionResponse::Json(Transcription {
+            text: String::from("Hello world"),
+        });
+        assert_eq!(
+            serde_json::to_string(&json).expect("Failed to serialize TranscriptionResponse::Json"),
+            r#"{"text":"Hello world"}"#
+        );
+
+        let text = TranscriptionResponse::Text(String::from("Hello world"));
+        assert_eq!(
+            serde_json::to_string(&text).expect("Failed to serialize TranscriptionResponse::Text"),
+            r#""Hello world""#
+        );
+    }
+
+    #[test]
+    fn serialize_verbose_transcription_snake_case() {
+        let segment = Segment::builder()
+            .id(0)
+            .start(0.0)
+            .end(1.5)
+            .temperature(0.0)
+            .text(String::from("Hello world"))
+            .tokens(vec![1, 2])
+            .build()
+            .expect("Failed to create segment");
+
+        let transcription = VerboseTranscription {
+            text: String::from("Hello world"),
+            duration: 1.5,
+            language: String::from("en"),
+            segments: vec![segment],
+            words: vec![],
+        };
+
+        let json = serde_json::to_value(&transcription)
+            .expect("Failed to serialize VerboseTranscription");
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "text": "Hello world",
+                "duration": 1.5,
+                "language": "en",
+                "segments": [{
+                    "id": 0,
+                    "start": 0.0,
+                    "end": 1.5,
+                    "seek": 0,
+                    "temperature": 0.0,
+                    "text": "Hello world",
+                    "tokens": [1, 2],
+                    "avg_logprob": 0.0,
+                    "compression_ratio": 0.0,
+                    "no_speech_prob": 0.0
+                }]
+            })
+        );
+    }
+
+    #[test]
+    fn serialize_code_switched_segments_language() {
+        let segment = |id, language: &str| {
+            Segment::builder()
+                .id(id)
+                .start(0.0)
+                .end(1.0)
+                .temperature(0.0)
+                .text(String::from("Hola, hello"))
+                .tokens(vec![])
+                .language(String::from(language))
+                .build()
+                .expect("Failed to create segment")
+        };
+
+        let transcription = VerboseTranscription {
+            text: String::from("Hola, hello"),
+            duration: 2.0,
+            language: String::from("es"),
+            segments: vec![segment(0, "es"), segment(1, "en")],
+            words: vec![],
+        };
+
+        let json = serde_json::to_value(&transcription)
+            .expect("Failed to serialize VerboseTranscription");
+        assert_eq!(json["segments"][0]["language"], "es");
+        assert_eq!(json["segments"][1]["language"], "en");
+    }
+
     #[test]
     fn segment_builder_all_field_set() {
         if let Ok(segment) = Segment::builder()
@@ -0,0 +1,303 @@
+use crate::audio::loudness::{LoudnessTarget, loudness_target_from_env, normalize_upload};
+use crate::audio::prompt::{PromptBudget, prompt_budget_from_env};
+use crate::audio::transcription::{ResponseFormat, TranscriptionResponse};
+use crate::audio::{AUDIO_TAG, MAX_AUDIO_BODY_SIZE};
+use crate::body::{BodyLimit, ReceivedBytes, count_received_bytes};
+use crate::context::{Context, RequestContext};
+use crate::endpoint::{schedule_task, task_router};
+use crate::error::{ErrorResponse, TaskErrors};
+use crate::estimate::{EstimatedTask, record_latency};
+use crate::multipart::{FilePart, FromMultipart};
+use crate::policy::RequestPolicy;
+use crate::telemetry::{record_audio_bytes, record_task};
+use crate::usage::record_audio_duration;
+use crate::{OpenAiError, OpenAiResult, TaskRequest};
+use axum::Extension;
+use axum::body::Bytes;
+use axum::extract::{DefaultBodyLimit, Multipart, State};
+use axum::middleware::from_fn;
+use axum::response::{IntoResponse, Response};
+use axum_extra::TypedHeader;
+use headers::ContentLength;
+use hfendpoints_core::{EndpointContext, RequestSender};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::Instant;
+use tower_http::decompression::RequestDecompressionLayer;
+use tracing::instrument;
+use utoipa::ToSchema;
+use utoipa_axum::router::OpenApiRouter;
+use utoipa_axum::routes;
+
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+
+/// Translates audio into English.
+#[derive(ToSchema, FromMultipart)]
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[multipart(file_limit = MAX_AUDIO_BODY_SIZE)]
+struct TranslationForm {
+    /// The audio file object (not file name) to translate, in one of these formats: flac, mp3, mp4, mpeg, mpga, m4a, ogg, wav, or webm.
+    #[schema(value_type = String, format = Binary)]
+    file: FilePart,
+
+    /// Not used, here for compatibility purpose with OpenAI Platform
+    #[allow(dead_code)]
+    model: Option<String>,
+
+    /// An optional text to guide the model's style or continue a previous audio segment.
+    /// The prompt should be in English.
+    prompt: Option<String>,
+
+    /// The sampling temperature, between 0 and 1.
+    /// Higher values like 0.8 will make the output more random, while lower values like 0.2 will make it more focused and deterministic.
+    /// If set to 0, the model will use log probability to automatically increase the temperature until certain thresholds are hit.
+    temperature: Option<f32>,
+
+    /// The format of the output, in one of these options: json, text, srt, verbose_json, or vtt.
+    response_format: Option<ResponseFormat>,
+}
+
+#[cfg_attr(feature = "python", pyclass(frozen))]
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Clone, Deserialize, Serialize)]
+pub struct TranslationRequest {
+    #[serde(with = "crate::base64_bytes")]
+    pub file: Bytes,
+    pub content_type: String,
+    pub prompt: Option<String>,
+    pub temperature: f32,
+    pub response_format: ResponseFormat,
+}
+
+impl From<TranslationForm> for TranslationRequest {
+    fn from(form: TranslationForm) -> Self {
+        #[cfg(feature = "metrics")]
+        hfendpoints_core::endpoint_metrics().record_audio_bytes(form.file.content.len());
+
+        Self {
+            file: form.file.content,
+            content_type: form.file.content_type,
+            prompt: form.prompt,
+            temperature: form.temperature.unwrap_or(0.0),
+            response_format: form.response_format.unwrap_or_default(),
+        }
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/audio/translations",
+    tag = AUDIO_TAG,
+    request_body(content = TranslationForm, content_type = "multipart/form-data"),
+    responses(
+        (status = OK, description = "Translates audio into English.", body = TranscriptionResponse),
+        TaskErrors,
+        (status = PAYLOAD_TOO_LARGE, description = "The uploaded audio exceeds the maximum allowed size, once decompressed.", body = ErrorResponse),
+    )
+)]
+#[instrument(skip(state, policy, budget, metadata, body_limit, received, multipart))]
+#[allow(clippy::too_many_arguments)]
+pub async fn translate(
+    State(state): State<EndpointContext<(TranslationRequest, Context), TranscriptionResponse>>,
+    Extension(policy): Extension<Arc<RequestPolicy>>,
+    Extension(loudness): Extension<Option<LoudnessTarget>>,
+    Extension(budget): Extension<Option<PromptBudget>>,
+    metadata: RequestContext,
+    content_length: Option<TypedHeader<ContentLength>>,
+    body_limit: BodyLimit,
+    Extension(received): Extension<ReceivedBytes>,
+    multipart: Multipart,
+) -> OpenAiResult<Response> {
+    record_task("translation");
+
+    // Reject upfront uploads announcing a size over the limit, the configured one if lower
+    let limit = body_limit.bound(MAX_AUDIO_BODY_SIZE);
+    let content_length = content_length.map(|length| length.0.0);
+    if content_length.is_some_and(|length| length > limit as u64) {
+        return Err(OpenAiError::PayloadTooLarge {
+            limit,
+            received: content_length,
+        });
+    }
+
+    // Decode request, streamed uploads going over the limit are only detected while reading
+    let mut form = TranslationForm::from_multipart(multipart, &policy)
+        .await
+        .map_err(|err| err.with_body_limit(limit, Some(received.get())))?;
+    record_audio_bytes(form.file.content.len());
+
+    // Bring the upload to the configured loudness before it reaches the handler
+    if let Some(target) = loudness {
+        form.file.content = normalize_upload(form.file.content, target).await?;
+    }
+
+    // Keep pathologically long prompts from destabilizing the decoding
+    let truncated = match budget {
+        Some(budget) => budget.enforce(&mut form.prompt)?,
+        None => None,
+    };
+    let request = TranslationRequest::from(form);
+
+    // Ask for the inference thread to handle it and wait for answers
+    let started = Instant::now();
+    let format = request.response_format;
+    let response = schedule_task(&state, metadata, request)
+        .await?
+        .response()
+        .await?;
+    if let Some(duration) = response.audio_duration() {
+        record_latency(EstimatedTask::Translation, duration, started);
+        record_audio_duration(duration);
+    }
+    Ok((truncated, response.into_format(format)).into_response())
+}
+
+/// Helper factory to build
+/// [OpenAi Platform compatible Translation endpoint](https://platform.openai.com/docs/api-reference/audio/createTranslation)
+///
+/// Translations share the response objects of transcriptions, the text being in English.
+#[derive(Clone)]
+pub struct TranslationRouter(
+    pub RequestSender<(TranslationRequest, Context), TranscriptionResponse>,
+);
+
+impl From<TranslationRouter> for OpenApiRouter {
+    fn from(value: TranslationRouter) -> Self {
+        let routes = OpenApiRouter::new().routes(routes!(translate));
+        task_router(routes, value.0, "/audio/translations")
+            .layer(Extension(loudness_target_from_env()))
+            .layer(Extension(prompt_budget_from_env()))
+            .layer(DefaultBodyLimit::max(MAX_AUDIO_BODY_SIZE))
+            .layer(from_fn(count_received_bytes))
+            .layer(RequestDecompressionLayer::new())
+    }
+}
+
+impl TaskRequest for TranslationRequest {
+    type Response = TranscriptionResponse;
+
+    fn router(sender: RequestSender<(Self, Context), Self::Response>) -> OpenApiRouter {
+        TranslationRouter(sender).into()
+    }
+}
+
+#[cfg(feature = "python")]
+pub(crate) mod python {
+    use crate::audio::transcription::ResponseFormat;
+    use crate::audio::transcription::python::TranscriptionResponseKind;
+    use crate::audio::translation::TranslationRequest;
+    use hfendpoints_binding_python::fill_view_from_readonly_data;
+    use pyo3::ffi::Py_buffer;
+    use pyo3::prelude::*;
+    use std::ffi::CString;
+    use tracing::{debug, instrument};
+
+    #[pymethods]
+    impl TranslationRequest {
+        /// Expose the audio as a read-only buffer of bytes, i.e. `memoryview(request)`
+        ///
+        /// # Safety
+        ///
+        /// Only called by the interpreter through the buffer protocol, `buffer` pointing to a
+        /// `Py_buffer` it owns, filled here and released through `__releasebuffer__`.
+        #[instrument(skip(slf, buffer))]
+        pub unsafe fn __getbuffer__(
+            slf: Bound<'_, Self>,
+            buffer: *mut Py_buffer,
+            flags: i32,
+        ) -> PyResult<()> {
+            debug!("Acquiring a memoryview over audio data (flags={})", flags);
+            unsafe {
+                fill_view_from_readonly_data(buffer, flags, &slf.borrow().file, slf.into_any())
+            }
+        }
+
+        /// Release the buffer exposed by `__getbuffer__`
+        ///
+        /// # Safety
+        ///
+        /// Only called by the interpreter through the buffer protocol, with a `buffer` previously
+        /// filled by `__getbuffer__`, and exactly once per such buffer.
+        #[instrument(skip_all)]
+        pub unsafe fn __releasebuffer__(&self, buffer: *mut Py_buffer) {
+            debug!("Releasing Python memoryview");
+            // Release memory held by the format string
+            drop(unsafe { CString::from_raw((*buffer).format) });
+        }
+
+        #[getter]
+        pub fn prompt(&self) -> &Option<String> {
+            &self.prompt
+        }
+
+        #[getter]
+        pub fn temperature(&self) -> f32 {
+            self.temperature
+        }
+
+        #[getter]
+        pub fn response_kind(&self) -> TranscriptionResponseKind {
+            match self.response_format {
+                ResponseFormat::Json => TranscriptionResponseKind::Json,
+                ResponseFormat::Text => TranscriptionResponseKind::Text,
+                ResponseFormat::VerboseJson => TranscriptionResponseKind::VerboseJson,
+                ResponseFormat::Srt => TranscriptionResponseKind::Srt,
+                ResponseFormat::Vtt => TranscriptionResponseKind::Vtt,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::audio::transcription::{ResponseFormat, TranscriptionResponse};
+    use crate::audio::translation::{TranslationRequest, TranslationRouter};
+    use crate::testing::{MULTIPART_FORM, answer, post};
+    use axum::http::StatusCode;
+    use hfendpoints_core::request_channel;
+
+    /// Answer with the uploaded file as the translated text
+    async fn translate(form: &'static str) -> (StatusCode, String) {
+        let (sender, receiver) = request_channel(1);
+        answer(receiver, |request: TranslationRequest| {
+            assert!(matches!(request.response_format, ResponseFormat::Text));
+            let text = String::from_utf8(request.file.to_vec()).unwrap();
+            Ok(TranscriptionResponse::Text(text))
+        });
+
+        let router = TranslationRouter(sender);
+        let (status, _, body) = post(router, "/audio/translations", MULTIPART_FORM, form).await;
+        (status, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    #[tokio::test]
+    async fn translate_accepts_openai_form() {
+        let (status, body) = translate(
+            "--hfendpoints\r\nContent-Disposition: form-data; name=\"model\"\r\n\r\nwhisper-1\r\n\
+             --hfendpoints\r\nContent-Disposition: form-data; name=\"response_format\"\r\n\r\ntext\r\n\
+             --hfendpoints\r\nContent-Disposition: form-data; name=\"file\"; filename=\"a.wav\"\r\n\r\nRIFF\r\n\
+             --hfendpoints--\r\n",
+        )
+        .await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, "RIFF");
+    }
+
+    #[tokio::test]
+    async fn translate_requires_file() {
+        let (status, body) = translate(
+            "--hfendpoints\r\nContent-Disposition: form-data; name=\"response_format\"\r\n\r\ntext\r\n\
+             --hfendpoints--\r\n",
+        )
+        .await;
+
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let body: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(
+            body["error"]["message"],
+            "Required parameter 'file' was not provided"
+        );
+    }
+}
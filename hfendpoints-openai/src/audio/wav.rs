@@ -0,0 +1,169 @@
+//! Minimal RIFF/WAVE codec for the uncompressed uploads, 16-bit integer or 32-bit float PCM.
+use axum::body::Bytes;
+
+/// `WAVE_FORMAT_PCM`
+const FORMAT_PCM: u16 = 1;
+
+/// `WAVE_FORMAT_IEEE_FLOAT`
+const FORMAT_FLOAT: u16 = 3;
+
+/// `WAVE_FORMAT_EXTENSIBLE`, the actual format being the first two bytes of the sub-format GUID
+const FORMAT_EXTENSIBLE: u16 = 0xFFFE;
+
+/// Encoding of the samples of a WAV file
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Copy, Clone, Eq, PartialEq)]
+pub(crate) enum SampleFormat {
+    Int16,
+    Float32,
+}
+
+/// Samples of a WAV file, interleaved and scaled to [-1.0, 1.0]
+pub(crate) struct Wav {
+    pub(crate) sample_rate: u32,
+    pub(crate) channels: u16,
+    pub(crate) format: SampleFormat,
+    pub(crate) samples: Vec<f32>,
+}
+
+#[inline]
+fn u16_at(bytes: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(
+        bytes.get(offset..offset + 2)?.try_into().ok()?,
+    ))
+}
+
+#[inline]
+fn u32_at(bytes: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(
+        bytes.get(offset..offset + 4)?.try_into().ok()?,
+    ))
+}
+
+impl Wav {
+    /// Decode `bytes`, `None` if they are not a WAV file in one of the supported sample formats
+    pub(crate) fn parse(bytes: &[u8]) -> Option<Self> {
+        if bytes.get(0..4)? != b"RIFF" || bytes.get(8..12)? != b"WAVE" {
+            return None;
+        }
+
+        let mut format = None;
+        let mut offset = 12;
+        while offset + 8 <= bytes.len() {
+            let id = &bytes[offset..offset + 4];
+            let size = u32_at(bytes, offset + 4)? as usize;
+            let body = offset + 8;
+
+            match id {
+                b"fmt " => {
+                    let tag = match u16_at(bytes, body)? {
+                        FORMAT_EXTENSIBLE => u16_at(bytes, body + 24)?,
+                        tag => tag,
+                    };
+                    let channels = u16_at(bytes, body + 2)?;
+                    let sample_rate = u32_at(bytes, body + 4)?;
+                    let sample_format = match (tag, u16_at(bytes, body + 14)?) {
+                        (FORMAT_PCM, 16) => SampleFormat::Int16,
+                        (FORMAT_FLOAT, 32) => SampleFormat::Float32,
+                        _ => return None,
+                    };
+                    if channels == 0 || sample_rate == 0 {
+                        return None;
+                    }
+                    format = Some((sample_rate, channels, sample_format));
+                }
+                b"data" => {
+                    let (sample_rate, channels, format) = format?;
+                    // Streamed recordings may announce more data than they hold
+                    let data = &bytes[body..(body + size).min(bytes.len())];
+                    let samples = match format {
+                        SampleFormat::Int16 => data
+                            .chunks_exact(2)
+                            .map(|sample| {
+                                i16::from_le_bytes([sample[0], sample[1]]) as f32 / 32768.0
+                            })
+                            .collect(),
+                        SampleFormat::Float32 => data
+                            .chunks_exact(4)
+                            .map(|sample| f32::from_le_bytes(sample.try_into().unwrap_or_default()))
+                            .collect(),
+                    };
+                    return Some(Self {
+                        sample_rate,
+                        channels,
+                        format,
+                        samples,
+                    });
+                }
+                _ => {}
+            }
+
+            // Chunks are padded to an even size
+            offset = body + size + (size & 1);
+        }
+        None
+    }
+
+    /// Encode the samples back, in their original sample format
+    pub(crate) fn encode(&self) -> Bytes {
+        let (tag, width) = match self.format {
+            SampleFormat::Int16 => (FORMAT_PCM, 2u16),
+            SampleFormat::Float32 => (FORMAT_FLOAT, 4u16),
+        };
+        let data_size = (self.samples.len() * width as usize) as u32;
+        let block_align = self.channels * width;
+
+        let mut wav = Vec::with_capacity(44 + data_size as usize);
+        wav.extend_from_slice(b"RIFF");
+        wav.extend_from_slice(&(36 + data_size).to_le_bytes());
+        wav.extend_from_slice(b"WAVEfmt ");
+        wav.extend_from_slice(&16u32.to_le_bytes());
+        wav.extend_from_slice(&tag.to_le_bytes());
+        wav.extend_from_slice(&self.channels.to_le_bytes());
+        wav.extend_from_slice(&self.sample_rate.to_le_bytes());
+        wav.extend_from_slice(&(self.sample_rate * block_align as u32).to_le_bytes());
+        wav.extend_from_slice(&block_align.to_le_bytes());
+        wav.extend_from_slice(&(width * 8).to_le_bytes());
+        wav.extend_from_slice(b"data");
+        wav.extend_from_slice(&data_size.to_le_bytes());
+
+        for sample in &self.samples {
+            match self.format {
+                SampleFormat::Int16 => {
+                    let sample = (sample.clamp(-1.0, 1.0) * 32767.0).round() as i16;
+                    wav.extend_from_slice(&sample.to_le_bytes());
+                }
+                SampleFormat::Float32 => wav.extend_from_slice(&sample.to_le_bytes()),
+            }
+        }
+        Bytes::from(wav)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::audio::wav::{SampleFormat, Wav};
+
+    #[test]
+    fn round_trip_pcm_and_float_files() {
+        for format in [SampleFormat::Int16, SampleFormat::Float32] {
+            let wav = Wav {
+                sample_rate: 16000,
+                channels: 2,
+                format,
+                samples: vec![0.0, 0.5, -0.5, 0.25],
+            };
+
+            let decoded = Wav::parse(&wav.encode()).unwrap();
+            assert_eq!(decoded.sample_rate, 16000);
+            assert_eq!(decoded.channels, 2);
+            assert_eq!(decoded.format, format);
+            for (decoded, original) in decoded.samples.iter().zip(&wav.samples) {
+                assert!((decoded - original).abs() < 1e-4);
+            }
+        }
+
+        assert!(Wav::parse(b"RIFF....WAVE").is_none());
+        assert!(Wav::parse(b"ID3\x03").is_none());
+    }
+}
@@ -0,0 +1,332 @@
+//! Bearer-token authentication of the routes.
+//!
+//! When `HF_ENDPOINT_API_KEYS` holds a comma-separated list of API keys, every request must carry
+//! one of them as `Authorization: Bearer <key>`, otherwise it is rejected with `401 Unauthorized`
+//! and the error body of the OpenAI API, so SDKs report an invalid API key. The keys may also be
+//! listed in the configuration file, see `config`.
+//!
+//! Tokens issued by an OpenID provider may be accepted as well, or instead, see `oidc`.
+//!
+//! Probes must not need credentials: `/health`, `/health/live`, `/health/ready` and `/metrics` stay
+//! public, which can be changed by listing the public paths in `HF_ENDPOINT_AUTH_EXEMPT` (an empty
+//! value exempting none of them).
+use crate::config::EndpointConfig;
+use crate::error::OpenAiError;
+use crate::oidc::{Identity, Oidc};
+use axum::extract::{Request, State};
+use axum::http::HeaderValue;
+use axum::http::header::AUTHORIZATION;
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+use tracing::{debug, info};
+
+/// Environment variable holding the comma-separated API keys accepted by the endpoint
+pub const API_KEYS_ENV: &str = "HF_ENDPOINT_API_KEYS";
+
+/// Environment variable holding the comma-separated paths reachable without API key
+pub const AUTH_EXEMPT_ENV: &str = "HF_ENDPOINT_AUTH_EXEMPT";
+
+/// Paths reachable without API key unless overridden through `HF_ENDPOINT_AUTH_EXEMPT`
+pub(crate) const DEFAULT_EXEMPT_PATHS: [&str; 4] =
+    ["/health", "/health/live", "/health/ready", "/metrics"];
+
+/// Split a comma-separated environment value, ignoring blank entries
+pub(crate) fn split_list(value: &str) -> impl Iterator<Item = &str> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+}
+
+/// Caller of a request, as identified by the credential it was authenticated with. Serialized to
+/// persist the owner of the batches, see `batches`
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub(crate) enum Caller {
+    /// Digest of the API key, the key itself not being kept around
+    ApiKey([u8; 32]),
+
+    /// Identity held by the token issued by the OpenID provider, see `oidc`
+    Token(Identity),
+}
+
+/// API keys accepted by the endpoint along with the paths which do not require any
+pub struct ApiKeys {
+    /// Keys are compared through their digest, so the time taken does not reveal how much of a key matched
+    digests: Vec<[u8; 32]>,
+    exempt: Vec<String>,
+}
+
+impl ApiKeys {
+    pub fn new<K: AsRef<str>>(keys: impl IntoIterator<Item = K>) -> Self {
+        Self {
+            digests: keys
+                .into_iter()
+                .map(|key| Sha256::digest(key.as_ref().as_bytes()).into())
+                .collect(),
+            exempt: DEFAULT_EXEMPT_PATHS.map(String::from).to_vec(),
+        }
+    }
+
+    /// Let requests for `paths` through without API key
+    pub fn with_exempt<P: Into<String>>(mut self, paths: impl IntoIterator<Item = P>) -> Self {
+        self.exempt = paths.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// API keys configured through `HF_ENDPOINT_API_KEYS` or the configuration file, see
+    /// [`EndpointConfig`], authentication being disabled when none is
+    pub fn from_env() -> Option<Self> {
+        Self::configured(&EndpointConfig::current().api_keys)
+    }
+
+    /// Accept the configured `keys`, the public paths being read from `HF_ENDPOINT_AUTH_EXEMPT`
+    pub(crate) fn configured(keys: &[String]) -> Option<Self> {
+        if keys.is_empty() {
+            return None;
+        }
+
+        let keys = Self::new(keys);
+        let keys = match std::env::var(AUTH_EXEMPT_ENV) {
+            Ok(paths) => keys.with_exempt(split_list(&paths)),
+            Err(_) => keys,
+        };
+
+        info!(
+            "Authenticating requests against {} API key(s), public paths: {:?}",
+            keys.digests.len(),
+            keys.exempt
+        );
+        Some(keys)
+    }
+
+    /// Digest of `key` when it is one of the accepted keys
+    fn accepts(&self, key: &str) -> Option<[u8; 32]> {
+        let digest: [u8; 32] = Sha256::digest(key.as_bytes()).into();
+        self.digests.contains(&digest).then_some(digest)
+    }
+
+    fn exempts(&self, path: &str) -> bool {
+        self.exempt.iter().any(|exempt| exempt == path)
+    }
+}
+
+/// Credentials the bearer tokens are checked against, API keys and tokens of an OpenID provider
+pub(crate) struct Authentication {
+    keys: Option<ApiKeys>,
+    oidc: Option<Oidc>,
+}
+
+impl Authentication {
+    /// Authentication through the configured credentials, disabled when there are none
+    pub(crate) fn new(keys: Option<ApiKeys>, oidc: Option<Oidc>) -> Option<Self> {
+        (keys.is_some() || oidc.is_some()).then_some(Self { keys, oidc })
+    }
+
+    /// Check the request carries one of the API keys or a valid token, unless targeting a path
+    /// every credential exempts, returning the caller they identify
+    async fn authenticate(
+        &self,
+        path: &str,
+        authorization: Option<&HeaderValue>,
+    ) -> Result<Option<Caller>, OpenAiError> {
+        let keys_exempt = self.keys.as_ref().is_none_or(|keys| keys.exempts(path));
+        let oidc_exempt = self.oidc.as_ref().is_none_or(|oidc| oidc.exempts(path));
+        if keys_exempt && oidc_exempt {
+            return Ok(None);
+        }
+
+        let Some(authorization) = authorization else {
+            let credential = if self.keys.is_some() {
+                "API key"
+            } else {
+                "token"
+            };
+            return Err(OpenAiError::Unauthorized(format!(
+                "You didn't provide an {credential}, provide it as 'Authorization: Bearer <{credential}>'"
+            )));
+        };
+
+        // The scheme is case-insensitive (RFC 7235)
+        let key = authorization
+            .to_str()
+            .ok()
+            .and_then(|authorization| authorization.split_once(' '))
+            .filter(|(scheme, _)| scheme.eq_ignore_ascii_case("bearer"))
+            .map(|(_, key)| key.trim());
+
+        let digest = key
+            .zip(self.keys.as_ref())
+            .and_then(|(key, keys)| keys.accepts(key));
+        if let Some(digest) = digest {
+            return Ok(Some(Caller::ApiKey(digest)));
+        }
+
+        match (key, &self.keys, &self.oidc) {
+            // API keys are told apart from tokens by the three dot-separated parts of the latter
+            (Some(token), _, Some(oidc))
+                if token.split('.').count() == 3 || self.keys.is_none() =>
+            {
+                oidc.verify(token)
+                    .await
+                    .map(|identity| Some(Caller::Token(identity)))
+            }
+            (Some(_), _, _) => Err(OpenAiError::Unauthorized(String::from(
+                "Incorrect API key provided",
+            ))),
+            (None, _, _) => Err(OpenAiError::Unauthorized(String::from(
+                "Malformed Authorization header, expected 'Bearer <API key>'",
+            ))),
+        }
+    }
+}
+
+/// Reject the requests which do not carry one of the configured API keys or a valid token, the
+/// caller they identify being attached to the request, along with the identity held by a token
+pub(crate) async fn authenticate(
+    State(authentication): State<Arc<Authentication>>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    // The request itself is not held across the verification, its body not being `Sync`
+    let authenticated = authentication
+        .authenticate(request.uri().path(), request.headers().get(AUTHORIZATION))
+        .await;
+    match authenticated {
+        Ok(caller) => {
+            if let Some(Caller::Token(identity)) = &caller {
+                request.extensions_mut().insert(identity.clone());
+            }
+            if let Some(caller) = caller {
+                request.extensions_mut().insert(caller);
+            }
+            next.run(request).await
+        }
+        Err(err) => {
+            debug!(
+                "Rejecting unauthenticated request to {}",
+                request.uri().path()
+            );
+            err.into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::auth::{ApiKeys, Authentication, authenticate};
+    use crate::oidc::Oidc;
+    use axum::Router;
+    use axum::body::{Body, to_bytes};
+    use axum::http::header::{AUTHORIZATION, WWW_AUTHENTICATE};
+    use axum::http::{Request, StatusCode};
+    use axum::middleware::from_fn_with_state;
+    use axum::routing::get;
+    use std::sync::Arc;
+    use tower::ServiceExt;
+
+    fn router(keys: ApiKeys) -> Router {
+        routes(Authentication::new(Some(keys), None).unwrap())
+    }
+
+    fn routes(authentication: Authentication) -> Router {
+        Router::new()
+            .route("/health", get(|| async { "ok" }))
+            .route("/health/ready", get(|| async { "ok" }))
+            .route("/api/v1/models", get(|| async { "models" }))
+            .layer(from_fn_with_state(Arc::new(authentication), authenticate))
+    }
+
+    fn request(path: &str, authorization: Option<&str>) -> Request<Body> {
+        let request = Request::get(path);
+        let request = match authorization {
+            Some(authorization) => request.header(AUTHORIZATION, authorization),
+            None => request,
+        };
+        request.body(Body::empty()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn accept_configured_keys() {
+        for authorization in ["Bearer key-1", "bearer key-2"] {
+            let response = router(ApiKeys::new(["key-1", "key-2"]))
+                .oneshot(request("/api/v1/models", Some(authorization)))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+        }
+    }
+
+    #[tokio::test]
+    async fn reject_missing_or_invalid_keys() {
+        for authorization in [
+            None,
+            Some("Bearer key-3"),
+            Some("Basic a2V5LTE="),
+            Some("key-1"),
+        ] {
+            let response = router(ApiKeys::new(["key-1"]))
+                .oneshot(request("/api/v1/models", authorization))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+            assert_eq!(response.headers()[WWW_AUTHENTICATE], "Bearer");
+
+            let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+            assert_eq!(body["error"]["code"], "invalid_api_key");
+        }
+    }
+
+    #[tokio::test]
+    async fn exempt_probes() {
+        for probe in ["/health", "/health/ready"] {
+            let response = router(ApiKeys::new(["key-1"]))
+                .oneshot(request(probe, None))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+        }
+
+        let keys = ApiKeys::new(["key-1"]).with_exempt(Vec::<String>::new());
+        let response = router(keys)
+            .oneshot(request("/health", None))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn tell_api_keys_and_tokens_apart() {
+        let oidc = Oidc::new("https://login.example.com", ["my-endpoint"])
+            .jwks_uri("file:///nonexistent/jwks.json");
+        let authentication = Authentication::new(Some(ApiKeys::new(["key-1"])), Some(oidc));
+        let router = routes(authentication.unwrap());
+
+        let response = router
+            .clone()
+            .oneshot(request("/api/v1/models", Some("Bearer key-1")))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        for (authorization, message) in [
+            ("Bearer key-2", "Incorrect API key provided"),
+            ("Bearer e30.e30.sig", "Invalid token"),
+        ] {
+            let response = router
+                .clone()
+                .oneshot(request("/api/v1/models", Some(authorization)))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+            let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+            let error = body["error"]["message"].as_str().unwrap();
+            assert!(error.starts_with(message), "{error}");
+        }
+    }
+}
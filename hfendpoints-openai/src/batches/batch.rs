@@ -0,0 +1,669 @@
+//! Batches of requests processed in the background, following the OpenAI Batch API.
+//!
+//! When `HFENDPOINT_JOBS_DIR` is set, `POST /batches` accepts a JSONL file, each line describing a
+//! request to one of the task routes (`{"custom_id": ..., "method": "POST", "url": "/v1/embeddings",
+//! "body": {...}}`). The file is sent as the body of the request, the endpoint not serving the Files
+//! API. Batches are persisted in this directory and their requests replayed through the task routes
+//! one at a time, only while no interactive request waits in the queue, see `JobRunner`.
+//!
+//! The outcome of each request is retrieved as JSON lines through `GET /batches/{batch_id}/output`,
+//! the requests answered with an error status through `GET /batches/{batch_id}/errors`.
+//!
+//! A batch is only exposed to the caller which created it, as identified by its API key or token,
+//! and its requests are replayed on behalf of this caller.
+use crate::auth::Caller;
+use crate::batches::BATCHES_TAG;
+use crate::{ErrorResponse, OpenAiError, OpenAiResult};
+use axum::body::{Body, Bytes, to_bytes};
+use axum::extract::{Path, State};
+use axum::http::Request;
+use axum::http::header::CONTENT_TYPE;
+use axum::response::{IntoResponse, Response};
+use axum::{Extension, Json};
+use hfendpoints_core::{Job, JobError, JobRunner, JobStatus, JobStore, Readiness};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::collections::{BTreeSet, HashSet};
+use std::sync::Arc;
+use tokio::task::JoinHandle;
+use tower::ServiceExt;
+use tracing::instrument;
+use utoipa::ToSchema;
+use utoipa_axum::router::OpenApiRouter;
+use utoipa_axum::routes;
+
+/// Prefix of the ids of the batches
+const BATCH_ID_PREFIX: &str = "batch_";
+
+/// Prefix of the urls of the requests of a batch, as sent to the OpenAI API
+const BATCH_URL_PREFIX: &str = "/v1";
+
+/// Content type of the outcomes of the requests of a batch
+const JSONL_CONTENT_TYPE: &str = "application/jsonl";
+
+/// Marks the requests of a batch replayed by the endpoint, see `dispatch`. The batch file being
+/// decrypted when the batch is created, they are not expected to be encrypted again
+#[derive(Clone, Copy)]
+pub(crate) struct Replayed;
+
+/// Request of a batch, as found on every line of its file
+#[derive(Deserialize)]
+struct BatchRequestLine {
+    custom_id: String,
+    method: String,
+    url: String,
+    #[serde(default)]
+    body: Value,
+}
+
+/// The current status of the batch.
+#[derive(Clone, Copy, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum BatchStatus {
+    InProgress,
+    Completed,
+    Failed,
+    Cancelling,
+    Cancelled,
+}
+
+impl From<JobStatus> for BatchStatus {
+    fn from(value: JobStatus) -> Self {
+        match value {
+            JobStatus::InProgress => Self::InProgress,
+            JobStatus::Completed => Self::Completed,
+            JobStatus::Failed => Self::Failed,
+            JobStatus::Cancelling => Self::Cancelling,
+            JobStatus::Cancelled => Self::Cancelled,
+        }
+    }
+}
+
+/// The request counts for different statuses within the batch.
+#[derive(Clone, Copy, Serialize, ToSchema)]
+pub struct BatchRequestCounts {
+    /// Total number of requests in the batch.
+    total: usize,
+
+    /// Number of requests that have been completed successfully.
+    completed: usize,
+
+    /// Number of requests that have failed.
+    failed: usize,
+}
+
+/// A batch of requests processed in the background.
+#[derive(Clone, Serialize, ToSchema)]
+pub struct Batch {
+    id: String,
+
+    /// The object type, which is always `batch`.
+    object: &'static str,
+
+    /// The API endpoint used by the batch.
+    endpoint: String,
+
+    /// The current status of the batch.
+    status: BatchStatus,
+
+    /// The Unix timestamp (in seconds) for when the batch was created.
+    created_at: u64,
+
+    /// The Unix timestamp (in seconds) for when the batch was completed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    completed_at: Option<u64>,
+
+    /// The Unix timestamp (in seconds) for when the batch failed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    failed_at: Option<u64>,
+
+    /// The Unix timestamp (in seconds) for when the batch was cancelled.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cancelled_at: Option<u64>,
+
+    /// Why the batch failed, if it did.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    failure: Option<String>,
+
+    /// The request counts for different statuses within the batch.
+    request_counts: BatchRequestCounts,
+}
+
+impl From<Job> for Batch {
+    fn from(job: Job) -> Self {
+        let finished_at = |status| (job.status == status).then_some(job.finished_at).flatten();
+        Self {
+            object: "batch",
+            endpoint: format!("{BATCH_URL_PREFIX}{}", job.kind),
+            status: job.status.into(),
+            created_at: job.created_at,
+            completed_at: finished_at(JobStatus::Completed),
+            failed_at: finished_at(JobStatus::Failed),
+            cancelled_at: finished_at(JobStatus::Cancelled),
+            request_counts: BatchRequestCounts {
+                total: job.counts.total,
+                completed: job.counts.completed,
+                failed: job.counts.failed,
+            },
+            failure: job.failure,
+            id: job.id,
+        }
+    }
+}
+
+/// The batches known to the endpoint.
+#[derive(Serialize, ToSchema)]
+pub struct BatchList {
+    object: &'static str,
+    data: Vec<Batch>,
+    has_more: bool,
+}
+
+/// Batches persisted in `store`, their requests being sent to one of the task `routes`
+#[derive(Clone)]
+struct Batches {
+    store: Arc<JobStore>,
+    routes: Arc<BTreeSet<String>>,
+}
+
+impl Batches {
+    /// The batch `id`, batches created by another caller than `caller` not being revealed to exist
+    fn get(&self, id: &str, caller: Option<&Caller>) -> OpenAiResult<Job> {
+        let job = self.store.get(id)?;
+        if is_owner(&job, caller) {
+            Ok(job)
+        } else {
+            Err(JobError::NotFound(id.to_string()).into())
+        }
+    }
+
+    /// Requests of the batch described by `file`, along with the route they are sent to
+    fn parse(&self, file: &[u8]) -> OpenAiResult<(String, Vec<Value>)> {
+        let mut route = None;
+        let mut custom_ids = HashSet::new();
+        let mut requests = Vec::new();
+        let lines = file.split(|&byte| byte == b'\n');
+        for (number, line) in lines.enumerate().map(|(index, line)| (index + 1, line)) {
+            if line.iter().all(u8::is_ascii_whitespace) {
+                continue;
+            }
+
+            let invalid = |reason: String| {
+                OpenAiError::Validation(format!("Line {number} of the batch is invalid: {reason}"))
+            };
+            let value: Value =
+                serde_json::from_slice(line).map_err(|err| invalid(err.to_string()))?;
+            let request =
+                BatchRequestLine::deserialize(&value).map_err(|err| invalid(err.to_string()))?;
+
+            if !custom_ids.insert(request.custom_id.clone()) {
+                return Err(invalid(format!(
+                    "custom_id '{}' is duplicated",
+                    request.custom_id
+                )));
+            }
+            if request.method != "POST" {
+                return Err(invalid(String::from("method must be POST")));
+            }
+            if request.body.get("stream").and_then(Value::as_bool) == Some(true) {
+                return Err(invalid(String::from("streamed requests cannot be batched")));
+            }
+
+            let path = request
+                .url
+                .strip_prefix(BATCH_URL_PREFIX)
+                .filter(|path| self.routes.contains(*path))
+                .ok_or_else(|| {
+                    invalid(format!(
+                        "url '{}' is not served by the endpoint",
+                        request.url
+                    ))
+                })?;
+            match &route {
+                None => route = Some(path.to_string()),
+                Some(route) if route != path => {
+                    return Err(invalid(String::from(
+                        "every request of a batch must be sent to the same url",
+                    )));
+                }
+                Some(_) => {}
+            }
+            requests.push(value);
+        }
+
+        match route {
+            Some(route) => Ok((route, requests)),
+            None => Err(OpenAiError::Validation(String::from(
+                "The batch does not hold any request",
+            ))),
+        }
+    }
+}
+
+/// Owner of the jobs created by `caller`, see `Job::owner`
+fn owner(caller: Option<&Caller>) -> Option<Value> {
+    caller.map(|caller| serde_json::to_value(caller).unwrap_or_default())
+}
+
+/// Whether the batch `job` was created by `caller`
+fn is_owner(job: &Job, caller: Option<&Caller>) -> bool {
+    job.owner == owner(caller)
+}
+
+/// Outcomes of the requests of a batch, as JSON lines
+fn jsonl(outcomes: Vec<u8>) -> Response {
+    ([(CONTENT_TYPE, JSONL_CONTENT_TYPE)], outcomes).into_response()
+}
+
+#[utoipa::path(
+    post,
+    path = "/batches",
+    tag = BATCHES_TAG,
+    request_body(content = String, description = "JSONL file of the requests of the batch", content_type = "application/jsonl"),
+    responses(
+        (status = OK, description = "Creates and executes a batch from the uploaded file of requests.", body = Batch),
+        (status = BAD_REQUEST, description = "The file of requests is invalid.", body = ErrorResponse),
+    )
+)]
+#[instrument(skip_all)]
+async fn create_batch(
+    State(batches): State<Batches>,
+    caller: Option<Extension<Caller>>,
+    file: Bytes,
+) -> OpenAiResult<Json<Batch>> {
+    let (route, requests) = batches.parse(&file)?;
+    let owner = owner(caller.as_deref());
+    let job = batches
+        .store
+        .create(BATCH_ID_PREFIX, &route, owner, &requests)?;
+    Ok(Json(job.into()))
+}
+
+#[utoipa::path(
+    get,
+    path = "/batches",
+    tag = BATCHES_TAG,
+    responses(
+        (status = OK, description = "List your organization's batches.", body = BatchList),
+    )
+)]
+#[instrument(skip_all)]
+async fn list_batches(
+    State(batches): State<Batches>,
+    caller: Option<Extension<Caller>>,
+) -> OpenAiResult<Json<BatchList>> {
+    let data = batches
+        .store
+        .list()?
+        .into_iter()
+        .filter(|job| is_owner(job, caller.as_deref()))
+        .map(Batch::from)
+        .collect();
+    Ok(Json(BatchList {
+        object: "list",
+        data,
+        has_more: false,
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/batches/{batch_id}",
+    tag = BATCHES_TAG,
+    params(("batch_id" = String, Path, description = "The ID of the batch to retrieve.")),
+    responses(
+        (status = OK, description = "Retrieves a batch.", body = Batch),
+        (status = NOT_FOUND, description = "The batch does not exist.", body = ErrorResponse),
+    )
+)]
+#[instrument(skip(batches, caller))]
+async fn retrieve_batch(
+    State(batches): State<Batches>,
+    caller: Option<Extension<Caller>>,
+    Path(batch_id): Path<String>,
+) -> OpenAiResult<Json<Batch>> {
+    Ok(Json(batches.get(&batch_id, caller.as_deref())?.into()))
+}
+
+#[utoipa::path(
+    post,
+    path = "/batches/{batch_id}/cancel",
+    tag = BATCHES_TAG,
+    params(("batch_id" = String, Path, description = "The ID of the batch to cancel.")),
+    responses(
+        (status = OK, description = "Cancels an in-progress batch, the request being processed completing first.", body = Batch),
+        (status = NOT_FOUND, description = "The batch does not exist.", body = ErrorResponse),
+    )
+)]
+#[instrument(skip(batches, caller))]
+async fn cancel_batch(
+    State(batches): State<Batches>,
+    caller: Option<Extension<Caller>>,
+    Path(batch_id): Path<String>,
+) -> OpenAiResult<Json<Batch>> {
+    batches.get(&batch_id, caller.as_deref())?;
+    Ok(Json(batches.store.cancel(&batch_id)?.into()))
+}
+
+#[utoipa::path(
+    get,
+    path = "/batches/{batch_id}/output",
+    tag = BATCHES_TAG,
+    params(("batch_id" = String, Path, description = "The ID of the batch.")),
+    responses(
+        (status = OK, description = "Responses of the requests of the batch processed so far.", body = String, content_type = "application/jsonl"),
+        (status = NOT_FOUND, description = "The batch does not exist.", body = ErrorResponse),
+    )
+)]
+#[instrument(skip(batches, caller))]
+async fn batch_output(
+    State(batches): State<Batches>,
+    caller: Option<Extension<Caller>>,
+    Path(batch_id): Path<String>,
+) -> OpenAiResult<Response> {
+    batches.get(&batch_id, caller.as_deref())?;
+    Ok(jsonl(batches.store.output(&batch_id)?))
+}
+
+#[utoipa::path(
+    get,
+    path = "/batches/{batch_id}/errors",
+    tag = BATCHES_TAG,
+    params(("batch_id" = String, Path, description = "The ID of the batch.")),
+    responses(
+        (status = OK, description = "Responses of the requests of the batch which failed so far.", body = String, content_type = "application/jsonl"),
+        (status = NOT_FOUND, description = "The batch does not exist.", body = ErrorResponse),
+    )
+)]
+#[instrument(skip(batches, caller))]
+async fn batch_errors(
+    State(batches): State<Batches>,
+    caller: Option<Extension<Caller>>,
+    Path(batch_id): Path<String>,
+) -> OpenAiResult<Response> {
+    batches.get(&batch_id, caller.as_deref())?;
+    Ok(jsonl(batches.store.errors(&batch_id)?))
+}
+
+/// Routes managing the batches persisted in `store`, sent to one of the task `routes`
+pub(crate) fn router(store: Arc<JobStore>, routes: BTreeSet<String>) -> OpenApiRouter {
+    OpenApiRouter::new()
+        .routes(routes!(create_batch, list_batches))
+        .routes(routes!(retrieve_batch))
+        .routes(routes!(cancel_batch))
+        .routes(routes!(batch_output))
+        .routes(routes!(batch_errors))
+        .with_state(Batches {
+            store,
+            routes: Arc::new(routes),
+        })
+}
+
+/// Send the request of the batch `job` at `index` to `tasks` on behalf of the caller which created
+/// the batch, the outcome being the response line of the batch output
+async fn dispatch(
+    tasks: axum::Router,
+    job: &Job,
+    index: usize,
+    request: Value,
+) -> Result<Value, Value> {
+    let request_id = format!("{}-{index}", job.id);
+    let line = BatchRequestLine::deserialize(&request);
+    let custom_id = line.as_ref().map(|line| line.custom_id.clone()).ok();
+    let failed = |message: String| {
+        json!({
+            "id": request_id,
+            "custom_id": custom_id,
+            "response": null,
+            "error": {"code": "invalid_request", "message": message},
+        })
+    };
+    let line = line.map_err(|err| failed(err.to_string()))?;
+
+    let mut request = Request::post(&job.kind)
+        .header(CONTENT_TYPE, "application/json")
+        .header("x-request-id", &request_id)
+        .extension(Replayed)
+        .body(Body::from(line.body.to_string()))
+        .map_err(|err| failed(err.to_string()))?;
+
+    // Handlers and usage records see the caller as if it sent the request itself, see `authenticate`
+    let caller = job.owner.clone().map(serde_json::from_value::<Caller>);
+    match caller {
+        Some(Ok(caller)) => {
+            if let Caller::Token(identity) = &caller {
+                request.extensions_mut().insert(identity.clone());
+            }
+            request.extensions_mut().insert(caller);
+        }
+        Some(Err(err)) => return Err(failed(format!("Malformed batch owner: {err}"))),
+        None => {}
+    }
+
+    let Ok(response) = tasks.oneshot(request).await;
+
+    let status = response.status();
+    let body = match to_bytes(response.into_body(), usize::MAX).await {
+        Ok(body) => serde_json::from_slice(&body)
+            .unwrap_or_else(|_| Value::String(String::from_utf8_lossy(&body).into_owned())),
+        Err(err) => return Err(failed(err.to_string())),
+    };
+    let outcome = json!({
+        "id": request_id,
+        "custom_id": custom_id,
+        "response": {"status_code": status.as_u16(), "request_id": request_id, "body": body},
+        "error": null,
+    });
+    if status.is_success() {
+        Ok(outcome)
+    } else {
+        Err(outcome)
+    }
+}
+
+/// Replay the requests of the batches persisted in `store` through `tasks`, the task routes as
+/// served, at low priority, see `JobRunner`
+pub(crate) fn spawn_runner(
+    store: Arc<JobStore>,
+    readiness: Arc<Readiness>,
+    tasks: axum::Router,
+) -> JoinHandle<()> {
+    JobRunner::spawn(store, readiness, move |job, index, request| {
+        let (tasks, job) = (tasks.clone(), job.clone());
+        async move { dispatch(tasks, &job, index, request).await }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::auth::Caller;
+    use crate::batches::batch::{router, spawn_runner};
+    use crate::oidc::Identity;
+    use axum::body::{Body, to_bytes};
+    use axum::http::{Method, Request, StatusCode};
+    use axum::middleware::{Next, from_fn};
+    use axum::routing::post;
+    use axum::{Extension, Router};
+    use hfendpoints_core::{JobStore, Readiness};
+    use serde_json::Value;
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tower::ServiceExt;
+
+    async fn call(batches: &Router, request: Request<Body>) -> (StatusCode, Vec<u8>) {
+        let response = batches.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, body.to_vec())
+    }
+
+    #[tokio::test]
+    async fn replay_batches_through_task_routes() {
+        let dir = std::env::temp_dir().join(format!("batches-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let store = Arc::new(JobStore::open(&dir).unwrap());
+
+        // Task route answering 400 to empty inputs
+        let tasks = Router::new().route(
+            "/echo",
+            post(|body: String| async move {
+                let input: Value = serde_json::from_str(&body).unwrap();
+                match input["input"].as_str() {
+                    Some("") => (StatusCode::BAD_REQUEST, String::from("empty")),
+                    _ => (StatusCode::OK, body),
+                }
+            }),
+        );
+        spawn_runner(Arc::clone(&store), Arc::new(Readiness::new(None)), tasks);
+        let (batches, _) = router(store, ["/echo".to_string()].into()).split_for_parts();
+
+        // Requests must all be sent to a task route
+        let unknown = r#"{"custom_id": "1", "method": "POST", "url": "/v1/unknown", "body": {}}"#;
+        let (status, _) = call(
+            &batches,
+            Request::post("/batches").body(Body::from(unknown)).unwrap(),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let file = concat!(
+            r#"{"custom_id": "a", "method": "POST", "url": "/v1/echo", "body": {"input": "hello"}}"#,
+            "\n",
+            r#"{"custom_id": "b", "method": "POST", "url": "/v1/echo", "body": {"input": ""}}"#,
+            "\n"
+        );
+        let (status, body) = call(
+            &batches,
+            Request::post("/batches").body(Body::from(file)).unwrap(),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        let batch: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(batch["endpoint"], "/v1/echo");
+        assert_eq!(batch["request_counts"]["total"], 2);
+        let id = batch["id"].as_str().unwrap().to_string();
+        assert!(id.starts_with("batch_"));
+
+        let mut batch = Value::Null;
+        for _ in 0..200 {
+            let request = Request::get(format!("/batches/{id}"))
+                .body(Body::empty())
+                .unwrap();
+            batch = serde_json::from_slice(&call(&batches, request).await.1).unwrap();
+            if batch["status"] == "completed" {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(batch["status"], "completed");
+        assert_eq!(batch["request_counts"]["completed"], 1);
+        assert_eq!(batch["request_counts"]["failed"], 1);
+
+        let request = Request::get(format!("/batches/{id}/output"))
+            .body(Body::empty())
+            .unwrap();
+        let output: Value = serde_json::from_slice(&call(&batches, request).await.1).unwrap();
+        assert_eq!(output["custom_id"], "a");
+        assert_eq!(output["response"]["status_code"], 200);
+        assert_eq!(output["response"]["body"]["input"], "hello");
+
+        let request = Request::get(format!("/batches/{id}/errors"))
+            .body(Body::empty())
+            .unwrap();
+        let errors: Value = serde_json::from_slice(&call(&batches, request).await.1).unwrap();
+        assert_eq!(errors["custom_id"], "b");
+        assert_eq!(errors["response"]["status_code"], 400);
+        assert_eq!(errors["response"]["body"], "empty");
+
+        let request = Request::get("/batches/batch_unknown")
+            .body(Body::empty())
+            .unwrap();
+        assert_eq!(call(&batches, request).await.0, StatusCode::NOT_FOUND);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn scope_batches_to_their_caller() {
+        let dir = std::env::temp_dir().join(format!("batches-callers-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let store = Arc::new(JobStore::open(&dir).unwrap());
+
+        // Task route answering with the subject of the caller it was sent on behalf of
+        let tasks = Router::new().route(
+            "/whoami",
+            post(|identity: Option<Extension<Identity>>| async move {
+                identity
+                    .and_then(|Extension(identity)| identity.subject)
+                    .unwrap_or_default()
+            }),
+        );
+        spawn_runner(Arc::clone(&store), Arc::new(Readiness::new(None)), tasks);
+
+        // Callers are authenticated from the subject they claim, see `authenticate`
+        let (batches, _) = router(store, ["/whoami".to_string()].into())
+            .layer(from_fn(
+                |mut request: Request<Body>, next: Next| async move {
+                    let subject = request.headers()["x-subject"].to_str().unwrap().to_string();
+                    request.extensions_mut().insert(Caller::Token(Identity {
+                        subject: Some(subject),
+                        tenant: None,
+                    }));
+                    next.run(request).await
+                },
+            ))
+            .split_for_parts();
+        let request = |method: Method, uri: &str, subject: &str, body: &'static str| {
+            Request::builder()
+                .method(method)
+                .uri(uri)
+                .header("x-subject", subject)
+                .body(Body::from(body))
+                .unwrap()
+        };
+
+        let file = r#"{"custom_id": "a", "method": "POST", "url": "/v1/whoami", "body": {}}"#;
+        let (status, body) = call(&batches, request(Method::POST, "/batches", "alice", file)).await;
+        assert_eq!(status, StatusCode::OK);
+        let batch: Value = serde_json::from_slice(&body).unwrap();
+        let id = batch["id"].as_str().unwrap().to_string();
+
+        // Batches of other callers are not revealed to exist
+        let (_, body) = call(&batches, request(Method::GET, "/batches", "bob", "")).await;
+        let list: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(list["data"], Value::Array(Vec::new()));
+        for (method, uri) in [
+            (Method::GET, format!("/batches/{id}")),
+            (Method::POST, format!("/batches/{id}/cancel")),
+            (Method::GET, format!("/batches/{id}/output")),
+            (Method::GET, format!("/batches/{id}/errors")),
+        ] {
+            let (status, _) = call(&batches, request(method, &uri, "bob", "")).await;
+            assert_eq!(status, StatusCode::NOT_FOUND, "{uri}");
+        }
+
+        let (_, body) = call(&batches, request(Method::GET, "/batches", "alice", "")).await;
+        let list: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(list["data"][0]["id"], id.as_str());
+
+        // Requests are replayed on behalf of the caller which created the batch
+        let mut batch = Value::Null;
+        for _ in 0..200 {
+            let uri = format!("/batches/{id}");
+            let (_, body) = call(&batches, request(Method::GET, &uri, "alice", "")).await;
+            batch = serde_json::from_slice(&body).unwrap();
+            if batch["status"] == "completed" {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(batch["status"], "completed");
+
+        let uri = format!("/batches/{id}/output");
+        let (_, body) = call(&batches, request(Method::GET, &uri, "alice", "")).await;
+        let output: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(output["response"]["body"], "alice");
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
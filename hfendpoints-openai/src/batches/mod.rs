@@ -0,0 +1,5 @@
+#[cfg(feature = "jobs")]
+pub mod batch;
+
+pub const BATCHES_TAG: &str = "Batch";
+pub const BATCHES_DESC: &str = "Create large batches of API requests for asynchronous processing, their results being retrieved once completed.";
@@ -0,0 +1,200 @@
+//! Reading of the request bodies under a size limit.
+//!
+//! Bodies announcing their size through `Content-Length` are rejected upfront when over the limit,
+//! streamed ones (`Transfer-Encoding: chunked`) only once reading them goes over it. The
+//! `PayloadTooLarge` errors of the latter report how many bytes were read when the limit was hit,
+//! the client being told the limit was not missed by a single byte.
+use crate::{OpenAiError, OpenAiResult};
+use axum::body::{Body, Bytes};
+use axum::extract::{FromRequestParts, Request};
+use axum::http::request::Parts;
+use axum::middleware::Next;
+use axum::response::Response;
+use futures_util::{StreamExt, TryStreamExt};
+use http_body_util::LengthLimitError;
+use std::convert::Infallible;
+use std::error::Error;
+use std::io::Error as IoError;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Whether reading a body failed because an outer layer limits its size, i.e. `RequestBodyLimitLayer`
+fn is_length_limit(err: &axum::Error) -> bool {
+    let mut source: Option<&(dyn Error + 'static)> = Some(err);
+    while let Some(err) = source {
+        if err.is::<LengthLimitError>() {
+            return true;
+        }
+        source = err.source();
+    }
+    false
+}
+
+/// Read `body` whole, failing once more than `limit` bytes were read. Callers pass the lowest of
+/// their limit and the configured one, see [`BodyLimit::bound`], the one reported to the client
+pub(crate) async fn read_limited(body: Body, limit: usize) -> OpenAiResult<Bytes> {
+    let mut stream = body.into_data_stream();
+    let mut payload = Vec::new();
+    while let Some(chunk) = stream.next().await {
+        // Outer layers may enforce a lower limit, other failures (i.e. the client going away)
+        // are not the size of the body to blame
+        let chunk = match chunk {
+            Ok(chunk) => chunk,
+            Err(err) if is_length_limit(&err) => {
+                return Err(OpenAiError::PayloadTooLarge {
+                    limit,
+                    received: Some(payload.len() as u64),
+                });
+            }
+            Err(err) => return Err(OpenAiError::Io(IoError::other(err))),
+        };
+
+        payload.extend_from_slice(&chunk);
+        if payload.len() > limit {
+            return Err(OpenAiError::PayloadTooLarge {
+                limit,
+                received: Some(payload.len() as u64),
+            });
+        }
+    }
+    Ok(Bytes::from(payload))
+}
+
+/// Body limit configured on the server, see `ServerBuilder::body_limit`, enforced on top of the
+/// limit of each route. Routes extract it to report the limit actually enforced
+#[derive(Clone, Copy, Default)]
+pub struct BodyLimit(pub(crate) Option<usize>);
+
+impl BodyLimit {
+    /// Limit enforced on the bodies of a route accepting up to `route` bytes
+    pub(crate) fn bound(self, route: usize) -> usize {
+        self.0.map_or(route, |limit| limit.min(route))
+    }
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for BodyLimit {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _: &S) -> Result<Self, Self::Rejection> {
+        Ok(parts.extensions.get::<Self>().copied().unwrap_or_default())
+    }
+}
+
+/// Count of the bytes read from the body of a request, see [`count_received_bytes`]
+#[derive(Clone, Default)]
+pub struct ReceivedBytes(Arc<AtomicU64>);
+
+impl ReceivedBytes {
+    pub(crate) fn get(&self) -> u64 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Count the bytes of the request body as extractors read it, i.e. `Multipart`, exposing the
+/// count to the route through the [`ReceivedBytes`] extension
+pub(crate) async fn count_received_bytes(mut request: Request, next: Next) -> Response {
+    let received = ReceivedBytes::default();
+    request.extensions_mut().insert(received.clone());
+
+    let request = request.map(|body| {
+        Body::from_stream(body.into_data_stream().inspect_ok(move |chunk| {
+            received.0.fetch_add(chunk.len() as u64, Ordering::Relaxed);
+        }))
+    });
+    next.run(request).await
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::OpenAiError;
+    use crate::body::{ReceivedBytes, count_received_bytes, read_limited};
+    use axum::body::{Body, Bytes};
+    use axum::extract::{DefaultBodyLimit, Multipart, Request};
+    use axum::http::StatusCode;
+    use axum::http::header::CONTENT_TYPE;
+    use axum::middleware::from_fn;
+    use axum::routing::post;
+    use axum::{Extension, Router};
+    use futures_util::stream;
+    use http_body_util::Limited;
+    use std::convert::Infallible;
+    use std::io;
+    use tower::ServiceExt;
+
+    fn chunked(chunks: usize) -> Body {
+        let chunk = Bytes::from_static(b"0123456789");
+        Body::from_stream(stream::iter(
+            (0..chunks).map(move |_| Ok::<_, Infallible>(chunk.clone())),
+        ))
+    }
+
+    #[tokio::test]
+    async fn report_bytes_read_over_the_limit() {
+        assert_eq!(read_limited(chunked(2), 20).await.unwrap().len(), 20);
+
+        let err = read_limited(chunked(4), 25).await.unwrap_err();
+        assert!(matches!(
+            err,
+            OpenAiError::PayloadTooLarge {
+                limit: 25,
+                received: Some(30)
+            }
+        ));
+    }
+
+    #[tokio::test]
+    async fn blame_only_limits_for_failed_reads() {
+        // The outer layer enforcing a lower limit, the caller passes it along
+        let limited = Body::new(Limited::new(chunked(4), 15));
+        let err = read_limited(limited, 15).await.unwrap_err();
+        assert!(matches!(
+            err,
+            OpenAiError::PayloadTooLarge {
+                limit: 15,
+                received: Some(10)
+            }
+        ));
+
+        let interrupted = Body::from_stream(stream::iter([
+            Ok(Bytes::from_static(b"0123456789")),
+            Err(io::Error::from(io::ErrorKind::ConnectionReset)),
+        ]));
+        let err = read_limited(interrupted, 25).await.unwrap_err();
+        assert!(matches!(err, OpenAiError::Io(_)));
+    }
+
+    #[tokio::test]
+    async fn count_bytes_read_by_extractors() {
+        async fn upload(
+            Extension(received): Extension<ReceivedBytes>,
+            mut multipart: Multipart,
+        ) -> StatusCode {
+            match multipart.next_field().await {
+                Ok(_) => StatusCode::OK,
+                Err(err) => {
+                    let err = OpenAiError::from(err).with_body_limit(25, Some(received.get()));
+                    assert!(matches!(
+                        err,
+                        OpenAiError::PayloadTooLarge {
+                            limit: 25,
+                            received: Some(30)
+                        }
+                    ));
+                    StatusCode::PAYLOAD_TOO_LARGE
+                }
+            }
+        }
+
+        let router = Router::new()
+            .route("/upload", post(upload))
+            .layer(DefaultBodyLimit::max(25))
+            .layer(from_fn(count_received_bytes));
+
+        let request = Request::post("/upload")
+            .header(CONTENT_TYPE, "multipart/form-data; boundary=hfendpoints")
+            .body(chunked(8))
+            .unwrap();
+        let response = router.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    }
+}
@@ -0,0 +1,142 @@
+//! Policy governing which request content is persisted by the endpoint.
+//!
+//! Logs, traces and usage records never carry the content of the requests (audio, prompts,
+//! transcripts), only their size and the metadata attached by the caller. The only place content
+//! persists is the journal (see `HFENDPOINT_JOURNAL_DIR`), whose payloads end up in `failed/` as
+//! dead-letters when the endpoint crashes. `HFENDPOINT_PAYLOAD_CAPTURE` governs what it keeps:
+//!
+//! - `full` (default): the body, as received
+//! - `off`: no body, entries only describing the request (path, size, timestamp)
+//! - `hashed`: the SHA-256 digest of the body, to correlate a dead-letter with a known input
+//! - `sampled:<ratio>`: the body of a fraction of the requests, i.e. `sampled:0.1` keeps one in ten
+//!
+//! Sampling is deterministic, the same sequence of requests always keeping the same bodies. A
+//! malformed policy is treated as `off`.
+use axum::body::Bytes;
+use sha2::{Digest, Sha256};
+use std::fmt::Write;
+use tracing::error;
+
+/// Environment variable holding which request content is persisted, see [`PayloadCapture`]
+pub const PAYLOAD_CAPTURE_ENV: &str = "HFENDPOINT_PAYLOAD_CAPTURE";
+
+/// Which request content is persisted
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum PayloadCapture {
+    /// Keep the body as received
+    #[default]
+    Full,
+
+    /// Keep nothing of the body but its size
+    Off,
+
+    /// Keep the SHA-256 digest of the body
+    Hashed,
+
+    /// Keep the body of this ratio of the requests, within `(0, 1)`
+    Sampled(f64),
+}
+
+/// What is kept of a body under the policy
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(PartialEq)]
+pub(crate) enum Captured {
+    Payload,
+    Digest(String),
+    Nothing,
+}
+
+/// Policy defined through `HFENDPOINT_PAYLOAD_CAPTURE`, bodies are kept when not set.
+///
+/// A malformed policy keeps nothing: the operator meant to restrict what is persisted, failing
+/// open would write the very content they tried to keep off the disk.
+pub(crate) fn payload_capture_from_env() -> PayloadCapture {
+    match std::env::var(PAYLOAD_CAPTURE_ENV) {
+        Ok(policy) => parse_capture(&policy).unwrap_or_else(|| {
+            error!(
+                "Malformed {PAYLOAD_CAPTURE_ENV} ({policy}), expected full, off, hashed or sampled:<ratio>, no payload will be persisted"
+            );
+            PayloadCapture::Off
+        }),
+        Err(_) => PayloadCapture::Full,
+    }
+}
+
+fn parse_capture(policy: &str) -> Option<PayloadCapture> {
+    match policy.trim() {
+        "" | "full" => Some(PayloadCapture::Full),
+        "off" => Some(PayloadCapture::Off),
+        "hashed" => Some(PayloadCapture::Hashed),
+        policy => {
+            let ratio = policy
+                .strip_prefix("sampled:")?
+                .trim()
+                .parse::<f64>()
+                .ok()?;
+            match ratio {
+                ratio if ratio <= 0.0 => Some(PayloadCapture::Off),
+                ratio if ratio >= 1.0 => Some(PayloadCapture::Full),
+                ratio if ratio.is_finite() => Some(PayloadCapture::Sampled(ratio)),
+                _ => None,
+            }
+        }
+    }
+}
+
+impl PayloadCapture {
+    /// What is kept of `payload`, the `sequence`-th body seen
+    pub(crate) fn capture(&self, sequence: u64, payload: &Bytes) -> Captured {
+        match self {
+            Self::Full => Captured::Payload,
+            Self::Off => Captured::Nothing,
+            Self::Hashed => {
+                let mut digest = String::with_capacity(64);
+                for byte in Sha256::digest(payload) {
+                    let _ = write!(digest, "{byte:02x}");
+                }
+                Captured::Digest(digest)
+            }
+            // Keep the body whenever the running count of kept ones, `sequence * ratio`, increments
+            Self::Sampled(ratio) => {
+                let kept = |sequence: u64| (sequence as f64 * ratio).floor();
+                if kept(sequence + 1) > kept(sequence) {
+                    Captured::Payload
+                } else {
+                    Captured::Nothing
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::capture::{Captured, PayloadCapture, parse_capture};
+    use axum::body::Bytes;
+
+    #[test]
+    fn capture_under_policy() {
+        assert_eq!(parse_capture("off"), Some(PayloadCapture::Off));
+        assert_eq!(
+            parse_capture("sampled:0.25"),
+            Some(PayloadCapture::Sampled(0.25))
+        );
+        assert_eq!(parse_capture("sampled:1"), Some(PayloadCapture::Full));
+        assert_eq!(parse_capture("sampled:NaN"), None);
+        assert_eq!(parse_capture("partial"), None);
+
+        let payload = Bytes::from_static(b"hello");
+        assert_eq!(
+            PayloadCapture::Hashed.capture(0, &payload),
+            Captured::Digest(String::from(
+                "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824"
+            ))
+        );
+
+        let sampled = PayloadCapture::Sampled(0.25);
+        let kept = (0..100)
+            .filter(|&sequence| sampled.capture(sequence, &payload) == Captured::Payload)
+            .count();
+        assert_eq!(kept, 25);
+    }
+}
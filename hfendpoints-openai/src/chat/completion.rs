@@ -0,0 +1,884 @@
+use crate::chat::CHAT_TAG;
+use crate::chat::streaming::{
+    ChatDeltas, DeltaNormalizer, StreamIdentity, event_stream, flush_interval_from_env,
+};
+use crate::context::{Context, RequestContext};
+use crate::endpoint::{schedule_task, task_router};
+use crate::error::TaskErrors;
+use crate::estimate::{EstimatedTask, record_latency};
+use crate::models::ModelInfo;
+use crate::policy::RequestPolicy;
+use crate::telemetry::record_task;
+use crate::usage::record_output_tokens;
+use crate::{OpenAiError, OpenAiResult, TaskRequest};
+use axum::body::Bytes;
+use axum::extract::State;
+use axum::response::{IntoResponse, Response};
+use axum::{Extension, Json};
+use hfendpoints_core::{EndpointContext, Error as EndpointError, RequestSender};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio_stream::{Stream, StreamExt};
+use tracing::instrument;
+use utoipa::ToSchema;
+use utoipa_axum::router::OpenApiRouter;
+use utoipa_axum::routes;
+
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+
+/// Author of a message within the conversation.
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Copy, Clone, Eq, PartialEq, Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    System,
+    Developer,
+    User,
+    Assistant,
+    Tool,
+}
+
+impl Role {
+    #[inline]
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::System => "system",
+            Role::Developer => "developer",
+            Role::User => "user",
+            Role::Assistant => "assistant",
+            Role::Tool => "tool",
+        }
+    }
+}
+
+/// One part of a multi-part message content, only text parts are supported.
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Clone, Deserialize, ToSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ContentPart {
+    Text { text: String },
+}
+
+/// Content of a message, either a plain string or an array of content parts.
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Clone, Deserialize, ToSchema)]
+#[serde(untagged)]
+pub enum MessageContent {
+    Text(String),
+    Parts(Vec<ContentPart>),
+}
+
+impl From<MessageContent> for String {
+    fn from(value: MessageContent) -> Self {
+        match value {
+            MessageContent::Text(text) => text,
+            MessageContent::Parts(parts) => parts
+                .into_iter()
+                .map(|ContentPart::Text { text }| text)
+                .collect::<Vec<_>>()
+                .join("\n"),
+        }
+    }
+}
+
+/// A message within the conversation.
+#[cfg_attr(feature = "python", pyclass(frozen))]
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Clone, Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct ChatMessage {
+    /// The role of the author of this message.
+    pub role: Role,
+
+    /// The contents of the message, multi-part contents are joined with new lines.
+    #[serde(default, deserialize_with = "deserialize_content")]
+    #[schema(value_type = Option<MessageContent>)]
+    pub content: Option<String>,
+
+    /// An optional name for the participant.
+    pub name: Option<String>,
+}
+
+fn deserialize_content<'de, D>(deserializer: D) -> Result<Option<String>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    Ok(Option::<MessageContent>::deserialize(deserializer)?.map(String::from))
+}
+
+/// Up to 4 sequences where the model will stop generating further tokens.
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Clone, Deserialize, ToSchema)]
+#[serde(untagged)]
+pub enum StopSequences {
+    Single(String),
+    Multiple(Vec<String>),
+}
+
+impl From<StopSequences> for Vec<String> {
+    fn from(value: StopSequences) -> Self {
+        match value {
+            StopSequences::Single(stop) => vec![stop],
+            StopSequences::Multiple(stops) => stops,
+        }
+    }
+}
+
+/// Creates a model response for the given chat conversation.
+#[cfg_attr(feature = "python", pyclass(frozen))]
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Clone, Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct ChatCompletionRequest {
+    /// A list of messages comprising the conversation so far.
+    pub messages: Vec<ChatMessage>,
+
+    /// Not used, here for compatibility purpose with OpenAI Platform
+    pub model: Option<String>,
+
+    /// An upper bound for the number of tokens that can be generated for a completion.
+    #[serde(alias = "max_tokens")]
+    pub max_completion_tokens: Option<u32>,
+
+    /// What sampling temperature to use, between 0 and 2.
+    /// Higher values like 0.8 will make the output more random, while lower values like 0.2 will make it more focused and deterministic.
+    pub temperature: Option<f32>,
+
+    /// An alternative to sampling with temperature, called nucleus sampling,
+    /// where the model considers the results of the tokens with top_p probability mass.
+    pub top_p: Option<f32>,
+
+    /// Up to 4 sequences where the model will stop generating further tokens.
+    #[serde(default, deserialize_with = "deserialize_stop")]
+    #[schema(value_type = Option<StopSequences>)]
+    pub stop: Vec<String>,
+
+    /// If specified, the system will make a best effort to sample deterministically.
+    pub seed: Option<u64>,
+
+    /// If set, partial message deltas are sent as server-sent events, ended by a `data: [DONE]` message.
+    pub stream: Option<bool>,
+}
+
+pub(crate) fn deserialize_stop<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    Ok(Option::<StopSequences>::deserialize(deserializer)?
+        .map(Vec::from)
+        .unwrap_or_default())
+}
+
+impl ChatCompletionRequest {
+    #[instrument(skip_all)]
+    fn validate(self) -> OpenAiResult<Self> {
+        if self.messages.is_empty() {
+            return Err(OpenAiError::Validation(String::from(
+                "Parameter 'messages' must contain at least one message",
+            )));
+        }
+
+        validate_sampling(self.temperature, self.top_p, &self.stop)?;
+        Ok(self)
+    }
+}
+
+/// Validate the sampling parameters shared by chat and legacy completions
+pub(crate) fn validate_sampling(
+    temperature: Option<f32>,
+    top_p: Option<f32>,
+    stop: &[String],
+) -> OpenAiResult<()> {
+    if let Some(temperature) = temperature.filter(|t| !(0.0..=2.0).contains(t)) {
+        return Err(OpenAiError::Validation(format!(
+            "Parameter 'temperature' must be between 0 and 2, got {temperature}"
+        )));
+    }
+
+    if let Some(top_p) = top_p.filter(|p| !(0.0..=1.0).contains(p)) {
+        return Err(OpenAiError::Validation(format!(
+            "Parameter 'top_p' must be between 0 and 1, got {top_p}"
+        )));
+    }
+
+    if stop.len() > 4 {
+        return Err(OpenAiError::Validation(String::from(
+            "Parameter 'stop' accepts up to 4 sequences",
+        )));
+    }
+
+    Ok(())
+}
+
+/// The reason the model stopped generating tokens.
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Copy, Clone, Eq, PartialEq, Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum FinishReason {
+    /// The model hit a natural stop point or a provided stop sequence.
+    Stop,
+
+    /// The maximum number of tokens specified in the request was reached.
+    Length,
+
+    /// Content was omitted due to a flag from content filters.
+    ContentFilter,
+}
+
+impl TryFrom<&str> for FinishReason {
+    type Error = OpenAiError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "stop" => Ok(FinishReason::Stop),
+            "length" => Ok(FinishReason::Length),
+            "content_filter" => Ok(FinishReason::ContentFilter),
+            _ => Err(OpenAiError::Validation(format!(
+                "Unknown finish_reason: {value}. Possible values are: 'stop', 'length', 'content_filter'."
+            ))),
+        }
+    }
+}
+
+/// A chat completion message generated by the model.
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Clone, Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct AssistantMessage {
+    /// The role of the author of this message, always `assistant`.
+    role: Role,
+
+    /// The contents of the message.
+    content: String,
+}
+
+/// One of the chat completion choices generated by the model.
+#[cfg_attr(feature = "python", pyclass(frozen))]
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Clone, Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct ChatCompletionChoice {
+    /// The index of the choice in the list of choices.
+    index: u32,
+
+    /// A chat completion message generated by the model.
+    message: AssistantMessage,
+
+    /// The reason the model stopped generating tokens.
+    finish_reason: FinishReason,
+}
+
+impl ChatCompletionChoice {
+    pub fn new(index: u32, content: String, finish_reason: FinishReason) -> Self {
+        Self {
+            index,
+            message: AssistantMessage {
+                role: Role::Assistant,
+                content,
+            },
+            finish_reason,
+        }
+    }
+}
+
+/// Usage statistics for the completion request.
+#[cfg_attr(feature = "python", pyclass(frozen))]
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Copy, Clone, Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct Usage {
+    /// Number of tokens in the prompt.
+    pub(crate) prompt_tokens: u32,
+
+    /// Number of tokens in the generated completion.
+    pub(crate) completion_tokens: u32,
+
+    /// Total number of tokens used in the request (prompt + completion).
+    pub(crate) total_tokens: u32,
+}
+
+impl Usage {
+    pub fn new(prompt_tokens: u32, completion_tokens: u32) -> Self {
+        Self {
+            prompt_tokens,
+            completion_tokens,
+            total_tokens: prompt_tokens + completion_tokens,
+        }
+    }
+}
+
+/// Represents a chat completion response returned by model, based on the provided input.
+#[cfg_attr(feature = "python", pyclass(frozen))]
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Clone, Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct ChatCompletionResponse {
+    /// A unique identifier for the chat completion.
+    id: String,
+
+    /// The object type, which is always `chat.completion`.
+    #[serde(skip_deserializing, default = "chat_completion_object")]
+    object: &'static str,
+
+    /// The Unix timestamp (in seconds) of when the chat completion was created.
+    created: u64,
+
+    /// The model used for the chat completion.
+    model: String,
+
+    /// A list of chat completion choices.
+    choices: Vec<ChatCompletionChoice>,
+
+    /// Usage statistics for the completion request.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    usage: Option<Usage>,
+}
+
+#[inline]
+fn chat_completion_object() -> &'static str {
+    "chat.completion"
+}
+
+impl ChatCompletionResponse {
+    pub fn new(model: String, choices: Vec<ChatCompletionChoice>, usage: Option<Usage>) -> Self {
+        Self {
+            id: String::new(),
+            object: "chat.completion",
+            created: 0,
+            model,
+            choices,
+            usage,
+        }
+    }
+
+    /// Assign the identifier and creation time, set by the route once the handler answered
+    fn stamp(mut self, request_id: &str, created: SystemTime) -> Self {
+        self.id = completion_id(request_id);
+        self.created = unix_timestamp(created);
+        self
+    }
+}
+
+#[inline]
+fn completion_id(request_id: &str) -> String {
+    format!("chatcmpl-{request_id}")
+}
+
+#[inline]
+pub(crate) fn unix_timestamp(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default()
+}
+
+impl IntoResponse for ChatCompletionResponse {
+    fn into_response(self) -> Response {
+        Json::from(self).into_response()
+    }
+}
+
+/// Delta of one of the choices, emitted by handlers streaming the completion.
+///
+/// The delta is raw UTF-8 which may end in the middle of a character, it is reassembled with the
+/// following deltas before reaching the client, see `chat::streaming`.
+#[cfg_attr(feature = "python", pyclass(frozen))]
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub struct ChatCompletionChunk {
+    /// The index of the choice in the list of choices.
+    pub(crate) index: u32,
+
+    /// Text generated since the previous chunk of this choice.
+    #[serde(with = "crate::base64_bytes")]
+    pub(crate) delta: Bytes,
+
+    /// The reason the model stopped generating tokens, for the last chunk of the choice.
+    pub(crate) finish_reason: Option<FinishReason>,
+}
+
+impl ChatCompletionChunk {
+    pub fn new(index: u32, delta: impl Into<Bytes>, finish_reason: Option<FinishReason>) -> Self {
+        Self {
+            index,
+            delta: delta.into(),
+            finish_reason,
+        }
+    }
+}
+
+/// Outcome of the handler, either the complete response or, when streaming, one of its chunks.
+///
+/// Handlers may stream chunks whether the client asked for a streamed response or not, and answer
+/// streamed requests with a complete response, the route converting one into the other.
+#[cfg_attr(feature = "python", derive(FromPyObject))]
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChatCompletionOutput {
+    Completion(ChatCompletionResponse),
+    Chunk(ChatCompletionChunk),
+}
+
+impl From<ChatCompletionResponse> for ChatCompletionOutput {
+    fn from(value: ChatCompletionResponse) -> Self {
+        Self::Completion(value)
+    }
+}
+
+impl From<ChatCompletionChunk> for ChatCompletionOutput {
+    fn from(value: ChatCompletionChunk) -> Self {
+        Self::Chunk(value)
+    }
+}
+
+impl ChatCompletionOutput {
+    /// Chunks streaming this outcome, a complete response streaming each choice at once
+    pub(crate) fn into_chunks(self) -> impl Iterator<Item = ChatCompletionChunk> {
+        let chunks = match self {
+            Self::Completion(completion) => completion
+                .choices
+                .into_iter()
+                .map(|choice| {
+                    ChatCompletionChunk::new(
+                        choice.index,
+                        choice.message.content,
+                        Some(choice.finish_reason),
+                    )
+                })
+                .collect(),
+            Self::Chunk(chunk) => vec![chunk],
+        };
+        chunks.into_iter()
+    }
+}
+
+/// Gather the outcomes of a handler streaming its chunks into a complete response
+async fn assemble(
+    mut outputs: impl Stream<Item = Result<ChatCompletionOutput, EndpointError>> + Unpin,
+    model: String,
+) -> OpenAiResult<ChatCompletionResponse> {
+    let mut normalizer = DeltaNormalizer::default();
+    let mut streamed = false;
+    while let Some(output) = outputs.next().await {
+        match output? {
+            // Handlers answering with a complete response are not expected to stream anything else
+            ChatCompletionOutput::Completion(completion) if !streamed => return Ok(completion),
+            output => {
+                streamed = true;
+                output
+                    .into_chunks()
+                    .for_each(|chunk| normalizer.push(chunk));
+            }
+        }
+    }
+
+    let choices: Vec<_> = normalizer
+        .drain(true)
+        .into_iter()
+        .map(|choice| {
+            let finish_reason = choice.finish_reason.unwrap_or(FinishReason::Stop);
+            ChatCompletionChoice::new(choice.index, choice.delta.content, finish_reason)
+        })
+        .collect();
+    if choices.is_empty() {
+        return Err(OpenAiError::NoResponse);
+    }
+    Ok(ChatCompletionResponse::new(model, choices, None))
+}
+
+#[utoipa::path(
+    post,
+    path = "/chat/completions",
+    tag = CHAT_TAG,
+    request_body(content = ChatCompletionRequest, content_type = "application/json"),
+    responses(
+        (status = OK, description = "Creates a model response for the given chat conversation, streamed as `chat.completion.chunk` events when `stream` is set.",
+            content((ChatCompletionResponse = "application/json"), (str = "text/event-stream"))),
+        TaskErrors,
+    )
+)]
+#[instrument(skip(state, policy, model, metadata, request))]
+pub async fn complete(
+    State(state): State<EndpointContext<(ChatCompletionRequest, Context), ChatCompletionOutput>>,
+    Extension(policy): Extension<Arc<RequestPolicy>>,
+    Extension(flush_interval): Extension<Option<Duration>>,
+    model: Option<Extension<Arc<ModelInfo>>>,
+    metadata: RequestContext,
+    Json(mut request): Json<Value>,
+) -> OpenAiResult<Response> {
+    record_task("chat_completion");
+
+    // Apply the operator's defaults and overrides before decoding
+    policy.apply(&mut request);
+    let request = serde_json::from_value::<ChatCompletionRequest>(request)
+        .map_err(|err| OpenAiError::Validation(err.to_string()))?
+        .validate()?;
+
+    // Generations are bounded by the limits of the served model
+    if let (Some(Extension(model)), Some(tokens)) = (&model, request.max_completion_tokens) {
+        model.ensure_output_tokens("max_completion_tokens", tokens)?;
+    }
+
+    // Ask for the inference thread to handle it and wait for answers
+    let started = Instant::now();
+    let id = metadata.request_id().to_string();
+    let stream = request.stream.unwrap_or(false);
+    let model = request.model.clone().unwrap_or_default();
+    let scheduled = schedule_task(&state, metadata, request).await?;
+    if stream {
+        let identity = StreamIdentity {
+            id: completion_id(&id),
+            created: unix_timestamp(SystemTime::now()),
+            model,
+        };
+        return Ok(
+            event_stream(scheduled, ChatDeltas::new(identity), flush_interval)
+                .await?
+                .into_response(),
+        );
+    }
+
+    let response = assemble(scheduled.stream(), model).await?;
+    if let Some(usage) = response.usage {
+        record_latency(
+            EstimatedTask::ChatCompletion,
+            usage.total_tokens as f64,
+            started,
+        );
+        record_output_tokens(usage.completion_tokens);
+    }
+    Ok(response.stamp(&id, SystemTime::now()).into_response())
+}
+
+/// Helper factory to build
+/// [OpenAi Platform compatible Chat Completion endpoint](https://platform.openai.com/docs/api-reference/chat/create)
+#[derive(Clone)]
+pub struct ChatCompletionRouter(
+    pub RequestSender<(ChatCompletionRequest, Context), ChatCompletionOutput>,
+);
+
+impl From<ChatCompletionRouter> for OpenApiRouter {
+    fn from(value: ChatCompletionRouter) -> Self {
+        let routes = OpenApiRouter::new().routes(routes!(complete));
+        task_router(routes, value.0, "/chat/completions")
+            .layer(Extension(flush_interval_from_env()))
+    }
+}
+
+impl TaskRequest for ChatCompletionRequest {
+    type Response = ChatCompletionOutput;
+
+    fn router(sender: RequestSender<(Self, Context), Self::Response>) -> OpenApiRouter {
+        ChatCompletionRouter(sender).into()
+    }
+}
+
+#[cfg(feature = "python")]
+pub(crate) mod python {
+    use crate::chat::completion::{
+        ChatCompletionChoice, ChatCompletionChunk, ChatCompletionOutput, ChatCompletionRequest,
+        ChatCompletionResponse, ChatMessage, FinishReason, Usage,
+    };
+    use crate::python::TextDelta;
+    use axum::body::Bytes;
+    use pyo3::exceptions::PyValueError;
+    use pyo3::prelude::*;
+    use pyo3::types::PyBytes;
+
+    impl TextDelta for ChatCompletionOutput {
+        fn from_text_delta(text: String) -> Option<Self> {
+            Some(ChatCompletionChunk::new(0, text, None).into())
+        }
+    }
+
+    #[pymethods]
+    impl ChatMessage {
+        #[getter(role)]
+        fn get_role(&self) -> &'static str {
+            self.role.as_str()
+        }
+
+        #[getter(content)]
+        fn get_content(&self) -> Option<&str> {
+            self.content.as_deref()
+        }
+
+        #[getter(name)]
+        fn get_name(&self) -> Option<&str> {
+            self.name.as_deref()
+        }
+    }
+
+    #[pymethods]
+    impl ChatCompletionRequest {
+        #[getter(messages)]
+        fn get_messages(&self) -> Vec<ChatMessage> {
+            self.messages.clone()
+        }
+
+        #[getter(model)]
+        fn get_model(&self) -> Option<&str> {
+            self.model.as_deref()
+        }
+
+        #[getter(max_completion_tokens)]
+        fn get_max_completion_tokens(&self) -> Option<u32> {
+            self.max_completion_tokens
+        }
+
+        #[getter(temperature)]
+        fn get_temperature(&self) -> Option<f32> {
+            self.temperature
+        }
+
+        #[getter(top_p)]
+        fn get_top_p(&self) -> Option<f32> {
+            self.top_p
+        }
+
+        #[getter(stop)]
+        fn get_stop(&self) -> Vec<String> {
+            self.stop.clone()
+        }
+
+        #[getter(seed)]
+        fn get_seed(&self) -> Option<u64> {
+            self.seed
+        }
+
+        #[getter(stream)]
+        fn get_stream(&self) -> bool {
+            self.stream.unwrap_or(false)
+        }
+    }
+
+    #[pymethods]
+    impl ChatCompletionChoice {
+        #[new]
+        #[pyo3(signature = (index, content, finish_reason = "stop"))]
+        fn py_new(index: u32, content: String, finish_reason: &str) -> PyResult<Self> {
+            let finish_reason = FinishReason::try_from(finish_reason)
+                .map_err(|err| PyValueError::new_err(err.to_string()))?;
+            Ok(Self::new(index, content, finish_reason))
+        }
+    }
+
+    #[pymethods]
+    impl ChatCompletionChunk {
+        /// `delta` may be `bytes` ending in the middle of a UTF-8 character, completed by the next chunks
+        #[new]
+        #[pyo3(signature = (index, delta, finish_reason = None))]
+        fn py_new(
+            index: u32,
+            delta: &Bound<'_, PyAny>,
+            finish_reason: Option<&str>,
+        ) -> PyResult<Self> {
+            let delta = match delta.downcast::<PyBytes>() {
+                Ok(bytes) => Bytes::copy_from_slice(bytes.as_bytes()),
+                Err(_) => Bytes::from(delta.extract::<String>()?),
+            };
+            let finish_reason = finish_reason
+                .map(FinishReason::try_from)
+                .transpose()
+                .map_err(|err| PyValueError::new_err(err.to_string()))?;
+            Ok(Self::new(index, delta, finish_reason))
+        }
+    }
+
+    #[pymethods]
+    impl Usage {
+        #[new]
+        fn py_new(prompt_tokens: u32, completion_tokens: u32) -> Self {
+            Self::new(prompt_tokens, completion_tokens)
+        }
+    }
+
+    #[pymethods]
+    impl ChatCompletionResponse {
+        #[new]
+        #[pyo3(signature = (model, choices, usage = None))]
+        fn py_new(model: String, choices: Vec<ChatCompletionChoice>, usage: Option<Usage>) -> Self {
+            Self::new(model, choices, usage)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::chat::completion::{
+        ChatCompletionChoice, ChatCompletionChunk, ChatCompletionRequest, ChatCompletionResponse,
+        ChatCompletionRouter, FinishReason, Role, Usage,
+    };
+    use crate::testing::{answer, post, stream};
+    use axum::body::Bytes;
+    use axum::http::header::CONTENT_TYPE;
+    use axum::http::{HeaderMap, StatusCode};
+    use hfendpoints_core::request_channel;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[test]
+    fn deserialize_request_with_content_parts() {
+        let request: ChatCompletionRequest = serde_json::from_value(serde_json::json!({
+            "model": "gpt-4o",
+            "messages": [
+                {"role": "system", "content": "You are a helpful assistant."},
+                {"role": "user", "content": [{"type": "text", "text": "Hello"}, {"type": "text", "text": "world"}]}
+            ],
+            "max_tokens": 16,
+            "stop": "\n"
+        }))
+        .expect("Failed to deserialize ChatCompletionRequest");
+
+        assert_eq!(request.messages.len(), 2);
+        assert_eq!(request.messages[0].role, Role::System);
+        assert_eq!(request.messages[1].content.as_deref(), Some("Hello\nworld"));
+        assert_eq!(request.max_completion_tokens, Some(16));
+        assert_eq!(request.stop, vec![String::from("\n")]);
+    }
+
+    #[test]
+    fn serialize_chat_completion_response() {
+        let response = ChatCompletionResponse::new(
+            String::from("my-model"),
+            vec![ChatCompletionChoice::new(
+                0,
+                String::from("Hi!"),
+                FinishReason::Stop,
+            )],
+            Some(Usage::new(9, 3)),
+        )
+        .stamp("abc", UNIX_EPOCH + Duration::from_secs(1700000000));
+
+        assert_eq!(
+            serde_json::to_value(&response).expect("Failed to serialize ChatCompletionResponse"),
+            serde_json::json!({
+                "id": "chatcmpl-abc",
+                "object": "chat.completion",
+                "created": 1700000000,
+                "model": "my-model",
+                "choices": [{
+                    "index": 0,
+                    "message": {"role": "assistant", "content": "Hi!"},
+                    "finish_reason": "stop"
+                }],
+                "usage": {"prompt_tokens": 9, "completion_tokens": 3, "total_tokens": 12}
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn complete_rejects_empty_messages() {
+        let (sender, _receiver) = request_channel(1);
+        let router = ChatCompletionRouter(sender);
+        let (status, _, body) = complete(router, r#"{"messages": []}"#).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(
+            body["error"]["message"],
+            "Parameter 'messages' must contain at least one message"
+        );
+    }
+
+    #[tokio::test]
+    async fn complete_forwards_request_to_handler() {
+        let (sender, receiver) = request_channel(1);
+        answer(receiver, |request: ChatCompletionRequest| {
+            let content = request.messages[0].content.clone().unwrap_or_default();
+            let choice = ChatCompletionChoice::new(0, content, FinishReason::Stop);
+            Ok(ChatCompletionResponse::new(String::from("echo"), vec![choice], None).into())
+        });
+
+        let router = ChatCompletionRouter(sender);
+        let body = r#"{"messages": [{"role": "user", "content": "ping"}]}"#;
+        let (status, _, body) = complete(router, body).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["id"], "chatcmpl-test");
+        assert_eq!(json["choices"][0]["message"]["content"], "ping");
+    }
+
+    async fn complete(
+        router: ChatCompletionRouter,
+        body: &'static str,
+    ) -> (StatusCode, HeaderMap, Bytes) {
+        post(router, "/chat/completions", "application/json", body).await
+    }
+
+    /// Spawn a handler streaming the chunks of "€!" split in the middle of the euro sign
+    fn streaming_router() -> ChatCompletionRouter {
+        let (sender, receiver) = request_channel(1);
+        stream(receiver, |_: ChatCompletionRequest| {
+            [
+                (b"\xE2\x82" as &[u8], None),
+                (b"\xAC", None),
+                (b"!", Some(FinishReason::Stop)),
+            ]
+            .map(|(delta, finish_reason)| {
+                Ok(ChatCompletionChunk::new(0, delta.to_vec(), finish_reason).into())
+            })
+        });
+        ChatCompletionRouter(sender)
+    }
+
+    #[tokio::test]
+    async fn complete_streams_normalized_chunks() {
+        let (status, headers, body) = complete(
+            streaming_router(),
+            r#"{"messages": [{"role": "user", "content": "ping"}], "model": "m", "stream": true}"#,
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(headers[CONTENT_TYPE], "text/event-stream");
+
+        let body = String::from_utf8(body.to_vec()).unwrap();
+        let events: Vec<_> = body
+            .lines()
+            .filter_map(|line| line.strip_prefix("data: "))
+            .collect();
+        assert_eq!(events.last(), Some(&"[DONE]"));
+
+        let chunks: Vec<serde_json::Value> = events[..events.len() - 1]
+            .iter()
+            .map(|event| serde_json::from_str(event).unwrap())
+            .collect();
+        assert!(
+            chunks
+                .iter()
+                .all(|chunk| chunk["object"] == "chat.completion.chunk")
+        );
+        assert!(
+            chunks
+                .iter()
+                .all(|chunk| chunk["id"] == "chatcmpl-test" && chunk["model"] == "m")
+        );
+
+        let content: String = chunks
+            .iter()
+            .map(|chunk| chunk["choices"][0]["delta"]["content"].as_str().unwrap())
+            .collect();
+        assert_eq!(content, "€!");
+        assert_eq!(chunks[0]["choices"][0]["delta"]["role"], "assistant");
+        assert_eq!(
+            chunks.last().unwrap()["choices"][0]["finish_reason"],
+            "stop"
+        );
+    }
+
+    #[tokio::test]
+    async fn complete_assembles_streamed_chunks() {
+        let body = r#"{"messages": [{"role": "user", "content": "ping"}]}"#;
+        let (status, _, body) = complete(streaming_router(), body).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["object"], "chat.completion");
+        assert_eq!(json["choices"][0]["message"]["content"], "€!");
+        assert_eq!(json["choices"][0]["finish_reason"], "stop");
+    }
+}
@@ -0,0 +1,50 @@
+pub mod completion;
+pub(crate) mod streaming;
+
+pub use streaming::STREAM_FLUSH_INTERVAL_ENV;
+
+pub const CHAT_TAG: &str = "Chat";
+pub const CHAT_DESC: &str =
+    "Given a list of messages comprising a conversation, the model will return a response.";
+
+#[cfg(feature = "python")]
+pub(crate) mod python {
+    use crate::chat::completion::{
+        ChatCompletionChoice, ChatCompletionChunk, ChatCompletionRequest, ChatCompletionResponse,
+        ChatMessage, Usage,
+    };
+    use hfendpoints_binding_python::ImportablePyModuleBuilder;
+    use pyo3::prelude::*;
+
+    mod completions {
+        use crate::chat::completion::{
+            ChatCompletionOutput, ChatCompletionRequest, ChatCompletionRouter,
+        };
+        use crate::python::{impl_pyendpoint, impl_pyhandler};
+
+        impl_pyhandler!(ChatCompletionRequest, ChatCompletionOutput);
+        impl_pyendpoint!(
+            "ChatCompletionEndpoint",
+            PyChatCompletionEndpoint,
+            PyHandler,
+            ChatCompletionRouter
+        );
+    }
+
+    /// Bind hfendpoints.openai.chat submodule into the exported Python wheel
+    pub fn bind<'py>(py: Python<'py>, name: &str) -> PyResult<Bound<'py, PyModule>> {
+        let module = ImportablePyModuleBuilder::new(py, name)?
+            .defaults()?
+            // completion
+            .add_class::<ChatMessage>()?
+            .add_class::<ChatCompletionRequest>()?
+            .add_class::<ChatCompletionChoice>()?
+            .add_class::<Usage>()?
+            .add_class::<ChatCompletionResponse>()?
+            .add_class::<ChatCompletionChunk>()?
+            .add_class::<completions::PyChatCompletionEndpoint>()?
+            .finish();
+
+        Ok(module)
+    }
+}
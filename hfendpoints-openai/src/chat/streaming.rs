@@ -0,0 +1,379 @@
+//! Normalization of the chat and legacy completion deltas streamed by handlers.
+//!
+//! Handlers yield raw deltas, possibly ending in the middle of a multi-byte UTF-8 character when
+//! they decode tokens one by one. Deltas are buffered per choice until they form valid UTF-8, so
+//! clients never receive a broken character, invalid sequences being replaced by `U+FFFD`.
+//!
+//! Handlers producing many tiny deltas may flood clients with events, `HFENDPOINT_STREAM_FLUSH_INTERVAL`
+//! coalesces the deltas produced within the given number of milliseconds into a single event.
+use crate::chat::completion::{ChatCompletionChunk, ChatCompletionOutput, FinishReason, Role};
+use crate::{OpenAiError, OpenAiResult};
+use axum::response::sse::{Event, KeepAlive, Sse};
+use futures_util::stream::{self, BoxStream, StreamExt};
+use hfendpoints_core::ScheduledRequest;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::convert::Infallible;
+use std::time::Duration;
+use tokio_stream::Stream;
+use tracing::{error, instrument, warn};
+
+/// Environment variable holding the number of milliseconds streamed deltas are coalesced over
+pub const STREAM_FLUSH_INTERVAL_ENV: &str = "HFENDPOINT_STREAM_FLUSH_INTERVAL";
+
+/// Upper bound of the deltas coalesced into a single event
+const MAX_COALESCED_DELTAS: usize = 256;
+
+/// Interval defined through `HFENDPOINT_STREAM_FLUSH_INTERVAL`, deltas are not coalesced when not set
+pub(crate) fn flush_interval_from_env() -> Option<Duration> {
+    let interval = std::env::var(STREAM_FLUSH_INTERVAL_ENV).ok()?;
+    match interval.parse::<u64>() {
+        Ok(0) => None,
+        Ok(millis) => Some(Duration::from_millis(millis)),
+        Err(err) => {
+            warn!("Ignoring malformed {STREAM_FLUSH_INTERVAL_ENV} ({interval}): {err}");
+            None
+        }
+    }
+}
+
+/// Text and outcome of a choice, not yet sent to the client
+#[derive(Default)]
+struct Pending {
+    /// Trailing bytes of an incomplete UTF-8 character
+    partial: Vec<u8>,
+    text: String,
+    finish_reason: Option<FinishReason>,
+
+    /// Whether the role was announced to the client, done along the first delta of each choice
+    announced: bool,
+}
+
+impl Pending {
+    /// Append the valid UTF-8 prefix of `partial` to the text, keeping an incomplete character back
+    fn decode(&mut self) {
+        let mut remaining = self.partial.as_slice();
+        loop {
+            match std::str::from_utf8(remaining) {
+                Ok(valid) => {
+                    self.text.push_str(valid);
+                    remaining = &[];
+                    break;
+                }
+                Err(err) => {
+                    let (valid, rest) = remaining.split_at(err.valid_up_to());
+                    self.text
+                        .push_str(std::str::from_utf8(valid).unwrap_or_default());
+                    match err.error_len() {
+                        Some(invalid) => {
+                            self.text.push(char::REPLACEMENT_CHARACTER);
+                            remaining = &rest[invalid..];
+                        }
+                        None => {
+                            remaining = rest;
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+        self.partial = remaining.to_vec();
+    }
+}
+
+/// Reassemble the deltas of every choice into valid UTF-8 text
+#[derive(Default)]
+pub(crate) struct DeltaNormalizer {
+    choices: BTreeMap<u32, Pending>,
+}
+
+impl DeltaNormalizer {
+    pub(crate) fn push(&mut self, chunk: ChatCompletionChunk) {
+        self.push_delta(chunk.index, &chunk.delta, chunk.finish_reason);
+    }
+
+    pub(crate) fn push_delta(
+        &mut self,
+        index: u32,
+        delta: &[u8],
+        finish_reason: Option<FinishReason>,
+    ) {
+        let pending = self.choices.entry(index).or_default();
+        pending.partial.extend_from_slice(delta);
+        pending.decode();
+        if finish_reason.is_some() {
+            pending.finish_reason = finish_reason;
+        }
+    }
+
+    /// Take the text decoded so far, along with the outcome of the finished choices.
+    ///
+    /// Once `finished`, incomplete characters held back are flushed as `U+FFFD`.
+    pub(crate) fn drain(&mut self, finished: bool) -> Vec<ChunkChoice> {
+        let mut choices = Vec::new();
+        for (&index, pending) in self.choices.iter_mut() {
+            if finished && !pending.partial.is_empty() {
+                pending.partial.clear();
+                pending.text.push(char::REPLACEMENT_CHARACTER);
+            }
+
+            // The outcome is only reported once the incomplete characters are resolved
+            let finish_reason = match pending.partial.is_empty() {
+                true => pending.finish_reason.take(),
+                false => None,
+            };
+            if pending.text.is_empty() && finish_reason.is_none() {
+                continue;
+            }
+
+            let role = (!pending.announced).then_some(Role::Assistant);
+            pending.announced = true;
+
+            choices.push(ChunkChoice {
+                index,
+                delta: ChunkDelta {
+                    role,
+                    content: std::mem::take(&mut pending.text),
+                },
+                finish_reason,
+            });
+        }
+        choices
+    }
+}
+
+/// Incremental content of a choice, as sent to the client
+#[derive(Serialize)]
+pub(crate) struct ChunkDelta {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    role: Option<Role>,
+    pub(crate) content: String,
+}
+
+/// One of the choices of a `chat.completion.chunk` object
+#[derive(Serialize)]
+pub(crate) struct ChunkChoice {
+    pub(crate) index: u32,
+    pub(crate) delta: ChunkDelta,
+    pub(crate) finish_reason: Option<FinishReason>,
+}
+
+/// Chunk object, i.e. `chat.completion.chunk`, sent as a Server-Sent Event
+#[derive(Serialize)]
+struct ChunkEvent<'a, C> {
+    id: &'a str,
+    object: &'static str,
+    created: u64,
+    model: &'a str,
+    choices: Vec<C>,
+}
+
+/// Identify the chunks of a streamed completion
+pub(crate) struct StreamIdentity {
+    pub(crate) id: String,
+    pub(crate) created: u64,
+    pub(crate) model: String,
+}
+
+impl StreamIdentity {
+    /// Event carrying the `choices` of an `object` chunk
+    pub(crate) fn event<C: Serialize>(&self, object: &'static str, choices: Vec<C>) -> Event {
+        let chunk = ChunkEvent {
+            id: &self.id,
+            object,
+            created: self.created,
+            model: &self.model,
+            choices,
+        };
+        Event::default()
+            .json_data(chunk)
+            .unwrap_or_else(|err| Event::default().event("error").data(err.to_string()))
+    }
+}
+
+/// Buffer the outputs of a handler, turning them into the events of the streamed response
+pub(crate) trait StreamedDeltas: Send + 'static {
+    type Output: Send + 'static;
+
+    /// Buffer the deltas carried by `output`
+    fn push(&mut self, output: Self::Output);
+
+    /// Event carrying the text buffered so far, if any. Once `finished`, incomplete characters
+    /// held back are flushed too
+    fn flush(&mut self, finished: bool) -> Option<Event>;
+}
+
+/// Deltas of a chat completion, sent as `chat.completion.chunk` objects
+pub(crate) struct ChatDeltas {
+    identity: StreamIdentity,
+    normalizer: DeltaNormalizer,
+}
+
+impl ChatDeltas {
+    pub(crate) fn new(identity: StreamIdentity) -> Self {
+        Self {
+            identity,
+            normalizer: DeltaNormalizer::default(),
+        }
+    }
+}
+
+impl StreamedDeltas for ChatDeltas {
+    type Output = ChatCompletionOutput;
+
+    fn push(&mut self, output: Self::Output) {
+        output
+            .into_chunks()
+            .for_each(|chunk| self.normalizer.push(chunk));
+    }
+
+    fn flush(&mut self, finished: bool) -> Option<Event> {
+        let choices = self.normalizer.drain(finished);
+        (!choices.is_empty()).then(|| self.identity.event("chat.completion.chunk", choices))
+    }
+}
+
+/// Group the responses produced within `interval`, one by one if not coalescing
+fn batches<S>(responses: S, interval: Option<Duration>) -> BoxStream<'static, Vec<S::Item>>
+where
+    S: Stream + Send + 'static,
+    S::Item: Send,
+{
+    match interval {
+        Some(interval) => Box::pin(tokio_stream::StreamExt::chunks_timeout(
+            responses,
+            MAX_COALESCED_DELTAS,
+            interval,
+        )),
+        None => responses.map(|response| vec![response]).boxed(),
+    }
+}
+
+/// Turn the deltas emitted by the handler into a Server-Sent Events stream of chunk objects,
+/// ended by `[DONE]`.
+///
+/// The first response is awaited before the stream is returned so handlers failing upfront
+/// are still reported with the appropriate status code rather than an `error` event.
+/// Deltas are coalesced over `interval`, if any.
+#[instrument(skip_all)]
+pub(crate) async fn event_stream<D: StreamedDeltas>(
+    scheduled: ScheduledRequest<D::Output>,
+    mut deltas: D,
+    interval: Option<Duration>,
+) -> OpenAiResult<Sse<impl Stream<Item = Result<Event, Infallible>>>> {
+    let mut responses = scheduled.stream();
+    let first = match tokio_stream::StreamExt::next(&mut responses).await {
+        Some(response) => response?,
+        None => return Err(OpenAiError::NoResponse),
+    };
+
+    let responses = stream::iter([Ok(first)]).chain(responses);
+    let events = batches(responses, interval)
+        .map(Some)
+        .chain(stream::iter([None]))
+        .flat_map(move |batch| {
+            let mut events = Vec::new();
+            let Some(batch) = batch else {
+                events.extend(deltas.flush(true));
+                events.push(Event::default().data("[DONE]"));
+                return stream::iter(events);
+            };
+
+            for response in batch {
+                match response {
+                    Ok(output) => deltas.push(output),
+                    Err(err) => {
+                        events.extend(deltas.flush(false));
+
+                        error!("Handler failed while streaming: {err}");
+                        events.push(Event::default().event("error").data(err.to_string()));
+                    }
+                }
+            }
+
+            events.extend(deltas.flush(false));
+            stream::iter(events)
+        })
+        .map(Ok);
+
+    Ok(Sse::new(events).keep_alive(KeepAlive::default()))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::chat::completion::{ChatCompletionChunk, FinishReason};
+    use crate::chat::streaming::DeltaNormalizer;
+    use axum::body::Bytes;
+
+    fn chunk(
+        index: u32,
+        delta: &'static [u8],
+        finish_reason: Option<FinishReason>,
+    ) -> ChatCompletionChunk {
+        ChatCompletionChunk {
+            index,
+            delta: Bytes::from_static(delta),
+            finish_reason,
+        }
+    }
+
+    fn contents(normalizer: &mut DeltaNormalizer, finished: bool) -> Vec<(u32, String)> {
+        normalizer
+            .drain(finished)
+            .into_iter()
+            .map(|choice| (choice.index, choice.delta.content))
+            .collect()
+    }
+
+    #[test]
+    fn merge_characters_split_across_deltas() {
+        let mut normalizer = DeltaNormalizer::default();
+
+        // "é" is 0xC3 0xA9, "€" is 0xE2 0x82 0xAC
+        normalizer.push(chunk(0, b"caf\xC3", None));
+        normalizer.push(chunk(1, b"\xE2", None));
+        assert_eq!(contents(&mut normalizer, false), [(0, String::from("caf"))]);
+
+        normalizer.push(chunk(0, b"\xA9", None));
+        normalizer.push(chunk(1, b"\x82", None));
+        assert_eq!(contents(&mut normalizer, false), [(0, String::from("é"))]);
+
+        normalizer.push(chunk(1, b"\xAC 5", Some(FinishReason::Stop)));
+        let choices = normalizer.drain(false);
+        assert_eq!(choices.len(), 1);
+        assert_eq!(choices[0].delta.content, "€ 5");
+        assert_eq!(choices[0].finish_reason, Some(FinishReason::Stop));
+    }
+
+    #[test]
+    fn replace_invalid_sequences() {
+        let mut normalizer = DeltaNormalizer::default();
+
+        normalizer.push(chunk(0, b"a\xFFb\xC3", Some(FinishReason::Length)));
+        let choices = normalizer.drain(false);
+        assert_eq!(choices[0].delta.content, "a\u{FFFD}b");
+        assert_eq!(choices[0].finish_reason, None);
+
+        // Incomplete characters left once the stream ended are replaced too
+        let choices = normalizer.drain(true);
+        assert_eq!(choices[0].delta.content, "\u{FFFD}");
+        assert_eq!(choices[0].finish_reason, Some(FinishReason::Length));
+    }
+
+    #[test]
+    fn coalesce_deltas_announcing_the_role_once() {
+        let mut normalizer = DeltaNormalizer::default();
+        for delta in [b"H" as &[u8], b"e", b"y"] {
+            normalizer.push(chunk(0, delta, None));
+        }
+
+        let choices = normalizer.drain(false);
+        assert_eq!(choices[0].delta.content, "Hey");
+        assert!(choices[0].delta.role.is_some());
+
+        normalizer.push(chunk(0, b"!", None));
+        let choices = normalizer.drain(false);
+        assert!(choices[0].delta.role.is_none());
+        assert!(normalizer.drain(true).is_empty());
+    }
+}
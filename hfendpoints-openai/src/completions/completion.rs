@@ -0,0 +1,926 @@
+use crate::chat::completion::{
+    FinishReason, StopSequences, Usage, deserialize_stop, unix_timestamp, validate_sampling,
+};
+use crate::chat::streaming::{
+    DeltaNormalizer, StreamIdentity, StreamedDeltas, event_stream, flush_interval_from_env,
+};
+use crate::completions::COMPLETIONS_TAG;
+use crate::context::{Context, RequestContext};
+use crate::endpoint::{schedule_task, task_router};
+use crate::error::TaskErrors;
+use crate::models::ModelInfo;
+use crate::policy::RequestPolicy;
+use crate::telemetry::record_task;
+use crate::usage::record_output_tokens;
+use crate::{OpenAiError, OpenAiResult, TaskRequest};
+use axum::body::Bytes;
+use axum::extract::State;
+use axum::response::sse::Event;
+use axum::response::{IntoResponse, Response};
+use axum::{Extension, Json};
+use hfendpoints_core::{EndpointContext, Error as EndpointError, RequestSender};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use tokio_stream::{Stream, StreamExt};
+use tracing::instrument;
+use utoipa::ToSchema;
+use utoipa_axum::router::OpenApiRouter;
+use utoipa_axum::routes;
+
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+
+/// Maximum number of alternatives reported along each token when `logprobs` is requested
+const MAX_LOGPROBS: u8 = 5;
+
+/// The prompt(s) to generate completions for, either a string or an array of strings.
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Clone, Deserialize, ToSchema)]
+#[serde(untagged)]
+pub enum Prompt {
+    Single(String),
+    Multiple(Vec<String>),
+}
+
+impl From<Prompt> for Vec<String> {
+    fn from(value: Prompt) -> Self {
+        match value {
+            Prompt::Single(prompt) => vec![prompt],
+            Prompt::Multiple(prompts) => prompts,
+        }
+    }
+}
+
+/// Creates a completion for the provided prompt and parameters.
+#[cfg_attr(feature = "python", pyclass(frozen))]
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Clone, Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct CompletionRequest {
+    /// The prompt(s) to generate completions for, encoded as a string or array of strings.
+    /// Each prompt gets its own choice.
+    #[serde(deserialize_with = "deserialize_prompt")]
+    #[schema(value_type = Prompt)]
+    pub prompt: Vec<String>,
+
+    /// Not used, here for compatibility purpose with OpenAI Platform
+    pub model: Option<String>,
+
+    /// The suffix that comes after a completion of inserted text.
+    pub suffix: Option<String>,
+
+    /// The maximum number of tokens that can be generated in the completion.
+    pub max_tokens: Option<u32>,
+
+    /// What sampling temperature to use, between 0 and 2.
+    /// Higher values like 0.8 will make the output more random, while lower values like 0.2 will make it more focused and deterministic.
+    pub temperature: Option<f32>,
+
+    /// An alternative to sampling with temperature, called nucleus sampling,
+    /// where the model considers the results of the tokens with top_p probability mass.
+    pub top_p: Option<f32>,
+
+    /// Up to 4 sequences where the model will stop generating further tokens.
+    #[serde(default, deserialize_with = "deserialize_stop")]
+    #[schema(value_type = Option<StopSequences>)]
+    pub stop: Vec<String>,
+
+    /// Include the log probabilities of the `logprobs` most likely tokens, as well as the chosen tokens.
+    /// The maximum value is 5.
+    pub logprobs: Option<u8>,
+
+    /// If specified, the system will make a best effort to sample deterministically.
+    pub seed: Option<u64>,
+
+    /// If set, partial completions are sent as server-sent events, ended by a `data: [DONE]` message.
+    pub stream: Option<bool>,
+
+    /// A unique identifier representing the end-user.
+    pub user: Option<String>,
+}
+
+fn deserialize_prompt<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    Ok(Prompt::deserialize(deserializer)?.into())
+}
+
+impl CompletionRequest {
+    #[instrument(skip_all)]
+    fn validate(self) -> OpenAiResult<Self> {
+        if self.prompt.is_empty() {
+            return Err(OpenAiError::Validation(String::from(
+                "Parameter 'prompt' must contain at least one prompt",
+            )));
+        }
+
+        if let Some(logprobs) = self.logprobs.filter(|n| *n > MAX_LOGPROBS) {
+            return Err(OpenAiError::Validation(format!(
+                "Parameter 'logprobs' must be between 0 and {MAX_LOGPROBS}, got {logprobs}"
+            )));
+        }
+
+        validate_sampling(self.temperature, self.top_p, &self.stop)?;
+        Ok(self)
+    }
+}
+
+/// The log probabilities of the generated tokens.
+#[cfg_attr(feature = "python", pyclass(frozen))]
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Clone, Default, Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct CompletionLogprobs {
+    /// The generated tokens.
+    tokens: Vec<String>,
+
+    /// The log probability of each token.
+    token_logprobs: Vec<f32>,
+
+    /// The most likely alternatives of each token, along with their log probability.
+    top_logprobs: Option<Vec<BTreeMap<String, f32>>>,
+
+    /// The offset, in characters, of each token within the text of the choice.
+    text_offset: Vec<u32>,
+}
+
+impl CompletionLogprobs {
+    /// Log probabilities of `tokens`, the first one starting the text they are attached to
+    pub fn new(
+        tokens: Vec<String>,
+        token_logprobs: Vec<f32>,
+        top_logprobs: Option<Vec<BTreeMap<String, f32>>>,
+    ) -> Self {
+        let text_offset = tokens
+            .iter()
+            .scan(0, |offset, token| {
+                let start = *offset;
+                *offset += token.chars().count() as u32;
+                Some(start)
+            })
+            .collect();
+
+        Self {
+            tokens,
+            token_logprobs,
+            top_logprobs,
+            text_offset,
+        }
+    }
+
+    /// Offset of the end of the last token
+    fn end(&self) -> u32 {
+        match (self.text_offset.last(), self.tokens.last()) {
+            (Some(offset), Some(token)) => offset + token.chars().count() as u32,
+            _ => 0,
+        }
+    }
+
+    /// Append the log probabilities of the tokens following the ones of `self`
+    fn extend(&mut self, other: Self) {
+        let end = self.end();
+        self.text_offset
+            .extend(other.text_offset.into_iter().map(|offset| end + offset));
+
+        // Alternatives are only reported when known for every token
+        self.top_logprobs = match (self.top_logprobs.take(), other.top_logprobs) {
+            (Some(mut top), Some(other)) => {
+                top.extend(other);
+                Some(top)
+            }
+            (None, top) if self.tokens.is_empty() => top,
+            _ => None,
+        };
+
+        self.tokens.extend(other.tokens);
+        self.token_logprobs.extend(other.token_logprobs);
+    }
+}
+
+/// Log probabilities streamed along the deltas of each choice
+#[derive(Default)]
+struct LogprobsBuffer(BTreeMap<u32, CompletionLogprobs>);
+
+impl LogprobsBuffer {
+    fn push(&mut self, index: u32, logprobs: Option<CompletionLogprobs>) {
+        if let Some(logprobs) = logprobs {
+            self.0.entry(index).or_default().extend(logprobs);
+        }
+    }
+
+    fn take(&mut self, index: u32) -> Option<CompletionLogprobs> {
+        self.0.remove(&index)
+    }
+}
+
+/// One of the completion choices generated by the model.
+#[cfg_attr(feature = "python", pyclass(frozen))]
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Clone, Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct CompletionChoice {
+    /// The generated text.
+    text: String,
+
+    /// The index of the choice in the list of choices.
+    index: u32,
+
+    /// The log probabilities of the generated tokens, when requested.
+    logprobs: Option<CompletionLogprobs>,
+
+    /// The reason the model stopped generating tokens.
+    finish_reason: FinishReason,
+}
+
+impl CompletionChoice {
+    pub fn new(
+        index: u32,
+        text: String,
+        finish_reason: FinishReason,
+        logprobs: Option<CompletionLogprobs>,
+    ) -> Self {
+        Self {
+            text,
+            index,
+            logprobs,
+            finish_reason,
+        }
+    }
+}
+
+/// Represents a completion response from the API.
+#[cfg_attr(feature = "python", pyclass(frozen))]
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Clone, Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct CompletionResponse {
+    /// A unique identifier for the completion.
+    id: String,
+
+    /// The object type, which is always `text_completion`.
+    #[serde(skip_deserializing, default = "text_completion_object")]
+    object: &'static str,
+
+    /// The Unix timestamp (in seconds) of when the completion was created.
+    created: u64,
+
+    /// The model used for completion.
+    model: String,
+
+    /// The list of completion choices the model generated for the input prompt.
+    choices: Vec<CompletionChoice>,
+
+    /// Usage statistics for the completion request.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    usage: Option<Usage>,
+}
+
+#[inline]
+fn text_completion_object() -> &'static str {
+    "text_completion"
+}
+
+impl CompletionResponse {
+    pub fn new(model: String, choices: Vec<CompletionChoice>, usage: Option<Usage>) -> Self {
+        Self {
+            id: String::new(),
+            object: "text_completion",
+            created: 0,
+            model,
+            choices,
+            usage,
+        }
+    }
+
+    /// Assign the identifier and creation time, set by the route once the handler answered
+    fn stamp(mut self, request_id: &str, created: SystemTime) -> Self {
+        self.id = completion_id(request_id);
+        self.created = unix_timestamp(created);
+        self
+    }
+}
+
+#[inline]
+fn completion_id(request_id: &str) -> String {
+    format!("cmpl-{request_id}")
+}
+
+impl IntoResponse for CompletionResponse {
+    fn into_response(self) -> Response {
+        Json::from(self).into_response()
+    }
+}
+
+/// Delta of one of the choices, emitted by handlers streaming the completion.
+///
+/// As for chat completions, the delta is raw UTF-8 which may end in the middle of a character.
+#[cfg_attr(feature = "python", pyclass(frozen))]
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub struct CompletionChunk {
+    /// The index of the choice in the list of choices.
+    pub(crate) index: u32,
+
+    /// Text generated since the previous chunk of this choice.
+    #[serde(with = "crate::base64_bytes")]
+    pub(crate) delta: Bytes,
+
+    /// The reason the model stopped generating tokens, for the last chunk of the choice.
+    pub(crate) finish_reason: Option<FinishReason>,
+
+    /// The log probabilities of the tokens generated since the previous chunk of this choice.
+    pub(crate) logprobs: Option<CompletionLogprobs>,
+}
+
+impl CompletionChunk {
+    pub fn new(
+        index: u32,
+        delta: impl Into<Bytes>,
+        finish_reason: Option<FinishReason>,
+        logprobs: Option<CompletionLogprobs>,
+    ) -> Self {
+        Self {
+            index,
+            delta: delta.into(),
+            finish_reason,
+            logprobs,
+        }
+    }
+}
+
+/// Outcome of the handler, either the complete response or, when streaming, one of its chunks.
+///
+/// As for chat completions, the route converts one into the other according to `stream`.
+#[cfg_attr(feature = "python", derive(FromPyObject))]
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CompletionOutput {
+    Completion(CompletionResponse),
+    Chunk(CompletionChunk),
+}
+
+impl From<CompletionResponse> for CompletionOutput {
+    fn from(value: CompletionResponse) -> Self {
+        Self::Completion(value)
+    }
+}
+
+impl From<CompletionChunk> for CompletionOutput {
+    fn from(value: CompletionChunk) -> Self {
+        Self::Chunk(value)
+    }
+}
+
+impl CompletionOutput {
+    /// Chunks streaming this outcome, a complete response streaming each choice at once
+    fn into_chunks(self) -> impl Iterator<Item = CompletionChunk> {
+        let chunks = match self {
+            Self::Completion(completion) => completion
+                .choices
+                .into_iter()
+                .map(|choice| {
+                    CompletionChunk::new(
+                        choice.index,
+                        choice.text,
+                        Some(choice.finish_reason),
+                        choice.logprobs,
+                    )
+                })
+                .collect(),
+            Self::Chunk(chunk) => vec![chunk],
+        };
+        chunks.into_iter()
+    }
+}
+
+/// One of the choices of a streamed `text_completion` object
+#[derive(Serialize)]
+struct CompletionChunkChoice {
+    text: String,
+    index: u32,
+    logprobs: Option<CompletionLogprobs>,
+    finish_reason: Option<FinishReason>,
+}
+
+/// Deltas of a completion, sent as `text_completion` objects
+struct CompletionDeltas {
+    identity: StreamIdentity,
+    normalizer: DeltaNormalizer,
+    logprobs: LogprobsBuffer,
+}
+
+impl CompletionDeltas {
+    fn new(identity: StreamIdentity) -> Self {
+        Self {
+            identity,
+            normalizer: DeltaNormalizer::default(),
+            logprobs: LogprobsBuffer::default(),
+        }
+    }
+}
+
+impl StreamedDeltas for CompletionDeltas {
+    type Output = CompletionOutput;
+
+    fn push(&mut self, output: Self::Output) {
+        for chunk in output.into_chunks() {
+            self.normalizer
+                .push_delta(chunk.index, &chunk.delta, chunk.finish_reason);
+            self.logprobs.push(chunk.index, chunk.logprobs);
+        }
+    }
+
+    fn flush(&mut self, finished: bool) -> Option<Event> {
+        let choices: Vec<_> = self
+            .normalizer
+            .drain(finished)
+            .into_iter()
+            .map(|choice| CompletionChunkChoice {
+                text: choice.delta.content,
+                index: choice.index,
+                logprobs: self.logprobs.take(choice.index),
+                finish_reason: choice.finish_reason,
+            })
+            .collect();
+        (!choices.is_empty()).then(|| self.identity.event("text_completion", choices))
+    }
+}
+
+/// Gather the outcomes of a handler streaming its chunks into a complete response
+async fn assemble(
+    mut outputs: impl Stream<Item = Result<CompletionOutput, EndpointError>> + Unpin,
+    model: String,
+) -> OpenAiResult<CompletionResponse> {
+    let mut normalizer = DeltaNormalizer::default();
+    let mut logprobs = LogprobsBuffer::default();
+    let mut streamed = false;
+    while let Some(output) = outputs.next().await {
+        match output? {
+            // Handlers answering with a complete response are not expected to stream anything else
+            CompletionOutput::Completion(completion) if !streamed => return Ok(completion),
+            output => {
+                streamed = true;
+                for chunk in output.into_chunks() {
+                    normalizer.push_delta(chunk.index, &chunk.delta, chunk.finish_reason);
+                    logprobs.push(chunk.index, chunk.logprobs);
+                }
+            }
+        }
+    }
+
+    let choices: Vec<_> = normalizer
+        .drain(true)
+        .into_iter()
+        .map(|choice| {
+            let finish_reason = choice.finish_reason.unwrap_or(FinishReason::Stop);
+            let logprobs = logprobs.take(choice.index);
+            CompletionChoice::new(choice.index, choice.delta.content, finish_reason, logprobs)
+        })
+        .collect();
+    if choices.is_empty() {
+        return Err(OpenAiError::NoResponse);
+    }
+    Ok(CompletionResponse::new(model, choices, None))
+}
+
+#[utoipa::path(
+    post,
+    path = "/completions",
+    tag = COMPLETIONS_TAG,
+    request_body(content = CompletionRequest, content_type = "application/json"),
+    responses(
+        (status = OK, description = "Creates a completion for the provided prompt, streamed as `text_completion` events when `stream` is set.",
+            content((CompletionResponse = "application/json"), (str = "text/event-stream"))),
+        TaskErrors,
+    )
+)]
+#[instrument(skip(state, policy, model, metadata, request))]
+pub async fn complete(
+    State(state): State<EndpointContext<(CompletionRequest, Context), CompletionOutput>>,
+    Extension(policy): Extension<Arc<RequestPolicy>>,
+    Extension(flush_interval): Extension<Option<Duration>>,
+    model: Option<Extension<Arc<ModelInfo>>>,
+    metadata: RequestContext,
+    Json(mut request): Json<Value>,
+) -> OpenAiResult<Response> {
+    record_task("completion");
+
+    // Apply the operator's defaults and overrides before decoding
+    policy.apply(&mut request);
+    let request = serde_json::from_value::<CompletionRequest>(request)
+        .map_err(|err| OpenAiError::Validation(err.to_string()))?
+        .validate()?;
+
+    // Generations are bounded by the limits of the served model
+    if let (Some(Extension(model)), Some(tokens)) = (&model, request.max_tokens) {
+        model.ensure_output_tokens("max_tokens", tokens)?;
+    }
+
+    // Ask for the inference thread to handle it and wait for answers
+    let id = metadata.request_id().to_string();
+    let stream = request.stream.unwrap_or(false);
+    let model = request.model.clone().unwrap_or_default();
+    let scheduled = schedule_task(&state, metadata, request).await?;
+    if stream {
+        let identity = StreamIdentity {
+            id: completion_id(&id),
+            created: unix_timestamp(SystemTime::now()),
+            model,
+        };
+        let deltas = CompletionDeltas::new(identity);
+        return Ok(event_stream(scheduled, deltas, flush_interval)
+            .await?
+            .into_response());
+    }
+
+    let response = assemble(scheduled.stream(), model).await?;
+    if let Some(usage) = response.usage {
+        record_output_tokens(usage.completion_tokens);
+    }
+    Ok(response.stamp(&id, SystemTime::now()).into_response())
+}
+
+/// Helper factory to build
+/// [OpenAi Platform compatible (legacy) Completion endpoint](https://platform.openai.com/docs/api-reference/completions/create)
+#[derive(Clone)]
+pub struct CompletionRouter(pub RequestSender<(CompletionRequest, Context), CompletionOutput>);
+
+impl From<CompletionRouter> for OpenApiRouter {
+    fn from(value: CompletionRouter) -> Self {
+        let routes = OpenApiRouter::new().routes(routes!(complete));
+        task_router(routes, value.0, "/completions").layer(Extension(flush_interval_from_env()))
+    }
+}
+
+impl TaskRequest for CompletionRequest {
+    type Response = CompletionOutput;
+
+    fn router(sender: RequestSender<(Self, Context), Self::Response>) -> OpenApiRouter {
+        CompletionRouter(sender).into()
+    }
+}
+
+#[cfg(feature = "python")]
+pub(crate) mod python {
+    use crate::chat::completion::{FinishReason, Usage};
+    use crate::completions::completion::{
+        CompletionChoice, CompletionChunk, CompletionLogprobs, CompletionOutput, CompletionRequest,
+        CompletionResponse,
+    };
+    use crate::python::TextDelta;
+    use axum::body::Bytes;
+    use pyo3::exceptions::PyValueError;
+    use pyo3::prelude::*;
+    use pyo3::types::PyBytes;
+    use std::collections::BTreeMap;
+
+    impl TextDelta for CompletionOutput {
+        fn from_text_delta(text: String) -> Option<Self> {
+            Some(CompletionChunk::new(0, text, None, None).into())
+        }
+    }
+
+    fn finish_reason(finish_reason: &str) -> PyResult<FinishReason> {
+        FinishReason::try_from(finish_reason).map_err(|err| PyValueError::new_err(err.to_string()))
+    }
+
+    #[pymethods]
+    impl CompletionRequest {
+        #[getter(prompt)]
+        fn get_prompt(&self) -> Vec<String> {
+            self.prompt.clone()
+        }
+
+        #[getter(model)]
+        fn get_model(&self) -> Option<&str> {
+            self.model.as_deref()
+        }
+
+        #[getter(suffix)]
+        fn get_suffix(&self) -> Option<&str> {
+            self.suffix.as_deref()
+        }
+
+        #[getter(max_tokens)]
+        fn get_max_tokens(&self) -> Option<u32> {
+            self.max_tokens
+        }
+
+        #[getter(temperature)]
+        fn get_temperature(&self) -> Option<f32> {
+            self.temperature
+        }
+
+        #[getter(top_p)]
+        fn get_top_p(&self) -> Option<f32> {
+            self.top_p
+        }
+
+        #[getter(stop)]
+        fn get_stop(&self) -> Vec<String> {
+            self.stop.clone()
+        }
+
+        #[getter(logprobs)]
+        fn get_logprobs(&self) -> Option<u8> {
+            self.logprobs
+        }
+
+        #[getter(seed)]
+        fn get_seed(&self) -> Option<u64> {
+            self.seed
+        }
+
+        #[getter(stream)]
+        fn get_stream(&self) -> bool {
+            self.stream.unwrap_or(false)
+        }
+
+        #[getter(user)]
+        fn get_user(&self) -> Option<&str> {
+            self.user.as_deref()
+        }
+    }
+
+    #[pymethods]
+    impl CompletionLogprobs {
+        #[new]
+        #[pyo3(signature = (tokens, token_logprobs, top_logprobs = None))]
+        fn py_new(
+            tokens: Vec<String>,
+            token_logprobs: Vec<f32>,
+            top_logprobs: Option<Vec<BTreeMap<String, f32>>>,
+        ) -> PyResult<Self> {
+            let lengths = [
+                Some(token_logprobs.len()),
+                top_logprobs.as_ref().map(Vec::len),
+            ];
+            if lengths
+                .into_iter()
+                .flatten()
+                .any(|length| length != tokens.len())
+            {
+                return Err(PyValueError::new_err(
+                    "Log probabilities must be provided for every token",
+                ));
+            }
+            Ok(Self::new(tokens, token_logprobs, top_logprobs))
+        }
+    }
+
+    #[pymethods]
+    impl CompletionChoice {
+        #[new]
+        #[pyo3(signature = (index, text, finish_reason = "stop", logprobs = None))]
+        fn py_new(
+            index: u32,
+            text: String,
+            finish_reason: &str,
+            logprobs: Option<CompletionLogprobs>,
+        ) -> PyResult<Self> {
+            Ok(Self::new(
+                index,
+                text,
+                self::finish_reason(finish_reason)?,
+                logprobs,
+            ))
+        }
+    }
+
+    #[pymethods]
+    impl CompletionChunk {
+        /// `delta` may be `bytes` ending in the middle of a UTF-8 character, completed by the next chunks
+        #[new]
+        #[pyo3(signature = (index, delta, finish_reason = None, logprobs = None))]
+        fn py_new(
+            index: u32,
+            delta: &Bound<'_, PyAny>,
+            finish_reason: Option<&str>,
+            logprobs: Option<CompletionLogprobs>,
+        ) -> PyResult<Self> {
+            let delta = match delta.downcast::<PyBytes>() {
+                Ok(bytes) => Bytes::copy_from_slice(bytes.as_bytes()),
+                Err(_) => Bytes::from(delta.extract::<String>()?),
+            };
+            let finish_reason = finish_reason.map(self::finish_reason).transpose()?;
+            Ok(Self::new(index, delta, finish_reason, logprobs))
+        }
+    }
+
+    #[pymethods]
+    impl CompletionResponse {
+        #[new]
+        #[pyo3(signature = (model, choices, usage = None))]
+        fn py_new(model: String, choices: Vec<CompletionChoice>, usage: Option<Usage>) -> Self {
+            Self::new(model, choices, usage)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::chat::completion::{FinishReason, Usage};
+    use crate::completions::completion::{
+        CompletionChoice, CompletionChunk, CompletionLogprobs, CompletionRequest,
+        CompletionResponse, CompletionRouter,
+    };
+    use crate::testing::{post, stream};
+    use axum::body::Bytes;
+    use axum::http::header::CONTENT_TYPE;
+    use axum::http::{HeaderMap, StatusCode};
+    use hfendpoints_core::request_channel;
+    use serde_json::{Value, json};
+    use std::collections::BTreeMap;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    fn logprobs(tokens: &[(&str, f32)]) -> CompletionLogprobs {
+        CompletionLogprobs::new(
+            tokens.iter().map(|(token, _)| token.to_string()).collect(),
+            tokens.iter().map(|(_, logprob)| *logprob).collect(),
+            None,
+        )
+    }
+
+    #[test]
+    fn deserialize_legacy_request() {
+        let request: CompletionRequest = serde_json::from_value(json!({
+            "model": "gpt-3.5-turbo-instruct",
+            "prompt": "Say this is a test",
+            "suffix": "\n",
+            "max_tokens": 7,
+            "stop": ["\n", "."],
+            "logprobs": 2
+        }))
+        .expect("Failed to deserialize CompletionRequest");
+
+        assert_eq!(request.prompt, vec![String::from("Say this is a test")]);
+        assert_eq!(request.suffix.as_deref(), Some("\n"));
+        assert_eq!(request.max_tokens, Some(7));
+        assert_eq!(request.stop.len(), 2);
+        assert_eq!(request.logprobs, Some(2));
+    }
+
+    #[test]
+    fn serialize_completion_response() {
+        let choice = CompletionChoice::new(
+            0,
+            String::from("This is a test"),
+            FinishReason::Length,
+            Some(logprobs(&[("This", -0.1), (" is", -0.2)])),
+        );
+        let response = CompletionResponse::new(
+            String::from("my-model"),
+            vec![choice],
+            Some(Usage::new(5, 2)),
+        )
+        .stamp("abc", UNIX_EPOCH + Duration::from_secs(1700000000));
+
+        assert_eq!(
+            serde_json::to_value(&response).expect("Failed to serialize CompletionResponse"),
+            json!({
+                "id": "cmpl-abc",
+                "object": "text_completion",
+                "created": 1700000000,
+                "model": "my-model",
+                "choices": [{
+                    "text": "This is a test",
+                    "index": 0,
+                    "logprobs": {
+                        "tokens": ["This", " is"],
+                        "token_logprobs": [-0.1f32, -0.2f32],
+                        "top_logprobs": null,
+                        "text_offset": [0, 4]
+                    },
+                    "finish_reason": "length"
+                }],
+                "usage": {"prompt_tokens": 5, "completion_tokens": 2, "total_tokens": 7}
+            })
+        );
+    }
+
+    #[test]
+    fn merge_streamed_logprobs() {
+        let top = |token: &str| Some(vec![BTreeMap::from([(token.to_string(), -0.5)])]);
+        let mut merged = CompletionLogprobs::new(vec![String::from("né")], vec![-0.5], top("né"));
+        merged.extend(CompletionLogprobs::new(
+            vec![String::from(" ok")],
+            vec![-1.0],
+            top(" ok"),
+        ));
+        assert_eq!(merged.text_offset, [0, 2]);
+        assert_eq!(merged.top_logprobs.map(|top| top.len()), Some(2));
+
+        // Alternatives missing for some tokens are dropped altogether
+        merged = CompletionLogprobs::default();
+        merged.extend(logprobs(&[("a", -0.1)]));
+        merged.extend(CompletionLogprobs::new(
+            vec![String::from("b")],
+            vec![-0.2],
+            top("b"),
+        ));
+        assert_eq!(merged.tokens, ["a", "b"]);
+        assert!(merged.top_logprobs.is_none());
+    }
+
+    async fn complete(
+        router: CompletionRouter,
+        body: &'static str,
+    ) -> (StatusCode, HeaderMap, Bytes) {
+        post(router, "/completions", "application/json", body).await
+    }
+
+    #[tokio::test]
+    async fn complete_rejects_invalid_requests() {
+        for (body, message) in [
+            (
+                r#"{"prompt": []}"#,
+                "Parameter 'prompt' must contain at least one prompt",
+            ),
+            (
+                r#"{"prompt": "Hello", "logprobs": 6}"#,
+                "Parameter 'logprobs' must be between 0 and 5, got 6",
+            ),
+        ] {
+            let (sender, _receiver) = request_channel(1);
+            let (status, _, body) = complete(CompletionRouter(sender), body).await;
+            assert_eq!(status, StatusCode::BAD_REQUEST);
+
+            let body: Value = serde_json::from_slice(&body).unwrap();
+            assert_eq!(body["error"]["message"], message);
+        }
+    }
+
+    /// Spawn a handler streaming "€!" split in the middle of the euro sign, with the logprobs of
+    /// each token
+    fn streaming_router() -> CompletionRouter {
+        let (sender, receiver) = request_channel(1);
+        stream(receiver, |_: CompletionRequest| {
+            [
+                (b"\xE2\x82" as &[u8], None, None),
+                (b"\xAC", None, Some(("€", -0.25))),
+                (b"!", Some(FinishReason::Stop), Some(("!", -0.5))),
+            ]
+            .map(|(delta, finish_reason, token)| {
+                let logprobs = token.map(|token| logprobs(&[token]));
+                let chunk = CompletionChunk::new(0, delta.to_vec(), finish_reason, logprobs);
+                Ok(chunk.into())
+            })
+        });
+        CompletionRouter(sender)
+    }
+
+    #[tokio::test]
+    async fn complete_streams_text_completion_events() {
+        let body = r#"{"prompt": "Price:", "model": "m", "stream": true}"#;
+        let (status, headers, body) = complete(streaming_router(), body).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(headers[CONTENT_TYPE], "text/event-stream");
+
+        let body = String::from_utf8(body.to_vec()).unwrap();
+        let events: Vec<_> = body
+            .lines()
+            .filter_map(|line| line.strip_prefix("data: "))
+            .collect();
+        assert_eq!(events.last(), Some(&"[DONE]"));
+
+        let chunks: Vec<Value> = events[..events.len() - 1]
+            .iter()
+            .map(|event| serde_json::from_str(event).unwrap())
+            .collect();
+        assert!(
+            chunks
+                .iter()
+                .all(|chunk| chunk["object"] == "text_completion")
+        );
+        assert!(chunks.iter().all(|chunk| chunk["id"] == "cmpl-test"));
+
+        let text: String = chunks
+            .iter()
+            .map(|chunk| chunk["choices"][0]["text"].as_str().unwrap())
+            .collect();
+        assert_eq!(text, "€!");
+        assert_eq!(
+            chunks.last().unwrap()["choices"][0]["finish_reason"],
+            "stop"
+        );
+    }
+
+    #[tokio::test]
+    async fn complete_assembles_streamed_chunks() {
+        let body = r#"{"prompt": "Price:", "logprobs": 0}"#;
+        let (status, _, body) = complete(streaming_router(), body).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let json: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["object"], "text_completion");
+        assert_eq!(json["choices"][0]["text"], "€!");
+        assert_eq!(json["choices"][0]["logprobs"]["tokens"], json!(["€", "!"]));
+        assert_eq!(json["choices"][0]["logprobs"]["text_offset"], json!([0, 1]));
+    }
+}
@@ -0,0 +1,47 @@
+pub mod completion;
+
+pub const COMPLETIONS_TAG: &str = "Completions";
+pub const COMPLETIONS_DESC: &str =
+    "Given a prompt, the model will return one or more predicted completions (legacy).";
+
+#[cfg(feature = "python")]
+pub(crate) mod python {
+    use crate::chat::completion::Usage;
+    use crate::completions::completion::{
+        CompletionChoice, CompletionChunk, CompletionLogprobs, CompletionRequest,
+        CompletionResponse,
+    };
+    use hfendpoints_binding_python::ImportablePyModuleBuilder;
+    use pyo3::prelude::*;
+
+    mod completions {
+        use crate::completions::completion::{
+            CompletionOutput, CompletionRequest, CompletionRouter,
+        };
+        use crate::python::{impl_pyendpoint, impl_pyhandler};
+
+        impl_pyhandler!(CompletionRequest, CompletionOutput);
+        impl_pyendpoint!(
+            "CompletionEndpoint",
+            PyCompletionEndpoint,
+            PyHandler,
+            CompletionRouter
+        );
+    }
+
+    /// Bind hfendpoints.openai.completions submodule into the exported Python wheel
+    pub fn bind<'py>(py: Python<'py>, name: &str) -> PyResult<Bound<'py, PyModule>> {
+        let module = ImportablePyModuleBuilder::new(py, name)?
+            .defaults()?
+            .add_class::<CompletionRequest>()?
+            .add_class::<CompletionLogprobs>()?
+            .add_class::<CompletionChoice>()?
+            .add_class::<Usage>()?
+            .add_class::<CompletionResponse>()?
+            .add_class::<CompletionChunk>()?
+            .add_class::<completions::PyCompletionEndpoint>()?
+            .finish();
+
+        Ok(module)
+    }
+}
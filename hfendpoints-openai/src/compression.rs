@@ -0,0 +1,148 @@
+use crate::{OpenAiError, OpenAiResult};
+use axum::body::Bytes;
+use axum::http::HeaderMap;
+use axum::http::header::CONTENT_ENCODING;
+use flate2::read::GzDecoder;
+use std::io::Read;
+use tokio::task::spawn_blocking;
+use tracing::{debug, instrument};
+
+/// Compression schemes accepted on uploaded payloads
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub(crate) enum ContentEncoding {
+    Identity,
+    Gzip,
+    Zstd,
+}
+
+impl ContentEncoding {
+    /// Read the `Content-Encoding` header of a request or a multipart field
+    pub(crate) fn from_headers(headers: &HeaderMap) -> OpenAiResult<Self> {
+        let Some(encoding) = headers.get(CONTENT_ENCODING) else {
+            return Ok(Self::Identity);
+        };
+
+        match encoding.to_str().map(str::trim) {
+            Ok("identity") => Ok(Self::Identity),
+            Ok("gzip" | "x-gzip") => Ok(Self::Gzip),
+            Ok("zstd") => Ok(Self::Zstd),
+            _ => Err(OpenAiError::Validation(format!(
+                "Unsupported Content-Encoding: {encoding:?}. Possible values are: 'gzip', 'zstd', 'identity'."
+            ))),
+        }
+    }
+}
+
+/// Decompress `content` encoded with `encoding`, failing as soon as the decompressed payload
+/// goes over `limit` bytes to protect against decompression bombs.
+#[instrument(skip(content))]
+pub(crate) async fn decompress(
+    content: Bytes,
+    encoding: ContentEncoding,
+    limit: usize,
+) -> OpenAiResult<Bytes> {
+    if encoding == ContentEncoding::Identity {
+        return Ok(content);
+    }
+
+    debug!("Decompressing {} bytes payload", content.len());
+
+    // Decompression is CPU bound, keep it away from the I/O threads
+    spawn_blocking(move || {
+        let reader = content.as_ref();
+        match encoding {
+            ContentEncoding::Identity => unreachable!(),
+            ContentEncoding::Gzip => read_bounded(GzDecoder::new(reader), limit),
+            ContentEncoding::Zstd => read_bounded(zstd::Decoder::new(reader)?, limit),
+        }
+    })
+    .await
+    .map_err(|err| OpenAiError::Io(err.into()))?
+}
+
+#[inline]
+pub(crate) fn read_bounded<R: Read>(decoder: R, limit: usize) -> OpenAiResult<Bytes> {
+    // Read one byte past the limit to tell apart payloads of exactly `limit` bytes
+    let mut decompressed = Vec::new();
+    decoder
+        .take(limit as u64 + 1)
+        .read_to_end(&mut decompressed)
+        .map_err(|err| OpenAiError::Validation(format!("Failed to decompress payload: {err}")))?;
+
+    if decompressed.len() > limit {
+        Err(OpenAiError::PayloadTooLarge {
+            limit,
+            received: Some(decompressed.len() as u64),
+        })
+    } else {
+        Ok(Bytes::from(decompressed))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compression::{ContentEncoding, decompress};
+    use crate::error::OpenAiError;
+    use axum::body::Bytes;
+    use axum::http::header::CONTENT_ENCODING;
+    use axum::http::{HeaderMap, HeaderValue};
+    use flate2::Compression;
+    use flate2::write::GzEncoder;
+    use std::io::Write;
+
+    fn gzip(content: &[u8]) -> Bytes {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(content).unwrap();
+        Bytes::from(encoder.finish().unwrap())
+    }
+
+    #[test]
+    fn parse_content_encoding() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(
+            ContentEncoding::from_headers(&headers).unwrap(),
+            ContentEncoding::Identity
+        );
+
+        headers.insert(CONTENT_ENCODING, HeaderValue::from_static("zstd"));
+        assert_eq!(
+            ContentEncoding::from_headers(&headers).unwrap(),
+            ContentEncoding::Zstd
+        );
+
+        headers.insert(CONTENT_ENCODING, HeaderValue::from_static("br"));
+        assert!(ContentEncoding::from_headers(&headers).is_err());
+    }
+
+    #[tokio::test]
+    async fn decompress_gzip_and_zstd() {
+        let content = b"RIFF....WAVEfmt ".repeat(64);
+
+        let decompressed = decompress(gzip(&content), ContentEncoding::Gzip, 4096)
+            .await
+            .unwrap();
+        assert_eq!(decompressed, content);
+
+        let compressed = Bytes::from(zstd::encode_all(content.as_slice(), 3).unwrap());
+        let decompressed = decompress(compressed, ContentEncoding::Zstd, 4096)
+            .await
+            .unwrap();
+        assert_eq!(decompressed, content);
+    }
+
+    #[tokio::test]
+    async fn decompress_rejects_bombs() {
+        let bomb = gzip(&vec![0u8; 1024 * 1024]);
+        let err = decompress(bomb, ContentEncoding::Gzip, 1024)
+            .await
+            .unwrap_err();
+
+        assert!(matches!(
+            err,
+            OpenAiError::PayloadTooLarge {
+                limit: 1024,
+                received: Some(1025)
+            }
+        ));
+    }
+}
@@ -0,0 +1,463 @@
+//! Settings of the endpoint, resolved from the environment and an optional configuration file.
+//!
+//! Containers are configured without code changes: the settings below are read from a TOML or
+//! YAML file referenced by `HFENDPOINT_CONFIG` (the format being inferred from its extension),
+//! each of them being overridden by its environment variable when set. Reading TOML files requires
+//! the `config-toml` feature, YAML ones the `config-yaml` feature.
+//!
+//! ```toml
+//! interface = "0.0.0.0"          # INTERFACE
+//! port = 8000                    # PORT
+//! body_limit = 33554432          # HFENDPOINT_BODY_LIMIT, in bytes
+//! queue_capacity = 128           # HFENDPOINT_QUEUE_CAPACITY
+//! max_concurrent_requests = 512  # HFENDPOINT_MAX_CONCURRENT_REQUESTS
+//! request_timeout = 30.0         # HFENDPOINT_REQUEST_TIMEOUT, in seconds
+//! shutdown_grace_period = 30     # HFENDPOINT_SHUTDOWN_GRACE_PERIOD, in seconds
+//! api_keys = ["my-key"]          # HF_ENDPOINT_API_KEYS, comma separated
+//! log_format = "json"            # HFENDPOINT_LOG_FORMAT, either text or json
+//! ```
+//!
+//! The file is read again whenever the settings are resolved, it holds secrets when listing API
+//! keys and is never recorded in the snapshots, only its path is.
+use crate::auth::split_list;
+use crate::priority::{DEFAULT_MAX_CONCURRENT_REQUESTS, MAX_CONCURRENT_REQUESTS_ENV};
+use crate::shutdown::DEFAULT_GRACE_PERIOD;
+use crate::{
+    API_KEYS_ENV, DEFAULT_INTERFACE, DEFAULT_PORT, OpenAiError, OpenAiResult,
+    SHUTDOWN_GRACE_PERIOD_ENV,
+};
+use hfendpoints_core::{DEFAULT_QUEUE_CAPACITY, QUEUE_CAPACITY_ENV, REQUEST_TIMEOUT_ENV};
+use serde::Deserialize;
+use std::fmt::{Debug, Formatter};
+use std::path::Path;
+use std::str::FromStr;
+use std::time::Duration;
+use tracing::warn;
+
+/// Environment variable holding the path of the TOML or YAML configuration file
+pub const CONFIG_FILE_ENV: &str = "HFENDPOINT_CONFIG";
+
+/// Environment variable holding the interface the endpoint listens on, as set by Inference Endpoints
+pub const INTERFACE_ENV: &str = "INTERFACE";
+
+/// Environment variable holding the port the endpoint listens on, as set by Inference Endpoints
+pub const PORT_ENV: &str = "PORT";
+
+/// Environment variable holding the maximum size, in bytes, of the request bodies
+pub const BODY_LIMIT_ENV: &str = "HFENDPOINT_BODY_LIMIT";
+
+/// Environment variable holding the format of the logs, either `text` or `json`
+pub const LOG_FORMAT_ENV: &str = "HFENDPOINT_LOG_FORMAT";
+
+/// Format of the logs written to the standard output
+#[derive(Copy, Clone, Debug, Default, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// Human-readable lines
+    #[default]
+    Text,
+
+    /// A JSON object per line, as ingested by log collectors
+    Json,
+}
+
+impl LogFormat {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Text => "text",
+            Self::Json => "json",
+        }
+    }
+}
+
+impl FromStr for LogFormat {
+    type Err = OpenAiError;
+
+    fn from_str(format: &str) -> Result<Self, Self::Err> {
+        match format.to_ascii_lowercase().as_str() {
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            _ => Err(OpenAiError::Config(format!(
+                "Unknown log format '{format}', expected text or json"
+            ))),
+        }
+    }
+}
+
+/// Settings as written in the configuration file, every one of them being optional
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct ConfigFile {
+    interface: Option<String>,
+    port: Option<u16>,
+    body_limit: Option<usize>,
+    queue_capacity: Option<usize>,
+    max_concurrent_requests: Option<usize>,
+    request_timeout: Option<f64>,
+    shutdown_grace_period: Option<u64>,
+    api_keys: Option<Vec<String>>,
+    log_format: Option<LogFormat>,
+}
+
+impl ConfigFile {
+    /// Decode the `content` of the file at `path`, in the format its extension denotes
+    #[cfg_attr(
+        not(all(feature = "config-toml", feature = "config-yaml")),
+        allow(unused_variables)
+    )]
+    fn parse(path: &Path, content: &str) -> Result<Self, String> {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            #[cfg(feature = "config-toml")]
+            Some("toml") => toml::from_str(content).map_err(|err| err.to_string()),
+            #[cfg(not(feature = "config-toml"))]
+            Some("toml") => Err(String::from(
+                "TOML files are only read with the config-toml feature",
+            )),
+            #[cfg(feature = "config-yaml")]
+            Some("yaml" | "yml") => serde_norway::from_str(content).map_err(|err| err.to_string()),
+            #[cfg(not(feature = "config-yaml"))]
+            Some("yaml" | "yml") => Err(String::from(
+                "YAML files are only read with the config-yaml feature",
+            )),
+            _ => Err(String::from(
+                "unknown format, expected a .toml, .yaml or .yml file",
+            )),
+        }
+    }
+}
+
+/// Settings of the endpoint, see the [module documentation](self) for the sources they are read from
+#[derive(Clone, PartialEq)]
+pub struct EndpointConfig {
+    /// Interface the endpoint listens on
+    pub interface: String,
+
+    /// Port the endpoint listens on
+    pub port: u16,
+
+    /// Maximum size of the request bodies, unbounded when not set
+    pub body_limit: Option<usize>,
+
+    /// Maximum number of requests queued or processed by the handler
+    pub queue_capacity: usize,
+
+    /// Maximum number of requests processed at once, the operational ones aside, see `priority`
+    pub max_concurrent_requests: usize,
+
+    /// Time a request may take before failing with a timeout, never when not set
+    pub request_timeout: Option<Duration>,
+
+    /// Time in-flight requests are given to complete once the process is asked to terminate
+    pub shutdown_grace_period: Duration,
+
+    /// API keys accepted by the endpoint, authentication being disabled when empty
+    pub api_keys: Vec<String>,
+
+    /// Format of the logs
+    pub log_format: LogFormat,
+}
+
+impl Default for EndpointConfig {
+    fn default() -> Self {
+        Self {
+            interface: String::from(DEFAULT_INTERFACE),
+            port: DEFAULT_PORT,
+            body_limit: None,
+            queue_capacity: DEFAULT_QUEUE_CAPACITY,
+            max_concurrent_requests: DEFAULT_MAX_CONCURRENT_REQUESTS,
+            request_timeout: None,
+            shutdown_grace_period: DEFAULT_GRACE_PERIOD,
+            api_keys: Vec::new(),
+            log_format: LogFormat::Text,
+        }
+    }
+}
+
+// API keys are secrets, they are never written to the logs
+impl Debug for EndpointConfig {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EndpointConfig")
+            .field("interface", &self.interface)
+            .field("port", &self.port)
+            .field("body_limit", &self.body_limit)
+            .field("queue_capacity", &self.queue_capacity)
+            .field("max_concurrent_requests", &self.max_concurrent_requests)
+            .field("request_timeout", &self.request_timeout)
+            .field("shutdown_grace_period", &self.shutdown_grace_period)
+            .field(
+                "api_keys",
+                &format_args!("[{} redacted]", self.api_keys.len()),
+            )
+            .field("log_format", &self.log_format)
+            .finish()
+    }
+}
+
+/// Value of the variable `name` looked up through `var`, parsed by `parse`, malformed values
+/// being ignored
+fn parse_var<T>(
+    var: &impl Fn(&str) -> Option<String>,
+    name: &str,
+    expected: &str,
+    parse: impl FnOnce(&str) -> Option<T>,
+) -> Option<T> {
+    let value = var(name)?;
+    let parsed = parse(value.trim());
+    if parsed.is_none() {
+        warn!("Ignoring malformed {name} ({value}), expected {expected}");
+    }
+    parsed
+}
+
+/// Duration of a positive number of `seconds`
+fn positive_seconds(seconds: f64) -> Option<Duration> {
+    Duration::try_from_secs_f64(seconds)
+        .ok()
+        .filter(|duration| !duration.is_zero())
+}
+
+impl EndpointConfig {
+    /// Settings of the TOML (`.toml`) or YAML (`.yaml`, `.yml`) file at `path`, the ones it does
+    /// not define keeping their default value. The environment is not looked at.
+    pub fn from_file(path: impl AsRef<Path>) -> OpenAiResult<Self> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path).map_err(|err| {
+            OpenAiError::Config(format!("Failed to read {}: {err}", path.display()))
+        })?;
+
+        let file = ConfigFile::parse(path, &content)
+            .map_err(|err| OpenAiError::Config(format!("{}: {err}", path.display())))?;
+
+        Self::default().merged(file)
+    }
+
+    /// Settings of the file referenced by `HFENDPOINT_CONFIG`, if any, overridden by the
+    /// environment variables which are set
+    pub fn from_env() -> OpenAiResult<Self> {
+        let config = match std::env::var(CONFIG_FILE_ENV) {
+            Ok(path) if !path.is_empty() => Self::from_file(path)?,
+            _ => Self::default(),
+        };
+        Ok(config.overridden(|name| std::env::var(name).ok()))
+    }
+
+    /// Settings resolved by [`EndpointConfig::from_env`], falling back to the environment alone
+    /// when the file is not valid
+    pub(crate) fn current() -> Self {
+        Self::from_env().unwrap_or_else(|err| {
+            warn!("Ignoring the configuration file: {err}");
+            Self::default().overridden(|name| std::env::var(name).ok())
+        })
+    }
+
+    /// Apply the settings defined by the configuration `file`
+    fn merged(mut self, file: ConfigFile) -> OpenAiResult<Self> {
+        if let Some(interface) = file.interface {
+            self.interface = interface;
+        }
+        if let Some(port) = file.port {
+            self.port = port;
+        }
+        if let Some(limit) = file.body_limit {
+            self.body_limit = Some(limit);
+        }
+        if let Some(capacity) = file.queue_capacity {
+            if capacity == 0 {
+                return Err(OpenAiError::Config(String::from(
+                    "queue_capacity must be a positive integer",
+                )));
+            }
+            self.queue_capacity = capacity;
+        }
+        if let Some(concurrency) = file.max_concurrent_requests {
+            if concurrency == 0 {
+                return Err(OpenAiError::Config(String::from(
+                    "max_concurrent_requests must be a positive integer",
+                )));
+            }
+            self.max_concurrent_requests = concurrency;
+        }
+        if let Some(timeout) = file.request_timeout {
+            self.request_timeout = Some(positive_seconds(timeout).ok_or_else(|| {
+                OpenAiError::Config(String::from(
+                    "request_timeout must be a positive number of seconds",
+                ))
+            })?);
+        }
+        if let Some(seconds) = file.shutdown_grace_period {
+            self.shutdown_grace_period = Duration::from_secs(seconds);
+        }
+        if let Some(keys) = file.api_keys {
+            self.api_keys = keys;
+        }
+        if let Some(format) = file.log_format {
+            self.log_format = format;
+        }
+        Ok(self)
+    }
+
+    /// Override the settings with the environment variables `var` resolves
+    fn overridden(mut self, var: impl Fn(&str) -> Option<String>) -> Self {
+        if let Some(interface) = var(INTERFACE_ENV) {
+            self.interface = interface;
+        }
+        if let Some(port) = parse_var(&var, PORT_ENV, "a port number", |port| port.parse().ok()) {
+            self.port = port;
+        }
+        if let Some(limit) = parse_var(&var, BODY_LIMIT_ENV, "a number of bytes", |limit| {
+            limit.parse().ok()
+        }) {
+            self.body_limit = Some(limit);
+        }
+        if let Some(capacity) =
+            parse_var(&var, QUEUE_CAPACITY_ENV, "a positive integer", |capacity| {
+                capacity.parse().ok().filter(|capacity| *capacity > 0)
+            })
+        {
+            self.queue_capacity = capacity;
+        }
+        if let Some(concurrency) = parse_var(
+            &var,
+            MAX_CONCURRENT_REQUESTS_ENV,
+            "a positive integer",
+            |concurrency| {
+                concurrency
+                    .parse()
+                    .ok()
+                    .filter(|concurrency| *concurrency > 0)
+            },
+        ) {
+            self.max_concurrent_requests = concurrency;
+        }
+        if let Some(timeout) = parse_var(
+            &var,
+            REQUEST_TIMEOUT_ENV,
+            "a positive number of seconds",
+            |seconds| seconds.parse().ok().and_then(positive_seconds),
+        ) {
+            self.request_timeout = Some(timeout);
+        }
+        if let Some(seconds) = parse_var(
+            &var,
+            SHUTDOWN_GRACE_PERIOD_ENV,
+            "a number of seconds",
+            |seconds| seconds.parse().ok(),
+        ) {
+            self.shutdown_grace_period = Duration::from_secs(seconds);
+        }
+        if let Some(keys) = var(API_KEYS_ENV) {
+            self.api_keys = split_list(&keys).map(String::from).collect();
+            if self.api_keys.is_empty() {
+                warn!("{API_KEYS_ENV} does not hold any API key, authentication is disabled");
+            }
+        }
+        if let Some(format) = parse_var(&var, LOG_FORMAT_ENV, "text or json", |format| {
+            format.parse().ok()
+        }) {
+            self.log_format = format;
+        }
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::EndpointConfig;
+    #[cfg(feature = "config-toml")]
+    use crate::config::LogFormat;
+    use crate::error::OpenAiError;
+    #[cfg(feature = "config-toml")]
+    use std::collections::HashMap;
+    #[cfg(any(feature = "config-toml", feature = "config-yaml"))]
+    use std::time::Duration;
+
+    fn write(name: &str, content: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("hfendpoints-config-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(name);
+        std::fs::write(&path, content).unwrap();
+        path
+    }
+
+    #[test]
+    #[cfg(feature = "config-toml")]
+    fn read_toml_files() {
+        let toml = write(
+            "endpoint.toml",
+            "port = 9000\nbody_limit = 1024\nrequest_timeout = 2.5\napi_keys = [\"a\", \"b\"]\nlog_format = \"json\"\n",
+        );
+        let config = EndpointConfig::from_file(&toml).unwrap();
+        assert_eq!(config.interface, "0.0.0.0");
+        assert_eq!(config.port, 9000);
+        assert_eq!(config.body_limit, Some(1024));
+        assert_eq!(config.request_timeout, Some(Duration::from_secs_f64(2.5)));
+        assert_eq!(config.api_keys, ["a", "b"]);
+        assert_eq!(config.log_format, LogFormat::Json);
+        assert!(!format!("{config:?}").contains("\"a\""));
+
+        // Typos are reported rather than silently ignored
+        let typo = write("typo.toml", "prot = 9000\n");
+        assert!(matches!(
+            EndpointConfig::from_file(&typo),
+            Err(OpenAiError::Config(_))
+        ));
+    }
+
+    #[test]
+    #[cfg(feature = "config-yaml")]
+    fn read_yaml_files() {
+        let yaml = write(
+            "endpoint.yaml",
+            "interface: 127.0.0.1\nqueue_capacity: 4\nmax_concurrent_requests: 8\nshutdown_grace_period: 5\n",
+        );
+        let config = EndpointConfig::from_file(&yaml).unwrap();
+        assert_eq!(config.interface, "127.0.0.1");
+        assert_eq!(config.queue_capacity, 4);
+        assert_eq!(config.max_concurrent_requests, 8);
+        assert_eq!(config.shutdown_grace_period, Duration::from_secs(5));
+
+        let invalid = write("invalid.yml", "queue_capacity: 0\n");
+        assert!(EndpointConfig::from_file(&invalid).is_err());
+    }
+
+    #[test]
+    fn reject_files_of_formats_not_enabled() {
+        let unknown = write("endpoint.json", "{}");
+        assert!(matches!(
+            EndpointConfig::from_file(&unknown),
+            Err(OpenAiError::Config(_))
+        ));
+
+        #[cfg(not(feature = "config-yaml"))]
+        {
+            let yaml = write("disabled.yaml", "port: 9000\n");
+            let Err(OpenAiError::Config(err)) = EndpointConfig::from_file(&yaml) else {
+                panic!("YAML files are not read without the config-yaml feature");
+            };
+            assert!(err.contains("config-yaml"), "{err}");
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "config-toml")]
+    fn environment_overrides_the_file() {
+        let file = write("overridden.toml", "port = 9000\nqueue_capacity = 4\n");
+        let variables = HashMap::from([
+            ("PORT", "9001"),
+            ("HFENDPOINT_QUEUE_CAPACITY", "not a number"),
+            ("HFENDPOINT_REQUEST_TIMEOUT", "10"),
+            ("HF_ENDPOINT_API_KEYS", "key-1, key-2"),
+            ("HFENDPOINT_LOG_FORMAT", "JSON"),
+        ]);
+
+        let config = EndpointConfig::from_file(file)
+            .unwrap()
+            .overridden(|name| variables.get(name).map(|value| value.to_string()));
+        assert_eq!(config.port, 9001);
+        // Malformed variables keep the value of the file
+        assert_eq!(config.queue_capacity, 4);
+        assert_eq!(config.request_timeout, Some(Duration::from_secs(10)));
+        assert_eq!(config.api_keys, ["key-1", "key-2"]);
+        assert_eq!(config.log_format, LogFormat::Json);
+    }
+}
@@ -1,32 +1,444 @@
+use crate::auth::Caller;
+use crate::fallback::{self, ServedBy};
 use crate::headers::RequestId;
+use crate::listener::PeerAddr;
+use crate::metadata::Metadata;
+use crate::oidc::Identity;
+use crate::telemetry::millis;
+#[cfg(feature = "distributed")]
+use crate::telemetry::remote_span;
+use axum::extract::{ConnectInfo, FromRequestParts};
+use axum::http::header::{ACCEPT_LANGUAGE, USER_AGENT};
+use axum::http::request::Parts;
+use axum::http::{HeaderMap, HeaderName};
+use axum_extra::typed_header::TypedHeaderRejection;
+use axum_extra::TypedHeader;
+use hfendpoints_core::{CancellationToken, Timeslice};
 #[cfg(feature = "python")]
 use pyo3::prelude::*;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::{Instant, SystemTime};
+use tracing::{info_span, Instrument, Span};
+
+/// Headers of the request handed over to the handler, the other ones (i.e. credentials) being withheld
+const FORWARDED_HEADERS: [HeaderName; 8] = [
+    USER_AGENT,
+    ACCEPT_LANGUAGE,
+    HeaderName::from_static("x-forwarded-for"),
+    HeaderName::from_static("x-forwarded-host"),
+    HeaderName::from_static("x-forwarded-proto"),
+    HeaderName::from_static("x-real-ip"),
+    HeaderName::from_static("traceparent"),
+    HeaderName::from_static("tracestate"),
+];
+
+/// Metadata of the request received, handed over to the handler along with it for logging and
+/// correlation purposes
+#[cfg_attr(feature = "python", pyclass(frozen))]
+#[derive(Clone, Debug)]
+pub struct RequestContext {
+    /// Correlation ID for the request
+    request_id: RequestId,
+
+    /// When the request was received
+    received_at: SystemTime,
+
+    /// Headers of the request among `FORWARDED_HEADERS`
+    headers: HeaderMap,
+
+    /// Address of the client, or of the proxy in front of the endpoint, if known
+    remote_addr: Option<SocketAddr>,
+
+    /// Caller authenticated through a token issued by the OpenID provider, see `oidc`
+    identity: Option<Identity>,
+
+    /// Caller as identified by the credential of the request, when authenticated, see `auth`
+    caller: Option<Caller>,
+
+    /// Opaque JSON attached by the caller, see `metadata`
+    metadata: Option<Metadata>,
+}
+
+impl RequestContext {
+    /// Metadata of the request identified by `request_id`, received now
+    pub fn new(request_id: RequestId) -> Self {
+        Self {
+            request_id,
+            received_at: SystemTime::now(),
+            headers: HeaderMap::new(),
+            remote_addr: None,
+            identity: None,
+            caller: None,
+            metadata: None,
+        }
+    }
+
+    /// Keep the forwarded headers among `headers`
+    pub fn with_headers(mut self, headers: &HeaderMap) -> Self {
+        for name in FORWARDED_HEADERS {
+            for value in headers.get_all(&name) {
+                self.headers.append(name.clone(), value.clone());
+            }
+        }
+        self
+    }
+
+    /// Request received from `remote_addr`
+    pub fn with_remote_addr(mut self, remote_addr: SocketAddr) -> Self {
+        self.remote_addr = Some(remote_addr);
+        self
+    }
+
+    /// Request sent by the caller `identity`
+    pub fn with_identity(mut self, identity: Identity) -> Self {
+        self.identity = Some(identity);
+        self
+    }
+
+    /// Request authenticated as sent by `caller`
+    pub(crate) fn with_caller(mut self, caller: Caller) -> Self {
+        self.caller = Some(caller);
+        self
+    }
+
+    /// Request carrying the caller's `metadata`
+    pub fn with_metadata(mut self, metadata: Metadata) -> Self {
+        self.metadata = Some(metadata);
+        self
+    }
+
+    /// Correlation ID for the request
+    pub fn request_id(&self) -> &RequestId {
+        &self.request_id
+    }
+
+    /// When the request was received
+    pub fn received_at(&self) -> SystemTime {
+        self.received_at
+    }
+
+    /// Headers of the request handed over to the handler: `user-agent`, `accept-language`,
+    /// the `x-forwarded-*` ones, `x-real-ip` and the trace context
+    pub fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+
+    /// Address of the client, or of the proxy in front of the endpoint, if known
+    pub fn remote_addr(&self) -> Option<SocketAddr> {
+        self.remote_addr
+    }
+
+    /// Caller authenticated through a token, when authenticated that way
+    pub fn identity(&self) -> Option<&Identity> {
+        self.identity.as_ref()
+    }
+
+    /// Opaque JSON attached by the caller, through the `x-metadata` header or the `metadata` field
+    pub fn metadata(&self) -> Option<&Metadata> {
+        self.metadata.as_ref()
+    }
+
+    /// Caller as identified by the credential of the request, when authenticated
+    pub(crate) fn caller(&self) -> Option<&Caller> {
+        self.caller.as_ref()
+    }
+}
+
+impl From<RequestId> for RequestContext {
+    fn from(request_id: RequestId) -> Self {
+        Self::new(request_id)
+    }
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for RequestContext {
+    type Rejection = TypedHeaderRejection;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let TypedHeader(request_id) =
+            TypedHeader::<RequestId>::from_request_parts(parts, state).await?;
+        let request = Self::new(request_id).with_headers(&parts.headers);
+        let request = match parts.extensions.get::<ConnectInfo<PeerAddr>>() {
+            Some(ConnectInfo(PeerAddr(remote_addr))) => request.with_remote_addr(*remote_addr),
+            None => request,
+        };
+        let request = match parts.extensions.get::<Identity>() {
+            Some(identity) => request.with_identity(identity.clone()),
+            None => request,
+        };
+        let request = match parts.extensions.get::<Caller>() {
+            Some(caller) => request.with_caller(caller.clone()),
+            None => request,
+        };
+        Ok(match parts.extensions.get::<Metadata>() {
+            Some(metadata) => request.with_metadata(metadata.clone()),
+            None => request,
+        })
+    }
+}
 
 /// Holds the context in which a request is being executed
 #[cfg_attr(feature = "python", pyclass)]
 #[cfg_attr(debug_assertions, derive(Debug))]
 #[derive(Clone)]
 pub struct Context {
-    /// Correlation ID for the current request
-    request_id: RequestId,
+    /// Metadata of the current request, its correlation ID included
+    request: Arc<RequestContext>,
+
+    /// Cancelled when the outcome is not awaited anymore, the client went away or the request timed out
+    cancellation: CancellationToken,
+
+    /// Turn of the request to run, handed over at checkpoints when preemption is enabled
+    timeslice: Timeslice,
+
+    /// Span of the request, recording the time spent waiting for and in the handler
+    span: Span,
+
+    /// When the request was received, to measure how long it waited for the handler
+    received: Instant,
+
+    /// Model which served the request when it fell back on another handler, see `fallback`
+    served_by: ServedBy,
 }
 
 impl Context {
-    pub fn new(request_id: RequestId) -> Self {
-        Self { request_id }
+    /// Context of a request received in the current span, see `telemetry`
+    pub fn new(request: impl Into<RequestContext>) -> Self {
+        Self {
+            request: Arc::new(request.into()),
+            cancellation: CancellationToken::new(),
+            timeslice: Timeslice::default(),
+            span: Span::current(),
+            received: Instant::now(),
+            served_by: fallback::served_by(),
+        }
+    }
+
+    /// Context of a request received from the queue, continuing the trace of `traceparent` if any
+    #[cfg(feature = "distributed")]
+    pub(crate) fn remote(request_id: RequestId, traceparent: Option<&str>) -> Self {
+        let span = remote_span(&request_id, traceparent);
+        Self {
+            span,
+            ..Self::new(request_id)
+        }
+    }
+
+    /// Run the request in the turn granted through `timeslice`
+    pub fn with_timeslice(mut self, timeslice: Timeslice) -> Self {
+        self.timeslice = timeslice;
+        self
+    }
+
+    /// Correlation ID for the current request
+    pub fn request_id(&self) -> &str {
+        self.request.request_id()
+    }
+
+    /// Metadata of the current request
+    pub fn request(&self) -> &RequestContext {
+        &self.request
+    }
+
+    /// Token cancelled when the outcome of the request is not awaited anymore,
+    /// handlers may check it to abort long-running inferences
+    pub fn cancellation(&self) -> &CancellationToken {
+        &self.cancellation
+    }
+
+    /// Whether the outcome of the request is not awaited anymore
+    pub fn is_cancelled(&self) -> bool {
+        self.cancellation.is_cancelled()
+    }
+
+    /// Context of the same request whose cancellation can be triggered on its own, still
+    /// cancelled along with the one of the request, see `fallback`
+    pub(crate) fn with_child_cancellation(&self) -> Self {
+        Self {
+            cancellation: self.cancellation.child_token(),
+            ..self.clone()
+        }
+    }
+
+    /// Let other requests run if this one exceeded its time slice, handlers producing long
+    /// generations should call it regularly. Returns whether the request was paused.
+    pub async fn checkpoint(&self) -> bool {
+        self.timeslice.checkpoint().await
+    }
+
+    /// Slot holding the model which served the request, when not the primary one
+    pub(crate) fn served_by(&self) -> &ServedBy {
+        &self.served_by
+    }
+
+    /// Span of the request, to correlate the traces of the handler with the request
+    pub fn span(&self) -> &Span {
+        &self.span
+    }
+
+    /// Run `handling` in a `handler` span, child of the request one, recording how long the
+    /// request waited for the handler and how long `handling` took
+    pub async fn handled<F: Future>(&self, handling: F) -> F::Output {
+        let started = Instant::now();
+        self.span
+            .record("queue_wait_ms", millis(started - self.received));
+
+        let output = handling
+            .instrument(info_span!(parent: &self.span, "handler"))
+            .await;
+
+        self.span.record("handler_ms", millis(started.elapsed()));
+        output
     }
 }
 
 #[cfg(feature = "python")]
 mod python {
-    use crate::context::Context;
-    use pyo3::pymethods;
+    use crate::context::{Context, RequestContext};
+    use pyo3::prelude::*;
+    use std::collections::HashMap;
+    use std::time::UNIX_EPOCH;
+
+    #[pymethods]
+    impl RequestContext {
+        #[getter(request_id)]
+        fn get_request_id(&self) -> &str {
+            self.request_id()
+        }
+
+        /// Seconds since the epoch at which the request was received, as `time.time()`
+        #[getter(received_at)]
+        fn get_received_at(&self) -> f64 {
+            self.received_at
+                .duration_since(UNIX_EPOCH)
+                .map(|elapsed| elapsed.as_secs_f64())
+                .unwrap_or_default()
+        }
+
+        /// Forwarded headers, the values of repeated ones being joined with commas
+        #[getter(headers)]
+        fn get_headers(&self) -> HashMap<String, String> {
+            let mut headers = HashMap::<String, String>::new();
+            for (name, value) in &self.headers {
+                let Ok(value) = value.to_str() else {
+                    continue;
+                };
+                headers
+                    .entry(name.to_string())
+                    .and_modify(|joined| {
+                        joined.push_str(", ");
+                        joined.push_str(value);
+                    })
+                    .or_insert_with(|| value.to_string());
+            }
+            headers
+        }
+
+        /// Host and port of the client, as `socket.getpeername()`
+        #[getter(remote_addr)]
+        fn get_remote_addr(&self) -> Option<(String, u16)> {
+            self.remote_addr
+                .map(|remote_addr| (remote_addr.ip().to_string(), remote_addr.port()))
+        }
+
+        /// Subject of the token the request was authenticated with
+        #[getter(subject)]
+        fn get_subject(&self) -> Option<&str> {
+            self.identity.as_ref()?.subject.as_deref()
+        }
+
+        /// Tenant of the caller, read from the claim set through `HFENDPOINT_OIDC_TENANT_CLAIM`
+        #[getter(tenant)]
+        fn get_tenant(&self) -> Option<&str> {
+            self.identity.as_ref()?.tenant.as_deref()
+        }
+
+        /// JSON attached by the caller to correlate the request with its own systems, as sent
+        #[getter(metadata)]
+        fn get_metadata(&self) -> Option<&str> {
+            self.metadata.as_ref().map(|metadata| metadata.as_str())
+        }
+    }
 
     #[pymethods]
     impl Context {
         #[getter]
-        fn request_id(&self) -> &str {
-            &self.request_id
+        fn get_request_id(&self) -> &str {
+            self.request_id()
+        }
+
+        #[getter(request)]
+        fn get_request(&self) -> RequestContext {
+            self.request().clone()
+        }
+
+        #[getter(is_cancelled)]
+        fn get_is_cancelled(&self) -> bool {
+            self.is_cancelled()
+        }
+
+        #[pyo3(name = "checkpoint")]
+        fn py_checkpoint<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+            let timeslice = self.timeslice.clone();
+            pyo3_async_runtimes::tokio::future_into_py(py, async move {
+                Ok(timeslice.checkpoint().await)
+            })
         }
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use crate::context::{Context, RequestContext};
+    use crate::listener::PeerAddr;
+    use crate::oidc::Identity;
+    use axum::extract::{ConnectInfo, FromRequestParts};
+    use axum::http::header::{AUTHORIZATION, USER_AGENT};
+    use axum::http::Request;
+    use std::net::SocketAddr;
+
+    #[tokio::test]
+    async fn extract_request_metadata() {
+        let remote_addr: SocketAddr = "10.0.0.7:51234".parse().unwrap();
+        let (mut parts, _) = Request::post("/chat/completions")
+            .header("x-request-id", "req-1")
+            .header(USER_AGENT, "openai-python/1.0")
+            .header("x-forwarded-for", "203.0.113.1")
+            .header(AUTHORIZATION, "Bearer secret")
+            .extension(ConnectInfo(PeerAddr(remote_addr)))
+            .extension(Identity {
+                subject: Some(String::from("alice")),
+                tenant: Some(String::from("acme")),
+            })
+            .body(())
+            .unwrap()
+            .into_parts();
+
+        let metadata = RequestContext::from_request_parts(&mut parts, &())
+            .await
+            .unwrap();
+        assert_eq!(&**metadata.request_id(), "req-1");
+        assert_eq!(metadata.remote_addr(), Some(remote_addr));
+        assert_eq!(metadata.headers()[USER_AGENT], "openai-python/1.0");
+        assert_eq!(metadata.headers()["x-forwarded-for"], "203.0.113.1");
+        assert!(!metadata.headers().contains_key(AUTHORIZATION));
+        assert_eq!(
+            metadata.identity().and_then(|identity| identity.tenant.as_deref()),
+            Some("acme")
+        );
+
+        let ctx = Context::new(metadata);
+        assert_eq!(ctx.request_id(), "req-1");
+        assert_eq!(ctx.request().remote_addr(), Some(remote_addr));
+
+        // Requests are correlated through their id, which the server always sets
+        let (mut parts, _) = Request::post("/chat/completions")
+            .body(())
+            .unwrap()
+            .into_parts();
+        assert!(
+            RequestContext::from_request_parts(&mut parts, &())
+                .await
+                .is_err()
+        );
+    }
+}
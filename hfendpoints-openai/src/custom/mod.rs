@@ -0,0 +1,132 @@
+pub mod task;
+
+pub const CUSTOM_TAG: &str = "Custom";
+pub const CUSTOM_DESC: &str =
+    "Tasks defined by the handler, along with the schemas of their requests and responses.";
+
+#[cfg(feature = "python")]
+pub(crate) mod python {
+    use crate::custom::task::python::PyTaskDefinition;
+    use hfendpoints_binding_python::ImportablePyModuleBuilder;
+    use pyo3::prelude::*;
+
+    mod custom {
+        use crate::custom::task::python::PyTaskDefinition;
+        use crate::custom::task::{CustomRequest, CustomResponse, CustomRouter, TaskDefinition};
+        use crate::models::python::handler_info;
+        use crate::python::{
+            FallbackHandler, PyMount, create_handlers, impl_pyhandler, mount_handler, serve_mounts,
+            task_channel,
+        };
+        use crate::{Context, ModelCard};
+        use hfendpoints_core::{Endpoint, WorkerPool};
+        use pyo3::exceptions::PyRuntimeError;
+        use pyo3::prelude::*;
+        use std::sync::Arc;
+        use tracing::error;
+
+        impl_pyhandler!(CustomRequest, CustomResponse);
+
+        /// Endpoint serving a task defined at runtime, driving either a single handler concurrently,
+        /// or a pool of workers each processing one request at a time, the requests they fail
+        /// being retried on the fallback handler if any
+        #[pyclass(name = "CustomEndpoint")]
+        pub(crate) struct PyCustomEndpoint {
+            task: Arc<TaskDefinition>,
+            handler: Arc<PyHandler>,
+            model: ModelCard,
+            pool: Option<WorkerPool<PyHandler>>,
+            fallback: Option<FallbackHandler<PyHandler>>,
+        }
+
+        impl Endpoint<(String, u16)> for PyCustomEndpoint {
+            #[instrument(skip(self))]
+            async fn serve(&self, inet_address: (String, u16)) -> Result<(), Error> {
+                let (sender, receiver, loopers) = task_channel(self.fallback.as_ref());
+                let router = CustomRouter(Arc::clone(&self.task), sender);
+
+                // Handlers are instantiated by Python, their model loaded, before reaching the endpoint
+                self.on_handler_initialized();
+                let mount =
+                    mount_handler(&self.handler, self.pool.as_ref(), receiver, router, None)?
+                        .with_loopers(loopers);
+                serve_mounts(vec![mount], self.model.clone(), inet_address).await
+            }
+        }
+
+        #[pymethods]
+        impl PyCustomEndpoint {
+            #[instrument(skip(py, task, inner, fallback))]
+            #[new]
+            #[pyo3(signature = (task, inner, model = None, workers = None, fallback = None, fallback_after = None))]
+            fn new(
+                py: Python<'_>,
+                task: PyRef<'_, PyTaskDefinition>,
+                inner: PyObject,
+                model: Option<ModelCard>,
+                workers: Option<usize>,
+                fallback: Option<PyObject>,
+                fallback_after: Option<f64>,
+            ) -> PyResult<Self> {
+                let info = handler_info(py, &inner)?;
+                let (handler, pool) = create_handlers(py, inner, workers, PyHandler::new)?;
+                let fallback =
+                    FallbackHandler::optional(py, fallback, fallback_after, PyHandler::new)?;
+                let model = model.unwrap_or_default();
+                Ok(Self {
+                    task: Arc::clone(&task.0),
+                    handler,
+                    model: match info {
+                        Some(info) => model.with_info(info),
+                        None => model,
+                    },
+                    pool,
+                    fallback,
+                })
+            }
+
+            /// Model card advertised by the endpoint, completed by the handler's `info()`
+            fn _model_(&self) -> ModelCard {
+                self.model.clone()
+            }
+
+            #[instrument(skip(self))]
+            async fn _serve_(&self, interface: String, port: u16) -> PyResult<()> {
+                if let Err(err) = self.serve((interface, port)).await {
+                    error!("Caught error while serving custom task endpoint: {err}");
+                    Err(PyRuntimeError::new_err(err.to_string()))
+                } else {
+                    Ok(())
+                }
+            }
+
+            /// Start processing the requests of the task, served by a `MultiTaskEndpoint` as its `task`-th one
+            #[instrument(skip(self))]
+            fn _mount_(&self, task: usize) -> PyResult<PyMount> {
+                let (sender, receiver, loopers) = task_channel(self.fallback.as_ref());
+                let router = CustomRouter(Arc::clone(&self.task), sender);
+                mount_handler(
+                    &self.handler,
+                    self.pool.as_ref(),
+                    receiver,
+                    router,
+                    Some(task),
+                )
+                .inspect(|_| self.on_handler_initialized())
+                .map(|mount| PyMount::from(mount.with_loopers(loopers)))
+                .map_err(|err| PyRuntimeError::new_err(err.to_string()))
+            }
+        }
+    }
+
+    /// Bind hfendpoints.openai.custom submodule into the exported Python wheel
+    pub fn bind<'py>(py: Python<'py>, name: &str) -> PyResult<Bound<'py, PyModule>> {
+        let module = ImportablePyModuleBuilder::new(py, name)?
+            .defaults()?
+            .add_class::<PyTaskDefinition>()?
+            .add_class::<custom::PyCustomEndpoint>()?
+            .finish();
+
+        Ok(module)
+    }
+}
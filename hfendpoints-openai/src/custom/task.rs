@@ -0,0 +1,608 @@
+use crate::context::{Context, RequestContext};
+use crate::custom::CUSTOM_TAG;
+use crate::endpoint::{schedule_task, task_router};
+use crate::error::TaskErrors;
+use crate::policy::RequestPolicy;
+use crate::telemetry::record_task;
+use crate::{OpenAiError, OpenAiResult};
+use axum::extract::State;
+use axum::routing::post;
+use axum::{Extension, Json};
+use hfendpoints_core::{EndpointContext, RequestSender};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use tracing::instrument;
+use utoipa::IntoResponses;
+use utoipa::openapi::path::{HttpMethod, OperationBuilder};
+use utoipa::openapi::request_body::RequestBodyBuilder;
+use utoipa::openapi::schema::{ArrayBuilder, ObjectBuilder, SchemaType, Type};
+use utoipa::openapi::{ContentBuilder, RefOr, Required, ResponseBuilder, Schema};
+use utoipa_axum::router::OpenApiRouter;
+
+/// Type of the values described by a [`ValueSchema`]
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Copy, Clone, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ValueType {
+    String,
+    Integer,
+    Number,
+    Boolean,
+    Array,
+    Object,
+}
+
+impl ValueType {
+    #[inline]
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ValueType::String => "a string",
+            ValueType::Integer => "an integer",
+            ValueType::Number => "a number",
+            ValueType::Boolean => "a boolean",
+            ValueType::Array => "an array",
+            ValueType::Object => "an object",
+        }
+    }
+
+    fn matches(&self, value: &Value) -> bool {
+        match self {
+            ValueType::String => value.is_string(),
+            ValueType::Integer => value.is_i64() || value.is_u64(),
+            ValueType::Number => value.is_number(),
+            ValueType::Boolean => value.is_boolean(),
+            ValueType::Array => value.is_array(),
+            ValueType::Object => value.is_object(),
+        }
+    }
+}
+
+impl From<ValueType> for Type {
+    fn from(value: ValueType) -> Self {
+        match value {
+            ValueType::String => Type::String,
+            ValueType::Integer => Type::Integer,
+            ValueType::Number => Type::Number,
+            ValueType::Boolean => Type::Boolean,
+            ValueType::Array => Type::Array,
+            ValueType::Object => Type::Object,
+        }
+    }
+}
+
+/// Subset of JSON Schema describing the requests and responses of a custom task
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ValueSchema {
+    /// Type of the value, any value is accepted when omitted
+    #[serde(rename = "type")]
+    pub kind: Option<ValueType>,
+
+    /// Description of the value, reported in the OpenAPI specification
+    pub description: Option<String>,
+
+    /// Whether `null` is accepted as well
+    pub nullable: bool,
+
+    /// Value filled in when the property is omitted
+    pub default: Option<Value>,
+
+    /// Schema of the items of an array, any item is accepted when omitted
+    pub items: Option<Box<ValueSchema>>,
+
+    /// Properties of an object, objects without any declared property accept every property
+    pub properties: BTreeMap<String, ValueSchema>,
+
+    /// Properties which must be provided
+    pub required: Vec<String>,
+}
+
+impl ValueSchema {
+    /// Check `value` complies with the schema, filling in the omitted properties having a default
+    pub fn validate(&self, value: &mut Value) -> Result<(), String> {
+        self.validate_at(value, "")
+    }
+
+    fn validate_at(&self, value: &mut Value, path: &str) -> Result<(), String> {
+        let describe = || match path {
+            "" => String::from("The body"),
+            path => format!("Property '{path}'"),
+        };
+
+        let Some(kind) = self.kind else {
+            return Ok(());
+        };
+
+        if value.is_null() && self.nullable {
+            return Ok(());
+        }
+
+        if !kind.matches(value) {
+            return Err(format!("{} must be {}", describe(), kind.as_str()));
+        }
+
+        match value {
+            Value::Array(items) => {
+                if let Some(schema) = &self.items {
+                    for (index, item) in items.iter_mut().enumerate() {
+                        schema.validate_at(item, &format!("{path}[{index}]"))?;
+                    }
+                }
+            }
+            Value::Object(properties) if !self.properties.is_empty() => {
+                self.validate_properties(properties, path)?
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn validate_properties(
+        &self,
+        properties: &mut Map<String, Value>,
+        path: &str,
+    ) -> Result<(), String> {
+        let nested = |name: &str| match path {
+            "" => name.to_string(),
+            path => format!("{path}.{name}"),
+        };
+
+        if let Some(unknown) = properties
+            .keys()
+            .find(|name| !self.properties.contains_key(*name))
+        {
+            return Err(format!("Unknown property '{}'", nested(unknown)));
+        }
+
+        for (name, schema) in &self.properties {
+            match properties.get_mut(name) {
+                Some(value) => schema.validate_at(value, &nested(name))?,
+                None if self.required.contains(name) => {
+                    return Err(format!("Missing required property '{}'", nested(name)));
+                }
+                None => {
+                    if let Some(default) = schema.default.as_ref().filter(|value| !value.is_null())
+                    {
+                        properties.insert(name.clone(), default.clone());
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Describe the schema in the OpenAPI specification
+    pub fn to_openapi(&self) -> RefOr<Schema> {
+        let schema_type = match (self.kind, self.nullable) {
+            (None, _) => SchemaType::AnyValue,
+            (Some(kind), false) => SchemaType::new(kind.into()),
+            (Some(kind), true) => SchemaType::from_iter([kind.into(), Type::Null]),
+        };
+
+        if self.kind == Some(ValueType::Array) {
+            let items = self
+                .items
+                .as_ref()
+                .map(|items| items.to_openapi())
+                .unwrap_or_else(|| {
+                    ObjectBuilder::new()
+                        .schema_type(SchemaType::AnyValue)
+                        .into()
+                });
+
+            return ArrayBuilder::new()
+                .schema_type(schema_type)
+                .items(items)
+                .description(self.description.clone())
+                .default(self.default.clone())
+                .into();
+        }
+
+        let mut object = ObjectBuilder::new()
+            .schema_type(schema_type)
+            .description(self.description.clone())
+            .default(self.default.clone());
+        for (name, schema) in &self.properties {
+            object = object.property(name, schema.to_openapi());
+        }
+        for name in &self.required {
+            object = object.required(name);
+        }
+        object.into()
+    }
+}
+
+/// Task defined at runtime, served on `path` with requests and responses described by schemas
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Clone)]
+pub struct TaskDefinition {
+    /// Route serving the task, relative to `/api/v1`
+    pub path: String,
+
+    /// Short summary of the task, reported in the OpenAPI specification
+    pub summary: Option<String>,
+
+    /// Description of the task, reported in the OpenAPI specification
+    pub description: Option<String>,
+
+    /// Schema of the JSON body of the requests
+    pub request: ValueSchema,
+
+    /// Schema of the JSON body of the responses
+    pub response: ValueSchema,
+}
+
+impl TaskDefinition {
+    /// Define the task served on `path`, which must start with `/` and only hold literal
+    /// segments, captures and wildcards being reserved to the builtin routes
+    pub fn new(path: String, request: ValueSchema, response: ValueSchema) -> OpenAiResult<Self> {
+        if !path.starts_with('/') || path.len() == 1 {
+            return Err(OpenAiError::Validation(format!(
+                "Task path must start with '/' and name the route, got '{path}'"
+            )));
+        }
+
+        let literal =
+            |segment: &str| !segment.is_empty() && !segment.contains([':', '*', '{', '}']);
+        if !path[1..].split('/').all(literal) {
+            return Err(OpenAiError::Validation(format!(
+                "Task path must only hold non-empty literal segments, without ':', '*', '{{' or '}}', got '{path}'"
+            )));
+        }
+
+        Ok(Self {
+            path,
+            summary: None,
+            description: None,
+            request,
+            response,
+        })
+    }
+
+    pub fn summary(mut self, summary: String) -> Self {
+        self.summary = Some(summary);
+        self
+    }
+
+    pub fn description(mut self, description: String) -> Self {
+        self.description = Some(description);
+        self
+    }
+}
+
+/// JSON body of a request to a custom task, validated against the request schema
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Clone, Deserialize, Serialize)]
+pub struct CustomRequest(pub Value);
+
+/// JSON body answered by the handler of a custom task
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Clone, Deserialize, Serialize)]
+pub struct CustomResponse(pub Value);
+
+#[instrument(skip(state, task, policy, metadata, body))]
+pub async fn invoke(
+    State(state): State<EndpointContext<(CustomRequest, Context), CustomResponse>>,
+    Extension(task): Extension<Arc<TaskDefinition>>,
+    Extension(policy): Extension<Arc<RequestPolicy>>,
+    metadata: RequestContext,
+    Json(mut body): Json<Value>,
+) -> OpenAiResult<Json<Value>> {
+    record_task(&task.path);
+
+    // Apply the operator's defaults and overrides before validating
+    policy.apply(&mut body);
+    task.request
+        .validate(&mut body)
+        .map_err(OpenAiError::Validation)?;
+
+    // Ask for the inference thread to handle it and wait for answers
+    let CustomResponse(mut response) = schedule_task(&state, metadata, CustomRequest(body))
+        .await?
+        .response()
+        .await?;
+
+    // Responses not matching the declared schema are a bug of the handler
+    task.response
+        .validate(&mut response)
+        .map_err(OpenAiError::InvalidResponse)?;
+    Ok(Json(response))
+}
+
+/// Helper factory to build the route serving a task defined at runtime,
+/// documented in the OpenAPI specification from the schemas of the task
+#[derive(Clone)]
+pub struct CustomRouter(
+    pub Arc<TaskDefinition>,
+    pub RequestSender<(CustomRequest, Context), CustomResponse>,
+);
+
+impl From<CustomRouter> for OpenApiRouter {
+    fn from(value: CustomRouter) -> Self {
+        let CustomRouter(task, sender) = value;
+        let operation = OperationBuilder::new()
+            .tag(CUSTOM_TAG)
+            .summary(task.summary.clone())
+            .description(task.description.clone())
+            .request_body(Some(
+                RequestBodyBuilder::new()
+                    .required(Some(Required::True))
+                    .content(
+                        "application/json",
+                        ContentBuilder::new()
+                            .schema(Some(task.request.to_openapi()))
+                            .build(),
+                    )
+                    .build(),
+            ))
+            .response(
+                "200",
+                ResponseBuilder::new()
+                    .description(task.summary.as_deref().unwrap_or("Success"))
+                    .content(
+                        "application/json",
+                        ContentBuilder::new()
+                            .schema(Some(task.response.to_openapi()))
+                            .build(),
+                    ),
+            );
+        let operation = TaskErrors::responses()
+            .into_iter()
+            .fold(operation, |operation, (status, response)| {
+                operation.response(status, response)
+            });
+
+        let routes = OpenApiRouter::new().route(&task.path, post(invoke));
+        let mut router =
+            task_router(routes, sender, &task.path).layer(Extension(Arc::clone(&task)));
+
+        router.get_openapi_mut().paths.add_path_operation(
+            &task.path,
+            vec![HttpMethod::Post],
+            operation,
+        );
+        router
+    }
+}
+
+#[cfg(feature = "python")]
+pub(crate) mod python {
+    use crate::custom::task::{CustomRequest, CustomResponse, TaskDefinition, ValueSchema};
+    use crate::python::TextDelta;
+    use pyo3::exceptions::PyValueError;
+    use pyo3::prelude::*;
+    use std::sync::Arc;
+
+    /// Serialize the Python `value` through the `json` module
+    fn dumps(value: &Bound<'_, PyAny>) -> PyResult<String> {
+        value
+            .py()
+            .import("json")?
+            .call_method1("dumps", (value,))?
+            .extract()
+    }
+
+    impl<'py> IntoPyObject<'py> for CustomRequest {
+        type Target = PyAny;
+        type Output = Bound<'py, PyAny>;
+        type Error = PyErr;
+
+        fn into_pyobject(self, py: Python<'py>) -> PyResult<Self::Output> {
+            py.import("json")?
+                .call_method1("loads", (self.0.to_string(),))
+        }
+    }
+
+    impl<'py> FromPyObject<'py> for CustomResponse {
+        fn extract_bound(response: &Bound<'py, PyAny>) -> PyResult<Self> {
+            serde_json::from_str(&dumps(response)?)
+                .map(CustomResponse)
+                .map_err(|err| PyValueError::new_err(err.to_string()))
+        }
+    }
+
+    impl TextDelta for CustomResponse {}
+
+    /// Definition of a custom task, `request` and `response` being JSON Schema documents
+    #[pyclass(name = "TaskDefinition", frozen)]
+    pub(crate) struct PyTaskDefinition(pub(crate) Arc<TaskDefinition>);
+
+    #[pymethods]
+    impl PyTaskDefinition {
+        #[new]
+        #[pyo3(signature = (path, request, response, summary = None, description = None))]
+        fn new(
+            path: String,
+            request: Bound<'_, PyAny>,
+            response: Bound<'_, PyAny>,
+            summary: Option<String>,
+            description: Option<String>,
+        ) -> PyResult<Self> {
+            let schema = |name: &str, schema: &Bound<'_, PyAny>| {
+                serde_json::from_str::<ValueSchema>(&dumps(schema)?)
+                    .map_err(|err| PyValueError::new_err(format!("Invalid {name} schema: {err}")))
+            };
+
+            let mut task = TaskDefinition::new(
+                path,
+                schema("request", &request)?,
+                schema("response", &response)?,
+            )
+            .map_err(|err| PyValueError::new_err(err.to_string()))?;
+            if let Some(summary) = summary {
+                task = task.summary(summary);
+            }
+            if let Some(description) = description {
+                task = task.description(description);
+            }
+            Ok(Self(Arc::new(task)))
+        }
+
+        #[getter]
+        fn path(&self) -> &str {
+            &self.0.path
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::custom::task::{
+        CustomRequest, CustomResponse, CustomRouter, TaskDefinition, ValueSchema,
+    };
+    use crate::testing::{answer, post};
+    use crate::{OpenAiError, OpenAiServer};
+    use axum::http::StatusCode;
+    use hfendpoints_core::request_channel;
+    use serde_json::{Value, json};
+    use std::sync::Arc;
+    use utoipa_axum::router::OpenApiRouter;
+
+    fn schema(schema: Value) -> ValueSchema {
+        serde_json::from_value(schema).unwrap()
+    }
+
+    fn classification() -> TaskDefinition {
+        TaskDefinition::new(
+            String::from("/classify"),
+            schema(json!({
+                "type": "object",
+                "properties": {
+                    "text": {"type": "string"},
+                    "top_k": {"type": "integer", "default": 3},
+                    "labels": {"type": "array", "items": {"type": "string"}, "nullable": true},
+                },
+                "required": ["text"],
+            })),
+            schema(json!({
+                "type": "object",
+                "properties": {"scores": {"type": "array", "items": {"type": "number"}}},
+                "required": ["scores"],
+            })),
+        )
+        .unwrap()
+        .summary(String::from("Classify a text"))
+    }
+
+    #[test]
+    fn validate_fills_defaults_and_reports_violations() {
+        let task = classification();
+
+        let mut body = json!({"text": "Hello", "labels": null});
+        task.request.validate(&mut body).unwrap();
+        assert_eq!(body, json!({"text": "Hello", "labels": null, "top_k": 3}));
+
+        let violation = |mut body: Value| task.request.validate(&mut body).unwrap_err();
+        assert_eq!(violation(json!({})), "Missing required property 'text'");
+        assert_eq!(
+            violation(json!({"text": "Hello", "top_k": 1.5})),
+            "Property 'top_k' must be an integer"
+        );
+        assert_eq!(
+            violation(json!({"text": "Hello", "labels": ["a", 2]})),
+            "Property 'labels[1]' must be a string"
+        );
+        assert_eq!(
+            violation(json!({"text": "Hello", "topk": 1})),
+            "Unknown property 'topk'"
+        );
+        assert_eq!(violation(json!(["Hello"])), "The body must be an object");
+    }
+
+    #[test]
+    fn task_path_must_name_a_route() {
+        for path in [
+            "classify",
+            "/",
+            "/classify/",
+            "/tasks//classify",
+            "/classify/{id}",
+            "/classify/:id",
+            "/classify/*rest",
+        ] {
+            assert!(
+                TaskDefinition::new(
+                    String::from(path),
+                    ValueSchema::default(),
+                    ValueSchema::default()
+                )
+                .is_err()
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn custom_task_is_routed_validated_and_documented() {
+        let (sender, receiver) = request_channel(2);
+
+        // First request is answered properly, the second one with a malformed response
+        let mut scores = [json!([0.9, 0.1]), json!("0.9")].into_iter();
+        answer(receiver, move |CustomRequest(request)| {
+            assert_eq!(request["top_k"], 3);
+            Ok(CustomResponse(json!({"scores": scores.next().unwrap()})))
+        });
+
+        let task = Arc::new(classification());
+        let classify = |body: &'static str| {
+            let router = CustomRouter(Arc::clone(&task), sender.clone());
+            post(router, "/classify", "application/json", body)
+        };
+
+        let (status, _, body) = classify(r#"{"text": "Hello"}"#).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, r#"{"scores":[0.9,0.1]}"#);
+
+        let (status, _, _) = classify(r#"{"top_k": 2}"#).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let (status, _, _) = classify(r#"{"text": "Hello"}"#).await;
+        assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+
+        let (_, api) = OpenApiRouter::from(CustomRouter(task, sender)).split_for_parts();
+        let operation = api
+            .paths
+            .get_path_operation("/classify", utoipa::openapi::HttpMethod::Post);
+        assert_eq!(
+            operation.and_then(|operation| operation.summary.as_deref()),
+            Some("Classify a text")
+        );
+    }
+
+    #[test]
+    fn overlapping_tasks_are_rejected() {
+        let (sender, _receiver) = request_channel(1);
+        let task = |path: &str| {
+            let task = TaskDefinition::new(
+                String::from(path),
+                ValueSchema::default(),
+                ValueSchema::default(),
+            )
+            .unwrap();
+            CustomRouter(Arc::new(task), sender.clone())
+        };
+
+        // Served twice, along a builtin route or shadowed by a builtin capture
+        for (first, second) in [
+            ("/classify", "/classify"),
+            ("/classify", "/models"),
+            ("/classify", "/models/gpt2/revision"),
+        ] {
+            let served = OpenAiServer::builder()
+                .task(task(first))
+                .task(task(second))
+                .into_parts();
+            assert!(matches!(served, Err(OpenAiError::Config(_))));
+        }
+
+        assert!(
+            OpenAiServer::builder()
+                .task(task("/classify"))
+                .task(task("/classify/batch"))
+                .into_parts()
+                .is_ok()
+        );
+    }
+}
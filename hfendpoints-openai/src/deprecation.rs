@@ -0,0 +1,199 @@
+//! Versioning and deprecation signaling of the exposed routes.
+//!
+//! Every response carries the `x-api-version` header. Routes can be flagged as deprecated
+//! through [`Deprecate::deprecate`], which marks them in the OpenAPI document and adds the
+//! `Deprecation` (RFC 9745), `Sunset` (RFC 8594) and `Link` headers to their responses.
+//! Deprecated parameters are flagged directly on the schema through utoipa's `deprecated` attribute.
+//!
+//! Operators can drop deprecated routes altogether by setting `HFENDPOINT_DISABLE_DEPRECATED_ROUTES=1`.
+use axum::http::{HeaderName, HeaderValue};
+use axum::middleware::map_response;
+use axum::response::Response;
+use httpdate::fmt_http_date;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::info;
+use utoipa::openapi::Deprecated;
+use utoipa_axum::router::OpenApiRouter;
+
+/// Version of the API exposed by the endpoint
+pub const API_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Header carrying the version of the API which produced the response
+pub const X_API_VERSION: HeaderName = HeaderName::from_static("x-api-version");
+
+/// Environment variable disabling the routes flagged as deprecated
+pub const DISABLE_DEPRECATED_ROUTES_ENV: &str = "HFENDPOINT_DISABLE_DEPRECATED_ROUTES";
+
+const DEPRECATION: HeaderName = HeaderName::from_static("deprecation");
+const SUNSET: HeaderName = HeaderName::from_static("sunset");
+const LINK: HeaderName = HeaderName::from_static("link");
+
+/// Describe when and how a set of routes got deprecated
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Clone)]
+pub struct Deprecation {
+    since: SystemTime,
+    sunset: Option<SystemTime>,
+    link: Option<String>,
+}
+
+impl Deprecation {
+    /// Routes deprecated starting from `since`
+    pub fn new(since: SystemTime) -> Self {
+        Self {
+            since,
+            sunset: None,
+            link: None,
+        }
+    }
+
+    /// Date after which the routes will stop being served
+    pub fn sunset(mut self, sunset: SystemTime) -> Self {
+        self.sunset = Some(sunset);
+        self
+    }
+
+    /// Link to the documentation describing the deprecation and how to migrate
+    pub fn link(mut self, link: impl Into<String>) -> Self {
+        self.link = Some(link.into());
+        self
+    }
+
+    fn headers(&self) -> Vec<(HeaderName, HeaderValue)> {
+        let since = self
+            .since
+            .duration_since(UNIX_EPOCH)
+            .map(|since| since.as_secs())
+            .unwrap_or_default();
+
+        let mut headers = vec![(DEPRECATION, format!("@{since}"))];
+
+        if let Some(sunset) = self.sunset {
+            headers.push((SUNSET, fmt_http_date(sunset)));
+        }
+
+        if let Some(link) = &self.link {
+            headers.push((LINK, format!("<{link}>; rel=\"deprecation\"")));
+        }
+
+        headers
+            .into_iter()
+            .filter_map(|(name, value)| Some((name, HeaderValue::try_from(value).ok()?)))
+            .collect()
+    }
+}
+
+/// Flag routes as deprecated
+pub trait Deprecate {
+    fn deprecate(self, deprecation: Deprecation) -> Self;
+}
+
+impl<S> Deprecate for OpenApiRouter<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    fn deprecate(self, deprecation: Deprecation) -> Self {
+        let disabled = std::env::var(DISABLE_DEPRECATED_ROUTES_ENV)
+            .is_ok_and(|value| matches!(value.as_str(), "1" | "true"));
+
+        apply_deprecation(self, deprecation, disabled)
+    }
+}
+
+fn apply_deprecation<S>(
+    mut router: OpenApiRouter<S>,
+    deprecation: Deprecation,
+    disabled: bool,
+) -> OpenApiRouter<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    if disabled {
+        for path in router.get_openapi().paths.paths.keys() {
+            info!("Deprecated route {path} is disabled");
+        }
+        return OpenApiRouter::new();
+    }
+
+    for item in router.get_openapi_mut().paths.paths.values_mut() {
+        let operations = [
+            &mut item.get,
+            &mut item.put,
+            &mut item.post,
+            &mut item.delete,
+            &mut item.options,
+            &mut item.head,
+            &mut item.patch,
+            &mut item.trace,
+        ];
+
+        for operation in operations.into_iter().flatten() {
+            operation.deprecated = Some(Deprecated::True);
+        }
+    }
+
+    let headers = Arc::new(deprecation.headers());
+    router.layer(map_response(move |mut response: Response| {
+        let headers = Arc::clone(&headers);
+        async move {
+            response.headers_mut().extend(headers.iter().cloned());
+            response
+        }
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::deprecation::{Deprecation, apply_deprecation};
+    use axum::body::Body;
+    use axum::http::{Request, StatusCode};
+    use std::time::{Duration, UNIX_EPOCH};
+    use tower::ServiceExt;
+    use utoipa::openapi::Deprecated;
+    use utoipa_axum::router::OpenApiRouter;
+    use utoipa_axum::routes;
+
+    #[utoipa::path(get, path = "/legacy", responses((status = OK)))]
+    async fn legacy() -> StatusCode {
+        StatusCode::OK
+    }
+
+    fn deprecation() -> Deprecation {
+        Deprecation::new(UNIX_EPOCH + Duration::from_secs(1_700_000_000))
+            .sunset(UNIX_EPOCH + Duration::from_secs(1_800_000_000))
+            .link("https://huggingface.co/docs")
+    }
+
+    #[tokio::test]
+    async fn deprecated_routes_signal_deprecation() {
+        let router = OpenApiRouter::new().routes(routes!(legacy));
+        let (router, api) = apply_deprecation(router, deprecation(), false).split_for_parts();
+
+        let operation = api.paths.paths["/legacy"].get.as_ref().unwrap();
+        assert!(matches!(operation.deprecated, Some(Deprecated::True)));
+
+        let request = Request::get("/legacy").body(Body::empty()).unwrap();
+        let response = router.oneshot(request).await.unwrap();
+        let headers = response.headers();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(headers["deprecation"], "@1700000000");
+        assert_eq!(headers["sunset"], "Fri, 15 Jan 2027 08:00:00 GMT");
+        assert_eq!(
+            headers["link"],
+            "<https://huggingface.co/docs>; rel=\"deprecation\""
+        );
+    }
+
+    #[tokio::test]
+    async fn disabled_deprecated_routes_are_removed() {
+        let router = OpenApiRouter::new().routes(routes!(legacy));
+        let (router, api) = apply_deprecation(router, deprecation(), true).split_for_parts();
+        assert!(api.paths.paths.is_empty());
+
+        let request = Request::get("/legacy").body(Body::empty()).unwrap();
+        let response = router.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+}
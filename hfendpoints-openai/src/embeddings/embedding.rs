@@ -0,0 +1,655 @@
+use crate::context::{Context, RequestContext};
+use crate::embeddings::EMBEDDINGS_TAG;
+use crate::endpoint::{schedule_task, task_router};
+use crate::error::TaskErrors;
+use crate::estimate::{EstimatedTask, record_latency};
+use crate::policy::RequestPolicy;
+use crate::telemetry::record_task;
+use crate::{OpenAiError, OpenAiResult, TaskRequest};
+use axum::extract::State;
+use axum::response::{IntoResponse, Response};
+use axum::{Extension, Json};
+use base64::Engine;
+use base64::prelude::BASE64_STANDARD;
+use half::f16;
+use hfendpoints_core::{EndpointContext, RequestSender};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::sync::Arc;
+use std::time::Instant;
+use tracing::instrument;
+use utoipa::ToSchema;
+use utoipa_axum::router::OpenApiRouter;
+use utoipa_axum::routes;
+
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+
+/// Maximum number of inputs embedded in a single request (as OpenAI)
+const MAX_EMBEDDING_INPUTS: usize = 2048;
+
+/// Input text to embed, either a single string or an array of strings.
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Clone, Deserialize, ToSchema)]
+#[serde(untagged)]
+pub enum EmbeddingInput {
+    Single(String),
+    Multiple(Vec<String>),
+}
+
+impl From<EmbeddingInput> for Vec<String> {
+    fn from(value: EmbeddingInput) -> Self {
+        match value {
+            EmbeddingInput::Single(input) => vec![input],
+            EmbeddingInput::Multiple(inputs) => inputs,
+        }
+    }
+}
+
+/// The format to return the embeddings in.
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Copy, Clone, Default, Eq, PartialEq, Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum EncodingFormat {
+    #[default]
+    Float,
+
+    /// Little-endian `f32` values encoded as base64
+    Base64,
+}
+
+impl EncodingFormat {
+    #[inline]
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EncodingFormat::Float => "float",
+            EncodingFormat::Base64 => "base64",
+        }
+    }
+}
+
+/// Extension: quantize the embeddings to reduce the size of the response.
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Copy, Clone, Eq, PartialEq, Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Quantization {
+    /// Signed 8-bit integers, each value being approximated as `q * scale`
+    Int8,
+
+    /// Unsigned 8-bit integers, each value being approximated as `q * scale + offset`
+    Uint8,
+
+    /// Half-precision floats, only available along the base64 encoding format
+    Float16,
+}
+
+impl Quantization {
+    #[inline]
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Quantization::Int8 => "int8",
+            Quantization::Uint8 => "uint8",
+            Quantization::Float16 => "float16",
+        }
+    }
+}
+
+/// Creates an embedding vector representing the input text.
+#[cfg_attr(feature = "python", pyclass(frozen))]
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Clone, Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct EmbeddingRequest {
+    /// Input text to embed, encoded as a string or array of strings.
+    #[serde(deserialize_with = "deserialize_input")]
+    #[schema(value_type = EmbeddingInput)]
+    pub input: Vec<String>,
+
+    /// Not used, here for compatibility purpose with OpenAI Platform
+    pub model: Option<String>,
+
+    /// The format to return the embeddings in. Can be either float or base64.
+    #[serde(default)]
+    pub encoding_format: EncodingFormat,
+
+    /// The number of dimensions the resulting output embeddings should have,
+    /// only supported by models trained with variable output dimensions.
+    pub dimensions: Option<u32>,
+
+    /// Extension: quantize the returned embeddings as int8, uint8 (along with the scale and offset
+    /// to recover the floats) or float16 (base64 only).
+    #[serde(default)]
+    pub quantization: Option<Quantization>,
+
+    /// A unique identifier representing the end-user.
+    pub user: Option<String>,
+}
+
+fn deserialize_input<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    Ok(EmbeddingInput::deserialize(deserializer)?.into())
+}
+
+impl EmbeddingRequest {
+    #[instrument(skip_all)]
+    fn validate(self) -> OpenAiResult<Self> {
+        if self.input.is_empty() || self.input.iter().any(String::is_empty) {
+            return Err(OpenAiError::Validation(String::from(
+                "Parameter 'input' cannot be empty",
+            )));
+        }
+
+        if self.input.len() > MAX_EMBEDDING_INPUTS {
+            return Err(OpenAiError::Validation(format!(
+                "Parameter 'input' is limited to {MAX_EMBEDDING_INPUTS} entries, got {}",
+                self.input.len()
+            )));
+        }
+
+        if self.dimensions == Some(0) {
+            return Err(OpenAiError::Validation(String::from(
+                "Parameter 'dimensions' must be greater than 0",
+            )));
+        }
+
+        if self.quantization == Some(Quantization::Float16)
+            && self.encoding_format != EncodingFormat::Base64
+        {
+            return Err(OpenAiError::Validation(String::from(
+                "Parameter 'quantization' float16 requires 'encoding_format' to be base64",
+            )));
+        }
+
+        Ok(self)
+    }
+}
+
+/// The embedding vector, a list of floats, quantized integers or their base64 representation.
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Clone, Deserialize, Serialize, ToSchema)]
+#[serde(untagged)]
+pub enum EmbeddingVector {
+    Float(Vec<f32>),
+    Int8(Vec<i8>),
+    Uint8(Vec<u8>),
+    Base64(String),
+}
+
+/// Represents an embedding vector returned by embedding endpoint.
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Clone, Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct Embedding {
+    /// The object type, which is always "embedding".
+    #[serde(skip_deserializing, default = "embedding_object")]
+    object: &'static str,
+
+    /// The index of the embedding in the list of embeddings.
+    index: usize,
+
+    /// The embedding vector.
+    embedding: EmbeddingVector,
+
+    /// Extension: scale to multiply quantized values with to recover the floats.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    scale: Option<f32>,
+
+    /// Extension: offset to add to scaled uint8 values to recover the floats.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    offset: Option<f32>,
+}
+
+impl Embedding {
+    /// Quantize the float values as requested, then encode them in the requested format
+    fn encode(&mut self, format: EncodingFormat, quantization: Option<Quantization>) {
+        let EmbeddingVector::Float(values) = &self.embedding else {
+            return;
+        };
+
+        let base64 = format == EncodingFormat::Base64;
+        self.embedding = match quantization {
+            None if base64 => {
+                let bytes: Vec<u8> = values.iter().flat_map(|v| v.to_le_bytes()).collect();
+                EmbeddingVector::Base64(BASE64_STANDARD.encode(bytes))
+            }
+            None => return,
+            Some(Quantization::Int8) => {
+                let scale = values.iter().fold(0.0f32, |max, v| max.max(v.abs())) / 127.0;
+                let quantized: Vec<i8> = values
+                    .iter()
+                    .map(|v| quantize(*v, scale, 0.0).clamp(-127.0, 127.0) as i8)
+                    .collect();
+
+                self.scale = Some(scale);
+                if base64 {
+                    let bytes: Vec<u8> = quantized.iter().map(|q| *q as u8).collect();
+                    EmbeddingVector::Base64(BASE64_STANDARD.encode(bytes))
+                } else {
+                    EmbeddingVector::Int8(quantized)
+                }
+            }
+            Some(Quantization::Uint8) => {
+                let (min, max) = values.iter().fold((f32::MAX, f32::MIN), |(min, max), v| {
+                    (min.min(*v), max.max(*v))
+                });
+                let offset = if values.is_empty() { 0.0 } else { min };
+                let scale = (max - offset).max(0.0) / 255.0;
+                let quantized: Vec<u8> = values
+                    .iter()
+                    .map(|v| quantize(*v, scale, offset).clamp(0.0, 255.0) as u8)
+                    .collect();
+
+                self.scale = Some(scale);
+                self.offset = Some(offset);
+                if base64 {
+                    EmbeddingVector::Base64(BASE64_STANDARD.encode(quantized))
+                } else {
+                    EmbeddingVector::Uint8(quantized)
+                }
+            }
+            Some(Quantization::Float16) => {
+                let bytes: Vec<u8> = values
+                    .iter()
+                    .flat_map(|v| f16::from_f32(*v).to_le_bytes())
+                    .collect();
+                EmbeddingVector::Base64(BASE64_STANDARD.encode(bytes))
+            }
+        };
+    }
+}
+
+#[inline]
+fn quantize(value: f32, scale: f32, offset: f32) -> f32 {
+    if scale > 0.0 {
+        ((value - offset) / scale).round()
+    } else {
+        0.0
+    }
+}
+
+/// Usage statistics for the embedding request.
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Copy, Clone, Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct EmbeddingUsage {
+    /// The number of tokens used by the prompt.
+    prompt_tokens: u32,
+
+    /// The total number of tokens used by the request.
+    total_tokens: u32,
+}
+
+/// List of embedding vectors, one per input, along with usage statistics.
+#[cfg_attr(feature = "python", pyclass(frozen))]
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Clone, Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct EmbeddingResponse {
+    /// The object type, which is always "list".
+    #[serde(skip_deserializing, default = "list_object")]
+    object: &'static str,
+
+    /// The list of embeddings generated by the model.
+    data: Vec<Embedding>,
+
+    /// The name of the model used to generate the embedding.
+    model: String,
+
+    /// The usage information for the request.
+    usage: EmbeddingUsage,
+}
+
+#[inline]
+fn embedding_object() -> &'static str {
+    "embedding"
+}
+
+#[inline]
+fn list_object() -> &'static str {
+    "list"
+}
+
+impl EmbeddingResponse {
+    pub fn new(model: String, embeddings: Vec<Vec<f32>>, prompt_tokens: u32) -> Self {
+        let data = embeddings
+            .into_iter()
+            .enumerate()
+            .map(|(index, embedding)| Embedding {
+                object: "embedding",
+                index,
+                embedding: EmbeddingVector::Float(embedding),
+                scale: None,
+                offset: None,
+            })
+            .collect();
+
+        Self {
+            object: "list",
+            data,
+            model,
+            usage: EmbeddingUsage {
+                prompt_tokens,
+                total_tokens: prompt_tokens,
+            },
+        }
+    }
+
+    /// Encode the embeddings as requested by the client, handlers always produce floats
+    fn encode(mut self, format: EncodingFormat, quantization: Option<Quantization>) -> Self {
+        for embedding in &mut self.data {
+            embedding.encode(format, quantization);
+        }
+        self
+    }
+}
+
+impl IntoResponse for EmbeddingResponse {
+    fn into_response(self) -> Response {
+        Json::from(self).into_response()
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/embeddings",
+    tag = EMBEDDINGS_TAG,
+    request_body(content = EmbeddingRequest, content_type = "application/json"),
+    responses(
+        (status = OK, description = "Creates an embedding vector representing the input text.", body = EmbeddingResponse),
+        TaskErrors,
+    )
+)]
+#[instrument(skip(state, policy, metadata, request))]
+pub async fn embed(
+    State(state): State<EndpointContext<(EmbeddingRequest, Context), EmbeddingResponse>>,
+    Extension(policy): Extension<Arc<RequestPolicy>>,
+    metadata: RequestContext,
+    Json(mut request): Json<Value>,
+) -> OpenAiResult<EmbeddingResponse> {
+    record_task("embedding");
+
+    // Apply the operator's defaults and overrides before decoding
+    policy.apply(&mut request);
+    let request = serde_json::from_value::<EmbeddingRequest>(request)
+        .map_err(|err| OpenAiError::Validation(err.to_string()))?
+        .validate()?;
+
+    // Ask for the inference thread to handle it and wait for answers
+    let started = Instant::now();
+    let (format, quantization) = (request.encoding_format, request.quantization);
+    let response = schedule_task(&state, metadata, request)
+        .await?
+        .response()
+        .await?;
+    record_latency(
+        EstimatedTask::Embedding,
+        response.usage.prompt_tokens as f64,
+        started,
+    );
+    Ok(response.encode(format, quantization))
+}
+
+/// Helper factory to build
+/// [OpenAi Platform compatible Embeddings endpoint](https://platform.openai.com/docs/api-reference/embeddings/create)
+#[derive(Clone)]
+pub struct EmbeddingRouter(pub RequestSender<(EmbeddingRequest, Context), EmbeddingResponse>);
+
+impl From<EmbeddingRouter> for OpenApiRouter {
+    fn from(value: EmbeddingRouter) -> Self {
+        task_router(
+            OpenApiRouter::new().routes(routes!(embed)),
+            value.0,
+            "/embeddings",
+        )
+    }
+}
+
+impl TaskRequest for EmbeddingRequest {
+    type Response = EmbeddingResponse;
+
+    fn router(sender: RequestSender<(Self, Context), Self::Response>) -> OpenApiRouter {
+        EmbeddingRouter(sender).into()
+    }
+}
+
+#[cfg(feature = "python")]
+pub(crate) mod python {
+    use crate::embeddings::embedding::{EmbeddingRequest, EmbeddingResponse, Quantization};
+    use crate::python::TextDelta;
+    use pyo3::prelude::*;
+
+    impl TextDelta for EmbeddingResponse {}
+
+    #[pymethods]
+    impl EmbeddingRequest {
+        #[getter(input)]
+        fn get_input(&self) -> Vec<String> {
+            self.input.clone()
+        }
+
+        #[getter(model)]
+        fn get_model(&self) -> Option<&str> {
+            self.model.as_deref()
+        }
+
+        #[getter(encoding_format)]
+        fn get_encoding_format(&self) -> &'static str {
+            self.encoding_format.as_str()
+        }
+
+        #[getter(dimensions)]
+        fn get_dimensions(&self) -> Option<u32> {
+            self.dimensions
+        }
+
+        #[getter(quantization)]
+        fn get_quantization(&self) -> Option<&'static str> {
+            self.quantization.as_ref().map(Quantization::as_str)
+        }
+
+        #[getter(user)]
+        fn get_user(&self) -> Option<&str> {
+            self.user.as_deref()
+        }
+    }
+
+    #[pymethods]
+    impl EmbeddingResponse {
+        #[new]
+        fn py_new(model: String, embeddings: Vec<Vec<f32>>, prompt_tokens: u32) -> Self {
+            Self::new(model, embeddings, prompt_tokens)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::context::Context;
+    use crate::embeddings::embedding::{
+        __path_embed, EmbeddingRequest, EmbeddingResponse, EmbeddingRouter, embed,
+    };
+    use crate::policy::RequestPolicy;
+    use crate::testing::{answer, post, request, send};
+    use axum::Extension;
+    use axum::body::{Body, to_bytes};
+    use axum::http::header::{CONTENT_TYPE, RETRY_AFTER};
+    use axum::http::{Request, StatusCode};
+    use hfendpoints_core::{EndpointContext, Error, request_channel};
+    use serde_json::json;
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tower::ServiceExt;
+    use utoipa_axum::router::OpenApiRouter;
+    use utoipa_axum::routes;
+
+    /// Answer with the same vector for every input
+    async fn embed_inputs(body: &'static str) -> (StatusCode, serde_json::Value) {
+        let (sender, receiver) = request_channel(1);
+        answer(receiver, |request: EmbeddingRequest| {
+            let embeddings = request.input.iter().map(|_| vec![1.0, -2.0]).collect();
+            Ok(EmbeddingResponse::new(String::from("e5"), embeddings, 4))
+        });
+
+        let router = EmbeddingRouter(sender);
+        let (status, _, body) = post(router, "/embeddings", "application/json", body).await;
+        (status, serde_json::from_slice(&body).unwrap_or_default())
+    }
+
+    #[tokio::test]
+    async fn embed_single_input_as_floats() {
+        let (status, body) = embed_inputs(r#"{"input": "Hello", "model": "e5"}"#).await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            body,
+            json!({
+                "object": "list",
+                "data": [{"object": "embedding", "index": 0, "embedding": [1.0, -2.0]}],
+                "model": "e5",
+                "usage": {"prompt_tokens": 4, "total_tokens": 4}
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn embed_multiple_inputs_as_base64() {
+        let (status, body) =
+            embed_inputs(r#"{"input": ["Hello", "world"], "encoding_format": "base64"}"#).await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["data"].as_array().unwrap().len(), 2);
+
+        // [1.0f32, -2.0f32] as little-endian bytes
+        assert_eq!(body["data"][1]["embedding"], "AACAPwAAAMA=");
+    }
+
+    #[tokio::test]
+    async fn embed_rejects_empty_input() {
+        let (status, _) = embed_inputs(r#"{"input": []}"#).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn embed_rejects_requests_when_queue_is_full() {
+        let (sender, mut receiver) = request_channel(1);
+        let (router, _) = OpenApiRouter::from(EmbeddingRouter(sender)).split_for_parts();
+
+        // The handler holds on the first request without answering
+        let pending = router.clone().oneshot(
+            Request::post("/embeddings")
+                .header("x-request-id", "pending")
+                .header(CONTENT_TYPE, "application/json")
+                .body(Body::from(r#"{"input": "Hello"}"#))
+                .unwrap(),
+        );
+        let pending = tokio::spawn(pending);
+        let held = receiver.recv().await.unwrap();
+
+        let request = Request::post("/embeddings")
+            .header("x-request-id", "rejected")
+            .header(CONTENT_TYPE, "application/json")
+            .body(Body::from(r#"{"input": "world"}"#))
+            .unwrap();
+
+        let response = router.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.headers()[RETRY_AFTER], "1");
+
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["error"]["type"], "rate_limit_exceeded");
+        assert_eq!(body["error"]["code"], "queue_full");
+
+        drop(held);
+        pending.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn embed_reports_handler_exceptions() {
+        let (sender, receiver) = request_channel(1);
+        answer(receiver, |_: EmbeddingRequest| {
+            Err::<EmbeddingResponse, _>(Error::HandlerException {
+                kind: String::from("ValueError"),
+                message: String::from("input is too long"),
+                traceback: Some(String::from("Traceback (most recent call last):")),
+            })
+        });
+
+        let router = EmbeddingRouter(sender);
+        let body = r#"{"input": "Hello"}"#;
+        let (status, _, body) = post(router, "/embeddings", "application/json", body).await;
+        assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(
+            body["error"]["message"],
+            "The handler raised ValueError: input is too long"
+        );
+        assert_eq!(body["error"]["type"], "server_error");
+        assert_eq!(body["error"]["code"], "handler_exception");
+    }
+
+    #[tokio::test]
+    async fn embed_times_out_and_flags_request_as_cancelled() {
+        let (sender, mut receiver) = request_channel(1);
+        let context = EndpointContext::new(sender).with_timeout(Some(Duration::from_millis(10)));
+        let (router, _) = OpenApiRouter::new()
+            .routes(routes!(embed))
+            .with_state(context)
+            .layer(Extension(Arc::new(RequestPolicy::from_env("/embeddings"))))
+            .split_for_parts();
+
+        // The handler never answers and only notices the request is not awaited anymore
+        let handler = tokio::spawn(async move {
+            let ((_, ctx), egress): ((EmbeddingRequest, Context), _) =
+                receiver.recv().await.unwrap();
+            assert!(!ctx.is_cancelled());
+            egress.closed().await;
+            ctx.is_cancelled()
+        });
+
+        let request = request("/embeddings")
+            .header(CONTENT_TYPE, "application/json")
+            .body(Body::from(r#"{"input": "Hello"}"#))
+            .unwrap();
+
+        let (status, _, _) = send(router, request).await;
+        assert_eq!(status, StatusCode::GATEWAY_TIMEOUT);
+        assert!(handler.await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn embed_quantized_vectors() {
+        // [1.0, -2.0] scaled by 2 / 127
+        let (status, body) = embed_inputs(r#"{"input": "Hello", "quantization": "int8"}"#).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["data"][0]["embedding"], json!([64, -127]));
+        assert_eq!(
+            body["data"][0]["scale"].as_f64().unwrap() as f32,
+            2.0 / 127.0
+        );
+        assert!(body["data"][0].get("offset").is_none());
+
+        // [1.0, -2.0] shifted by -2 and scaled by 3 / 255 gives [255, 0]
+        let (status, body) = embed_inputs(
+            r#"{"input": "Hello", "quantization": "uint8", "encoding_format": "base64"}"#,
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["data"][0]["embedding"], "/wA=");
+        assert_eq!(body["data"][0]["offset"], json!(-2.0));
+
+        // [1.0f16, -2.0f16] as little-endian bytes
+        let (status, body) = embed_inputs(
+            r#"{"input": "Hello", "quantization": "float16", "encoding_format": "base64"}"#,
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["data"][0]["embedding"], "ADwAwA==");
+
+        let (status, _) = embed_inputs(r#"{"input": "Hello", "quantization": "float16"}"#).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+}
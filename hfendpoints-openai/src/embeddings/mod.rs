@@ -0,0 +1,36 @@
+pub mod embedding;
+
+pub const EMBEDDINGS_TAG: &str = "Embeddings";
+pub const EMBEDDINGS_DESC: &str = "Get a vector representation of a given input that can be easily consumed by machine learning models and algorithms.";
+
+#[cfg(feature = "python")]
+pub(crate) mod python {
+    use crate::embeddings::embedding::{EmbeddingRequest, EmbeddingResponse};
+    use hfendpoints_binding_python::ImportablePyModuleBuilder;
+    use pyo3::prelude::*;
+
+    mod embeddings {
+        use crate::embeddings::embedding::{EmbeddingRequest, EmbeddingResponse, EmbeddingRouter};
+        use crate::python::{impl_pyendpoint, impl_pyhandler};
+
+        impl_pyhandler!(EmbeddingRequest, EmbeddingResponse);
+        impl_pyendpoint!(
+            "EmbeddingEndpoint",
+            PyEmbeddingEndpoint,
+            PyHandler,
+            EmbeddingRouter
+        );
+    }
+
+    /// Bind hfendpoints.openai.embeddings submodule into the exported Python wheel
+    pub fn bind<'py>(py: Python<'py>, name: &str) -> PyResult<Bound<'py, PyModule>> {
+        let module = ImportablePyModuleBuilder::new(py, name)?
+            .defaults()?
+            .add_class::<EmbeddingRequest>()?
+            .add_class::<EmbeddingResponse>()?
+            .add_class::<embeddings::PyEmbeddingEndpoint>()?
+            .finish();
+
+        Ok(module)
+    }
+}
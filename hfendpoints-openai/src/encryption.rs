@@ -0,0 +1,706 @@
+//! End-to-end encrypted request payloads, sent as JSON Web Encryption envelopes (JWE, RFC 7516).
+//!
+//! Clients sending `Content-Type: application/jose` wrap the original body in a JWE compact
+//! serialization, the content type of the original body (i.e. `multipart/form-data; boundary=...`)
+//! being carried by the `cty` header parameter, `application/json` when omitted. The envelope is
+//! decrypted before the request reaches the route, so payloads can transit untrusted intermediaries.
+//!
+//! Decryption keys are read from the JSON Web Key Set file pointed to by `HFENDPOINT_JWE_KEYS`:
+//! - `oct` keys, used directly (`dir`) or to unwrap the content key (`A128KW`, `A256KW`)
+//! - `RSA` private keys, unwrapping the content key with `RSA-OAEP-256`, with the `jwe-rsa` feature
+//!
+//! RSA decryption goes through aws-lc, whose padding checks run in constant time. Envelopes
+//! are attacker-controlled: pure Rust RSA implementations leaking the decryption timing
+//! (RUSTSEC-2023-0071) are not an option. The feature is off by default, as aws-lc is built
+//! from C sources.
+//!
+//! Contents are encrypted with `A128GCM` or `A256GCM`, optionally compressed beforehand (`zip: DEF`).
+//! Setting `HFENDPOINT_JWE_REQUIRED` rejects requests whose payload is not encrypted.
+//! Responses are sent in clear, relying on TLS.
+use crate::audio::MAX_AUDIO_BODY_SIZE;
+#[cfg(feature = "jobs")]
+use crate::batches::batch::Replayed;
+use crate::body::{BodyLimit, read_limited};
+use crate::compression::read_bounded;
+use crate::{OpenAiError, OpenAiResult};
+use aes_gcm::aead::{Aead, KeyInit, Payload};
+use aes_gcm::{Aes128Gcm, Aes256Gcm, Nonce};
+use aes_kw::{KekAes128, KekAes256};
+#[cfg(feature = "jwe-rsa")]
+use aws_lc_rs::rsa::{OAEP_SHA256_MGF1SHA256, OaepPrivateDecryptingKey, PrivateDecryptingKey};
+use axum::body::{Body, Bytes};
+use axum::extract::{Request, State};
+use axum::http::header::{CONTENT_LENGTH, CONTENT_TYPE};
+use axum::http::{HeaderValue, Method};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use base64::Engine;
+use base64::prelude::BASE64_URL_SAFE_NO_PAD;
+use flate2::read::DeflateDecoder;
+use serde::Deserialize;
+use std::sync::Arc;
+use tokio::task::spawn_blocking;
+use tracing::{debug, error, info, instrument};
+
+/// Environment variable holding the path of the JSON Web Key Set used to decrypt the payloads
+pub const JWE_KEYS_ENV: &str = "HFENDPOINT_JWE_KEYS";
+
+/// Environment variable rejecting requests whose payload is not encrypted when set to `true`
+pub const JWE_REQUIRED_ENV: &str = "HFENDPOINT_JWE_REQUIRED";
+
+/// Media type of the JWE compact serialization
+const JOSE_CONTENT_TYPE: &str = "application/jose";
+
+/// Largest decrypted payload, the one of the audio routes
+const MAX_PLAINTEXT_SIZE: usize = MAX_AUDIO_BODY_SIZE;
+
+/// Largest envelope, the base64url encoding of the largest payload along with the JWE parts
+const MAX_ENVELOPE_SIZE: usize = MAX_PLAINTEXT_SIZE / 3 * 4 + 64 * 1024;
+
+/// Key as described in the JSON Web Key Set, only the parameters used for decryption are read
+#[derive(Deserialize)]
+struct Jwk {
+    kty: String,
+    kid: Option<String>,
+    alg: Option<String>,
+    k: Option<String>,
+    #[cfg(feature = "jwe-rsa")]
+    #[serde(flatten)]
+    rsa: RsaParameters,
+}
+
+/// Parameters of RSA private keys, see RFC 7518 section 6.3
+#[cfg(feature = "jwe-rsa")]
+#[derive(Deserialize)]
+struct RsaParameters {
+    n: Option<String>,
+    e: Option<String>,
+    d: Option<String>,
+    p: Option<String>,
+    q: Option<String>,
+    dp: Option<String>,
+    dq: Option<String>,
+    qi: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct JwkSet {
+    keys: Vec<Jwk>,
+}
+
+enum KeyMaterial {
+    Symmetric(Vec<u8>),
+    #[cfg(feature = "jwe-rsa")]
+    Rsa(Box<OaepPrivateDecryptingKey>),
+}
+
+struct DecryptionKey {
+    kid: Option<String>,
+    alg: Option<String>,
+    material: KeyMaterial,
+}
+
+impl TryFrom<Jwk> for DecryptionKey {
+    type Error = String;
+
+    fn try_from(jwk: Jwk) -> Result<Self, Self::Error> {
+        let name = jwk.kid.as_deref().unwrap_or("without kid").to_string();
+        let decode = |parameter: Option<&String>, field: &str| match parameter {
+            Some(value) => BASE64_URL_SAFE_NO_PAD
+                .decode(value)
+                .map_err(|err| format!("key {name}: parameter '{field}' is not base64url: {err}")),
+            None => Err(format!("key {name}: missing parameter '{field}'")),
+        };
+
+        let material = match jwk.kty.as_str() {
+            "oct" => KeyMaterial::Symmetric(decode(jwk.k.as_ref(), "k")?),
+            #[cfg(feature = "jwe-rsa")]
+            "RSA" => {
+                let mut integers = Vec::new();
+                for (parameter, field) in [
+                    (&jwk.rsa.n, "n"),
+                    (&jwk.rsa.e, "e"),
+                    (&jwk.rsa.d, "d"),
+                    (&jwk.rsa.p, "p"),
+                    (&jwk.rsa.q, "q"),
+                    (&jwk.rsa.dp, "dp"),
+                    (&jwk.rsa.dq, "dq"),
+                    (&jwk.rsa.qi, "qi"),
+                ] {
+                    integers.push(decode(parameter.as_ref(), field)?);
+                }
+
+                let key = PrivateDecryptingKey::from_pkcs8(&rsa_pkcs8(&integers))
+                    .map_err(|err| format!("key {name}: invalid RSA key: {err}"))?;
+                let key = OaepPrivateDecryptingKey::new(key)
+                    .map_err(|_| format!("key {name}: invalid RSA key"))?;
+                KeyMaterial::Rsa(Box::new(key))
+            }
+            #[cfg(not(feature = "jwe-rsa"))]
+            "RSA" => {
+                return Err(format!(
+                    "key {name}: RSA keys require hfendpoints to be built with the jwe-rsa feature"
+                ));
+            }
+            kty => return Err(format!("key {name}: unsupported key type '{kty}'")),
+        };
+
+        Ok(Self {
+            kid: jwk.kid,
+            alg: jwk.alg,
+            material,
+        })
+    }
+}
+
+/// PKCS#8 document holding the RSA private key made of the `integers` of its JWK, in the order
+/// of the PKCS#1 `RSAPrivateKey` structure (`n`, `e`, `d`, `p`, `q`, `dp`, `dq`, `qi`)
+#[cfg(feature = "jwe-rsa")]
+fn rsa_pkcs8(integers: &[Vec<u8>]) -> Vec<u8> {
+    fn der(tag: u8, content: &[u8]) -> Vec<u8> {
+        let mut encoded = vec![tag];
+        let length = content.len().to_be_bytes();
+        let significant = length.iter().skip_while(|byte| **byte == 0).count();
+        if content.len() < 0x80 {
+            encoded.push(content.len() as u8);
+        } else {
+            encoded.push(0x80 | significant as u8);
+            encoded.extend_from_slice(&length[length.len() - significant..]);
+        }
+        encoded.extend_from_slice(content);
+        encoded
+    }
+
+    // Unsigned big-endian integers, prefixed with a zero byte when their high bit is set
+    fn integer(value: &[u8]) -> Vec<u8> {
+        let leading = value.iter().take_while(|byte| **byte == 0).count();
+        let value = &value[leading.min(value.len().saturating_sub(1))..];
+        let mut content = Vec::with_capacity(value.len() + 1);
+        if value.first().is_none_or(|byte| byte & 0x80 != 0) {
+            content.push(0);
+        }
+        content.extend_from_slice(value);
+        der(0x02, &content)
+    }
+
+    let mut key = integer(&[0]);
+    for value in integers {
+        key.extend(integer(value));
+    }
+
+    // Version 0, rsaEncryption (1.2.840.113549.1.1.1) without parameters, then the key itself
+    let mut pkcs8 = integer(&[0]);
+    pkcs8.extend(der(
+        0x30,
+        &[
+            0x06, 0x09, 0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x01, 0x05, 0x00,
+        ],
+    ));
+    pkcs8.extend(der(0x04, &der(0x30, &key)));
+    der(0x30, &pkcs8)
+}
+
+/// Key management algorithms accepted, as listed when rejecting the others
+#[cfg(feature = "jwe-rsa")]
+const KEY_MANAGEMENT_ALGORITHMS: &str = "'dir', 'A128KW', 'A256KW', 'RSA-OAEP-256'";
+#[cfg(not(feature = "jwe-rsa"))]
+const KEY_MANAGEMENT_ALGORITHMS: &str = "'dir', 'A128KW', 'A256KW'";
+
+/// Protected header of the envelope
+#[derive(Deserialize)]
+struct JweHeader {
+    alg: String,
+    enc: String,
+    kid: Option<String>,
+    zip: Option<String>,
+    cty: Option<String>,
+    crit: Option<Vec<String>>,
+}
+
+/// Keys decrypting the payloads sent as JWE envelopes
+pub(crate) struct Decryption {
+    keys: Vec<DecryptionKey>,
+
+    /// Whether payloads sent in clear are rejected
+    required: bool,
+}
+
+impl Decryption {
+    /// Decryption configured through `HFENDPOINT_JWE_KEYS` and `HFENDPOINT_JWE_REQUIRED`, if any
+    pub(crate) fn from_env() -> Option<Arc<Self>> {
+        let required = std::env::var(JWE_REQUIRED_ENV)
+            .is_ok_and(|required| matches!(required.trim(), "1" | "true" | "yes"));
+        let path = std::env::var(JWE_KEYS_ENV).ok();
+        if path.is_none() && !required {
+            return None;
+        }
+
+        // Encrypted payloads are rejected rather than served in clear when the keys cannot be loaded
+        let keys = match path.map(|path| (std::fs::read_to_string(&path), path)) {
+            Some((Ok(jwks), path)) => Self::parse(&jwks).unwrap_or_else(|err| {
+                error!("Ignoring malformed key set {path} ({JWE_KEYS_ENV}): {err}");
+                Vec::new()
+            }),
+            Some((Err(err), path)) => {
+                error!("Failed to read key set {path} ({JWE_KEYS_ENV}): {err}");
+                Vec::new()
+            }
+            None => Vec::new(),
+        };
+
+        info!(
+            "Accepting JWE encrypted payloads with {} key(s), encryption required: {required}",
+            keys.len()
+        );
+        Some(Arc::new(Self { keys, required }))
+    }
+
+    fn parse(jwks: &str) -> Result<Vec<DecryptionKey>, String> {
+        let jwks: JwkSet = serde_json::from_str(jwks).map_err(|err| err.to_string())?;
+        jwks.keys.into_iter().map(DecryptionKey::try_from).collect()
+    }
+
+    /// Content encryption key of the envelope, unwrapped with the first matching key
+    fn content_key(&self, header: &JweHeader, encrypted_key: &[u8]) -> OpenAiResult<Vec<u8>> {
+        let candidates = self.keys.iter().filter(|key| {
+            header
+                .kid
+                .as_ref()
+                .is_none_or(|kid| key.kid.as_ref() == Some(kid))
+                && key.alg.as_ref().is_none_or(|alg| *alg == header.alg)
+        });
+
+        for key in candidates {
+            let unwrapped = match (header.alg.as_str(), &key.material) {
+                ("dir", KeyMaterial::Symmetric(key)) => {
+                    encrypted_key.is_empty().then(|| key.clone())
+                }
+                ("A128KW", KeyMaterial::Symmetric(key)) => KekAes128::try_from(key.as_slice())
+                    .ok()
+                    .and_then(|kek| kek.unwrap_vec(encrypted_key).ok()),
+                ("A256KW", KeyMaterial::Symmetric(key)) => KekAes256::try_from(key.as_slice())
+                    .ok()
+                    .and_then(|kek| kek.unwrap_vec(encrypted_key).ok()),
+                #[cfg(feature = "jwe-rsa")]
+                ("RSA-OAEP-256", KeyMaterial::Rsa(key)) => {
+                    let mut content_key = vec![0; key.min_output_size()];
+                    key.decrypt(
+                        &OAEP_SHA256_MGF1SHA256,
+                        encrypted_key,
+                        &mut content_key,
+                        None,
+                    )
+                    .ok()
+                    .map(|content_key| content_key.to_vec())
+                }
+                #[cfg(feature = "jwe-rsa")]
+                ("dir" | "A128KW" | "A256KW" | "RSA-OAEP-256", _) => None,
+                (alg, _) => {
+                    return Err(OpenAiError::Decryption(format!(
+                        "Unsupported key management algorithm '{alg}'. Possible values are: {KEY_MANAGEMENT_ALGORITHMS}."
+                    )));
+                }
+            };
+
+            if let Some(content_key) = unwrapped {
+                return Ok(content_key);
+            }
+        }
+
+        Err(OpenAiError::Decryption(String::from(
+            "No configured key decrypts the envelope",
+        )))
+    }
+
+    /// Decrypt the compact serialization `envelope`, returning the payload along with its content type
+    pub(crate) fn decrypt(&self, envelope: &[u8]) -> OpenAiResult<(Bytes, String)> {
+        let malformed =
+            |reason: &str| OpenAiError::Decryption(format!("Malformed envelope: {reason}"));
+        let envelope = std::str::from_utf8(envelope)
+            .map_err(|_| malformed("expected the compact serialization"))?
+            .trim();
+
+        let parts: Vec<&str> = envelope.split('.').collect();
+        let [protected, encrypted_key, iv, ciphertext, tag] = parts.as_slice() else {
+            return Err(malformed(
+                "expected the five parts of the compact serialization",
+            ));
+        };
+        let decode = |part: &str, name: &str| {
+            BASE64_URL_SAFE_NO_PAD
+                .decode(part)
+                .map_err(|_| malformed(&format!("the {name} is not base64url encoded")))
+        };
+
+        let header: JweHeader = serde_json::from_slice(&decode(protected, "header")?)
+            .map_err(|err| malformed(&format!("invalid header: {err}")))?;
+        if let Some(crit) = &header.crit {
+            return Err(OpenAiError::Decryption(format!(
+                "Unsupported critical header parameters: {crit:?}"
+            )));
+        }
+
+        let content_key = self.content_key(&header, &decode(encrypted_key, "encrypted key")?)?;
+        let iv = decode(iv, "initialization vector")?;
+        if iv.len() != 12 {
+            return Err(malformed("the initialization vector must be 96 bits long"));
+        }
+
+        let mut sealed = decode(ciphertext, "ciphertext")?;
+        sealed.extend(decode(tag, "authentication tag")?);
+        let payload = Payload {
+            msg: &sealed,
+            aad: protected.as_bytes(),
+        };
+
+        let nonce = Nonce::from_slice(&iv);
+        let mismatch = || {
+            OpenAiError::Decryption(format!(
+                "The content key does not match the '{}' encryption",
+                header.enc
+            ))
+        };
+        let plaintext = match header.enc.as_str() {
+            "A128GCM" => Aes128Gcm::new_from_slice(&content_key)
+                .map_err(|_| mismatch())?
+                .decrypt(nonce, payload),
+            "A256GCM" => Aes256Gcm::new_from_slice(&content_key)
+                .map_err(|_| mismatch())?
+                .decrypt(nonce, payload),
+            enc => {
+                return Err(OpenAiError::Decryption(format!(
+                    "Unsupported content encryption '{enc}'. Possible values are: 'A128GCM', 'A256GCM'."
+                )));
+            }
+        }
+        .map_err(|_| OpenAiError::Decryption(String::from("The payload failed authentication")))?;
+
+        let plaintext = match header.zip.as_deref() {
+            None => Bytes::from(plaintext),
+            Some("DEF") => read_bounded(
+                DeflateDecoder::new(plaintext.as_slice()),
+                MAX_PLAINTEXT_SIZE,
+            )?,
+            Some(zip) => {
+                return Err(OpenAiError::Decryption(format!(
+                    "Unsupported compression '{zip}'. Possible values are: 'DEF'."
+                )));
+            }
+        };
+
+        // RFC 7515 allows omitting the "application/" prefix of the content type
+        let content_type = match header.cty {
+            Some(cty) if cty.contains('/') => cty,
+            Some(cty) => format!("application/{cty}"),
+            None => String::from("application/json"),
+        };
+
+        Ok((plaintext, content_type))
+    }
+}
+
+/// Whether the request body is a JWE envelope, rather than the payload itself
+fn is_envelope(request: &Request) -> bool {
+    request
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|content_type| content_type.to_str().ok())
+        .and_then(|content_type| content_type.split(';').next())
+        .is_some_and(|media_type| media_type.trim().eq_ignore_ascii_case(JOSE_CONTENT_TYPE))
+}
+
+/// Replace the JWE envelope sent as body by the payload it holds, before the request reaches the route
+#[instrument(skip_all)]
+pub(crate) async fn decrypt_payload(
+    State(decryption): State<Arc<Decryption>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    // Requests of a batch are sent by the endpoint itself, the batch file being decrypted already
+    #[cfg(feature = "jobs")]
+    if request.extensions().get::<Replayed>().is_some() {
+        return next.run(request).await;
+    }
+
+    if !is_envelope(&request) {
+        if decryption.required && request.method() == Method::POST {
+            let err = OpenAiError::Decryption(format!(
+                "The payload must be encrypted and sent as {JOSE_CONTENT_TYPE}"
+            ));
+            return err.into_response();
+        }
+        return next.run(request).await;
+    }
+
+    let (mut parts, body) = request.into_parts();
+    let limit = parts
+        .extensions
+        .get::<BodyLimit>()
+        .copied()
+        .unwrap_or_default();
+    let envelope = match read_limited(body, limit.bound(MAX_ENVELOPE_SIZE)).await {
+        Ok(envelope) => envelope,
+        Err(err) => return err.into_response(),
+    };
+
+    // Decryption is CPU bound, keep it away from the I/O threads
+    debug!("Decrypting {} bytes envelope", envelope.len());
+    let decrypted = spawn_blocking(move || decryption.decrypt(&envelope))
+        .await
+        .map_err(|err| OpenAiError::Io(err.into()))
+        .and_then(|decrypted| decrypted);
+
+    let (payload, content_type) = match decrypted {
+        Ok(decrypted) => decrypted,
+        Err(err) => return err.into_response(),
+    };
+    let Ok(content_type) = HeaderValue::from_str(&content_type) else {
+        let err = OpenAiError::Decryption(String::from("Invalid 'cty' header parameter"));
+        return err.into_response();
+    };
+
+    parts.headers.insert(CONTENT_TYPE, content_type);
+    parts
+        .headers
+        .insert(CONTENT_LENGTH, HeaderValue::from(payload.len()));
+    next.run(Request::from_parts(parts, Body::from(payload)))
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "jobs")]
+    use crate::batches::batch::Replayed;
+    use crate::encryption::{Decryption, decrypt_payload};
+    use crate::error::OpenAiError;
+    use aes_gcm::aead::{Aead, KeyInit, Payload};
+    use aes_gcm::{Aes256Gcm, Nonce};
+    use aes_kw::KekAes128;
+    use axum::Router;
+    use axum::body::{Body, to_bytes};
+    use axum::http::header::CONTENT_TYPE;
+    use axum::http::{Request, StatusCode};
+    use axum::middleware::from_fn_with_state;
+    use axum::routing::post;
+    use base64::Engine;
+    use base64::prelude::BASE64_URL_SAFE_NO_PAD;
+    use flate2::Compression;
+    use flate2::write::DeflateEncoder;
+    use serde_json::json;
+    use std::io::Write;
+    use std::sync::Arc;
+    use tower::ServiceExt;
+
+    const DIRECT_KEY: [u8; 32] = [7; 32];
+    const WRAPPING_KEY: [u8; 16] = [9; 16];
+
+    /// RSA key and envelope produced by another JWE implementation (Python `cryptography`)
+    const RSA_JWK: &str = r#"{"kty":"RSA","kid":"rsa-1","alg":"RSA-OAEP-256","n":"hgp4QNPnL8z0QoFAj9kshONjZQtq9gh4Mifw2oA8RBEjF948htWnX1dz_xkR-xdftWCy8x1lKwkoEWCe0PGnZ_INNu8pzlgiWdDiVOqO0X7OQJEQroCr0ipHEs1I3sd4oSwhfFBEwmeXb54HGbrGT4pyK0h8GtuH_HLplfyBhTQrAsN25otyQBIQ7TUMUApWLR4vP9WPVktrv0nrB06Povea15GL4wPLou8e1FyNX-8cCJwlx8q1da6gYRKAIZssPM3kYCled2Lgm7t1-wAQZuvyTfbq4iGjHZ620Fp-7vS1SrsR_aqbWg8Ymw4nEyKwbXRbRsZ0ep-a_I7tDNpk1w","e":"AQAB","d":"G7wh2sQd44GkjvhAnCMjN5H3WIqOxL7G5BXom8WJ8sma-c7Vtk6SFcO6MDAHTO2us5CBJpXP_6Ii5MmZwwElkvf-LP46p8QLw4rZshlgmM-ErM53ZawhIVj3INhSOiqK9spzWU4glBssNf-8V2ISs5Whfvw-Fa6UCjPaylqXkDCJBmw-NmkEwuYfP21AHsGP3bn1X2cJf1UcBJKNgJVelyllfeCKFrkXBk_9atle5m2VXpuVwABrntd9SCtkGANlKRhTgnXwQooIakSrWjzK3pSyKfKZSPTeamcPapZD9o4iZ5pxiCOhgbBGhHQxPZF_YCQOrNWY-mxSeyQuhLVV5Q","p":"uq_m61D0lRObeZK8tS829gXy5DXN6wvX8mK8iRzJyMLoEnwQNqGk4U0qxuAF9hIaJ3WNg3B2-hfUojI9YziQ_uCrHz_6J2WEW3aSlDmYDtaP1fC1Ujz4x7VHz4Dl46Kn4PNwiVQO5djjSlErlQX0BdEjx27_YLEsit8wgNKrH1s","q":"t86wZUlkUscqBvrvqP598HQoi2EWz_pR4gKiIN49nvwTj26BdNV6QYk4O8Wn87oSPQQ72bqFLAmCqMY0kP7Cb6bFi_Tlt63WMhMerQLYalAYvR7IZI8IAG6u3HsZTO2MO7INPiETraBTFA8HHYeCcx10FOz7ekvHrXUEmx-KZTU","dp":"oR8Dm4BpNuiPPOPJ_k3bj4Ye1ZsiN4QnQki5E1VHSf_9r0Zn796eoNyq2K2sN230KK4qDpDX_ozmtX3KwYGmknNw_S2IRsw06YNyin8Sn2vLBAgVVf7Nb_8jFRHYVxig72Lrc3qayQDhWdioVpumugaAbLV9rwP6v3hVMjmf-xU","dq":"N_xy6kcSkcwsVvf0K-tofOdEuj-WbT731fmrXIuVBWoOoy-rquSjWnbrc_lekPRqPb-eY1FW4vT6oWHN_SQ8IToRDfJITkxa4-HDBDvLo4ZGIsTPkSGn9EnCZVHxgOcSQdSYCAaXMdmVtJRnPG1cJKgiBYWxi6ytY34zKvTOeMk","qi":"bpi0GZlkWt1sMZkgWQOnxJrqrZ3BnD_AXj5wJMMgs5xKIEGB5KX7PwxaOvG2dnfIjqGzFkdxvkaj04vTjpS4sCeJZDMVnyWQOQF1TLkRkOTWGVcivdVeKaAkIejLuKFZmwD_H9kWaxT45GPrXkwTuocE6bzEErv3H-nt62W-P5c"}"#;
+    #[cfg(feature = "jwe-rsa")]
+    const RSA_ENVELOPE: &str = "eyJhbGciOiJSU0EtT0FFUC0yNTYiLCJlbmMiOiJBMjU2R0NNIiwia2lkIjoicnNhLTEifQ.MkO8exjjLoDb-t2GyCBZbFI3bfD8wPDaQ6YqF5VJf28OPGyRrbw5OOVwLIhgULpikxSxR-am4ogh7z-zATtPjUQkjvIjmlDuiqF0RhT1D7ET8-Y5AbhyFzCBnVJ5mG9jAAoA0mM32vNTw06k2dPRbKyUfOhwHelVmYT_hF7JeSxnckrdCW5QvY0gDZCiFECFkImru6oW5Dt6EGou1lm8xJucSpQvo12ZWVGo-39MEf4F6vLnbdp3yWp_IxHS77vXGjMX_ALeJXqZjMk8d2kwvBO8DhoTLhNx3G5ZsBntmydU_28aPmIalzalcHJR2-HHFB3AL67eMaenXwmC9bl3Vg.2BGjiaje1wBSFnl3.__IZCSf8B0QZdbLuVFweK-woi8cXbz5ThKk.uvsnHEYaEwLH-Nj5Mxtdag";
+
+    fn decryption(required: bool) -> Decryption {
+        let mut keys = vec![
+            json!({"kty": "oct", "kid": "direct", "alg": "dir", "k": BASE64_URL_SAFE_NO_PAD.encode(DIRECT_KEY)}),
+            json!({"kty": "oct", "kid": "wrapping", "k": BASE64_URL_SAFE_NO_PAD.encode(WRAPPING_KEY)}),
+        ];
+        if cfg!(feature = "jwe-rsa") {
+            keys.push(serde_json::from_str(RSA_JWK).unwrap());
+        }
+        let jwks = json!({"keys": keys});
+
+        Decryption {
+            keys: Decryption::parse(&jwks.to_string()).unwrap(),
+            required,
+        }
+    }
+
+    /// Encrypt `payload` with A256GCM, the content key being either the direct key or wrapped with A128KW
+    fn seal(header: serde_json::Value, payload: &[u8]) -> String {
+        let content_key = [3u8; 32];
+        let (content_key, encrypted_key) = match header["alg"].as_str().unwrap() {
+            "dir" => (DIRECT_KEY, Vec::new()),
+            _ => (
+                content_key,
+                KekAes128::from(WRAPPING_KEY)
+                    .wrap_vec(&content_key)
+                    .unwrap(),
+            ),
+        };
+
+        let protected = BASE64_URL_SAFE_NO_PAD.encode(header.to_string());
+        let iv = [1u8; 12];
+        let sealed = Aes256Gcm::new_from_slice(&content_key)
+            .unwrap()
+            .encrypt(
+                Nonce::from_slice(&iv),
+                Payload {
+                    msg: payload,
+                    aad: protected.as_bytes(),
+                },
+            )
+            .unwrap();
+        let (ciphertext, tag) = sealed.split_at(sealed.len() - 16);
+
+        [
+            protected,
+            BASE64_URL_SAFE_NO_PAD.encode(encrypted_key),
+            BASE64_URL_SAFE_NO_PAD.encode(iv),
+            BASE64_URL_SAFE_NO_PAD.encode(ciphertext),
+            BASE64_URL_SAFE_NO_PAD.encode(tag),
+        ]
+        .join(".")
+    }
+
+    #[test]
+    fn decrypt_envelopes() {
+        let decryption = decryption(false);
+
+        let envelope = seal(
+            json!({"alg": "dir", "enc": "A256GCM"}),
+            b"{\"input\":\"Hello\"}",
+        );
+        let (payload, content_type) = decryption.decrypt(envelope.as_bytes()).unwrap();
+        assert_eq!(&payload[..], b"{\"input\":\"Hello\"}");
+        assert_eq!(content_type, "application/json");
+
+        // Compressed payload of another content type, with a wrapped content key
+        let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(b"--boundary--").unwrap();
+        let header = json!({"alg": "A128KW", "enc": "A256GCM", "kid": "wrapping", "zip": "DEF", "cty": "multipart/form-data; boundary=boundary"});
+        let envelope = seal(header, &encoder.finish().unwrap());
+        let (payload, content_type) = decryption.decrypt(envelope.as_bytes()).unwrap();
+        assert_eq!(&payload[..], b"--boundary--");
+        assert_eq!(content_type, "multipart/form-data; boundary=boundary");
+    }
+
+    #[cfg(feature = "jwe-rsa")]
+    #[test]
+    fn decrypt_rsa_envelopes() {
+        let decryption = decryption(false);
+        let (payload, _) = decryption.decrypt(RSA_ENVELOPE.as_bytes()).unwrap();
+        assert_eq!(&payload[..], b"{\"input\":\"Patient record\"}");
+
+        // Content keys wrapped for another key fail the same way as any other undecryptable one
+        let mut parts: Vec<&str> = RSA_ENVELOPE.split('.').collect();
+        let tampered = BASE64_URL_SAFE_NO_PAD.encode([1u8; 256]);
+        parts[1] = &tampered;
+        assert!(matches!(
+            decryption.decrypt(parts.join(".").as_bytes()),
+            Err(OpenAiError::Decryption(message)) if message == "No configured key decrypts the envelope"
+        ));
+    }
+
+    #[cfg(not(feature = "jwe-rsa"))]
+    #[test]
+    fn rsa_keys_require_the_feature() {
+        let jwks = format!(r#"{{"keys": [{RSA_JWK}]}}"#);
+        let err = Decryption::parse(&jwks).err().unwrap();
+        assert_eq!(
+            err,
+            "key rsa-1: RSA keys require hfendpoints to be built with the jwe-rsa feature"
+        );
+    }
+
+    #[test]
+    fn reject_invalid_envelopes() {
+        let decryption = decryption(false);
+        let envelope = seal(json!({"alg": "dir", "enc": "A256GCM"}), b"{}");
+
+        // Tampered ciphertext
+        let mut tampered: Vec<String> = envelope.split('.').map(String::from).collect();
+        tampered[3] = BASE64_URL_SAFE_NO_PAD.encode(b"??");
+        let rejected = [
+            tampered.join("."),
+            seal(
+                json!({"alg": "dir", "enc": "A256GCM", "kid": "unknown"}),
+                b"{}",
+            ),
+            seal(
+                json!({"alg": "dir", "enc": "A256GCM", "crit": ["exp"]}),
+                b"{}",
+            ),
+            seal(json!({"alg": "ECDH-ES", "enc": "A256GCM"}), b"{}"),
+            String::from("not.an.envelope"),
+        ];
+
+        for envelope in rejected {
+            assert!(
+                matches!(
+                    decryption.decrypt(envelope.as_bytes()),
+                    Err(OpenAiError::Decryption(_))
+                ),
+                "{envelope}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn decrypt_payload_before_routing() {
+        let router = |required| {
+            Router::new()
+                .route(
+                    "/echo",
+                    post(|request: axum::extract::Request| async move {
+                        let content_type = request.headers()[CONTENT_TYPE].clone();
+                        let body = to_bytes(request.into_body(), usize::MAX).await.unwrap();
+                        format!(
+                            "{}:{}",
+                            content_type.to_str().unwrap(),
+                            String::from_utf8_lossy(&body)
+                        )
+                    }),
+                )
+                .layer(from_fn_with_state(
+                    Arc::new(decryption(required)),
+                    decrypt_payload,
+                ))
+        };
+        let request = |content_type: &str, body: String| {
+            Request::post("/echo")
+                .header(CONTENT_TYPE, content_type)
+                .body(Body::from(body))
+                .unwrap()
+        };
+
+        let envelope = seal(
+            json!({"alg": "dir", "enc": "A256GCM", "cty": "json"}),
+            b"{}",
+        );
+        let response = router(true)
+            .oneshot(request("application/jose", envelope))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(&body[..], b"application/json:{}");
+
+        // Payloads in clear are only accepted when encryption is optional
+        let response = router(false)
+            .oneshot(request("application/json", String::from("{}")))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = router(true)
+            .oneshot(request("application/json", String::from("{}")))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        // Requests replayed from a batch were decrypted along with the batch file
+        #[cfg(feature = "jobs")]
+        {
+            let mut replayed = request("application/json", String::from("{}"));
+            replayed.extensions_mut().insert(Replayed);
+            let response = router(true).oneshot(replayed).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+        }
+
+        let response = router(false)
+            .oneshot(request("application/jose", String::from("a.b.c.d.e")))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+}
@@ -0,0 +1,652 @@
+//! Serving handlers implemented in Rust.
+//!
+//! [`Endpoint::builder`] mounts the routes of the task of every handler provided, each handler
+//! processing the requests of its task from a queue of its own, and serves them the way the Python
+//! bindings do: the server is configured through the environment (see [`serve_openai`]), workers
+//! through `HFENDPOINT_WORKERS` and, with the `distributed` feature, the distributed mode through
+//! `HFENDPOINT_REDIS_URL`.
+//!
+//! ```no_run
+//! # use hfendpoints_core::{Error, Handler};
+//! # use hfendpoints_openai::embeddings::embedding::{EmbeddingRequest, EmbeddingResponse};
+//! # use hfendpoints_openai::{Context, Endpoint, ModelCard};
+//! struct Embedder;
+//!
+//! impl Handler for Embedder {
+//!     type Request = (EmbeddingRequest, Context);
+//!     type Response = EmbeddingResponse;
+//!
+//!     async fn on_request(&self, (request, _): Self::Request) -> Result<Self::Response, Error> {
+//!         let embeddings = request.input.iter().map(|_| vec![0.0; 8]).collect();
+//!         Ok(EmbeddingResponse::new(String::from("embedder"), embeddings, 0))
+//!     }
+//! }
+//!
+//! # async fn run() {
+//! Endpoint::builder()
+//!     .handler(Embedder)
+//!     .model(ModelCard::new("embedder"))
+//!     .serve(("0.0.0.0", 8000))
+//!     .await
+//!     .unwrap();
+//! # }
+//! ```
+//!
+//! Handlers whose model takes a while to load are rather given through [`EndpointBuilder::load`]:
+//! the endpoint is served right away, loading every handler in parallel once the tasks it depends
+//! on are loaded, and `/health/ready` only succeeds once every task is, reporting the progress of
+//! each of them meanwhile.
+//!
+//! [`serve_openai`]: crate::serve_openai
+use crate::policy::RequestPolicy;
+#[cfg(feature = "distributed")]
+use crate::shutdown::Shutdown;
+use crate::{
+    Context, EndpointConfig, ModelCard, OpenAiError, OpenAiResult, RequestContext,
+    serve_openai_tasks,
+};
+use axum::Extension;
+#[cfg(feature = "distributed")]
+use hfendpoints_core::{DistributedQueue, Payload, distributed_queue_from_env};
+use hfendpoints_core::{
+    EndpointContext, Error, Handler, RequestReceiver, RequestSender, ScheduledRequest, WorkerPool,
+    preemption_from_env, readiness, request_channel, wait_for_requests, workers_from_env,
+};
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::future::Future;
+use std::sync::Arc;
+use tokio::net::ToSocketAddrs;
+use tokio::runtime::Handle;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use tracing::{error, info};
+use utoipa_axum::router::OpenApiRouter;
+
+/// Request of a task served by an [`Endpoint`], routed to the handler processing it
+pub trait TaskRequest: Sized {
+    /// Response the handlers of the task produce
+    type Response;
+
+    /// Routes of the task, scheduling the requests through `sender`
+    fn router(sender: RequestSender<(Self, Context), Self::Response>) -> OpenApiRouter;
+}
+
+/// Serve the `routes` of the task at `path`, scheduling the requests through `sender` within the
+/// request timeout and turns of the endpoint, once the operator's policy for `path` applied
+pub(crate) fn task_router<I, O>(
+    routes: OpenApiRouter<EndpointContext<(I, Context), O>>,
+    sender: RequestSender<(I, Context), O>,
+    path: &str,
+) -> OpenApiRouter
+where
+    EndpointContext<(I, Context), O>: Clone + Send + Sync + 'static,
+{
+    let state = EndpointContext::new(sender)
+        .with_timeout(EndpointConfig::current().request_timeout)
+        .with_preemption(preemption_from_env());
+    routes
+        .with_state(state)
+        .layer(Extension(Arc::new(RequestPolicy::from_env(path))))
+}
+
+/// Hand `request` over to the handler of the task once its turn to run came, when preemption is
+/// enabled, the handler being notified when the outcome stops being awaited
+pub(crate) async fn schedule_task<I, O>(
+    state: &EndpointContext<(I, Context), O>,
+    metadata: RequestContext,
+    request: I,
+) -> OpenAiResult<ScheduledRequest<O>> {
+    let ctx = Context::new(metadata).with_timeslice(state.timeslice().await?);
+    let cancellation = ctx.cancellation().clone();
+    Ok(state
+        .schedule((request, ctx))
+        .with_cancellation(cancellation))
+}
+
+/// Let `handler`, or the workers of `pool`, process the requests received through `receiver` on `runtime`
+pub(crate) fn spawn_looper<H>(
+    runtime: &Handle,
+    handler: &Arc<H>,
+    pool: Option<&WorkerPool<H>>,
+    receiver: RequestReceiver<H::Request, H::Response>,
+) -> JoinHandle<Result<(), Error>>
+where
+    H: Handler + Send + Sync + 'static,
+    H::Request: Send + 'static,
+    H::Response: Send + 'static,
+{
+    match pool {
+        Some(pool) => runtime.spawn(pool.clone().run(receiver)),
+        None => runtime.spawn(wait_for_requests(receiver, Arc::clone(handler))),
+    }
+}
+
+/// Whether the process serves the task routes, i.e. unless it only acts as a worker in
+/// distributed mode, see [`mount_handler`]
+fn serves_routes() -> Result<bool, Error> {
+    #[cfg(feature = "distributed")]
+    if let Some(queue) = distributed_queue_from_env()? {
+        return Ok(queue.role().is_transport());
+    }
+    Ok(true)
+}
+
+/// Wait for `loopers` to complete the requests in flight, reporting the first failure
+async fn join_loopers(
+    loopers: impl IntoIterator<Item = JoinHandle<Result<(), Error>>>,
+) -> Result<(), Error> {
+    let mut outcome = Ok(());
+    for looper in loopers {
+        match looper.await {
+            Ok(result) => outcome = outcome.and(result),
+            Err(err) => error!("Handler loop panicked: {err}"),
+        }
+    }
+    outcome
+}
+
+/// Handler loops of a task, along with the routes forwarding them the requests when the
+/// process serves HTTP
+pub(crate) struct Mount {
+    router: Option<OpenApiRouter>,
+    loopers: Vec<JoinHandle<Result<(), Error>>>,
+}
+
+impl Mount {
+    /// Await `loopers` as well once the server stopped
+    #[cfg(feature = "python")]
+    pub(crate) fn with_loopers(
+        mut self,
+        loopers: impl IntoIterator<Item = JoinHandle<Result<(), Error>>>,
+    ) -> Self {
+        self.loopers.extend(loopers);
+        self
+    }
+}
+
+/// Requests and responses of the handlers, exchanged with the remote workers in distributed mode
+#[cfg(feature = "distributed")]
+pub trait Queued: Payload {}
+
+#[cfg(feature = "distributed")]
+impl<T: Payload> Queued for T {}
+
+/// Requests and responses of the handlers, only exchanged in memory without the `distributed` feature
+#[cfg(not(feature = "distributed"))]
+pub trait Queued {}
+
+#[cfg(not(feature = "distributed"))]
+impl<T> Queued for T {}
+
+/// Let `handler`, or the workers of `pool`, process the requests received through `receiver` on `runtime`.
+///
+/// In distributed mode (`HFENDPOINT_REDIS_URL` being set), transports append the requests to the
+/// Redis queue and workers process the requests consumed from it, see `hfendpoints_core::QueueRole`.
+/// Tasks served along others (`task` being set) get a queue of their own, named after their position.
+#[cfg_attr(not(feature = "distributed"), allow(unused_variables))]
+pub(crate) fn mount_handler<H, R>(
+    runtime: &Handle,
+    handler: &Arc<H>,
+    pool: Option<&WorkerPool<H>>,
+    receiver: RequestReceiver<H::Request, H::Response>,
+    router: R,
+    task: Option<usize>,
+) -> Result<Mount, Error>
+where
+    H: Handler + Send + Sync + 'static,
+    H::Request: Queued + Send + 'static,
+    H::Response: Queued + Send + 'static,
+    R: Into<OpenApiRouter> + Send + 'static,
+{
+    #[cfg(feature = "distributed")]
+    if let Some(queue) = distributed_queue_from_env()? {
+        let queue = match task {
+            Some(task) => {
+                let name = format!("{}:{task}", queue.name());
+                queue.with_name(name)
+            }
+            None => queue,
+        };
+        return Ok(mount_queue(runtime, handler, pool, receiver, router, queue));
+    }
+
+    Ok(Mount {
+        router: Some(router.into()),
+        loopers: vec![spawn_looper(runtime, handler, pool, receiver)],
+    })
+}
+
+/// Let the process play its role in the distributed mode, serving HTTP only as a transport
+#[cfg(feature = "distributed")]
+fn mount_queue<H, R>(
+    runtime: &Handle,
+    handler: &Arc<H>,
+    pool: Option<&WorkerPool<H>>,
+    receiver: RequestReceiver<H::Request, H::Response>,
+    router: R,
+    queue: DistributedQueue,
+) -> Mount
+where
+    H: Handler + Send + Sync + 'static,
+    H::Request: Payload + Send + 'static,
+    H::Response: Payload + Send + 'static,
+    R: Into<OpenApiRouter> + Send + 'static,
+{
+    let mut loopers = Vec::with_capacity(3);
+    let role = queue.role();
+    info!(
+        "Distributed mode enabled, acting as {role} on {}",
+        queue.name()
+    );
+
+    if role.is_worker() {
+        // Requests are claimed from the queue as long as a worker is available to process them
+        let concurrency = pool
+            .map(WorkerPool::len)
+            .unwrap_or_else(|| EndpointConfig::current().queue_capacity);
+        let (sender, local) = request_channel(concurrency);
+        loopers.push(spawn_looper(runtime, handler, pool, local));
+
+        let shutdown = Shutdown::from_env().signal();
+        let queue = queue.clone();
+        loopers
+            .push(runtime.spawn(async move { queue.consume(sender, concurrency, shutdown).await }));
+    }
+
+    if role.is_transport() {
+        loopers.push(runtime.spawn(async move { queue.forward(receiver).await }));
+    }
+
+    Mount {
+        router: role.is_transport().then(|| router.into()),
+        loopers,
+    }
+}
+
+/// Serve the routes of `mounts` on `interface` advertising `model`, until the server stops
+/// and every handler loop completed the requests in flight
+pub(crate) async fn serve_mounts<A>(
+    runtime: &Handle,
+    mounts: Vec<Mount>,
+    model: ModelCard,
+    interface: A,
+) -> OpenAiResult<()>
+where
+    A: ToSocketAddrs + Debug + Send + 'static,
+{
+    let (routers, loopers): (Vec<_>, Vec<_>) = mounts
+        .into_iter()
+        .map(|mount| (mount.router, mount.loopers))
+        .unzip();
+
+    let routers: Vec<_> = routers.into_iter().flatten().collect();
+    let mut outcome = Ok(());
+    if !routers.is_empty() {
+        info!("Starting endpoint at {interface:?}");
+        let served = runtime
+            .spawn(serve_openai_tasks(interface, routers, model))
+            .await
+            .inspect_err(|err| {
+                info!("Caught error while serving endpoint: {err}");
+            })
+            .unwrap();
+
+        // i.e. the endpoint does not match the snapshot it boots from
+        if let Err(err) = served {
+            error!("Failed to serve endpoint: {err}");
+            outcome = Err(err);
+        }
+    }
+
+    // The server dropped the request senders, let the handlers complete in-flight requests
+    let completed = join_loopers(loopers.into_iter().flatten()).await;
+    outcome.and(completed.map_err(OpenAiError::from))
+}
+
+/// Tasks whose handler is loaded by the endpoint, the ones loaded after them waiting for their turn
+#[derive(Default)]
+struct Startup {
+    loaded: HashMap<String, watch::Receiver<bool>>,
+}
+
+impl Startup {
+    /// Register `task`, loaded once every task of `after` is, these being registered before it.
+    /// Returns the sender flagging `task` loaded, along with the future resolving once the tasks
+    /// of `after` are loaded, or to the first of them which failed to.
+    fn register(
+        &mut self,
+        task: &str,
+        after: &[String],
+    ) -> Result<
+        (
+            watch::Sender<bool>,
+            impl Future<Output = Result<(), String>> + use<>,
+        ),
+        Error,
+    > {
+        let dependencies = after
+            .iter()
+            .map(|dependency| match self.loaded.get(dependency) {
+                Some(loaded) => Ok((dependency.clone(), loaded.clone())),
+                None => Err(Error::UnknownDependency {
+                    task: String::from(task),
+                    dependency: dependency.clone(),
+                }),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let (sender, loaded) = watch::channel(false);
+        self.loaded.insert(String::from(task), loaded);
+        let dependencies = async move {
+            // Tasks failing to load drop their sender without flagging them loaded
+            for (dependency, mut loaded) in dependencies {
+                if loaded.wait_for(|loaded| *loaded).await.is_err() {
+                    return Err(dependency);
+                }
+            }
+            Ok(())
+        };
+        Ok((sender, dependencies))
+    }
+}
+
+/// Mount of the task of a handler on the runtime, `task` being its position when served along others
+type MountTask =
+    Box<dyn FnOnce(&Handle, Option<usize>, &mut Startup) -> Result<Mount, Error> + Send>;
+
+/// Endpoint serving handlers implemented in Rust, configured through [`EndpointBuilder`]
+pub struct Endpoint;
+
+impl Endpoint {
+    /// Endpoint without any handler, see `EndpointBuilder::handler`
+    pub fn builder() -> EndpointBuilder {
+        EndpointBuilder {
+            tasks: Vec::new(),
+            model: ModelCard::default(),
+        }
+    }
+}
+
+/// Configures the handlers served by an [`Endpoint`] and the model it advertises
+pub struct EndpointBuilder {
+    tasks: Vec<MountTask>,
+    model: ModelCard,
+}
+
+impl EndpointBuilder {
+    /// Serve the task of `handler`, i.e. transcriptions for handlers of `TranscriptionRequest`.
+    ///
+    /// The handler is driven concurrently, one task per request, unless `HFENDPOINT_WORKERS` is set,
+    /// as many workers sharing the handler then processing one request at a time. Several handlers
+    /// may be provided, one per task, each of them getting its own request queue.
+    pub fn handler<H, R>(mut self, handler: H) -> Self
+    where
+        H: Handler<Request = (R, Context), Response = R::Response> + Send + Sync + 'static,
+        R: TaskRequest + Queued + Send + 'static,
+        R::Response: Queued + Send + 'static,
+    {
+        self.tasks.push(Box::new(move |runtime, task, _| {
+            let handler = Arc::new(handler);
+            let pool =
+                workers_from_env().map(|count| WorkerPool::replicate(Arc::clone(&handler), count));
+
+            let capacity = EndpointConfig::current().queue_capacity;
+            let (sender, receiver) = request_channel(capacity);
+            let router = R::router(sender);
+            mount_handler(runtime, &handler, pool.as_ref(), receiver, router, task)
+        }));
+        self
+    }
+
+    /// Serve the task of the handler `load` resolves to, i.e. once its model is loaded, the
+    /// endpoint being served meanwhile. The task is reported as `name` through `/health/ready`,
+    /// which only succeeds once every task is loaded.
+    ///
+    /// Handlers are loaded in parallel, each of them once the tasks named in `after`, given
+    /// previously through this method, are loaded: a model sharing the weights of another one is
+    /// only loaded after it. Requests received meanwhile wait in the queue of the task.
+    pub fn load<H, R, F, Fut>(mut self, name: &str, after: &[&str], load: F) -> Self
+    where
+        H: Handler<Request = (R, Context), Response = R::Response> + Send + Sync + 'static,
+        R: TaskRequest + Queued + Send + 'static,
+        R::Response: Queued + Send + 'static,
+        F: FnOnce() -> Fut + Send + 'static,
+        Fut: Future<Output = Result<H, Error>> + Send + 'static,
+    {
+        let name = String::from(name);
+        let after: Vec<_> = after.iter().map(|task| String::from(*task)).collect();
+        self.tasks.push(Box::new(move |runtime, task, startup| {
+            let (loaded, dependencies) = startup.register(&name, &after)?;
+            let capacity = EndpointConfig::current().queue_capacity;
+            let (sender, receiver) = request_channel(capacity);
+            let router = serves_routes()?.then(|| R::router(sender));
+
+            readiness().task_loading(&name);
+            let handle = runtime.clone();
+            let looper = runtime.spawn(async move {
+                if let Err(dependency) = dependencies.await {
+                    readiness().task_failed(&name, format!("{dependency} failed to load"));
+                    return Ok(());
+                }
+
+                let handler = Arc::new(
+                    load()
+                        .await
+                        .inspect_err(|err| readiness().task_failed(&name, err.to_string()))?,
+                );
+                let pool = workers_from_env()
+                    .map(|count| WorkerPool::replicate(Arc::clone(&handler), count));
+
+                // The routes of the task are already served, only its handler loops are started
+                let mount = mount_handler(
+                    &handle,
+                    &handler,
+                    pool.as_ref(),
+                    receiver,
+                    OpenApiRouter::new(),
+                    task,
+                )
+                .inspect_err(|err| readiness().task_failed(&name, err.to_string()))?;
+                readiness().task_ready(&name);
+                loaded.send_replace(true);
+                join_loopers(mount.loopers).await
+            });
+
+            Ok(Mount {
+                router,
+                loopers: vec![looper],
+            })
+        }));
+        self
+    }
+
+    /// Model advertised by the endpoint on `/models`, `/info` and in the responses
+    pub fn model(mut self, model: ModelCard) -> Self {
+        self.model = model;
+        self
+    }
+
+    /// Serve the handlers on `interface` until the process is asked to terminate, the handlers
+    /// given through [`EndpointBuilder::handler`] being reported initialized right away and the
+    /// ones given through [`EndpointBuilder::load`] once loaded, then let them complete the
+    /// requests in flight
+    pub async fn serve<A>(self, interface: A) -> OpenAiResult<()>
+    where
+        A: ToSocketAddrs + Debug + Send + 'static,
+    {
+        let runtime = Handle::current();
+        let multitask = self.tasks.len() > 1;
+        let mut startup = Startup::default();
+        let mounts = self
+            .tasks
+            .into_iter()
+            .enumerate()
+            .map(|(task, mount)| mount(&runtime, multitask.then_some(task), &mut startup))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // Handlers are created, their model loaded, before reaching the endpoint unless loaded by
+        // the endpoint itself, the tasks of the latter reporting their own progress
+        readiness().handler_initialized();
+        serve_mounts(&runtime, mounts, self.model, interface).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::embeddings::embedding::{EmbeddingRequest, EmbeddingResponse};
+    use crate::endpoint::Endpoint;
+    use crate::rerank::ranking::{RerankRequest, RerankResponse};
+    use crate::{Context, ModelCard, OpenAiError};
+    use hfendpoints_core::{Error, Handler};
+    use serde_json::{Value, json};
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::time::Duration;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::{TcpListener, TcpStream};
+    use tokio::sync::oneshot;
+
+    struct Embedder;
+
+    impl Handler for Embedder {
+        type Request = (EmbeddingRequest, Context);
+        type Response = EmbeddingResponse;
+
+        async fn on_request(&self, (request, _): Self::Request) -> Result<Self::Response, Error> {
+            let embeddings = request
+                .input
+                .iter()
+                .map(|input| vec![input.len() as f32])
+                .collect();
+            Ok(EmbeddingResponse::new(
+                String::from("embedder"),
+                embeddings,
+                0,
+            ))
+        }
+    }
+
+    struct Reranker;
+
+    impl Handler for Reranker {
+        type Request = (RerankRequest, Context);
+        type Response = RerankResponse;
+
+        async fn on_request(&self, (request, _): Self::Request) -> Result<Self::Response, Error> {
+            Ok(RerankResponse::new(vec![0.5; request.documents.len()]))
+        }
+    }
+
+    /// Port on which nothing listens yet
+    async fn free_port() -> u16 {
+        TcpListener::bind("127.0.0.1:0")
+            .await
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port()
+    }
+
+    /// Send `request` to the endpoint listening on `port` once it does, returning the head and the
+    /// body of the response
+    async fn exchange(port: u16, request: &str) -> (String, Value) {
+        let mut stream = loop {
+            match TcpStream::connect(("127.0.0.1", port)).await {
+                Ok(stream) => break stream,
+                Err(_) => tokio::time::sleep(Duration::from_millis(10)).await,
+            }
+        };
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        (String::from(head), serde_json::from_str(body).unwrap())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn serve_rust_handler() {
+        let port = free_port().await;
+        let server = tokio::spawn(
+            Endpoint::builder()
+                .handler(Embedder)
+                .model(ModelCard::new("embedder"))
+                .serve(("127.0.0.1", port)),
+        );
+
+        let body = json!({"input": ["a", "abc"]}).to_string();
+        let request = format!(
+            "POST /api/v1/embeddings HTTP/1.1\r\nHost: localhost\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+            body.len()
+        );
+
+        let (head, body) = exchange(port, &request).await;
+        assert!(head.starts_with("HTTP/1.1 200"), "{head}");
+        assert_eq!(body["data"][1]["embedding"], json!([3.0]));
+
+        server.abort();
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn load_handlers_after_their_dependencies() {
+        const READY: &str =
+            "GET /health/ready HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n";
+        let port = free_port().await;
+        let (release, released) = oneshot::channel();
+        let loading = Arc::new(AtomicBool::new(false));
+        let reranker = Arc::clone(&loading);
+        let server = tokio::spawn(
+            Endpoint::builder()
+                .load("embedder", &[], || async move {
+                    released.await.unwrap();
+                    Ok(Embedder)
+                })
+                .load("reranker", &["embedder"], move || async move {
+                    reranker.store(true, Ordering::SeqCst);
+                    Ok(Reranker)
+                })
+                .serve(("127.0.0.1", port)),
+        );
+
+        // The endpoint is served while the embedder loads, the reranker waiting for it
+        let (head, body) = exchange(port, READY).await;
+        assert!(head.starts_with("HTTP/1.1 503"), "{head}");
+        assert_eq!(
+            body["tasks"],
+            json!([
+                {"task": "embedder", "status": "loading"},
+                {"task": "reranker", "status": "loading"},
+            ])
+        );
+        assert!(!loading.load(Ordering::SeqCst));
+
+        release.send(()).unwrap();
+        let loaded = json!([
+            {"task": "embedder", "status": "ready"},
+            {"task": "reranker", "status": "ready"},
+        ]);
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while exchange(port, READY).await.1["tasks"] != loaded {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+        assert!(loading.load(Ordering::SeqCst));
+
+        server.abort();
+    }
+
+    #[tokio::test]
+    async fn load_after_unknown_task() {
+        let served = Endpoint::builder()
+            .load("reranker", &["embedder"], || async { Ok(Reranker) })
+            .serve(("127.0.0.1", free_port().await))
+            .await;
+        assert!(matches!(
+            served,
+            Err(OpenAiError::Endpoint(Error::UnknownDependency { .. }))
+        ));
+    }
+}
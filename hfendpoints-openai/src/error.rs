@@ -1,16 +1,30 @@
+use axum::Json;
+use axum::body::to_bytes;
+use axum::extract::Request;
 use axum::extract::multipart::MultipartError;
 use axum::http::StatusCode;
+use axum::http::header::{CONTENT_LENGTH, CONTENT_TYPE, RETRY_AFTER, WWW_AUTHENTICATE};
+use axum::middleware::Next;
 use axum::response::{IntoResponse, Response};
 use hfendpoints_core::Error as EndpointError;
-use std::num::ParseFloatError;
+#[cfg(feature = "jobs")]
+use hfendpoints_core::JobError;
+use hfendpoints_core::latency_statistics;
+use hfendpoints_schemas::SchemaError;
+use serde::{Deserialize, Serialize};
+use std::convert::Infallible;
+use std::num::{ParseFloatError, ParseIntError};
+use std::str::ParseBoolError;
+use std::time::Duration;
 use thiserror::Error;
 use tokio::io::Error as TokioIoError;
+use utoipa::{IntoResponses, ToSchema};
 
 /// Define all the possible errors for OpenAI Compatible Endpoint
 #[derive(Debug, Error)]
 pub enum OpenAiError {
     #[error("Endpoint error: {0}")]
-    Endpoint(#[from] EndpointError),
+    Endpoint(EndpointError),
 
     #[error("I/O Error occured: {0}")]
     Io(#[from] TokioIoError),
@@ -21,8 +35,118 @@ pub enum OpenAiError {
     #[error("Validation failed: {0}")]
     Validation(String),
 
+    #[error("Not found: {0}")]
+    NotFound(String),
+
+    #[error("Conflict: {0}")]
+    Conflict(String),
+
+    #[error("Request body{} exceeds the maximum allowed size of {limit} bytes", describe_size(.received))]
+    PayloadTooLarge { limit: usize, received: Option<u64> },
+
+    #[error(
+        "The endpoint is processing too many requests ({capacity} pending), please retry later"
+    )]
+    QueueFull { capacity: usize },
+
+    #[error("The request did not complete within {after:?}")]
+    Timeout { after: Duration },
+
     #[error("No response was returned by the inference engine")]
     NoResponse,
+
+    #[error("The handler raised {kind}: {message}")]
+    HandlerException { kind: String, message: String },
+
+    #[error("The handler returned an invalid response: {0}")]
+    InvalidResponse(String),
+
+    #[error("Failed to decrypt the request payload: {0}")]
+    Decryption(String),
+
+    #[error("{0}")]
+    Unauthorized(String),
+
+    #[error("Invalid TLS configuration: {0}")]
+    Tls(String),
+
+    #[error("Invalid endpoint snapshot: {0}")]
+    Snapshot(String),
+
+    #[error("Invalid endpoint configuration: {0}")]
+    Config(String),
+}
+
+/// Bounds of the seconds clients are asked to wait before retrying a request rejected because
+/// the queue is full
+const MIN_RETRY_AFTER: u64 = 1;
+const MAX_RETRY_AFTER: u64 = 60;
+
+/// Seconds the `depth` queued requests take to be served at the recent service time of the
+/// endpoint, so clients back off proportionally to the load, see `LatencyStatistics::service_time`
+fn retry_after(depth: usize) -> u64 {
+    drain_time(latency_statistics().service_time(), depth)
+}
+
+/// Seconds `depth` requests take to be served every `service_time`, within the bounds
+fn drain_time(service_time: Option<Duration>, depth: usize) -> u64 {
+    service_time
+        .map(|service_time| (service_time.as_secs_f64() * depth as f64).ceil() as u64)
+        .unwrap_or(MIN_RETRY_AFTER)
+        .clamp(MIN_RETRY_AFTER, MAX_RETRY_AFTER)
+}
+
+impl OpenAiError {
+    /// Turn failures caused by reading past the body limit into a descriptive `PayloadTooLarge`
+    pub(crate) fn with_body_limit(self, limit: usize, received: Option<u64>) -> Self {
+        match self {
+            Self::Multipart(err) if err.status() == StatusCode::PAYLOAD_TOO_LARGE => {
+                Self::PayloadTooLarge { limit, received }
+            }
+            err => err,
+        }
+    }
+}
+
+#[inline]
+fn describe_size(received: &Option<u64>) -> String {
+    received
+        .map(|size| format!(" of {size} bytes"))
+        .unwrap_or_default()
+}
+
+impl From<EndpointError> for OpenAiError {
+    #[inline]
+    fn from(value: EndpointError) -> Self {
+        match value {
+            EndpointError::NoResponse => Self::NoResponse,
+            EndpointError::QueueFull { capacity } => Self::QueueFull { capacity },
+            EndpointError::Timeout { after } => Self::Timeout { after },
+            EndpointError::HandlerException { kind, message, .. } => {
+                Self::HandlerException { kind, message }
+            }
+            err => Self::Endpoint(err),
+        }
+    }
+}
+
+#[cfg(feature = "jobs")]
+impl From<JobError> for OpenAiError {
+    #[inline]
+    fn from(value: JobError) -> Self {
+        match value {
+            JobError::NotFound(id) => Self::NotFound(format!("The batch '{id}' does not exist")),
+            JobError::Io(err) => Self::Io(err),
+            JobError::Malformed(err) => Self::Io(TokioIoError::other(err)),
+        }
+    }
+}
+
+impl From<Infallible> for OpenAiError {
+    #[inline]
+    fn from(value: Infallible) -> Self {
+        match value {}
+    }
 }
 
 impl From<ParseFloatError> for OpenAiError {
@@ -32,19 +156,422 @@ impl From<ParseFloatError> for OpenAiError {
     }
 }
 
+impl From<ParseIntError> for OpenAiError {
+    #[inline]
+    fn from(value: ParseIntError) -> Self {
+        Self::Validation(value.to_string())
+    }
+}
+
+impl From<SchemaError> for OpenAiError {
+    #[inline]
+    fn from(value: SchemaError) -> Self {
+        Self::Validation(value.to_string())
+    }
+}
+
+impl From<ParseBoolError> for OpenAiError {
+    #[inline]
+    fn from(value: ParseBoolError) -> Self {
+        Self::Validation(value.to_string())
+    }
+}
+
+/// Error body following the OpenAI format, as parsed by the SDKs
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct ErrorResponse {
+    pub error: ErrorDetail,
+}
+
+/// Details of the failure, see [`ErrorResponse`]
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+pub struct ErrorDetail {
+    /// Human-readable description of the failure
+    pub message: String,
+
+    /// Category of the failure, i.e. `invalid_request_error` or `server_error`
+    #[serde(rename = "type")]
+    pub kind: String,
+
+    /// Parameter of the request which caused the failure, if any
+    pub param: Option<String>,
+
+    /// Machine-readable identifier of the failure, i.e. `invalid_api_key`
+    pub code: Option<String>,
+}
+
+impl ErrorResponse {
+    pub fn new(message: impl Into<String>, kind: &str, code: &str) -> Self {
+        Self {
+            error: ErrorDetail {
+                message: message.into(),
+                kind: String::from(kind),
+                param: None,
+                code: Some(String::from(code)),
+            },
+        }
+    }
+
+    /// Error body for a response of `status` produced without any, i.e. by the routing itself
+    pub(crate) fn from_status(status: StatusCode, message: Option<String>) -> Self {
+        let code = match status {
+            StatusCode::NOT_FOUND => "not_found",
+            StatusCode::METHOD_NOT_ALLOWED => "method_not_allowed",
+            StatusCode::PAYLOAD_TOO_LARGE => "payload_too_large",
+            StatusCode::UNSUPPORTED_MEDIA_TYPE => "unsupported_media_type",
+            StatusCode::UNAUTHORIZED => "invalid_api_key",
+            StatusCode::TOO_MANY_REQUESTS => "rate_limit_exceeded",
+            StatusCode::SERVICE_UNAVAILABLE => "service_unavailable",
+            StatusCode::GATEWAY_TIMEOUT | StatusCode::REQUEST_TIMEOUT => "timeout",
+            status if status.is_server_error() => "internal_error",
+            _ => "invalid_value",
+        };
+        let kind = if status.is_server_error() {
+            "server_error"
+        } else {
+            "invalid_request_error"
+        };
+        let message = message
+            .filter(|message| !message.trim().is_empty())
+            .or_else(|| status.canonical_reason().map(String::from))
+            .unwrap_or_else(|| status.to_string());
+        Self::new(message.trim(), kind, code)
+    }
+}
+
+/// Failures the task routes may answer, documented along with their error body
+#[derive(IntoResponses)]
+#[allow(dead_code)]
+pub(crate) enum TaskErrors {
+    #[response(status = BAD_REQUEST, description = "The request is malformed or holds invalid parameters.")]
+    BadRequest(#[to_schema] ErrorResponse),
+
+    #[response(status = UNAUTHORIZED, description = "The request does not carry a valid API key or token.")]
+    Unauthorized(#[to_schema] ErrorResponse),
+
+    #[response(status = PAYLOAD_TOO_LARGE, description = "The request body exceeds the maximum allowed size.")]
+    PayloadTooLarge(#[to_schema] ErrorResponse),
+
+    #[response(status = TOO_MANY_REQUESTS, description = "The endpoint is processing too many requests, retry after the `Retry-After` delay.")]
+    QueueFull(#[to_schema] ErrorResponse),
+
+    #[response(status = INTERNAL_SERVER_ERROR, description = "The handler failed to process the request.")]
+    Internal(#[to_schema] ErrorResponse),
+
+    #[response(status = SERVICE_UNAVAILABLE, description = "The handler is not running anymore.")]
+    Unavailable(#[to_schema] ErrorResponse),
+
+    #[response(status = GATEWAY_TIMEOUT, description = "The request did not complete within the configured timeout.")]
+    Timeout(#[to_schema] ErrorResponse),
+}
+
+/// Rejected requests follow the OpenAI rate limit error so SDKs back off and retry
+fn queue_full(message: String, depth: usize) -> Response {
+    (
+        StatusCode::TOO_MANY_REQUESTS,
+        [(RETRY_AFTER, retry_after(depth).to_string())],
+        Json(ErrorResponse::new(
+            message,
+            "rate_limit_exceeded",
+            "queue_full",
+        )),
+    )
+        .into_response()
+}
+
 impl IntoResponse for OpenAiError {
     fn into_response(self) -> Response {
-        let (status, body) = match self {
-            Self::Endpoint(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
-            Self::Io(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
-            Self::Multipart(e) => (StatusCode::BAD_REQUEST, e.to_string()),
-            Self::Validation(e) => (StatusCode::FORBIDDEN, e),
+        let message = self.to_string();
+        let (status, kind, code) = match self {
+            Self::Endpoint(EndpointError::HandlerTerminated) => (
+                StatusCode::SERVICE_UNAVAILABLE,
+                "server_error",
+                "handler_terminated",
+            ),
+            Self::Endpoint(_) | Self::Io(_) => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "server_error",
+                "internal_error",
+            ),
+            Self::Multipart(_) => (
+                StatusCode::BAD_REQUEST,
+                "invalid_request_error",
+                "invalid_multipart",
+            ),
+            Self::Validation(message) => {
+                let body = ErrorResponse::new(message, "invalid_request_error", "invalid_value");
+                return (StatusCode::BAD_REQUEST, Json(body)).into_response();
+            }
+            Self::NotFound(message) => {
+                let body = ErrorResponse::new(message, "invalid_request_error", "not_found");
+                return (StatusCode::NOT_FOUND, Json(body)).into_response();
+            }
+            Self::Conflict(message) => {
+                let body = ErrorResponse::new(message, "invalid_request_error", "conflict");
+                return (StatusCode::CONFLICT, Json(body)).into_response();
+            }
+            Self::PayloadTooLarge { .. } => (
+                StatusCode::PAYLOAD_TOO_LARGE,
+                "invalid_request_error",
+                "payload_too_large",
+            ),
+            Self::QueueFull { capacity } => return queue_full(message, capacity),
+            Self::Timeout { .. } => (StatusCode::GATEWAY_TIMEOUT, "server_error", "timeout"),
             Self::NoResponse => (
                 StatusCode::INTERNAL_SERVER_ERROR,
-                String::from("No response returned by the inference engine"),
+                "server_error",
+                "no_response",
+            ),
+            // The traceback stays in the server logs, only the exception is reported to the client
+            Self::HandlerException { .. } => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "server_error",
+                "handler_exception",
+            ),
+            Self::InvalidResponse(_) => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "server_error",
+                "invalid_response",
+            ),
+            Self::Decryption(_) => (
+                StatusCode::BAD_REQUEST,
+                "invalid_request_error",
+                "invalid_encryption",
+            ),
+            Self::Unauthorized(message) => {
+                let body = ErrorResponse::new(message, "invalid_request_error", "invalid_api_key");
+                return (
+                    StatusCode::UNAUTHORIZED,
+                    [(WWW_AUTHENTICATE, "Bearer")],
+                    Json(body),
+                )
+                    .into_response();
+            }
+            Self::Tls(_) | Self::Snapshot(_) | Self::Config(_) => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "server_error",
+                "invalid_configuration",
+            ),
+        };
+
+        (status, Json(ErrorResponse::new(message, kind, code))).into_response()
+    }
+}
+
+/// Size above which the bodies of the failed responses are not rewritten
+const MAX_ERROR_MESSAGE: usize = 64 * 1024;
+
+/// Middleware turning the failures answered without an OpenAI error body, by the routing (404,
+/// 405), the body limit (413) or the extractors rejecting a request (400, 415, 422), into one
+/// holding their message, so SDKs report them rather than failing to parse them
+pub(crate) async fn openai_errors(request: Request, next: Next) -> Response {
+    let response = next.run(request).await;
+    let status = response.status();
+    let has_body = response.headers().contains_key(CONTENT_TYPE);
+    let is_text = response
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|content_type| content_type.to_str().ok())
+        .is_some_and(|content_type| content_type.starts_with("text/plain"));
+    if !(status.is_client_error() || status.is_server_error()) || (has_body && !is_text) {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let message = match to_bytes(body, MAX_ERROR_MESSAGE).await {
+        Ok(message) => String::from_utf8_lossy(&message).into_owned(),
+        Err(_) => String::new(),
+    };
+    parts.headers.remove(CONTENT_TYPE);
+    parts.headers.remove(CONTENT_LENGTH);
+    let body = Json(ErrorResponse::from_status(status, Some(message)));
+    (parts, body).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::audio::transcription::TranscriptionResponse;
+    use crate::audio::translation::{__path_translate, TranslationRequest, translate};
+    use crate::context::Context;
+    use crate::error::{ErrorResponse, OpenAiError, drain_time, openai_errors};
+    use axum::body::{Body, to_bytes};
+    use axum::http::header::{CONTENT_TYPE, RETRY_AFTER};
+    use axum::http::{Request, StatusCode};
+    use axum::middleware::from_fn;
+    use axum::response::{IntoResponse, Response};
+    use axum::routing::post;
+    use axum::{Json, Router};
+    use hfendpoints_core::{EndpointContext, Error as EndpointError};
+    use serde_json::Value;
+    use std::time::Duration;
+    use tower::ServiceExt;
+    use utoipa::openapi::RefOr;
+    use utoipa_axum::router::OpenApiRouter;
+    use utoipa_axum::routes;
+
+    async fn error_body(response: Response) -> ErrorResponse {
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    #[tokio::test]
+    async fn every_error_carries_an_openai_body() {
+        for (err, status, kind, code) in [
+            (
+                OpenAiError::Validation(String::from("'n' must be positive")),
+                StatusCode::BAD_REQUEST,
+                "invalid_request_error",
+                "invalid_value",
+            ),
+            (
+                OpenAiError::PayloadTooLarge {
+                    limit: 16,
+                    received: Some(32),
+                },
+                StatusCode::PAYLOAD_TOO_LARGE,
+                "invalid_request_error",
+                "payload_too_large",
+            ),
+            (
+                OpenAiError::Timeout {
+                    after: Duration::from_secs(30),
+                },
+                StatusCode::GATEWAY_TIMEOUT,
+                "server_error",
+                "timeout",
+            ),
+            (
+                OpenAiError::QueueFull { capacity: 8 },
+                StatusCode::TOO_MANY_REQUESTS,
+                "rate_limit_exceeded",
+                "queue_full",
+            ),
+            (
+                OpenAiError::Endpoint(EndpointError::HandlerTerminated),
+                StatusCode::SERVICE_UNAVAILABLE,
+                "server_error",
+                "handler_terminated",
+            ),
+            (
+                OpenAiError::NoResponse,
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "server_error",
+                "no_response",
+            ),
+            (
+                OpenAiError::NotFound(String::from("No stream to resume")),
+                StatusCode::NOT_FOUND,
+                "invalid_request_error",
+                "not_found",
             ),
+            (
+                OpenAiError::Conflict(String::from("A stream is already running")),
+                StatusCode::CONFLICT,
+                "invalid_request_error",
+                "conflict",
+            ),
+        ] {
+            let message = err.to_string();
+            let response = err.into_response();
+            assert_eq!(response.status(), status);
+            assert_eq!(response.headers()[CONTENT_TYPE], "application/json");
+            let retry_after = response.headers().get(RETRY_AFTER).cloned();
+
+            let body = error_body(response).await;
+            assert!(message.contains(&body.error.message), "{message}");
+            assert_eq!(body.error.kind, kind);
+            assert_eq!(body.error.param, None);
+            assert_eq!(body.error.code.as_deref(), Some(code));
+            assert_eq!(
+                retry_after.is_some(),
+                status == StatusCode::TOO_MANY_REQUESTS
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn rewrite_plain_failures() {
+        let router = Router::new()
+            .route(
+                "/echo",
+                post(|Json(body): Json<Value>| async { Json(body) }),
+            )
+            .layer(from_fn(openai_errors));
+
+        for (content_type, body, status, code) in [
+            (
+                "text/plain",
+                "{}",
+                StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                "unsupported_media_type",
+            ),
+            (
+                "application/json",
+                "{",
+                StatusCode::BAD_REQUEST,
+                "invalid_value",
+            ),
+        ] {
+            let request = Request::post("/echo")
+                .header(CONTENT_TYPE, content_type)
+                .body(Body::from(body))
+                .unwrap();
+            let response = router.clone().oneshot(request).await.unwrap();
+            assert_eq!(response.status(), status);
+
+            let body = error_body(response).await;
+            assert_eq!(body.error.kind, "invalid_request_error");
+            assert_eq!(body.error.code.as_deref(), Some(code));
+            assert!(!body.error.message.is_empty());
+        }
+
+        // Successful responses are left untouched
+        let request = Request::post("/echo")
+            .header(CONTENT_TYPE, "application/json")
+            .body(Body::from(r#"{"a":1}"#))
+            .unwrap();
+        let response = router.oneshot(request).await.unwrap();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(&body[..], br#"{"a":1}"#);
+    }
+
+    #[test]
+    fn document_error_responses() {
+        let router = OpenApiRouter::<
+            EndpointContext<(TranslationRequest, Context), TranscriptionResponse>,
+        >::new()
+        .routes(routes!(translate));
+        let openapi = router.get_openapi();
+        let operation = openapi.paths.paths["/audio/translations"]
+            .post
+            .as_ref()
+            .unwrap();
+
+        for status in ["400", "401", "413", "429", "500", "503", "504"] {
+            let Some(RefOr::T(response)) = operation.responses.responses.get(status) else {
+                panic!("{status} is not documented");
+            };
+            assert!(
+                response.content.contains_key("application/json"),
+                "{status}"
+            );
+        }
+
+        // Responses specific to the route take precedence over the generic ones
+        let Some(RefOr::T(too_large)) = operation.responses.responses.get("413") else {
+            unreachable!()
         };
+        assert!(too_large.description.contains("once decompressed"));
+    }
+
+    #[test]
+    fn retry_after_queue_drained() {
+        // No request completed yet
+        assert_eq!(drain_time(None, 128), 1);
 
-        (status, body).into_response()
+        // 128 requests served every 250ms take 32s to drain
+        assert_eq!(drain_time(Some(Duration::from_millis(250)), 128), 32);
+        assert_eq!(drain_time(Some(Duration::from_millis(10)), 8), 1);
+        assert_eq!(drain_time(Some(Duration::from_secs(2)), 128), 60);
     }
 }
@@ -0,0 +1,253 @@
+//! Estimation of the latency of a request before submitting it.
+//!
+//! Routes record how long their requests took along with the amount of work they carried, clients
+//! describe a prospective request to `/estimate` (audio duration, token counts) and get back the
+//! latency expected from the recent requests, letting them budget or pick another endpoint before
+//! submitting heavy jobs.
+use crate::{ErrorResponse, OpenAiError, OpenAiResult};
+use axum::Json;
+use hfendpoints_core::latency_statistics;
+use serde::{Deserialize, Serialize};
+use std::time::Instant;
+use tracing::instrument;
+use utoipa::ToSchema;
+use utoipa_axum::router::OpenApiRouter;
+use utoipa_axum::routes;
+
+pub const ESTIMATE_TAG: &str = "Estimate";
+pub const ESTIMATE_DESC: &str = "Estimate the latency of a request before submitting it.";
+
+/// Tasks whose latency can be estimated
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Clone, Copy, PartialEq, Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum EstimatedTask {
+    /// `/chat/completions`, the work being the prompt and completion tokens
+    ChatCompletion,
+
+    /// `/embeddings`, the work being the input tokens
+    Embedding,
+
+    /// `/audio/transcriptions`, the work being the seconds of audio
+    Transcription,
+
+    /// `/audio/translations`, the work being the seconds of audio
+    Translation,
+}
+
+impl EstimatedTask {
+    fn name(self) -> &'static str {
+        match self {
+            Self::ChatCompletion => "chat_completion",
+            Self::Embedding => "embedding",
+            Self::Transcription => "transcription",
+            Self::Translation => "translation",
+        }
+    }
+}
+
+/// Account for a request of `task` carrying `units` of work, submitted at `started`
+pub(crate) fn record_latency(task: EstimatedTask, units: f64, started: Instant) {
+    latency_statistics().record(task.name(), units, started.elapsed());
+}
+
+/// Describes a prospective request.
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Clone, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+struct EstimateRequest {
+    /// The task targeted by the request.
+    task: EstimatedTask,
+
+    /// The duration of the audio, in seconds, required for transcriptions and translations.
+    audio_duration: Option<f64>,
+
+    /// The number of tokens of the input, required for embeddings and chat completions.
+    prompt_tokens: Option<u32>,
+
+    /// The number of tokens expected to be generated, i.e. `max_completion_tokens`, for chat completions.
+    completion_tokens: Option<u32>,
+}
+
+/// Amount of work carried by the request.
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Clone, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+struct EstimatedUsage {
+    /// The duration of the audio, in seconds.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    audio_duration: Option<f64>,
+
+    /// The number of tokens of the input.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    prompt_tokens: Option<u32>,
+
+    /// The number of tokens expected to be generated.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    completion_tokens: Option<u32>,
+
+    /// The total number of tokens of the request.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    total_tokens: Option<u32>,
+}
+
+/// Latency expected for the request, from the requests recently completed by the endpoint.
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Clone, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+struct EstimateResponse {
+    /// The object type, which is always "estimate".
+    object: &'static str,
+
+    /// The task targeted by the request.
+    task: EstimatedTask,
+
+    /// The expected time, in seconds, between submitting the request and receiving its response.
+    /// Null until enough requests of the task completed.
+    latency: Option<f64>,
+
+    /// The amount of work carried by the request.
+    usage: EstimatedUsage,
+
+    /// The number of completed requests the estimate derives from.
+    samples: u64,
+}
+
+impl EstimateRequest {
+    /// Units of work carried by the request, along with its usage
+    fn usage(&self) -> OpenAiResult<(f64, EstimatedUsage)> {
+        let missing = |field: &str| {
+            OpenAiError::Validation(format!(
+                "Parameter '{field}' is required to estimate a {} request",
+                self.task.name()
+            ))
+        };
+
+        match self.task {
+            EstimatedTask::Transcription | EstimatedTask::Translation => {
+                let duration = self
+                    .audio_duration
+                    .ok_or_else(|| missing("audio_duration"))?;
+                if !duration.is_finite() || duration < 0.0 {
+                    return Err(OpenAiError::Validation(String::from(
+                        "Parameter 'audio_duration' must be a positive number of seconds",
+                    )));
+                }
+
+                let usage = EstimatedUsage {
+                    audio_duration: Some(duration),
+                    prompt_tokens: None,
+                    completion_tokens: None,
+                    total_tokens: None,
+                };
+                Ok((duration, usage))
+            }
+            EstimatedTask::Embedding => {
+                let tokens = self.prompt_tokens.ok_or_else(|| missing("prompt_tokens"))?;
+                let usage = EstimatedUsage {
+                    audio_duration: None,
+                    prompt_tokens: Some(tokens),
+                    completion_tokens: None,
+                    total_tokens: Some(tokens),
+                };
+                Ok((tokens as f64, usage))
+            }
+            EstimatedTask::ChatCompletion => {
+                let prompt = self.prompt_tokens.ok_or_else(|| missing("prompt_tokens"))?;
+                let completion = self.completion_tokens.unwrap_or_default();
+                let total = prompt.saturating_add(completion);
+                let usage = EstimatedUsage {
+                    audio_duration: None,
+                    prompt_tokens: Some(prompt),
+                    completion_tokens: Some(completion),
+                    total_tokens: Some(total),
+                };
+                Ok((total as f64, usage))
+            }
+        }
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/estimate",
+    tag = ESTIMATE_TAG,
+    request_body(content = EstimateRequest, content_type = "application/json"),
+    responses(
+        (status = OK, description = "Estimates the latency of a request from the requests recently completed.", body = EstimateResponse),
+        (status = BAD_REQUEST, description = "The request is malformed or holds invalid parameters.", body = ErrorResponse),
+    )
+)]
+#[instrument(skip(request))]
+async fn estimate(Json(request): Json<EstimateRequest>) -> OpenAiResult<Json<EstimateResponse>> {
+    let (units, usage) = request.usage()?;
+    let estimate = latency_statistics().estimate(request.task.name(), units);
+
+    Ok(Json(EstimateResponse {
+        object: "estimate",
+        task: request.task,
+        latency: estimate.map(|estimate| estimate.latency.as_secs_f64()),
+        usage,
+        samples: estimate
+            .map(|estimate| estimate.samples)
+            .unwrap_or_default(),
+    }))
+}
+
+/// Route estimating the latency of prospective requests, estimates being available once
+/// `MIN_ESTIMATION_SAMPLES` requests of the task completed
+pub(crate) fn router() -> OpenApiRouter {
+    OpenApiRouter::new().routes(routes!(estimate))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::estimate::{EstimatedTask, record_latency, router};
+    use axum::body::{Body, to_bytes};
+    use axum::http::header::CONTENT_TYPE;
+    use axum::http::{Request, StatusCode};
+    use serde_json::{Value, json};
+    use std::time::Instant;
+    use tower::ServiceExt;
+
+    async fn post(body: Value) -> (StatusCode, Value) {
+        let (router, _) = router().split_for_parts();
+        let request = Request::post("/estimate")
+            .header(CONTENT_TYPE, "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap();
+
+        let response = router.oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&body).unwrap_or_default())
+    }
+
+    #[tokio::test]
+    async fn estimate_once_requests_completed() {
+        let request = json!({"task": "translation", "audio_duration": 30.0});
+        let (status, body) = post(request.clone()).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["latency"], Value::Null);
+        assert_eq!(body["usage"], json!({"audio_duration": 30.0}));
+
+        for _ in 0..3 {
+            record_latency(EstimatedTask::Translation, 10.0, Instant::now());
+        }
+        let (_, body) = post(request).await;
+        assert!(body["latency"].is_f64());
+        assert_eq!(body["samples"], 3);
+    }
+
+    #[tokio::test]
+    async fn estimate_requires_the_work_of_the_task() {
+        let (status, _) = post(json!({"task": "chat_completion", "audio_duration": 3.0})).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let (status, body) =
+            post(json!({"task": "chat_completion", "prompt_tokens": 12, "completion_tokens": 100}))
+                .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["usage"]["total_tokens"], 112);
+    }
+}
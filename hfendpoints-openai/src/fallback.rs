@@ -0,0 +1,296 @@
+//! Fallback of the task routes on a secondary handler.
+//!
+//! Endpoints may be given a fallback handler, usually running a smaller model, on which the
+//! requests are retried when the primary handler fails them, or does not start answering them
+//! within the configured delay. Responses carry the `x-served-by` header, holding the identifier
+//! of the model which served them. Streamed responses are only retried until their first event,
+//! which the streaming routes await before answering, the header being accurate for them too.
+//!
+//! A primary handler given up on, for being too slow or failing, is cancelled before the request
+//! is retried: it runs with a child of the cancellation token of the request, cancelled when
+//! falling back, handlers checking `Context::is_cancelled` stop working on an outcome nobody awaits
+//! anymore. The fallback keeps the token of the request, cancelled when the client goes away.
+use crate::Context;
+use axum::extract::{Request, State};
+use axum::http::{HeaderName, HeaderValue};
+use axum::middleware::Next;
+use axum::response::Response;
+use hfendpoints_core::{
+    Error, RESPONSE_CHANNEL_CAPACITY, RequestReceiver, RequestSender, ResponseSender,
+    request_channel,
+};
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+use tokio::sync::mpsc::channel;
+use tokio::sync::mpsc::error::TrySendError;
+use tokio::time::timeout;
+use tracing::{debug, warn};
+
+/// Header carrying the identifier of the model which served the request
+pub const X_SERVED_BY: HeaderName = HeaderName::from_static("x-served-by");
+
+/// Identifier of the fallback model, once a request fell back on it
+pub(crate) type ServedBy = Arc<OnceLock<String>>;
+
+tokio::task_local! {
+    /// Model which served the request handled by the current task, when not the primary one
+    static SERVED_BY: ServedBy;
+}
+
+/// Slot of the request handled by the current task, detached when not served by a task route
+pub(crate) fn served_by() -> ServedBy {
+    SERVED_BY.try_with(Arc::clone).unwrap_or_default()
+}
+
+/// Middleware annotating the responses of the task routes with the model which served them,
+/// `model` unless the request fell back on another one
+pub(crate) async fn annotate_served_by(
+    State(model): State<Arc<str>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let served_by = ServedBy::default();
+    let mut response = SERVED_BY
+        .scope(Arc::clone(&served_by), next.run(request))
+        .await;
+
+    let model = served_by.get().map(String::as_str).unwrap_or(&model);
+    if let Ok(value) = HeaderValue::from_str(model) {
+        response.headers_mut().insert(X_SERVED_BY, value);
+    }
+    response
+}
+
+/// Handler the requests are retried on when the primary one fails
+#[derive(Clone, Debug)]
+pub struct Fallback {
+    model: String,
+    after: Option<Duration>,
+}
+
+impl Fallback {
+    /// Retry the requests failed by the primary handler on the one serving `model`
+    pub fn new(model: impl Into<String>) -> Self {
+        Self {
+            model: model.into(),
+            after: None,
+        }
+    }
+
+    /// Retry as well the requests the primary handler did not start answering within `after`
+    pub fn after(mut self, after: Duration) -> Self {
+        self.after = Some(after);
+        self
+    }
+
+    /// Identifier of the model served by the fallback handler
+    pub fn model(&self) -> &str {
+        &self.model
+    }
+
+    /// Sender scheduling the requests on `primary`, retried on `fallback` when failing, along
+    /// with the future forwarding them, to be spawned on the runtime. The future completes once
+    /// the returned sender is dropped, the requests in flight keeping the handlers' senders alive.
+    pub fn route<R, O>(
+        self,
+        primary: RequestSender<(R, Context), O>,
+        fallback: RequestSender<(R, Context), O>,
+    ) -> (
+        RequestSender<(R, Context), O>,
+        impl Future<Output = ()> + Send + 'static,
+    )
+    where
+        R: Clone + Send + 'static,
+        O: Send + 'static,
+    {
+        let (sender, receiver) = request_channel(primary.max_capacity());
+        let forwarding = forward_all(receiver, primary, fallback, Arc::new(self));
+        (sender, forwarding)
+    }
+}
+
+/// Forward every request received through `receiver`, each one on its own task
+async fn forward_all<R, O>(
+    mut receiver: RequestReceiver<(R, Context), O>,
+    primary: RequestSender<(R, Context), O>,
+    fallback: RequestSender<(R, Context), O>,
+    spec: Arc<Fallback>,
+) where
+    R: Clone + Send + 'static,
+    O: Send + 'static,
+{
+    while let Some((request, egress)) = receiver.recv().await {
+        tokio::spawn(forward(
+            request,
+            egress,
+            primary.clone(),
+            fallback.clone(),
+            Arc::clone(&spec),
+        ));
+    }
+    debug!("Transport went away, stop forwarding requests");
+}
+
+/// Schedule `request` on `primary`, piping its responses to `egress`, unless it fails before
+/// producing the first one, the request being scheduled on `fallback` instead
+async fn forward<R, O>(
+    (request, ctx): (R, Context),
+    egress: ResponseSender<O>,
+    primary: RequestSender<(R, Context), O>,
+    fallback: RequestSender<(R, Context), O>,
+    spec: Arc<Fallback>,
+) where
+    R: Clone,
+{
+    let (sender, mut responses) = channel(RESPONSE_CHANNEL_CAPACITY);
+    let attempt = ctx.with_child_cancellation();
+    let cancellation = attempt.cancellation().clone();
+    let (err, dequeued) = match primary.try_send(((request.clone(), attempt), sender)) {
+        Ok(()) => {
+            let first = match spec.after {
+                Some(after) => timeout(after, responses.recv())
+                    .await
+                    .unwrap_or(Some(Err(Error::Timeout { after }))),
+                None => responses.recv().await,
+            };
+
+            match first {
+                Some(Ok(response)) => {
+                    if egress.send(Ok(response)).await.is_ok() {
+                        while let Some(response) = responses.recv().await {
+                            if egress.send(response).await.is_err() {
+                                break;
+                            }
+                        }
+                    }
+                    return;
+                }
+                Some(Err(err)) => (err, true),
+                None => (Error::NoResponse, true),
+            }
+        }
+        Err(TrySendError::Full(_)) => (
+            Error::QueueFull {
+                capacity: primary.max_capacity(),
+            },
+            false,
+        ),
+        Err(TrySendError::Closed(_)) => (Error::HandlerTerminated, false),
+    };
+
+    // The primary handler is given up on, its late responses being discarded
+    cancellation.cancel();
+    drop(responses);
+    if egress.is_closed() {
+        debug!("Client went away, not falling back on {}", spec.model);
+        return;
+    }
+
+    let permit = match fallback.try_reserve() {
+        Ok(permit) => permit,
+        Err(TrySendError::Full(())) => {
+            warn!("Cannot fall back on {}: the queue is full", spec.model);
+            let _ = egress
+                .send(Err(Error::QueueFull {
+                    capacity: fallback.max_capacity(),
+                }))
+                .await;
+            return;
+        }
+        Err(TrySendError::Closed(())) => {
+            warn!(
+                "Cannot fall back on {}: its handler is not running anymore",
+                spec.model
+            );
+            let _ = egress.send(Err(err)).await;
+            return;
+        }
+    };
+
+    warn!("Primary handler failed ({err}), retrying on {}", spec.model);
+    let _ = ctx.served_by().set(spec.model.clone());
+    permit.send(((request, ctx), egress));
+
+    // Requests the primary handler picked are scheduled once more, see `EndpointContext::schedule`
+    if dequeued {
+        hfendpoints_core::readiness().on_scheduled();
+        #[cfg(feature = "metrics")]
+        hfendpoints_core::endpoint_metrics().on_scheduled();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Context;
+    use crate::fallback::{Fallback, SERVED_BY, ServedBy};
+    use crate::headers::RequestId;
+    use hfendpoints_core::{Error, RESPONSE_CHANNEL_CAPACITY, RequestReceiver, request_channel};
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::time::Duration;
+    use tokio::sync::mpsc::channel;
+
+    /// Answer the requests tagged with the name of the `model`, failing the ones asking to, and
+    /// flagging `cancelled` when the slow ones are cancelled
+    async fn serve(
+        model: &'static str,
+        mut receiver: RequestReceiver<(&'static str, Context), String>,
+        cancelled: Arc<AtomicBool>,
+    ) {
+        while let Some(((request, ctx), egress)) = receiver.recv().await {
+            let cancelled = Arc::clone(&cancelled);
+            tokio::spawn(async move {
+                let response = match request {
+                    "fail" if model == "primary" => Err(Error::NoResponse),
+                    "slow" if model == "primary" => {
+                        tokio::select! {
+                            _ = ctx.cancellation().cancelled() => {
+                                cancelled.store(true, Ordering::SeqCst);
+                                return;
+                            }
+                            _ = tokio::time::sleep(Duration::from_secs(5)) => {}
+                        }
+                        Ok(format!("{model}: {request}"))
+                    }
+                    request => Ok(format!("{model}: {request}")),
+                };
+                let _ = egress.send(response).await;
+            });
+        }
+    }
+
+    #[tokio::test]
+    async fn failed_requests_are_retried_on_the_fallback() {
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let (primary, receiver) = request_channel(4);
+        tokio::spawn(serve("primary", receiver, Arc::clone(&cancelled)));
+        let (fallback, receiver) = request_channel(4);
+        tokio::spawn(serve("fallback", receiver, Arc::default()));
+
+        let (sender, forwarding) = Fallback::new("fallback")
+            .after(Duration::from_millis(100))
+            .route(primary, fallback);
+        tokio::spawn(forwarding);
+
+        for (request, expected, served_by) in [
+            ("ok", "primary: ok", None),
+            ("fail", "fallback: fail", Some("fallback")),
+            ("slow", "fallback: slow", Some("fallback")),
+        ] {
+            let slot = ServedBy::default();
+            let ctx = SERVED_BY.scope(Arc::clone(&slot), async {
+                Context::new(RequestId::from(String::from(request)))
+            });
+
+            let ctx = ctx.await;
+            let (egress, mut responses) = channel(RESPONSE_CHANNEL_CAPACITY);
+            sender.send(((request, ctx.clone()), egress)).await.unwrap();
+            assert_eq!(responses.recv().await.unwrap().unwrap(), expected);
+            assert_eq!(slot.get().map(String::as_str), served_by);
+
+            // Only the attempt on the primary handler is cancelled, not the request
+            assert!(!ctx.is_cancelled());
+        }
+        assert!(cancelled.load(Ordering::SeqCst));
+    }
+}
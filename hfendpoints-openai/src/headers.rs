@@ -23,6 +23,12 @@ impl Deref for RequestId {
     }
 }
 
+impl From<String> for RequestId {
+    fn from(value: String) -> Self {
+        RequestId(Cow::Owned(value))
+    }
+}
+
 impl Header for RequestId {
     fn name() -> &'static HeaderName {
         &X_REQUEST_ID_NAME
@@ -47,4 +53,39 @@ impl Header for RequestId {
         let value = HeaderValue::from_str(&self.0).unwrap();
         values.extend(std::iter::once(value));
     }
-}
\ No newline at end of file
+}
+static LAST_EVENT_ID_NAME: HeaderName = HeaderName::from_static("last-event-id");
+
+/// Holds the id of the last Server-Sent Event received by a client resuming a stream
+#[derive(Debug, Clone, Copy)]
+pub struct LastEventId(pub u64);
+
+impl Header for LastEventId {
+    fn name() -> &'static HeaderName {
+        &LAST_EVENT_ID_NAME
+    }
+
+    fn decode<'i, I>(values: &mut I) -> Result<Self, Error>
+    where
+        Self: Sized,
+        I: Iterator<Item=&'i HeaderValue>,
+    {
+        let value = values
+            .next()
+            .ok_or_else(headers::Error::invalid)?;
+
+        value
+            .to_str()
+            .ok()
+            .and_then(|value| value.trim().parse().ok())
+            .map(LastEventId)
+            .ok_or_else(|| {
+                error!("Failed to decode Last-Event-ID header: {value:?}");
+                Error::invalid()
+            })
+    }
+
+    fn encode<E: Extend<HeaderValue>>(&self, values: &mut E) {
+        values.extend(std::iter::once(HeaderValue::from(self.0)));
+    }
+}
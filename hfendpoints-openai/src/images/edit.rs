@@ -0,0 +1,323 @@
+use crate::body::{BodyLimit, ReceivedBytes, count_received_bytes};
+use crate::context::{Context, RequestContext};
+use crate::endpoint::{schedule_task, task_router};
+use crate::error::{ErrorResponse, TaskErrors};
+use crate::images::generation::{
+    ImageGenerationResponse, ImageQuality, ImageResponseFormat, ImageSize, ImagesResponse,
+    default_count, validate_images, validate_prompt,
+};
+use crate::images::{IMAGES_TAG, ImageFile, MAX_IMAGE_BODY_SIZE};
+use crate::multipart::{FilePart, FromMultipart};
+use crate::policy::RequestPolicy;
+use crate::telemetry::record_task;
+use crate::{OpenAiError, OpenAiResult, TaskRequest};
+use axum::Extension;
+use axum::extract::{DefaultBodyLimit, Multipart, State};
+use axum::middleware::from_fn;
+use axum_extra::TypedHeader;
+use headers::ContentLength;
+use hfendpoints_core::{EndpointContext, RequestSender};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::SystemTime;
+use tower_http::decompression::RequestDecompressionLayer;
+use tracing::instrument;
+use utoipa::ToSchema;
+use utoipa_axum::router::OpenApiRouter;
+use utoipa_axum::routes;
+
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+
+/// Creates edited or extended images given an original image and a prompt.
+#[derive(ToSchema, FromMultipart)]
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[multipart(file_limit = MAX_IMAGE_BODY_SIZE)]
+struct ImageEditForm {
+    /// The image to edit, a PNG, JPEG, WEBP or GIF file.
+    #[schema(value_type = String, format = Binary)]
+    image: FilePart,
+
+    /// An additional image whose fully transparent areas indicate where `image` should be edited.
+    /// It must have the same dimensions as `image`.
+    #[schema(value_type = Option<String>, format = Binary)]
+    mask: Option<FilePart>,
+
+    /// A text description of the desired images.
+    prompt: String,
+
+    /// Not used, here for compatibility purpose with OpenAI Platform
+    model: Option<String>,
+
+    /// The number of images to generate, between 1 and 10.
+    n: Option<u8>,
+
+    /// The size of the generated images, `auto` or `{width}x{height}` in pixels (i.e. `1024x1024`).
+    #[schema(value_type = Option<String>, example = "1024x1024")]
+    size: Option<ImageSize>,
+
+    /// The quality of the images that will be generated.
+    quality: Option<ImageQuality>,
+
+    /// The format in which the generated images are returned, `url` or `b64_json`.
+    response_format: Option<ImageResponseFormat>,
+
+    /// A unique identifier representing the end-user.
+    user: Option<String>,
+}
+
+#[cfg_attr(feature = "python", pyclass(frozen))]
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Clone, Deserialize, Serialize)]
+pub struct ImageEditRequest {
+    pub image: ImageFile,
+    pub mask: Option<ImageFile>,
+    pub prompt: String,
+    pub model: Option<String>,
+    pub n: u8,
+    pub size: ImageSize,
+    pub quality: ImageQuality,
+    pub response_format: ImageResponseFormat,
+    pub user: Option<String>,
+}
+
+impl ImageEditRequest {
+    #[instrument(skip_all)]
+    fn validate(form: ImageEditForm) -> OpenAiResult<Self> {
+        let n = form.n.unwrap_or_else(default_count);
+        let size = form.size.unwrap_or_default();
+        validate_prompt(&form.prompt)?;
+        validate_images(n, size)?;
+
+        Ok(Self {
+            image: ImageFile::from_part(form.image, "image")?,
+            mask: form
+                .mask
+                .map(|mask| ImageFile::from_part(mask, "mask"))
+                .transpose()?,
+            prompt: form.prompt,
+            model: form.model,
+            n,
+            size,
+            quality: form.quality.unwrap_or_default(),
+            response_format: form.response_format.unwrap_or_default(),
+            user: form.user,
+        })
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/images/edits",
+    tag = IMAGES_TAG,
+    request_body(content = ImageEditForm, content_type = "multipart/form-data"),
+    responses(
+        (status = OK, description = "Returns a list of image objects.", body = ImagesResponse),
+        TaskErrors,
+        (status = PAYLOAD_TOO_LARGE, description = "The uploaded images exceed the maximum allowed size, once decompressed.", body = ErrorResponse),
+    )
+)]
+#[instrument(skip(state, policy, metadata, body_limit, received, multipart))]
+pub async fn edit(
+    State(state): State<EndpointContext<(ImageEditRequest, Context), ImageGenerationResponse>>,
+    Extension(policy): Extension<Arc<RequestPolicy>>,
+    metadata: RequestContext,
+    content_length: Option<TypedHeader<ContentLength>>,
+    body_limit: BodyLimit,
+    Extension(received): Extension<ReceivedBytes>,
+    multipart: Multipart,
+) -> OpenAiResult<ImagesResponse> {
+    record_task("image_edit");
+
+    // Reject upfront uploads announcing a size over the limit, the configured one if lower
+    let limit = body_limit.bound(MAX_IMAGE_BODY_SIZE);
+    let content_length = content_length.map(|length| length.0.0);
+    if content_length.is_some_and(|length| length > limit as u64) {
+        return Err(OpenAiError::PayloadTooLarge {
+            limit,
+            received: content_length,
+        });
+    }
+
+    // Decode request, streamed uploads going over the limit are only detected while reading
+    let form = ImageEditForm::from_multipart(multipart, &policy)
+        .await
+        .map_err(|err| err.with_body_limit(limit, Some(received.get())))?;
+    let request = ImageEditRequest::validate(form)?;
+
+    // Ask for the inference thread to handle it and wait for answers
+    let format = request.response_format;
+    let response = schedule_task(&state, metadata, request)
+        .await?
+        .response()
+        .await?;
+    response.render(format, SystemTime::now())
+}
+
+/// Helper factory to build
+/// [OpenAi Platform compatible Image edit endpoint](https://platform.openai.com/docs/api-reference/images/createEdit)
+///
+/// Edits share the response objects of generations.
+#[derive(Clone)]
+pub struct ImageEditRouter(pub RequestSender<(ImageEditRequest, Context), ImageGenerationResponse>);
+
+impl From<ImageEditRouter> for OpenApiRouter {
+    fn from(value: ImageEditRouter) -> Self {
+        task_router(
+            OpenApiRouter::new().routes(routes!(edit)),
+            value.0,
+            "/images/edits",
+        )
+        .layer(DefaultBodyLimit::max(MAX_IMAGE_BODY_SIZE))
+        .layer(from_fn(count_received_bytes))
+        .layer(RequestDecompressionLayer::new())
+    }
+}
+
+impl TaskRequest for ImageEditRequest {
+    type Response = ImageGenerationResponse;
+
+    fn router(sender: RequestSender<(Self, Context), Self::Response>) -> OpenApiRouter {
+        ImageEditRouter(sender).into()
+    }
+}
+
+#[cfg(feature = "python")]
+pub(crate) mod python {
+    use crate::images::ImageFile;
+    use crate::images::edit::ImageEditRequest;
+    use pyo3::prelude::*;
+
+    #[pymethods]
+    impl ImageEditRequest {
+        /// The image to edit, shared with the request rather than copied
+        #[getter(image)]
+        fn get_image(&self) -> ImageFile {
+            self.image.clone()
+        }
+
+        #[getter(mask)]
+        fn get_mask(&self) -> Option<ImageFile> {
+            self.mask.clone()
+        }
+
+        #[getter(prompt)]
+        fn get_prompt(&self) -> &str {
+            &self.prompt
+        }
+
+        #[getter(model)]
+        fn get_model(&self) -> Option<&str> {
+            self.model.as_deref()
+        }
+
+        #[getter(n)]
+        fn get_n(&self) -> u8 {
+            self.n
+        }
+
+        #[getter(size)]
+        fn get_size(&self) -> String {
+            self.size.to_string()
+        }
+
+        #[getter(width)]
+        fn get_width(&self) -> Option<u32> {
+            self.size.width()
+        }
+
+        #[getter(height)]
+        fn get_height(&self) -> Option<u32> {
+            self.size.height()
+        }
+
+        #[getter(quality)]
+        fn get_quality(&self) -> &'static str {
+            self.quality.as_str()
+        }
+
+        #[getter(response_format)]
+        fn get_response_format(&self) -> &'static str {
+            self.response_format.as_str()
+        }
+
+        #[getter(user)]
+        fn get_user(&self) -> Option<&str> {
+            self.user.as_deref()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::images::edit::{ImageEditRequest, ImageEditRouter};
+    use crate::images::generation::{GeneratedImage, ImageGenerationResponse};
+    use crate::testing::{MULTIPART_FORM, answer, post};
+    use axum::http::StatusCode;
+    use hfendpoints_core::request_channel;
+    use serde_json::Value;
+
+    /// Answer with the masked image
+    async fn edit(form: &'static [u8]) -> (StatusCode, Value) {
+        let (sender, receiver) = request_channel(1);
+        answer(receiver, |request: ImageEditRequest| {
+            let mask = request.mask.expect("mask was uploaded");
+            let mut image = request.image.content.to_vec();
+            image.extend_from_slice(&mask.content);
+            let images = vec![GeneratedImage::Bytes(image.into()); request.n as usize];
+            Ok(ImageGenerationResponse::new(images, None))
+        });
+
+        let router = ImageEditRouter(sender);
+        let (status, _, body) = post(router, "/images/edits", MULTIPART_FORM, form).await;
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    #[tokio::test]
+    async fn edit_accepts_openai_form() {
+        let (status, body) = edit(
+            b"--hfendpoints\r\nContent-Disposition: form-data; name=\"image\"; filename=\"cat.png\"\r\n\r\n\x89PNG\r\n\
+             --hfendpoints\r\nContent-Disposition: form-data; name=\"mask\"; filename=\"mask.png\"\r\n\r\nmask\r\n\
+             --hfendpoints\r\nContent-Disposition: form-data; name=\"prompt\"\r\n\r\nA cat wearing a hat\r\n\
+             --hfendpoints\r\nContent-Disposition: form-data; name=\"n\"\r\n\r\n2\r\n\
+             --hfendpoints\r\nContent-Disposition: form-data; name=\"response_format\"\r\n\r\nb64_json\r\n\
+             --hfendpoints--\r\n",
+        )
+        .await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["data"].as_array().unwrap().len(), 2);
+        assert_eq!(body["data"][0]["b64_json"], "iVBOR21hc2s=");
+    }
+
+    #[tokio::test]
+    async fn edit_rejects_invalid_forms() {
+        for (form, message) in [
+            (
+                b"--hfendpoints\r\nContent-Disposition: form-data; name=\"image\"; filename=\"cat.png\"\r\n\r\n\x89PNG\r\n\
+                 --hfendpoints--\r\n"
+                    .as_slice(),
+                "Required parameter 'prompt' was not provided",
+            ),
+            (
+                b"--hfendpoints\r\nContent-Disposition: form-data; name=\"image\"; filename=\"cat.txt\"\r\n\r\nmeow\r\n\
+                 --hfendpoints\r\nContent-Disposition: form-data; name=\"prompt\"\r\n\r\nA cat\r\n\
+                 --hfendpoints--\r\n"
+                    .as_slice(),
+                "Parameter 'image' must be a PNG, JPEG, WEBP or GIF image, got application/octet-stream",
+            ),
+            (
+                b"--hfendpoints\r\nContent-Disposition: form-data; name=\"image\"; filename=\"cat.png\"\r\n\r\n\x89PNG\r\n\
+                 --hfendpoints\r\nContent-Disposition: form-data; name=\"prompt\"\r\n\r\nA cat\r\n\
+                 --hfendpoints\r\nContent-Disposition: form-data; name=\"response_format\"\r\n\r\npng\r\n\
+                 --hfendpoints--\r\n"
+                    .as_slice(),
+                "Invalid value for parameter 'response_format': Unknown response_format: png. Possible values are: 'url', 'b64_json'.",
+            ),
+        ] {
+            let (status, body) = edit(form).await;
+            assert_eq!(status, StatusCode::BAD_REQUEST);
+            assert_eq!(body["error"]["message"], message);
+        }
+    }
+}
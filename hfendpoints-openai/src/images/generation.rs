@@ -0,0 +1,669 @@
+use crate::context::{Context, RequestContext};
+use crate::endpoint::{schedule_task, task_router};
+use crate::error::TaskErrors;
+use crate::images::IMAGES_TAG;
+use crate::policy::RequestPolicy;
+use crate::telemetry::record_task;
+use crate::{OpenAiError, OpenAiResult, TaskRequest};
+use axum::body::Bytes;
+use axum::extract::State;
+use axum::response::{IntoResponse, Response};
+use axum::{Extension, Json};
+use base64::Engine;
+use base64::prelude::BASE64_STANDARD;
+use hfendpoints_core::{EndpointContext, RequestSender};
+use hfendpoints_schemas::SchemaError;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::instrument;
+use utoipa::ToSchema;
+use utoipa_axum::router::OpenApiRouter;
+use utoipa_axum::routes;
+
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+
+/// Maximum number of characters accepted in the prompt (as OpenAI)
+const MAX_PROMPT_LENGTH: usize = 32000;
+
+/// Maximum number of images generated by a single request (as OpenAI)
+const MAX_IMAGES: u8 = 10;
+
+/// Largest width or height, in pixels, of the generated images
+const MAX_IMAGE_DIMENSION: u32 = 4096;
+
+/// The size of the generated images, either `auto` or `{width}x{height}` in pixels.
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Copy, Clone, Default, Eq, PartialEq, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum ImageSize {
+    /// The model picks the size of the images
+    #[default]
+    Auto,
+
+    /// Images of `width` by `height` pixels
+    Dimensions { width: u32, height: u32 },
+}
+
+impl ImageSize {
+    /// Width of the images, unless left to the model
+    #[inline]
+    pub fn width(&self) -> Option<u32> {
+        match self {
+            ImageSize::Auto => None,
+            ImageSize::Dimensions { width, .. } => Some(*width),
+        }
+    }
+
+    /// Height of the images, unless left to the model
+    #[inline]
+    pub fn height(&self) -> Option<u32> {
+        match self {
+            ImageSize::Auto => None,
+            ImageSize::Dimensions { height, .. } => Some(*height),
+        }
+    }
+}
+
+impl FromStr for ImageSize {
+    type Err = OpenAiError;
+
+    fn from_str(size: &str) -> Result<Self, Self::Err> {
+        if size == "auto" {
+            return Ok(Self::Auto);
+        }
+
+        let dimensions = size.split_once('x').and_then(|(width, height)| {
+            let width = width.parse::<u32>().ok().filter(|width| *width > 0)?;
+            let height = height.parse::<u32>().ok().filter(|height| *height > 0)?;
+            Some(Self::Dimensions { width, height })
+        });
+        dimensions.ok_or_else(|| {
+            OpenAiError::Validation(format!(
+                "expected auto or {{width}}x{{height}} in pixels, got {size}"
+            ))
+        })
+    }
+}
+
+impl TryFrom<String> for ImageSize {
+    type Error = OpenAiError;
+
+    fn try_from(size: String) -> Result<Self, Self::Error> {
+        size.parse()
+    }
+}
+
+impl Display for ImageSize {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ImageSize::Auto => f.write_str("auto"),
+            ImageSize::Dimensions { width, height } => write!(f, "{width}x{height}"),
+        }
+    }
+}
+
+impl From<ImageSize> for String {
+    fn from(size: ImageSize) -> Self {
+        size.to_string()
+    }
+}
+
+/// The quality of the generated images.
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Copy, Clone, Default, Eq, PartialEq, Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ImageQuality {
+    #[default]
+    Auto,
+    Standard,
+    Hd,
+    Low,
+    Medium,
+    High,
+}
+
+impl ImageQuality {
+    #[inline]
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ImageQuality::Auto => "auto",
+            ImageQuality::Standard => "standard",
+            ImageQuality::Hd => "hd",
+            ImageQuality::Low => "low",
+            ImageQuality::Medium => "medium",
+            ImageQuality::High => "high",
+        }
+    }
+}
+
+impl FromStr for ImageQuality {
+    type Err = SchemaError;
+
+    fn from_str(quality: &str) -> Result<Self, Self::Err> {
+        match quality {
+            "auto" => Ok(ImageQuality::Auto),
+            "standard" => Ok(ImageQuality::Standard),
+            "hd" => Ok(ImageQuality::Hd),
+            "low" => Ok(ImageQuality::Low),
+            "medium" => Ok(ImageQuality::Medium),
+            "high" => Ok(ImageQuality::High),
+            _ => Err(SchemaError::UnknownVariant {
+                field: "quality",
+                value: String::from(quality),
+                expected: "'auto', 'standard', 'hd', 'low', 'medium', 'high'",
+            }),
+        }
+    }
+}
+
+/// The format in which the generated images are returned.
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Copy, Clone, Default, Eq, PartialEq, Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ImageResponseFormat {
+    /// URL of the image, a `data:` URL holding it when the handler returns its bytes
+    #[default]
+    Url,
+
+    /// The image encoded as base64
+    B64Json,
+}
+
+impl ImageResponseFormat {
+    #[inline]
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ImageResponseFormat::Url => "url",
+            ImageResponseFormat::B64Json => "b64_json",
+        }
+    }
+}
+
+impl FromStr for ImageResponseFormat {
+    type Err = SchemaError;
+
+    fn from_str(format: &str) -> Result<Self, Self::Err> {
+        match format {
+            "url" => Ok(ImageResponseFormat::Url),
+            "b64_json" => Ok(ImageResponseFormat::B64Json),
+            _ => Err(SchemaError::UnknownVariant {
+                field: "response_format",
+                value: String::from(format),
+                expected: "'url', 'b64_json'",
+            }),
+        }
+    }
+}
+
+#[inline]
+pub(crate) fn default_count() -> u8 {
+    1
+}
+
+/// Creates images given a prompt.
+#[cfg_attr(feature = "python", pyclass(frozen))]
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Clone, Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct ImageGenerationRequest {
+    /// A text description of the desired images.
+    pub prompt: String,
+
+    /// Not used, here for compatibility purpose with OpenAI Platform
+    pub model: Option<String>,
+
+    /// The number of images to generate, between 1 and 10.
+    #[serde(default = "default_count")]
+    pub n: u8,
+
+    /// The size of the generated images, `auto` or `{width}x{height}` in pixels (i.e. `1024x1024`).
+    #[serde(default)]
+    #[schema(value_type = String, example = "1024x1024")]
+    pub size: ImageSize,
+
+    /// The quality of the images that will be generated.
+    #[serde(default)]
+    pub quality: ImageQuality,
+
+    /// The format in which the generated images are returned, `url` or `b64_json`.
+    #[serde(default)]
+    pub response_format: ImageResponseFormat,
+
+    /// A unique identifier representing the end-user.
+    pub user: Option<String>,
+}
+
+/// Reject empty prompts and the ones over `MAX_PROMPT_LENGTH` characters
+pub(crate) fn validate_prompt(prompt: &str) -> OpenAiResult<()> {
+    if prompt.is_empty() {
+        return Err(OpenAiError::Validation(String::from(
+            "Parameter 'prompt' cannot be empty",
+        )));
+    }
+
+    let length = prompt.chars().count();
+    if length > MAX_PROMPT_LENGTH {
+        return Err(OpenAiError::Validation(format!(
+            "Parameter 'prompt' is limited to {MAX_PROMPT_LENGTH} characters, got {length}"
+        )));
+    }
+    Ok(())
+}
+
+/// Reject requests for no image at all or more than `MAX_IMAGES` of them, or for images larger
+/// than `MAX_IMAGE_DIMENSION` pixels on any side
+pub(crate) fn validate_images(n: u8, size: ImageSize) -> OpenAiResult<()> {
+    if !(1..=MAX_IMAGES).contains(&n) {
+        return Err(OpenAiError::Validation(format!(
+            "Parameter 'n' must be between 1 and {MAX_IMAGES}, got {n}"
+        )));
+    }
+
+    if let ImageSize::Dimensions { width, height } = size
+        && width.max(height) > MAX_IMAGE_DIMENSION
+    {
+        return Err(OpenAiError::Validation(format!(
+            "Parameter 'size' is limited to {MAX_IMAGE_DIMENSION} pixels per side, got {size}"
+        )));
+    }
+    Ok(())
+}
+
+impl ImageGenerationRequest {
+    #[instrument(skip_all)]
+    fn validate(self) -> OpenAiResult<Self> {
+        validate_prompt(&self.prompt)?;
+        validate_images(self.n, self.size)?;
+        Ok(self)
+    }
+}
+
+/// Image produced by the handler, either its encoded bytes (i.e. PNG) or the URL it is available at
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GeneratedImage {
+    Bytes(#[serde(with = "crate::base64_bytes")] Bytes),
+    Url(String),
+}
+
+/// Media type of the encoded `image`, sniffed from its signature
+pub(crate) fn sniff_media_type(image: &[u8]) -> Option<&'static str> {
+    if image.starts_with(b"\x89PNG\r\n\x1a\n") {
+        Some("image/png")
+    } else if image.starts_with(&[0xFF, 0xD8, 0xFF]) {
+        Some("image/jpeg")
+    } else if image.starts_with(b"RIFF") && image.get(8..12) == Some(b"WEBP") {
+        Some("image/webp")
+    } else if image.starts_with(b"GIF8") {
+        Some("image/gif")
+    } else {
+        None
+    }
+}
+
+/// The images generated by the model, rendered by the endpoint in the requested `response_format`.
+#[cfg_attr(feature = "python", pyclass(frozen))]
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Clone, Deserialize, Serialize)]
+pub struct ImageGenerationResponse {
+    /// The generated images
+    pub images: Vec<GeneratedImage>,
+
+    /// The prompt the images were generated from, if the model revised the one provided
+    pub revised_prompt: Option<String>,
+}
+
+impl ImageGenerationResponse {
+    pub fn new(images: Vec<GeneratedImage>, revised_prompt: Option<String>) -> Self {
+        Self {
+            images,
+            revised_prompt,
+        }
+    }
+
+    /// Render the images in `format`, images only available through their URL being invalid
+    /// when their content is requested
+    pub(crate) fn render(
+        self,
+        format: ImageResponseFormat,
+        created: SystemTime,
+    ) -> OpenAiResult<ImagesResponse> {
+        if self.images.is_empty() {
+            return Err(OpenAiError::InvalidResponse(String::from(
+                "no image was generated",
+            )));
+        }
+
+        let data = self
+            .images
+            .into_iter()
+            .map(|image| {
+                let (b64_json, url) = match (image, format) {
+                    (GeneratedImage::Bytes(bytes), ImageResponseFormat::B64Json) => {
+                        (Some(BASE64_STANDARD.encode(bytes)), None)
+                    }
+                    (GeneratedImage::Bytes(bytes), ImageResponseFormat::Url) => {
+                        let encoded = BASE64_STANDARD.encode(&bytes);
+                        // Images which cannot be sniffed are assumed to be PNG, the default of OpenAI
+                        let media_type = sniff_media_type(&bytes).unwrap_or("image/png");
+                        (None, Some(format!("data:{media_type};base64,{encoded}")))
+                    }
+                    (GeneratedImage::Url(url), ImageResponseFormat::Url) => (None, Some(url)),
+                    (GeneratedImage::Url(_), ImageResponseFormat::B64Json) => {
+                        return Err(OpenAiError::InvalidResponse(String::from(
+                            "b64_json was requested but the handler returned the URL of an image",
+                        )));
+                    }
+                };
+
+                Ok(Image {
+                    b64_json,
+                    url,
+                    revised_prompt: self.revised_prompt.clone(),
+                })
+            })
+            .collect::<OpenAiResult<_>>()?;
+
+        Ok(ImagesResponse {
+            created: created
+                .duration_since(UNIX_EPOCH)
+                .map(|elapsed| elapsed.as_secs())
+                .unwrap_or_default(),
+            data,
+        })
+    }
+}
+
+/// Represents the content or the URL of an image generated by the model.
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Clone, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct Image {
+    /// The base64-encoded content of the image, if `response_format` is `b64_json`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    b64_json: Option<String>,
+
+    /// The URL of the image, if `response_format` is `url`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    url: Option<String>,
+
+    /// The prompt that was used to generate the image, if there was any revision to the prompt.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    revised_prompt: Option<String>,
+}
+
+/// The list of generated images.
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Clone, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct ImagesResponse {
+    /// The Unix timestamp (in seconds) of when the images were created.
+    created: u64,
+
+    /// The list of generated images.
+    data: Vec<Image>,
+}
+
+impl IntoResponse for ImagesResponse {
+    fn into_response(self) -> Response {
+        Json::from(self).into_response()
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/images/generations",
+    tag = IMAGES_TAG,
+    request_body(content = ImageGenerationRequest, content_type = "application/json"),
+    responses(
+        (status = OK, description = "Returns a list of image objects.", body = ImagesResponse),
+        TaskErrors,
+    )
+)]
+#[instrument(skip(state, policy, metadata, request))]
+pub async fn generate(
+    State(state): State<
+        EndpointContext<(ImageGenerationRequest, Context), ImageGenerationResponse>,
+    >,
+    Extension(policy): Extension<Arc<RequestPolicy>>,
+    metadata: RequestContext,
+    Json(mut request): Json<Value>,
+) -> OpenAiResult<ImagesResponse> {
+    record_task("image_generation");
+
+    // Apply the operator's defaults and overrides before decoding
+    policy.apply(&mut request);
+    let request = serde_json::from_value::<ImageGenerationRequest>(request)
+        .map_err(|err| OpenAiError::Validation(err.to_string()))?
+        .validate()?;
+
+    // Ask for the inference thread to handle it and wait for answers
+    let format = request.response_format;
+    let response = schedule_task(&state, metadata, request)
+        .await?
+        .response()
+        .await?;
+    response.render(format, SystemTime::now())
+}
+
+/// Helper factory to build
+/// [OpenAi Platform compatible Image generation endpoint](https://platform.openai.com/docs/api-reference/images/create)
+#[derive(Clone)]
+pub struct ImageGenerationRouter(
+    pub RequestSender<(ImageGenerationRequest, Context), ImageGenerationResponse>,
+);
+
+impl From<ImageGenerationRouter> for OpenApiRouter {
+    fn from(value: ImageGenerationRouter) -> Self {
+        let routes = OpenApiRouter::new().routes(routes!(generate));
+        task_router(routes, value.0, "/images/generations")
+    }
+}
+
+impl TaskRequest for ImageGenerationRequest {
+    type Response = ImageGenerationResponse;
+
+    fn router(sender: RequestSender<(Self, Context), Self::Response>) -> OpenApiRouter {
+        ImageGenerationRouter(sender).into()
+    }
+}
+
+#[cfg(feature = "python")]
+pub(crate) mod python {
+    use crate::images::generation::{
+        GeneratedImage, ImageGenerationRequest, ImageGenerationResponse,
+    };
+    use crate::python::TextDelta;
+    use axum::body::Bytes;
+    use pyo3::exceptions::PyTypeError;
+    use pyo3::prelude::*;
+    use pyo3::types::PyBytes;
+
+    impl TextDelta for ImageGenerationResponse {}
+
+    #[pymethods]
+    impl ImageGenerationRequest {
+        #[getter(prompt)]
+        fn get_prompt(&self) -> &str {
+            &self.prompt
+        }
+
+        #[getter(model)]
+        fn get_model(&self) -> Option<&str> {
+            self.model.as_deref()
+        }
+
+        #[getter(n)]
+        fn get_n(&self) -> u8 {
+            self.n
+        }
+
+        #[getter(size)]
+        fn get_size(&self) -> String {
+            self.size.to_string()
+        }
+
+        #[getter(width)]
+        fn get_width(&self) -> Option<u32> {
+            self.size.width()
+        }
+
+        #[getter(height)]
+        fn get_height(&self) -> Option<u32> {
+            self.size.height()
+        }
+
+        #[getter(quality)]
+        fn get_quality(&self) -> &'static str {
+            self.quality.as_str()
+        }
+
+        #[getter(response_format)]
+        fn get_response_format(&self) -> &'static str {
+            self.response_format.as_str()
+        }
+
+        #[getter(user)]
+        fn get_user(&self) -> Option<&str> {
+            self.user.as_deref()
+        }
+    }
+
+    #[pymethods]
+    impl ImageGenerationResponse {
+        #[new]
+        #[pyo3(signature = (images, revised_prompt = None))]
+        fn py_new(images: Vec<Bound<'_, PyAny>>, revised_prompt: Option<String>) -> PyResult<Self> {
+            let images = images
+                .iter()
+                .map(|image| match image.downcast::<PyBytes>() {
+                    Ok(bytes) => Ok(GeneratedImage::Bytes(Bytes::copy_from_slice(
+                        bytes.as_bytes(),
+                    ))),
+                    Err(_) => image.extract::<String>().map(GeneratedImage::Url).map_err(|_| {
+                        PyTypeError::new_err(
+                            "Images must be either their encoded bytes or the URL they are available at",
+                        )
+                    }),
+                })
+                .collect::<PyResult<_>>()?;
+            Ok(Self::new(images, revised_prompt))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::images::generation::{
+        GeneratedImage, ImageGenerationRequest, ImageGenerationResponse, ImageGenerationRouter,
+        ImageSize,
+    };
+    use crate::testing::{answer, post};
+    use axum::body::Bytes;
+    use axum::http::StatusCode;
+    use hfendpoints_core::request_channel;
+    use serde_json::{Value, json};
+
+    /// PNG signature, standing for the content of a generated image
+    const PNG: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+    /// Answer with `image` as many times as requested, revising the prompt
+    async fn generate(body: &'static str, image: GeneratedImage) -> (StatusCode, Value) {
+        let (sender, receiver) = request_channel(1);
+        answer(receiver, move |request: ImageGenerationRequest| {
+            let images = vec![image.clone(); request.n as usize];
+            let revised = Some(format!("A painting of {}", request.prompt));
+            Ok(ImageGenerationResponse::new(images, revised))
+        });
+
+        let router = ImageGenerationRouter(sender);
+        let (status, _, body) = post(router, "/images/generations", "application/json", body).await;
+        (status, serde_json::from_slice(&body).unwrap_or_default())
+    }
+
+    #[tokio::test]
+    async fn generate_images_as_base64() {
+        let image = GeneratedImage::Bytes(Bytes::from_static(PNG));
+        let (status, body) = generate(
+            r#"{"prompt": "a cat", "n": 2, "size": "512x512", "response_format": "b64_json"}"#,
+            image,
+        )
+        .await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert!(body["created"].as_u64().unwrap() > 0);
+        assert_eq!(body["data"].as_array().unwrap().len(), 2);
+        assert_eq!(
+            body["data"][1],
+            json!({"b64_json": "iVBORw0KGgo=", "revised_prompt": "A painting of a cat"})
+        );
+    }
+
+    #[tokio::test]
+    async fn generate_images_as_urls() {
+        // Images the handler returns the content of are embedded in data URLs
+        let image = GeneratedImage::Bytes(Bytes::from_static(PNG));
+        let (status, body) = generate(r#"{"prompt": "a cat"}"#, image).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["data"][0]["url"], "data:image/png;base64,iVBORw0KGgo=");
+
+        let image = GeneratedImage::Url(String::from("https://images.example.com/cat.png"));
+        let (status, body) = generate(r#"{"prompt": "a cat"}"#, image).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["data"][0]["url"], "https://images.example.com/cat.png");
+
+        // The content of images only available through their URL cannot be returned
+        let image = GeneratedImage::Url(String::from("https://images.example.com/cat.png"));
+        let (status, body) = generate(
+            r#"{"prompt": "a cat", "response_format": "b64_json"}"#,
+            image,
+        )
+        .await;
+        assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(body["error"]["code"], "invalid_response");
+    }
+
+    #[tokio::test]
+    async fn generate_rejects_invalid_requests() {
+        for (body, message) in [
+            (r#"{"prompt": ""}"#, "Parameter 'prompt' cannot be empty"),
+            (
+                r#"{"prompt": "a cat", "n": 11}"#,
+                "Parameter 'n' must be between 1 and 10, got 11",
+            ),
+            (
+                r#"{"prompt": "a cat", "size": "8192x1024"}"#,
+                "Parameter 'size' is limited to 4096 pixels per side, got 8192x1024",
+            ),
+        ] {
+            let image = GeneratedImage::Bytes(Bytes::from_static(PNG));
+            let (status, response) = generate(body, image).await;
+            assert_eq!(status, StatusCode::BAD_REQUEST);
+            assert_eq!(response["error"]["message"], message);
+        }
+
+        let image = GeneratedImage::Bytes(Bytes::from_static(PNG));
+        let (status, _) = generate(r#"{"prompt": "a cat", "size": "large"}"#, image).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[test]
+    fn parse_image_sizes() {
+        assert_eq!("auto".parse::<ImageSize>().unwrap(), ImageSize::Auto);
+        assert_eq!(
+            "1792x1024".parse::<ImageSize>().unwrap(),
+            ImageSize::Dimensions {
+                width: 1792,
+                height: 1024
+            }
+        );
+        assert!("0x1024".parse::<ImageSize>().is_err());
+        assert!("1024".parse::<ImageSize>().is_err());
+    }
+}
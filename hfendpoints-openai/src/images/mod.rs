@@ -0,0 +1,176 @@
+use crate::images::generation::sniff_media_type;
+use crate::multipart::FilePart;
+use crate::{OpenAiError, OpenAiResult};
+use axum::body::Bytes;
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+
+pub mod edit;
+pub mod generation;
+pub mod variation;
+
+pub const IMAGES_TAG: &str = "Images";
+pub const IMAGES_DESC: &str =
+    "Given a prompt and/or an input image, the model will generate new images.";
+
+/// Maximum size of the payloads accepted by the image edit and variation routes (50Mb as OpenAI)
+pub const MAX_IMAGE_BODY_SIZE: usize = 50 * 1024 * 1024;
+
+/// Image uploaded along an edit or variation request, its content being exposed to Python
+/// through the buffer protocol, without copy
+#[cfg_attr(feature = "python", pyclass(frozen))]
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Clone, Deserialize, Serialize)]
+pub struct ImageFile {
+    #[serde(with = "crate::base64_bytes")]
+    pub content: Bytes,
+    pub content_type: String,
+}
+
+impl ImageFile {
+    /// Image uploaded as the form field `name`, rejecting empty files and the ones neither
+    /// declared nor sniffed as an image
+    pub(crate) fn from_part(part: FilePart, name: &str) -> OpenAiResult<Self> {
+        if part.content.is_empty() {
+            return Err(OpenAiError::Validation(format!(
+                "Parameter '{name}' cannot be an empty file"
+            )));
+        }
+
+        let content_type = if part.content_type.starts_with("image/") {
+            part.content_type
+        } else {
+            sniff_media_type(&part.content)
+                .map(String::from)
+                .ok_or_else(|| {
+                    OpenAiError::Validation(format!(
+                        "Parameter '{name}' must be a PNG, JPEG, WEBP or GIF image, got {}",
+                        part.content_type
+                    ))
+                })?
+        };
+
+        Ok(Self {
+            content: part.content,
+            content_type,
+        })
+    }
+}
+
+#[cfg(feature = "python")]
+pub(crate) mod python {
+    use crate::images::ImageFile;
+    use crate::images::edit::ImageEditRequest;
+    use crate::images::generation::{ImageGenerationRequest, ImageGenerationResponse};
+    use crate::images::variation::ImageVariationRequest;
+    use hfendpoints_binding_python::{
+        ImportablePyModuleBuilder, fill_view_from_readonly_data, release_view,
+    };
+    use pyo3::ffi::Py_buffer;
+    use pyo3::prelude::*;
+    use tracing::{debug, instrument};
+
+    mod generation {
+        use crate::images::generation::{
+            ImageGenerationRequest, ImageGenerationResponse, ImageGenerationRouter,
+        };
+        use crate::python::{impl_pyendpoint, impl_pyhandler};
+
+        impl_pyhandler!(ImageGenerationRequest, ImageGenerationResponse);
+        impl_pyendpoint!(
+            "ImageGenerationEndpoint",
+            PyImageGenerationEndpoint,
+            PyHandler,
+            ImageGenerationRouter
+        );
+    }
+
+    mod edit {
+        use crate::images::edit::{ImageEditRequest, ImageEditRouter};
+        use crate::images::generation::ImageGenerationResponse;
+        use crate::python::{impl_pyendpoint, impl_pyhandler};
+
+        impl_pyhandler!(ImageEditRequest, ImageGenerationResponse);
+        impl_pyendpoint!(
+            "ImageEditEndpoint",
+            PyImageEditEndpoint,
+            PyHandler,
+            ImageEditRouter
+        );
+    }
+
+    mod variation {
+        use crate::images::generation::ImageGenerationResponse;
+        use crate::images::variation::{ImageVariationRequest, ImageVariationRouter};
+        use crate::python::{impl_pyendpoint, impl_pyhandler};
+
+        impl_pyhandler!(ImageVariationRequest, ImageGenerationResponse);
+        impl_pyendpoint!(
+            "ImageVariationEndpoint",
+            PyImageVariationEndpoint,
+            PyHandler,
+            ImageVariationRouter
+        );
+    }
+
+    #[pymethods]
+    impl ImageFile {
+        /// Expose the image as a read-only buffer of bytes, i.e. `memoryview(image)`
+        ///
+        /// # Safety
+        ///
+        /// Only called by the interpreter through the buffer protocol, `buffer` pointing to a
+        /// `Py_buffer` it owns, filled here and released through `__releasebuffer__`.
+        #[instrument(skip(slf, buffer))]
+        pub unsafe fn __getbuffer__(
+            slf: Bound<'_, Self>,
+            buffer: *mut Py_buffer,
+            flags: i32,
+        ) -> PyResult<()> {
+            debug!("Acquiring a memoryview over image data (flags={})", flags);
+            unsafe {
+                fill_view_from_readonly_data(buffer, flags, &slf.borrow().content, slf.into_any())
+            }
+        }
+
+        /// Release the buffer exposed by `__getbuffer__`
+        ///
+        /// # Safety
+        ///
+        /// Only called by the interpreter through the buffer protocol, with a `buffer` previously
+        /// filled by `__getbuffer__`, and exactly once per such buffer.
+        #[instrument(skip_all)]
+        pub unsafe fn __releasebuffer__(&self, buffer: *mut Py_buffer) {
+            debug!("Releasing Python memoryview");
+            unsafe { release_view(buffer) }
+        }
+
+        fn __len__(&self) -> usize {
+            self.content.len()
+        }
+
+        #[getter(content_type)]
+        fn get_content_type(&self) -> &str {
+            &self.content_type
+        }
+    }
+
+    /// Bind hfendpoints.openai.images submodule into the exported Python wheel
+    pub fn bind<'py>(py: Python<'py>, name: &str) -> PyResult<Bound<'py, PyModule>> {
+        let module = ImportablePyModuleBuilder::new(py, name)?
+            .defaults()?
+            .add_class::<ImageFile>()?
+            .add_class::<ImageGenerationRequest>()?
+            .add_class::<ImageGenerationResponse>()?
+            .add_class::<ImageEditRequest>()?
+            .add_class::<ImageVariationRequest>()?
+            .add_class::<generation::PyImageGenerationEndpoint>()?
+            .add_class::<edit::PyImageEditEndpoint>()?
+            .add_class::<variation::PyImageVariationEndpoint>()?
+            .finish();
+
+        Ok(module)
+    }
+}
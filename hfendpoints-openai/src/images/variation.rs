@@ -0,0 +1,282 @@
+use crate::body::{BodyLimit, ReceivedBytes, count_received_bytes};
+use crate::context::{Context, RequestContext};
+use crate::endpoint::{schedule_task, task_router};
+use crate::error::{ErrorResponse, TaskErrors};
+use crate::images::generation::{
+    ImageGenerationResponse, ImageResponseFormat, ImageSize, ImagesResponse, default_count,
+    validate_images,
+};
+use crate::images::{IMAGES_TAG, ImageFile, MAX_IMAGE_BODY_SIZE};
+use crate::multipart::{FilePart, FromMultipart};
+use crate::policy::RequestPolicy;
+use crate::telemetry::record_task;
+use crate::{OpenAiError, OpenAiResult, TaskRequest};
+use axum::Extension;
+use axum::extract::{DefaultBodyLimit, Multipart, State};
+use axum::middleware::from_fn;
+use axum_extra::TypedHeader;
+use headers::ContentLength;
+use hfendpoints_core::{EndpointContext, RequestSender};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::SystemTime;
+use tower_http::decompression::RequestDecompressionLayer;
+use tracing::instrument;
+use utoipa::ToSchema;
+use utoipa_axum::router::OpenApiRouter;
+use utoipa_axum::routes;
+
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+
+/// Creates variations of a given image.
+#[derive(ToSchema, FromMultipart)]
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[multipart(file_limit = MAX_IMAGE_BODY_SIZE)]
+struct ImageVariationForm {
+    /// The image to use as the basis for the variations, a PNG, JPEG, WEBP or GIF file.
+    #[schema(value_type = String, format = Binary)]
+    image: FilePart,
+
+    /// Not used, here for compatibility purpose with OpenAI Platform
+    model: Option<String>,
+
+    /// The number of images to generate, between 1 and 10.
+    n: Option<u8>,
+
+    /// The size of the generated images, `auto` or `{width}x{height}` in pixels (i.e. `1024x1024`).
+    #[schema(value_type = Option<String>, example = "1024x1024")]
+    size: Option<ImageSize>,
+
+    /// The format in which the generated images are returned, `url` or `b64_json`.
+    response_format: Option<ImageResponseFormat>,
+
+    /// A unique identifier representing the end-user.
+    user: Option<String>,
+}
+
+#[cfg_attr(feature = "python", pyclass(frozen))]
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Clone, Deserialize, Serialize)]
+pub struct ImageVariationRequest {
+    pub image: ImageFile,
+    pub model: Option<String>,
+    pub n: u8,
+    pub size: ImageSize,
+    pub response_format: ImageResponseFormat,
+    pub user: Option<String>,
+}
+
+impl ImageVariationRequest {
+    #[instrument(skip_all)]
+    fn validate(form: ImageVariationForm) -> OpenAiResult<Self> {
+        let n = form.n.unwrap_or_else(default_count);
+        let size = form.size.unwrap_or_default();
+        validate_images(n, size)?;
+
+        Ok(Self {
+            image: ImageFile::from_part(form.image, "image")?,
+            model: form.model,
+            n,
+            size,
+            response_format: form.response_format.unwrap_or_default(),
+            user: form.user,
+        })
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/images/variations",
+    tag = IMAGES_TAG,
+    request_body(content = ImageVariationForm, content_type = "multipart/form-data"),
+    responses(
+        (status = OK, description = "Returns a list of image objects.", body = ImagesResponse),
+        TaskErrors,
+        (status = PAYLOAD_TOO_LARGE, description = "The uploaded image exceeds the maximum allowed size, once decompressed.", body = ErrorResponse),
+    )
+)]
+#[instrument(skip(state, policy, metadata, body_limit, received, multipart))]
+pub async fn vary(
+    State(state): State<EndpointContext<(ImageVariationRequest, Context), ImageGenerationResponse>>,
+    Extension(policy): Extension<Arc<RequestPolicy>>,
+    metadata: RequestContext,
+    content_length: Option<TypedHeader<ContentLength>>,
+    body_limit: BodyLimit,
+    Extension(received): Extension<ReceivedBytes>,
+    multipart: Multipart,
+) -> OpenAiResult<ImagesResponse> {
+    record_task("image_variation");
+
+    // Reject upfront uploads announcing a size over the limit, the configured one if lower
+    let limit = body_limit.bound(MAX_IMAGE_BODY_SIZE);
+    let content_length = content_length.map(|length| length.0.0);
+    if content_length.is_some_and(|length| length > limit as u64) {
+        return Err(OpenAiError::PayloadTooLarge {
+            limit,
+            received: content_length,
+        });
+    }
+
+    // Decode request, streamed uploads going over the limit are only detected while reading
+    let form = ImageVariationForm::from_multipart(multipart, &policy)
+        .await
+        .map_err(|err| err.with_body_limit(limit, Some(received.get())))?;
+    let request = ImageVariationRequest::validate(form)?;
+
+    // Ask for the inference thread to handle it and wait for answers
+    let format = request.response_format;
+    let response = schedule_task(&state, metadata, request)
+        .await?
+        .response()
+        .await?;
+    response.render(format, SystemTime::now())
+}
+
+/// Helper factory to build
+/// [OpenAi Platform compatible Image variation endpoint](https://platform.openai.com/docs/api-reference/images/createVariation)
+///
+/// Variations share the response objects of generations.
+#[derive(Clone)]
+pub struct ImageVariationRouter(
+    pub RequestSender<(ImageVariationRequest, Context), ImageGenerationResponse>,
+);
+
+impl From<ImageVariationRouter> for OpenApiRouter {
+    fn from(value: ImageVariationRouter) -> Self {
+        let routes = OpenApiRouter::new().routes(routes!(vary));
+        task_router(routes, value.0, "/images/variations")
+            .layer(DefaultBodyLimit::max(MAX_IMAGE_BODY_SIZE))
+            .layer(from_fn(count_received_bytes))
+            .layer(RequestDecompressionLayer::new())
+    }
+}
+
+impl TaskRequest for ImageVariationRequest {
+    type Response = ImageGenerationResponse;
+
+    fn router(sender: RequestSender<(Self, Context), Self::Response>) -> OpenApiRouter {
+        ImageVariationRouter(sender).into()
+    }
+}
+
+#[cfg(feature = "python")]
+pub(crate) mod python {
+    use crate::images::ImageFile;
+    use crate::images::variation::ImageVariationRequest;
+    use pyo3::prelude::*;
+
+    #[pymethods]
+    impl ImageVariationRequest {
+        /// The image to vary, shared with the request rather than copied
+        #[getter(image)]
+        fn get_image(&self) -> ImageFile {
+            self.image.clone()
+        }
+
+        #[getter(model)]
+        fn get_model(&self) -> Option<&str> {
+            self.model.as_deref()
+        }
+
+        #[getter(n)]
+        fn get_n(&self) -> u8 {
+            self.n
+        }
+
+        #[getter(size)]
+        fn get_size(&self) -> String {
+            self.size.to_string()
+        }
+
+        #[getter(width)]
+        fn get_width(&self) -> Option<u32> {
+            self.size.width()
+        }
+
+        #[getter(height)]
+        fn get_height(&self) -> Option<u32> {
+            self.size.height()
+        }
+
+        #[getter(response_format)]
+        fn get_response_format(&self) -> &'static str {
+            self.response_format.as_str()
+        }
+
+        #[getter(user)]
+        fn get_user(&self) -> Option<&str> {
+            self.user.as_deref()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::images::generation::{GeneratedImage, ImageGenerationResponse};
+    use crate::images::variation::{ImageVariationRequest, ImageVariationRouter};
+    use crate::testing::{MULTIPART_FORM, answer, post};
+    use axum::http::StatusCode;
+    use hfendpoints_core::request_channel;
+    use serde_json::Value;
+
+    /// Answer with the uploaded image, as many times as requested
+    async fn vary(form: &'static [u8]) -> (StatusCode, Value) {
+        let (sender, receiver) = request_channel(1);
+        answer(receiver, |request: ImageVariationRequest| {
+            assert_eq!(request.image.content_type, "image/jpeg");
+            let image = GeneratedImage::Bytes(request.image.content);
+            let images = vec![image; request.n as usize];
+            Ok(ImageGenerationResponse::new(images, None))
+        });
+
+        let router = ImageVariationRouter(sender);
+        let (status, _, body) = post(router, "/images/variations", MULTIPART_FORM, form).await;
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    #[tokio::test]
+    async fn vary_accepts_openai_form() {
+        // Uploads without content type nor extension are sniffed
+        let (status, body) = vary(
+            b"--hfendpoints\r\nContent-Disposition: form-data; name=\"image\"; filename=\"cat\"\r\n\r\n\xFF\xD8\xFF\xE0\r\n\
+              --hfendpoints\r\nContent-Disposition: form-data; name=\"n\"\r\n\r\n3\r\n\
+              --hfendpoints\r\nContent-Disposition: form-data; name=\"size\"\r\n\r\n256x256\r\n\
+              --hfendpoints--\r\n",
+        )
+        .await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["data"].as_array().unwrap().len(), 3);
+        assert_eq!(body["data"][2]["url"], "data:image/jpeg;base64,/9j/4A==");
+    }
+
+    #[tokio::test]
+    async fn vary_rejects_invalid_forms() {
+        for (form, message) in [
+            (
+                b"--hfendpoints\r\nContent-Disposition: form-data; name=\"n\"\r\n\r\n2\r\n\
+                  --hfendpoints--\r\n"
+                    .as_slice(),
+                "Required parameter 'image' was not provided",
+            ),
+            (
+                b"--hfendpoints\r\nContent-Disposition: form-data; name=\"image\"; filename=\"cat.jpg\"\r\n\r\n\xFF\xD8\xFF\xE0\r\n\
+                  --hfendpoints\r\nContent-Disposition: form-data; name=\"n\"\r\n\r\nmany\r\n\
+                  --hfendpoints--\r\n"
+                    .as_slice(),
+                "Invalid value for parameter 'n': invalid digit found in string",
+            ),
+            (
+                b"--hfendpoints\r\nContent-Disposition: form-data; name=\"image\"; filename=\"cat.jpg\"\r\n\r\n\r\n\
+                  --hfendpoints--\r\n"
+                    .as_slice(),
+                "Parameter 'image' cannot be an empty file",
+            ),
+        ] {
+            let (status, body) = vary(form).await;
+            assert_eq!(status, StatusCode::BAD_REQUEST);
+            assert_eq!(body["error"]["message"], message);
+        }
+    }
+}
@@ -0,0 +1,460 @@
+//! Journal of the requests accepted by the task routes, so none is lost silently on a crash.
+//!
+//! When `HFENDPOINT_JOURNAL_DIR` is set, every request reaching a task route is recorded in this
+//! directory before being handled: its metadata as `<entry>.json` along with its body, as received
+//! (i.e. still encrypted), spilled to `<entry>.body`. Both are removed once the response is produced,
+//! when the response starts being sent for streamed ones.
+//!
+//! Entries left over are the ones of requests the process accepted but never answered, because it
+//! crashed or was killed. When the endpoint boots again, they are moved to `failed/`, payload
+//! included, and reported failed with a 503 usage record (see `HFENDPOINT_USAGE_SINK`, with the
+//! `usage` feature), the task being the path of the route. Replicas must each get a directory of
+//! their own.
+//!
+//! Which payloads are kept is governed by `HFENDPOINT_PAYLOAD_CAPTURE`, see [`PayloadCapture`].
+use crate::audio::MAX_AUDIO_BODY_SIZE;
+use crate::body::read_limited;
+use crate::capture::{Captured, PayloadCapture, payload_capture_from_env};
+use crate::{OpenAiError, OpenAiResult};
+use axum::body::{Body, Bytes};
+use axum::extract::{Request, State};
+use axum::http::Method;
+use axum::http::header::CONTENT_TYPE;
+use axum::http::request::Parts;
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+#[cfg(feature = "usage")]
+use hfendpoints_core::{UsageRecord, UsageRecorder};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::{error, info, instrument, warn};
+
+/// Environment variable holding the directory the accepted requests are journaled in
+pub const JOURNAL_DIR_ENV: &str = "HFENDPOINT_JOURNAL_DIR";
+
+/// Subdirectory the entries of the requests interrupted by a crash are moved to
+const FAILED_DIR: &str = "failed";
+
+/// Why the requests left over in the journal failed
+const INTERRUPTED: &str = "The endpoint stopped before answering the request";
+
+/// Status reported for the requests left over in the journal
+#[cfg(feature = "usage")]
+const INTERRUPTED_STATUS: u16 = 503;
+
+#[inline]
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|now| now.as_secs())
+        .unwrap_or_default()
+}
+
+/// Request accepted by a task route and not answered yet
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct JournalEntry {
+    /// Correlation ID of the request (`x-request-id`)
+    pub(crate) request_id: String,
+
+    pub(crate) method: String,
+
+    /// Path of the route the request was sent to, i.e. `/api/v1/audio/transcriptions`
+    pub(crate) path: String,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) content_type: Option<String>,
+
+    /// Unix timestamp (in seconds) when the request was received
+    pub(crate) received_at: u64,
+
+    /// File holding the body of the request, as received, if captured
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) payload: Option<PathBuf>,
+
+    /// SHA-256 digest of the body, when hashed rather than captured
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) payload_sha256: Option<String>,
+
+    /// Size of the body, in bytes
+    pub(crate) payload_bytes: u64,
+
+    /// Why the request failed, set once recovered after a crash
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) failure: Option<String>,
+}
+
+/// Directory the requests accepted by the task routes are journaled in
+pub struct Journal {
+    dir: PathBuf,
+
+    /// Prefix of the entries written by this process, distinguishing them from the previous runs
+    run: String,
+    sequence: AtomicU64,
+
+    /// Largest body journaled, bodies being buffered before being spilled
+    limit: usize,
+
+    /// What is kept of the bodies
+    capture: PayloadCapture,
+}
+
+impl Journal {
+    /// Journal the requests in `dir`, created if missing
+    pub fn open(dir: impl Into<PathBuf>) -> OpenAiResult<Self> {
+        let dir = dir.into();
+        std::fs::create_dir_all(dir.join(FAILED_DIR))?;
+
+        let started = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|now| now.as_millis())
+            .unwrap_or_default();
+        Ok(Self {
+            dir,
+            run: format!("{started}-{}", std::process::id()),
+            sequence: AtomicU64::new(0),
+            limit: MAX_AUDIO_BODY_SIZE,
+            capture: PayloadCapture::Full,
+        })
+    }
+
+    /// Journal defined through `HFENDPOINT_JOURNAL_DIR`, requests are not journaled when not set
+    pub fn from_env() -> OpenAiResult<Option<Self>> {
+        match std::env::var(JOURNAL_DIR_ENV) {
+            Ok(dir) if !dir.trim().is_empty() => {
+                info!("Journaling accepted requests in {dir}");
+                let capture = payload_capture_from_env();
+                Self::open(dir).map(|journal| Some(journal.with_capture(capture)))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Reject the bodies larger than `limit` bytes, instead of the largest audio upload
+    pub(crate) fn with_body_limit(mut self, limit: Option<usize>) -> Self {
+        if let Some(limit) = limit {
+            self.limit = limit;
+        }
+        self
+    }
+
+    /// Keep the bodies as defined by `capture`, instead of keeping them all
+    pub fn with_capture(mut self, capture: PayloadCapture) -> Self {
+        self.capture = capture;
+        self
+    }
+
+    /// Move the entries left over by a previous run to `failed/`
+    pub(crate) fn recover(&self) -> Vec<JournalEntry> {
+        let files = match std::fs::read_dir(&self.dir) {
+            Ok(files) => files,
+            Err(err) => {
+                error!("Unable to read the journal {}: {err}", self.dir.display());
+                return Vec::new();
+            }
+        };
+
+        let mut recovered = Vec::new();
+        for path in files.flatten().map(|file| file.path()) {
+            match path.extension().and_then(|extension| extension.to_str()) {
+                Some("json") => match self.fail(&path) {
+                    Ok(entry) => recovered.push(entry),
+                    Err(err) => error!("Unable to recover {}: {err}", path.display()),
+                },
+                // Payloads of the entries which did not make it to the disk, or being written
+                Some("body" | "tmp") if !path.with_extension("json").exists() => {
+                    let _ = std::fs::remove_file(&path);
+                }
+                _ => {}
+            }
+        }
+
+        recovered.sort_by_key(|entry| entry.received_at);
+        for entry in &recovered {
+            error!(
+                "Request {} ({} {}) received at {} was interrupted, recorded in {}",
+                entry.request_id,
+                entry.method,
+                entry.path,
+                entry.received_at,
+                self.dir.join(FAILED_DIR).display()
+            );
+        }
+        recovered
+    }
+
+    /// Move the entry at `path`, along with its payload, to `failed/`
+    fn fail(&self, path: &Path) -> std::io::Result<JournalEntry> {
+        let mut entry: JournalEntry = serde_json::from_slice(&std::fs::read(path)?)?;
+        let failed = self.dir.join(FAILED_DIR);
+
+        if let Some(captured) = entry.payload.take()
+            && let Some(name) = captured.file_name()
+        {
+            let payload = failed.join(name);
+            match std::fs::rename(&captured, &payload) {
+                Ok(()) => entry.payload = Some(payload),
+                Err(err) => warn!("Payload of {} was lost: {err}", path.display()),
+            }
+        }
+        entry.failure = Some(String::from(INTERRUPTED));
+
+        let name = path.file_name().unwrap_or_default();
+        std::fs::write(failed.join(name), serde_json::to_vec_pretty(&entry)?)?;
+        std::fs::remove_file(path)?;
+        Ok(entry)
+    }
+
+    /// Write the entry of the request described by `parts`, spilling its `payload`.
+    /// Returns the path of the entry, the payload being written first so entries always reference one.
+    async fn record(&self, parts: &Parts, payload: &Bytes) -> std::io::Result<PathBuf> {
+        let sequence = self.sequence.fetch_add(1, Ordering::Relaxed);
+        let stem = self.dir.join(format!("{}-{sequence}", self.run));
+        let (captured, payload_sha256) = match self.capture.capture(sequence, payload) {
+            Captured::Payload => (Some(stem.with_extension("body")), None),
+            Captured::Digest(digest) => (None, Some(digest)),
+            Captured::Nothing => (None, None),
+        };
+        let entry = JournalEntry {
+            request_id: parts
+                .headers
+                .get("x-request-id")
+                .and_then(|value| value.to_str().ok())
+                .unwrap_or_default()
+                .to_string(),
+            method: parts.method.to_string(),
+            path: parts.uri.path().to_string(),
+            content_type: parts
+                .headers
+                .get(CONTENT_TYPE)
+                .and_then(|value| value.to_str().ok())
+                .map(String::from),
+            received_at: unix_now(),
+            payload: captured,
+            payload_sha256,
+            payload_bytes: payload.len() as u64,
+            failure: None,
+        };
+
+        if let Some(captured) = &entry.payload {
+            tokio::fs::write(captured, payload).await?;
+        }
+
+        // Entries are renamed once complete, so a crash never leaves a truncated one behind
+        let path = stem.with_extension("json");
+        let partial = stem.with_extension("tmp");
+        tokio::fs::write(&partial, serde_json::to_vec(&entry)?).await?;
+        tokio::fs::rename(&partial, &path).await?;
+        Ok(path)
+    }
+
+    /// Remove the entry at `path` along with its payload, the request being answered
+    async fn complete(&self, path: PathBuf) {
+        for path in [path.clone(), path.with_extension("body")] {
+            if let Err(err) = tokio::fs::remove_file(&path).await
+                && err.kind() != std::io::ErrorKind::NotFound
+            {
+                warn!(
+                    "Unable to remove {} from the journal: {err}",
+                    path.display()
+                );
+            }
+        }
+    }
+}
+
+/// Report the `recovered` requests failed through `usage`
+#[cfg(feature = "usage")]
+pub(crate) fn report_interrupted(recovered: &[JournalEntry], usage: &UsageRecorder) {
+    for entry in recovered {
+        let mut record = UsageRecord::new(entry.request_id.clone(), entry.path.clone())
+            .input_bytes(entry.payload_bytes)
+            .status(INTERRUPTED_STATUS);
+        record.timestamp = entry.received_at;
+        usage.record(record);
+    }
+}
+
+/// Journal the requests sent to a task route while they are handled, see [`Journal`]
+#[instrument(skip_all)]
+pub(crate) async fn journal_requests(
+    State(journal): State<Arc<Journal>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if request.method() != Method::POST {
+        return next.run(request).await;
+    }
+
+    let (parts, body) = request.into_parts();
+    let payload = match read_limited(body, journal.limit).await {
+        Ok(payload) => payload,
+        Err(err) => return err.into_response(),
+    };
+
+    // Requests which could not be journaled are refused, rather than being lost on a crash
+    let entry = match journal.record(&parts, &payload).await {
+        Ok(entry) => entry,
+        Err(err) => {
+            error!("Unable to journal the request: {err}");
+            return OpenAiError::Io(err).into_response();
+        }
+    };
+
+    let response = next
+        .run(Request::from_parts(parts, Body::from(payload)))
+        .await;
+    journal.complete(entry).await;
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::capture::PayloadCapture;
+    #[cfg(feature = "usage")]
+    use crate::journal::report_interrupted;
+    use crate::journal::{Journal, journal_requests};
+    use axum::Router;
+    use axum::body::Body;
+    use axum::extract::State;
+    use axum::http::{Request, StatusCode};
+    use axum::middleware::from_fn_with_state;
+    use axum::routing::post;
+    #[cfg(feature = "usage")]
+    use hfendpoints_core::{UsageError, UsageRecord, UsageRecorder, UsageSink};
+    use std::path::{Path, PathBuf};
+    use std::sync::Arc;
+    #[cfg(feature = "usage")]
+    use std::sync::Mutex;
+    use tower::ServiceExt;
+
+    /// Usage sink keeping the records in memory
+    #[cfg(feature = "usage")]
+    #[derive(Clone, Default)]
+    struct Collect(Arc<Mutex<Vec<UsageRecord>>>);
+
+    #[cfg(feature = "usage")]
+    impl UsageSink for Collect {
+        async fn emit(&mut self, record: &UsageRecord) -> Result<(), UsageError> {
+            self.0.lock().unwrap().push(record.clone());
+            Ok(())
+        }
+    }
+
+    fn directory(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("journal-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn entries(dir: &Path) -> Vec<PathBuf> {
+        std::fs::read_dir(dir)
+            .unwrap()
+            .flatten()
+            .map(|file| file.path())
+            .filter(|path| path.is_file())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn journal_requests_while_handled() {
+        let dir = directory("handled");
+        let journal = Arc::new(Journal::open(&dir).unwrap());
+
+        // The route sees the request journaled along with its payload
+        let route = |State(dir): State<PathBuf>, body: String| async move {
+            let mut entries = entries(&dir);
+            entries.sort();
+            assert_eq!(entries.len(), 2);
+            assert_eq!(std::fs::read_to_string(&entries[0]).unwrap(), "hello");
+            body
+        };
+        let router = Router::new()
+            .route("/echo", post(route).with_state(dir.clone()))
+            .layer(from_fn_with_state(Arc::clone(&journal), journal_requests));
+
+        let request = Request::post("/echo").body(Body::from("hello")).unwrap();
+        let response = router.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(entries(&dir).is_empty());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn recover_interrupted_requests() {
+        let dir = directory("recover");
+        let journal = Journal::open(&dir).unwrap();
+
+        // Entries left over by a crash, along with a payload whose entry never made it to the disk
+        let parts = Request::post("/api/v1/audio/transcriptions")
+            .header("x-request-id", "req-1")
+            .body(())
+            .unwrap()
+            .into_parts()
+            .0;
+        journal.record(&parts, &"audio".into()).await.unwrap();
+        std::fs::write(dir.join("orphan.body"), "partial").unwrap();
+
+        let recovered = Journal::open(&dir).unwrap().recover();
+        assert_eq!(recovered.len(), 1);
+        assert_eq!(recovered[0].request_id, "req-1");
+        assert_eq!(recovered[0].path, "/api/v1/audio/transcriptions");
+        assert!(recovered[0].failure.is_some());
+        assert_eq!(
+            std::fs::read_to_string(recovered[0].payload.as_ref().unwrap()).unwrap(),
+            "audio"
+        );
+
+        assert!(entries(&dir).is_empty());
+        assert_eq!(entries(&dir.join("failed")).len(), 2);
+
+        // Interrupted requests are reported failed to the usage sink
+        #[cfg(feature = "usage")]
+        {
+            let sink = Collect::default();
+            let usage = UsageRecorder::spawn(sink.clone());
+            report_interrupted(&recovered, &usage);
+            usage.flush().await;
+            let records = sink.0.lock().unwrap();
+            assert_eq!(records.len(), 1);
+            assert_eq!(records[0].request_id, "req-1");
+            assert_eq!(records[0].status, 503);
+            assert_eq!(records[0].input_bytes, Some(5));
+        }
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn recover_without_payloads() {
+        let dir = directory("capture");
+        let journal = Journal::open(&dir)
+            .unwrap()
+            .with_capture(PayloadCapture::Hashed);
+
+        let parts = Request::post("/api/v1/embeddings")
+            .body(())
+            .unwrap()
+            .into_parts()
+            .0;
+        journal.record(&parts, &"hello".into()).await.unwrap();
+        assert_eq!(entries(&dir).len(), 1);
+
+        // Only the digest of the payload is dead-lettered
+        let recovered = Journal::open(&dir).unwrap().recover();
+        assert_eq!(recovered.len(), 1);
+        assert_eq!(recovered[0].payload, None);
+        assert_eq!(recovered[0].payload_bytes, 5);
+        assert_eq!(
+            recovered[0].payload_sha256.as_deref(),
+            Some("2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824")
+        );
+        assert_eq!(entries(&dir.join("failed")).len(), 1);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
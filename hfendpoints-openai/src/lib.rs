@@ -1,22 +1,131 @@
 use crate::audio::{AUDIO_DESC, AUDIO_TAG};
-use axum::http::{HeaderName, StatusCode};
+use crate::auth::{authenticate, Authentication};
+#[cfg(feature = "jobs")]
+use crate::batches::batch;
+use crate::batches::{BATCHES_DESC, BATCHES_TAG};
+use crate::body::BodyLimit;
+use crate::chat::{CHAT_DESC, CHAT_TAG};
+use crate::completions::{COMPLETIONS_DESC, COMPLETIONS_TAG};
+use crate::custom::{CUSTOM_DESC, CUSTOM_TAG};
+use crate::embeddings::{EMBEDDINGS_DESC, EMBEDDINGS_TAG};
+use crate::encryption::{decrypt_payload, Decryption};
+use crate::estimate::{ESTIMATE_DESC, ESTIMATE_TAG};
+use crate::images::{IMAGES_DESC, IMAGES_TAG};
+use crate::journal::journal_requests;
+use crate::listener::PeerAddr;
+use crate::metadata::echo_metadata;
+use crate::models::{MODELS_DESC, MODELS_TAG};
+use crate::moderations::{MODERATIONS_DESC, MODERATIONS_TAG};
+use crate::priority::{prioritize, PriorityLane};
+use crate::rerank::{RERANK_DESC, RERANK_TAG};
+use crate::snapshot::export_snapshot;
+use crate::tls::TlsListener;
+#[cfg(feature = "usage")]
+use crate::usage::account_usage;
+use axum::http::{HeaderName, HeaderValue, StatusCode};
+#[cfg(feature = "metrics")]
+use axum::http::header::CONTENT_TYPE;
+use axum::middleware::{from_fn, from_fn_with_state};
+use axum::response::IntoResponse;
+use axum::extract::{DefaultBodyLimit, Request};
+use axum::response::Response;
+use axum::routing::{get, Route};
+use axum::{Extension, Json, Router, ServiceExt};
 use error::OpenAiError;
+#[cfg(feature = "jobs")]
+use hfendpoints_core::JobStore;
+use hfendpoints_core::Readiness;
+#[cfg(feature = "usage")]
+use hfendpoints_core::{usage_recorder_from_env, UsageRecorder};
+use serde::Serialize;
+use std::collections::BTreeSet;
+use std::convert::Infallible;
 use std::fmt::Debug;
-use tokio::net::{TcpListener, ToSocketAddrs};
-use tower::ServiceBuilder;
+use std::future::{Future, IntoFuture};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
+use shutdown::Shutdown;
+use tokio::net::ToSocketAddrs;
+use tokio::select;
+use tower::{Layer, Service, ServiceBuilder};
+use tower_http::cors::CorsLayer;
+use tower_http::limit::RequestBodyLimitLayer;
 use tower_http::request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer};
+use tower_http::set_header::SetResponseHeaderLayer;
 use tower_http::trace::TraceLayer;
-use tracing::instrument;
-use utoipa::OpenApi;
+use tracing::{info, instrument, warn};
+use utoipa::{OpenApi, ToSchema};
 use utoipa_axum::router::OpenApiRouter;
 use utoipa_axum::routes;
 use utoipa_scalar::{Scalar, Servable};
 
-pub(crate) mod audio;
+pub mod audio;
+pub mod batches;
+pub mod chat;
+pub mod completions;
+pub mod custom;
+pub mod embeddings;
+pub mod images;
+pub mod moderations;
+pub mod rerank;
+mod auth;
+mod base64_bytes;
+mod body;
+mod capture;
+mod compression;
+mod config;
 mod context;
+mod deprecation;
+mod encryption;
+mod endpoint;
 mod error;
+mod estimate;
+mod fallback;
 mod headers;
-pub use context::Context;
+mod journal;
+mod listener;
+mod metadata;
+mod methods;
+mod models;
+mod oidc;
+mod multipart;
+#[cfg(feature = "distributed")]
+mod payload;
+mod policy;
+mod priority;
+mod replay;
+mod shutdown;
+mod snapshot;
+mod telemetry;
+#[cfg(test)]
+mod testing;
+mod tls;
+mod usage;
+pub use auth::{ApiKeys, API_KEYS_ENV, AUTH_EXEMPT_ENV};
+pub use capture::{PayloadCapture, PAYLOAD_CAPTURE_ENV};
+pub use config::{
+    EndpointConfig, LogFormat, BODY_LIMIT_ENV, CONFIG_FILE_ENV, INTERFACE_ENV, LOG_FORMAT_ENV,
+    PORT_ENV,
+};
+pub use context::{Context, RequestContext};
+pub use endpoint::{Endpoint, EndpointBuilder, Queued, TaskRequest};
+pub use error::{ErrorDetail, ErrorResponse};
+pub use deprecation::{Deprecate, Deprecation, API_VERSION};
+pub use fallback::{Fallback, X_SERVED_BY};
+pub use journal::{Journal, JOURNAL_DIR_ENV};
+pub use metadata::{Metadata, MAX_METADATA_SIZE, X_METADATA};
+pub use models::{ModelCard, ModelInfo, MODEL_ID_ENV};
+pub use oidc::{
+    Identity, Oidc, OIDC_AUDIENCE_ENV, OIDC_ISSUER_ENV, OIDC_JWKS_URI_ENV, OIDC_TENANT_CLAIM_ENV,
+};
+pub use policy::{RequestPolicy, REQUEST_POLICY_ENV};
+pub use priority::MAX_CONCURRENT_REQUESTS_ENV;
+pub use shutdown::SHUTDOWN_GRACE_PERIOD_ENV;
+pub use snapshot::{Snapshot, SnapshotKey, SNAPSHOT_ENV, SNAPSHOT_KEY_ENV};
+#[cfg(feature = "otel")]
+pub use telemetry::{init_tracing, shutdown_tracing};
+pub use tls::{TlsConfig, TLS_CERT_ENV, TLS_KEY_ENV};
 
 type OpenAiResult<T> = Result<T, OpenAiError>;
 
@@ -36,78 +145,1381 @@ async fn health() -> StatusCode {
     StatusCode::OK
 }
 
+/// Report the status of the endpoint, answering 503 unless `ready`
+fn probe(ready: Result<(), String>) -> (StatusCode, Json<EndpointInfo>) {
+    let (code, status, reason) = match ready {
+        Ok(()) => (StatusCode::OK, EndpointStatus::Ready, None),
+        Err(reason) => (
+            StatusCode::SERVICE_UNAVAILABLE,
+            EndpointStatus::Unavailable,
+            Some(reason),
+        ),
+    };
+    let info = EndpointInfo {
+        status,
+        reason,
+        model: None,
+        tasks: Vec::new(),
+    };
+    (code, Json(info))
+}
+
+#[utoipa::path(
+    method(get, head),
+    path = "/health/live",
+    tag = STATUS_TAG,
+    responses(
+        (status = OK, description = "The process serves", body = EndpointInfo),
+        (status = SERVICE_UNAVAILABLE, description = "A handler loop crashed, the process should be restarted", body = EndpointInfo)
+    )
+)]
+#[instrument(skip(readiness))]
+async fn live(Extension(readiness): Extension<Arc<Readiness>>) -> impl IntoResponse {
+    if readiness.is_live() {
+        probe(Ok(()))
+    } else {
+        probe(Err(String::from("A handler loop crashed")))
+    }
+}
+
+#[utoipa::path(
+    method(get, head),
+    path = "/health/ready",
+    tag = STATUS_TAG,
+    responses(
+        (status = OK, description = "The handler is initialized and able to process requests", body = EndpointInfo),
+        (status = SERVICE_UNAVAILABLE, description = "The handler is initializing, stopped or overloaded, or a task is loading its handler", body = EndpointInfo)
+    )
+)]
+#[instrument(skip(readiness))]
+async fn ready(Extension(readiness): Extension<Arc<Readiness>>) -> impl IntoResponse {
+    let (code, Json(info)) = probe(readiness.check().map_err(|reason| reason.to_string()));
+    let tasks = readiness.tasks().into_iter().map(TaskInfo::from).collect();
+    (code, Json(EndpointInfo { tasks, ..info }))
+}
+
+/// Lifecycle status of the endpoint
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Clone, Copy, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+enum EndpointStatus {
+    /// The handler is loaded and the endpoint accepts requests
+    Ready,
+
+    /// The handler failed to initialize, task routes answer 503
+    Unavailable,
+}
+
+/// Information about the running endpoint
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Clone, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+struct EndpointInfo {
+    /// Current status of the endpoint
+    status: EndpointStatus,
+
+    /// Why the endpoint is not able to serve requests, if any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reason: Option<String>,
+
+    /// The model served by the endpoint, as reported by the handler
+    #[serde(skip_serializing_if = "Option::is_none")]
+    model: Option<ModelInfo>,
+
+    /// Progress of the tasks whose handler the endpoint loads, see `EndpointBuilder::load`
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    tasks: Vec<TaskInfo>,
+}
+
+/// Progress of a task loading its handler
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Clone, Copy, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+enum TaskStatus {
+    /// The handler, i.e. its model, is being loaded
+    Loading,
+
+    /// The handler is loaded and processes the requests of the task
+    Ready,
+
+    /// The handler, or one of the tasks it is loaded after, failed to load
+    Failed,
+}
+
+/// Readiness of a task served by the endpoint
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Clone, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+struct TaskInfo {
+    /// Name of the task, as registered on the endpoint
+    task: String,
+
+    /// Progress of the task loading its handler
+    status: TaskStatus,
+
+    /// Why the handler failed to load, if it did
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reason: Option<String>,
+}
+
+impl From<(String, hfendpoints_core::TaskStatus)> for TaskInfo {
+    fn from((task, status): (String, hfendpoints_core::TaskStatus)) -> Self {
+        let (status, reason) = match status {
+            hfendpoints_core::TaskStatus::Loading => (TaskStatus::Loading, None),
+            hfendpoints_core::TaskStatus::Ready => (TaskStatus::Ready, None),
+            hfendpoints_core::TaskStatus::Failed { reason } => (TaskStatus::Failed, Some(reason)),
+        };
+        Self {
+            task,
+            status,
+            reason,
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/info",
+    tag = STATUS_TAG,
+    responses(
+        (status = OK, description = "Information about the endpoint", body = EndpointInfo)
+    )
+)]
+#[instrument(skip(model))]
+async fn info(model: Option<Extension<Arc<ModelInfo>>>) -> Json<EndpointInfo> {
+    Json(EndpointInfo {
+        status: EndpointStatus::Ready,
+        reason: None,
+        model: model.map(|Extension(model)| model.as_ref().clone()),
+        tasks: Vec::new(),
+    })
+}
+
+#[cfg(feature = "metrics")]
+#[utoipa::path(
+    get,
+    path = "/metrics",
+    tag = STATUS_TAG,
+    responses(
+        (status = OK, description = "Metrics in the Prometheus text exposition format", body = str, content_type = "text/plain; version=0.0.4")
+    )
+)]
+#[instrument]
+async fn metrics() -> impl IntoResponse {
+    (
+        [(CONTENT_TYPE, "text/plain; version=0.0.4")],
+        hfendpoints_core::endpoint_metrics().encode(),
+    )
+}
+
 #[derive(OpenApi)]
 #[openapi(
     info(title = "Hugging Face Inference Endpoint Open AI Compatible Endpoint"),
     tags(
         (name = STATUS_TAG, description = STATUS_DESC),
         (name = AUDIO_TAG, description = AUDIO_DESC),
+        (name = BATCHES_TAG, description = BATCHES_DESC),
+        (name = CHAT_TAG, description = CHAT_DESC),
+        (name = COMPLETIONS_TAG, description = COMPLETIONS_DESC),
+        (name = CUSTOM_TAG, description = CUSTOM_DESC),
+        (name = EMBEDDINGS_TAG, description = EMBEDDINGS_DESC),
+        (name = ESTIMATE_TAG, description = ESTIMATE_DESC),
+        (name = IMAGES_TAG, description = IMAGES_DESC),
+        (name = MODELS_TAG, description = MODELS_DESC),
+        (name = MODERATIONS_TAG, description = MODERATIONS_DESC),
+        (name = RERANK_TAG, description = RERANK_DESC),
     )
 )]
 struct ApiDoc;
 
+/// Interface listened on unless configured through `ServerBuilder::bind`
+const DEFAULT_INTERFACE: &str = "0.0.0.0";
+
+/// Port listened on unless configured through `ServerBuilder::bind`
+const DEFAULT_PORT: u16 = 8000;
+
+/// Middleware registered through `ServerBuilder::layer`, applied once the routes are assembled
+type RouterLayer = Box<dyn FnOnce(Router) -> Router + Send>;
+
+/// OpenAI compatible endpoint, configured through [`ServerBuilder`]
+pub struct OpenAiServer;
+
+impl OpenAiServer {
+    /// Endpoint listening on `0.0.0.0:8000` without any task route, see `ServerBuilder::task`
+    pub fn builder() -> ServerBuilder {
+        ServerBuilder {
+            interface: (String::from(DEFAULT_INTERFACE), DEFAULT_PORT),
+            tasks: Vec::new(),
+            model: ModelCard::default(),
+            body_limit: None,
+            cors: None,
+            auth: None,
+            oidc: None,
+            layers: Vec::new(),
+            tls: None,
+            snapshot: None,
+            restore: None,
+            #[cfg(feature = "usage")]
+            usage: None,
+            journal: None,
+            #[cfg(feature = "jobs")]
+            jobs: None,
+            readiness: hfendpoints_core::readiness(),
+            grace_period: None,
+            concurrency: None,
+        }
+    }
+}
+
+/// Configures the routes served by the endpoint, the middlewares wrapping them and how the
+/// endpoint is exposed.
+///
+/// ```no_run
+/// # use hfendpoints_openai::{ApiKeys, OpenAiServer};
+/// # use utoipa_axum::router::OpenApiRouter;
+/// # async fn run(transcriptions: OpenApiRouter, embeddings: OpenApiRouter) {
+/// OpenAiServer::builder()
+///     .bind(("0.0.0.0", 8080))
+///     .body_limit(32 * 1024 * 1024)
+///     .auth(ApiKeys::new(["my-key"]))
+///     .task(transcriptions)
+///     .task(embeddings)
+///     .serve()
+///     .await
+///     .unwrap();
+/// # }
+/// ```
+pub struct ServerBuilder<A = (String, u16)> {
+    interface: A,
+    tasks: Vec<OpenApiRouter>,
+    model: ModelCard,
+    body_limit: Option<usize>,
+    cors: Option<CorsLayer>,
+    auth: Option<ApiKeys>,
+    oidc: Option<Oidc>,
+    layers: Vec<RouterLayer>,
+    tls: Option<TlsConfig>,
+    snapshot: Option<SnapshotKey>,
+    restore: Option<Snapshot>,
+    #[cfg(feature = "usage")]
+    usage: Option<UsageRecorder>,
+    journal: Option<Journal>,
+    #[cfg(feature = "jobs")]
+    jobs: Option<JobStore>,
+    readiness: Arc<Readiness>,
+    grace_period: Option<Duration>,
+    concurrency: Option<usize>,
+}
+
+impl<A> ServerBuilder<A>
+where
+    A: ToSocketAddrs + Debug,
+{
+    /// Listen on `interface`, unless a socket is inherited from the supervisor, see `listener`
+    pub fn bind<B>(self, interface: B) -> ServerBuilder<B>
+    where
+        B: ToSocketAddrs + Debug,
+    {
+        ServerBuilder {
+            interface,
+            tasks: self.tasks,
+            model: self.model,
+            body_limit: self.body_limit,
+            cors: self.cors,
+            auth: self.auth,
+            oidc: self.oidc,
+            layers: self.layers,
+            tls: self.tls,
+            snapshot: self.snapshot,
+            restore: self.restore,
+            #[cfg(feature = "usage")]
+            usage: self.usage,
+            journal: self.journal,
+            #[cfg(feature = "jobs")]
+            jobs: self.jobs,
+            readiness: self.readiness,
+            grace_period: self.grace_period,
+            concurrency: self.concurrency,
+        }
+    }
+
+    /// Serve the routes of `router` under `/api/v1`, along with the ones registered previously
+    pub fn task<R: Into<OpenApiRouter>>(mut self, router: R) -> Self {
+        self.tasks.push(router.into());
+        self
+    }
+
+    /// Advertise `model` through `/api/v1/models`
+    pub fn model(mut self, model: ModelCard) -> Self {
+        self.model = model;
+        self
+    }
+
+    /// Reject request bodies larger than `limit` bytes, once decompressed, on every route
+    pub fn body_limit(mut self, limit: usize) -> Self {
+        self.body_limit = Some(limit);
+        self
+    }
+
+    /// Answer cross-origin requests, and their preflight, according to `cors`
+    pub fn cors(mut self, cors: CorsLayer) -> Self {
+        self.cors = Some(cors);
+        self
+    }
+
+    /// Require one of the API `keys` on every route except the exempted ones, see `ApiKeys`
+    pub fn auth(mut self, keys: ApiKeys) -> Self {
+        self.auth = Some(keys);
+        self
+    }
+
+    /// Accept as well the tokens issued by the OpenID provider `oidc`, see `Oidc`
+    pub fn oidc(mut self, oidc: Oidc) -> Self {
+        self.oidc = Some(oidc);
+        self
+    }
+
+    /// Wrap every route with `layer`, requests reaching it once authenticated.
+    /// Layers are applied in order, the last one registered seeing the requests first.
+    pub fn layer<L>(mut self, layer: L) -> Self
+    where
+        L: Layer<Route> + Clone + Send + Sync + 'static,
+        L::Service: Service<Request> + Clone + Send + Sync + 'static,
+        <L::Service as Service<Request>>::Response: IntoResponse + 'static,
+        <L::Service as Service<Request>>::Error: Into<Infallible> + 'static,
+        <L::Service as Service<Request>>::Future: Send + 'static,
+    {
+        self.layers
+            .push(Box::new(move |router: Router| router.layer(layer)));
+        self
+    }
+
+    /// Serve HTTPS, negotiating HTTP/2 through ALPN, with the certificate and key of `tls`
+    pub fn tls(mut self, tls: TlsConfig) -> Self {
+        self.tls = Some(tls);
+        self
+    }
+
+    /// Export the manifest of the endpoint through `GET /snapshot`, signed with `key`
+    pub fn snapshot(mut self, key: SnapshotKey) -> Self {
+        self.snapshot = Some(key);
+        self
+    }
+
+    /// Refuse to serve unless the endpoint resolves to the state recorded in `snapshot`
+    pub fn restore(mut self, snapshot: Snapshot) -> Self {
+        self.restore = Some(snapshot);
+        self
+    }
+
+    /// Hand the usage record of every request served by a task route over to `recorder`
+    #[cfg(feature = "usage")]
+    pub fn usage(mut self, recorder: UsageRecorder) -> Self {
+        self.usage = Some(recorder);
+        self
+    }
+
+    /// Record the requests reaching the task routes in `journal` until they are answered, the ones
+    /// left over by a crash being reported failed when the endpoint is assembled
+    pub fn journal(mut self, journal: Journal) -> Self {
+        self.journal = Some(journal);
+        self
+    }
+
+    /// Accept batches of requests through `/api/v1/batches`, persisted in `store` and replayed
+    /// through the task routes while no interactive request waits in the queue
+    #[cfg(feature = "jobs")]
+    pub fn jobs(mut self, store: JobStore) -> Self {
+        self.jobs = Some(store);
+        self
+    }
+
+    /// Report the readiness of the endpoint through `/health/ready` according to `readiness`,
+    /// the one of the handler loops run by this process otherwise
+    pub fn readiness(mut self, readiness: Arc<Readiness>) -> Self {
+        self.readiness = readiness;
+        self
+    }
+
+    /// Give in-flight requests `grace_period` to complete once the process is asked to terminate
+    pub fn shutdown_grace_period(mut self, grace_period: Duration) -> Self {
+        self.grace_period = Some(grace_period);
+        self
+    }
+
+    /// Process at most `limit` requests at once, rejecting the others with 429, the operational
+    /// routes (probes, metrics, administration) being served under a budget of their own
+    pub fn max_concurrent_requests(mut self, limit: usize) -> Self {
+        self.concurrency = Some(limit);
+        self
+    }
+
+    /// Apply the body limit, API keys, concurrency and shutdown grace period of `config`, along
+    /// with the OpenID provider configured through `HFENDPOINT_OIDC_ISSUER`, the interface being
+    /// configured through [`ServerBuilder::bind`]
+    pub fn config(self, config: &EndpointConfig) -> Self {
+        let server = match config.body_limit {
+            Some(limit) => self.body_limit(limit),
+            None => self,
+        };
+        let server = match ApiKeys::configured(&config.api_keys) {
+            Some(keys) => server.auth(keys),
+            None => server,
+        };
+        let server = match Oidc::from_env() {
+            Some(oidc) => server.oidc(oidc),
+            None => server,
+        };
+        server
+            .max_concurrent_requests(config.max_concurrent_requests)
+            .shutdown_grace_period(config.shutdown_grace_period)
+    }
+
+    /// Assemble the task routes along with the status, documentation, `/estimate` and `/models`
+    /// routes, wrapped by the configured middlewares
+    fn into_parts(
+        self,
+    ) -> OpenAiResult<(
+        A,
+        Option<TlsConfig>,
+        impl Service<Request, Response = Response, Error = Infallible, Future: Send>
+        + Clone
+        + Send
+        + 'static,
+    )> {
+        // Correlation-ID middleware (x-request-id)
+        let x_request_id_header_name = HeaderName::from_static("x-request-id");
+
+        if self.tasks.is_empty() {
+            warn!("No task route was registered, only the status routes are served");
+        }
+        let task_router = self
+            .tasks
+            .into_iter()
+            .try_fold(OpenApiRouter::new(), merge_routes)?;
+        let model = self.model.clone();
+
+        // Request policies must target the routes served, see `policy`
+        let paths = &task_router.get_openapi().paths.paths;
+        policy::validate_request_policies(paths.keys().map(String::as_str))?;
+        let model_info = Arc::new(model.info());
+
+        // Responses tell which model served them, the fallback one if the request fell back on it
+        let task_router = task_router.layer(from_fn_with_state(
+            Arc::<str>::from(model.id()),
+            fallback::annotate_served_by,
+        ));
+
+        // Metadata attached by callers is echoed back, once decrypted, see `metadata`
+        let task_router =
+            task_router.layer(from_fn_with_state(self.body_limit, echo_metadata));
+
+        // Encrypted payloads are decrypted before reaching the task routes
+        let decryption = Decryption::from_env();
+        let task_router = match &decryption {
+            Some(decryption) => {
+                task_router.layer(from_fn_with_state(Arc::clone(decryption), decrypt_payload))
+            }
+            None => task_router,
+        };
+
+        // Extractors default to the configured limit, routes setting their own keep it
+        let task_router = match self.body_limit {
+            Some(limit) => task_router.layer(DefaultBodyLimit::max(limit)),
+            None => task_router,
+        };
+
+        // Requests are journaled as received, the ones a crash interrupted being reported failed
+        let task_router = match self.journal {
+            Some(journal) => {
+                #[cfg_attr(not(feature = "usage"), allow(unused_variables))]
+                let recovered = journal.recover();
+                #[cfg(feature = "usage")]
+                if let Some(recorder) = &self.usage {
+                    journal::report_interrupted(&recovered, recorder);
+                }
+                let journal = journal.with_body_limit(self.body_limit);
+                task_router.layer(from_fn_with_state(Arc::new(journal), journal_requests))
+            }
+            None => task_router,
+        };
+
+        // Requests are accounted whatever their outcome, rejected ones included
+        #[cfg(feature = "usage")]
+        let task_router = match self.usage {
+            Some(recorder) => task_router.layer(from_fn_with_state(recorder, account_usage)),
+            None => task_router,
+        };
+
+        // Batches are replayed through the task routes as served, at low priority, see `batches`
+        #[cfg(feature = "jobs")]
+        let task_router = match self.jobs {
+            Some(store) => {
+                let store = Arc::new(store);
+                let routes = task_router.get_openapi().paths.paths.keys().cloned().collect();
+                let (tasks, _) = task_router.clone().split_for_parts();
+                let tasks = tasks
+                    .layer(from_fn(error::openai_errors))
+                    .layer(Extension(Arc::clone(&model_info)))
+                    .layer(Extension(Arc::clone(&self.readiness)));
+                batch::spawn_runner(Arc::clone(&store), Arc::clone(&self.readiness), tasks);
+
+                // Batch files are decrypted when created, their requests are replayed in clear
+                let batches = batch::router(store, routes);
+                let batches = match decryption {
+                    Some(decryption) => {
+                        batches.layer(from_fn_with_state(decryption, decrypt_payload))
+                    }
+                    None => batches,
+                };
+                let batches = match self.body_limit {
+                    Some(limit) => batches.layer(DefaultBodyLimit::max(limit)),
+                    None => batches,
+                };
+                merge_routes(task_router, batches)?
+            }
+            None => task_router,
+        };
+
+        // Default routes
+        let task_router = merge_routes(task_router, models::router(self.model))?;
+        let task_router = merge_routes(task_router, estimate::router())?;
+        let router = OpenApiRouter::with_openapi(ApiDoc::openapi())
+            .nest("/api/v1", task_router)
+            .layer(
+                ServiceBuilder::new()
+                    .layer(SetRequestIdLayer::x_request_id(MakeRequestUuid))
+                    .layer(TraceLayer::new_for_http().make_span_with(telemetry::request_span))
+                    .layer(PropagateRequestIdLayer::new(x_request_id_header_name)),
+            )
+            .routes(routes!(health))
+            .routes(routes!(live))
+            .routes(routes!(ready))
+            .routes(routes!(info))
+            // Task routes bound their requests to the limits of the model
+            .layer(Extension(Arc::clone(&model_info)))
+            .layer(Extension(self.readiness))
+            // Routes with a limit of their own report the lowest of both, see `body`
+            .layer(Extension(BodyLimit(self.body_limit)));
+
+        #[cfg(feature = "metrics")]
+        let router = router.routes(routes!(metrics));
+
+        let (router, mut api) = router.split_for_parts();
+        api.info.description = Some(model_info.describe());
+
+        // The served state is compared to the manifest booted from, and exported when asked for
+        let snapshot = Snapshot::capture(&model, &api);
+        if let Some(restored) = &self.restore {
+            restored.ensure_matches(&snapshot)?;
+            info!("Endpoint matches the restored snapshot");
+        }
+        let router = match &self.snapshot {
+            Some(key) => router.route(
+                "/snapshot",
+                get(export_snapshot).with_state(Arc::new(snapshot.signed(key))),
+            ),
+            None => router,
+        };
+
+        // Documentation route
+        let router = router.merge(Scalar::with_url("/docs", api));
+
+        // Custom middlewares only see authenticated requests
+        let router = self
+            .layers
+            .into_iter()
+            .fold(router, |router, layer| layer(router));
+
+        // Bodies are bounded whatever the extractor reading them, streamed ones included
+        let router = match self.body_limit {
+            Some(limit) => router.layer(RequestBodyLimitLayer::new(limit)),
+            None => router,
+        };
+
+        // API keys and tokens are checked on every route except the probes, see `auth`
+        let router = match Authentication::new(self.auth, self.oidc) {
+            Some(auth) => router.layer(from_fn_with_state(Arc::new(auth), authenticate)),
+            None => router,
+        };
+
+        // OPTIONS/HEAD probes handling and API versioning, wrapping the whole router as they must
+        // see the responses produced by the routing itself (405) which are not covered by Router::layer.
+        // CORS preflights are answered before reaching them, as they do not carry any API key.
+        // Failures answered by the routing or the extractors are given the OpenAI error body.
+        // Probes are answered whatever the load, through their own lane, see `priority`.
+        let lane = self
+            .concurrency
+            .map(|capacity| from_fn_with_state(Arc::new(PriorityLane::new(capacity)), prioritize));
+        let service = ServiceBuilder::new()
+            .option_layer(lane)
+            .option_layer(self.cors)
+            .layer(SetResponseHeaderLayer::overriding(
+                deprecation::X_API_VERSION,
+                HeaderValue::from_static(API_VERSION),
+            ))
+            .layer(from_fn(error::openai_errors))
+            .layer(from_fn(methods::allowed_methods))
+            .service(router);
+
+        Ok((self.interface, self.tls, service))
+    }
+
+    /// Serve the endpoint until the process is asked to terminate, see `shutdown`
+    #[instrument(skip_all)]
+    pub async fn serve(self) -> OpenAiResult<()> {
+        let shutdown = match self.grace_period {
+            Some(grace_period) => Shutdown::new(grace_period),
+            None => Shutdown::from_env(),
+        };
+        let (interface, tls, service) = self.into_parts()?;
+
+        let listener = listener::bind(interface).await?;
+        let service = service.into_make_service_with_connect_info::<PeerAddr>();
+        let server: Pin<Box<dyn Future<Output = std::io::Result<()>> + Send>> = match tls {
+            Some(tls) => Box::pin(
+                axum::serve(TlsListener::new(listener, tls)?, service)
+                    .with_graceful_shutdown(shutdown.signal())
+                    .into_future(),
+            ),
+            None => Box::pin(
+                axum::serve(listener, service)
+                    .with_graceful_shutdown(shutdown.signal())
+                    .into_future(),
+            ),
+        };
+
+        // Requests still running after the grace period are dropped along with the server
+        select! {
+            result = server => result?,
+            _ = shutdown.deadline() => {},
+        }
+
+        info!("Server stopped");
+
+        #[cfg(feature = "otel")]
+        shutdown_tracing();
+        Ok(())
+    }
+}
+
+/// Merge the routes of `other` into `router`, failing on the paths both would answer rather than
+/// letting axum panic on them
+fn merge_routes(router: OpenApiRouter, other: OpenApiRouter) -> OpenAiResult<OpenApiRouter> {
+    let served = &router.get_openapi().paths.paths;
+    let overlapping: BTreeSet<_> = other
+        .get_openapi()
+        .paths
+        .paths
+        .keys()
+        .filter(|path| served.keys().any(|served| overlap(served, path)))
+        .collect();
+    if !overlapping.is_empty() {
+        return Err(OpenAiError::Config(format!(
+            "Routes {overlapping:?} are served by several tasks"
+        )));
+    }
+    Ok(router.merge(other))
+}
+
+/// Whether some request path would match both routes, a capture matching any segment and a
+/// wildcard any remainder
+fn overlap(left: &str, right: &str) -> bool {
+    let mut left = left.split('/');
+    let mut right = right.split('/');
+    loop {
+        match (left.next(), right.next()) {
+            (None, None) => return true,
+            (Some(l), Some(r)) if l.starts_with("{*") || r.starts_with("{*") => return true,
+            (Some(l), Some(r)) if l == r || l.starts_with('{') || r.starts_with('{') => {}
+            _ => return false,
+        }
+    }
+}
+
+/// Serve `task_router` on `interface`, along with the status, documentation, `/estimate` and
+/// `/models` routes advertising the deployed `model`.
+///
+/// The body limit, API keys and shutdown grace period are resolved through [`EndpointConfig::from_env`],
+/// from the configuration file referenced by `HFENDPOINT_CONFIG` and the environment. HTTPS,
+/// snapshots, the journal and the batches are configured through the environment, see
+/// `TlsConfig::from_env`, `Snapshot::from_env`, `Journal::from_env` and `JobStore::from_env`, use
+/// [`OpenAiServer::builder`] to configure them programmatically.
 #[instrument(skip(task_router))]
-pub async fn serve_openai<A, R>(interface: A, task_router: R) -> OpenAiResult<()>
+pub async fn serve_openai<A, R>(interface: A, task_router: R, model: ModelCard) -> OpenAiResult<()>
 where
     A: ToSocketAddrs + Debug,
     R: Into<OpenApiRouter>,
 {
-    // Correlation-ID middleware (x-request-id)
-    let x_request_id_header_name = HeaderName::from_static("x-request-id");
-
-    // Default routes
-    let (router, api) = OpenApiRouter::with_openapi(ApiDoc::openapi())
-        .nest("/api/v1", task_router.into())
-        .layer(
-            ServiceBuilder::new()
-                .layer(TraceLayer::new_for_http())
-                .layer(SetRequestIdLayer::x_request_id(MakeRequestUuid))
-                .layer(PropagateRequestIdLayer::new(x_request_id_header_name)),
-        )
-        .routes(routes!(health))
-        .split_for_parts();
-
-    // Documentation route
-    let router = router.merge(Scalar::with_url("/docs", api));
-
-    let listener = TcpListener::bind(interface).await?;
-    axum::serve(listener, router).await?;
+    serve_openai_tasks(interface, [task_router], model).await
+}
+
+/// Serve every router of `task_routers` on `interface`, i.e. transcriptions and translations
+/// for Whisper-like models, as done by [`serve_openai`] for a single one.
+#[instrument(skip(task_routers))]
+pub async fn serve_openai_tasks<A, R>(
+    interface: A,
+    task_routers: impl IntoIterator<Item = R>,
+    model: ModelCard,
+) -> OpenAiResult<()>
+where
+    A: ToSocketAddrs + Debug,
+    R: Into<OpenApiRouter>,
+{
+    let config = EndpointConfig::from_env()?;
+    let server = task_routers.into_iter().fold(
+        OpenAiServer::builder()
+            .bind(interface)
+            .model(model)
+            .config(&config),
+        ServerBuilder::task,
+    );
+    let server = match TlsConfig::from_env()? {
+        Some(tls) => server.tls(tls),
+        None => server,
+    };
+    let server = match SnapshotKey::from_env() {
+        Some(key) => server.snapshot(key),
+        None => server,
+    };
+    let server = match Snapshot::from_env()? {
+        Some(snapshot) => server.restore(snapshot),
+        None => server,
+    };
+    let server = match Journal::from_env()? {
+        Some(journal) => server.journal(journal),
+        None => server,
+    };
+    #[cfg(feature = "jobs")]
+    let server = match JobStore::from_env()? {
+        Some(store) => server.jobs(store),
+        None => server,
+    };
+    #[cfg(feature = "usage")]
+    let usage = usage_recorder_from_env();
+    #[cfg(feature = "usage")]
+    let server = match &usage {
+        Some(recorder) => server.usage(recorder.clone()),
+        None => server,
+    };
+
+    let served = server.serve().await;
+
+    // The records of the last requests are emitted before the process exits
+    #[cfg(feature = "usage")]
+    if let Some(recorder) = usage {
+        recorder.flush().await;
+    }
+    served
+}
+
+/// Routes served when the handler failed to initialize: `/health` and `/health/ready` report the
+/// endpoint as unavailable, `/info` exposes the failure `reason` and every other route answers 503.
+/// The process keeps being live, restarting it would fail the same way.
+///
+/// Only the last line of `reason` is exposed, the kind and message of the exception ending a
+/// Python traceback: the frames above it reveal the paths and source of the handler to callers
+/// which are not authenticated, they are only logged.
+fn unavailable_router(reason: String) -> Router {
+    let reason: Arc<str> = Arc::from(
+        reason
+            .lines()
+            .rev()
+            .map(str::trim)
+            .find(|line| !line.is_empty())
+            .unwrap_or("Unknown failure"),
+    );
+    let info = EndpointInfo {
+        status: EndpointStatus::Unavailable,
+        reason: Some(reason.to_string()),
+        model: None,
+        tasks: Vec::new(),
+    };
+
+    Router::new()
+        .route("/health", get(|| async { StatusCode::SERVICE_UNAVAILABLE }))
+        .route("/health/live", get(|| async { probe(Ok(())) }))
+        .route("/health/ready", get(|| async { StatusCode::SERVICE_UNAVAILABLE }))
+        .route("/info", get(|| async move { Json(info) }))
+        .fallback(|| async move {
+            let message = format!("Endpoint failed to initialize: {reason}");
+            let body = ErrorResponse::new(message, "server_error", "service_unavailable");
+            (StatusCode::SERVICE_UNAVAILABLE, Json(body)).into_response()
+        })
+        .layer(TraceLayer::new_for_http())
+}
+
+/// Keep serving HTTP on `interface` while the handler could not be created, so the failure
+/// `reason` is observable through `/info` instead of the process exiting.
+#[instrument(skip(reason))]
+pub async fn serve_unavailable<A>(interface: A, reason: String) -> OpenAiResult<()>
+where
+    A: ToSocketAddrs + Debug,
+{
+    warn!("Serving endpoint in degraded mode: {reason}");
+
+    // Probes keep reaching the endpoint over HTTPS when it is configured
+    let listener = listener::bind(interface).await?;
+    let shutdown = Shutdown::from_env();
+    match TlsConfig::from_env()? {
+        Some(tls) => {
+            axum::serve(TlsListener::new(listener, tls)?, unavailable_router(reason))
+                .with_graceful_shutdown(shutdown.signal())
+                .await?
+        }
+        None => {
+            axum::serve(listener, unavailable_router(reason))
+                .with_graceful_shutdown(shutdown.signal())
+                .await?
+        }
+    }
     Ok(())
 }
 
+#[cfg(test)]
+mod tests {
+    use crate::priority::DEFAULT_MAX_CONCURRENT_REQUESTS;
+    #[cfg(feature = "usage")]
+    use crate::telemetry::record_task;
+    use crate::{unavailable_router, ApiKeys, ErrorResponse, OpenAiServer};
+    use axum::body::{to_bytes, Body};
+    #[cfg(feature = "usage")]
+    use axum::http::header::CONTENT_LENGTH;
+    use axum::http::header::{
+        ACCESS_CONTROL_ALLOW_ORIGIN, ACCESS_CONTROL_REQUEST_METHOD, AUTHORIZATION, ORIGIN,
+    };
+    use axum::http::{HeaderName, HeaderValue, Method, Request, StatusCode};
+    use axum::routing::{self, post};
+    use hfendpoints_core::Readiness;
+    #[cfg(feature = "usage")]
+    use hfendpoints_core::{UsageError, UsageRecord, UsageRecorder, UsageSink};
+    #[cfg(feature = "usage")]
+    use std::sync::Mutex;
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
+    use tower::ServiceExt;
+    use tower_http::cors::CorsLayer;
+    use tower_http::set_header::SetResponseHeaderLayer;
+    use utoipa_axum::router::OpenApiRouter;
+
+    async fn get(uri: &str) -> (StatusCode, String) {
+        let request = Request::get(uri).body(Body::empty()).unwrap();
+        let traceback = concat!(
+            "Traceback (most recent call last):\n",
+            "  File \"/app/handler.py\", line 1, in <module>\n",
+            "    import torch\n",
+            "ImportError: No module named 'torch'\n",
+        );
+        let response = unavailable_router(String::from(traceback))
+            .oneshot(request)
+            .await
+            .unwrap();
+
+        let status = response.status();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    #[tokio::test]
+    async fn builder_serves_every_task_behind_layers() {
+        let (_, _, service) = OpenAiServer::builder()
+            .task(OpenApiRouter::new().route("/ping", routing::get(|| async { "ping" })))
+            .task(OpenApiRouter::new().route("/echo", post(|body: String| async { body })))
+            .layer(SetResponseHeaderLayer::overriding(
+                HeaderName::from_static("x-custom"),
+                HeaderValue::from_static("1"),
+            ))
+            .auth(ApiKeys::new(["key-1"]))
+            .body_limit(16)
+            .cors(CorsLayer::permissive())
+            .into_parts()
+            .unwrap();
+
+        let request = |method: Method, uri: &str, body: &'static str| {
+            Request::builder()
+                .method(method)
+                .uri(uri)
+                .header(AUTHORIZATION, "Bearer key-1")
+                .body(Body::from(body))
+                .unwrap()
+        };
+
+        let response = service
+            .clone()
+            .oneshot(request(Method::GET, "/api/v1/ping", ""))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["x-custom"], "1");
+
+        let response = service
+            .clone()
+            .oneshot(request(Method::POST, "/api/v1/echo", "hello"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = service
+            .clone()
+            .oneshot(request(Method::POST, "/api/v1/echo", "a body over the limit"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+        // Failures answered by the layers and the routing carry the OpenAI error body as well
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: ErrorResponse = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body.error.code.as_deref(), Some("payload_too_large"));
+
+        let response = service
+            .clone()
+            .oneshot(request(Method::GET, "/api/v1/unknown", ""))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: ErrorResponse = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body.error.kind, "invalid_request_error");
+        assert_eq!(body.error.code.as_deref(), Some("not_found"));
+
+        // Requests without API key are rejected before reaching the custom layers
+        let unauthenticated = Request::get("/api/v1/ping").body(Body::empty()).unwrap();
+        let response = service.clone().oneshot(unauthenticated).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert!(!response.headers().contains_key("x-custom"));
+
+        // CORS preflights do not carry the API key
+        let preflight = Request::options("/api/v1/echo")
+            .header(ORIGIN, "https://example.com")
+            .header(ACCESS_CONTROL_REQUEST_METHOD, "POST")
+            .body(Body::empty())
+            .unwrap();
+        let response = service.oneshot(preflight).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[ACCESS_CONTROL_ALLOW_ORIGIN], "*");
+    }
+
+    /// Usage sink keeping the records in memory
+    #[cfg(feature = "usage")]
+    #[derive(Clone, Default)]
+    struct Collect(Arc<Mutex<Vec<UsageRecord>>>);
+
+    #[cfg(feature = "usage")]
+    impl UsageSink for Collect {
+        async fn emit(&mut self, record: &UsageRecord) -> Result<(), UsageError> {
+            self.0.lock().unwrap().push(record.clone());
+            Ok(())
+        }
+    }
+
+    #[cfg(feature = "usage")]
+    #[tokio::test]
+    async fn builder_accounts_task_requests() {
+        let sink = Collect::default();
+        let recorder = UsageRecorder::spawn(sink.clone());
+        let echo = |body: String| async move {
+            record_task("echo");
+            body
+        };
+        let (_, _, service) = OpenAiServer::builder()
+            .task(OpenApiRouter::new().route("/echo", post(echo)))
+            .usage(recorder.clone())
+            .into_parts()
+            .unwrap();
+
+        let request = Request::post("/api/v1/echo")
+            .header("x-request-id", "req-1")
+            .header(CONTENT_LENGTH, 5)
+            .body(Body::from("hello"))
+            .unwrap();
+        let response = service.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        // Routes other than the task ones are not accounted
+        let request = Request::get("/health").body(Body::empty()).unwrap();
+        service.oneshot(request).await.unwrap();
+        recorder.flush().await;
+
+        let records = sink.0.lock().unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].request_id, "req-1");
+        assert_eq!(records[0].task, "echo");
+        assert_eq!(records[0].input_bytes, Some(5));
+        assert_eq!(records[0].status, 200);
+    }
+
+    #[tokio::test]
+    async fn probes_report_readiness_of_the_handler() {
+        let readiness = Arc::new(Readiness::new(None));
+        let (_, _, service) = OpenAiServer::builder()
+            .readiness(Arc::clone(&readiness))
+            .into_parts()
+            .unwrap();
+        let probe = |uri: &'static str| {
+            let service = service.clone();
+            async move {
+                let request = Request::get(uri).body(Body::empty()).unwrap();
+                let response = service.oneshot(request).await.unwrap();
+                let status = response.status();
+                let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+                let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+                (status, body)
+            }
+        };
+
+        // Live while the handler initializes, but not ready
+        assert_eq!(probe("/health/live").await.0, StatusCode::OK);
+        let (status, body) = probe("/health/ready").await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(body["reason"], "The handler is still initializing");
+
+        readiness.handler_initialized();
+        let alive = readiness.track_loop();
+        let (status, body) = probe("/health/ready").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["status"], "ready");
+
+        // A crashed handler loop is neither ready nor live anymore
+        drop(alive);
+        assert_eq!(probe("/health/ready").await.0, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(probe("/health/live").await.0, StatusCode::SERVICE_UNAVAILABLE);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn probes_stay_responsive_at_full_load() {
+        let readiness = Arc::new(Readiness::new(None));
+        readiness.handler_initialized();
+        let _alive = readiness.track_loop();
+
+        // Requests wait for their outcome as long as the handler loops take to produce it
+        let (_, _, service) = OpenAiServer::builder()
+            .task(OpenApiRouter::new().route(
+                "/heavy",
+                post(|| async {
+                    tokio::time::sleep(Duration::from_millis(300)).await;
+                    "done"
+                }),
+            ))
+            .readiness(readiness)
+            .max_concurrent_requests(DEFAULT_MAX_CONCURRENT_REQUESTS)
+            .into_parts()
+            .unwrap();
+
+        let flood: Vec<_> = (0..2 * DEFAULT_MAX_CONCURRENT_REQUESTS)
+            .map(|_| {
+                let request = Request::post("/api/v1/heavy").body(Body::empty()).unwrap();
+                tokio::spawn(service.clone().oneshot(request))
+            })
+            .collect();
+
+        for _ in 0..5 {
+            let probes: Vec<_> = ["/health", "/health/live", "/health/ready", "/info"]
+                .into_iter()
+                .map(|uri| {
+                    let request = Request::get(uri).body(Body::empty()).unwrap();
+                    let started = Instant::now();
+                    let response = tokio::spawn(service.clone().oneshot(request));
+                    async move { (uri, response.await.unwrap().unwrap().status(), started.elapsed()) }
+                })
+                .collect();
+
+            for probe in probes {
+                let (uri, status, elapsed) = probe.await;
+                assert_eq!(status, StatusCode::OK, "{uri}");
+                assert!(elapsed < Duration::from_millis(100), "{uri} took {elapsed:?}");
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+
+        // Requests over the budget were rejected right away instead of piling up
+        let mut statuses = Vec::new();
+        for response in flood {
+            statuses.push(response.await.unwrap().unwrap().status());
+        }
+        assert!(statuses.contains(&StatusCode::OK));
+        assert!(statuses.contains(&StatusCode::TOO_MANY_REQUESTS));
+    }
+
+    #[tokio::test]
+    async fn unavailable_health_reports_service_unavailable() {
+        let (status, _) = get("/health").await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+
+        let (status, _) = get("/health/ready").await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+
+        let (status, _) = get("/health/live").await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn unavailable_info_exposes_reason() {
+        let (status, body) = get("/info").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            body,
+            r#"{"status":"unavailable","reason":"ImportError: No module named 'torch'"}"#
+        );
+    }
+
+    #[tokio::test]
+    async fn unavailable_task_routes_answer_service_unavailable() {
+        let (status, body) = get("/api/v1/audio/transcriptions").await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+
+        let body: ErrorResponse = serde_json::from_str(&body).unwrap();
+        assert!(body.error.message.contains("No module named 'torch'"));
+        assert!(!body.error.message.contains("/app/handler.py"));
+        assert_eq!(body.error.code.as_deref(), Some("service_unavailable"));
+    }
+}
+
 #[cfg(feature = "python")]
 pub mod python {
+    use crate::endpoint::{self, spawn_looper, Mount, Queued};
+    use crate::models::python::handler_model_id;
+    use crate::{
+        EndpointConfig, Fallback, OpenAiError, Snapshot, SnapshotKey, SNAPSHOT_KEY_ENV,
+    };
     use hfendpoints_binding_python::tokio::create_multithreaded_runtime;
     use hfendpoints_binding_python::ImportablePyModuleBuilder;
-    use hfendpoints_core::Endpoint;
+    use hfendpoints_core::{
+        negotiate_protocol_version, request_channel, workers_from_env, Error, Handler,
+        RequestReceiver, RequestSender, WorkerPool, HANDLER_PROTOCOL_VERSION,
+        MIN_HANDLER_PROTOCOL_VERSION,
+    };
+    use pyo3::exceptions::{PyRuntimeError, PyValueError};
     use pyo3::prelude::*;
     use pyo3::prepare_freethreaded_python;
+    use pyo3::types::{PyDict, PyList, PyTuple};
     use pyo3_async_runtimes::tokio::init;
     use pyo3_async_runtimes::TaskLocals;
+    use std::collections::BTreeMap;
+    use std::sync::{Arc, Mutex, PoisonError};
+    use std::time::Duration;
     use tokio::sync::OnceCell;
-    use tracing::instrument;
+    use tokio::task::JoinHandle;
+    use tracing::{error, info, instrument, warn};
+    use utoipa_axum::router::OpenApiRouter;
 
     pub(crate) static TASK_LOCALS: OnceCell<TaskLocals> = OnceCell::const_new();
 
     macro_rules! impl_pyhandler {
         ($request: ident, $response: ident) => {
             use crate::python::TASK_LOCALS;
-            use hfendpoints_core::{Error, Handler};
-            use pyo3_async_runtimes::TaskLocals;
-            use std::process;
-            use tokio::sync::OnceCell;
-            use tracing::{debug, info, instrument};
+            use hfendpoints_core::{max_batch_size_from_env, Batcher, Error, Handler, ResponseSender};
+            use crate::python::{Generator, TextDelta};
+            use pyo3::exceptions::{PyStopAsyncIteration, PyStopIteration};
+            use std::sync::OnceLock;
+            use tracing::{debug, instrument};
 
             /// Wraps the underlying, Python's heap-allocated, object in a GIL independent way
             /// to be shared between threads.
             ///
             /// `PyHandler` implements the `Handler` trait which forwards the request handling
             /// logic back to Python through the `hfendpoints.Handler` protocol enforcing
-            /// implementation of `__call__` method.
+            /// implementation of `__call__` method, either as a coroutine or an async generator
+            /// when the handler streams several responses back. Plain `def __call__` handlers are
+            /// supported too, and invoked from a blocking thread not to stall the other requests,
+            /// as well as the generators they return which are iterated from a blocking thread.
+            /// Streamed responses may be plain strings, see [`TextDelta`].
+            ///
+            /// Handlers defining `__call_batch__` are given the queued requests in batches instead,
+            /// a list of `(request, ctx)` tuples per call, amortizing the cost of acquiring the GIL
+            /// and converting the requests over the batch, see [`Batcher`].
             ///
             pub struct PyHandler {
                 /// Python allocated object with `Handler` protocol implementation, GIL-independent
                 inner: PyObject,
+
+                /// Whether `__call__` is a plain function, rather than a coroutine or async generator
+                blocking: bool,
+
+                /// Maximum number of requests per `__call_batch__`, if the handler defines it
+                max_batch_size: Option<usize>,
+
+                /// Batcher feeding `__call_batch__`, spawned with the first request
+                batcher: OnceLock<Batcher<($request, Context), $response>>,
+            }
+
+            impl PyHandler {
+                fn new(py: Python<'_>, inner: PyObject) -> PyResult<Self> {
+                    let inspect = py.import("inspect")?;
+                    let handler = inner.bind(py);
+                    let call = handler.getattr("__call__")?;
+
+                    let mut asynchronous = false;
+                    for predicate in ["iscoroutinefunction", "isasyncgenfunction"] {
+                        let predicate = inspect.getattr(predicate)?;
+                        asynchronous |= predicate.call1((handler,))?.is_truthy()?
+                            || predicate.call1((&call,))?.is_truthy()?;
+                    }
+
+                    let batched = handler.hasattr("__call_batch__")?;
+                    Ok(Self {
+                        inner,
+                        blocking: !asynchronous,
+                        max_batch_size: batched.then(max_batch_size_from_env),
+                        batcher: OnceLock::new(),
+                    })
+                }
+
+                /// Await the Python `awaitable` through tokio on the event loop bound to the endpoint
+                async fn resolve(awaitable: PyObject) -> Result<PyObject, Error> {
+                    // Retrieve the current event loop
+                    let locals = Python::with_gil(|py| TASK_LOCALS.get().unwrap().clone_ref(py));
+                    let future = Python::with_gil(|py| {
+                        pyo3_async_runtimes::into_future_with_locals(&locals, awaitable.into_bound(py))
+                    })?;
+
+                    pyo3_async_runtimes::tokio::get_runtime()
+                        .spawn(pyo3_async_runtimes::tokio::scope(locals, future))
+                        .await
+                        .map_err(|err| PyRuntimeError::new_err(err.to_string()))?
+                        .map_err(Error::from)
+                }
+
+                /// Invoke the Python handler's `__call__`, returning either a coroutine, an async generator
+                /// or, for blocking handlers, the response itself
+                async fn call(&self, request: <Self as Handler>::Request) -> Result<PyObject, Error> {
+                    let (request, ctx) = request;
+                    let called = if self.blocking {
+                        let inner = Python::with_gil(|py| self.inner.clone_ref(py));
+                        tokio::task::spawn_blocking(move || {
+                            Python::with_gil(|py| inner.call1(py, (request, ctx)))
+                        })
+                        .await
+                        .map_err(|err| PyRuntimeError::new_err(err.to_string()))?
+                    } else {
+                        Python::with_gil(|py| self.inner.call1(py, (request, ctx)))
+                    };
+
+                    called.map_err(Error::from)
+                }
+
+                /// Batcher handing the queued requests over to `__call_batch__`, if the handler defines it
+                fn batcher(&self) -> Option<&Batcher<($request, Context), $response>> {
+                    let max_batch_size = self.max_batch_size?;
+                    Some(self.batcher.get_or_init(|| {
+                        let inner = std::sync::Arc::new(Python::with_gil(|py| self.inner.clone_ref(py)));
+                        Batcher::spawn(max_batch_size, move |requests| {
+                            Self::call_batch(std::sync::Arc::clone(&inner), requests)
+                        })
+                    }))
+                }
+
+                /// Invoke the Python handler's `__call_batch__` from a blocking thread with all the
+                /// `requests` at once, converted while holding the GIL a single time, and extract the
+                /// response of each of them. An exception raised by the handler fails the whole batch.
+                async fn call_batch(
+                    inner: std::sync::Arc<PyObject>,
+                    requests: Vec<($request, Context)>,
+                ) -> Vec<Result<$response, Error>> {
+                    let size = requests.len();
+                    let called = tokio::task::spawn_blocking(move || {
+                        Python::with_gil(|py| inner.call_method1(py, "__call_batch__", (requests,)))
+                    })
+                    .await
+                    .map_err(|err| PyRuntimeError::new_err(err.to_string()))
+                    .and_then(|called| called)
+                    .map_err(Error::from);
+
+                    let responses = match called {
+                        Ok(called) => Self::settle(called).await,
+                        Err(err) => Err(err),
+                    };
+
+                    Python::with_gil(|py| {
+                        match responses.and_then(|responses| Ok(responses.extract::<Vec<PyObject>>(py)?)) {
+                            Ok(responses) => responses.into_iter().map(Self::extract).collect(),
+                            Err(Error::PythonError(err)) => {
+                                (0..size).map(|_| Err(Error::PythonError(err.clone_ref(py)))).collect()
+                            }
+                            Err(err) => {
+                                error!("Failed to process a batch of {size} request(s): {err}");
+                                (0..size).map(|_| Err(Error::NoResponse)).collect()
+                            }
+                        }
+                    })
+                }
+
+                /// Await the outcome of `__call__` if it is awaitable, blocking handlers return the response directly
+                async fn settle(called: PyObject) -> Result<PyObject, Error> {
+                    let awaitable = Python::with_gil(|py| called.bind(py).hasattr("__await__"))?;
+                    if awaitable {
+                        Self::resolve(called).await
+                    } else {
+                        Ok(called)
+                    }
+                }
+
+                /// We are downcasting from Python object to Rust typed type, strings being
+                /// the text generated since the previous response
+                #[inline]
+                fn extract(response: PyObject) -> Result<$response, Error> {
+                    Python::with_gil(|py| match response.extract::<$response>(py) {
+                        Ok(response) => Ok(response),
+                        Err(err) => response
+                            .extract::<String>(py)
+                            .ok()
+                            .and_then(<$response as TextDelta>::from_text_delta)
+                            .ok_or(Error::from(err)),
+                    })
+                }
+
+                /// Iterate over the plain generator returned by a blocking handler from a blocking
+                /// thread, the GIL being only held to advance it, sending every response through `egress`.
+                /// The generator is advanced no faster than the client reads the responses.
+                async fn iterate(generator: PyObject, egress: ResponseSender<$response>) {
+                    let iterated = tokio::task::spawn_blocking(move || {
+                        let generator = Generator::new(generator, false);
+                        loop {
+                            let next = Python::with_gil(|py| match generator.inner().call_method0(py, "__next__") {
+                                Ok(response) => Some(Self::extract(response)),
+                                Err(err) if err.is_instance_of::<PyStopIteration>(py) => None,
+                                Err(err) => Some(Err(Error::from(err))),
+                            });
+                            let Some(response) = next else {
+                                debug!("[NATIVE] Handler's generator (__call__) exhausted");
+                                generator.exhausted();
+                                break;
+                            };
+
+                            let response = response.map_err(Self::raised);
+                            let failed = response.is_err();
+                            if egress.blocking_send(response).is_err() {
+                                debug!("Client went away, stop iterating over the handler's generator");
+                                break;
+                            }
+
+                            if failed {
+                                break;
+                            }
+                        }
+                    })
+                    .await;
+
+                    if let Err(err) = iterated {
+                        error!("Handler's generator panicked: {err}");
+                    }
+                }
+
+                /// Turn the exception raised by the Python handler into a structured error, logging its traceback
+                fn raised(err: Error) -> Error {
+                    match err {
+                        Error::PythonError(err) => {
+                            let err = Error::from_exception(err);
+                            if let Error::HandlerException { kind, message, traceback } = &err {
+                                error!(
+                                    "Handler raised {kind}: {message}\n{}",
+                                    traceback.as_deref().unwrap_or_default()
+                                );
+                            }
+                            err
+                        }
+                        err => err,
+                    }
+                }
             }
 
             impl Handler for PyHandler {
@@ -119,40 +1531,110 @@ pub mod python {
                     &self,
                     request: Self::Request,
                 ) -> Result<Self::Response, Error> {
-                    // Retrieve the current event loop
-                    let locals = Python::with_gil(|py| TASK_LOCALS.get().unwrap().clone_ref(py));
+                    // Queue wait and handler time are recorded on the request span
+                    let ctx = request.1.clone();
+                    ctx.handled(async move {
+                        if let Some(batcher) = self.batcher() {
+                            return batcher.submit(request).await.map_err(Self::raised);
+                        }
 
-                    let (request, ctx) = request;
+                        // Create the coroutine on Python side to await through tokio
+                        let coro = self.call(request).await.map_err(Self::raised)?;
+                        debug!("[NATIVE] asyncio Handler's coroutine (__call__) created");
 
-                    // Create the coroutine on Python side to await through tokio
-                    let coro = Python::with_gil(|py| {
-                        let py_coro_call = self.inner.call1(py, (request, ctx))?.into_bound(py);
+                        let response = Self::settle(coro).await.map_err(Self::raised)?;
 
-                        debug!("[NATIVE] asyncio Handler's coroutine (__call__) created");
-                        pyo3_async_runtimes::into_future_with_locals(&locals, py_coro_call)
+                        debug!("[NATIVE] asyncio Handler's coroutine (__call__) done");
+                        Self::extract(response).map_err(Self::raised)
                     })
-                    .inspect_err(|err| {
-                        error!("Failed to retrieve __call__ coroutine: {err}");
-                    })?;
+                    .await
+                }
 
-                    pyo3_async_runtimes::tokio::get_runtime()
-                        .spawn(async {
-                            // Schedule the coroutine
-                            let response = pyo3_async_runtimes::tokio::scope(locals, coro)
-                                .await
-                                .inspect_err(|err| {
-                                    error!("Failed to execute __call__: {err}");
-                                })?;
+                #[instrument(skip_all)]
+                async fn on_stream(&self, request: Self::Request, egress: ResponseSender<Self::Response>) {
+                    // Queue wait and handler time are recorded on the request span
+                    let ctx = request.1.clone();
+                    ctx.handled(async move {
+                        // Batched handlers answer every request with a single response
+                        if let Some(batcher) = self.batcher() {
+                            let response = batcher.submit(request).await.map_err(Self::raised);
+                            if let Err(err) = egress.send(response).await {
+                                error!("Failed to send back response to client: {err}");
+                            }
+                            return;
+                        }
 
-                            debug!("[NATIVE] asyncio Handler's coroutine (__call__) done");
+                        let called = match self.call(request).await {
+                            Ok(called) => called,
+                            Err(err) => {
+                                let _ = egress.send(Err(Self::raised(err))).await;
+                                return;
+                            }
+                        };
 
-                            // We are downcasting from Python object to Rust typed type
-                            Ok(Python::with_gil(|py| {
-                                response.extract::<Self::Response>(py)
-                            })?)
+                        // Blocking handlers yielding their responses return a plain generator
+                        let is_blocking_generator = Python::with_gil(|py| {
+                            py.import("inspect")?
+                                .getattr("isgenerator")?
+                                .call1((called.bind(py),))?
+                                .is_truthy()
                         })
-                        .await
-                        .map_err(|err| PyRuntimeError::new_err(err.to_string()))?
+                        .unwrap_or(false);
+                        if is_blocking_generator {
+                            debug!("[NATIVE] Handler's generator (__call__) created");
+                            Self::iterate(called, egress).await;
+                            return;
+                        }
+
+                        // Handlers implemented as `async def` return a coroutine producing a single response
+                        let is_generator = Python::with_gil(|py| called.bind(py).hasattr("__anext__"))
+                            .unwrap_or(false);
+                        if !is_generator {
+                            let response = Self::settle(called)
+                                .await
+                                .and_then(Self::extract)
+                                .map_err(Self::raised);
+                            if let Err(err) = egress.send(response).await {
+                                error!("Failed to send back response to client: {err}");
+                            }
+                            return;
+                        }
+
+                        // Async generators yield as many responses as needed until exhausted, closed
+                        // when this future is dropped as the client goes away
+                        debug!("[NATIVE] asyncio Handler's async generator (__call__) created");
+                        let generator = Generator::new(called, true);
+                        loop {
+                            let next = Python::with_gil(|py| generator.inner().call_method0(py, "__anext__"))
+                                .map_err(Error::from);
+                            let response = match next {
+                                Ok(awaitable) => Self::resolve(awaitable).await,
+                                Err(err) => Err(err),
+                            };
+
+                            let response = match response {
+                                Err(Error::PythonError(err))
+                                    if Python::with_gil(|py| err.is_instance_of::<PyStopAsyncIteration>(py)) =>
+                                {
+                                    debug!("[NATIVE] asyncio Handler's async generator (__call__) exhausted");
+                                    generator.exhausted();
+                                    break;
+                                }
+                                response => response.and_then(Self::extract).map_err(Self::raised),
+                            };
+
+                            let failed = response.is_err();
+                            if egress.send(response).await.is_err() {
+                                debug!("Client went away, stop iterating over the handler's async generator");
+                                break;
+                            }
+
+                            if failed {
+                                break;
+                            }
+                        }
+                    })
+                    .await
                 }
             }
         };
@@ -160,54 +1642,74 @@ pub mod python {
 
     macro_rules! impl_pyendpoint {
         ($name: literal, $pyname: ident, $handler: ident, $router: ident) => {
-            use crate::{__path_health, ApiDoc, Context, health, serve_openai};
-            use hfendpoints_core::{Endpoint, wait_for_requests};
+            use crate::models::python::handler_info;
+            use crate::python::{
+                create_handlers, mount_handler, serve_mounts, task_channel, FallbackHandler,
+                PyMount,
+            };
+            use crate::{Context, ModelCard};
+            use hfendpoints_core::{Endpoint, WorkerPool};
             use pyo3::exceptions::PyRuntimeError;
             use pyo3::prelude::*;
-            use pyo3::types::PyNone;
             use std::sync::Arc;
-            use tokio::net::TcpListener;
-            use tokio::sync::mpsc::unbounded_channel;
-            use tokio::task::spawn;
             use tracing::error;
-            use utoipa::OpenApi;
-            use utoipa_axum::{router::OpenApiRouter, routes};
-            use utoipa_scalar::{Scalar, Servable};
 
+            /// Endpoint driving either a single handler concurrently, or a pool of workers
+            /// each processing one request at a time, the requests they fail being retried
+            /// on the fallback handler if any
             #[pyclass(name = $name)]
-            pub(crate) struct $pyname(Arc<$handler>);
+            pub(crate) struct $pyname(
+                Arc<$handler>,
+                ModelCard,
+                Option<WorkerPool<$handler>>,
+                Option<FallbackHandler<$handler>>,
+            );
 
             impl Endpoint<(String, u16)> for $pyname {
                 #[instrument(skip(self))]
                 async fn serve(&self, inet_address: (String, u16)) -> Result<(), Error> {
-                    let (sender, receiver) = unbounded_channel();
+                    let (sender, receiver, loopers) = task_channel(self.3.as_ref());
                     let router = $router { 0: sender };
 
-                    // Handler in another thread
-                    let handler = Arc::clone(&self.0);
-                    let _ = pyo3_async_runtimes::tokio::get_runtime()
-                        .spawn(wait_for_requests(receiver, handler));
-
-                    info!("Starting endpoint at {}:{}", &inet_address.0, &inet_address.1);
-                    pyo3_async_runtimes::tokio::get_runtime().spawn(serve_openai(inet_address, router))
-                        .await
-                        .inspect_err(|err| {
-                            info!("Caught error while serving endpoint: {err}");
-                        })
-                        .unwrap();
-
-                    Ok(())
+                    // Handlers are instantiated by Python, their model loaded, before reaching the endpoint
+                    self.on_handler_initialized();
+                    let mount = mount_handler(&self.0, self.2.as_ref(), receiver, router, None)?
+                        .with_loopers(loopers);
+                    serve_mounts(vec![mount], self.1.clone(), inet_address).await
                 }
             }
 
             #[pymethods]
             impl $pyname {
-                #[instrument(skip(inner))]
+                #[instrument(skip(py, inner, fallback))]
                 #[new]
-                fn new(inner: PyObject) -> Self {
-                    Self {
-                        0: Arc::new(PyHandler { inner }),
-                    }
+                #[pyo3(signature = (inner, model = None, workers = None, fallback = None, fallback_after = None))]
+                fn new(
+                    py: Python<'_>,
+                    inner: PyObject,
+                    model: Option<ModelCard>,
+                    workers: Option<usize>,
+                    fallback: Option<PyObject>,
+                    fallback_after: Option<f64>,
+                ) -> PyResult<Self> {
+                    let info = handler_info(py, &inner)?;
+                    let (handler, pool) = create_handlers(py, inner, workers, PyHandler::new)?;
+                    let fallback = FallbackHandler::optional(py, fallback, fallback_after, PyHandler::new)?;
+                    let model = model.unwrap_or_default();
+                    Ok(Self {
+                        0: handler,
+                        1: match info {
+                            Some(info) => model.with_info(info),
+                            None => model,
+                        },
+                        2: pool,
+                        3: fallback,
+                    })
+                }
+
+                /// Model card advertised by the endpoint, completed by the handler's `info()`
+                fn _model_(&self) -> ModelCard {
+                    self.1.clone()
                 }
 
                 #[instrument(skip(self))]
@@ -219,24 +1721,392 @@ pub mod python {
                         Ok(())
                     }
                 }
+
+                /// Start processing the requests of the task, served by a `MultiTaskEndpoint` as its `task`-th one
+                #[instrument(skip(self))]
+                fn _mount_(&self, task: usize) -> PyResult<PyMount> {
+                    let (sender, receiver, loopers) = task_channel(self.3.as_ref());
+                    let router = $router { 0: sender };
+                    mount_handler(&self.0, self.2.as_ref(), receiver, router, Some(task))
+                        .inspect(|_| self.on_handler_initialized())
+                        .map(|mount| PyMount::from(mount.with_loopers(loopers)))
+                        .map_err(|err| PyRuntimeError::new_err(err.to_string()))
+                }
             }
         };
     }
 
-    use crate::context::Context;
+    use crate::context::{Context, RequestContext};
+    use crate::models::{ModelCard, ModelInfo};
     pub(crate) use impl_pyendpoint;
     pub(crate) use impl_pyhandler;
 
+    /// Generator returned by a Python handler streaming its responses, closed when dropped before
+    /// being exhausted (the client went away or the handler failed) so its `finally` blocks release
+    /// the resources it holds right away rather than whenever it is garbage collected
+    pub(crate) struct Generator {
+        inner: Option<PyObject>,
+        asynchronous: bool,
+    }
+
+    impl Generator {
+        pub(crate) fn new(inner: PyObject, asynchronous: bool) -> Self {
+            Self {
+                inner: Some(inner),
+                asynchronous,
+            }
+        }
+
+        /// Python generator, either a plain or an async one
+        pub(crate) fn inner(&self) -> &PyObject {
+            self.inner.as_ref().expect("Generator is only released once exhausted")
+        }
+
+        /// The generator raised `StopIteration`, leaving nothing to close
+        pub(crate) fn exhausted(mut self) {
+            self.inner = None;
+        }
+    }
+
+    impl Drop for Generator {
+        fn drop(&mut self) {
+            let Some(generator) = self.inner.take() else {
+                return;
+            };
+
+            if !self.asynchronous {
+                if let Err(err) = Python::with_gil(|py| generator.call_method0(py, "close")) {
+                    warn!("Failed to close the handler's generator: {err}");
+                }
+                return;
+            }
+
+            // `aclose()` only returns the awaitable running the `finally` blocks, scheduled on the
+            // event loop bound to the endpoint
+            let closing = Python::with_gil(|py| {
+                let locals = TASK_LOCALS
+                    .get()
+                    .ok_or_else(|| PyRuntimeError::new_err("No event loop bound to the endpoint"))?
+                    .clone_ref(py);
+                let awaitable = generator.call_method0(py, "aclose")?;
+                let future =
+                    pyo3_async_runtimes::into_future_with_locals(&locals, awaitable.into_bound(py))?;
+                PyResult::Ok((locals, future))
+            });
+
+            match closing {
+                Ok((locals, future)) => {
+                    pyo3_async_runtimes::tokio::get_runtime().spawn(
+                        pyo3_async_runtimes::tokio::scope(locals, async move {
+                            if let Err(err) = future.await {
+                                warn!("Failed to close the handler's async generator: {err}");
+                            }
+                        }),
+                    );
+                }
+                Err(err) => warn!("Failed to close the handler's async generator: {err}"),
+            }
+        }
+    }
+
+    /// Responses Python handlers may stream as plain strings, holding the text generated since
+    /// the previous response, i.e. the tokens of a chat completion or the words of a transcription
+    pub(crate) trait TextDelta: Sized {
+        /// Response streaming `text`, if the task streams text at all
+        fn from_text_delta(_text: String) -> Option<Self> {
+            None
+        }
+    }
+
+    /// Check the protocol version a handler declares through `__protocol_version__` is supported.
+    ///
+    /// Values which are not a version at all (negative, too large or not an integer) are reported
+    /// as such, naming the value declared, rather than a bare conversion error.
+    fn declared_protocol_version(declared: &Bound<'_, PyAny>) -> PyResult<u16> {
+        let err = match declared.extract::<u16>() {
+            Ok(version) => match negotiate_protocol_version(version) {
+                Ok(version) => return Ok(version),
+                Err(err) => err,
+            },
+            Err(_) => Error::InvalidProtocolVersion {
+                found: declared
+                    .repr()
+                    .map(|repr| repr.to_string())
+                    .unwrap_or_else(|_| String::from("<unprintable>")),
+                min: MIN_HANDLER_PROTOCOL_VERSION,
+                max: HANDLER_PROTOCOL_VERSION,
+            },
+        };
+        Err(PyRuntimeError::new_err(err.to_string()))
+    }
+
+    /// Wrap the handler provided by Python through `wrap`, `inner` being either a single handler
+    /// or a list of handler instances, one per worker
+    pub(crate) fn create_handlers<H>(
+        py: Python<'_>,
+        inner: PyObject,
+        workers: Option<usize>,
+        wrap: impl Fn(Python<'_>, PyObject) -> PyResult<H>,
+    ) -> PyResult<(Arc<H>, Option<WorkerPool<H>>)> {
+        // A sequence of handlers gets one worker per instance
+        let bound = inner.bind(py);
+        let instances = if bound.is_instance_of::<PyList>() || bound.is_instance_of::<PyTuple>() {
+            bound.extract::<Vec<PyObject>>()?
+        } else {
+            vec![inner]
+        };
+
+        if instances.is_empty() {
+            return Err(PyValueError::new_err("At least one handler is required"));
+        }
+        if workers == Some(0) {
+            return Err(PyValueError::new_err("Parameter 'workers' must be greater than 0"));
+        }
+
+        let mut handlers = Vec::with_capacity(instances.len());
+        for inner in instances {
+            // Handlers not declaring any version are assumed to target the first protocol
+            let handler = inner.bind(py);
+            if handler.hasattr("__protocol_version__")? {
+                declared_protocol_version(&handler.getattr("__protocol_version__")?)?;
+            } else {
+                negotiate_protocol_version(1)
+                    .map_err(|err| PyRuntimeError::new_err(err.to_string()))?;
+            }
+            handlers.push(Arc::new(wrap(py, inner)?));
+        }
+
+        let pool = match (handlers.len(), workers) {
+            (1, workers) => workers
+                .or_else(workers_from_env)
+                .map(|count| WorkerPool::replicate(Arc::clone(&handlers[0]), count)),
+            (instances, Some(workers)) if instances != workers => {
+                return Err(PyValueError::new_err(format!(
+                    "{instances} handlers were provided for {workers} workers"
+                )));
+            }
+            _ => Some(WorkerPool::new(handlers.clone())),
+        };
+
+        Ok((Arc::clone(&handlers[0]), pool))
+    }
+
+    /// Task mounted by an endpoint to be served by a `MultiTaskEndpoint`
+    #[pyclass(frozen, name = "MountedTask")]
+    pub(crate) struct PyMount(Mutex<Option<Mount>>);
+
+    impl From<Mount> for PyMount {
+        fn from(mount: Mount) -> Self {
+            Self(Mutex::new(Some(mount)))
+        }
+    }
+
+    /// Handler, or workers, the requests of an endpoint are retried on when failed, see `fallback`
+    pub(crate) struct FallbackHandler<H> {
+        handler: Arc<H>,
+        pool: Option<WorkerPool<H>>,
+        fallback: Fallback,
+    }
+
+    impl<H> FallbackHandler<H> {
+        /// Wrap the fallback handler provided by Python through `wrap`, requests the primary
+        /// handler did not answer within `after` seconds, if set, being retried as well
+        pub(crate) fn new(
+            py: Python<'_>,
+            inner: PyObject,
+            after: Option<f64>,
+            wrap: impl Fn(Python<'_>, PyObject) -> PyResult<H>,
+        ) -> PyResult<Self> {
+            let mut fallback = Fallback::new(handler_model_id(py, &inner)?);
+            if let Some(after) = after {
+                let after = Duration::try_from_secs_f64(after)
+                    .ok()
+                    .filter(|after| !after.is_zero())
+                    .ok_or_else(|| {
+                        PyValueError::new_err(
+                            "Parameter 'fallback_after' must be a positive number of seconds",
+                        )
+                    })?;
+                fallback = fallback.after(after);
+            }
+
+            let (handler, pool) = create_handlers(py, inner, None, wrap)?;
+            Ok(Self {
+                handler,
+                pool,
+                fallback,
+            })
+        }
+
+        /// Wrap the optional fallback handler of an endpoint, `after` requiring one to be provided
+        pub(crate) fn optional(
+            py: Python<'_>,
+            inner: Option<PyObject>,
+            after: Option<f64>,
+            wrap: impl Fn(Python<'_>, PyObject) -> PyResult<H>,
+        ) -> PyResult<Option<Self>> {
+            match (inner, after) {
+                (Some(inner), after) => Self::new(py, inner, after, wrap).map(Some),
+                (None, Some(_)) => Err(PyValueError::new_err(
+                    "Parameter 'fallback_after' requires a 'fallback' handler",
+                )),
+                (None, None) => Ok(None),
+            }
+        }
+    }
+
+    /// Sender and receiver of the requests of an endpoint, along with the loops retrying them on
+    /// its fallback handler
+    pub(crate) type TaskChannel<R, O> = (
+        RequestSender<(R, Context), O>,
+        RequestReceiver<(R, Context), O>,
+        Vec<JoinHandle<Result<(), Error>>>,
+    );
+
+    /// Queue of the requests of an endpoint, as the sender the routes schedule them through and
+    /// the receiver its handler processes them from. Requests failed by the handler are retried
+    /// on `fallback`, if any, whose loops are returned along.
+    pub(crate) fn task_channel<H, R, O>(fallback: Option<&FallbackHandler<H>>) -> TaskChannel<R, O>
+    where
+        H: Handler<Request = (R, Context), Response = O> + Send + Sync + 'static,
+        R: Clone + Send + 'static,
+        O: Send + 'static,
+    {
+        let capacity = EndpointConfig::current().queue_capacity;
+        let (sender, receiver) = request_channel(capacity);
+        let Some(fallback) = fallback else {
+            return (sender, receiver, Vec::new());
+        };
+
+        info!(
+            "Requests failed by the handler are retried on {}",
+            fallback.fallback.model()
+        );
+        let runtime = pyo3_async_runtimes::tokio::get_runtime();
+        let (fallback_sender, fallback_receiver) = request_channel(capacity);
+        let (sender, forwarding) = fallback.fallback.clone().route(sender, fallback_sender);
+        let loopers = vec![
+            spawn_looper(
+                runtime.handle(),
+                &fallback.handler,
+                fallback.pool.as_ref(),
+                fallback_receiver,
+            ),
+            runtime.spawn(async move {
+                forwarding.await;
+                Ok(())
+            }),
+        ];
+        (sender, receiver, loopers)
+    }
+
+    /// Let `handler`, or the workers of `pool`, process the requests received through `receiver`
+    /// on the runtime driving the Python event loop, see [`crate::endpoint::mount_handler`]
+    pub(crate) fn mount_handler<H, R>(
+        handler: &Arc<H>,
+        pool: Option<&WorkerPool<H>>,
+        receiver: RequestReceiver<H::Request, H::Response>,
+        router: R,
+        task: Option<usize>,
+    ) -> Result<Mount, Error>
+    where
+        H: Handler + Send + Sync + 'static,
+        H::Request: Queued + Send + 'static,
+        H::Response: Queued + Send + 'static,
+        R: Into<OpenApiRouter> + Send + 'static,
+    {
+        let runtime = pyo3_async_runtimes::tokio::get_runtime();
+        endpoint::mount_handler(runtime.handle(), handler, pool, receiver, router, task)
+    }
+
+    /// Serve the routes of `mounts` on `inet_address` advertising `model`, until the server stops
+    /// and every handler loop completed the requests in flight
+    pub(crate) async fn serve_mounts(
+        mounts: Vec<Mount>,
+        model: ModelCard,
+        inet_address: (String, u16),
+    ) -> Result<(), Error> {
+        let runtime = pyo3_async_runtimes::tokio::get_runtime();
+        endpoint::serve_mounts(runtime.handle(), mounts, model, inet_address)
+            .await
+            .map_err(|err| match err {
+                OpenAiError::Endpoint(err) => err,
+                err => Error::PythonError(PyRuntimeError::new_err(err.to_string())),
+            })
+    }
+
+    /// Endpoint serving the tasks of several endpoints, i.e. transcriptions and translations for
+    /// Whisper-like models, each task keeping its own handler and request queue
+    #[pyclass(name = "MultiTaskEndpoint")]
+    pub(crate) struct PyMultiTaskEndpoint {
+        endpoints: Vec<PyObject>,
+        model: ModelCard,
+    }
+
+    #[pymethods]
+    impl PyMultiTaskEndpoint {
+        #[new]
+        #[pyo3(signature = (endpoints, model = None))]
+        fn new(py: Python<'_>, endpoints: Vec<PyObject>, model: Option<ModelCard>) -> PyResult<Self> {
+            // The model is described by the first endpoint unless explicitly provided
+            if endpoints.is_empty() {
+                return Err(PyValueError::new_err("At least one endpoint is required"));
+            }
+
+            for endpoint in &endpoints {
+                let endpoint = endpoint.bind(py);
+                if !endpoint.hasattr("_mount_")? {
+                    return Err(PyValueError::new_err(format!(
+                        "{} cannot be served along other tasks",
+                        endpoint.get_type().name()?
+                    )));
+                }
+            }
+
+            let model = match model {
+                Some(model) => model,
+                None if endpoints[0].bind(py).hasattr("_model_")? => {
+                    endpoints[0].bind(py).call_method0("_model_")?.extract()?
+                }
+                None => ModelCard::default(),
+            };
+            Ok(Self { endpoints, model })
+        }
+
+        #[instrument(skip(self))]
+        async fn _serve_(&self, interface: String, port: u16) -> PyResult<()> {
+            let mounts = Python::with_gil(|py| {
+                self.endpoints
+                    .iter()
+                    .enumerate()
+                    .map(|(task, endpoint)| {
+                        let mounted = endpoint.bind(py).call_method1("_mount_", (task,))?;
+                        let mounted = mounted.downcast::<PyMount>()?.get();
+                        let mount = mounted.0.lock().unwrap_or_else(PoisonError::into_inner).take();
+                        mount.ok_or_else(|| PyRuntimeError::new_err("Endpoint is already served"))
+                    })
+                    .collect::<PyResult<Vec<_>>>()
+            })?;
+
+            if let Err(err) = serve_mounts(mounts, self.model.clone(), (interface, port)).await {
+                error!("Caught error while serving multi-task endpoint: {err}");
+                Err(PyRuntimeError::new_err(err.to_string()))
+            } else {
+                Ok(())
+            }
+        }
+    }
+
     async fn serve(endpoint: PyObject, interface: String, port: u16) -> PyResult<()> {
         let locals = TASK_LOCALS
             .get_or_try_init(|| async {
-                Python::with_gil(|py| pyo3_async_runtimes::tokio::get_current_locals(py))
+                Python::with_gil(pyo3_async_runtimes::tokio::get_current_locals)
             })
             .await?;
 
         Python::with_gil(|py| {
             let coro = endpoint.bind(py).call_method1("_serve_", (interface, port))?;
-            pyo3_async_runtimes::into_future_with_locals(&locals, coro)
+            pyo3_async_runtimes::into_future_with_locals(locals, coro)
         })?.await?;
         Ok(())
     }
@@ -264,15 +2134,95 @@ pub mod python {
         Ok::<_, PyErr>(())
     }
 
+    #[pyfunction]
+    #[instrument(skip(py, reason))]
+    #[pyo3(name = "run_unavailable")]
+    fn run_unavailable(py: Python<'_>, reason: String, interface: String, port: u16) -> PyResult<()> {
+        // Initialize the tokio runtime, no Python code will be scheduled on it
+        init(create_multithreaded_runtime());
+
+        py.allow_threads(|| {
+            pyo3_async_runtimes::tokio::get_runtime()
+                .block_on(crate::serve_unavailable((interface, port), reason))
+        })
+        .map_err(|err| PyRuntimeError::new_err(err.to_string()))
+    }
+
+    #[pyfunction]
+    #[instrument]
+    #[pyo3(name = "load_snapshot")]
+    fn load_snapshot(path: String) -> PyResult<BTreeMap<&'static str, Option<String>>> {
+        let key = SnapshotKey::from_env().ok_or_else(|| {
+            PyValueError::new_err(format!("{SNAPSHOT_KEY_ENV} must be set to load {path}"))
+        })?;
+        let snapshot =
+            Snapshot::from_file(&path, &key).map_err(|err| PyValueError::new_err(err.to_string()))?;
+
+        Ok(snapshot
+            .variables()
+            .map(|(name, value)| (name, value.map(String::from)))
+            .collect())
+    }
+
+    #[pyfunction]
+    #[instrument(skip(py))]
+    #[pyo3(name = "load_config")]
+    fn load_config(py: Python<'_>) -> PyResult<Bound<'_, PyDict>> {
+        let config =
+            EndpointConfig::from_env().map_err(|err| PyValueError::new_err(err.to_string()))?;
+
+        let settings = PyDict::new(py);
+        settings.set_item("interface", config.interface)?;
+        settings.set_item("port", config.port)?;
+        settings.set_item("body_limit", config.body_limit)?;
+        settings.set_item("queue_capacity", config.queue_capacity)?;
+        settings.set_item("max_concurrent_requests", config.max_concurrent_requests)?;
+        settings.set_item(
+            "request_timeout",
+            config.request_timeout.map(|timeout| timeout.as_secs_f64()),
+        )?;
+        settings.set_item(
+            "shutdown_grace_period",
+            config.shutdown_grace_period.as_secs_f64(),
+        )?;
+        settings.set_item("api_keys", config.api_keys)?;
+        settings.set_item("log_format", config.log_format.as_str())?;
+        Ok(settings)
+    }
+
     /// Bind hfendpoints.openai submodule into the exported Python wheel
     pub fn bind<'py>(py: Python<'py>, name: &str) -> PyResult<Bound<'py, PyModule>> {
         let module = ImportablePyModuleBuilder::new(py, name)?
             .defaults()?
             .add_class::<Context>()?
+            .add_class::<RequestContext>()?
+            .add_class::<ModelCard>()?
+            .add_class::<ModelInfo>()?
+            .add_class::<PyMount>()?
+            .add_class::<PyMultiTaskEndpoint>()?
             .add_submodule(&crate::audio::python::bind(py, &format!("{name}.audio"))?)?
+            .add_submodule(&crate::chat::python::bind(py, &format!("{name}.chat"))?)?
+            .add_submodule(&crate::completions::python::bind(
+                py,
+                &format!("{name}.completions"),
+            )?)?
+            .add_submodule(&crate::custom::python::bind(py, &format!("{name}.custom"))?)?
+            .add_submodule(&crate::embeddings::python::bind(
+                py,
+                &format!("{name}.embeddings"),
+            )?)?
+            .add_submodule(&crate::images::python::bind(py, &format!("{name}.images"))?)?
+            .add_submodule(&crate::moderations::python::bind(
+                py,
+                &format!("{name}.moderations"),
+            )?)?
+            .add_submodule(&crate::rerank::python::bind(py, &format!("{name}.rerank"))?)?
             .finish();
 
         module.add_function(wrap_pyfunction!(run, &module)?)?;
+        module.add_function(wrap_pyfunction!(run_unavailable, &module)?)?;
+        module.add_function(wrap_pyfunction!(load_snapshot, &module)?)?;
+        module.add_function(wrap_pyfunction!(load_config, &module)?)?;
         Ok(module)
     }
 }
@@ -0,0 +1,62 @@
+//! Socket activation support.
+//!
+//! When the process is started by a supervisor holding the listening socket (systemd socket units,
+//! `systemfd`, ...), the socket is inherited through `LISTEN_FDS`/`LISTEN_PID` and used as is
+//! instead of binding the configured interface. Connections accepted by the kernel while the
+//! endpoint restarts stay queued on the socket rather than being refused.
+use crate::OpenAiResult;
+use crate::tls::TlsListener;
+use axum::extract::connect_info::Connected;
+use axum::serve::IncomingStream;
+use listenfd::ListenFd;
+use std::fmt::Debug;
+use std::net::SocketAddr;
+use tokio::net::{TcpListener, ToSocketAddrs};
+use tracing::{info, instrument};
+
+/// Address of the peer a request was received from, over plain TCP or TLS
+#[derive(Copy, Clone, Debug)]
+pub(crate) struct PeerAddr(pub(crate) SocketAddr);
+
+impl Connected<IncomingStream<'_, TcpListener>> for PeerAddr {
+    fn connect_info(stream: IncomingStream<'_, TcpListener>) -> Self {
+        Self(*stream.remote_addr())
+    }
+}
+
+impl Connected<IncomingStream<'_, TlsListener>> for PeerAddr {
+    fn connect_info(stream: IncomingStream<'_, TlsListener>) -> Self {
+        Self(*stream.remote_addr())
+    }
+}
+
+/// Use the listener inherited from the supervisor if any, otherwise bind `interface`
+#[instrument]
+pub(crate) async fn bind<A>(interface: A) -> OpenAiResult<TcpListener>
+where
+    A: ToSocketAddrs + Debug,
+{
+    // Environment variables are consumed so they do not leak to subprocesses
+    if let Some(listener) = ListenFd::from_env().take_tcp_listener(0)? {
+        listener.set_nonblocking(true)?;
+        let listener = TcpListener::from_std(listener)?;
+        info!(
+            "Using inherited socket listening on {} (ignoring {interface:?})",
+            listener.local_addr()?
+        );
+        return Ok(listener);
+    }
+
+    Ok(TcpListener::bind(interface).await?)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::listener::bind;
+
+    #[tokio::test]
+    async fn bind_interface_without_inherited_socket() {
+        let listener = bind(("127.0.0.1", 0)).await.unwrap();
+        assert!(listener.local_addr().unwrap().ip().is_loopback());
+    }
+}
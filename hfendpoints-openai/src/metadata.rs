@@ -0,0 +1,223 @@
+//! Metadata attached by callers to their requests, echoed back along the responses.
+//!
+//! Callers may attach opaque JSON to a request, through the `x-metadata` header or the `metadata`
+//! field of JSON bodies, to correlate the results with their own systems without maintaining a
+//! mapping. The metadata is echoed through the `x-metadata` header of the response, recorded in
+//! the `request` span, carried by the usage records (see `HFENDPOINT_USAGE_SINK`) and handed over
+//! to the handler through `RequestContext.metadata`.
+//!
+//! The header is echoed verbatim. The body field is echoed compacted, its non-ASCII characters
+//! escaped so it fits in a header. Metadata which is not valid JSON, or larger than 4KiB, is
+//! rejected with 400.
+use crate::body::read_limited;
+use crate::usage::record_metadata;
+use crate::{OpenAiError, OpenAiResult};
+use axum::body::Body;
+use axum::extract::{Request, State};
+use axum::http::header::CONTENT_TYPE;
+use axum::http::{HeaderName, HeaderValue, Method};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use serde::Deserialize;
+use serde_json::Value;
+use std::fmt::Write;
+use std::sync::Arc;
+use tracing::Span;
+
+/// Header carrying the metadata of the request, and echoing it along the response
+pub const X_METADATA: HeaderName = HeaderName::from_static("x-metadata");
+
+/// Maximum size, in bytes, of the metadata attached to a request
+pub const MAX_METADATA_SIZE: usize = 4096;
+
+/// Limit of the JSON bodies read when no body limit is configured, the one of axum's extractors
+const DEFAULT_JSON_BODY_LIMIT: usize = 2 * 1024 * 1024;
+
+/// Opaque JSON attached by the caller to a request
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Metadata(Arc<str>);
+
+impl Metadata {
+    /// Metadata sent as `json`, kept verbatim once validated
+    fn parse(json: &str, source: &str) -> OpenAiResult<Self> {
+        if json.len() > MAX_METADATA_SIZE {
+            return Err(OpenAiError::Validation(format!(
+                "{source} is limited to {MAX_METADATA_SIZE} bytes, got {}",
+                json.len()
+            )));
+        }
+
+        serde_json::from_str::<Value>(json).map_err(|err| {
+            OpenAiError::Validation(format!("{source} must be valid JSON: {err}"))
+        })?;
+        Ok(Self(Arc::from(json)))
+    }
+
+    /// The metadata as sent by the caller
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// The metadata as a header value, non-ASCII characters being escaped
+    fn header_value(&self) -> Option<HeaderValue> {
+        let mut escaped = String::with_capacity(self.0.len());
+        for character in self.0.chars() {
+            if character.is_ascii() {
+                escaped.push(character);
+            } else {
+                let mut units = [0u16; 2];
+                for unit in character.encode_utf16(&mut units) {
+                    let _ = write!(escaped, "\\u{unit:04x}");
+                }
+            }
+        }
+        HeaderValue::from_str(&escaped).ok()
+    }
+}
+
+/// Body of a JSON request, only the `metadata` being decoded
+#[derive(Deserialize)]
+struct WithMetadata {
+    metadata: Option<Value>,
+}
+
+/// Metadata of `request`, from the `x-metadata` header or the `metadata` field of its JSON body,
+/// the body being buffered to be read
+async fn extract(request: Request, limit: usize) -> Result<(Request, Option<Metadata>), Response> {
+    if let Some(header) = request.headers().get(&X_METADATA) {
+        let metadata = header
+            .to_str()
+            .map_err(|_| OpenAiError::Validation(String::from("Header 'x-metadata' must be ASCII")))
+            .and_then(|header| Metadata::parse(header, "Header 'x-metadata'"))
+            .map_err(IntoResponse::into_response)?;
+        return Ok((request, Some(metadata)));
+    }
+
+    let is_json = request
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("application/json"));
+    if request.method() != Method::POST || !is_json {
+        return Ok((request, None));
+    }
+
+    let (parts, body) = request.into_parts();
+    let payload = read_limited(body, limit)
+        .await
+        .map_err(IntoResponse::into_response)?;
+
+    // Malformed bodies are left for the route to reject
+    let metadata = match serde_json::from_slice::<WithMetadata>(&payload) {
+        Ok(WithMetadata {
+            metadata: Some(metadata),
+        }) => Some(
+            Metadata::parse(&metadata.to_string(), "Parameter 'metadata'")
+                .map_err(IntoResponse::into_response)?,
+        ),
+        _ => None,
+    };
+    Ok((Request::from_parts(parts, Body::from(payload)), metadata))
+}
+
+/// Middleware echoing the metadata of the requests along their responses, see `metadata`
+pub(crate) async fn echo_metadata(
+    State(limit): State<Option<usize>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let limit = limit.unwrap_or(DEFAULT_JSON_BODY_LIMIT);
+    let (mut request, metadata) = match extract(request, limit).await {
+        Ok(extracted) => extracted,
+        Err(response) => return response,
+    };
+    let Some(metadata) = metadata else {
+        return next.run(request).await;
+    };
+
+    Span::current().record("metadata", metadata.as_str());
+    record_metadata(&metadata);
+    request.extensions_mut().insert(metadata.clone());
+
+    let mut response = next.run(request).await;
+    if let Some(value) = metadata.header_value() {
+        response.headers_mut().insert(X_METADATA, value);
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::context::RequestContext;
+    use crate::metadata::{X_METADATA, echo_metadata};
+    use crate::testing::{request, send};
+    use axum::Router;
+    use axum::body::Body;
+    use axum::http::header::CONTENT_TYPE;
+    use axum::http::{HeaderName, StatusCode};
+    use axum::middleware::from_fn_with_state;
+    use axum::routing::post;
+
+    /// Route answering with the metadata handed over to the handler
+    fn router() -> Router {
+        Router::new()
+            .route(
+                "/echo",
+                post(|metadata: RequestContext, body: String| async move {
+                    let metadata = metadata.metadata().map(|metadata| metadata.as_str());
+                    format!("{} {body}", metadata.unwrap_or("-"))
+                }),
+            )
+            .layer(from_fn_with_state(None, echo_metadata))
+    }
+
+    async fn post_echo(
+        headers: &[(HeaderName, &str)],
+        body: &'static str,
+    ) -> (StatusCode, Option<String>, String) {
+        let request = headers
+            .iter()
+            .fold(request("/echo"), |request, (name, value)| {
+                request.header(name, *value)
+            })
+            .body(Body::from(body))
+            .unwrap();
+
+        let (status, headers, body) = send(router(), request).await;
+        let echoed = headers
+            .get(X_METADATA)
+            .map(|value| value.to_str().unwrap().to_string());
+        (status, echoed, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    #[tokio::test]
+    async fn echo_metadata_from_header_or_body() {
+        // Headers are echoed verbatim
+        let (status, echoed, body) = post_echo(&[(X_METADATA, r#"{ "job": 42 }"#)], "hello").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(echoed.as_deref(), Some(r#"{ "job": 42 }"#));
+        assert_eq!(body, r#"{ "job": 42 } hello"#);
+
+        // Body fields are compacted and escaped, the body reaching the route untouched
+        let json = r#"{"input": "x", "metadata": {"user": "Zoë"}}"#;
+        let (status, echoed, body) = post_echo(&[(CONTENT_TYPE, "application/json")], json).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(echoed.as_deref(), Some(r#"{"user":"Zo\u00eb"}"#));
+        assert_eq!(body, format!(r#"{{"user":"Zoë"}} {json}"#));
+
+        let (_, echoed, body) = post_echo(&[], r#"{"metadata": 1}"#).await;
+        assert_eq!(echoed, None);
+        assert_eq!(body, r#"- {"metadata": 1}"#);
+    }
+
+    #[tokio::test]
+    async fn reject_invalid_metadata() {
+        let (status, _, _) = post_echo(&[(X_METADATA, "{job")], "hello").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let large = format!(r#""{}""#, "a".repeat(4096));
+        let (status, _, body) = post_echo(&[(X_METADATA, large.as_str())], "hello").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(body.contains("Header 'x-metadata' is limited to 4096 bytes, got 4098"));
+    }
+}
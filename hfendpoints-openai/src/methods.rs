@@ -0,0 +1,111 @@
+use axum::extract::Request;
+use axum::http::header::ALLOW;
+use axum::http::{HeaderValue, Method, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+
+/// Answer `OPTIONS` and `HEAD` probes consistently on every route.
+///
+/// Routes only declare the methods they actually implement, so probing them with
+/// `OPTIONS` or `HEAD` ends up as a `405 Method Not Allowed` carrying the `Allow` header.
+/// For routes which exist, this middleware turns such responses into `204 No Content`
+/// advertising the allowed methods, so load balancers and SDKs see the route as available.
+/// Routes implementing `GET` already answer `HEAD` and are left untouched.
+pub(crate) async fn allowed_methods(request: Request, next: Next) -> Response {
+    let method = request.method().clone();
+    let response = next.run(request).await;
+
+    if (method != Method::OPTIONS && method != Method::HEAD)
+        || response.status() != StatusCode::METHOD_NOT_ALLOWED
+    {
+        return response;
+    }
+
+    match response.headers().get(ALLOW).map(with_probe_methods) {
+        Some(Some(allow)) => (StatusCode::NO_CONTENT, [(ALLOW, allow)]).into_response(),
+        _ => response,
+    }
+}
+
+/// Append `HEAD` and `OPTIONS` to the methods advertised by the router
+fn with_probe_methods(allow: &HeaderValue) -> Option<HeaderValue> {
+    let mut methods = allow
+        .to_str()
+        .ok()?
+        .split(',')
+        .map(str::trim)
+        .filter(|method| !method.is_empty())
+        .map(str::to_string)
+        .collect::<Vec<_>>();
+
+    for probe in [Method::HEAD, Method::OPTIONS] {
+        if !methods.iter().any(|method| method == probe.as_str()) {
+            methods.push(probe.to_string());
+        }
+    }
+
+    HeaderValue::from_str(&methods.join(", ")).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::methods::allowed_methods;
+    use axum::Router;
+    use axum::body::Body;
+    use axum::http::header::ALLOW;
+    use axum::http::{Method, Request, StatusCode};
+    use axum::middleware::from_fn;
+    use axum::routing::{get, post};
+    use tower::{Layer, ServiceExt};
+
+    fn router() -> Router {
+        Router::new()
+            .route("/health", get(|| async { StatusCode::OK }))
+            .route("/transcriptions", post(|| async { StatusCode::OK }))
+    }
+
+    async fn probe(method: Method, uri: &str) -> axum::response::Response {
+        let request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .body(Body::empty())
+            .unwrap();
+        from_fn(allowed_methods)
+            .layer(router())
+            .oneshot(request)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn options_advertises_allowed_methods() {
+        let response = probe(Method::OPTIONS, "/transcriptions").await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        assert_eq!(response.headers()[ALLOW], "POST, HEAD, OPTIONS");
+    }
+
+    #[tokio::test]
+    async fn head_on_post_only_route() {
+        let response = probe(Method::HEAD, "/transcriptions").await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        assert_eq!(response.headers()[ALLOW], "POST, HEAD, OPTIONS");
+    }
+
+    #[tokio::test]
+    async fn head_on_get_route_is_untouched() {
+        let response = probe(Method::HEAD, "/health").await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn unknown_route_is_not_found() {
+        let response = probe(Method::OPTIONS, "/unknown").await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn other_methods_keep_method_not_allowed() {
+        let response = probe(Method::DELETE, "/health").await;
+        assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+    }
+}
@@ -0,0 +1,628 @@
+//! Description of the model served by the endpoint.
+//!
+//! OpenAI SDKs frequently probe `/models` before sending any request, the card supplied when
+//! creating the endpoint is advertised there alongside every task route.
+//!
+//! Handlers may describe the model they loaded through a [`ModelInfo`], returned by their `info()`
+//! hook. It completes the card advertised on `/models` and `/info`, bounds the requests accepted by
+//! the task routes and documents the model in the OpenAPI specification.
+use crate::{ErrorResponse, OpenAiError, OpenAiResult};
+use axum::Json;
+use axum::extract::{Path, State};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::instrument;
+use utoipa::ToSchema;
+use utoipa_axum::router::OpenApiRouter;
+use utoipa_axum::routes;
+
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+
+pub const MODELS_TAG: &str = "Models";
+pub const MODELS_DESC: &str = "Describe the model deployed on the endpoint.";
+
+/// Environment variable holding the model served when no card is explicitly provided
+pub const MODEL_ID_ENV: &str = "MODEL_ID";
+
+/// Describes the model loaded by the handler, as reported by its `info()` hook.
+///
+/// Every field is optional, the constraints left unset are not enforced.
+#[cfg_attr(feature = "python", pyclass(frozen))]
+#[derive(Clone, Debug, Default, Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct ModelInfo {
+    /// The model identifier, i.e. the repository the weights were loaded from.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    id: Option<String>,
+
+    /// The revision (branch, tag or commit) of the model weights.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    revision: Option<String>,
+
+    /// The task the model is served for, i.e. `automatic-speech-recognition`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    task: Option<String>,
+
+    /// The maximum number of tokens, prompt and completion, the model attends to.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    context_length: Option<u32>,
+
+    /// The maximum number of tokens the model generates for a single request.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_output_tokens: Option<u32>,
+
+    /// The languages the model understands, as ISO-639-1 codes, any if empty.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    languages: Vec<String>,
+
+    /// The voices the model speaks with, any if empty.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    voices: Vec<String>,
+
+    /// The data type the weights are loaded in, i.e. `bfloat16`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    dtype: Option<String>,
+
+    /// The device the model runs on, i.e. `cuda:0`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    device: Option<String>,
+}
+
+impl ModelInfo {
+    /// The model identifier, i.e. the repository the weights were loaded from
+    pub fn id(mut self, id: impl Into<String>) -> Self {
+        self.id = Some(id.into());
+        self
+    }
+
+    /// The revision (branch, tag or commit) of the model weights
+    pub fn revision(mut self, revision: impl Into<String>) -> Self {
+        self.revision = Some(revision.into());
+        self
+    }
+
+    /// The task the model is served for
+    pub fn task(mut self, task: impl Into<String>) -> Self {
+        self.task = Some(task.into());
+        self
+    }
+
+    /// The maximum number of tokens, prompt and completion, the model attends to
+    pub fn context_length(mut self, tokens: u32) -> Self {
+        self.context_length = Some(tokens);
+        self
+    }
+
+    /// The maximum number of tokens the model generates for a single request
+    pub fn max_output_tokens(mut self, tokens: u32) -> Self {
+        self.max_output_tokens = Some(tokens);
+        self
+    }
+
+    /// The languages the model understands, requests asking for another one are rejected
+    pub fn languages<I: IntoIterator<Item = S>, S: Into<String>>(mut self, languages: I) -> Self {
+        self.languages = languages.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// The voices the model speaks with, requests asking for another one are rejected
+    pub fn voices<I: IntoIterator<Item = S>, S: Into<String>>(mut self, voices: I) -> Self {
+        self.voices = voices.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// The data type the weights are loaded in
+    pub fn dtype(mut self, dtype: impl Into<String>) -> Self {
+        self.dtype = Some(dtype.into());
+        self
+    }
+
+    /// The device the model runs on
+    pub fn device(mut self, device: impl Into<String>) -> Self {
+        self.device = Some(device.into());
+        self
+    }
+
+    /// Reject the requests asking for a `language` the model does not understand
+    pub(crate) fn ensure_language(&self, language: &str) -> OpenAiResult<()> {
+        if self.languages.is_empty() || self.languages.iter().any(|known| known == language) {
+            Ok(())
+        } else {
+            Err(OpenAiError::Validation(format!(
+                "Parameter 'language' must be one of {}, got {language}",
+                self.languages.join(", ")
+            )))
+        }
+    }
+
+    /// Reject the requests asking for a `voice` the model does not speak with
+    pub(crate) fn ensure_voice(&self, voice: &str) -> OpenAiResult<()> {
+        if self.voices.is_empty() || self.voices.iter().any(|known| known == voice) {
+            Ok(())
+        } else {
+            Err(OpenAiError::Validation(format!(
+                "Parameter 'voice' must be one of {}, got {voice}",
+                self.voices.join(", ")
+            )))
+        }
+    }
+
+    /// Reject the requests asking for more tokens than the model generates or attends to
+    pub(crate) fn ensure_output_tokens(&self, parameter: &str, tokens: u32) -> OpenAiResult<()> {
+        let limit = match (self.max_output_tokens, self.context_length) {
+            (Some(output), Some(context)) => Some(output.min(context)),
+            (output, context) => output.or(context),
+        };
+
+        match limit {
+            Some(limit) if tokens > limit => Err(OpenAiError::Validation(format!(
+                "Parameter '{parameter}' must be at most {limit}, got {tokens}"
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    /// Markdown description of the model, documenting the OpenAPI specification
+    pub(crate) fn describe(&self) -> String {
+        let mut description = match (&self.id, &self.task) {
+            (Some(id), Some(task)) => format!("Serves `{id}` for {task}."),
+            (Some(id), None) => format!("Serves `{id}`."),
+            (None, Some(task)) => format!("Serves a model for {task}."),
+            (None, None) => String::from("Serves a model."),
+        };
+
+        let mut details = Vec::new();
+        if let Some(revision) = &self.revision {
+            details.push(format!("- Revision: `{revision}`"));
+        }
+        if let Some(tokens) = self.context_length {
+            details.push(format!("- Context length: {tokens} tokens"));
+        }
+        if let Some(tokens) = self.max_output_tokens {
+            details.push(format!("- Maximum output: {tokens} tokens"));
+        }
+        if !self.languages.is_empty() {
+            details.push(format!("- Languages: {}", self.languages.join(", ")));
+        }
+        if !self.voices.is_empty() {
+            details.push(format!("- Voices: {}", self.voices.join(", ")));
+        }
+
+        if !details.is_empty() {
+            description.push_str("\n\n");
+            description.push_str(&details.join("\n"));
+        }
+        description
+    }
+}
+
+/// Describes a model offering that can be used with the API.
+#[cfg_attr(feature = "python", pyclass(frozen))]
+#[derive(Clone, Debug, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct ModelCard {
+    /// The model identifier, which can be referenced in the API endpoints.
+    id: String,
+
+    /// The object type, which is always "model".
+    object: &'static str,
+
+    /// The Unix timestamp (in seconds) when the model was created.
+    created: u64,
+
+    /// The organization that owns the model.
+    owned_by: String,
+
+    /// Extension: the revision (branch, tag or commit) of the model weights.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) revision: Option<String>,
+
+    /// Extension: the model description reported by the handler, see `ModelInfo`.
+    #[serde(flatten)]
+    details: ModelInfo,
+}
+
+impl ModelCard {
+    /// Card for the model `id`, created now and owned by `huggingface`
+    pub fn new(id: impl Into<String>) -> Self {
+        Self {
+            id: id.into(),
+            object: "model",
+            created: 0,
+            owned_by: String::from("huggingface"),
+            revision: None,
+            details: ModelInfo::default(),
+        }
+        .created(SystemTime::now())
+    }
+
+    /// Card for the model referenced by `MODEL_ID`, as configured by Inference Endpoints
+    pub fn from_env() -> Self {
+        Self::new(std::env::var(MODEL_ID_ENV).unwrap_or_else(|_| String::from("/repository")))
+    }
+
+    /// The organization that owns the model
+    pub fn owned_by(mut self, owned_by: impl Into<String>) -> Self {
+        self.owned_by = owned_by.into();
+        self
+    }
+
+    /// The revision (branch, tag or commit) of the model weights
+    pub fn revision(mut self, revision: impl Into<String>) -> Self {
+        self.revision = Some(revision.into());
+        self
+    }
+
+    /// When the model was created
+    pub fn created(mut self, created: SystemTime) -> Self {
+        self.created = created
+            .duration_since(UNIX_EPOCH)
+            .map(|created| created.as_secs())
+            .unwrap_or_default();
+        self
+    }
+
+    /// Complete the card with the description reported by the handler, the model identifier
+    /// and revision it reports replacing the ones of the card
+    pub fn with_info(mut self, mut info: ModelInfo) -> Self {
+        if let Some(id) = info.id.take() {
+            self.id = id;
+        }
+        if let Some(revision) = info.revision.take() {
+            self.revision = Some(revision);
+        }
+        self.details = info;
+        self
+    }
+
+    #[inline]
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Description of the advertised model, identifier and revision included
+    pub fn info(&self) -> ModelInfo {
+        ModelInfo {
+            id: Some(self.id.clone()),
+            revision: self.revision.clone(),
+            ..self.details.clone()
+        }
+    }
+}
+
+impl Default for ModelCard {
+    fn default() -> Self {
+        Self::from_env()
+    }
+}
+
+/// Lists the currently available models.
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Clone, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+struct ModelList {
+    /// The object type, which is always "list".
+    object: &'static str,
+
+    /// The models served by the endpoint.
+    data: Vec<ModelCard>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/models",
+    tag = MODELS_TAG,
+    responses(
+        (status = OK, description = "Lists the currently available models.", body = ModelList),
+    )
+)]
+#[instrument(skip(card))]
+async fn list_models(State(card): State<Arc<ModelCard>>) -> Json<ModelList> {
+    Json(ModelList {
+        object: "list",
+        data: vec![card.as_ref().clone()],
+    })
+}
+
+#[utoipa::path(
+    get,
+    path = "/models/{*model}",
+    tag = MODELS_TAG,
+    params(("model" = String, Path, description = "The ID of the model to use for this request")),
+    responses(
+        (status = OK, description = "Retrieves a model instance.", body = ModelCard),
+        (status = NOT_FOUND, description = "The model is not served by this endpoint.", body = ErrorResponse),
+    )
+)]
+#[instrument(skip(card))]
+async fn retrieve_model(
+    State(card): State<Arc<ModelCard>>,
+    Path(model): Path<String>,
+) -> OpenAiResult<Json<ModelCard>> {
+    if model == card.id {
+        Ok(Json(card.as_ref().clone()))
+    } else {
+        Err(OpenAiError::NotFound(format!(
+            "The model '{model}' does not exist"
+        )))
+    }
+}
+
+/// Routes advertising the model described by `card`
+pub(crate) fn router(card: ModelCard) -> OpenApiRouter {
+    OpenApiRouter::new()
+        .routes(routes!(list_models))
+        .routes(routes!(retrieve_model))
+        .with_state(Arc::new(card))
+}
+
+#[cfg(feature = "python")]
+pub(crate) mod python {
+    use crate::models::{ModelCard, ModelInfo};
+    use pyo3::prelude::*;
+    use pyo3::types::{PyList, PyTuple};
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[pymethods]
+    impl ModelInfo {
+        #[new]
+        #[pyo3(signature = (
+            id = None,
+            revision = None,
+            task = None,
+            context_length = None,
+            max_output_tokens = None,
+            languages = None,
+            voices = None,
+            dtype = None,
+            device = None,
+        ))]
+        #[allow(clippy::too_many_arguments)]
+        fn py_new(
+            id: Option<String>,
+            revision: Option<String>,
+            task: Option<String>,
+            context_length: Option<u32>,
+            max_output_tokens: Option<u32>,
+            languages: Option<Vec<String>>,
+            voices: Option<Vec<String>>,
+            dtype: Option<String>,
+            device: Option<String>,
+        ) -> Self {
+            Self {
+                id,
+                revision,
+                task,
+                context_length,
+                max_output_tokens,
+                languages: languages.unwrap_or_default(),
+                voices: voices.unwrap_or_default(),
+                dtype,
+                device,
+            }
+        }
+
+        #[getter(id)]
+        fn get_id(&self) -> Option<&str> {
+            self.id.as_deref()
+        }
+
+        #[getter(revision)]
+        fn get_revision(&self) -> Option<&str> {
+            self.revision.as_deref()
+        }
+
+        #[getter(task)]
+        fn get_task(&self) -> Option<&str> {
+            self.task.as_deref()
+        }
+
+        #[getter(context_length)]
+        fn get_context_length(&self) -> Option<u32> {
+            self.context_length
+        }
+
+        #[getter(max_output_tokens)]
+        fn get_max_output_tokens(&self) -> Option<u32> {
+            self.max_output_tokens
+        }
+
+        #[getter(languages)]
+        fn get_languages(&self) -> Vec<String> {
+            self.languages.clone()
+        }
+
+        #[getter(voices)]
+        fn get_voices(&self) -> Vec<String> {
+            self.voices.clone()
+        }
+
+        #[getter(dtype)]
+        fn get_dtype(&self) -> Option<&str> {
+            self.dtype.as_deref()
+        }
+
+        #[getter(device)]
+        fn get_device(&self) -> Option<&str> {
+            self.device.as_deref()
+        }
+    }
+
+    /// Description of the model loaded by `handler`, as returned by its optional `info()` hook.
+    /// A list of handlers, one per worker, is described by its first instance.
+    pub(crate) fn handler_info(py: Python<'_>, handler: &PyObject) -> PyResult<Option<ModelInfo>> {
+        let bound = handler.bind(py);
+        let handler = if bound.is_instance_of::<PyList>() || bound.is_instance_of::<PyTuple>() {
+            match bound.get_item(0) {
+                Ok(first) => first,
+                Err(_) => return Ok(None),
+            }
+        } else {
+            bound.clone()
+        };
+
+        if !handler.hasattr("info")? {
+            return Ok(None);
+        }
+        handler.call_method0("info")?.extract::<Option<ModelInfo>>()
+    }
+
+    /// Identifier of the model loaded by `handler`, as returned by its `info()` hook, falling
+    /// back to the name of its class
+    pub(crate) fn handler_model_id(py: Python<'_>, handler: &PyObject) -> PyResult<String> {
+        if let Some(ModelInfo { id: Some(id), .. }) = handler_info(py, handler)? {
+            return Ok(id);
+        }
+
+        let bound = handler.bind(py);
+        let handler = if bound.is_instance_of::<PyList>() || bound.is_instance_of::<PyTuple>() {
+            bound.get_item(0)?
+        } else {
+            bound.clone()
+        };
+        Ok(handler.get_type().name()?.to_string())
+    }
+
+    #[pymethods]
+    impl ModelCard {
+        #[new]
+        #[pyo3(signature = (id, owned_by = None, created = None, revision = None))]
+        fn py_new(
+            id: String,
+            owned_by: Option<String>,
+            created: Option<u64>,
+            revision: Option<String>,
+        ) -> Self {
+            let mut card = Self::new(id);
+            if let Some(revision) = revision {
+                card = card.revision(revision);
+            }
+            if let Some(owned_by) = owned_by {
+                card = card.owned_by(owned_by);
+            }
+            if let Some(created) = created {
+                card = card.created(UNIX_EPOCH + Duration::from_secs(created));
+            }
+            card
+        }
+
+        #[getter(id)]
+        fn get_id(&self) -> &str {
+            &self.id
+        }
+
+        #[getter(created)]
+        fn get_created(&self) -> u64 {
+            self.created
+        }
+
+        #[getter(owned_by)]
+        fn get_owned_by(&self) -> &str {
+            &self.owned_by
+        }
+
+        #[getter(revision)]
+        fn py_revision(&self) -> Option<&str> {
+            self.revision.as_deref()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::OpenAiError;
+    use crate::models::{ModelCard, ModelInfo, router};
+    use axum::body::{Body, to_bytes};
+    use axum::http::{Request, StatusCode};
+    use std::time::{Duration, UNIX_EPOCH};
+    use tower::ServiceExt;
+
+    async fn get(uri: &str) -> (StatusCode, String) {
+        let card = ModelCard::new("openai/whisper-large-v3")
+            .created(UNIX_EPOCH + Duration::from_secs(1700000000));
+        let (router, _) = router(card).split_for_parts();
+
+        let request = Request::get(uri).body(Body::empty()).unwrap();
+        let response = router.oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    #[tokio::test]
+    async fn list_models_advertises_card() {
+        let (status, body) = get("/models").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            body,
+            r#"{"object":"list","data":[{"id":"openai/whisper-large-v3","object":"model","created":1700000000,"owned_by":"huggingface"}]}"#
+        );
+    }
+
+    #[tokio::test]
+    async fn retrieve_model_by_id() {
+        let (status, body) = get("/models/openai/whisper-large-v3").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains(r#""id":"openai/whisper-large-v3""#));
+
+        let (status, _) = get("/models/gpt-4o").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[test]
+    fn card_is_completed_by_handler_info() {
+        let info = ModelInfo::default()
+            .id("openai/whisper-large-v3-turbo")
+            .revision("main")
+            .task("automatic-speech-recognition")
+            .languages(["en", "fr"])
+            .dtype("float16");
+        let card = ModelCard::new("/repository")
+            .created(UNIX_EPOCH + Duration::from_secs(1700000000))
+            .with_info(info);
+
+        assert_eq!(
+            serde_json::to_string(&card).unwrap(),
+            r#"{"id":"openai/whisper-large-v3-turbo","object":"model","created":1700000000,"owned_by":"huggingface","revision":"main","task":"automatic-speech-recognition","languages":["en","fr"],"dtype":"float16"}"#
+        );
+        assert_eq!(
+            card.info().id.as_deref(),
+            Some("openai/whisper-large-v3-turbo")
+        );
+        assert!(card.info().describe().contains("- Languages: en, fr"));
+    }
+
+    #[test]
+    fn info_bounds_requests() {
+        let info = ModelInfo::default()
+            .context_length(4096)
+            .max_output_tokens(8192)
+            .languages(["en"]);
+
+        assert!(
+            info.ensure_output_tokens("max_completion_tokens", 4096)
+                .is_ok()
+        );
+        let err = info
+            .ensure_output_tokens("max_completion_tokens", 4097)
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            OpenAiError::Validation(message)
+                if message == "Parameter 'max_completion_tokens' must be at most 4096, got 4097"
+        ));
+
+        assert!(info.ensure_language("en").is_ok());
+        assert!(info.ensure_language("fr").is_err());
+
+        // Constraints left unset are not enforced
+        assert!(info.ensure_voice("alloy").is_ok());
+        assert!(
+            ModelInfo::default()
+                .ensure_output_tokens("max_completion_tokens", u32::MAX)
+                .is_ok()
+        );
+    }
+}
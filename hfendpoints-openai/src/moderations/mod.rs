@@ -0,0 +1,40 @@
+pub mod moderation;
+
+pub const MODERATIONS_TAG: &str = "Moderations";
+pub const MODERATIONS_DESC: &str =
+    "Given text, the model will classify if it is potentially harmful.";
+
+#[cfg(feature = "python")]
+pub(crate) mod python {
+    use crate::moderations::moderation::{ModerationRequest, ModerationResponse, ModerationResult};
+    use hfendpoints_binding_python::ImportablePyModuleBuilder;
+    use pyo3::prelude::*;
+
+    mod moderations {
+        use crate::moderations::moderation::{
+            ModerationRequest, ModerationResponse, ModerationRouter,
+        };
+        use crate::python::{impl_pyendpoint, impl_pyhandler};
+
+        impl_pyhandler!(ModerationRequest, ModerationResponse);
+        impl_pyendpoint!(
+            "ModerationEndpoint",
+            PyModerationEndpoint,
+            PyHandler,
+            ModerationRouter
+        );
+    }
+
+    /// Bind hfendpoints.openai.moderations submodule into the exported Python wheel
+    pub fn bind<'py>(py: Python<'py>, name: &str) -> PyResult<Bound<'py, PyModule>> {
+        let module = ImportablePyModuleBuilder::new(py, name)?
+            .defaults()?
+            .add_class::<ModerationRequest>()?
+            .add_class::<ModerationResult>()?
+            .add_class::<ModerationResponse>()?
+            .add_class::<moderations::PyModerationEndpoint>()?
+            .finish();
+
+        Ok(module)
+    }
+}
@@ -0,0 +1,352 @@
+use crate::context::{Context, RequestContext};
+use crate::endpoint::{schedule_task, task_router};
+use crate::error::TaskErrors;
+use crate::moderations::MODERATIONS_TAG;
+use crate::policy::RequestPolicy;
+use crate::telemetry::record_task;
+use crate::{OpenAiError, OpenAiResult, TaskRequest};
+use axum::extract::State;
+use axum::response::{IntoResponse, Response};
+use axum::{Extension, Json};
+use hfendpoints_core::{EndpointContext, RequestSender};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use tracing::instrument;
+use utoipa::ToSchema;
+use utoipa_axum::router::OpenApiRouter;
+use utoipa_axum::routes;
+
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+
+/// Score from which a category is flagged, unless the handler flags the categories itself
+const DEFAULT_FLAG_THRESHOLD: f32 = 0.5;
+
+/// Input text to classify, either a single string or an array of strings.
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Clone, Deserialize, ToSchema)]
+#[serde(untagged)]
+pub enum ModerationInput {
+    Single(String),
+    Multiple(Vec<String>),
+}
+
+impl From<ModerationInput> for Vec<String> {
+    fn from(value: ModerationInput) -> Self {
+        match value {
+            ModerationInput::Single(input) => vec![input],
+            ModerationInput::Multiple(inputs) => inputs,
+        }
+    }
+}
+
+/// Classifies if text is potentially harmful.
+#[cfg_attr(feature = "python", pyclass(frozen))]
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Clone, Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct ModerationRequest {
+    /// Input text to classify, encoded as a string or array of strings.
+    #[serde(deserialize_with = "deserialize_input")]
+    #[schema(value_type = ModerationInput)]
+    pub input: Vec<String>,
+
+    /// Not used, here for compatibility purpose with OpenAI Platform
+    pub model: Option<String>,
+}
+
+fn deserialize_input<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    Ok(ModerationInput::deserialize(deserializer)?.into())
+}
+
+impl ModerationRequest {
+    #[instrument(skip_all)]
+    fn validate(self) -> OpenAiResult<Self> {
+        if self.input.is_empty() || self.input.iter().any(String::is_empty) {
+            return Err(OpenAiError::Validation(String::from(
+                "Parameter 'input' cannot be empty",
+            )));
+        }
+
+        Ok(self)
+    }
+}
+
+/// Classification of a single input, the categories being the ones of the served model
+/// (i.e. `harassment`, `self-harm/intent` or `violence` for OpenAI's).
+#[cfg_attr(feature = "python", pyclass(frozen))]
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Clone, Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct ModerationResult {
+    /// Whether any of the categories are flagged.
+    flagged: bool,
+
+    /// The categories, along with whether they are flagged or not.
+    categories: BTreeMap<String, bool>,
+
+    /// The categories, along with the scores predicted by the model.
+    category_scores: BTreeMap<String, f32>,
+}
+
+impl ModerationResult {
+    /// Classification scoring every category of `category_scores`, categories not flagged by
+    /// `categories` being flagged when scored at least 0.5
+    pub fn new(
+        category_scores: BTreeMap<String, f32>,
+        categories: Option<BTreeMap<String, bool>>,
+    ) -> Self {
+        let mut categories = categories.unwrap_or_default();
+        for (category, score) in &category_scores {
+            categories
+                .entry(category.clone())
+                .or_insert(*score >= DEFAULT_FLAG_THRESHOLD);
+        }
+
+        Self {
+            flagged: categories.values().any(|flagged| *flagged),
+            categories,
+            category_scores,
+        }
+    }
+}
+
+/// Represents if a given text input is potentially harmful.
+#[cfg_attr(feature = "python", pyclass(frozen))]
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Clone, Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct ModerationResponse {
+    /// The unique identifier for the moderation request.
+    #[serde(default)]
+    id: String,
+
+    /// The model used to generate the moderation results.
+    model: String,
+
+    /// A list of moderation objects, one per input.
+    results: Vec<ModerationResult>,
+}
+
+impl ModerationResponse {
+    pub fn new(model: String, results: Vec<ModerationResult>) -> Self {
+        Self {
+            id: String::new(),
+            model,
+            results,
+        }
+    }
+
+    /// Identify the response after the request, ensuring every input got classified
+    fn identified(mut self, request_id: &str, inputs: usize) -> OpenAiResult<Self> {
+        if self.results.len() != inputs {
+            return Err(OpenAiError::InvalidResponse(format!(
+                "{inputs} inputs were classified, got {} results",
+                self.results.len()
+            )));
+        }
+
+        self.id = format!("modr-{request_id}");
+        Ok(self)
+    }
+}
+
+impl IntoResponse for ModerationResponse {
+    fn into_response(self) -> Response {
+        Json::from(self).into_response()
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/moderations",
+    tag = MODERATIONS_TAG,
+    request_body(content = ModerationRequest, content_type = "application/json"),
+    responses(
+        (status = OK, description = "Classifies if text is potentially harmful.", body = ModerationResponse),
+        TaskErrors,
+    )
+)]
+#[instrument(skip(state, policy, metadata, request))]
+pub async fn moderate(
+    State(state): State<EndpointContext<(ModerationRequest, Context), ModerationResponse>>,
+    Extension(policy): Extension<Arc<RequestPolicy>>,
+    metadata: RequestContext,
+    Json(mut request): Json<Value>,
+) -> OpenAiResult<ModerationResponse> {
+    record_task("moderation");
+
+    // Apply the operator's defaults and overrides before decoding
+    policy.apply(&mut request);
+    let request = serde_json::from_value::<ModerationRequest>(request)
+        .map_err(|err| OpenAiError::Validation(err.to_string()))?
+        .validate()?;
+
+    // Ask for the inference thread to handle it and wait for answers
+    let id = metadata.request_id().to_string();
+    let inputs = request.input.len();
+    let response = schedule_task(&state, metadata, request)
+        .await?
+        .response()
+        .await?;
+    response.identified(&id, inputs)
+}
+
+/// Helper factory to build
+/// [OpenAi Platform compatible Moderations endpoint](https://platform.openai.com/docs/api-reference/moderations/create)
+#[derive(Clone)]
+pub struct ModerationRouter(pub RequestSender<(ModerationRequest, Context), ModerationResponse>);
+
+impl From<ModerationRouter> for OpenApiRouter {
+    fn from(value: ModerationRouter) -> Self {
+        task_router(
+            OpenApiRouter::new().routes(routes!(moderate)),
+            value.0,
+            "/moderations",
+        )
+    }
+}
+
+impl TaskRequest for ModerationRequest {
+    type Response = ModerationResponse;
+
+    fn router(sender: RequestSender<(Self, Context), Self::Response>) -> OpenApiRouter {
+        ModerationRouter(sender).into()
+    }
+}
+
+#[cfg(feature = "python")]
+pub(crate) mod python {
+    use crate::moderations::moderation::{ModerationRequest, ModerationResponse, ModerationResult};
+    use crate::python::TextDelta;
+    use pyo3::prelude::*;
+    use std::collections::BTreeMap;
+
+    impl TextDelta for ModerationResponse {}
+
+    #[pymethods]
+    impl ModerationRequest {
+        #[getter(input)]
+        fn get_input(&self) -> Vec<String> {
+            self.input.clone()
+        }
+
+        #[getter(model)]
+        fn get_model(&self) -> Option<&str> {
+            self.model.as_deref()
+        }
+    }
+
+    #[pymethods]
+    impl ModerationResult {
+        #[new]
+        #[pyo3(signature = (category_scores, categories = None))]
+        fn py_new(
+            category_scores: BTreeMap<String, f32>,
+            categories: Option<BTreeMap<String, bool>>,
+        ) -> Self {
+            Self::new(category_scores, categories)
+        }
+
+        #[getter(flagged)]
+        fn get_flagged(&self) -> bool {
+            self.flagged
+        }
+    }
+
+    #[pymethods]
+    impl ModerationResponse {
+        #[new]
+        fn py_new(model: String, results: Vec<ModerationResult>) -> Self {
+            Self::new(model, results)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::moderations::moderation::{
+        ModerationRequest, ModerationResponse, ModerationResult, ModerationRouter,
+    };
+    use crate::testing::{answer, post};
+    use axum::http::StatusCode;
+    use hfendpoints_core::request_channel;
+    use serde_json::{Value, json};
+    use std::collections::BTreeMap;
+
+    /// Classify every input containing "hate" as such, answering `missing` results less than inputs
+    async fn moderate(body: &'static str, missing: usize) -> (StatusCode, Value) {
+        let (sender, receiver) = request_channel(1);
+        answer(receiver, move |request: ModerationRequest| {
+            let results = request
+                .input
+                .iter()
+                .skip(missing)
+                .map(|input| {
+                    let score = if input.contains("hate") { 0.9 } else { 0.1 };
+                    let scores = BTreeMap::from([
+                        (String::from("hate"), score),
+                        (String::from("violence"), 0.25),
+                    ]);
+                    ModerationResult::new(scores, None)
+                })
+                .collect();
+            Ok(ModerationResponse::new(String::from("guard"), results))
+        });
+
+        let router = ModerationRouter(sender);
+        let (status, _, body) = post(router, "/moderations", "application/json", body).await;
+        (status, serde_json::from_slice(&body).unwrap_or_default())
+    }
+
+    #[tokio::test]
+    async fn moderate_flags_categories_over_threshold() {
+        let (status, body) = moderate(r#"{"input": ["I hate you", "Hello"]}"#, 0).await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            body,
+            json!({
+                "id": "modr-test",
+                "model": "guard",
+                "results": [
+                    {
+                        "flagged": true,
+                        "categories": {"hate": true, "violence": false},
+                        "category_scores": {"hate": 0.9, "violence": 0.25}
+                    },
+                    {
+                        "flagged": false,
+                        "categories": {"hate": false, "violence": false},
+                        "category_scores": {"hate": 0.1, "violence": 0.25}
+                    }
+                ]
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn moderate_rejects_invalid_exchanges() {
+        let (status, _) = moderate(r#"{"input": ""}"#, 0).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        // Every input must be classified
+        let (status, body) = moderate(r#"{"input": ["Hello", "world"]}"#, 1).await;
+        assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(body["error"]["code"], "invalid_response");
+    }
+
+    #[test]
+    fn handler_flags_take_precedence() {
+        let scores = BTreeMap::from([(String::from("sexual"), 0.4)]);
+        let flags = BTreeMap::from([(String::from("sexual"), true)]);
+        let result = ModerationResult::new(scores, Some(flags));
+        assert!(result.flagged);
+        assert!(result.categories["sexual"]);
+    }
+}
@@ -0,0 +1,251 @@
+//! Parsing of `multipart/form-data` bodies into typed forms.
+//!
+//! Forms derive `FromMultipart`, which matches the parts against the fields of the struct, see
+//! `hfendpoints_openai_derive` for the supported field types. The struct also derives `ToSchema`,
+//! so the documented form and the parsed one cannot drift apart.
+//!
+//! Malformed forms, i.e. parts without a name, fields provided more than once or values failing
+//! to parse, are rejected as invalid requests naming the offending field.
+use crate::audio::decode::StreamedDecoding;
+use crate::compression::{ContentEncoding, decompress};
+use crate::policy::RequestPolicy;
+use crate::{OpenAiError, OpenAiResult};
+use axum::body::Bytes;
+use axum::extract::Multipart;
+use axum::extract::multipart::Field;
+use std::str::FromStr;
+
+pub(crate) use hfendpoints_openai_derive::FromMultipart;
+
+/// Forms read from a `multipart/form-data` body
+pub(crate) trait FromMultipart: Sized {
+    /// Read the parts of `multipart`, resolving the text fields through `policy`
+    async fn from_multipart(multipart: Multipart, policy: &RequestPolicy) -> OpenAiResult<Self>;
+}
+
+/// File uploaded through a form, decompressed when the part carries a `Content-Encoding`
+#[cfg_attr(debug_assertions, derive(Debug))]
+pub(crate) struct FilePart {
+    pub(crate) content: Bytes,
+    pub(crate) content_type: String,
+
+    /// Decoding of the audio file started while it was received, see `decode_while_receiving`
+    pub(crate) decoding: Option<StreamedDecoding>,
+}
+
+/// Content type of the files uploaded without one, guessed from the extension of `file_name`
+fn guess_content_type(file_name: Option<&str>) -> &'static str {
+    let extension = file_name
+        .and_then(|name| name.rsplit_once('.'))
+        .map(|(_, extension)| extension.to_ascii_lowercase());
+
+    match extension.as_deref() {
+        Some("flac") => "audio/flac",
+        Some("gif") => "image/gif",
+        Some("jpg" | "jpeg") => "image/jpeg",
+        Some("m4a") => "audio/mp4",
+        Some("mp3" | "mpga" | "mpeg") => "audio/mpeg",
+        Some("mp4") => "video/mp4",
+        Some("ogg" | "oga" | "opus") => "audio/ogg",
+        Some("png") => "image/png",
+        Some("wav") => "audio/wav",
+        Some("webm") => "audio/webm",
+        Some("webp") => "image/webp",
+        _ => "application/octet-stream",
+    }
+}
+
+impl FilePart {
+    pub(crate) async fn read(mut field: Field<'_>, limit: usize) -> OpenAiResult<Self> {
+        let content_type = match field.content_type() {
+            Some(content_type) => content_type.to_string(),
+            None => guess_content_type(field.file_name()).to_string(),
+        };
+
+        // Batching clients may pre-compress the file part itself
+        let encoding = ContentEncoding::from_headers(field.headers())?;
+        if encoding != ContentEncoding::Identity {
+            let content = decompress(field.bytes().await?, encoding, limit).await?;
+            return Ok(Self {
+                content,
+                content_type,
+                decoding: None,
+            });
+        }
+
+        // Audio files may be decoded while received, chunk by chunk
+        let decoding = StreamedDecoding::start(&content_type);
+        let mut content = Vec::new();
+        while let Some(chunk) = field.chunk().await? {
+            content.extend_from_slice(&chunk);
+            if let Some(decoding) = &decoding {
+                decoding.push(chunk);
+            }
+        }
+
+        Ok(Self {
+            content: Bytes::from(content),
+            content_type,
+            decoding,
+        })
+    }
+}
+
+/// Name of the part `field`, rejecting parts not declaring one
+pub(crate) fn field_name(field: &Field<'_>) -> OpenAiResult<String> {
+    match field.name() {
+        Some(name) if !name.is_empty() => Ok(name.to_string()),
+        _ => Err(OpenAiError::Validation(String::from(
+            "Form part is missing its name, expected Content-Disposition: form-data; name=\"...\"",
+        ))),
+    }
+}
+
+/// Reject forms providing the field `name`, already read into `value`, more than once
+pub(crate) fn unique<T>(value: &Option<T>, name: &str) -> OpenAiResult<()> {
+    match value {
+        Some(_) => Err(OpenAiError::Validation(format!(
+            "Parameter '{name}' was provided more than once"
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// Point the failure to parse the field `name` to the field
+fn invalid(name: &str, err: OpenAiError) -> OpenAiError {
+    match err {
+        OpenAiError::Validation(message) => {
+            OpenAiError::Validation(format!("Invalid value for parameter '{name}': {message}"))
+        }
+        err => err,
+    }
+}
+
+/// Reject forms missing the field `name`
+pub(crate) fn required<T>(value: Option<T>, name: &str) -> OpenAiResult<T> {
+    value.ok_or_else(|| {
+        OpenAiError::Validation(format!("Required parameter '{name}' was not provided"))
+    })
+}
+
+/// Parse the text of the field `name`, if provided
+pub(crate) fn parse<T>(name: &str, value: Option<String>) -> OpenAiResult<Option<T>>
+where
+    T: FromStr,
+    OpenAiError: From<T::Err>,
+{
+    value
+        .as_deref()
+        .map(|value| T::from_str(value).map_err(|err| invalid(name, err.into())))
+        .transpose()
+}
+
+/// Parse the text of every occurrence of the repeated field `name`
+pub(crate) fn parse_all<T>(name: &str, values: Vec<String>) -> OpenAiResult<Vec<T>>
+where
+    T: FromStr,
+    OpenAiError: From<T::Err>,
+{
+    values
+        .iter()
+        .map(|value| T::from_str(value).map_err(|err| invalid(name, err.into())))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::multipart::{FilePart, FromMultipart};
+    use crate::policy::RequestPolicy;
+    use axum::body::Body;
+    use axum::extract::{FromRequest, Multipart};
+    use axum::http::Request;
+    use axum::http::header::CONTENT_TYPE;
+
+    #[derive(FromMultipart)]
+    #[multipart(file_limit = 16)]
+    struct Form {
+        file: FilePart,
+        prompt: Option<String>,
+        temperature: Option<f32>,
+        stream: bool,
+        #[multipart(rename = "tag")]
+        tags: Vec<String>,
+    }
+
+    async fn parse(form: &'static str, policy: &RequestPolicy) -> Result<Form, String> {
+        let request = Request::post("/")
+            .header(CONTENT_TYPE, "multipart/form-data; boundary=hfendpoints")
+            .body(Body::from(form))
+            .unwrap();
+        let multipart = Multipart::from_request(request, &()).await.unwrap();
+        Form::from_multipart(multipart, policy)
+            .await
+            .map_err(|err| err.to_string())
+    }
+
+    #[tokio::test]
+    async fn parse_fields_by_type() {
+        let form = "--hfendpoints\r\nContent-Disposition: form-data; name=\"file\"; filename=\"a.wav\"\r\nContent-Type: audio/wav\r\n\r\nRIFF\r\n\
+            --hfendpoints\r\nContent-Disposition: form-data; name=\"stream\"\r\n\r\ntrue\r\n\
+            --hfendpoints\r\nContent-Disposition: form-data; name=\"tag[]\"\r\n\r\na\r\n\
+            --hfendpoints\r\nContent-Disposition: form-data; name=\"tag\"\r\n\r\nb\r\n\
+            --hfendpoints--\r\n";
+        let policy: RequestPolicy =
+            serde_json::from_str(r#"{"defaults": {"temperature": 0.5}}"#).unwrap();
+
+        let form = parse(form, &policy).await.unwrap();
+        assert_eq!(form.file.content.as_ref(), b"RIFF");
+        assert_eq!(form.file.content_type, "audio/wav");
+        assert_eq!(form.prompt, None);
+        assert_eq!(form.temperature, Some(0.5));
+        assert!(form.stream);
+        assert_eq!(form.tags, ["a", "b"]);
+    }
+
+    #[tokio::test]
+    async fn reject_malformed_forms() {
+        let policy = RequestPolicy::default();
+
+        let missing = "--hfendpoints\r\nContent-Disposition: form-data; name=\"stream\"\r\n\r\ntrue\r\n--hfendpoints--\r\n";
+        let err = parse(missing, &policy).await.err().unwrap();
+        assert!(err.contains("Required parameter 'file' was not provided"));
+
+        let unknown = "--hfendpoints\r\nContent-Disposition: form-data; name=\"voice\"\r\n\r\nalloy\r\n--hfendpoints--\r\n";
+        let err = parse(unknown, &policy).await.err().unwrap();
+        assert!(err.contains("Unknown field: voice"));
+
+        let invalid = "--hfendpoints\r\nContent-Disposition: form-data; name=\"file\"\r\n\r\nRIFF\r\n\
+            --hfendpoints\r\nContent-Disposition: form-data; name=\"stream\"\r\n\r\nyes\r\n\
+            --hfendpoints--\r\n";
+        let err = parse(invalid, &policy).await.err().unwrap();
+        assert!(err.contains(
+            "Invalid value for parameter 'stream': provided string was not `true` or `false`"
+        ));
+
+        let unnamed =
+            "--hfendpoints\r\nContent-Disposition: form-data\r\n\r\ntrue\r\n--hfendpoints--\r\n";
+        let err = parse(unnamed, &policy).await.err().unwrap();
+        assert!(err.contains("Form part is missing its name"));
+
+        let duplicated = "--hfendpoints\r\nContent-Disposition: form-data; name=\"file\"\r\n\r\nRIFF\r\n\
+            --hfendpoints\r\nContent-Disposition: form-data; name=\"file\"\r\n\r\nRIFF\r\n\
+            --hfendpoints--\r\n";
+        let err = parse(duplicated, &policy).await.err().unwrap();
+        assert!(err.contains("Parameter 'file' was provided more than once"));
+    }
+
+    #[tokio::test]
+    async fn guess_missing_content_types() {
+        let form = "--hfendpoints\r\nContent-Disposition: form-data; name=\"file\"; filename=\"a.MP3\"\r\n\r\nID3\r\n\
+            --hfendpoints\r\nContent-Disposition: form-data; name=\"stream\"\r\n\r\nfalse\r\n\
+            --hfendpoints--\r\n";
+        let form = parse(form, &RequestPolicy::default()).await.unwrap();
+        assert_eq!(form.file.content_type, "audio/mpeg");
+
+        let form = "--hfendpoints\r\nContent-Disposition: form-data; name=\"file\"\r\n\r\nID3\r\n\
+            --hfendpoints\r\nContent-Disposition: form-data; name=\"stream\"\r\n\r\nfalse\r\n\
+            --hfendpoints--\r\n";
+        let form = parse(form, &RequestPolicy::default()).await.unwrap();
+        assert_eq!(form.file.content_type, "application/octet-stream");
+    }
+}
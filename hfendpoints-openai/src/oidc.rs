@@ -0,0 +1,694 @@
+//! Authentication of the requests carrying a JSON Web Token issued by an OpenID provider.
+//!
+//! Enterprises fronting the endpoint with their identity provider set `HFENDPOINT_OIDC_ISSUER` to
+//! the issuer of the tokens and `HFENDPOINT_OIDC_AUDIENCE` to the comma-separated audiences the
+//! endpoint accepts. Requests then carry the token as `Authorization: Bearer <JWT>`, which must:
+//! - be signed (`RS256`, `RS384`, `RS512`, `PS256`, `PS384`, `PS512`, `ES256`, `ES384` or `EdDSA`)
+//!   by one of the keys published by the provider
+//! - be issued by the configured issuer, for one of the configured audiences
+//! - not be expired, nor used before its `nbf` time, a minute of clock skew being tolerated
+//!
+//! The keys are discovered through `<issuer>/.well-known/openid-configuration`, unless their
+//! location is set through `HFENDPOINT_OIDC_JWKS_URI` (`file://` URIs being read from disk), and
+//! cached for ten minutes. Tokens signed by an unknown key trigger a refresh, at most every
+//! thirty seconds, so rotated keys are picked up without waiting for the cache to expire.
+//!
+//! The caller is identified by the `sub` claim and, when `HFENDPOINT_OIDC_TENANT_CLAIM` names a
+//! claim (`org_id`, or `org.id` for nested ones), by the tenant it holds, tokens missing it being
+//! rejected. Both are handed over to the handler through [`RequestContext`](crate::RequestContext).
+//! API keys, when configured as well, keep being accepted, see `auth`.
+use crate::auth::{DEFAULT_EXEMPT_PATHS, split_list};
+use crate::{AUTH_EXEMPT_ENV, OpenAiError, OpenAiResult};
+use base64::Engine;
+use base64::prelude::BASE64_URL_SAFE_NO_PAD;
+use ring::signature::{self, RsaPublicKeyComponents, UnparsedPublicKey};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::sync::Mutex;
+use tracing::{debug, error, info, instrument, warn};
+
+/// Environment variable holding the issuer of the tokens, enabling their authentication
+pub const OIDC_ISSUER_ENV: &str = "HFENDPOINT_OIDC_ISSUER";
+
+/// Environment variable holding the comma-separated audiences the tokens must be issued for
+pub const OIDC_AUDIENCE_ENV: &str = "HFENDPOINT_OIDC_AUDIENCE";
+
+/// Environment variable holding the location of the keys, discovered from the issuer when not set
+pub const OIDC_JWKS_URI_ENV: &str = "HFENDPOINT_OIDC_JWKS_URI";
+
+/// Environment variable holding the claim identifying the tenant of the caller
+pub const OIDC_TENANT_CLAIM_ENV: &str = "HFENDPOINT_OIDC_TENANT_CLAIM";
+
+/// Time the keys of the provider are cached for
+const JWKS_TTL: Duration = Duration::from_secs(600);
+
+/// Minimum time between two fetches of the keys, whatever the unknown keys presented
+const JWKS_MIN_REFRESH: Duration = Duration::from_secs(30);
+
+/// Time the provider has to answer for the keys
+const JWKS_FETCH_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Clock skew tolerated between the provider and the endpoint
+const DEFAULT_LEEWAY: Duration = Duration::from_secs(60);
+
+/// Caller authenticated through a token, handed over to the handler
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Identity {
+    /// Subject of the token (`sub`)
+    pub subject: Option<String>,
+
+    /// Tenant of the caller, read from the claim configured through `HFENDPOINT_OIDC_TENANT_CLAIM`
+    pub tenant: Option<String>,
+}
+
+/// Key as described in the JSON Web Key Set, only the parameters used for verification are read
+#[derive(Deserialize)]
+struct Jwk {
+    kty: String,
+    kid: Option<String>,
+    alg: Option<String>,
+    #[serde(rename = "use")]
+    usage: Option<String>,
+    crv: Option<String>,
+    n: Option<String>,
+    e: Option<String>,
+    x: Option<String>,
+    y: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct JwkSet {
+    keys: Vec<Jwk>,
+}
+
+/// Subset of the OpenID provider metadata locating the keys
+#[derive(Deserialize)]
+struct Discovery {
+    jwks_uri: String,
+}
+
+enum PublicKey {
+    Rsa { n: Vec<u8>, e: Vec<u8> },
+    P256(Vec<u8>),
+    P384(Vec<u8>),
+    Ed25519(Vec<u8>),
+}
+
+struct VerifyingKey {
+    kid: Option<String>,
+    alg: Option<String>,
+    key: PublicKey,
+}
+
+impl TryFrom<Jwk> for VerifyingKey {
+    type Error = String;
+
+    fn try_from(jwk: Jwk) -> Result<Self, Self::Error> {
+        let name = jwk.kid.as_deref().unwrap_or("without kid").to_string();
+        let decode = |parameter: Option<&String>, field: &str| match parameter {
+            Some(value) => BASE64_URL_SAFE_NO_PAD
+                .decode(value)
+                .map_err(|err| format!("key {name}: parameter '{field}' is not base64url: {err}")),
+            None => Err(format!("key {name}: missing parameter '{field}'")),
+        };
+
+        // Elliptic curve points are handed over to ring uncompressed
+        let point = || -> Result<Vec<u8>, String> {
+            let mut point = vec![0x04];
+            point.extend(decode(jwk.x.as_ref(), "x")?);
+            point.extend(decode(jwk.y.as_ref(), "y")?);
+            Ok(point)
+        };
+
+        let key = match (jwk.kty.as_str(), jwk.crv.as_deref()) {
+            ("RSA", _) => PublicKey::Rsa {
+                n: decode(jwk.n.as_ref(), "n")?,
+                e: decode(jwk.e.as_ref(), "e")?,
+            },
+            ("EC", Some("P-256")) => PublicKey::P256(point()?),
+            ("EC", Some("P-384")) => PublicKey::P384(point()?),
+            ("OKP", Some("Ed25519")) => PublicKey::Ed25519(decode(jwk.x.as_ref(), "x")?),
+            (kty, crv) => {
+                return Err(format!(
+                    "key {name}: unsupported key type '{kty}' (curve {crv:?})"
+                ));
+            }
+        };
+
+        Ok(Self {
+            kid: jwk.kid,
+            alg: jwk.alg,
+            key,
+        })
+    }
+}
+
+impl VerifyingKey {
+    /// Whether `signature` of `message` was produced by this key through `alg`
+    fn verifies(&self, alg: &str, message: &[u8], signature: &[u8]) -> bool {
+        if self.alg.as_deref().is_some_and(|expected| expected != alg) {
+            return false;
+        }
+
+        let rsa = |algorithm: &'static signature::RsaParameters, n: &[u8], e: &[u8]| {
+            RsaPublicKeyComponents { n, e }
+                .verify(algorithm, message, signature)
+                .is_ok()
+        };
+        let unparsed = |algorithm: &'static dyn signature::VerificationAlgorithm, key: &[u8]| {
+            UnparsedPublicKey::new(algorithm, key)
+                .verify(message, signature)
+                .is_ok()
+        };
+
+        match (alg, &self.key) {
+            ("RS256", PublicKey::Rsa { n, e }) => rsa(&signature::RSA_PKCS1_2048_8192_SHA256, n, e),
+            ("RS384", PublicKey::Rsa { n, e }) => rsa(&signature::RSA_PKCS1_2048_8192_SHA384, n, e),
+            ("RS512", PublicKey::Rsa { n, e }) => rsa(&signature::RSA_PKCS1_2048_8192_SHA512, n, e),
+            ("PS256", PublicKey::Rsa { n, e }) => rsa(&signature::RSA_PSS_2048_8192_SHA256, n, e),
+            ("PS384", PublicKey::Rsa { n, e }) => rsa(&signature::RSA_PSS_2048_8192_SHA384, n, e),
+            ("PS512", PublicKey::Rsa { n, e }) => rsa(&signature::RSA_PSS_2048_8192_SHA512, n, e),
+            ("ES256", PublicKey::P256(point)) => {
+                unparsed(&signature::ECDSA_P256_SHA256_FIXED, point)
+            }
+            ("ES384", PublicKey::P384(point)) => {
+                unparsed(&signature::ECDSA_P384_SHA384_FIXED, point)
+            }
+            ("EdDSA", PublicKey::Ed25519(key)) => unparsed(&signature::ED25519, key),
+            _ => false,
+        }
+    }
+}
+
+/// Signing algorithms of the tokens which are accepted
+const SUPPORTED_ALGORITHMS: [&str; 9] = [
+    "RS256", "RS384", "RS512", "PS256", "PS384", "PS512", "ES256", "ES384", "EdDSA",
+];
+
+/// Protected header of the token
+#[derive(Deserialize)]
+struct JwtHeader {
+    alg: String,
+    kid: Option<String>,
+    crit: Option<Vec<String>>,
+}
+
+/// Keys of the provider, along with when they were fetched
+struct FetchedKeys {
+    keys: Arc<[VerifyingKey]>,
+    fetched: Instant,
+}
+
+/// Validation of the tokens issued by an OpenID provider
+pub struct Oidc {
+    issuer: String,
+    audiences: Vec<String>,
+    jwks_uri: Option<String>,
+    tenant_claim: Option<String>,
+    leeway: Duration,
+    exempt: Vec<String>,
+    keys: RwLock<Option<FetchedKeys>>,
+
+    /// Held while fetching the keys, so concurrent requests wait for a single fetch
+    refresh: Mutex<()>,
+    client: reqwest::Client,
+}
+
+impl Oidc {
+    /// Accept the tokens issued by `issuer` for one of the `audiences`
+    pub fn new<A: Into<String>>(
+        issuer: impl Into<String>,
+        audiences: impl IntoIterator<Item = A>,
+    ) -> Self {
+        Self {
+            issuer: issuer.into(),
+            audiences: audiences.into_iter().map(Into::into).collect(),
+            jwks_uri: None,
+            tenant_claim: None,
+            leeway: DEFAULT_LEEWAY,
+            exempt: DEFAULT_EXEMPT_PATHS.map(String::from).to_vec(),
+            keys: RwLock::new(None),
+            refresh: Mutex::new(()),
+            client: reqwest::Client::builder()
+                .timeout(JWKS_FETCH_TIMEOUT)
+                .build()
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Read the keys from `uri` rather than the location advertised by the provider
+    pub fn jwks_uri(mut self, uri: impl Into<String>) -> Self {
+        self.jwks_uri = Some(uri.into());
+        self
+    }
+
+    /// Identify the tenant of the caller through `claim`, dots separating nested claims
+    pub fn tenant_claim(mut self, claim: impl Into<String>) -> Self {
+        self.tenant_claim = Some(claim.into());
+        self
+    }
+
+    /// Tolerate `leeway` of clock skew when checking the validity period of the tokens
+    pub fn leeway(mut self, leeway: Duration) -> Self {
+        self.leeway = leeway;
+        self
+    }
+
+    /// Let requests for `paths` through without token
+    pub fn with_exempt<P: Into<String>>(mut self, paths: impl IntoIterator<Item = P>) -> Self {
+        self.exempt = paths.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Provider configured through `HFENDPOINT_OIDC_ISSUER` and the related variables, if any
+    pub fn from_env() -> Option<Self> {
+        let issuer = std::env::var(OIDC_ISSUER_ENV).ok()?;
+        let audiences = std::env::var(OIDC_AUDIENCE_ENV).unwrap_or_default();
+        let oidc = Self::new(issuer, split_list(&audiences));
+
+        // Tokens are all rejected rather than accepted whatever their audience
+        if oidc.audiences.is_empty() {
+            error!("{OIDC_AUDIENCE_ENV} is not set, every token will be rejected");
+        }
+
+        let oidc = match std::env::var(OIDC_JWKS_URI_ENV) {
+            Ok(uri) => oidc.jwks_uri(uri),
+            Err(_) => oidc,
+        };
+        let oidc = match std::env::var(OIDC_TENANT_CLAIM_ENV) {
+            Ok(claim) => oidc.tenant_claim(claim),
+            Err(_) => oidc,
+        };
+        let oidc = match std::env::var(AUTH_EXEMPT_ENV) {
+            Ok(paths) => oidc.with_exempt(split_list(&paths)),
+            Err(_) => oidc,
+        };
+
+        info!(
+            "Authenticating tokens issued by {} for {:?}, public paths: {:?}",
+            oidc.issuer, oidc.audiences, oidc.exempt
+        );
+        Some(oidc)
+    }
+
+    /// Whether requests for `path` go through without token
+    pub(crate) fn exempts(&self, path: &str) -> bool {
+        self.exempt.iter().any(|exempt| exempt == path)
+    }
+
+    /// Location of the keys, as configured or advertised by the provider
+    async fn locate_keys(&self) -> Result<String, String> {
+        if let Some(uri) = &self.jwks_uri {
+            return Ok(uri.clone());
+        }
+
+        let discovery = format!(
+            "{}/.well-known/openid-configuration",
+            self.issuer.trim_end_matches('/')
+        );
+        let discovery: Discovery = self
+            .client
+            .get(&discovery)
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status)
+            .map_err(|err| format!("failed to discover the provider at {discovery}: {err}"))?
+            .json()
+            .await
+            .map_err(|err| format!("malformed provider metadata at {discovery}: {err}"))?;
+        Ok(discovery.jwks_uri)
+    }
+
+    /// Fetch the signing keys of the provider, skipping the ones which cannot be used
+    #[instrument(skip(self))]
+    async fn fetch_keys(&self) -> Result<Vec<VerifyingKey>, String> {
+        let uri = self.locate_keys().await?;
+        let jwks = match uri.strip_prefix("file://") {
+            Some(path) => tokio::fs::read_to_string(path)
+                .await
+                .map_err(|err| format!("failed to read the keys at {uri}: {err}"))?,
+            None => self
+                .client
+                .get(&uri)
+                .send()
+                .await
+                .and_then(reqwest::Response::error_for_status)
+                .map_err(|err| format!("failed to fetch the keys at {uri}: {err}"))?
+                .text()
+                .await
+                .map_err(|err| format!("failed to fetch the keys at {uri}: {err}"))?,
+        };
+
+        let jwks: JwkSet = serde_json::from_str(&jwks)
+            .map_err(|err| format!("malformed key set at {uri}: {err}"))?;
+        let keys: Vec<_> = jwks
+            .keys
+            .into_iter()
+            .filter(|jwk| jwk.usage.as_deref().is_none_or(|usage| usage == "sig"))
+            .filter_map(|jwk| {
+                VerifyingKey::try_from(jwk)
+                    .inspect_err(|err| warn!("Ignoring signing key {err}"))
+                    .ok()
+            })
+            .collect();
+
+        debug!("Fetched {} signing key(s) from {uri}", keys.len());
+        Ok(keys)
+    }
+
+    /// Cached keys, if fetched within `max_age`
+    fn cached(&self, max_age: Duration) -> Option<Arc<[VerifyingKey]>> {
+        let keys = self.keys.read().unwrap_or_else(|err| err.into_inner());
+        keys.as_ref()
+            .filter(|keys| keys.fetched.elapsed() < max_age)
+            .map(|keys| Arc::clone(&keys.keys))
+    }
+
+    /// Keys of the provider, fetched again when expired or when none is identified by `kid`
+    async fn keys(&self, kid: Option<&str>) -> OpenAiResult<Arc<[VerifyingKey]>> {
+        let known = |keys: &Arc<[VerifyingKey]>| {
+            kid.is_none_or(|kid| keys.iter().any(|key| key.kid.as_deref() == Some(kid)))
+        };
+        if let Some(keys) = self.cached(JWKS_TTL).filter(known) {
+            return Ok(keys);
+        }
+
+        // Another request may have fetched the keys while this one was waiting
+        let _refreshing = self.refresh.lock().await;
+        if let Some(keys) = self.cached(JWKS_MIN_REFRESH) {
+            return Ok(keys);
+        }
+
+        match self.fetch_keys().await {
+            Ok(keys) => {
+                let keys: Arc<[VerifyingKey]> = keys.into();
+                let mut cached = self.keys.write().unwrap_or_else(|err| err.into_inner());
+                *cached = Some(FetchedKeys {
+                    keys: Arc::clone(&keys),
+                    fetched: Instant::now(),
+                });
+                Ok(keys)
+            }
+
+            // The provider being unreachable, tokens keep being checked against the known keys
+            Err(err) => match self.cached(Duration::MAX) {
+                Some(keys) => {
+                    warn!("Keeping the signing keys fetched previously: {err}");
+                    Ok(keys)
+                }
+                None => {
+                    error!("No signing key to authenticate tokens with: {err}");
+                    Err(OpenAiError::Unauthorized(String::from(
+                        "The signing keys of the identity provider are unavailable",
+                    )))
+                }
+            },
+        }
+    }
+
+    /// Identity of the caller presenting `token`, once its signature and claims are verified
+    pub(crate) async fn verify(&self, token: &str) -> OpenAiResult<Identity> {
+        let invalid = |reason: &str| OpenAiError::Unauthorized(format!("Invalid token: {reason}"));
+        let parts: Vec<&str> = token.split('.').collect();
+        let [header, claims, signature] = parts.as_slice() else {
+            return Err(invalid("expected the three parts of a JSON Web Token"));
+        };
+        let decode = |part: &str, name: &str| {
+            BASE64_URL_SAFE_NO_PAD
+                .decode(part)
+                .map_err(|_| invalid(&format!("the {name} is not base64url encoded")))
+        };
+
+        let header: JwtHeader = serde_json::from_slice(&decode(header, "header")?)
+            .map_err(|err| invalid(&format!("malformed header: {err}")))?;
+        if header.crit.is_some() {
+            return Err(invalid("unsupported critical header parameters"));
+        }
+        if !SUPPORTED_ALGORITHMS.contains(&header.alg.as_str()) {
+            return Err(invalid(&format!(
+                "unsupported signing algorithm '{}'",
+                header.alg
+            )));
+        }
+
+        // The signature covers the encoded header and claims
+        let signed = &token[..token.rfind('.').unwrap_or_default()];
+        let signature = decode(signature, "signature")?;
+        let keys = self.keys(header.kid.as_deref()).await?;
+        let verified = keys
+            .iter()
+            .filter(|key| header.kid.is_none() || key.kid == header.kid)
+            .any(|key| key.verifies(&header.alg, signed.as_bytes(), &signature));
+        if !verified {
+            return Err(invalid(
+                "the signature does not match any key of the provider",
+            ));
+        }
+
+        let claims: Value = serde_json::from_slice(&decode(claims, "claims")?)
+            .map_err(|err| invalid(&format!("malformed claims: {err}")))?;
+        self.validate(&claims).map_err(|reason| invalid(&reason))
+    }
+
+    /// Check the issuer, audience and validity period of the token holding `claims`
+    fn validate(&self, claims: &Value) -> Result<Identity, String> {
+        if claims["iss"].as_str() != Some(self.issuer.as_str()) {
+            return Err(format!("not issued by {}", self.issuer));
+        }
+
+        let audiences = match &claims["aud"] {
+            Value::String(audience) => vec![audience.as_str()],
+            Value::Array(audiences) => audiences.iter().filter_map(Value::as_str).collect(),
+            _ => Vec::new(),
+        };
+        if !audiences
+            .iter()
+            .any(|audience| self.audiences.iter().any(|accepted| accepted == audience))
+        {
+            return Err(String::from("not issued for this endpoint"));
+        }
+
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs_f64();
+        let leeway = self.leeway.as_secs_f64();
+        match claims["exp"].as_f64() {
+            Some(expiration) if now - leeway < expiration => {}
+            Some(_) => return Err(String::from("expired")),
+            None => return Err(String::from("missing claim 'exp'")),
+        }
+        if claims["nbf"].as_f64().is_some_and(|nbf| now + leeway < nbf) {
+            return Err(String::from("not valid yet"));
+        }
+
+        let tenant = match &self.tenant_claim {
+            Some(claim) => {
+                let value = claim
+                    .split('.')
+                    .try_fold(claims, |value, name| value.get(name));
+                match value {
+                    Some(Value::String(tenant)) => Some(tenant.clone()),
+                    Some(Value::Number(tenant)) => Some(tenant.to_string()),
+                    _ => return Err(format!("missing claim '{claim}'")),
+                }
+            }
+            None => None,
+        };
+
+        Ok(Identity {
+            subject: claims["sub"].as_str().map(String::from),
+            tenant,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::OpenAiError;
+    use crate::oidc::{Identity, Oidc};
+    use base64::Engine;
+    use base64::prelude::BASE64_URL_SAFE_NO_PAD;
+    use ring::rand::SystemRandom;
+    use ring::signature::{ECDSA_P256_SHA256_FIXED_SIGNING, EcdsaKeyPair, KeyPair};
+    use serde_json::{Value, json};
+    use std::path::PathBuf;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    const ISSUER: &str = "https://login.example.com";
+
+    /// Signing key of the provider, along with the JWK publishing it
+    fn signing_key(kid: &str) -> (EcdsaKeyPair, Value) {
+        let rng = SystemRandom::new();
+        let pkcs8 = EcdsaKeyPair::generate_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, &rng).unwrap();
+        let key = EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, pkcs8.as_ref(), &rng)
+            .unwrap();
+
+        let point = key.public_key().as_ref();
+        let jwk = json!({
+            "kty": "EC",
+            "crv": "P-256",
+            "kid": kid,
+            "use": "sig",
+            "x": BASE64_URL_SAFE_NO_PAD.encode(&point[1..33]),
+            "y": BASE64_URL_SAFE_NO_PAD.encode(&point[33..]),
+        });
+        (key, jwk)
+    }
+
+    /// Token holding `claims`, signed with `key` advertised as `kid`
+    fn sign(key: &EcdsaKeyPair, kid: &str, claims: Value) -> String {
+        let encode = |value: Value| BASE64_URL_SAFE_NO_PAD.encode(value.to_string());
+        let signed = format!(
+            "{}.{}",
+            encode(json!({"alg": "ES256", "typ": "JWT", "kid": kid})),
+            encode(claims)
+        );
+        let signature = key.sign(&SystemRandom::new(), signed.as_bytes()).unwrap();
+        format!("{signed}.{}", BASE64_URL_SAFE_NO_PAD.encode(signature))
+    }
+
+    /// Provider publishing `jwks` through a file named after `name`
+    fn provider(name: &str, jwks: Value) -> (Oidc, PathBuf) {
+        let path = std::env::temp_dir().join(format!(
+            "hfendpoints-oidc-{name}-{}.json",
+            std::process::id()
+        ));
+        std::fs::write(&path, jwks.to_string()).unwrap();
+        let oidc = Oidc::new(ISSUER, ["my-endpoint"])
+            .jwks_uri(format!("file://{}", path.display()))
+            .tenant_claim("org.id");
+        (oidc, path)
+    }
+
+    fn now() -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs()
+    }
+
+    #[tokio::test]
+    async fn verify_tokens_issued_by_the_provider() {
+        let (key, jwk) = signing_key("key-1");
+        let (oidc, path) = provider("verify", json!({"keys": [jwk]}));
+        let claims = json!({
+            "iss": ISSUER,
+            "aud": ["other", "my-endpoint"],
+            "sub": "alice",
+            "exp": now() + 300,
+            "org": {"id": "acme"},
+        });
+
+        let identity = oidc.verify(&sign(&key, "key-1", claims)).await.unwrap();
+        assert_eq!(
+            identity,
+            Identity {
+                subject: Some(String::from("alice")),
+                tenant: Some(String::from("acme")),
+            }
+        );
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn reject_invalid_tokens() {
+        let (key, jwk) = signing_key("key-1");
+        let (forged, _) = signing_key("key-1");
+        let (oidc, path) = provider("reject", json!({"keys": [jwk]}));
+        let claims = |overrides: Value| {
+            let mut claims = json!({
+                "iss": ISSUER,
+                "aud": "my-endpoint",
+                "exp": now() + 300,
+                "org": {"id": "acme"},
+            });
+            claims
+                .as_object_mut()
+                .unwrap()
+                .extend(overrides.as_object().unwrap().clone());
+            claims
+        };
+
+        for (token, reason) in [
+            (
+                sign(
+                    &key,
+                    "key-1",
+                    claims(json!({"iss": "https://evil.example.com"})),
+                ),
+                "not issued by https://login.example.com",
+            ),
+            (
+                sign(&key, "key-1", claims(json!({"aud": "other"}))),
+                "not issued for this endpoint",
+            ),
+            (
+                sign(&key, "key-1", claims(json!({"exp": now() - 3600}))),
+                "expired",
+            ),
+            (
+                sign(&key, "key-1", claims(json!({"nbf": now() + 3600}))),
+                "not valid yet",
+            ),
+            (
+                sign(&key, "key-1", claims(json!({"org": {}}))),
+                "missing claim 'org.id'",
+            ),
+            (
+                sign(&forged, "key-1", claims(json!({}))),
+                "the signature does not match any key of the provider",
+            ),
+            (
+                format!(
+                    "{}.{}.",
+                    BASE64_URL_SAFE_NO_PAD.encode(r#"{"alg":"none"}"#),
+                    BASE64_URL_SAFE_NO_PAD.encode(claims(json!({})).to_string())
+                ),
+                "unsupported signing algorithm 'none'",
+            ),
+            (String::from("key-1"), "expected the three parts"),
+        ] {
+            match oidc.verify(&token).await {
+                Err(OpenAiError::Unauthorized(message)) => {
+                    assert!(message.contains(reason), "{message}")
+                }
+                outcome => panic!("Expected {reason}, got {outcome:?}"),
+            }
+        }
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn refresh_keys_on_rotation() {
+        let (key, jwk) = signing_key("key-1");
+        let (oidc, path) = provider("rotation", json!({"keys": [jwk]}));
+        let claims = json!({
+            "iss": ISSUER,
+            "aud": "my-endpoint",
+            "exp": now() + 300,
+            "org": {"id": 42},
+        });
+        let identity = oidc
+            .verify(&sign(&key, "key-1", claims.clone()))
+            .await
+            .unwrap();
+        assert_eq!(identity.tenant.as_deref(), Some("42"));
+
+        // Keys fetched a moment ago are not fetched again whatever the key presented
+        let (rotated, jwk) = signing_key("key-2");
+        std::fs::write(&path, json!({"keys": [jwk]}).to_string()).unwrap();
+        assert!(
+            oidc.verify(&sign(&rotated, "key-2", claims.clone()))
+                .await
+                .is_err()
+        );
+
+        // Once they are old enough, an unknown key triggers a refresh
+        oidc.keys.write().unwrap().as_mut().unwrap().fetched -= super::JWKS_MIN_REFRESH;
+        assert!(oidc.verify(&sign(&rotated, "key-2", claims)).await.is_ok());
+        std::fs::remove_file(path).unwrap();
+    }
+}
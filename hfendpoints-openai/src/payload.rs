@@ -0,0 +1,193 @@
+//! Encoding of the requests and responses exchanged with remote workers in distributed mode.
+use crate::audio::speech::{SpeechRequest, SpeechResponse};
+use crate::audio::transcription::{
+    StreamEvent, Transcription, TranscriptionRequest, TranscriptionResponse, VerboseTranscription,
+};
+use crate::audio::translation::TranslationRequest;
+use crate::chat::completion::{ChatCompletionOutput, ChatCompletionRequest};
+use crate::completions::completion::{CompletionOutput, CompletionRequest};
+use crate::context::Context;
+use crate::custom::task::{CustomRequest, CustomResponse};
+use crate::embeddings::embedding::{EmbeddingRequest, EmbeddingResponse};
+use crate::headers::RequestId;
+use crate::images::edit::ImageEditRequest;
+use crate::images::generation::{ImageGenerationRequest, ImageGenerationResponse};
+use crate::images::variation::ImageVariationRequest;
+use crate::moderations::moderation::{ModerationRequest, ModerationResponse};
+use crate::rerank::ranking::{RerankRequest, RerankResponse};
+use crate::telemetry::traceparent;
+use hfendpoints_core::{DistributedError, Payload};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+
+/// Implement `Payload` through the serde implementations of the types
+macro_rules! serde_payload {
+    ($($payload: ty),+ $(,)?) => {
+        $(
+            impl Payload for $payload {
+                fn encode(&self) -> Result<Value, DistributedError> {
+                    Ok(serde_json::to_value(self)?)
+                }
+
+                fn decode(payload: Value) -> Result<Self, DistributedError> {
+                    Ok(serde_json::from_value(payload)?)
+                }
+            }
+        )+
+    };
+}
+
+serde_payload!(
+    ChatCompletionOutput,
+    ChatCompletionRequest,
+    CompletionOutput,
+    CompletionRequest,
+    CustomRequest,
+    CustomResponse,
+    EmbeddingRequest,
+    EmbeddingResponse,
+    ImageEditRequest,
+    ImageGenerationRequest,
+    ImageGenerationResponse,
+    ImageVariationRequest,
+    ModerationRequest,
+    ModerationResponse,
+    RerankRequest,
+    RerankResponse,
+    SpeechRequest,
+    SpeechResponse,
+    TranscriptionRequest,
+    TranslationRequest,
+);
+
+/// Only the correlation ID and the trace travel, cancellation and preemption are local to the worker
+impl Payload for Context {
+    fn encode(&self) -> Result<Value, DistributedError> {
+        Ok(match traceparent(self.span()) {
+            Some(traceparent) => {
+                json!({ "request_id": self.request_id(), "traceparent": traceparent })
+            }
+            None => json!({ "request_id": self.request_id() }),
+        })
+    }
+
+    fn decode(payload: Value) -> Result<Self, DistributedError> {
+        let traceparent = payload.get("traceparent").and_then(Value::as_str);
+        match payload.get("request_id").and_then(Value::as_str) {
+            Some(request_id) => Ok(Context::remote(
+                RequestId::from(request_id.to_string()),
+                traceparent,
+            )),
+            None => Err(DistributedError::Payload(String::from(
+                "the context does not hold a request_id",
+            ))),
+        }
+    }
+}
+
+/// Variant of a `TranscriptionResponse`, which clients receive untagged
+#[derive(Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum TaggedTranscription {
+    Json(Transcription),
+    Text(String),
+    VerboseJson(VerboseTranscription),
+    Srt(String),
+    Vtt(String),
+    Event(StreamEvent),
+}
+
+impl Payload for TranscriptionResponse {
+    fn encode(&self) -> Result<Value, DistributedError> {
+        let tagged = match self.clone() {
+            TranscriptionResponse::Json(transcription) => TaggedTranscription::Json(transcription),
+            TranscriptionResponse::Text(text) => TaggedTranscription::Text(text),
+            TranscriptionResponse::VerboseJson(transcription) => {
+                TaggedTranscription::VerboseJson(transcription)
+            }
+            TranscriptionResponse::Srt(subtitles) => TaggedTranscription::Srt(subtitles),
+            TranscriptionResponse::Vtt(subtitles) => TaggedTranscription::Vtt(subtitles),
+            TranscriptionResponse::Event(event) => TaggedTranscription::Event(event),
+        };
+        Ok(serde_json::to_value(tagged)?)
+    }
+
+    fn decode(payload: Value) -> Result<Self, DistributedError> {
+        Ok(match serde_json::from_value(payload)? {
+            TaggedTranscription::Json(transcription) => TranscriptionResponse::Json(transcription),
+            TaggedTranscription::Text(text) => TranscriptionResponse::Text(text),
+            TaggedTranscription::VerboseJson(transcription) => {
+                TranscriptionResponse::VerboseJson(transcription)
+            }
+            TaggedTranscription::Srt(subtitles) => TranscriptionResponse::Srt(subtitles),
+            TaggedTranscription::Vtt(subtitles) => TranscriptionResponse::Vtt(subtitles),
+            TaggedTranscription::Event(event) => TranscriptionResponse::Event(event),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::audio::speech::SpeechResponse;
+    use crate::audio::transcription::{TranscriptionRequest, TranscriptionResponse};
+    use crate::context::Context;
+    use crate::headers::RequestId;
+    use axum::body::Bytes;
+    use hfendpoints_core::Payload;
+    use hfendpoints_schemas::audio::{ResponseFormat, Transcription};
+    use serde_json::json;
+
+    #[test]
+    fn transcriptions_keep_their_variant() {
+        let text = TranscriptionResponse::Text(String::from("Hello"));
+        let encoded = text.encode().unwrap();
+        assert_eq!(encoded, json!({"text": "Hello"}));
+        assert!(matches!(
+            TranscriptionResponse::decode(encoded).unwrap(),
+            TranscriptionResponse::Text(text) if text == "Hello"
+        ));
+
+        let json = TranscriptionResponse::Json(Transcription {
+            text: String::from("Hello"),
+        });
+        assert!(matches!(
+            TranscriptionResponse::decode(json.encode().unwrap()).unwrap(),
+            TranscriptionResponse::Json(_)
+        ));
+    }
+
+    #[test]
+    fn requests_travel_with_their_context() {
+        let request = TranscriptionRequest {
+            file: Bytes::from_static(b"RIFF"),
+            content_type: String::from("audio/wav"),
+            language: String::from("fr"),
+            prompt: None,
+            temperature: 0.0,
+            response_format: ResponseFormat::Srt,
+            stream: false,
+            allow_code_switching: false,
+            timestamp_granularities: vec![],
+            pcm: None,
+        };
+        let context = Context::new(RequestId::from(String::from("req-1")));
+
+        let encoded = (request, context).encode().unwrap();
+        assert_eq!(encoded[0]["file"], "UklGRg==");
+
+        let (request, context) = <(TranscriptionRequest, Context)>::decode(encoded).unwrap();
+        assert_eq!(&request.file[..], b"RIFF");
+        assert_eq!(request.response_format, ResponseFormat::Srt);
+        assert_eq!(context.request_id(), "req-1");
+    }
+
+    #[test]
+    fn speech_is_encoded_as_base64() {
+        let speech = SpeechResponse {
+            audio: Bytes::from_static(&[0, 1, 2]),
+        };
+        let decoded = SpeechResponse::decode(speech.encode().unwrap()).unwrap();
+        assert_eq!(&decoded.audio[..], &[0, 1, 2]);
+        assert!(SpeechResponse::decode(json!({"audio": "not base64!"})).is_err());
+    }
+}
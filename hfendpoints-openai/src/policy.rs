@@ -0,0 +1,200 @@
+//! Operator-defined default values and overrides of request parameters.
+//!
+//! The policy is read from the `HFENDPOINT_REQUEST_POLICY` environment variable, a JSON document
+//! keyed by route (relative to `/api/v1`), each route holding `defaults` and `overrides` objects:
+//!
+//! ```json
+//! {
+//!   "/audio/transcriptions": {
+//!     "defaults": { "temperature": 0.2 },
+//!     "overrides": { "language": "de", "response_format": "verbose_json" }
+//!   }
+//! }
+//! ```
+//!
+//! Once the request is parsed, every parameter is resolved with the following precedence:
+//! 1. the operator's `overrides`, always winning over the client's value
+//! 2. the value sent by the client
+//! 3. the operator's `defaults`
+//! 4. the built-in default of the route
+//!
+//! Resolved values go through the same validation as client-provided ones. Uploaded files cannot be set
+//! through the policy. The endpoint refuses to start when the policy is malformed or targets a
+//! route it does not serve, most likely a typo which would otherwise silently leave the route
+//! without its policy.
+use crate::{OpenAiError, OpenAiResult};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use std::collections::{BTreeSet, HashMap};
+use tracing::info;
+
+/// Environment variable holding the request policy of every route
+pub const REQUEST_POLICY_ENV: &str = "HFENDPOINT_REQUEST_POLICY";
+
+/// Default values and overrides applied to the parameters of a route
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RequestPolicy {
+    defaults: Map<String, Value>,
+    overrides: Map<String, Value>,
+}
+
+impl RequestPolicy {
+    /// Load the policy defined for `route` through `HFENDPOINT_REQUEST_POLICY`, a malformed
+    /// policy being rejected once the endpoint starts, see [`validate_request_policies`]
+    pub(crate) fn from_env(route: &str) -> Self {
+        match std::env::var(REQUEST_POLICY_ENV) {
+            Ok(policies) => Self::parse(&policies, route),
+            Err(_) => Self::default(),
+        }
+    }
+
+    fn parse(policies: &str, route: &str) -> Self {
+        let Ok(mut policies) = serde_json::from_str::<HashMap<String, RequestPolicy>>(policies)
+        else {
+            return Self::default();
+        };
+
+        let policy = policies.remove(route).unwrap_or_default();
+        if !policy.overrides.is_empty() {
+            let fields: Vec<_> = policy.overrides.keys().collect();
+            info!("Parameters {fields:?} of {route} are overridden by {REQUEST_POLICY_ENV}");
+        }
+        policy
+    }
+
+    /// Resolve a multipart form field from the value sent by the client, if any
+    pub(crate) fn resolve(&self, field: &str, value: Option<String>) -> Option<String> {
+        let as_field = |value: &Value| match value {
+            Value::String(value) => value.clone(),
+            value => value.to_string(),
+        };
+
+        self.overrides
+            .get(field)
+            .map(as_field)
+            .or(value)
+            .or_else(|| self.defaults.get(field).map(as_field))
+    }
+
+    /// Resolve the parameters of a JSON body
+    pub(crate) fn apply(&self, body: &mut Value) {
+        if let Value::Object(body) = body {
+            for (field, value) in &self.defaults {
+                if body.get(field).is_none_or(Value::is_null) {
+                    body.insert(field.clone(), value.clone());
+                }
+            }
+
+            for (field, value) in &self.overrides {
+                body.insert(field.clone(), value.clone());
+            }
+        }
+    }
+}
+
+/// Check the policies defined through `HFENDPOINT_REQUEST_POLICY`, if any, are well-formed and
+/// only target the `routes` served by the endpoint (relative to `/api/v1`)
+pub(crate) fn validate_request_policies<'a>(
+    routes: impl IntoIterator<Item = &'a str>,
+) -> OpenAiResult<()> {
+    match std::env::var(REQUEST_POLICY_ENV) {
+        Ok(policies) => validate(&policies, routes),
+        Err(_) => Ok(()),
+    }
+}
+
+fn validate<'a>(policies: &str, routes: impl IntoIterator<Item = &'a str>) -> OpenAiResult<()> {
+    let policies = serde_json::from_str::<HashMap<String, RequestPolicy>>(policies)
+        .map_err(|err| OpenAiError::Config(format!("Malformed {REQUEST_POLICY_ENV}: {err}")))?;
+
+    let routes: BTreeSet<_> = routes.into_iter().collect();
+    let unknown: BTreeSet<_> = policies
+        .keys()
+        .map(String::as_str)
+        .filter(|route| !routes.contains(route))
+        .collect();
+    if unknown.is_empty() {
+        Ok(())
+    } else {
+        Err(OpenAiError::Config(format!(
+            "{REQUEST_POLICY_ENV} targets routes {unknown:?} which are not served, expected one of {routes:?}"
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::OpenAiError;
+    use crate::policy::{RequestPolicy, validate};
+    use serde_json::json;
+
+    const POLICIES: &str = r#"{
+        "/audio/transcriptions": {
+            "defaults": { "temperature": 0.2, "prompt": "Meeting notes" },
+            "overrides": { "language": "de" }
+        }
+    }"#;
+
+    #[test]
+    fn resolve_form_fields_precedence() {
+        let policy = RequestPolicy::parse(POLICIES, "/audio/transcriptions");
+
+        // Overrides win over the client
+        assert_eq!(
+            policy.resolve("language", Some(String::from("en"))),
+            Some(String::from("de"))
+        );
+
+        // Client wins over defaults, defaults fill the gaps
+        assert_eq!(
+            policy.resolve("prompt", Some(String::from("Lecture"))),
+            Some(String::from("Lecture"))
+        );
+        assert_eq!(
+            policy.resolve("temperature", None),
+            Some(String::from("0.2"))
+        );
+        assert_eq!(policy.resolve("response_format", None), None);
+    }
+
+    #[test]
+    fn apply_json_body_precedence() {
+        let policy = RequestPolicy::parse(POLICIES, "/audio/transcriptions");
+        let mut body = json!({"language": "en", "prompt": null});
+        policy.apply(&mut body);
+
+        assert_eq!(
+            body,
+            json!({"language": "de", "prompt": "Meeting notes", "temperature": 0.2})
+        );
+    }
+
+    #[test]
+    fn malformed_or_missing_policies_are_empty() {
+        let policy = RequestPolicy::parse("not json", "/audio/transcriptions");
+        assert_eq!(policy.resolve("language", None), None);
+
+        let policy = RequestPolicy::parse(POLICIES, "/chat/completions");
+        assert_eq!(policy.resolve("language", None), None);
+    }
+
+    #[test]
+    fn reject_malformed_or_unknown_policies() {
+        let routes = ["/audio/transcriptions", "/audio/translations"];
+        assert!(validate(POLICIES, routes).is_ok());
+
+        assert!(matches!(
+            validate("not json", routes),
+            Err(OpenAiError::Config(_))
+        ));
+        assert!(matches!(
+            validate(r#"{"/audio/transcriptions": {"default": {}}}"#, routes),
+            Err(OpenAiError::Config(_))
+        ));
+
+        let err = validate(r#"{"/audio/transcription": {}}"#, routes).unwrap_err();
+        assert!(err.to_string().contains("/audio/transcription\""));
+    }
+}
@@ -0,0 +1,124 @@
+//! Priority lane of the operational routes.
+//!
+//! Probes failing while the endpoint is saturated get it restarted by the orchestrator, losing the
+//! requests in flight and moving the load onto the other replicas, which end up saturated in turn.
+//! The operational routes (`/health`, `/health/live`, `/health/ready`, `/metrics`, `/info` and the
+//! ones under `/admin`) are thus served under a concurrency budget of their own, while at most
+//! `HFENDPOINT_MAX_CONCURRENT_REQUESTS` other requests (512 by default) are processed at once, the
+//! others being rejected right away with `429 Too Many Requests`, so the probes never wait behind
+//! the traffic.
+//!
+//! The budget bounds the requests in flight, not the runtime threads they occupy: task routes
+//! await the handler loops and run their CPU-bound steps (decoding, decompression, decryption) on
+//! the blocking pool, which keeps the runtime threads available to answer the probes. A route
+//! blocking a runtime thread would still delay them, whatever the budget.
+use crate::OpenAiError;
+use axum::extract::{Request, State};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use std::sync::Arc;
+use tokio::sync::Semaphore;
+use tracing::debug;
+
+/// Environment variable holding the number of requests processed at once, operational ones aside
+pub const MAX_CONCURRENT_REQUESTS_ENV: &str = "HFENDPOINT_MAX_CONCURRENT_REQUESTS";
+
+/// Requests processed at once when `HFENDPOINT_MAX_CONCURRENT_REQUESTS` is not set
+pub(crate) const DEFAULT_MAX_CONCURRENT_REQUESTS: usize = 512;
+
+/// Operational requests processed at once, the other ones waiting for a slot
+const PRIORITY_CONCURRENCY: usize = 64;
+
+/// Operational routes, reached by the orchestrator and the monitoring
+const PRIORITY_PATHS: [&str; 5] = [
+    "/health",
+    "/health/live",
+    "/health/ready",
+    "/metrics",
+    "/info",
+];
+
+/// Prefix of the administration routes, operational as well
+const ADMIN_PREFIX: &str = "/admin";
+
+/// Whether the request for `path` goes through the priority lane
+fn is_priority(path: &str) -> bool {
+    PRIORITY_PATHS.contains(&path)
+        || path
+            .strip_prefix(ADMIN_PREFIX)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+}
+
+/// Concurrency budgets of the operational requests and of the other ones
+pub(crate) struct PriorityLane {
+    priority: Semaphore,
+    traffic: Semaphore,
+    capacity: usize,
+}
+
+impl PriorityLane {
+    /// Process at most `capacity` requests at once, operational ones aside
+    pub(crate) fn new(capacity: usize) -> Self {
+        let capacity = capacity.max(1);
+        Self {
+            priority: Semaphore::new(PRIORITY_CONCURRENCY),
+            traffic: Semaphore::new(capacity),
+            capacity,
+        }
+    }
+}
+
+/// Middleware processing the request under the budget of its lane, the requests over the budget
+/// of the traffic lane being rejected without waiting
+pub(crate) async fn prioritize(
+    State(lane): State<Arc<PriorityLane>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if is_priority(request.uri().path()) {
+        // The semaphore is never closed, operational requests are cheap enough to wait for a slot
+        let _slot = lane.priority.acquire().await;
+        return next.run(request).await;
+    }
+
+    match lane.traffic.try_acquire() {
+        Ok(_slot) => next.run(request).await,
+        Err(_) => {
+            debug!(
+                "Rejecting {}, {} requests are already being processed",
+                request.uri().path(),
+                lane.capacity
+            );
+            OpenAiError::QueueFull {
+                capacity: lane.capacity,
+            }
+            .into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::priority::is_priority;
+
+    #[test]
+    fn operational_routes_are_prioritized() {
+        for path in [
+            "/health",
+            "/health/ready",
+            "/metrics",
+            "/admin",
+            "/admin/drain",
+        ] {
+            assert!(is_priority(path), "{path}");
+        }
+        for path in [
+            "/api/v1/audio/transcriptions",
+            "/administration",
+            "/docs",
+            "/",
+        ] {
+            assert!(!is_priority(path), "{path}");
+        }
+    }
+}
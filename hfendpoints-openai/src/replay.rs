@@ -0,0 +1,292 @@
+//! Resumption of Server-Sent Events streams after a disconnection.
+//!
+//! Every event of a stream is numbered and journaled in memory under the request id of the request
+//! which created it. A client losing the connection can send the same request again with the same
+//! `x-request-id` and the id of the last event it received in `Last-Event-ID`; it is then served the
+//! events it missed followed by the remaining ones, rather than starting the generation over.
+//!
+//! A stream is only served to the caller which created it, as identified by its API key or token
+//! (see `auth`), and a request id can only create a single stream while its journal is kept: the
+//! request ids being chosen by the clients, they would otherwise read or clobber the streams of
+//! one another.
+//!
+//! The generation keeps running while the client is away. It is cancelled once nobody listened to
+//! it for [`REPLAY_RETENTION`], and the journal of a completed stream is kept for the same duration.
+use crate::auth::Caller;
+use crate::{OpenAiError, OpenAiResult};
+use axum::response::sse::Event;
+use futures_util::stream::{self, Stream};
+use std::collections::{HashMap, VecDeque};
+use std::convert::Infallible;
+use std::pin::pin;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Duration;
+use tokio::sync::watch;
+use tokio::time::sleep;
+use tokio_stream::StreamExt;
+use tracing::{debug, instrument};
+
+/// Maximum number of events kept per stream, the oldest ones are evicted first
+pub(crate) const REPLAY_CAPACITY: usize = 1024;
+
+/// Time a stream is kept around without anyone listening to it
+pub(crate) const REPLAY_RETENTION: Duration = Duration::from_secs(30);
+
+#[derive(Default)]
+struct Journal {
+    /// Events still available, with their ids
+    events: VecDeque<(u64, Event)>,
+
+    /// Id of the next appended event
+    next: u64,
+
+    /// Whether the stream ended
+    finished: bool,
+}
+
+enum Next {
+    Event(u64, Event),
+    Pending,
+    Finished,
+}
+
+impl Journal {
+    fn append(&mut self, event: Event) {
+        if self.events.len() == REPLAY_CAPACITY {
+            self.events.pop_front();
+        }
+
+        let id = self.next;
+        self.events.push_back((id, event.id(id.to_string())));
+        self.next += 1;
+    }
+
+    /// Whether all the events following `last` are still journaled
+    fn covers(&self, last: Option<u64>) -> bool {
+        let first = self.events.front().map_or(self.next, |(id, _)| *id);
+        last.map_or(0, |id| id + 1) >= first
+    }
+
+    fn after(&self, last: Option<u64>) -> Next {
+        let expected = last.map_or(0, |id| id + 1);
+        match self.events.iter().find(|(id, _)| *id >= expected) {
+            Some((id, event)) => Next::Event(*id, event.clone()),
+            None if self.finished => Next::Finished,
+            None => Next::Pending,
+        }
+    }
+}
+
+/// Journal of the events emitted by a single stream
+pub(crate) struct Replay {
+    journal: watch::Sender<Journal>,
+
+    /// Caller which created the stream, the only one it is served to
+    caller: Option<Caller>,
+}
+
+impl Replay {
+    fn new(caller: Option<Caller>) -> Self {
+        Self {
+            journal: watch::Sender::new(Journal::default()),
+            caller,
+        }
+    }
+
+    pub(crate) fn append(&self, event: Event) {
+        self.journal.send_modify(|journal| journal.append(event));
+    }
+
+    pub(crate) fn finish(&self) {
+        self.journal.send_modify(|journal| journal.finished = true);
+    }
+
+    /// Resolve once nobody listened to the stream for [`REPLAY_RETENTION`]
+    pub(crate) async fn abandoned(&self) {
+        loop {
+            self.journal.closed().await;
+            sleep(REPLAY_RETENTION).await;
+            if self.journal.receiver_count() == 0 {
+                return;
+            }
+        }
+    }
+
+    /// Stream the events following `last`, then the upcoming ones until the stream ends
+    pub(crate) fn subscribe(
+        &self,
+        last: Option<u64>,
+    ) -> OpenAiResult<impl Stream<Item = Result<Event, Infallible>> + use<>> {
+        let receiver = self.journal.subscribe();
+        if !receiver.borrow().covers(last) {
+            return Err(OpenAiError::NotFound(format!(
+                "Events following {} are no longer available",
+                last.map_or(String::from("the start"), |id| id.to_string())
+            )));
+        }
+
+        Ok(stream::unfold(
+            (receiver, last),
+            |(mut receiver, last)| async move {
+                loop {
+                    let next = receiver.borrow_and_update().after(last);
+                    match next {
+                        Next::Event(id, event) => return Some((Ok(event), (receiver, Some(id)))),
+                        Next::Finished => return None,
+                        Next::Pending => receiver.changed().await.ok()?,
+                    }
+                }
+            },
+        ))
+    }
+}
+
+/// Streams which can be resumed, indexed by the request id which created them
+#[derive(Default)]
+pub struct Replays {
+    streams: Mutex<HashMap<String, Arc<Replay>>>,
+}
+
+impl Replays {
+    #[inline]
+    fn streams(&self) -> std::sync::MutexGuard<'_, HashMap<String, Arc<Replay>>> {
+        self.streams.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Start journaling a new stream created by `caller` for `request_id`, failing when the
+    /// journal of another stream is still kept under the same id
+    pub(crate) fn create(
+        &self,
+        request_id: &str,
+        caller: Option<&Caller>,
+    ) -> OpenAiResult<Arc<Replay>> {
+        let mut streams = self.streams();
+        if streams.contains_key(request_id) {
+            return Err(OpenAiError::Conflict(format!(
+                "A stream was already created for request {request_id}, resume it through Last-Event-ID or use another x-request-id"
+            )));
+        }
+
+        let replay = Arc::new(Replay::new(caller.cloned()));
+        streams.insert(request_id.to_string(), Arc::clone(&replay));
+        Ok(replay)
+    }
+
+    /// Retrieve the stream created by `request_id`, if still around and created by `caller`
+    pub(crate) fn get(&self, request_id: &str, caller: Option<&Caller>) -> Option<Arc<Replay>> {
+        self.streams()
+            .get(request_id)
+            .filter(|replay| replay.caller.as_ref() == caller)
+            .cloned()
+    }
+
+    /// Forget about `replay`, unless another stream was since created with the same request id
+    #[instrument(skip(self, replay))]
+    pub(crate) fn evict(&self, request_id: &str, replay: &Arc<Replay>) {
+        let mut streams = self.streams();
+        if streams
+            .get(request_id)
+            .is_some_and(|current| Arc::ptr_eq(current, replay))
+        {
+            debug!("Evicting the journal of the stream");
+            streams.remove(request_id);
+        }
+    }
+}
+
+/// Journal the events of `events` in `replay`, created for `request_id`, in a background task
+/// outliving the connection of the client
+pub(crate) fn journal<S>(
+    replays: Arc<Replays>,
+    request_id: String,
+    replay: Arc<Replay>,
+    events: S,
+) -> OpenAiResult<impl Stream<Item = Result<Event, Infallible>> + use<S>>
+where
+    S: Stream<Item = Event> + Send + 'static,
+{
+    let subscription = replay.subscribe(None)?;
+
+    tokio::spawn(async move {
+        let mut events = pin!(events);
+        let mut abandoned = pin!(replay.abandoned());
+        loop {
+            tokio::select! {
+                event = events.next() => match event {
+                    Some(event) => replay.append(event),
+                    None => break,
+                },
+                // Dropping the events cancels the request on the handler side
+                _ = &mut abandoned => {
+                    debug!("Nobody listened to the stream for {REPLAY_RETENTION:?}, cancelling");
+                    break;
+                }
+            }
+        }
+
+        replay.finish();
+        sleep(REPLAY_RETENTION).await;
+        replays.evict(&request_id, &replay);
+    });
+
+    Ok(subscription)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::OpenAiError;
+    use crate::auth::Caller;
+    use crate::oidc::Identity;
+    use crate::replay::{Journal, Next, REPLAY_CAPACITY, Replays};
+    use axum::response::sse::Event;
+
+    #[test]
+    fn streams_belong_to_their_caller() {
+        let alice = Caller::Token(Identity {
+            subject: Some(String::from("alice")),
+            tenant: None,
+        });
+        let key = Caller::ApiKey([7; 32]);
+
+        let replays = Replays::default();
+        let replay = replays.create("req-1", Some(&alice)).unwrap();
+        assert!(replays.get("req-1", Some(&alice)).is_some());
+        assert!(replays.get("req-1", Some(&key)).is_none());
+        assert!(replays.get("req-1", None).is_none());
+
+        // Request ids cannot be reused while the journal is kept, whoever the caller
+        for caller in [Some(&alice), Some(&key), None] {
+            assert!(matches!(
+                replays.create("req-1", caller),
+                Err(OpenAiError::Conflict(_))
+            ));
+        }
+
+        replays.evict("req-1", &replay);
+        assert!(replays.create("req-1", Some(&key)).is_ok());
+        assert!(replays.get("req-1", Some(&alice)).is_none());
+    }
+
+    #[test]
+    fn journal_evicts_oldest_events() {
+        let mut journal = Journal::default();
+        for _ in 0..REPLAY_CAPACITY + 2 {
+            journal.append(Event::default().data("delta"));
+        }
+
+        assert!(!journal.covers(None));
+        assert!(!journal.covers(Some(0)));
+        assert!(journal.covers(Some(1)));
+        assert!(matches!(journal.after(Some(1)), Next::Event(2, _)));
+        assert!(matches!(
+            journal.after(Some(REPLAY_CAPACITY as u64 + 1)),
+            Next::Pending
+        ));
+
+        journal.finished = true;
+        assert!(matches!(
+            journal.after(Some(REPLAY_CAPACITY as u64 + 1)),
+            Next::Finished
+        ));
+    }
+}
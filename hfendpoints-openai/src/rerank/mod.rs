@@ -0,0 +1,32 @@
+pub mod ranking;
+
+pub const RERANK_TAG: &str = "Rerank";
+pub const RERANK_DESC: &str =
+    "Given a query and a list of documents, the model will rank the documents by relevance.";
+
+#[cfg(feature = "python")]
+pub(crate) mod python {
+    use crate::rerank::ranking::{RerankRequest, RerankResponse};
+    use hfendpoints_binding_python::ImportablePyModuleBuilder;
+    use pyo3::prelude::*;
+
+    mod rerank {
+        use crate::python::{impl_pyendpoint, impl_pyhandler};
+        use crate::rerank::ranking::{RerankRequest, RerankResponse, RerankRouter};
+
+        impl_pyhandler!(RerankRequest, RerankResponse);
+        impl_pyendpoint!("RerankEndpoint", PyRerankEndpoint, PyHandler, RerankRouter);
+    }
+
+    /// Bind hfendpoints.openai.rerank submodule into the exported Python wheel
+    pub fn bind<'py>(py: Python<'py>, name: &str) -> PyResult<Bound<'py, PyModule>> {
+        let module = ImportablePyModuleBuilder::new(py, name)?
+            .defaults()?
+            .add_class::<RerankRequest>()?
+            .add_class::<RerankResponse>()?
+            .add_class::<rerank::PyRerankEndpoint>()?
+            .finish();
+
+        Ok(module)
+    }
+}
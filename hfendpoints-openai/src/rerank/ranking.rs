@@ -0,0 +1,376 @@
+use crate::context::{Context, RequestContext};
+use crate::endpoint::{schedule_task, task_router};
+use crate::error::TaskErrors;
+use crate::policy::RequestPolicy;
+use crate::rerank::RERANK_TAG;
+use crate::telemetry::record_task;
+use crate::{OpenAiError, OpenAiResult, TaskRequest};
+use axum::extract::State;
+use axum::response::{IntoResponse, Response};
+use axum::{Extension, Json};
+use hfendpoints_core::{EndpointContext, RequestSender};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::sync::Arc;
+use tracing::instrument;
+use utoipa::ToSchema;
+use utoipa_axum::router::OpenApiRouter;
+use utoipa_axum::routes;
+
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+
+/// A document to rank, either a string or an object holding its `text`.
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Clone, Deserialize, Serialize, ToSchema)]
+#[serde(untagged)]
+pub enum RerankDocument {
+    Text(String),
+    Object { text: String },
+}
+
+impl From<RerankDocument> for String {
+    fn from(value: RerankDocument) -> Self {
+        match value {
+            RerankDocument::Text(text) | RerankDocument::Object { text } => text,
+        }
+    }
+}
+
+/// Ranks a list of documents by their relevance to a query.
+#[cfg_attr(feature = "python", pyclass(frozen))]
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Clone, Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct RerankRequest {
+    /// The search query.
+    pub query: String,
+
+    /// The documents to rank, as strings or objects holding their `text`.
+    #[serde(deserialize_with = "deserialize_documents")]
+    #[schema(value_type = Vec<RerankDocument>)]
+    pub documents: Vec<String>,
+
+    /// Not used, here for compatibility purpose with Cohere
+    pub model: Option<String>,
+
+    /// The number of most relevant documents to return, all of them when not set.
+    pub top_n: Option<usize>,
+
+    /// Whether the text of the documents is returned along their relevance.
+    #[serde(default)]
+    pub return_documents: bool,
+}
+
+fn deserialize_documents<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    Ok(Vec::<RerankDocument>::deserialize(deserializer)?
+        .into_iter()
+        .map(String::from)
+        .collect())
+}
+
+impl RerankRequest {
+    #[instrument(skip_all)]
+    fn validate(self) -> OpenAiResult<Self> {
+        if self.query.is_empty() {
+            return Err(OpenAiError::Validation(String::from(
+                "Parameter 'query' cannot be empty",
+            )));
+        }
+
+        if self.documents.is_empty() {
+            return Err(OpenAiError::Validation(String::from(
+                "Parameter 'documents' must contain at least one document",
+            )));
+        }
+
+        if self.top_n == Some(0) {
+            return Err(OpenAiError::Validation(String::from(
+                "Parameter 'top_n' must be at least 1",
+            )));
+        }
+
+        Ok(self)
+    }
+}
+
+/// Relevance scores computed by the handler, one per document in the order of the request.
+#[cfg_attr(feature = "python", pyclass(frozen))]
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Clone, Deserialize, Serialize)]
+pub struct RerankResponse {
+    scores: Vec<f32>,
+}
+
+impl RerankResponse {
+    pub fn new(scores: Vec<f32>) -> Self {
+        Self { scores }
+    }
+
+    /// Rank the `documents` of the request by decreasing relevance, keeping the `top_n` first
+    fn rank(
+        self,
+        request_id: &str,
+        documents: Vec<String>,
+        top_n: Option<usize>,
+        return_documents: bool,
+    ) -> OpenAiResult<RerankResults> {
+        if self.scores.len() != documents.len() {
+            return Err(OpenAiError::InvalidResponse(format!(
+                "{} documents were ranked, got {} scores",
+                documents.len(),
+                self.scores.len()
+            )));
+        }
+
+        let mut results: Vec<_> = documents
+            .into_iter()
+            .zip(self.scores)
+            .enumerate()
+            .map(|(index, (text, relevance_score))| RerankResult {
+                index,
+                relevance_score,
+                document: return_documents.then_some(RankedDocument { text }),
+            })
+            .collect();
+
+        // Ties keep the order of the request
+        results.sort_by(|a, b| b.relevance_score.total_cmp(&a.relevance_score));
+        results.truncate(top_n.unwrap_or(usize::MAX));
+
+        Ok(RerankResults {
+            id: request_id.to_string(),
+            results,
+        })
+    }
+}
+
+/// A ranked document, returned when `return_documents` is set.
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Clone, Serialize, ToSchema)]
+pub struct RankedDocument {
+    /// The text of the document.
+    text: String,
+}
+
+/// The relevance of one of the documents.
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Clone, Serialize, ToSchema)]
+pub struct RerankResult {
+    /// The index of the document in the list of documents of the request.
+    index: usize,
+
+    /// The relevance of the document to the query, higher being more relevant.
+    relevance_score: f32,
+
+    /// The document, when `return_documents` is set.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    document: Option<RankedDocument>,
+}
+
+/// The documents ranked by decreasing relevance.
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Clone, Serialize, ToSchema)]
+pub struct RerankResults {
+    /// The correlation ID of the request.
+    id: String,
+
+    /// The ranked documents, the most relevant first.
+    results: Vec<RerankResult>,
+}
+
+impl IntoResponse for RerankResults {
+    fn into_response(self) -> Response {
+        Json::from(self).into_response()
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/rerank",
+    tag = RERANK_TAG,
+    request_body(content = RerankRequest, content_type = "application/json"),
+    responses(
+        (status = OK, description = "Returns the documents ranked by decreasing relevance.", body = RerankResults),
+        TaskErrors,
+    )
+)]
+#[instrument(skip(state, policy, metadata, request))]
+pub async fn rerank(
+    State(state): State<EndpointContext<(RerankRequest, Context), RerankResponse>>,
+    Extension(policy): Extension<Arc<RequestPolicy>>,
+    metadata: RequestContext,
+    Json(mut request): Json<Value>,
+) -> OpenAiResult<RerankResults> {
+    record_task("rerank");
+
+    // Apply the operator's defaults and overrides before decoding
+    policy.apply(&mut request);
+    let request = serde_json::from_value::<RerankRequest>(request)
+        .map_err(|err| OpenAiError::Validation(err.to_string()))?
+        .validate()?;
+
+    // Ask for the inference thread to handle it and wait for answers
+    let id = metadata.request_id().to_string();
+    let documents = request.documents.clone();
+    let (top_n, return_documents) = (request.top_n, request.return_documents);
+    let response = schedule_task(&state, metadata, request)
+        .await?
+        .response()
+        .await?;
+    response.rank(&id, documents, top_n, return_documents)
+}
+
+/// Helper factory to build
+/// [Cohere compatible Rerank endpoint](https://docs.cohere.com/reference/rerank), as served by
+/// Text Embeddings Inference
+#[derive(Clone)]
+pub struct RerankRouter(pub RequestSender<(RerankRequest, Context), RerankResponse>);
+
+impl From<RerankRouter> for OpenApiRouter {
+    fn from(value: RerankRouter) -> Self {
+        task_router(
+            OpenApiRouter::new().routes(routes!(rerank)),
+            value.0,
+            "/rerank",
+        )
+    }
+}
+
+impl TaskRequest for RerankRequest {
+    type Response = RerankResponse;
+
+    fn router(sender: RequestSender<(Self, Context), Self::Response>) -> OpenApiRouter {
+        RerankRouter(sender).into()
+    }
+}
+
+#[cfg(feature = "python")]
+pub(crate) mod python {
+    use crate::python::TextDelta;
+    use crate::rerank::ranking::{RerankRequest, RerankResponse};
+    use pyo3::prelude::*;
+
+    impl TextDelta for RerankResponse {}
+
+    #[pymethods]
+    impl RerankRequest {
+        #[getter(query)]
+        fn get_query(&self) -> &str {
+            &self.query
+        }
+
+        #[getter(documents)]
+        fn get_documents(&self) -> Vec<String> {
+            self.documents.clone()
+        }
+
+        #[getter(model)]
+        fn get_model(&self) -> Option<&str> {
+            self.model.as_deref()
+        }
+
+        #[getter(top_n)]
+        fn get_top_n(&self) -> Option<usize> {
+            self.top_n
+        }
+
+        #[getter(return_documents)]
+        fn get_return_documents(&self) -> bool {
+            self.return_documents
+        }
+    }
+
+    #[pymethods]
+    impl RerankResponse {
+        #[new]
+        fn py_new(scores: Vec<f32>) -> Self {
+            Self::new(scores)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::rerank::ranking::{RerankRequest, RerankResponse, RerankRouter};
+    use crate::testing::{answer, post};
+    use axum::http::StatusCode;
+    use hfendpoints_core::request_channel;
+    use serde_json::{Value, json};
+
+    /// Score every document with the number of query words it contains, dropping `missing` scores
+    async fn rerank(body: &'static str, missing: usize) -> (StatusCode, Value) {
+        let (sender, receiver) = request_channel(1);
+        answer(receiver, move |request: RerankRequest| {
+            let scores = request
+                .documents
+                .iter()
+                .skip(missing)
+                .map(|document| {
+                    let words = request.query.split_whitespace();
+                    words.filter(|word| document.contains(word)).count() as f32
+                })
+                .collect();
+            Ok(RerankResponse::new(scores))
+        });
+
+        let router = RerankRouter(sender);
+        let (status, _, body) = post(router, "/rerank", "application/json", body).await;
+        (status, serde_json::from_slice(&body).unwrap_or_default())
+    }
+
+    #[tokio::test]
+    async fn rerank_documents_by_relevance() {
+        let (status, body) = rerank(
+            r#"{
+                "query": "capital of France",
+                "documents": ["Berlin", {"text": "Paris is the capital of France"}, "France", "Rome"],
+                "top_n": 3,
+                "return_documents": true
+            }"#,
+            0,
+        )
+        .await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            body,
+            json!({
+                "id": "test",
+                "results": [
+                    {"index": 1, "relevance_score": 3.0, "document": {"text": "Paris is the capital of France"}},
+                    {"index": 2, "relevance_score": 1.0, "document": {"text": "France"}},
+                    {"index": 0, "relevance_score": 0.0, "document": {"text": "Berlin"}}
+                ]
+            })
+        );
+
+        // Documents are only returned when asked for
+        let (_, body) = rerank(r#"{"query": "Rome", "documents": ["Paris", "Rome"]}"#, 0).await;
+        assert_eq!(
+            body["results"],
+            json!([{"index": 1, "relevance_score": 1.0}, {"index": 0, "relevance_score": 0.0}])
+        );
+    }
+
+    #[tokio::test]
+    async fn rerank_rejects_invalid_exchanges() {
+        for body in [
+            r#"{"query": "", "documents": ["Paris"]}"#,
+            r#"{"query": "Paris", "documents": []}"#,
+            r#"{"query": "Paris", "documents": ["Paris"], "top_n": 0}"#,
+        ] {
+            let (status, _) = rerank(body, 0).await;
+            assert_eq!(status, StatusCode::BAD_REQUEST);
+        }
+
+        // Every document must be scored
+        let body = r#"{"query": "Paris", "documents": ["Paris", "Rome"]}"#;
+        let (status, body) = rerank(body, 1).await;
+        assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(body["error"]["code"], "invalid_response");
+    }
+}
@@ -0,0 +1,143 @@
+//! Graceful shutdown of the HTTP server.
+//!
+//! On `SIGTERM` (or `SIGINT`, or the console close and shutdown events on Windows) the server stops
+//! accepting new connections and lets in-flight requests complete. Requests still running once the
+//! grace period is over are dropped, which cancels them on the handler side. The grace period
+//! defaults to 30 seconds, matching Kubernetes' `terminationGracePeriodSeconds`, and can be tuned
+//! through `HFENDPOINT_SHUTDOWN_GRACE_PERIOD` or the configuration file.
+use crate::config::EndpointConfig;
+use std::time::Duration;
+use tokio::sync::watch;
+use tokio::time::sleep;
+use tracing::{info, warn};
+
+/// Environment variable holding the number of seconds in-flight requests are given to complete
+pub const SHUTDOWN_GRACE_PERIOD_ENV: &str = "HFENDPOINT_SHUTDOWN_GRACE_PERIOD";
+
+/// Grace period when neither configured through the file nor `HFENDPOINT_SHUTDOWN_GRACE_PERIOD`
+pub(crate) const DEFAULT_GRACE_PERIOD: Duration = Duration::from_secs(30);
+
+/// Coordinate the shutdown of the server between the OS signals and the draining deadline
+pub(crate) struct Shutdown {
+    grace_period: Duration,
+    initiated: watch::Sender<bool>,
+}
+
+impl Shutdown {
+    pub(crate) fn new(grace_period: Duration) -> Self {
+        Self {
+            grace_period,
+            initiated: watch::Sender::new(false),
+        }
+    }
+
+    /// Use the grace period defined through `HFENDPOINT_SHUTDOWN_GRACE_PERIOD` or the
+    /// configuration file, see `config`
+    pub(crate) fn from_env() -> Self {
+        Self::new(EndpointConfig::current().shutdown_grace_period)
+    }
+
+    /// Stop accepting connections and start draining the in-flight requests
+    #[cfg(test)]
+    pub(crate) fn initiate(&self) {
+        self.initiated.send_replace(true);
+    }
+
+    /// Resolve once the process is asked to terminate, initiating the shutdown
+    pub(crate) fn signal(&self) -> impl Future<Output = ()> + Send + 'static {
+        let initiated = self.initiated.clone();
+        let grace_period = self.grace_period;
+        async move {
+            let mut receiver = initiated.subscribe();
+            tokio::select! {
+                _ = terminate() => {
+                    info!("Shutting down, draining in-flight requests for up to {grace_period:?}");
+                    initiated.send_replace(true);
+                }
+                _ = receiver.wait_for(|initiated| *initiated) => {}
+            }
+        }
+    }
+
+    /// Resolve once the grace period elapsed after the shutdown was initiated
+    pub(crate) fn deadline(&self) -> impl Future<Output = ()> + Send + 'static {
+        let mut receiver = self.initiated.subscribe();
+        let grace_period = self.grace_period;
+        async move {
+            if receiver.wait_for(|initiated| *initiated).await.is_ok() {
+                sleep(grace_period).await;
+                warn!("Grace period of {grace_period:?} elapsed, dropping remaining requests");
+            } else {
+                std::future::pending::<()>().await;
+            }
+        }
+    }
+}
+
+#[cfg(unix)]
+async fn terminate() {
+    use tokio::signal::unix::{SignalKind, signal};
+
+    match signal(SignalKind::terminate()) {
+        Ok(mut sigterm) => {
+            tokio::select! {
+                _ = sigterm.recv() => {}
+                _ = tokio::signal::ctrl_c() => {}
+            }
+        }
+        Err(err) => {
+            warn!("Failed to listen for SIGTERM, only SIGINT will stop the server: {err}");
+            let _ = tokio::signal::ctrl_c().await;
+        }
+    }
+}
+
+/// Windows has no `SIGTERM`, services and consoles are closed through control events instead
+#[cfg(windows)]
+async fn terminate() {
+    use tokio::signal::windows::{ctrl_break, ctrl_close, ctrl_shutdown};
+
+    match (ctrl_break(), ctrl_close(), ctrl_shutdown()) {
+        (Ok(mut ctrl_break), Ok(mut ctrl_close), Ok(mut ctrl_shutdown)) => {
+            tokio::select! {
+                _ = ctrl_break.recv() => {}
+                _ = ctrl_close.recv() => {}
+                _ = ctrl_shutdown.recv() => {}
+                _ = tokio::signal::ctrl_c() => {}
+            }
+        }
+        _ => {
+            warn!("Failed to listen for console events, only Ctrl-C will stop the server");
+            let _ = tokio::signal::ctrl_c().await;
+        }
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+async fn terminate() {
+    let _ = tokio::signal::ctrl_c().await;
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::shutdown::Shutdown;
+    use std::time::Duration;
+    use tokio::time::timeout;
+
+    #[tokio::test]
+    async fn deadline_elapses_after_grace_period() {
+        let shutdown = Shutdown::new(Duration::from_millis(10));
+        let (signal, deadline) = (shutdown.signal(), shutdown.deadline());
+
+        // Nothing happens until the shutdown is initiated
+        assert!(
+            timeout(Duration::from_millis(50), shutdown.deadline())
+                .await
+                .is_err()
+        );
+
+        shutdown.initiate();
+        timeout(Duration::from_millis(50), signal).await.unwrap();
+        timeout(Duration::from_millis(50), deadline).await.unwrap();
+    }
+}
@@ -0,0 +1,388 @@
+//! Export and import of the resolved state of the endpoint.
+//!
+//! A snapshot records what makes two deployments of the same image behave the same: the model
+//! served and its revision, the environment variables configuring the runtime (request policies
+//! included) and the routes exposed along with their deprecation flag. Secrets (API keys, decryption
+//! keys, the Redis URL and the usage sink, whose webhook URL may carry a token) are never recorded.
+//! Snapshots hold no timestamp, exporting the same deployment twice produces the same bytes.
+//!
+//! When `HFENDPOINT_SNAPSHOT_KEY` is set, `GET /snapshot` returns the manifest of the running
+//! endpoint, signed with HMAC-SHA256 under this key. Booting with `HFENDPOINT_SNAPSHOT` holding the
+//! path of such a manifest restores its environment (see `hfendpoints.snapshot.restore`) and the
+//! server refuses to start when the signature is invalid or the state it resolves differs from the
+//! manifest, i.e. after an upgrade adding or removing routes.
+use crate::audio::{
+    AUDIO_PROMPT_LIMIT_ENV, AUDIO_PROMPT_OVERFLOW_ENV, AUDIO_SAMPLE_RATE_ENV, LOUDNESS_TARGET_ENV,
+};
+use crate::capture::PAYLOAD_CAPTURE_ENV;
+use crate::chat::STREAM_FLUSH_INTERVAL_ENV;
+use crate::deprecation::{API_VERSION, DISABLE_DEPRECATED_ROUTES_ENV};
+use crate::encryption::JWE_REQUIRED_ENV;
+use crate::journal::JOURNAL_DIR_ENV;
+use crate::models::{MODEL_ID_ENV, ModelCard};
+use crate::{
+    AUTH_EXEMPT_ENV, BODY_LIMIT_ENV, CONFIG_FILE_ENV, LOG_FORMAT_ENV, MAX_CONCURRENT_REQUESTS_ENV,
+    OIDC_AUDIENCE_ENV, OIDC_ISSUER_ENV, OIDC_JWKS_URI_ENV, OIDC_TENANT_CLAIM_ENV, OpenAiError,
+    OpenAiResult, REQUEST_POLICY_ENV, SHUTDOWN_GRACE_PERIOD_ENV, TLS_CERT_ENV, TLS_KEY_ENV,
+};
+use axum::Json;
+use axum::extract::State;
+use base64::Engine;
+use base64::prelude::BASE64_URL_SAFE_NO_PAD;
+#[cfg(feature = "jobs")]
+use hfendpoints_core::JOBS_DIR_ENV;
+use hfendpoints_core::{
+    MAX_BATCH_SIZE_ENV, PREEMPTION_RUNNING_ENV, PREEMPTION_SLICE_ENV, QUEUE_CAPACITY_ENV,
+    READY_QUEUE_THRESHOLD_ENV, REQUEST_TIMEOUT_ENV, WORKERS_ENV,
+};
+#[cfg(feature = "distributed")]
+use hfendpoints_core::{QUEUE_NAME_ENV, QUEUE_ROLE_ENV, VISIBILITY_TIMEOUT_ENV};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+use std::sync::Arc;
+use utoipa::openapi::{Deprecated, OpenApi};
+
+/// Environment variable holding the path of the manifest the endpoint boots from
+pub const SNAPSHOT_ENV: &str = "HFENDPOINT_SNAPSHOT";
+
+/// Environment variable holding the key signing and verifying the manifests
+pub const SNAPSHOT_KEY_ENV: &str = "HFENDPOINT_SNAPSHOT_KEY";
+
+/// Version of the manifest format, bumped on breaking changes
+const SNAPSHOT_VERSION: u32 = 1;
+
+/// Environment variables recorded in the snapshots, secrets being left out on purpose
+pub(crate) const SNAPSHOT_VARIABLES: [&str; 30] = [
+    MODEL_ID_ENV,
+    AUDIO_PROMPT_LIMIT_ENV,
+    AUDIO_PROMPT_OVERFLOW_ENV,
+    AUDIO_SAMPLE_RATE_ENV,
+    AUTH_EXEMPT_ENV,
+    BODY_LIMIT_ENV,
+    CONFIG_FILE_ENV,
+    DISABLE_DEPRECATED_ROUTES_ENV,
+    JOURNAL_DIR_ENV,
+    JWE_REQUIRED_ENV,
+    LOG_FORMAT_ENV,
+    LOUDNESS_TARGET_ENV,
+    MAX_BATCH_SIZE_ENV,
+    MAX_CONCURRENT_REQUESTS_ENV,
+    OIDC_AUDIENCE_ENV,
+    OIDC_ISSUER_ENV,
+    OIDC_JWKS_URI_ENV,
+    OIDC_TENANT_CLAIM_ENV,
+    PAYLOAD_CAPTURE_ENV,
+    PREEMPTION_RUNNING_ENV,
+    PREEMPTION_SLICE_ENV,
+    QUEUE_CAPACITY_ENV,
+    READY_QUEUE_THRESHOLD_ENV,
+    REQUEST_POLICY_ENV,
+    REQUEST_TIMEOUT_ENV,
+    SHUTDOWN_GRACE_PERIOD_ENV,
+    STREAM_FLUSH_INTERVAL_ENV,
+    TLS_CERT_ENV,
+    TLS_KEY_ENV,
+    WORKERS_ENV,
+];
+
+/// Variables of the distributed mode recorded in the snapshots, when built with it
+#[cfg(feature = "distributed")]
+const DISTRIBUTED_VARIABLES: [&str; 3] = [QUEUE_NAME_ENV, QUEUE_ROLE_ENV, VISIBILITY_TIMEOUT_ENV];
+
+#[cfg(not(feature = "distributed"))]
+const DISTRIBUTED_VARIABLES: [&str; 0] = [];
+
+/// Variables of the batches recorded in the snapshots, when built with them
+#[cfg(feature = "jobs")]
+const JOBS_VARIABLES: [&str; 1] = [JOBS_DIR_ENV];
+
+#[cfg(not(feature = "jobs"))]
+const JOBS_VARIABLES: [&str; 0] = [];
+
+/// Every environment variable recorded in the snapshots
+fn snapshot_variables() -> impl Iterator<Item = &'static str> {
+    SNAPSHOT_VARIABLES
+        .into_iter()
+        .chain(DISTRIBUTED_VARIABLES)
+        .chain(JOBS_VARIABLES)
+}
+
+/// Key signing the exported manifests and verifying the ones the endpoint boots from
+#[derive(Clone)]
+pub struct SnapshotKey(Vec<u8>);
+
+impl SnapshotKey {
+    pub fn new(key: impl Into<Vec<u8>>) -> Self {
+        Self(key.into())
+    }
+
+    /// Key defined through `HFENDPOINT_SNAPSHOT_KEY`, if any
+    pub fn from_env() -> Option<Self> {
+        std::env::var(SNAPSHOT_KEY_ENV)
+            .ok()
+            .filter(|key| !key.is_empty())
+            .map(Self::new)
+    }
+
+    fn mac(&self, payload: &[u8]) -> Hmac<Sha256> {
+        // HMAC accepts keys of any size
+        let mut mac = Hmac::<Sha256>::new_from_slice(&self.0).expect("HMAC key of any size");
+        mac.update(payload);
+        mac
+    }
+}
+
+/// Model served by the endpoint
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Clone, PartialEq, Deserialize, Serialize)]
+struct ModelSnapshot {
+    id: String,
+    revision: Option<String>,
+}
+
+/// Methods a route answers to
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Clone, PartialEq, Deserialize, Serialize)]
+struct RouteSnapshot {
+    methods: Vec<String>,
+    deprecated: bool,
+}
+
+/// Resolved state of the endpoint, see the module documentation
+#[cfg_attr(debug_assertions, derive(Debug))]
+#[derive(Clone, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Snapshot {
+    version: u32,
+    api_version: String,
+    model: ModelSnapshot,
+    environment: BTreeMap<String, String>,
+    routes: BTreeMap<String, RouteSnapshot>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    signature: Option<String>,
+}
+
+impl Snapshot {
+    /// Snapshot of the endpoint serving `model` through the routes documented by `api`
+    pub(crate) fn capture(model: &ModelCard, api: &OpenApi) -> Self {
+        let environment = snapshot_variables()
+            .filter_map(|name| Some((name.to_string(), std::env::var(name).ok()?)))
+            .collect();
+
+        let routes = api
+            .paths
+            .paths
+            .iter()
+            .map(|(path, item)| {
+                let operations = [
+                    ("GET", &item.get),
+                    ("PUT", &item.put),
+                    ("POST", &item.post),
+                    ("DELETE", &item.delete),
+                    ("OPTIONS", &item.options),
+                    ("HEAD", &item.head),
+                    ("PATCH", &item.patch),
+                    ("TRACE", &item.trace),
+                ];
+
+                let mut route = RouteSnapshot {
+                    methods: Vec::new(),
+                    deprecated: false,
+                };
+                for (method, operation) in operations {
+                    if let Some(operation) = operation {
+                        route.methods.push(method.to_string());
+                        route.deprecated |= matches!(operation.deprecated, Some(Deprecated::True));
+                    }
+                }
+                (path.clone(), route)
+            })
+            .collect();
+
+        Self {
+            version: SNAPSHOT_VERSION,
+            api_version: API_VERSION.to_string(),
+            model: ModelSnapshot {
+                id: model.id().to_string(),
+                revision: model.revision.clone(),
+            },
+            environment,
+            routes,
+            signature: None,
+        }
+    }
+
+    /// Parse the manifest, rejecting it unless signed with `key`
+    pub fn from_json(manifest: &[u8], key: &SnapshotKey) -> OpenAiResult<Self> {
+        let snapshot: Self = serde_json::from_slice(manifest)
+            .map_err(|err| OpenAiError::Snapshot(format!("malformed manifest: {err}")))?;
+
+        if snapshot.version != SNAPSHOT_VERSION {
+            return Err(OpenAiError::Snapshot(format!(
+                "unsupported manifest version {} (expected {SNAPSHOT_VERSION})",
+                snapshot.version
+            )));
+        }
+
+        let signature = snapshot
+            .signature
+            .as_deref()
+            .and_then(|signature| BASE64_URL_SAFE_NO_PAD.decode(signature).ok())
+            .ok_or_else(|| OpenAiError::Snapshot(String::from("manifest is not signed")))?;
+        key.mac(&snapshot.payload())
+            .verify_slice(&signature)
+            .map_err(|_| OpenAiError::Snapshot(String::from("signature does not match")))?;
+
+        Ok(snapshot)
+    }
+
+    /// Read the manifest stored at `path`, rejecting it unless signed with `key`
+    pub fn from_file(path: impl AsRef<Path>, key: &SnapshotKey) -> OpenAiResult<Self> {
+        let path = path.as_ref();
+        let manifest = std::fs::read(path)
+            .map_err(|err| OpenAiError::Snapshot(format!("{}: {err}", path.display())))?;
+        Self::from_json(&manifest, key)
+    }
+
+    /// Manifest referenced by `HFENDPOINT_SNAPSHOT`, verified with `HFENDPOINT_SNAPSHOT_KEY`
+    pub fn from_env() -> OpenAiResult<Option<Self>> {
+        let Ok(path) = std::env::var(SNAPSHOT_ENV) else {
+            return Ok(None);
+        };
+
+        let key = SnapshotKey::from_env().ok_or_else(|| {
+            OpenAiError::Snapshot(format!(
+                "{SNAPSHOT_KEY_ENV} must be set to boot from {path}"
+            ))
+        })?;
+        Self::from_file(path, &key).map(Some)
+    }
+
+    /// Value of the recorded environment variables, `None` for the ones which were not set
+    pub fn variables(&self) -> impl Iterator<Item = (&'static str, Option<&str>)> {
+        snapshot_variables().map(|name| (name, self.environment.get(name).map(String::as_str)))
+    }
+
+    /// Sign the snapshot with `key`
+    pub fn signed(mut self, key: &SnapshotKey) -> Self {
+        let signature = key.mac(&self.payload()).finalize().into_bytes();
+        self.signature = Some(BASE64_URL_SAFE_NO_PAD.encode(signature));
+        self
+    }
+
+    /// Fail unless `resolved`, the state of the booting endpoint, is the one recorded
+    pub(crate) fn ensure_matches(&self, resolved: &Snapshot) -> OpenAiResult<()> {
+        let mut drifts = Vec::new();
+
+        if self.api_version != resolved.api_version {
+            drifts.push(format!(
+                "API version {} instead of {}",
+                resolved.api_version, self.api_version
+            ));
+        }
+
+        if self.model != resolved.model {
+            drifts.push(format!(
+                "model {:?}@{:?} instead of {:?}@{:?}",
+                resolved.model.id, resolved.model.revision, self.model.id, self.model.revision
+            ));
+        }
+
+        for (name, recorded) in self.variables() {
+            if resolved.environment.get(name).map(String::as_str) != recorded {
+                drifts.push(format!("{name} differs"));
+            }
+        }
+
+        if self.routes != resolved.routes {
+            let paths = self.routes.keys().chain(resolved.routes.keys());
+            let differing: BTreeSet<_> = paths
+                .filter(|path| self.routes.get(*path) != resolved.routes.get(*path))
+                .collect();
+            drifts.push(format!("routes {differing:?} differ"));
+        }
+
+        if drifts.is_empty() {
+            Ok(())
+        } else {
+            Err(OpenAiError::Snapshot(format!(
+                "endpoint does not match the manifest: {}",
+                drifts.join(", ")
+            )))
+        }
+    }
+
+    /// Canonical bytes covered by the signature
+    fn payload(&self) -> Vec<u8> {
+        let unsigned = Self {
+            signature: None,
+            ..self.clone()
+        };
+        serde_json::to_vec(&unsigned).expect("snapshots serialize to JSON")
+    }
+}
+
+/// Manifest of the running endpoint, signed once when the server starts
+pub(crate) async fn export_snapshot(State(snapshot): State<Arc<Snapshot>>) -> Json<Snapshot> {
+    Json(snapshot.as_ref().clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::models::ModelCard;
+    use crate::snapshot::{Snapshot, SnapshotKey};
+    use axum::http::StatusCode;
+    use utoipa::openapi::Deprecated;
+    use utoipa_axum::router::OpenApiRouter;
+    use utoipa_axum::routes;
+
+    #[utoipa::path(get, path = "/legacy", responses((status = OK)))]
+    async fn legacy() -> StatusCode {
+        StatusCode::OK
+    }
+
+    fn capture(revision: &str, deprecated: bool) -> Snapshot {
+        let mut router = OpenApiRouter::<()>::new().routes(routes!(legacy));
+        if deprecated {
+            let item = router.get_openapi_mut().paths.paths.get_mut("/legacy");
+            item.unwrap().get.as_mut().unwrap().deprecated = Some(Deprecated::True);
+        }
+        let card = ModelCard::new("openai/whisper-large-v3").revision(revision);
+        Snapshot::capture(&card, router.get_openapi())
+    }
+
+    #[test]
+    fn signed_snapshots_round_trip() {
+        let key = SnapshotKey::new("secret");
+        let snapshot = capture("main", false).signed(&key);
+        let manifest = serde_json::to_vec(&snapshot).unwrap();
+
+        // Exports are reproducible byte-for-byte
+        let again = serde_json::to_vec(&capture("main", false).signed(&key)).unwrap();
+        assert_eq!(manifest, again);
+
+        let restored = Snapshot::from_json(&manifest, &key).unwrap();
+        assert_eq!(restored, snapshot);
+        assert!(restored.ensure_matches(&capture("main", false)).is_ok());
+
+        let drift = restored.ensure_matches(&capture("v2", true)).unwrap_err();
+        assert!(drift.to_string().contains("model"));
+        assert!(drift.to_string().contains("routes {\"/legacy\"} differ"));
+    }
+
+    #[test]
+    fn reject_unsigned_or_tampered_manifests() {
+        let key = SnapshotKey::new("secret");
+
+        let unsigned = serde_json::to_vec(&capture("main", false)).unwrap();
+        assert!(Snapshot::from_json(&unsigned, &key).is_err());
+
+        let signed = serde_json::to_string(&capture("main", false).signed(&key)).unwrap();
+        let tampered = signed.replace("\"main\"", "\"v2\"");
+        assert!(Snapshot::from_json(tampered.as_bytes(), &key).is_err());
+        assert!(Snapshot::from_json(signed.as_bytes(), &SnapshotKey::new("other")).is_err());
+    }
+}
@@ -0,0 +1,259 @@
+//! Tracing of the requests served by the endpoint.
+//!
+//! Every request runs in a `request` span holding its correlation ID, task, audio size, the
+//! metadata attached by the caller, the time spent waiting for the handler and the time spent in
+//! the handler, the handler itself running in a child `handler` span.
+//!
+//! With the `otel` feature, spans are exported over OTLP (HTTP/protobuf) as configured through the
+//! standard `OTEL_*` environment variables (`OTEL_EXPORTER_OTLP_ENDPOINT`, `OTEL_SERVICE_NAME`,
+//! `OTEL_TRACES_SAMPLER`, ...). The W3C `traceparent` of incoming requests is continued and handed
+//! over to remote workers, so the gateway, the endpoint and the handler share the same trace.
+use crate::usage;
+use axum::http::Request;
+use std::time::Duration;
+use tracing::field::Empty;
+use tracing::{Span, info_span};
+
+/// Span of a request served over HTTP, continuing the trace of the caller if any
+pub(crate) fn request_span<B>(request: &Request<B>) -> Span {
+    let request_id = request
+        .headers()
+        .get("x-request-id")
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+
+    let span = info_span!(
+        "request",
+        method = %request.method(),
+        uri = %request.uri(),
+        request_id,
+        task = Empty,
+        audio_bytes = Empty,
+        metadata = Empty,
+        queue_wait_ms = Empty,
+        handler_ms = Empty,
+    );
+
+    #[cfg(feature = "otel")]
+    otel::continue_trace(
+        &span,
+        &opentelemetry_http::HeaderExtractor(request.headers()),
+    );
+    span
+}
+
+/// Span of a request received from the queue by a remote worker, continuing the trace of the
+/// endpoint which scheduled it
+#[cfg(feature = "distributed")]
+pub(crate) fn remote_span(request_id: &str, traceparent: Option<&str>) -> Span {
+    let span = info_span!(
+        "request",
+        request_id,
+        queue_wait_ms = Empty,
+        handler_ms = Empty,
+    );
+
+    #[cfg(feature = "otel")]
+    if let Some(traceparent) = traceparent {
+        let carrier = std::collections::HashMap::from([(
+            String::from("traceparent"),
+            traceparent.to_string(),
+        )]);
+        otel::continue_trace(&span, &carrier);
+    }
+    #[cfg(not(feature = "otel"))]
+    let _ = traceparent;
+    span
+}
+
+/// W3C `traceparent` of `span`, when exported
+#[cfg(all(feature = "distributed", feature = "otel"))]
+pub(crate) fn traceparent(span: &Span) -> Option<String> {
+    use opentelemetry::global;
+    use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+    let mut carrier = std::collections::HashMap::new();
+    global::get_text_map_propagator(|propagator| {
+        propagator.inject_context(&span.context(), &mut carrier)
+    });
+    carrier.remove("traceparent")
+}
+
+/// W3C `traceparent` of `span`, only exported with the `otel` feature
+#[cfg(all(feature = "distributed", not(feature = "otel")))]
+pub(crate) fn traceparent(_span: &Span) -> Option<String> {
+    None
+}
+
+/// Record the task served by the request running in the current span, and accounted for its usage
+pub(crate) fn record_task(task: &str) {
+    Span::current().record("task", task);
+    usage::record_task(task);
+}
+
+/// Record the size of the audio uploaded along the request running in the current span, and
+/// accounted for its usage
+pub(crate) fn record_audio_bytes(bytes: usize) {
+    Span::current().record("audio_bytes", bytes);
+    usage::record_audio_bytes(bytes);
+}
+
+#[inline]
+pub(crate) fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+#[cfg(feature = "otel")]
+pub use otel::{init_tracing, shutdown_tracing};
+
+#[cfg(feature = "otel")]
+mod otel {
+    use crate::{EndpointConfig, LogFormat};
+    use opentelemetry::global;
+    use opentelemetry::propagation::Extractor;
+    use opentelemetry::trace::TracerProvider;
+    use opentelemetry_otlp::SpanExporter;
+    use opentelemetry_sdk::Resource;
+    use opentelemetry_sdk::propagation::TraceContextPropagator;
+    use opentelemetry_sdk::trace::SdkTracerProvider;
+    use std::sync::OnceLock;
+    use tracing::{Span, warn};
+    use tracing_opentelemetry::OpenTelemetrySpanExt;
+    use tracing_subscriber::filter::LevelFilter;
+    use tracing_subscriber::layer::SubscriberExt;
+    use tracing_subscriber::util::SubscriberInitExt;
+    use tracing_subscriber::{Layer, fmt};
+
+    /// Provider exporting the spans, flushed when the server stops
+    static PROVIDER: OnceLock<SdkTracerProvider> = OnceLock::new();
+
+    /// Whether traces are disabled through `OTEL_SDK_DISABLED` or `OTEL_TRACES_EXPORTER`
+    fn disabled() -> bool {
+        let sdk_disabled = std::env::var("OTEL_SDK_DISABLED")
+            .is_ok_and(|disabled| disabled.eq_ignore_ascii_case("true"));
+        let no_exporter = std::env::var("OTEL_TRACES_EXPORTER")
+            .is_ok_and(|exporter| exporter.eq_ignore_ascii_case("none"));
+        sdk_disabled || no_exporter
+    }
+
+    /// Install the logging subscriber, exporting the spans over OTLP unless disabled through
+    /// `OTEL_SDK_DISABLED=true` or `OTEL_TRACES_EXPORTER=none`. Logs are written in the format
+    /// configured through `HFENDPOINT_LOG_FORMAT` or the configuration file.
+    pub fn init_tracing() {
+        let logs = match EndpointConfig::current().log_format {
+            LogFormat::Text => fmt::layer().boxed(),
+            LogFormat::Json => fmt::layer().json().boxed(),
+        };
+        let registry = tracing_subscriber::registry().with(logs.with_filter(LevelFilter::INFO));
+        if disabled() {
+            registry.init();
+            return;
+        }
+
+        let exporter = match SpanExporter::builder().with_http().build() {
+            Ok(exporter) => exporter,
+            Err(err) => {
+                registry.init();
+                warn!("Failed to create the OTLP exporter, traces are not exported: {err}");
+                return;
+            }
+        };
+
+        // Sampling and batching are configured through OTEL_TRACES_SAMPLER and OTEL_BSP_*
+        let provider = SdkTracerProvider::builder()
+            .with_batch_exporter(exporter)
+            .with_resource(Resource::builder().build())
+            .build();
+        let tracer = provider.tracer(env!("CARGO_PKG_NAME"));
+        let _ = PROVIDER.set(provider);
+
+        global::set_text_map_propagator(TraceContextPropagator::new());
+        registry
+            .with(
+                tracing_opentelemetry::layer()
+                    .with_tracer(tracer)
+                    .with_filter(LevelFilter::INFO),
+            )
+            .init();
+    }
+
+    /// Export the spans not sent yet, to be called before the process exits
+    pub fn shutdown_tracing() {
+        if let Some(provider) = PROVIDER.get()
+            && let Err(err) = provider.shutdown()
+        {
+            warn!("Failed to flush the traces: {err}");
+        }
+    }
+
+    /// Attach `span` to the trace propagated through `carrier`, if any
+    pub(super) fn continue_trace(span: &Span, carrier: &dyn Extractor) {
+        let parent = global::get_text_map_propagator(|propagator| propagator.extract(carrier));
+        let _ = span.set_parent(parent);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::context::Context;
+    use crate::headers::RequestId;
+    use crate::telemetry::{record_audio_bytes, record_task, request_span};
+    use axum::body::Body;
+    use axum::http::Request;
+    use std::io::Write;
+    use std::sync::{Arc, Mutex};
+    use tracing::info;
+    use tracing::instrument::WithSubscriber;
+
+    /// Logs written by the subscriber, shared with the test
+    #[derive(Clone, Default)]
+    struct Logs(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Logs {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn requests_record_their_timings() {
+        let logs = Logs::default();
+        let writer = logs.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_ansi(false)
+            .with_writer(move || writer.clone())
+            .finish();
+
+        async {
+            let request = Request::post("/api/v1/audio/transcriptions")
+                .header("x-request-id", "req-1")
+                .body(Body::empty())
+                .unwrap();
+            let span = request_span(&request);
+            let _entered = span.enter();
+
+            record_task("transcription");
+            record_audio_bytes(4);
+            let ctx = Context::new(RequestId::from(String::from("req-1")));
+            ctx.handled(async { info!("handled") }).await;
+            info!("done");
+        }
+        .with_subscriber(subscriber)
+        .await;
+
+        let logs = String::from_utf8(logs.0.lock().unwrap().clone()).unwrap();
+        let (handled, done) = logs.split_once('\n').unwrap();
+        assert!(handled.contains("request{"));
+        assert!(
+            handled.contains(
+                "request_id=\"req-1\" task=\"transcription\" audio_bytes=4 queue_wait_ms="
+            )
+        );
+        assert!(handled.contains("}:handler: "));
+        assert!(done.contains("handler_ms="));
+    }
+}
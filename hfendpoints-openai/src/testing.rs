@@ -0,0 +1,71 @@
+//! Helpers shared by the tests of the task routes.
+use crate::context::Context;
+use axum::Router;
+use axum::body::{Body, Bytes, to_bytes};
+use axum::http::header::CONTENT_TYPE;
+use axum::http::request::Builder;
+use axum::http::{HeaderMap, Request, StatusCode};
+use hfendpoints_core::{Error, RequestReceiver};
+use tower::ServiceExt;
+use utoipa_axum::router::OpenApiRouter;
+
+/// Content type of the multipart forms built by the tests, their parts delimited by `--hfendpoints`
+pub(crate) const MULTIPART_FORM: &str = "multipart/form-data; boundary=hfendpoints";
+
+/// `POST` request to `path`, given a request id as `SetRequestIdLayer` does when served
+pub(crate) fn request(path: &str) -> Builder {
+    Request::post(path).header("x-request-id", "test")
+}
+
+/// Send `request` to `router`, reading the response whole
+pub(crate) async fn send(router: Router, request: Request<Body>) -> (StatusCode, HeaderMap, Bytes) {
+    let (parts, body) = router.oneshot(request).await.unwrap().into_parts();
+    let body = to_bytes(body, usize::MAX).await.unwrap();
+    (parts.status, parts.headers, body)
+}
+
+/// Send `body` as `content_type` to the route of `router` at `path`, reading the response whole
+pub(crate) async fn post(
+    router: impl Into<OpenApiRouter>,
+    path: &str,
+    content_type: &str,
+    body: impl Into<Body>,
+) -> (StatusCode, HeaderMap, Bytes) {
+    let (router, _) = router.into().split_for_parts();
+    let request = request(path)
+        .header(CONTENT_TYPE, content_type)
+        .body(body.into())
+        .unwrap();
+    send(router, request).await
+}
+
+/// Spawn a fake handler answering every request scheduled on `receiver` with `answer`
+pub(crate) fn answer<I, O>(
+    receiver: RequestReceiver<(I, Context), O>,
+    mut answer: impl FnMut(I) -> Result<O, Error> + Send + 'static,
+) where
+    I: Send + 'static,
+    O: Send + 'static,
+{
+    stream(receiver, move |request| [answer(request)]);
+}
+
+/// Spawn a fake handler streaming the responses `respond` gives for every request scheduled on
+/// `receiver`
+pub(crate) fn stream<I, O, R>(
+    mut receiver: RequestReceiver<(I, Context), O>,
+    mut respond: impl FnMut(I) -> R + Send + 'static,
+) where
+    I: Send + 'static,
+    O: Send + 'static,
+    R: IntoIterator<Item = Result<O, Error>>,
+    R::IntoIter: Send,
+{
+    tokio::spawn(async move {
+        while let Some(((request, _ctx), egress)) = receiver.recv().await {
+            for response in respond(request) {
+                let _ = egress.send(response).await;
+            }
+        }
+    });
+}
@@ -0,0 +1,97 @@
+//! Usage accounting of the task routes, see [`hfendpoints_core::usage`].
+//!
+//! Every request reaching a task route gets a usage record, completed by the route along its
+//! handling (task, audio size and duration, generated tokens, caller metadata) and emitted once the response
+//! headers are produced. Streamed responses are accounted when the stream starts, without the
+//! tokens generated along the stream.
+//!
+//! Without the `usage` feature nothing is accounted, the routes recording into the void.
+#![cfg_attr(not(feature = "usage"), allow(unused_variables))]
+use crate::metadata::Metadata;
+#[cfg(feature = "usage")]
+use axum::{
+    extract::{Request, State},
+    http::header::CONTENT_LENGTH,
+    middleware::Next,
+    response::Response,
+};
+#[cfg(feature = "usage")]
+use hfendpoints_core::{UsageRecord, UsageRecorder};
+#[cfg(feature = "usage")]
+use std::{cell::RefCell, time::Instant};
+
+#[cfg(feature = "usage")]
+tokio::task_local! {
+    /// Usage record of the request handled by the current task
+    static USAGE: RefCell<UsageRecord>;
+}
+
+/// Complete the usage record of the request handled by the current task, if accounted
+#[cfg(feature = "usage")]
+fn update(complete: impl FnOnce(&mut UsageRecord)) {
+    let _ = USAGE.try_with(|usage| complete(&mut usage.borrow_mut()));
+}
+
+/// Record the task served by the request handled by the current task
+pub(crate) fn record_task(task: &str) {
+    #[cfg(feature = "usage")]
+    update(|usage| usage.task = task.to_string());
+}
+
+/// Record the size of the audio uploaded along the request handled by the current task
+pub(crate) fn record_audio_bytes(bytes: usize) {
+    #[cfg(feature = "usage")]
+    update(|usage| usage.input_bytes = Some(bytes as u64));
+}
+
+/// Record the duration, in seconds, of the audio uploaded along the request handled by the current task
+pub(crate) fn record_audio_duration(duration: f64) {
+    #[cfg(feature = "usage")]
+    update(|usage| usage.input_duration = Some(duration));
+}
+
+/// Record the number of tokens generated for the request handled by the current task
+pub(crate) fn record_output_tokens(tokens: u32) {
+    #[cfg(feature = "usage")]
+    update(|usage| usage.output_tokens = Some(tokens));
+}
+
+/// Record the metadata attached by the caller to the request handled by the current task
+pub(crate) fn record_metadata(metadata: &Metadata) {
+    #[cfg(feature = "usage")]
+    update(|usage| usage.metadata = serde_json::from_str(metadata.as_str()).ok());
+}
+
+/// Middleware handing the usage record of every request served by a task route over to `recorder`
+#[cfg(feature = "usage")]
+pub(crate) async fn account_usage(
+    State(recorder): State<UsageRecorder>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let request_id = request
+        .headers()
+        .get("x-request-id")
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+    let mut usage = UsageRecord::new(request_id, "");
+    usage.input_bytes = request
+        .headers()
+        .get(CONTENT_LENGTH)
+        .and_then(|value| value.to_str().ok()?.parse().ok());
+
+    let started = Instant::now();
+    let (response, usage) = USAGE
+        .scope(RefCell::new(usage), async {
+            let response = next.run(request).await;
+            (response, USAGE.with(|usage| usage.borrow().clone()))
+        })
+        .await;
+
+    // Requests not reaching any task route, i.e. unknown paths, are not accounted
+    if !usage.task.is_empty() {
+        let status = response.status().as_u16();
+        recorder.record(usage.latency(started.elapsed()).status(status));
+    }
+    response
+}
@@ -0,0 +1,1079 @@
+use crate::SchemaError;
+use alloc::collections::BTreeSet;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::str::FromStr;
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "python")]
+use pyo3::prelude::*;
+
+// Responses follow the snake_case naming used by OpenAI. Fields which are not emitted by every
+// OpenAI compatible server (or SDK version) are defaulted, and camelCase spellings are aliased,
+// so parsing does not break on these variations.
+
+/// One segment of the transcribed text and the corresponding details.
+#[cfg_attr(feature = "python", pyclass(frozen))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct Segment {
+    /// Unique identifier of the segment.
+    pub id: u16,
+
+    /// Start time of the segment in seconds.
+    pub start: f32,
+
+    /// End time of the segment in seconds.
+    pub end: f32,
+
+    /// Seek offset of the segment.
+    #[serde(default)]
+    pub seek: u16,
+
+    /// Temperature parameter used for generating the segment.
+    #[serde(default)]
+    pub temperature: f32,
+
+    /// Text content of the segment.
+    pub text: String,
+
+    /// Array of token IDs for the text content.
+    #[serde(default)]
+    pub tokens: Vec<u32>,
+
+    /// Average logprob of the segment.
+    /// If the value is lower than -1, consider the logprobs failed.
+    #[serde(default, alias = "avgLogprob")]
+    pub avg_logprob: f32,
+
+    /// Compression ratio of the segment.
+    /// If the value is greater than 2.4, consider the compression failed.
+    #[serde(default, alias = "compressionRatio")]
+    pub compression_ratio: f32,
+
+    /// Probability of no speech in the segment.
+    /// If the value is higher than 1.0 and the avg_logprob is below -1, consider this segment silent.
+    #[serde(default, alias = "noSpeechProb")]
+    pub no_speech_prob: f32,
+
+    /// Language spoken in the segment, only reported when code-switching was allowed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+
+    /// Channel of the audio the segment was transcribed from (`left`, `right`, `channel_2`, ...),
+    /// only reported when the channels were transcribed separately.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub channel: Option<String>,
+}
+
+#[cfg_attr(feature = "python", pyclass)]
+#[derive(Clone, Default)]
+pub struct SegmentBuilder {
+    id: Option<u16>,
+    start: Option<f32>,
+    end: Option<f32>,
+    seek: Option<u16>,
+    temperature: Option<f32>,
+    text: Option<String>,
+    tokens: Option<Vec<u32>>,
+    avg_logprob: Option<f32>,
+    compression_ratio: Option<f32>,
+    no_speech_prob: Option<f32>,
+    language: Option<String>,
+    channel: Option<String>,
+}
+
+impl SegmentBuilder {
+    pub fn id(mut self, id: u16) -> Self {
+        self.id = Some(id);
+        self
+    }
+
+    pub fn start(mut self, start: f32) -> Self {
+        self.start = Some(start);
+        self
+    }
+
+    pub fn end(mut self, end: f32) -> Self {
+        self.end = Some(end);
+        self
+    }
+
+    pub fn seek(mut self, seek: u16) -> Self {
+        self.seek = Some(seek);
+        self
+    }
+
+    pub fn temperature(mut self, temperature: f32) -> Self {
+        self.temperature = Some(temperature);
+        self
+    }
+
+    pub fn text(mut self, text: String) -> Self {
+        self.text = Some(text);
+        self
+    }
+
+    pub fn tokens(mut self, tokens: Vec<u32>) -> Self {
+        self.tokens = Some(tokens);
+        self
+    }
+
+    pub fn avg_logprob(mut self, avg_logprob: f32) -> Self {
+        self.avg_logprob = Some(avg_logprob);
+        self
+    }
+
+    pub fn compression_ratio(mut self, compression_ratio: f32) -> Self {
+        self.compression_ratio = Some(compression_ratio);
+        self
+    }
+
+    pub fn no_speech_prob(mut self, no_speech_prob: f32) -> Self {
+        self.no_speech_prob = Some(no_speech_prob);
+        self
+    }
+
+    pub fn language(mut self, language: String) -> Self {
+        self.language = Some(language);
+        self
+    }
+
+    pub fn channel(mut self, channel: String) -> Self {
+        self.channel = Some(channel);
+        self
+    }
+
+    /// Segment holding the fields set, `id`, `start`, `end`, `text` and `tokens` being required
+    pub fn build(self) -> Result<Segment, SchemaError> {
+        let segment = Segment {
+            id: self.id.ok_or(SchemaError::MissingField("Segment::id"))?,
+            start: self
+                .start
+                .ok_or(SchemaError::MissingField("Segment::start"))?,
+            end: self.end.ok_or(SchemaError::MissingField("Segment::end"))?,
+            seek: self.seek.unwrap_or(0),
+            temperature: self.temperature.unwrap_or(0.0),
+            text: self
+                .text
+                .ok_or(SchemaError::MissingField("Segment::text"))?,
+            tokens: self
+                .tokens
+                .ok_or(SchemaError::MissingField("Segment::tokens"))?,
+            avg_logprob: self.avg_logprob.unwrap_or(0.0),
+            compression_ratio: self.compression_ratio.unwrap_or(0.0),
+            no_speech_prob: self.no_speech_prob.unwrap_or(0.0),
+            language: self.language,
+            channel: self.channel,
+        };
+        segment.validate()?;
+        Ok(segment)
+    }
+}
+
+/// Check `start` and `end` delimit a span of the audio
+fn validate_timespan(object: &'static str, start: f32, end: f32) -> Result<(), SchemaError> {
+    if start.is_finite() && end.is_finite() && start <= end {
+        Ok(())
+    } else {
+        Err(SchemaError::InvalidTimespan { object, start, end })
+    }
+}
+
+impl Segment {
+    pub fn builder() -> SegmentBuilder {
+        SegmentBuilder::default()
+    }
+
+    /// Check the segment does not end before it starts
+    pub fn validate(&self) -> Result<(), SchemaError> {
+        validate_timespan("Segment", self.start, self.end)
+    }
+}
+
+/// A word of the transcribed text along with its timing, reported when word timestamps were requested.
+#[cfg_attr(feature = "python", pyclass(frozen))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct Word {
+    /// The text content of the word.
+    pub word: String,
+
+    /// Start time of the word in seconds.
+    pub start: f32,
+
+    /// End time of the word in seconds.
+    pub end: f32,
+}
+
+#[cfg_attr(feature = "python", pyclass)]
+#[derive(Clone, Default)]
+pub struct WordBuilder {
+    word: Option<String>,
+    start: Option<f32>,
+    end: Option<f32>,
+}
+
+impl WordBuilder {
+    pub fn word(mut self, word: String) -> Self {
+        self.word = Some(word);
+        self
+    }
+
+    pub fn start(mut self, start: f32) -> Self {
+        self.start = Some(start);
+        self
+    }
+
+    pub fn end(mut self, end: f32) -> Self {
+        self.end = Some(end);
+        self
+    }
+
+    pub fn build(self) -> Result<Word, SchemaError> {
+        let word = Word {
+            word: self.word.ok_or(SchemaError::MissingField("Word::word"))?,
+            start: self.start.ok_or(SchemaError::MissingField("Word::start"))?,
+            end: self.end.ok_or(SchemaError::MissingField("Word::end"))?,
+        };
+        word.validate()?;
+        Ok(word)
+    }
+}
+
+impl Word {
+    pub fn builder() -> WordBuilder {
+        WordBuilder::default()
+    }
+
+    /// Check the word does not end before it starts
+    pub fn validate(&self) -> Result<(), SchemaError> {
+        validate_timespan("Word", self.start, self.end)
+    }
+}
+
+/// Represents a transcription response returned by model, based on the provided input.
+#[cfg_attr(feature = "python", pyclass(frozen))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct Transcription {
+    /// The transcribed text.
+    pub text: String,
+}
+
+/// Represents a verbose json transcription response returned by model, based on the provided input.
+#[cfg_attr(feature = "python", pyclass(frozen))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct VerboseTranscription {
+    /// The transcribed text.
+    pub text: String,
+
+    /// The duration of the input audio.
+    #[serde(default)]
+    pub duration: f32,
+
+    /// The language of the input audio.
+    #[serde(default)]
+    pub language: String,
+
+    /// Segments of the transcribed text and their corresponding details.
+    #[serde(default)]
+    pub segments: Vec<Segment>,
+
+    /// Extracted words and their corresponding timestamps, only when word timestamps were requested.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub words: Vec<Word>,
+}
+
+#[cfg_attr(feature = "python", pyclass)]
+#[derive(Clone, Default)]
+pub struct VerboseTranscriptionBuilder {
+    text: Option<String>,
+    duration: Option<f32>,
+    language: Option<String>,
+    segments: Vec<Segment>,
+    words: Vec<Word>,
+}
+
+impl VerboseTranscriptionBuilder {
+    pub fn text(mut self, text: String) -> Self {
+        self.text = Some(text);
+        self
+    }
+
+    pub fn duration(mut self, duration: f32) -> Self {
+        self.duration = Some(duration);
+        self
+    }
+
+    pub fn language(mut self, language: String) -> Self {
+        self.language = Some(language);
+        self
+    }
+
+    /// Append `segment` to the segments of the transcription
+    pub fn segment(mut self, segment: Segment) -> Self {
+        self.segments.push(segment);
+        self
+    }
+
+    pub fn segments(mut self, segments: Vec<Segment>) -> Self {
+        self.segments = segments;
+        self
+    }
+
+    /// Append `word` to the words of the transcription
+    pub fn word(mut self, word: Word) -> Self {
+        self.words.push(word);
+        self
+    }
+
+    pub fn words(mut self, words: Vec<Word>) -> Self {
+        self.words = words;
+        self
+    }
+
+    /// Transcription holding the fields set, `text` being required
+    pub fn build(self) -> Result<VerboseTranscription, SchemaError> {
+        let transcription = VerboseTranscription {
+            text: self
+                .text
+                .ok_or(SchemaError::MissingField("VerboseTranscription::text"))?,
+            duration: self.duration.unwrap_or(0.0),
+            language: self.language.unwrap_or_default(),
+            segments: self.segments,
+            words: self.words,
+        };
+        transcription.validate()?;
+        Ok(transcription)
+    }
+}
+
+impl VerboseTranscription {
+    pub fn builder() -> VerboseTranscriptionBuilder {
+        VerboseTranscriptionBuilder::default()
+    }
+
+    /// Check neither the segments nor the words end before they start, and every segment has
+    /// its own id
+    pub fn validate(&self) -> Result<(), SchemaError> {
+        let mut ids = BTreeSet::new();
+        for segment in &self.segments {
+            segment.validate()?;
+            if !ids.insert(segment.id) {
+                return Err(SchemaError::DuplicateId {
+                    object: "Segment",
+                    id: segment.id,
+                });
+            }
+        }
+        self.words.iter().try_for_each(Word::validate)
+    }
+}
+
+#[cfg_attr(feature = "python", pyclass(frozen))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(
+    tag = "type",
+    rename = "transcript.text.delta",
+    rename_all = "snake_case"
+)]
+pub struct Delta {
+    /// The text delta that was additionally transcribed.
+    pub delta: String,
+    // TODO: logprobs -> https://platform.openai.com/docs/api-reference/audio/transcript-text-delta-event#audio/transcript-text-delta-event-logprobs
+}
+
+#[cfg_attr(feature = "python", pyclass(frozen))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(
+    tag = "type",
+    rename = "transcript.text.done",
+    rename_all = "snake_case"
+)]
+pub struct Done {
+    /// The text that was transcribed.
+    pub text: String,
+    // TODO: logprobs -> https://platform.openai.com/docs/api-reference/audio/transcript-text-done-event#audio/transcript-text-done-event-logprobs
+}
+
+#[cfg_attr(feature = "python", pyclass(frozen))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum StreamEvent {
+    /// Emitted when there is an additional text delta.
+    /// This is also the first event emitted when the transcription starts.
+    /// Only emitted when you create a transcription with the Stream parameter set to true.
+    Delta(Delta),
+
+    /// Emitted when the transcription is complete.
+    /// Contains the complete transcription text.
+    /// Only emitted when you create a transcription with the Stream parameter set to true.
+    Done(Done),
+}
+
+/// The format of the transcription returned by the endpoint.
+#[cfg_attr(feature = "python", pyclass(frozen))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ResponseFormat {
+    #[default]
+    Json,
+    Text,
+    VerboseJson,
+    Srt,
+    Vtt,
+}
+
+impl ResponseFormat {
+    #[inline]
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ResponseFormat::Json => "json",
+            ResponseFormat::Text => "text",
+            ResponseFormat::VerboseJson => "verbose_json",
+            ResponseFormat::Srt => "srt",
+            ResponseFormat::Vtt => "vtt",
+        }
+    }
+}
+
+/// The channels of the audio to transcribe.
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChannelSelection {
+    /// All the channels, mixed down
+    #[default]
+    Mixdown,
+
+    /// The first channel only
+    Left,
+
+    /// The second channel only
+    Right,
+
+    /// Every channel on its own, the segments reporting the channel they were transcribed from
+    PerChannel,
+}
+
+impl ChannelSelection {
+    #[inline]
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ChannelSelection::Mixdown => "mixdown",
+            ChannelSelection::Left => "left",
+            ChannelSelection::Right => "right",
+            ChannelSelection::PerChannel => "per_channel",
+        }
+    }
+}
+
+impl FromStr for ChannelSelection {
+    type Err = SchemaError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "mixdown" => Ok(ChannelSelection::Mixdown),
+            "left" => Ok(ChannelSelection::Left),
+            "right" => Ok(ChannelSelection::Right),
+            "per_channel" => Ok(ChannelSelection::PerChannel),
+            _ => Err(SchemaError::UnknownVariant {
+                field: "channels",
+                value: String::from(value),
+                expected: "'mixdown', 'left', 'right', 'per_channel'",
+            }),
+        }
+    }
+}
+
+/// The timestamp granularities to populate a `verbose_json` transcription with.
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TimestampGranularity {
+    Word,
+    Segment,
+}
+
+impl TimestampGranularity {
+    #[inline]
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TimestampGranularity::Word => "word",
+            TimestampGranularity::Segment => "segment",
+        }
+    }
+}
+
+impl FromStr for TimestampGranularity {
+    type Err = SchemaError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "word" => Ok(TimestampGranularity::Word),
+            "segment" => Ok(TimestampGranularity::Segment),
+            _ => Err(SchemaError::UnknownVariant {
+                field: "timestamp_granularities",
+                value: String::from(value),
+                expected: "'word', 'segment'",
+            }),
+        }
+    }
+}
+
+impl FromStr for ResponseFormat {
+    type Err = SchemaError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "json" => Ok(ResponseFormat::Json),
+            "verbose_json" => Ok(ResponseFormat::VerboseJson),
+            "text" => Ok(ResponseFormat::Text),
+            "srt" => Ok(ResponseFormat::Srt),
+            "vtt" => Ok(ResponseFormat::Vtt),
+            _ => Err(SchemaError::UnknownVariant {
+                field: "response_format",
+                value: String::from(value),
+                expected: "'json', 'verbose_json', 'text', 'srt', 'vtt'",
+            }),
+        }
+    }
+}
+
+#[cfg(feature = "python")]
+mod python {
+    use crate::audio::{
+        Segment, SegmentBuilder, Transcription, VerboseTranscription, VerboseTranscriptionBuilder,
+        Word, WordBuilder,
+    };
+    use alloc::vec::Vec;
+    use pyo3::prelude::*;
+
+    #[pymethods]
+    impl Segment {
+        #[allow(clippy::too_many_arguments)]
+        #[new]
+        #[pyo3(signature = (id, start, end, seek, temperature, text, tokens, avg_logprob, compression_ratio, no_speech_prob, language = None, channel = None))]
+        pub fn new(
+            id: u16,
+            start: f32,
+            end: f32,
+            seek: u16,
+            temperature: f32,
+            text: String,
+            tokens: Vec<u32>,
+            avg_logprob: f32,
+            compression_ratio: f32,
+            no_speech_prob: f32,
+            language: Option<String>,
+            channel: Option<String>,
+        ) -> PyResult<Self> {
+            let segment = Self {
+                id,
+                start,
+                end,
+                seek,
+                temperature,
+                text,
+                tokens,
+                avg_logprob,
+                compression_ratio,
+                no_speech_prob,
+                language,
+                channel,
+            };
+            segment.validate()?;
+            Ok(segment)
+        }
+
+        #[staticmethod]
+        #[pyo3(name = "builder")]
+        fn py_builder() -> SegmentBuilder {
+            SegmentBuilder::default()
+        }
+
+        #[getter(id)]
+        fn get_id(&self) -> u16 {
+            self.id
+        }
+
+        #[getter(start)]
+        fn get_start(&self) -> f32 {
+            self.start
+        }
+
+        #[getter(end)]
+        fn get_end(&self) -> f32 {
+            self.end
+        }
+
+        #[getter(seek)]
+        fn get_seek(&self) -> u16 {
+            self.seek
+        }
+
+        #[getter(temperature)]
+        fn get_temperature(&self) -> f32 {
+            self.temperature
+        }
+
+        #[getter(text)]
+        fn get_text(&self) -> &str {
+            &self.text
+        }
+
+        #[getter(tokens)]
+        fn get_tokens(&self) -> Vec<u32> {
+            self.tokens.clone()
+        }
+
+        #[getter(avg_logprob)]
+        fn get_avg_logprob(&self) -> f32 {
+            self.avg_logprob
+        }
+
+        #[getter(compression_ratio)]
+        fn get_compression_ratio(&self) -> f32 {
+            self.compression_ratio
+        }
+
+        #[getter(no_speech_prob)]
+        fn get_no_speech_prob(&self) -> f32 {
+            self.no_speech_prob
+        }
+
+        #[getter(language)]
+        fn get_language(&self) -> Option<&str> {
+            self.language.as_deref()
+        }
+
+        #[getter(channel)]
+        fn get_channel(&self) -> Option<&str> {
+            self.channel.as_deref()
+        }
+    }
+
+    #[pymethods]
+    impl Transcription {
+        #[new]
+        pub fn new(text: String) -> Self {
+            Self { text }
+        }
+
+        #[getter(text)]
+        fn get_text(&self) -> &str {
+            &self.text
+        }
+    }
+
+    #[pymethods]
+    impl Word {
+        #[new]
+        pub fn new(word: String, start: f32, end: f32) -> PyResult<Self> {
+            let word = Self { word, start, end };
+            word.validate()?;
+            Ok(word)
+        }
+
+        #[staticmethod]
+        #[pyo3(name = "builder")]
+        fn py_builder() -> WordBuilder {
+            WordBuilder::default()
+        }
+
+        #[getter(word)]
+        fn get_word(&self) -> &str {
+            &self.word
+        }
+
+        #[getter(start)]
+        fn get_start(&self) -> f32 {
+            self.start
+        }
+
+        #[getter(end)]
+        fn get_end(&self) -> f32 {
+            self.end
+        }
+    }
+
+    #[pymethods]
+    impl VerboseTranscription {
+        #[new]
+        #[pyo3(signature = (text, duration, language, segments, words = None))]
+        pub fn new(
+            text: String,
+            duration: f32,
+            language: String,
+            segments: Vec<Segment>,
+            words: Option<Vec<Word>>,
+        ) -> PyResult<Self> {
+            let transcription = Self {
+                text,
+                duration,
+                language,
+                segments,
+                words: words.unwrap_or_default(),
+            };
+            transcription.validate()?;
+            Ok(transcription)
+        }
+
+        #[staticmethod]
+        #[pyo3(name = "builder")]
+        fn py_builder() -> VerboseTranscriptionBuilder {
+            VerboseTranscriptionBuilder::default()
+        }
+
+        #[getter(text)]
+        fn get_text(&self) -> &str {
+            &self.text
+        }
+
+        #[getter(duration)]
+        fn get_duration(&self) -> f32 {
+            self.duration
+        }
+
+        #[getter(language)]
+        fn get_language(&self) -> &str {
+            &self.language
+        }
+
+        #[getter(segments)]
+        fn get_segments(&self) -> Vec<Segment> {
+            self.segments.clone()
+        }
+
+        #[getter(words)]
+        fn get_words(&self) -> Vec<Word> {
+            self.words.clone()
+        }
+    }
+
+    // Setters return the builder so calls can be chained, `build` raising `SchemaError` when the
+    // object would not be valid
+
+    #[pymethods]
+    impl SegmentBuilder {
+        #[new]
+        fn py_new() -> Self {
+            Self::default()
+        }
+
+        #[pyo3(name = "id")]
+        fn py_id(mut slf: PyRefMut<'_, Self>, id: u16) -> PyRefMut<'_, Self> {
+            slf.id = Some(id);
+            slf
+        }
+
+        #[pyo3(name = "start")]
+        fn py_start(mut slf: PyRefMut<'_, Self>, start: f32) -> PyRefMut<'_, Self> {
+            slf.start = Some(start);
+            slf
+        }
+
+        #[pyo3(name = "end")]
+        fn py_end(mut slf: PyRefMut<'_, Self>, end: f32) -> PyRefMut<'_, Self> {
+            slf.end = Some(end);
+            slf
+        }
+
+        #[pyo3(name = "seek")]
+        fn py_seek(mut slf: PyRefMut<'_, Self>, seek: u16) -> PyRefMut<'_, Self> {
+            slf.seek = Some(seek);
+            slf
+        }
+
+        #[pyo3(name = "temperature")]
+        fn py_temperature(mut slf: PyRefMut<'_, Self>, temperature: f32) -> PyRefMut<'_, Self> {
+            slf.temperature = Some(temperature);
+            slf
+        }
+
+        #[pyo3(name = "text")]
+        fn py_text(mut slf: PyRefMut<'_, Self>, text: String) -> PyRefMut<'_, Self> {
+            slf.text = Some(text);
+            slf
+        }
+
+        #[pyo3(name = "tokens")]
+        fn py_tokens(mut slf: PyRefMut<'_, Self>, tokens: Vec<u32>) -> PyRefMut<'_, Self> {
+            slf.tokens = Some(tokens);
+            slf
+        }
+
+        #[pyo3(name = "avg_logprob")]
+        fn py_avg_logprob(mut slf: PyRefMut<'_, Self>, avg_logprob: f32) -> PyRefMut<'_, Self> {
+            slf.avg_logprob = Some(avg_logprob);
+            slf
+        }
+
+        #[pyo3(name = "compression_ratio")]
+        fn py_compression_ratio(
+            mut slf: PyRefMut<'_, Self>,
+            compression_ratio: f32,
+        ) -> PyRefMut<'_, Self> {
+            slf.compression_ratio = Some(compression_ratio);
+            slf
+        }
+
+        #[pyo3(name = "no_speech_prob")]
+        fn py_no_speech_prob(
+            mut slf: PyRefMut<'_, Self>,
+            no_speech_prob: f32,
+        ) -> PyRefMut<'_, Self> {
+            slf.no_speech_prob = Some(no_speech_prob);
+            slf
+        }
+
+        #[pyo3(name = "language")]
+        fn py_language(mut slf: PyRefMut<'_, Self>, language: String) -> PyRefMut<'_, Self> {
+            slf.language = Some(language);
+            slf
+        }
+
+        #[pyo3(name = "channel")]
+        fn py_channel(mut slf: PyRefMut<'_, Self>, channel: String) -> PyRefMut<'_, Self> {
+            slf.channel = Some(channel);
+            slf
+        }
+
+        #[pyo3(name = "build")]
+        fn py_build(&self) -> PyResult<Segment> {
+            Ok(self.clone().build()?)
+        }
+    }
+
+    #[pymethods]
+    impl WordBuilder {
+        #[new]
+        fn py_new() -> Self {
+            Self::default()
+        }
+
+        #[pyo3(name = "word")]
+        fn py_word(mut slf: PyRefMut<'_, Self>, word: String) -> PyRefMut<'_, Self> {
+            slf.word = Some(word);
+            slf
+        }
+
+        #[pyo3(name = "start")]
+        fn py_start(mut slf: PyRefMut<'_, Self>, start: f32) -> PyRefMut<'_, Self> {
+            slf.start = Some(start);
+            slf
+        }
+
+        #[pyo3(name = "end")]
+        fn py_end(mut slf: PyRefMut<'_, Self>, end: f32) -> PyRefMut<'_, Self> {
+            slf.end = Some(end);
+            slf
+        }
+
+        #[pyo3(name = "build")]
+        fn py_build(&self) -> PyResult<Word> {
+            Ok(self.clone().build()?)
+        }
+    }
+
+    #[pymethods]
+    impl VerboseTranscriptionBuilder {
+        #[new]
+        fn py_new() -> Self {
+            Self::default()
+        }
+
+        #[pyo3(name = "text")]
+        fn py_text(mut slf: PyRefMut<'_, Self>, text: String) -> PyRefMut<'_, Self> {
+            slf.text = Some(text);
+            slf
+        }
+
+        #[pyo3(name = "duration")]
+        fn py_duration(mut slf: PyRefMut<'_, Self>, duration: f32) -> PyRefMut<'_, Self> {
+            slf.duration = Some(duration);
+            slf
+        }
+
+        #[pyo3(name = "language")]
+        fn py_language(mut slf: PyRefMut<'_, Self>, language: String) -> PyRefMut<'_, Self> {
+            slf.language = Some(language);
+            slf
+        }
+
+        #[pyo3(name = "segment")]
+        fn py_segment(mut slf: PyRefMut<'_, Self>, segment: Segment) -> PyRefMut<'_, Self> {
+            slf.segments.push(segment);
+            slf
+        }
+
+        #[pyo3(name = "segments")]
+        fn py_segments(mut slf: PyRefMut<'_, Self>, segments: Vec<Segment>) -> PyRefMut<'_, Self> {
+            slf.segments = segments;
+            slf
+        }
+
+        #[pyo3(name = "word")]
+        fn py_word(mut slf: PyRefMut<'_, Self>, word: Word) -> PyRefMut<'_, Self> {
+            slf.words.push(word);
+            slf
+        }
+
+        #[pyo3(name = "words")]
+        fn py_words(mut slf: PyRefMut<'_, Self>, words: Vec<Word>) -> PyRefMut<'_, Self> {
+            slf.words = words;
+            slf
+        }
+
+        #[pyo3(name = "build")]
+        fn py_build(&self) -> PyResult<VerboseTranscription> {
+            Ok(self.clone().build()?)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::SchemaError;
+    use crate::audio::{
+        Done, ResponseFormat, Segment, StreamEvent, TimestampGranularity, VerboseTranscription,
+        Word,
+    };
+    use alloc::vec;
+    use core::str::FromStr;
+
+    #[test]
+    fn stream_events_roundtrip() {
+        let done = StreamEvent::Done(Done {
+            text: "Hello world".into(),
+        });
+
+        let payload = serde_json::to_string(&done).unwrap();
+        assert_eq!(
+            payload,
+            r#"{"type":"transcript.text.done","text":"Hello world"}"#
+        );
+
+        match serde_json::from_str::<StreamEvent>(&payload).unwrap() {
+            StreamEvent::Done(done) => assert_eq!(done.text, "Hello world"),
+            StreamEvent::Delta(_) => panic!("Expected a transcript.text.done event"),
+        }
+    }
+
+    #[test]
+    fn parse_response_format() {
+        assert_eq!(
+            ResponseFormat::from_str("verbose_json"),
+            Ok(ResponseFormat::VerboseJson)
+        );
+        assert_eq!(ResponseFormat::from_str("vtt"), Ok(ResponseFormat::Vtt));
+        assert!(matches!(
+            ResponseFormat::from_str("xml"),
+            Err(SchemaError::UnknownVariant { .. })
+        ));
+    }
+
+    #[test]
+    fn parse_timestamp_granularity() {
+        assert_eq!(
+            TimestampGranularity::from_str("word"),
+            Ok(TimestampGranularity::Word)
+        );
+        assert!(matches!(
+            TimestampGranularity::from_str("token"),
+            Err(SchemaError::UnknownVariant { .. })
+        ));
+    }
+
+    #[test]
+    fn word_builder_requires_every_field() {
+        let word = Word::builder()
+            .word("Hello".into())
+            .start(0.0)
+            .end(0.4)
+            .build()
+            .unwrap();
+        assert_eq!(
+            serde_json::to_string(&word).unwrap(),
+            r#"{"word":"Hello","start":0.0,"end":0.4}"#
+        );
+
+        assert_eq!(
+            Word::builder()
+                .word("Hello".into())
+                .start(0.0)
+                .build()
+                .err(),
+            Some(SchemaError::MissingField("Word::end"))
+        );
+    }
+
+    #[test]
+    fn verbose_transcription_builder_rejects_inconsistent_segments() {
+        let segment = |id: u16, start: f32, end: f32| {
+            Segment::builder()
+                .id(id)
+                .start(start)
+                .end(end)
+                .text("Hello".into())
+                .tokens(vec![1, 2])
+                .build()
+        };
+        let first = segment(0, 0.0, 1.5).unwrap();
+        let second = segment(1, 1.5, 3.0).unwrap();
+
+        let transcription = VerboseTranscription::builder()
+            .text("Hello Hello".into())
+            .duration(3.0)
+            .segment(first.clone())
+            .segment(second)
+            .build()
+            .unwrap();
+        assert_eq!(transcription.segments.len(), 2);
+        assert_eq!(transcription.language, "");
+
+        assert_eq!(
+            segment(2, 3.0, 2.0).err(),
+            Some(SchemaError::InvalidTimespan {
+                object: "Segment",
+                start: 3.0,
+                end: 2.0,
+            })
+        );
+        assert!(segment(2, f32::NAN, 2.0).is_err());
+        assert!(
+            Word::builder()
+                .word("Hello".into())
+                .start(1.0)
+                .end(0.5)
+                .build()
+                .is_err()
+        );
+
+        let duplicated = VerboseTranscription::builder()
+            .text("Hello Hello".into())
+            .segments(vec![first.clone(), first])
+            .build();
+        assert_eq!(
+            duplicated.err(),
+            Some(SchemaError::DuplicateId {
+                object: "Segment",
+                id: 0,
+            })
+        );
+        assert_eq!(
+            VerboseTranscription::builder().build().err(),
+            Some(SchemaError::MissingField("VerboseTranscription::text"))
+        );
+    }
+}
@@ -0,0 +1,69 @@
+use core::fmt::{Display, Formatter};
+
+/// Failure to build a valid schema object
+#[derive(Clone, Debug, PartialEq)]
+pub enum SchemaError {
+    /// A required field was not provided
+    MissingField(&'static str),
+
+    /// The value is not one of the variants accepted by `field`
+    UnknownVariant {
+        field: &'static str,
+        value: alloc::string::String,
+        expected: &'static str,
+    },
+
+    /// The span of the audio covered by `object` ends before it starts, or is not finite
+    InvalidTimespan {
+        object: &'static str,
+        start: f32,
+        end: f32,
+    },
+
+    /// Several `object`s share the same `id`
+    DuplicateId { object: &'static str, id: u16 },
+}
+
+impl Display for SchemaError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::MissingField(field) => write!(f, "{field} is not set"),
+            Self::UnknownVariant {
+                field,
+                value,
+                expected,
+            } => write!(
+                f,
+                "Unknown {field}: {value}. Possible values are: {expected}."
+            ),
+            Self::InvalidTimespan { object, start, end } => write!(
+                f,
+                "{object} spans from {start}s to {end}s, its end must not precede its start"
+            ),
+            Self::DuplicateId { object, id } => {
+                write!(f, "{object} id {id} is used more than once")
+            }
+        }
+    }
+}
+
+impl core::error::Error for SchemaError {}
+
+#[cfg(feature = "python")]
+pub mod python {
+    use pyo3::PyErr;
+    use pyo3::exceptions::PyValueError;
+
+    pyo3::create_exception!(
+        hfendpoints,
+        SchemaError,
+        PyValueError,
+        "Raised when building a response object which would not be valid"
+    );
+
+    impl From<crate::SchemaError> for PyErr {
+        fn from(err: crate::SchemaError) -> Self {
+            SchemaError::new_err(err.to_string())
+        }
+    }
+}
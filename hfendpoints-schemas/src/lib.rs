@@ -0,0 +1,17 @@
+//! Request and response types shared by the endpoints and their clients.
+//!
+//! The crate only depends on `serde` and is `no_std` (with `alloc`) by default so browser (wasm) and
+//! edge clients can reuse the exact wire definitions. Transports opt into the `openapi` (utoipa
+//! schemas) and `python` (pyo3 classes) features.
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+pub mod audio;
+mod error;
+
+pub use error::SchemaError;
+
+/// Exception raised to Python for a [`SchemaError`]
+#[cfg(feature = "python")]
+pub use error::python::SchemaError as PySchemaError;
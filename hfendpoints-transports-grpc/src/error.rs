@@ -0,0 +1,47 @@
+use hfendpoints_core::Error as EndpointError;
+use std::convert::Infallible;
+use thiserror::Error;
+use tonic::Status;
+use tonic::transport::Error as TransportError;
+
+/// Define all the possible errors for the KServe v2 gRPC transport
+#[derive(Debug, Error)]
+pub enum GrpcError {
+    #[error("Endpoint error: {0}")]
+    Endpoint(#[from] EndpointError),
+
+    #[error("gRPC transport error: {0}")]
+    Transport(#[from] TransportError),
+
+    #[error("Invalid tensor: {0}")]
+    InvalidTensor(String),
+
+    #[error("Unknown model '{0}'")]
+    ModelNotFound(String),
+}
+
+impl From<Infallible> for GrpcError {
+    fn from(value: Infallible) -> Self {
+        match value {}
+    }
+}
+
+/// Map the failures to the gRPC status codes KServe and Triton clients expect
+impl From<GrpcError> for Status {
+    fn from(err: GrpcError) -> Self {
+        match &err {
+            GrpcError::Endpoint(EndpointError::QueueFull { .. }) => {
+                Status::resource_exhausted(err.to_string())
+            }
+            GrpcError::Endpoint(EndpointError::Timeout { .. }) => {
+                Status::deadline_exceeded(err.to_string())
+            }
+            GrpcError::Endpoint(EndpointError::HandlerTerminated) => {
+                Status::unavailable(err.to_string())
+            }
+            GrpcError::InvalidTensor(_) => Status::invalid_argument(err.to_string()),
+            GrpcError::ModelNotFound(_) => Status::not_found(err.to_string()),
+            GrpcError::Endpoint(_) | GrpcError::Transport(_) => Status::internal(err.to_string()),
+        }
+    }
+}
@@ -0,0 +1,317 @@
+use crate::error::GrpcError;
+use crate::proto::infer_parameter::ParameterChoice;
+use crate::proto::model_infer_response::InferOutputTensor;
+use crate::proto::{InferParameter, ModelInferRequest, ModelInferResponse};
+use crate::tensor::Tensor;
+use std::collections::HashMap;
+
+/// Parameter attached to a request or a response, i.e. sampling settings
+#[derive(Clone, Debug, PartialEq)]
+pub enum Parameter {
+    Bool(bool),
+    Int64(i64),
+    String(String),
+    Double(f64),
+    Uint64(u64),
+}
+
+impl From<Parameter> for InferParameter {
+    fn from(parameter: Parameter) -> Self {
+        let choice = match parameter {
+            Parameter::Bool(value) => ParameterChoice::BoolParam(value),
+            Parameter::Int64(value) => ParameterChoice::Int64Param(value),
+            Parameter::String(value) => ParameterChoice::StringParam(value),
+            Parameter::Double(value) => ParameterChoice::DoubleParam(value),
+            Parameter::Uint64(value) => ParameterChoice::Uint64Param(value),
+        };
+        Self {
+            parameter_choice: Some(choice),
+        }
+    }
+}
+
+/// Keep the parameters holding a value, as sent by clients not setting the `oneof`
+fn parameters_from_proto(
+    parameters: HashMap<String, InferParameter>,
+) -> HashMap<String, Parameter> {
+    parameters
+        .into_iter()
+        .filter_map(|(name, parameter)| {
+            let parameter = match parameter.parameter_choice? {
+                ParameterChoice::BoolParam(value) => Parameter::Bool(value),
+                ParameterChoice::Int64Param(value) => Parameter::Int64(value),
+                ParameterChoice::StringParam(value) => Parameter::String(value),
+                ParameterChoice::DoubleParam(value) => Parameter::Double(value),
+                ParameterChoice::Uint64Param(value) => Parameter::Uint64(value),
+            };
+            Some((name, parameter))
+        })
+        .collect()
+}
+
+/// Inference request received through `ModelInfer`, the input tensors being decoded
+/// whether they were sent as typed or raw contents
+#[derive(Clone, Debug)]
+pub struct InferenceRequest {
+    pub id: String,
+    pub model_name: String,
+    pub model_version: String,
+    pub parameters: HashMap<String, Parameter>,
+    pub inputs: Vec<Tensor>,
+
+    /// Names of the outputs the client asked for, all of them when empty
+    pub outputs: Vec<String>,
+}
+
+impl InferenceRequest {
+    /// Input tensor called `name`, if provided
+    pub fn input(&self, name: &str) -> Option<&Tensor> {
+        self.inputs.iter().find(|input| input.name() == name)
+    }
+}
+
+impl TryFrom<ModelInferRequest> for InferenceRequest {
+    type Error = GrpcError;
+
+    fn try_from(request: ModelInferRequest) -> Result<Self, Self::Error> {
+        // Raw contents are provided either for all the inputs or for none of them
+        let raw = request.raw_input_contents;
+        if !raw.is_empty() && raw.len() != request.inputs.len() {
+            return Err(GrpcError::InvalidTensor(format!(
+                "{} raw contents were provided for {} inputs",
+                raw.len(),
+                request.inputs.len()
+            )));
+        }
+
+        let inputs = request
+            .inputs
+            .into_iter()
+            .enumerate()
+            .map(|(index, input)| {
+                let datatype = input.datatype.parse()?;
+                match (raw.get(index), input.contents) {
+                    (Some(raw), _) => Tensor::from_raw(input.name, datatype, input.shape, raw),
+                    (None, Some(contents)) => {
+                        Tensor::from_contents(input.name, datatype, input.shape, contents)
+                    }
+                    (None, None) => Err(GrpcError::InvalidTensor(format!(
+                        "{}: no contents were provided",
+                        input.name
+                    ))),
+                }
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self {
+            id: request.id,
+            model_name: request.model_name,
+            model_version: request.model_version,
+            parameters: parameters_from_proto(request.parameters),
+            inputs,
+            outputs: request
+                .outputs
+                .into_iter()
+                .map(|output| output.name)
+                .collect(),
+        })
+    }
+}
+
+/// Output tensors produced by the model for an `InferenceRequest`
+#[derive(Clone, Debug, Default)]
+pub struct InferenceResponse {
+    pub parameters: HashMap<String, Parameter>,
+    pub outputs: Vec<Tensor>,
+}
+
+impl InferenceResponse {
+    pub fn new(outputs: Vec<Tensor>) -> Self {
+        Self {
+            parameters: HashMap::new(),
+            outputs,
+        }
+    }
+
+    pub fn with_parameter(mut self, name: impl Into<String>, parameter: Parameter) -> Self {
+        self.parameters.insert(name.into(), parameter);
+        self
+    }
+
+    /// Encode the response to the request `id`, keeping only the `requested` outputs (all when empty).
+    ///
+    /// Outputs are sent as raw contents, the only representation Triton clients decode.
+    pub(crate) fn into_proto(
+        self,
+        id: String,
+        model_name: &str,
+        model_version: String,
+        requested: &[String],
+    ) -> Result<ModelInferResponse, GrpcError> {
+        if let Some(missing) = requested
+            .iter()
+            .find(|name| !self.outputs.iter().any(|output| output.name() == *name))
+        {
+            return Err(GrpcError::InvalidTensor(format!(
+                "the model did not produce the requested output '{missing}'"
+            )));
+        }
+
+        let (outputs, raw_output_contents) = self
+            .outputs
+            .into_iter()
+            .filter(|output| {
+                requested.is_empty() || requested.iter().any(|name| name == output.name())
+            })
+            .map(|output| {
+                let raw = output.to_raw();
+                let tensor = InferOutputTensor {
+                    name: output.name().to_string(),
+                    datatype: output.datatype().to_string(),
+                    shape: output.shape().to_vec(),
+                    ..Default::default()
+                };
+                (tensor, raw)
+            })
+            .unzip();
+
+        Ok(ModelInferResponse {
+            model_name: model_name.to_string(),
+            model_version,
+            id,
+            parameters: self
+                .parameters
+                .into_iter()
+                .map(|(name, parameter)| (name, parameter.into()))
+                .collect(),
+            outputs,
+            raw_output_contents,
+        })
+    }
+}
+
+/// Build the request handed to the handler from a v2 inference request,
+/// letting handlers written for other transports serve KServe clients
+pub trait FromInference: Sized {
+    fn from_inference(request: InferenceRequest) -> Result<Self, GrpcError>;
+}
+
+/// Turn the response of the handler into the output tensors of a v2 inference response
+pub trait IntoInference {
+    fn into_inference(self) -> Result<InferenceResponse, GrpcError>;
+}
+
+impl FromInference for InferenceRequest {
+    fn from_inference(request: InferenceRequest) -> Result<Self, GrpcError> {
+        Ok(request)
+    }
+}
+
+impl IntoInference for InferenceResponse {
+    fn into_inference(self) -> Result<InferenceResponse, GrpcError> {
+        Ok(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::inference::{InferenceRequest, InferenceResponse};
+    use crate::proto::model_infer_request::{InferInputTensor, InferRequestedOutputTensor};
+    use crate::proto::{InferTensorContents, ModelInferRequest};
+    use crate::tensor::{DataType, Tensor, TensorData};
+    use prost::bytes::Bytes;
+
+    fn input(
+        name: &str,
+        datatype: &str,
+        contents: Option<InferTensorContents>,
+    ) -> InferInputTensor {
+        InferInputTensor {
+            name: name.to_string(),
+            datatype: datatype.to_string(),
+            shape: vec![2],
+            contents,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn decode_typed_and_raw_inputs() {
+        let contents = InferTensorContents {
+            fp32_contents: vec![1.0, 2.0],
+            ..Default::default()
+        };
+        let request = ModelInferRequest {
+            id: String::from("req-1"),
+            inputs: vec![input("x", "FP32", Some(contents))],
+            ..Default::default()
+        };
+        let request = InferenceRequest::try_from(request).unwrap();
+        assert_eq!(
+            request.input("x").unwrap().data(),
+            &TensorData::Fp32(vec![1.0, 2.0])
+        );
+
+        let request = ModelInferRequest {
+            inputs: vec![input("x", "INT64", None)],
+            raw_input_contents: vec![Bytes::from(
+                [1i64.to_le_bytes(), 2i64.to_le_bytes()].concat(),
+            )],
+            ..Default::default()
+        };
+        let request = InferenceRequest::try_from(request).unwrap();
+        assert_eq!(
+            request.input("x").unwrap().data(),
+            &TensorData::Int(vec![1, 2])
+        );
+
+        // Neither typed nor raw contents
+        let request = ModelInferRequest {
+            inputs: vec![input("x", "INT64", None)],
+            ..Default::default()
+        };
+        assert!(InferenceRequest::try_from(request).is_err());
+    }
+
+    #[test]
+    fn encode_requested_outputs() {
+        let request = ModelInferRequest {
+            id: String::from("req-1"),
+            outputs: vec![InferRequestedOutputTensor {
+                name: String::from("scores"),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let request = InferenceRequest::try_from(request).unwrap();
+
+        let outputs = vec![
+            Tensor::new(
+                "scores",
+                DataType::Fp32,
+                vec![1],
+                TensorData::Fp32(vec![0.5]),
+            )
+            .unwrap(),
+            Tensor::new("labels", DataType::Int32, vec![1], TensorData::Int(vec![3])).unwrap(),
+        ];
+        let id = request.id.clone();
+        let response = InferenceResponse::new(outputs.clone())
+            .into_proto(id, "model", String::new(), &request.outputs)
+            .unwrap();
+        assert_eq!(response.id, "req-1");
+        assert_eq!(response.model_name, "model");
+        assert_eq!(response.outputs.len(), 1);
+        assert_eq!(response.outputs[0].datatype, "FP32");
+        assert_eq!(&response.raw_output_contents[0][..], &0.5f32.to_le_bytes());
+
+        // Requested output not produced by the model
+        let response = InferenceResponse::new(outputs[1..].to_vec()).into_proto(
+            request.id,
+            "model",
+            String::new(),
+            &request.outputs,
+        );
+        assert!(response.is_err());
+    }
+}
@@ -0,0 +1,115 @@
+//! KServe / Triton v2 inference protocol over gRPC.
+//!
+//! Serves the `inference.GRPCInferenceService` (health, metadata and `ModelInfer`) on top of the same
+//! `Handler` / `EndpointContext` plumbing as the OpenAI compatible transport, so gateways probing the
+//! v2 protocol (KServe, Knative) can reach the handler:
+//!
+//! ```no_run
+//! # use hfendpoints_core::{request_channel, wait_for_requests, Endpoint, EndpointContext, Handler};
+//! # use hfendpoints_transports_grpc::{InferenceRequest, InferenceResponse, KServeEndpoint, ModelMetadata};
+//! # use std::sync::Arc;
+//! # async fn run<H>(handler: H) where H: Handler<Request = InferenceRequest, Response = InferenceResponse> + Send + Sync + 'static {
+//! let (sender, receiver) = request_channel(32);
+//! tokio::spawn(wait_for_requests(receiver, Arc::new(handler)));
+//!
+//! let endpoint = KServeEndpoint::new(EndpointContext::new(sender), ModelMetadata::new("my-model"));
+//! endpoint.on_handler_initialized();
+//! endpoint.serve_with_shutdown("0.0.0.0:8001".parse().unwrap(), tokio::signal::ctrl_c()).await.unwrap();
+//! # }
+//! ```
+//!
+//! `ServerReady` reports the endpoint ready once the handler is reported initialized, as long as its loop runs.
+//!
+//! Handlers receive the decoded input tensors as an `InferenceRequest` and answer an `InferenceResponse`.
+//! Handlers written for other request types implement `FromInference` / `IntoInference` to serve both.
+mod error;
+mod inference;
+mod model;
+pub mod proto;
+mod service;
+mod tensor;
+
+pub use error::GrpcError;
+pub use inference::{FromInference, InferenceRequest, InferenceResponse, IntoInference, Parameter};
+pub use model::ModelMetadata;
+pub use service::InferenceService;
+pub use tensor::{DataType, Tensor, TensorData};
+
+use hfendpoints_core::{Endpoint, EndpointContext, Error};
+use std::net::SocketAddr;
+use tokio::net::lookup_host;
+use tonic::transport::Server;
+use tracing::{error, info, instrument};
+
+pub type GrpcResult<T> = Result<T, GrpcError>;
+
+/// Endpoint exposing the handler behind an `EndpointContext` to KServe v2 gRPC clients
+pub struct KServeEndpoint<I, O> {
+    service: InferenceService<I, O>,
+}
+
+impl<I, O> KServeEndpoint<I, O>
+where
+    I: FromInference + Send + 'static,
+    O: IntoInference + Send + 'static,
+{
+    /// Schedule the inference requests targeting `model` through `context`
+    pub fn new(context: EndpointContext<I, O>, model: ModelMetadata) -> Self {
+        Self {
+            service: InferenceService::new(context, model),
+        }
+    }
+
+    /// The `tonic` service, to be mounted along with other services on a custom server
+    pub fn service(&self) -> InferenceService<I, O> {
+        self.service.clone()
+    }
+
+    /// Serve on `address` until `signal` resolves, letting in-flight calls complete
+    #[instrument(skip(self, signal))]
+    pub async fn serve_with_shutdown(
+        &self,
+        address: SocketAddr,
+        signal: impl Future<Output = impl Sized> + Send,
+    ) -> GrpcResult<()> {
+        info!("Starting KServe v2 gRPC endpoint at {address}");
+        Server::builder()
+            .add_service(self.service())
+            .serve_with_shutdown(address, async {
+                signal.await;
+                info!("Shutting down KServe v2 gRPC endpoint");
+            })
+            .await?;
+        Ok(())
+    }
+}
+
+impl<I, O> Endpoint<(String, u16)> for KServeEndpoint<I, O>
+where
+    I: FromInference + Send + 'static,
+    O: IntoInference + Send + 'static,
+{
+    /// Serve on `interface:port` until `SIGINT`, failures of the transport being logged
+    async fn serve(&self, (interface, port): (String, u16)) -> Result<(), Error> {
+        let address = match lookup_host((interface.as_str(), port)).await {
+            Ok(mut addresses) => addresses.next(),
+            Err(err) => {
+                error!("Failed to resolve {interface}:{port}: {err}");
+                return Ok(());
+            }
+        };
+
+        let Some(address) = address else {
+            error!("{interface}:{port} does not resolve to any address");
+            return Ok(());
+        };
+
+        if let Err(err) = self
+            .serve_with_shutdown(address, tokio::signal::ctrl_c())
+            .await
+        {
+            error!("Caught error while serving KServe v2 gRPC endpoint: {err}");
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,91 @@
+use crate::proto::ModelMetadataResponse;
+use crate::proto::model_metadata_response::TensorMetadata;
+use crate::tensor::DataType;
+
+/// Platform reported when none is provided
+const DEFAULT_PLATFORM: &str = "hfendpoints";
+
+/// Description of the model served by the endpoint, as reported by `ModelMetadata`.
+/// Shapes use `-1` for dimensions of variable size.
+#[derive(Clone, Debug)]
+pub struct ModelMetadata {
+    name: String,
+    versions: Vec<String>,
+    platform: String,
+    inputs: Vec<TensorMetadata>,
+    outputs: Vec<TensorMetadata>,
+}
+
+impl ModelMetadata {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            versions: Vec::new(),
+            platform: String::from(DEFAULT_PLATFORM),
+            inputs: Vec::new(),
+            outputs: Vec::new(),
+        }
+    }
+
+    /// Declare `version` as served, requests targeting any other version being rejected
+    pub fn with_version(mut self, version: impl Into<String>) -> Self {
+        self.versions.push(version.into());
+        self
+    }
+
+    /// Framework running the model, i.e. `pytorch_libtorch`
+    pub fn with_platform(mut self, platform: impl Into<String>) -> Self {
+        self.platform = platform.into();
+        self
+    }
+
+    pub fn with_input(
+        mut self,
+        name: impl Into<String>,
+        datatype: DataType,
+        shape: Vec<i64>,
+    ) -> Self {
+        self.inputs.push(TensorMetadata {
+            name: name.into(),
+            datatype: datatype.to_string(),
+            shape,
+        });
+        self
+    }
+
+    pub fn with_output(
+        mut self,
+        name: impl Into<String>,
+        datatype: DataType,
+        shape: Vec<i64>,
+    ) -> Self {
+        self.outputs.push(TensorMetadata {
+            name: name.into(),
+            datatype: datatype.to_string(),
+            shape,
+        });
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Whether requests for `name` at `version` (any version when empty) target this model
+    pub(crate) fn serves(&self, name: &str, version: &str) -> bool {
+        name == self.name
+            && (version.is_empty()
+                || self.versions.is_empty()
+                || self.versions.iter().any(|v| v == version))
+    }
+
+    pub(crate) fn to_proto(&self) -> ModelMetadataResponse {
+        ModelMetadataResponse {
+            name: self.name.clone(),
+            versions: self.versions.clone(),
+            platform: self.platform.clone(),
+            inputs: self.inputs.clone(),
+            outputs: self.outputs.clone(),
+        }
+    }
+}
@@ -0,0 +1,330 @@
+use crate::error::GrpcError;
+use crate::inference::{FromInference, InferenceRequest, IntoInference};
+use crate::model::ModelMetadata;
+use crate::proto::*;
+use hfendpoints_core::EndpointContext;
+use prost::Message;
+use std::convert::Infallible;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tonic::Status;
+use tonic::body::Body;
+use tonic::codec::ProstCodec;
+use tonic::codegen::{Body as HttpBody, BoxFuture, Service, StdError, http};
+use tonic::server::{Grpc, NamedService};
+use tracing::{debug, instrument};
+
+/// Name reported by `ServerMetadata`
+const SERVER_NAME: &str = "hfendpoints";
+
+/// Unary method of the service, adapting an async function to what `tonic::server::Grpc` drives
+struct Method<F>(F);
+
+impl<F, M, R, Fut> Service<tonic::Request<M>> for Method<F>
+where
+    F: FnMut(M) -> Fut,
+    Fut: Future<Output = Result<R, GrpcError>> + Send + 'static,
+{
+    type Response = tonic::Response<R>;
+    type Error = Status;
+    type Future = BoxFuture<Self::Response, Self::Error>;
+
+    fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: tonic::Request<M>) -> Self::Future {
+        let response = (self.0)(request.into_inner());
+        Box::pin(async move {
+            response
+                .await
+                .map(tonic::Response::new)
+                .map_err(Status::from)
+        })
+    }
+}
+
+/// Decode the request of a unary call, let `method` handle it and encode its response
+fn unary<B, M, R, F, Fut>(
+    request: http::Request<B>,
+    method: F,
+) -> BoxFuture<http::Response<Body>, Infallible>
+where
+    B: HttpBody + Send + 'static,
+    B::Error: Into<StdError> + Send + 'static,
+    M: Message + Default + Send + 'static,
+    R: Message + Send + 'static,
+    F: FnMut(M) -> Fut + Send + 'static,
+    Fut: Future<Output = Result<R, GrpcError>> + Send + 'static,
+{
+    Box::pin(async move {
+        let mut grpc = Grpc::new(ProstCodec::<R, M>::default());
+        Ok(grpc.unary(Method(method), request).await)
+    })
+}
+
+/// The `inference.GRPCInferenceService`, scheduling the `ModelInfer` requests to the handler
+/// behind the `EndpointContext`
+pub struct InferenceService<I, O> {
+    context: Arc<EndpointContext<I, O>>,
+    model: Arc<ModelMetadata>,
+}
+
+impl<I, O> Clone for InferenceService<I, O> {
+    fn clone(&self) -> Self {
+        Self {
+            context: Arc::clone(&self.context),
+            model: Arc::clone(&self.model),
+        }
+    }
+}
+
+impl<I, O> InferenceService<I, O>
+where
+    I: FromInference + Send + 'static,
+    O: IntoInference + Send + 'static,
+{
+    pub fn new(context: EndpointContext<I, O>, model: ModelMetadata) -> Self {
+        Self {
+            context: Arc::new(context),
+            model: Arc::new(model),
+        }
+    }
+
+    fn check_model(&self, name: &str, version: &str) -> Result<(), GrpcError> {
+        if self.model.serves(name, version) {
+            Ok(())
+        } else if version.is_empty() {
+            Err(GrpcError::ModelNotFound(name.to_string()))
+        } else {
+            Err(GrpcError::ModelNotFound(format!("{name}:{version}")))
+        }
+    }
+
+    #[instrument(skip_all, fields(id = %request.id))]
+    async fn model_infer(
+        self,
+        request: ModelInferRequest,
+    ) -> Result<ModelInferResponse, GrpcError> {
+        self.check_model(&request.model_name, &request.model_version)?;
+        let request = InferenceRequest::try_from(request)?;
+        let (id, version, requested) = (
+            request.id.clone(),
+            request.model_version.clone(),
+            request.outputs.clone(),
+        );
+
+        // Dropping the call, i.e. when the client cancels it, cancels the request on the handler side
+        debug!("Scheduling inference request");
+        let response = self
+            .context
+            .schedule(I::from_inference(request)?)
+            .response()
+            .await?;
+
+        response
+            .into_inference()?
+            .into_proto(id, self.model.name(), version, &requested)
+    }
+}
+
+impl<I, O> NamedService for InferenceService<I, O> {
+    const NAME: &'static str = SERVICE_NAME;
+}
+
+impl<I, O, B> Service<http::Request<B>> for InferenceService<I, O>
+where
+    I: FromInference + Send + 'static,
+    O: IntoInference + Send + 'static,
+    B: HttpBody + Send + 'static,
+    B::Error: Into<StdError> + Send + 'static,
+{
+    type Response = http::Response<Body>;
+    type Error = Infallible;
+    type Future = BoxFuture<Self::Response, Self::Error>;
+
+    fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: http::Request<B>) -> Self::Future {
+        let method = request
+            .uri()
+            .path()
+            .strip_prefix(&format!("/{SERVICE_NAME}/"))
+            .unwrap_or_default()
+            .to_string();
+
+        let service = self.clone();
+        match method.as_str() {
+            "ServerLive" => unary(request, |_: ServerLiveRequest| async {
+                Ok(ServerLiveResponse { live: true })
+            }),
+            "ServerReady" => unary(request, |_: ServerReadyRequest| async {
+                let ready = hfendpoints_core::readiness().check().is_ok();
+                Ok(ServerReadyResponse { ready })
+            }),
+            "ServerMetadata" => unary(request, |_: ServerMetadataRequest| async {
+                Ok(ServerMetadataResponse {
+                    name: String::from(SERVER_NAME),
+                    version: String::from(env!("CARGO_PKG_VERSION")),
+                    extensions: Vec::new(),
+                })
+            }),
+            "ModelReady" => unary(request, move |request: ModelReadyRequest| {
+                let ready = service.check_model(&request.name, &request.version);
+                async move { ready.map(|_| ModelReadyResponse { ready: true }) }
+            }),
+            "ModelMetadata" => unary(request, move |request: ModelMetadataRequest| {
+                let metadata = service
+                    .check_model(&request.name, &request.version)
+                    .map(|_| service.model.to_proto());
+                async move { metadata }
+            }),
+            "ModelInfer" => unary(request, move |request: ModelInferRequest| {
+                service.clone().model_infer(request)
+            }),
+            _ => Box::pin(async move {
+                let status = Status::unimplemented(format!("Unknown method '{method}'"));
+                Ok(status.into_http())
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::inference::{InferenceRequest, InferenceResponse};
+    use crate::model::ModelMetadata;
+    use crate::proto::model_infer_request::InferInputTensor;
+    use crate::proto::*;
+    use crate::service::InferenceService;
+    use crate::tensor::{DataType, Tensor, TensorData};
+    use hfendpoints_core::{EndpointContext, Error, Handler, request_channel, wait_for_requests};
+    use prost::Message;
+    use std::sync::Arc;
+    use tonic::client::Grpc;
+    use tonic::codec::ProstCodec;
+    use tonic::codegen::http::uri::PathAndQuery;
+    use tonic::{Code, Status};
+
+    /// Double the elements of the `x` input
+    struct Doubler;
+
+    impl Handler for Doubler {
+        type Request = InferenceRequest;
+        type Response = InferenceResponse;
+
+        async fn on_request(&self, request: InferenceRequest) -> Result<InferenceResponse, Error> {
+            let x = request.input("x").unwrap();
+            let TensorData::Fp32(values) = x.data() else {
+                panic!("x should hold FP32 elements");
+            };
+
+            let y = values.iter().map(|value| value * 2.0).collect();
+            let y =
+                Tensor::new("y", DataType::Fp32, x.shape().to_vec(), TensorData::Fp32(y)).unwrap();
+            Ok(InferenceResponse::new(vec![y]))
+        }
+    }
+
+    fn service() -> InferenceService<InferenceRequest, InferenceResponse> {
+        let (sender, receiver) = request_channel(4);
+        tokio::spawn(wait_for_requests(receiver, Arc::new(Doubler)));
+
+        let model = ModelMetadata::new("doubler")
+            .with_input("x", DataType::Fp32, vec![-1])
+            .with_output("y", DataType::Fp32, vec![-1]);
+        InferenceService::new(EndpointContext::new(sender), model)
+    }
+
+    async fn call<M, R>(method: &'static str, request: M) -> Result<R, Status>
+    where
+        M: Message + Send + Sync + 'static,
+        R: Message + Default + Send + Sync + 'static,
+    {
+        let mut client = Grpc::new(service());
+        client.ready().await.unwrap();
+
+        let path = PathAndQuery::from_static(method);
+        let response = client
+            .unary(
+                tonic::Request::new(request),
+                path,
+                ProstCodec::<M, R>::default(),
+            )
+            .await?;
+        Ok(response.into_inner())
+    }
+
+    #[tokio::test]
+    async fn model_infer_reaches_the_handler() {
+        let request = ModelInferRequest {
+            model_name: String::from("doubler"),
+            id: String::from("req-1"),
+            inputs: vec![InferInputTensor {
+                name: String::from("x"),
+                datatype: String::from("FP32"),
+                shape: vec![2],
+                contents: Some(InferTensorContents {
+                    fp32_contents: vec![1.5, -2.0],
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        let response: ModelInferResponse = call(
+            "/inference.GRPCInferenceService/ModelInfer",
+            request.clone(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(response.id, "req-1");
+        assert_eq!(response.outputs[0].name, "y");
+        assert_eq!(
+            &response.raw_output_contents[0][..],
+            &[3.0f32.to_le_bytes(), (-4.0f32).to_le_bytes()].concat()
+        );
+
+        let request = ModelInferRequest {
+            model_name: String::from("unknown"),
+            ..request
+        };
+        let status =
+            call::<_, ModelInferResponse>("/inference.GRPCInferenceService/ModelInfer", request)
+                .await
+                .unwrap_err();
+        assert_eq!(status.code(), Code::NotFound);
+    }
+
+    #[tokio::test]
+    async fn health_and_metadata() {
+        let live: ServerLiveResponse = call(
+            "/inference.GRPCInferenceService/ServerLive",
+            ServerLiveRequest {},
+        )
+        .await
+        .unwrap();
+        assert!(live.live);
+
+        let request = ModelMetadataRequest {
+            name: String::from("doubler"),
+            version: String::new(),
+        };
+        let metadata: ModelMetadataResponse =
+            call("/inference.GRPCInferenceService/ModelMetadata", request)
+                .await
+                .unwrap();
+        assert_eq!(metadata.inputs[0].datatype, "FP32");
+
+        let status = call::<_, ServerLiveResponse>(
+            "/inference.GRPCInferenceService/Unknown",
+            ServerLiveRequest {},
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(status.code(), Code::Unimplemented);
+    }
+}
@@ -0,0 +1,438 @@
+use crate::error::GrpcError;
+use crate::proto::InferTensorContents;
+use half::f16;
+use prost::bytes::Bytes;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+/// Type of the elements of a tensor, as named by the v2 protocol
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DataType {
+    Bool,
+    Uint8,
+    Uint16,
+    Uint32,
+    Uint64,
+    Int8,
+    Int16,
+    Int32,
+    Int64,
+    Fp16,
+    Fp32,
+    Fp64,
+    Bytes,
+}
+
+const DATATYPES: [(DataType, &str); 13] = [
+    (DataType::Bool, "BOOL"),
+    (DataType::Uint8, "UINT8"),
+    (DataType::Uint16, "UINT16"),
+    (DataType::Uint32, "UINT32"),
+    (DataType::Uint64, "UINT64"),
+    (DataType::Int8, "INT8"),
+    (DataType::Int16, "INT16"),
+    (DataType::Int32, "INT32"),
+    (DataType::Int64, "INT64"),
+    (DataType::Fp16, "FP16"),
+    (DataType::Fp32, "FP32"),
+    (DataType::Fp64, "FP64"),
+    (DataType::Bytes, "BYTES"),
+];
+
+impl DataType {
+    /// Size in bytes of an element, `None` for `BYTES` elements which are variable-sized
+    pub fn size(&self) -> Option<usize> {
+        match self {
+            Self::Bool | Self::Uint8 | Self::Int8 => Some(1),
+            Self::Uint16 | Self::Int16 | Self::Fp16 => Some(2),
+            Self::Uint32 | Self::Int32 | Self::Fp32 => Some(4),
+            Self::Uint64 | Self::Int64 | Self::Fp64 => Some(8),
+            Self::Bytes => None,
+        }
+    }
+}
+
+impl FromStr for DataType {
+    type Err = GrpcError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        DATATYPES
+            .iter()
+            .find(|(_, name)| *name == s)
+            .map(|(datatype, _)| *datatype)
+            .ok_or_else(|| GrpcError::InvalidTensor(format!("unsupported datatype '{s}'")))
+    }
+}
+
+impl Display for DataType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let (_, name) = DATATYPES
+            .iter()
+            .find(|(datatype, _)| datatype == self)
+            .expect("every datatype is named");
+        f.write_str(name)
+    }
+}
+
+/// Elements of a tensor, flattened in row-major order.
+///
+/// Integers are widened to 64 bits whatever their datatype, and `FP16` elements are held as `f32`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum TensorData {
+    Bool(Vec<bool>),
+    Int(Vec<i64>),
+    Uint(Vec<u64>),
+    Fp32(Vec<f32>),
+    Fp64(Vec<f64>),
+    Bytes(Vec<Bytes>),
+}
+
+impl TensorData {
+    pub fn len(&self) -> usize {
+        match self {
+            Self::Bool(values) => values.len(),
+            Self::Int(values) => values.len(),
+            Self::Uint(values) => values.len(),
+            Self::Fp32(values) => values.len(),
+            Self::Fp64(values) => values.len(),
+            Self::Bytes(values) => values.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Whether the elements can be held by a tensor of `datatype`
+    fn fits(&self, datatype: DataType) -> bool {
+        let bits = datatype.size().unwrap_or_default() * 8;
+        match (self, datatype) {
+            (Self::Bool(_), DataType::Bool) => true,
+            (Self::Int(_), DataType::Int64) | (Self::Uint(_), DataType::Uint64) => true,
+            (Self::Int(values), DataType::Int8 | DataType::Int16 | DataType::Int32) => {
+                let bound = 1i64 << (bits - 1);
+                values.iter().all(|value| (-bound..bound).contains(value))
+            }
+            (Self::Uint(values), DataType::Uint8 | DataType::Uint16 | DataType::Uint32) => {
+                values.iter().all(|value| *value < 1u64 << bits)
+            }
+            (Self::Fp32(_), DataType::Fp16 | DataType::Fp32) => true,
+            (Self::Fp64(_), DataType::Fp64) => true,
+            (Self::Bytes(_), DataType::Bytes) => true,
+            _ => false,
+        }
+    }
+}
+
+/// Named tensor exchanged with the clients, either as input or output of the model
+#[derive(Clone, Debug, PartialEq)]
+pub struct Tensor {
+    name: String,
+    datatype: DataType,
+    shape: Vec<i64>,
+    data: TensorData,
+}
+
+/// Number of elements of a tensor of `shape`
+fn element_count(shape: &[i64]) -> Result<usize, GrpcError> {
+    shape.iter().try_fold(1usize, |count, dim| {
+        usize::try_from(*dim)
+            .ok()
+            .and_then(|dim| count.checked_mul(dim))
+            .ok_or_else(|| GrpcError::InvalidTensor(format!("invalid shape {shape:?}")))
+    })
+}
+
+impl Tensor {
+    /// Create the tensor `name`, checking `data` matches both `datatype` and `shape`
+    pub fn new(
+        name: impl Into<String>,
+        datatype: DataType,
+        shape: Vec<i64>,
+        data: TensorData,
+    ) -> Result<Self, GrpcError> {
+        let name = name.into();
+        if !data.fits(datatype) {
+            return Err(GrpcError::InvalidTensor(format!(
+                "{name}: elements do not fit the {datatype} datatype"
+            )));
+        }
+
+        let expected = element_count(&shape)?;
+        if data.len() != expected {
+            return Err(GrpcError::InvalidTensor(format!(
+                "{name}: shape {shape:?} holds {expected} elements but {} were provided",
+                data.len()
+            )));
+        }
+
+        Ok(Self {
+            name,
+            datatype,
+            shape,
+            data,
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn datatype(&self) -> DataType {
+        self.datatype
+    }
+
+    pub fn shape(&self) -> &[i64] {
+        &self.shape
+    }
+
+    pub fn data(&self) -> &TensorData {
+        &self.data
+    }
+
+    pub fn into_data(self) -> TensorData {
+        self.data
+    }
+
+    /// Read the tensor from the typed fields of the protocol
+    pub(crate) fn from_contents(
+        name: String,
+        datatype: DataType,
+        shape: Vec<i64>,
+        contents: InferTensorContents,
+    ) -> Result<Self, GrpcError> {
+        let data = match datatype {
+            DataType::Bool => TensorData::Bool(contents.bool_contents),
+            DataType::Int8 | DataType::Int16 | DataType::Int32 => {
+                TensorData::Int(contents.int_contents.into_iter().map(i64::from).collect())
+            }
+            DataType::Int64 => TensorData::Int(contents.int64_contents),
+            DataType::Uint8 | DataType::Uint16 | DataType::Uint32 => {
+                TensorData::Uint(contents.uint_contents.into_iter().map(u64::from).collect())
+            }
+            DataType::Uint64 => TensorData::Uint(contents.uint64_contents),
+            DataType::Fp16 => {
+                return Err(GrpcError::InvalidTensor(format!(
+                    "{name}: FP16 tensors must be sent as raw contents"
+                )));
+            }
+            DataType::Fp32 => TensorData::Fp32(contents.fp32_contents),
+            DataType::Fp64 => TensorData::Fp64(contents.fp64_contents),
+            DataType::Bytes => TensorData::Bytes(contents.bytes_contents),
+        };
+
+        Self::new(name, datatype, shape, data)
+    }
+
+    /// Read the tensor from its raw representation: little-endian elements, each `BYTES` element
+    /// being prefixed by its length as a 4 bytes little-endian integer
+    pub(crate) fn from_raw(
+        name: String,
+        datatype: DataType,
+        shape: Vec<i64>,
+        raw: &[u8],
+    ) -> Result<Self, GrpcError> {
+        let Some(size) = datatype.size() else {
+            let mut elements = Vec::new();
+            let mut remaining = raw;
+            while !remaining.is_empty() {
+                let element = remaining
+                    .split_first_chunk::<4>()
+                    .map(|(length, rest)| (u32::from_le_bytes(*length) as usize, rest))
+                    .and_then(|(length, rest)| {
+                        (rest.len() >= length).then(|| rest.split_at(length))
+                    });
+                let Some((element, rest)) = element else {
+                    return Err(GrpcError::InvalidTensor(format!(
+                        "{name}: truncated BYTES element in raw contents"
+                    )));
+                };
+
+                elements.push(Bytes::copy_from_slice(element));
+                remaining = rest;
+            }
+            return Self::new(name, datatype, shape, TensorData::Bytes(elements));
+        };
+
+        if !raw.len().is_multiple_of(size) {
+            return Err(GrpcError::InvalidTensor(format!(
+                "{name}: raw contents of {} bytes are not a whole number of {datatype} elements",
+                raw.len()
+            )));
+        }
+
+        let elements = raw.chunks_exact(size);
+        let data = match datatype {
+            DataType::Bool => TensorData::Bool(elements.map(|element| element[0] != 0).collect()),
+            DataType::Int8 => {
+                TensorData::Int(elements.map(|e| i8::from_le_bytes([e[0]]) as i64).collect())
+            }
+            DataType::Int16 => TensorData::Int(
+                elements
+                    .map(|e| i16::from_le_bytes([e[0], e[1]]) as i64)
+                    .collect(),
+            ),
+            DataType::Int32 => TensorData::Int(
+                elements
+                    .map(|e| i32::from_le_bytes(e.try_into().unwrap()) as i64)
+                    .collect(),
+            ),
+            DataType::Int64 => TensorData::Int(
+                elements
+                    .map(|e| i64::from_le_bytes(e.try_into().unwrap()))
+                    .collect(),
+            ),
+            DataType::Uint8 => TensorData::Uint(elements.map(|e| e[0] as u64).collect()),
+            DataType::Uint16 => TensorData::Uint(
+                elements
+                    .map(|e| u16::from_le_bytes([e[0], e[1]]) as u64)
+                    .collect(),
+            ),
+            DataType::Uint32 => TensorData::Uint(
+                elements
+                    .map(|e| u32::from_le_bytes(e.try_into().unwrap()) as u64)
+                    .collect(),
+            ),
+            DataType::Uint64 => TensorData::Uint(
+                elements
+                    .map(|e| u64::from_le_bytes(e.try_into().unwrap()))
+                    .collect(),
+            ),
+            DataType::Fp16 => TensorData::Fp32(
+                elements
+                    .map(|e| f16::from_le_bytes([e[0], e[1]]).to_f32())
+                    .collect(),
+            ),
+            DataType::Fp32 => TensorData::Fp32(
+                elements
+                    .map(|e| f32::from_le_bytes(e.try_into().unwrap()))
+                    .collect(),
+            ),
+            DataType::Fp64 => TensorData::Fp64(
+                elements
+                    .map(|e| f64::from_le_bytes(e.try_into().unwrap()))
+                    .collect(),
+            ),
+            DataType::Bytes => unreachable!("BYTES elements are variable-sized"),
+        };
+
+        Self::new(name, datatype, shape, data)
+    }
+
+    /// Raw representation of the tensor, see [`Tensor::from_raw`]
+    pub(crate) fn to_raw(&self) -> Bytes {
+        let size = self.datatype.size().unwrap_or(4);
+        let mut raw = Vec::with_capacity(self.data.len() * size);
+        match (&self.data, self.datatype) {
+            (TensorData::Bool(values), _) => raw.extend(values.iter().map(|value| *value as u8)),
+            (TensorData::Int(values), _) => {
+                for value in values {
+                    raw.extend_from_slice(&value.to_le_bytes()[..size]);
+                }
+            }
+            (TensorData::Uint(values), _) => {
+                for value in values {
+                    raw.extend_from_slice(&value.to_le_bytes()[..size]);
+                }
+            }
+            (TensorData::Fp32(values), DataType::Fp16) => {
+                for value in values {
+                    raw.extend_from_slice(&f16::from_f32(*value).to_le_bytes());
+                }
+            }
+            (TensorData::Fp32(values), _) => {
+                for value in values {
+                    raw.extend_from_slice(&value.to_le_bytes());
+                }
+            }
+            (TensorData::Fp64(values), _) => {
+                for value in values {
+                    raw.extend_from_slice(&value.to_le_bytes());
+                }
+            }
+            (TensorData::Bytes(values), _) => {
+                for value in values {
+                    raw.extend_from_slice(&(value.len() as u32).to_le_bytes());
+                    raw.extend_from_slice(value);
+                }
+            }
+        }
+        Bytes::from(raw)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::proto::InferTensorContents;
+    use crate::tensor::{DataType, Tensor, TensorData};
+    use prost::bytes::Bytes;
+
+    #[test]
+    fn raw_contents_roundtrip() {
+        let tensors = [
+            Tensor::new(
+                "ids",
+                DataType::Int32,
+                vec![1, 3],
+                TensorData::Int(vec![-1, 0, 70000]),
+            ),
+            Tensor::new(
+                "mask",
+                DataType::Uint8,
+                vec![2],
+                TensorData::Uint(vec![0, 255]),
+            ),
+            Tensor::new(
+                "scores",
+                DataType::Fp16,
+                vec![2],
+                TensorData::Fp32(vec![0.5, -2.0]),
+            ),
+            Tensor::new(
+                "text",
+                DataType::Bytes,
+                vec![2],
+                TensorData::Bytes(vec![Bytes::from_static(b"Hello"), Bytes::new()]),
+            ),
+        ];
+
+        for tensor in tensors {
+            let tensor = tensor.unwrap();
+            let raw = tensor.to_raw();
+            let decoded = Tensor::from_raw(
+                tensor.name().to_string(),
+                tensor.datatype(),
+                tensor.shape().to_vec(),
+                &raw,
+            )
+            .unwrap();
+            assert_eq!(decoded, tensor);
+        }
+    }
+
+    #[test]
+    fn reject_mismatching_tensors() {
+        // Out of range, wrong element count, wrong element type
+        assert!(Tensor::new("x", DataType::Int8, vec![1], TensorData::Int(vec![128])).is_err());
+        assert!(Tensor::new("x", DataType::Fp32, vec![2, 2], TensorData::Fp32(vec![1.0])).is_err());
+        assert!(Tensor::new("x", DataType::Fp64, vec![1], TensorData::Fp32(vec![1.0])).is_err());
+        assert!(Tensor::new("x", DataType::Bool, vec![-1], TensorData::Bool(vec![])).is_err());
+
+        // Truncated BYTES element
+        let raw = [5, 0, 0, 0, b'H', b'i'];
+        assert!(Tensor::from_raw(String::from("x"), DataType::Bytes, vec![1], &raw).is_err());
+
+        // FP16 has no typed contents
+        let contents = InferTensorContents::default();
+        assert!(
+            Tensor::from_contents(String::from("x"), DataType::Fp16, vec![0], contents).is_err()
+        );
+    }
+
+    #[test]
+    fn parse_datatypes() {
+        assert_eq!("FP32".parse::<DataType>().unwrap(), DataType::Fp32);
+        assert_eq!(DataType::Uint16.to_string(), "UINT16");
+        assert!("BF16".parse::<DataType>().is_err());
+    }
+}
@@ -1,3 +1,4 @@
+from argparse import ArgumentParser
 from typing import Protocol, TypeVar, runtime_checkable
 
 from .config import EndpointConfig, ensure_supported_architectures
@@ -8,7 +9,44 @@ Response = TypeVar("Response", infer_variance=True)
 
 @runtime_checkable
 class Handler(Protocol[Request, Response]):
+    # Version of the handler protocol targeted by this handler, checked by the runtime at startup
+    __protocol_version__: int = 1
 
     def __init__(self, model_id_or_path: str): ...
 
+    # Either an `async def` returning a single response or an async generator yielding
+    # incremental responses (i.e. streamed transcription events).
+    # A plain `def` is supported as well and runs on a dedicated thread, leaving the event loop free,
+    # generators it returns being iterated on that thread as well.
+    # Chat and transcription handlers may yield plain strings, the text generated since the previous one
     def __call__(self, request: Request, ctx) -> Response: ...
+
+    # Handlers may also define `__call_batch__(self, requests: list[tuple[Request, ctx]]) -> list[Response]`,
+    # invoked on a dedicated thread (or awaited when `async def`) with up to `HFENDPOINT_MAX_BATCH_SIZE` queued
+    # requests at once instead of calling `__call__` once per request, answering each of them with a single response.
+    # Raising fails every request of the batch
+
+    # Handlers may also define `def info(self) -> hfendpoints.openai.ModelInfo` describing the loaded model,
+    # advertised on `/models` and `/info` and bounding the accepted requests (languages, voices, tokens)
+
+
+def main():
+    """
+    Entry point of the `hfendpoints` command line
+    """
+    from . import doctor, snapshot
+
+    parser = ArgumentParser(prog="hfendpoints")
+    commands = parser.add_subparsers(dest="command", required=True)
+    doctor.add_arguments(
+        commands.add_parser("doctor", help="Validate the environment the endpoint is deployed in")
+    )
+    snapshot.add_arguments(
+        commands.add_parser("snapshot", help="Export or verify the signed manifest of an endpoint")
+    )
+
+    args = parser.parse_args()
+    if args.command == "doctor":
+        raise SystemExit(doctor.run(args))
+    if args.command == "snapshot":
+        raise SystemExit(snapshot.run(args))
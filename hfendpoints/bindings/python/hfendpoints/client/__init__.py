@@ -0,0 +1 @@
+from hfendpoints._hfendpoints.client import Client
@@ -0,0 +1,48 @@
+from typing import Optional, Union
+
+from hfendpoints.openai.audio import Transcription, VerboseTranscription
+
+class Client:
+    """
+    Async client for endpoints built with hfendpoints, requests are executed natively
+    """
+
+    def __init__(
+        self, base_url: str, max_retries: int = 3, backoff: float = 0.25
+    ) -> None:
+        """
+        :param base_url: Base url of the endpoint (i.e. http://localhost:8000)
+        :param max_retries: Maximum number of retries after a transient failure
+        :param backoff: Delay, in seconds, before the first retry, doubled on every subsequent attempt
+        """
+        ...
+
+    async def health(self) -> bool:
+        """
+        Check whether the endpoint reports itself as healthy
+        :return: `True` if the endpoint is healthy
+        """
+        ...
+
+    async def transcribe(
+        self,
+        file: bytes,
+        file_name: str,
+        language: Optional[str] = None,
+        prompt: Optional[str] = None,
+        temperature: Optional[float] = None,
+        response_format: str = "json",
+        allow_code_switching: bool = False,
+    ) -> Union[str, Transcription, VerboseTranscription]:
+        """
+        Transcribe the provided audio content
+        :param file: Raw content of the audio file
+        :param file_name: Name of the audio file
+        :param language: Language of the audio, in ISO-639-1 format
+        :param prompt: Optional text to guide the model's style
+        :param temperature: Sampling temperature, between 0 and 1
+        :param response_format: One of 'json', 'text' or 'verbose_json'
+        :param allow_code_switching: Report the language of every segment for audio mixing languages
+        :return: The transcription matching the requested `response_format`
+        """
+        ...
@@ -1,6 +1,6 @@
 import os
-from dataclasses import dataclass
-from typing import TYPE_CHECKING, Iterable
+from dataclasses import dataclass, field
+from typing import TYPE_CHECKING, Iterable, List, Optional
 
 from .errors import UnsupportedModelArchitecture
 
@@ -23,17 +23,42 @@ class EndpointConfig:
     # Model to use for this endpoint
     model_id: str
 
+    # Maximum size, in bytes, of the request bodies, unbounded if `None`
+    body_limit: Optional[int] = None
+
+    # Maximum number of requests queued or processed by the handler
+    queue_capacity: int = 128
+
+    # Maximum number of requests processed at once, probes, metrics and administration requests aside
+    max_concurrent_requests: int = 512
+
+    # Seconds a request may take before failing with a timeout, never if `None`
+    request_timeout: Optional[float] = None
+
+    # Seconds in-flight requests are given to complete once the process is asked to terminate
+    shutdown_grace_period: float = 30.0
+
+    # API keys accepted by the endpoint, authentication being disabled if empty
+    api_keys: List[str] = field(default_factory=list, repr=False)
+
+    # Format of the logs, either `text` or `json`
+    log_format: str = "text"
+
     @staticmethod
     def from_env() -> "EndpointConfig":
         """
-        Parse the operating system environment variables to retrieve Inference Endpoints defined variables
+        Resolve the settings of the endpoint from the TOML or YAML file referenced by `HFENDPOINT_CONFIG` (if any)
+        and the Inference Endpoints defined variables, which take precedence, once the environment of the manifest
+        referenced by `HFENDPOINT_SNAPSHOT` (if any) is restored. The server resolves the same settings when served
         :return:
+        :raises ValueError if the configuration file cannot be read, is malformed or defines unknown settings
         """
-        return EndpointConfig(
-            interface=os.environ.get("INTERFACE", "0.0.0.0"),
-            port=int(os.environ.get("PORT", 8000)),
-            model_id=os.environ.get("MODEL_ID", "/repository"),
-        )
+        from hfendpoints._hfendpoints.openai import load_config
+
+        from .snapshot import restore
+
+        restore()
+        return EndpointConfig(model_id=os.environ.get("MODEL_ID", "/repository"), **load_config())
 
 
 def ensure_supported_architectures(
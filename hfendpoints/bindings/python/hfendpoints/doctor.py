@@ -0,0 +1,497 @@
+import asyncio
+import importlib
+import importlib.util
+import inspect
+import io
+import json
+import os
+import shutil
+import socket
+import subprocess
+import wave
+from argparse import ArgumentParser, Namespace
+from dataclasses import dataclass
+from pathlib import Path
+from types import SimpleNamespace
+from typing import Any, Callable, List, Optional
+from urllib.error import HTTPError, URLError
+from urllib.request import Request, urlopen
+
+from .config import EndpointConfig
+
+OK = "ok"
+WARNING = "warning"
+ERROR = "error"
+SKIPPED = "skipped"
+
+
+def _is_payload_capture(value: str) -> bool:
+    value = value.strip()
+    if value in ("full", "off", "hashed"):
+        return True
+
+    if value.startswith("sampled:"):
+        try:
+            return 0 <= float(value[len("sampled:") :]) <= 1
+        except ValueError:
+            return False
+    return False
+
+
+# Environment variables read by the runtime, along with the validation applied to their value
+_RUNTIME_VARIABLES = {
+    "PORT": (int, lambda value: 0 < value < 65536, "a port number"),
+    "HFENDPOINT_CONFIG": (str, os.path.isfile, "an existing .toml, .yaml or .yml file"),
+    "HFENDPOINT_BODY_LIMIT": (int, lambda value: value >= 0, "a number of bytes"),
+    "HFENDPOINT_LOG_FORMAT": (str, lambda value: value.lower() in ("text", "json"), "text or json"),
+    "HFENDPOINT_QUEUE_CAPACITY": (int, lambda value: value > 0, "a positive integer"),
+    "HFENDPOINT_MAX_CONCURRENT_REQUESTS": (int, lambda value: value > 0, "a positive integer"),
+    "HFENDPOINT_READY_QUEUE_THRESHOLD": (int, lambda value: value > 0, "a positive integer"),
+    "HFENDPOINT_REQUEST_TIMEOUT": (float, lambda value: value > 0, "a positive number of seconds"),
+    "HFENDPOINT_SHUTDOWN_GRACE_PERIOD": (int, lambda value: value >= 0, "a number of seconds"),
+    "HFENDPOINT_WORKERS": (int, lambda value: value > 0, "a positive integer"),
+    "HFENDPOINT_MAX_BATCH_SIZE": (int, lambda value: value > 0, "a positive integer"),
+    "HFENDPOINT_PREEMPTION_SLICE": (float, lambda value: value > 0, "a positive number of seconds"),
+    "HFENDPOINT_PREEMPTION_RUNNING": (int, lambda value: value > 0, "a positive integer"),
+    "HFENDPOINT_AUDIO_SAMPLE_RATE": (int, lambda value: value > 0, "a positive sample rate in Hz"),
+    "HFENDPOINT_AUDIO_PROMPT_LIMIT": (int, lambda value: value > 0, "a positive number of characters"),
+    "HFENDPOINT_AUDIO_PROMPT_OVERFLOW": (str, lambda value: value in ("truncate", "reject"), "truncate or reject"),
+    "HFENDPOINT_LOUDNESS_TARGET": (float, lambda value: -70 <= value <= 0, "a loudness in LUFS between -70 and 0"),
+    "HFENDPOINT_JOURNAL_DIR": (str, lambda value: bool(value.strip()), "a directory path"),
+    "HFENDPOINT_JOBS_DIR": (str, lambda value: bool(value.strip()), "a directory path"),
+    "HFENDPOINT_PAYLOAD_CAPTURE": (str, _is_payload_capture, "full, off, hashed or sampled:<ratio>"),
+    "HFENDPOINT_USAGE_SINK": (
+        str,
+        lambda value: "://" not in value or value.startswith(("http://", "https://", "file://")),
+        "stdout, an http(s):// webhook or a file path",
+    ),
+    "HFENDPOINT_OIDC_ISSUER": (str, lambda value: value.startswith("https://"), "an https:// issuer URL"),
+    "HFENDPOINT_OIDC_AUDIENCE": (str, lambda value: bool(value.strip(" ,")), "comma-separated audiences"),
+    "HFENDPOINT_OIDC_JWKS_URI": (
+        str,
+        lambda value: value.startswith(("https://", "http://", "file://")),
+        "an http(s):// or file:// URI",
+    ),
+    "HFENDPOINT_OIDC_TENANT_CLAIM": (str, lambda value: bool(value.strip()), "a claim name"),
+}
+
+
+@dataclass
+class Diagnostic:
+    """
+    Outcome of a single check performed by `hfendpoints doctor`
+    """
+
+    # Name of the check
+    name: str
+
+    # One of `ok`, `warning`, `error` or `skipped`
+    status: str
+
+    # What was found
+    detail: str
+
+    # What to do about it, if anything
+    hint: Optional[str] = None
+
+
+def check_config() -> Diagnostic:
+    """
+    Parse the endpoint configuration and the runtime environment variables
+    """
+    try:
+        config = EndpointConfig.from_env()
+    except ValueError as e:
+        return Diagnostic(
+            "config",
+            ERROR,
+            f"Failed to parse the configuration: {e}",
+            "Fix the file referenced by HFENDPOINT_CONFIG, unknown settings are rejected",
+        )
+    except ImportError as e:
+        return Diagnostic("config", ERROR, f"Failed to load the native extension: {e}")
+
+    malformed = []
+    for name, (parse, valid, expected) in _RUNTIME_VARIABLES.items():
+        if (value := os.environ.get(name)) is None:
+            continue
+        try:
+            if not valid(parse(value)):
+                raise ValueError(value)
+        except ValueError:
+            malformed.append(f"{name}={value!r} (expected {expected})")
+
+    detail = f"Serving {config.model_id} on {config.interface}:{config.port}"
+    if malformed:
+        return Diagnostic(
+            "config",
+            WARNING,
+            f"{detail}, ignoring malformed variables: {', '.join(malformed)}",
+            "The runtime falls back to the defaults for these variables, fix or unset them",
+        )
+    return Diagnostic("config", OK, detail)
+
+
+def check_port(interface: str, port: int) -> Diagnostic:
+    """
+    Ensure the endpoint is able to listen on `interface:port`
+    """
+    if os.environ.get("LISTEN_FDS"):
+        return Diagnostic("port", SKIPPED, "Listening socket is inherited through socket activation (LISTEN_FDS)")
+
+    family = socket.AF_INET6 if ":" in interface else socket.AF_INET
+    with socket.socket(family, socket.SOCK_STREAM) as listener:
+        # On Windows SO_REUSEADDR lets the socket bind a port already in use, hiding the conflict
+        if os.name != "nt":
+            listener.setsockopt(socket.SOL_SOCKET, socket.SO_REUSEADDR, 1)
+        try:
+            listener.bind((interface, port))
+        except OSError as e:
+            return Diagnostic(
+                "port",
+                ERROR,
+                f"Cannot listen on {interface}:{port}: {e.strerror}",
+                "Stop the process using this port or pick another one through PORT / INTERFACE",
+            )
+    return Diagnostic("port", OK, f"{interface}:{port} is available")
+
+
+def check_runtime() -> Diagnostic:
+    """
+    Ensure the native extension of hfendpoints can be loaded
+    """
+    try:
+        native = importlib.import_module("hfendpoints._hfendpoints")
+    except ImportError as e:
+        return Diagnostic(
+            "runtime",
+            ERROR,
+            f"Failed to load the native extension: {e}",
+            "Reinstall hfendpoints with a wheel matching this platform and Python version",
+        )
+    return Diagnostic(
+        "runtime",
+        OK,
+        f"hfendpoints {native.__version__} (handler protocol v{native.__handler_protocol_version__})",
+    )
+
+
+def load_handler(spec: str) -> Any:
+    """
+    Import the handler referenced by `spec`, formatted as `module:attribute` (or `path/to/file.py:attribute`)
+    """
+    module_name, _, attribute = spec.partition(":")
+    if not attribute:
+        raise ValueError(f"Expected 'module:attribute', got '{spec}'")
+
+    if module_name.endswith(".py"):
+        spec_from_path = importlib.util.spec_from_file_location(Path(module_name).stem, module_name)
+        if spec_from_path is None or spec_from_path.loader is None:
+            raise ImportError(f"Cannot load {module_name}")
+        module = importlib.util.module_from_spec(spec_from_path)
+        spec_from_path.loader.exec_module(module)
+    else:
+        module = importlib.import_module(module_name)
+
+    return getattr(module, attribute)
+
+
+def check_handler(spec: Optional[str]) -> Diagnostic:
+    """
+    Import the handler and check it implements a supported version of the handler protocol
+    """
+    if spec is None:
+        return Diagnostic("handler", SKIPPED, "No handler provided", "Pass --handler module:HandlerClass to check it")
+
+    try:
+        handler = load_handler(spec)
+    except Exception as e:
+        return Diagnostic(
+            "handler",
+            ERROR,
+            f"Failed to import {spec}: {type(e).__name__}: {e}",
+            "Make sure the handler's module and its dependencies are installed and on PYTHONPATH",
+        )
+
+    if not callable(handler):
+        return Diagnostic("handler", ERROR, f"{spec} is not callable", "Handlers implement __call__(request, ctx)")
+
+    version = getattr(handler, "__protocol_version__", 1)
+    try:
+        supported = importlib.import_module("hfendpoints._hfendpoints").__handler_protocol_version__
+    except ImportError:
+        supported = None
+
+    if supported is not None and version > supported:
+        return Diagnostic(
+            "handler",
+            ERROR,
+            f"{spec} targets protocol v{version} but this runtime supports up to v{supported}",
+            "Upgrade hfendpoints to the version the handler was written for",
+        )
+    return Diagnostic("handler", OK, f"{spec} imported (protocol v{version})")
+
+
+def check_gpu() -> Diagnostic:
+    """
+    List the GPUs visible to the endpoint
+    """
+    visible = os.environ.get("CUDA_VISIBLE_DEVICES")
+    if visible is not None and not visible.strip():
+        return Diagnostic("gpu", WARNING, "CUDA_VISIBLE_DEVICES is empty, no GPU is visible")
+
+    if (nvidia_smi := shutil.which("nvidia-smi")) is not None:
+        try:
+            listed = subprocess.run([nvidia_smi, "-L"], capture_output=True, text=True, timeout=10)
+        except (OSError, subprocess.TimeoutExpired) as e:
+            return Diagnostic("gpu", WARNING, f"nvidia-smi failed: {e}", "Check the NVIDIA driver installation")
+
+        if listed.returncode != 0:
+            return Diagnostic(
+                "gpu",
+                WARNING,
+                f"nvidia-smi failed: {listed.stderr.strip() or listed.stdout.strip()}",
+                "Check the NVIDIA driver is loaded and the container runs with GPU access",
+            )
+
+        gpus = [line for line in listed.stdout.splitlines() if line.startswith("GPU")]
+        return Diagnostic("gpu", OK, f"{len(gpus)} GPU(s) visible: {'; '.join(gpus)}")
+
+    try:
+        import torch
+    except ImportError:
+        return Diagnostic("gpu", WARNING, "No GPU detected (nvidia-smi not found)", "Ignore this if the endpoint runs on CPU")
+
+    if torch.cuda.is_available():
+        return Diagnostic("gpu", OK, f"{torch.cuda.device_count()} GPU(s) visible through torch")
+
+    # Apple Silicon GPUs are exposed through Metal Performance Shaders rather than CUDA
+    mps = getattr(torch.backends, "mps", None)
+    if mps is not None and mps.is_available():
+        return Diagnostic("gpu", OK, "Apple Silicon GPU visible through torch (mps)")
+    return Diagnostic("gpu", WARNING, "torch does not see any GPU", "Ignore this if the endpoint runs on CPU")
+
+
+def _hub_token() -> Optional[str]:
+    for name in ("HF_TOKEN", "HUGGING_FACE_HUB_TOKEN"):
+        if token := os.environ.get(name):
+            return token.strip()
+
+    hf_home = Path(os.environ.get("HF_HOME", Path.home() / ".cache" / "huggingface"))
+    token_path = Path(os.environ.get("HF_TOKEN_PATH", hf_home / "token"))
+    if token_path.is_file():
+        return token_path.read_text().strip() or None
+    return None
+
+
+def check_hub_token() -> Diagnostic:
+    """
+    Validate the Hugging Face Hub token, if any, against the Hub
+    """
+    token = _hub_token()
+    if token is None:
+        return Diagnostic("hub token", SKIPPED, "No token found, only public models can be downloaded")
+
+    hub = os.environ.get("HF_ENDPOINT", "https://huggingface.co").rstrip("/")
+    request = Request(f"{hub}/api/whoami-v2", headers={"Authorization": f"Bearer {token}"})
+    try:
+        with urlopen(request, timeout=10) as response:
+            name = json.loads(response.read()).get("name", "unknown")
+    except HTTPError as e:
+        if e.code == 401:
+            return Diagnostic("hub token", ERROR, "The Hub rejected the token", "Generate a new token on the Hub and set HF_TOKEN")
+        return Diagnostic("hub token", WARNING, f"Failed to validate the token: HTTP {e.code}")
+    except (URLError, OSError) as e:
+        return Diagnostic("hub token", WARNING, f"Failed to reach {hub}: {e}", "Check the network access to the Hub")
+    return Diagnostic("hub token", OK, f"Authenticated as {name}")
+
+
+class _DryRunContext:
+    """
+    Stand-in for `hfendpoints.openai.Context` handed to the handler during the dry-run
+    """
+
+    request_id = "hfendpoints-doctor"
+    is_cancelled = False
+
+    async def checkpoint(self) -> bool:
+        return False
+
+
+class _DryRunAudio(SimpleNamespace):
+    """
+    Stand-in for a transcription request, exposing the audio through the buffer protocol
+    """
+
+    def __init__(self, audio: bytes, **kwargs):
+        super().__init__(**kwargs)
+        self._audio = audio
+
+    def __buffer__(self, flags: int) -> memoryview:
+        return memoryview(self._audio)
+
+
+def _silence(seconds: float = 1.0, rate: int = 16000) -> bytes:
+    buffer = io.BytesIO()
+    with wave.open(buffer, "wb") as wav:
+        wav.setnchannels(1)
+        wav.setsampwidth(2)
+        wav.setframerate(rate)
+        wav.writeframes(b"\x00\x00" * int(seconds * rate))
+    return buffer.getvalue()
+
+
+def _dry_run_request(task: str) -> Any:
+    if task == "embeddings":
+        return SimpleNamespace(
+            input=["Hello world"], model=None, encoding_format="float", dimensions=None, quantization=None, user=None
+        )
+    if task == "chat":
+        message = SimpleNamespace(role="user", content="Hello", name=None)
+        return SimpleNamespace(
+            messages=[message], model=None, max_completion_tokens=8, temperature=None, top_p=None, stop=[], seed=None
+        )
+
+    from .openai.audio import TranscriptionResponseKind
+
+    return _DryRunAudio(
+        _silence(),
+        language="en",
+        prompt=None,
+        temperature=0.0,
+        stream=False,
+        allow_code_switching=False,
+        response_kind=TranscriptionResponseKind.JSON,
+    )
+
+
+def _expected_response(task: str) -> type:
+    if task == "embeddings":
+        from .openai.embeddings import EmbeddingResponse
+
+        return EmbeddingResponse
+    if task == "chat":
+        from .openai.chat import ChatCompletionResponse
+
+        return ChatCompletionResponse
+
+    from .openai.audio import TranscriptionResponse
+
+    return TranscriptionResponse
+
+
+async def _first_response(called: Any) -> Any:
+    if inspect.isasyncgen(called):
+        try:
+            return await anext(called)
+        finally:
+            await called.aclose()
+    if inspect.isawaitable(called):
+        return await called
+    return called
+
+
+def check_dry_run(spec: Optional[str], task: Optional[str], model_id: str) -> Diagnostic:
+    """
+    Instantiate the handler for `model_id` and process a sample request of `task`
+    """
+    if spec is None or task is None:
+        return Diagnostic("dry-run", SKIPPED, "No handler or task provided", "Pass --handler and --task to run it")
+
+    try:
+        handler = load_handler(spec)
+        if inspect.isclass(handler):
+            handler = handler(model_id)
+    except Exception as e:
+        return Diagnostic(
+            "dry-run",
+            ERROR,
+            f"Failed to create the handler for {model_id}: {type(e).__name__}: {e}",
+            "Check MODEL_ID points to the model weights and the handler's __init__ succeeds on its own",
+        )
+
+    try:
+        request = _dry_run_request(task)
+    except ImportError as e:
+        return Diagnostic("dry-run", SKIPPED, f"Cannot create a sample {task} request: {e}")
+
+    try:
+        response = asyncio.run(_first_response(handler(request, _DryRunContext())))
+    except Exception as e:
+        return Diagnostic(
+            "dry-run",
+            ERROR,
+            f"Handler raised {type(e).__name__}: {e}",
+            "Run the handler outside of the endpoint with the same request to debug it",
+        )
+
+    try:
+        expected = _expected_response(task)
+    except ImportError:
+        return Diagnostic("dry-run", WARNING, f"Handler returned {type(response).__name__}, not checked")
+
+    if not isinstance(response, expected):
+        return Diagnostic(
+            "dry-run",
+            ERROR,
+            f"Handler returned {type(response).__name__}, expected {expected.__name__}",
+            f"Return an instance of {expected.__module__}.{expected.__name__} from __call__",
+        )
+    return Diagnostic("dry-run", OK, f"Handler answered a sample {task} request")
+
+
+def run_checks(args: Namespace) -> List[Diagnostic]:
+    """
+    Run every check, in order, according to the command line arguments
+    """
+    diagnostics = [check_config()]
+    try:
+        config = EndpointConfig.from_env()
+    except (ValueError, ImportError):
+        config = None
+
+    checks: List[Callable[[], Diagnostic]] = [
+        lambda: check_port(config.interface, config.port)
+        if config
+        else Diagnostic("port", SKIPPED, "Configuration is invalid"),
+        check_runtime,
+        lambda: check_handler(args.handler),
+        check_gpu,
+        check_hub_token,
+    ]
+    if not args.skip_dry_run:
+        checks.append(lambda: check_dry_run(args.handler, args.task, config.model_id if config else "/repository"))
+
+    diagnostics.extend(check() for check in checks)
+    return diagnostics
+
+
+def add_arguments(parser: ArgumentParser):
+    parser.add_argument("--handler", default=None, help="Handler to check, as 'module:HandlerClass'")
+    parser.add_argument(
+        "--task", default=None, choices=["embeddings", "chat", "transcription"], help="Task served by the handler"
+    )
+    parser.add_argument("--skip-dry-run", action="store_true", help="Do not load the model to process a sample request")
+
+
+def run(args: Namespace) -> int:
+    """
+    Print the diagnostics, returning a non-zero exit code if any check failed
+    """
+    diagnostics = run_checks(args)
+    for diagnostic in diagnostics:
+        print(f"[{diagnostic.status.upper():^7}] {diagnostic.name}: {diagnostic.detail}")
+        if diagnostic.hint and diagnostic.status != OK:
+            print(f"          -> {diagnostic.hint}")
+
+    return int(any(diagnostic.status == ERROR for diagnostic in diagnostics))
+
+
+def main():
+    parser = ArgumentParser(description="Validate the environment the endpoint is deployed in")
+    add_arguments(parser)
+    raise SystemExit(run(parser.parse_args()))
+
+
+if __name__ == "__main__":
+    main()
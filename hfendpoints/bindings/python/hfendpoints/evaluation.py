@@ -0,0 +1,244 @@
+import json
+import mimetypes
+import os
+import statistics
+import time
+import uuid
+from argparse import ArgumentParser
+from dataclasses import asdict, dataclass, field
+from typing import Callable, Iterable, List, Optional, Sequence
+from urllib.request import Request, urlopen
+
+
+@dataclass
+class EvaluationSample:
+    """
+    One entry of an evaluation dataset: an audio file and its reference transcript
+    """
+
+    # Path to the audio file to transcribe
+    audio: str
+
+    # Expected transcript for the audio file
+    reference: str
+
+
+@dataclass
+class EvaluationReport:
+    """
+    Aggregated results of an evaluation run
+    """
+
+    # Number of samples successfully transcribed
+    num_samples: int
+
+    # Number of samples which failed to be transcribed
+    num_failures: int
+
+    # Corpus-level word error rate
+    wer: float
+
+    # Corpus-level character error rate
+    cer: float
+
+    # Latency statistics, in seconds
+    latency_mean: float
+    latency_p50: float
+    latency_p90: float
+    latency_p99: float
+
+    # Audio paths which failed along with the reason
+    failures: List[str] = field(default_factory=list)
+
+
+def _edit_distance(reference: Sequence, hypothesis: Sequence) -> int:
+    """
+    Compute the Levenshtein distance between two sequences
+    :param reference: Reference sequence
+    :param hypothesis: Sequence to compare against the reference
+    :return: Minimal number of substitutions, insertions and deletions
+    """
+    previous = list(range(len(hypothesis) + 1))
+    for i, ref in enumerate(reference, start=1):
+        current = [i] + [0] * len(hypothesis)
+        for j, hyp in enumerate(hypothesis, start=1):
+            current[j] = min(
+                previous[j] + 1,
+                current[j - 1] + 1,
+                previous[j - 1] + (ref != hyp),
+            )
+        previous = current
+    return previous[-1]
+
+
+def _normalize(text: str) -> str:
+    return " ".join(text.lower().split())
+
+
+def word_error_rate(reference: str, hypothesis: str) -> float:
+    """
+    Compute the word error rate of `hypothesis` against `reference`
+    :param reference: Expected transcript
+    :param hypothesis: Transcript produced by the model
+    :return: Word error rate, 0.0 when both are empty
+    """
+    ref, hyp = _normalize(reference).split(), _normalize(hypothesis).split()
+    if not ref:
+        return float(len(hyp) > 0)
+    return _edit_distance(ref, hyp) / len(ref)
+
+
+def character_error_rate(reference: str, hypothesis: str) -> float:
+    """
+    Compute the character error rate of `hypothesis` against `reference`
+    :param reference: Expected transcript
+    :param hypothesis: Transcript produced by the model
+    :return: Character error rate, 0.0 when both are empty
+    """
+    ref, hyp = _normalize(reference), _normalize(hypothesis)
+    if not ref:
+        return float(len(hyp) > 0)
+    return _edit_distance(ref, hyp) / len(ref)
+
+
+def load_samples(path: str) -> List[EvaluationSample]:
+    """
+    Read a JSONL dataset where each line holds an `audio` path and a `reference` transcript.
+    Relative audio paths are resolved against the dataset's folder.
+    :param path: Path to the JSONL file
+    :return: List of samples
+    """
+    root = os.path.dirname(os.path.abspath(path))
+    samples = []
+    with open(path, encoding="utf-8") as dataset:
+        for line in dataset:
+            if not line.strip():
+                continue
+            entry = json.loads(line)
+            samples.append(
+                EvaluationSample(
+                    audio=os.path.join(root, entry["audio"]),
+                    reference=entry["reference"],
+                )
+            )
+    return samples
+
+
+def endpoint_transcriber(
+    url: str, language: Optional[str] = None
+) -> Callable[[str], str]:
+    """
+    Create a callable transcribing an audio file through a running endpoint
+    :param url: Base url of the endpoint (i.e. http://localhost:8000)
+    :param language: Optional language forwarded to the endpoint
+    :return: Callable taking an audio path and returning the transcribed text
+    """
+    target = f"{url.rstrip('/')}/api/v1/audio/transcriptions"
+
+    def transcribe(audio: str) -> str:
+        boundary = uuid.uuid4().hex
+        content_type = mimetypes.guess_type(audio)[0] or "application/octet-stream"
+        with open(audio, "rb") as f:
+            content = f.read()
+
+        fields = [("response_format", "text")]
+        if language:
+            fields.append(("language", language))
+
+        body = b"".join(
+            f'--{boundary}\r\nContent-Disposition: form-data; name="{name}"\r\n\r\n{value}\r\n'.encode()
+            for name, value in fields
+        )
+        body += (
+            f'--{boundary}\r\nContent-Disposition: form-data; name="file"; filename="{os.path.basename(audio)}"\r\n'
+            f"Content-Type: {content_type}\r\n\r\n"
+        ).encode()
+        body += content + f"\r\n--{boundary}--\r\n".encode()
+
+        request = Request(
+            target,
+            data=body,
+            headers={"Content-Type": f"multipart/form-data; boundary={boundary}"},
+        )
+        with urlopen(request) as response:
+            return response.read().decode("utf-8")
+
+    return transcribe
+
+
+def _percentile(values: List[float], q: float) -> float:
+    if not values:
+        return 0.0
+    ordered = sorted(values)
+    return ordered[min(len(ordered) - 1, int(round(q * (len(ordered) - 1))))]
+
+
+def evaluate(
+    samples: Iterable[EvaluationSample], transcribe: Callable[[str], str]
+) -> EvaluationReport:
+    """
+    Run every sample through `transcribe` and compute corpus-level WER/CER along with latency stats.
+    `transcribe` can either call a running endpoint (see `endpoint_transcriber`) or a model directly.
+    :param samples: Samples to evaluate
+    :param transcribe: Callable taking an audio path and returning the transcribed text
+    :return: `EvaluationReport` summarizing the run
+    """
+    word_errors, words, char_errors, chars = 0, 0, 0, 0
+    latencies, failures = [], []
+
+    for sample in samples:
+        start = time.perf_counter()
+        try:
+            hypothesis = transcribe(sample.audio)
+        except Exception as e:
+            failures.append(f"{sample.audio}: {e}")
+            continue
+        latencies.append(time.perf_counter() - start)
+
+        reference = _normalize(sample.reference)
+        hypothesis = _normalize(hypothesis)
+        word_errors += _edit_distance(reference.split(), hypothesis.split())
+        words += len(reference.split())
+        char_errors += _edit_distance(reference, hypothesis)
+        chars += len(reference)
+
+    return EvaluationReport(
+        num_samples=len(latencies),
+        num_failures=len(failures),
+        wer=word_errors / words if words else 0.0,
+        cer=char_errors / chars if chars else 0.0,
+        latency_mean=statistics.fmean(latencies) if latencies else 0.0,
+        latency_p50=_percentile(latencies, 0.5),
+        latency_p90=_percentile(latencies, 0.9),
+        latency_p99=_percentile(latencies, 0.99),
+        failures=failures,
+    )
+
+
+def main():
+    parser = ArgumentParser(
+        description="Evaluate transcription quality and latency of an endpoint"
+    )
+    parser.add_argument(
+        "dataset", help="JSONL file with 'audio' and 'reference' entries"
+    )
+    parser.add_argument(
+        "--endpoint", default="http://localhost:8000", help="Endpoint base url"
+    )
+    parser.add_argument("--language", default=None, help="Language of the audio")
+    parser.add_argument("--output", default=None, help="Write the JSON report here")
+    args = parser.parse_args()
+
+    report = evaluate(
+        load_samples(args.dataset), endpoint_transcriber(args.endpoint, args.language)
+    )
+    serialized = json.dumps(asdict(report), indent=2)
+
+    if args.output:
+        with open(args.output, "w", encoding="utf-8") as f:
+            f.write(serialized)
+    print(serialized)
+
+
+if __name__ == "__main__":
+    main()
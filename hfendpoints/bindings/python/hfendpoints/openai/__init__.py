@@ -1 +1,33 @@
-from hfendpoints._hfendpoints.openai import Context, run
+import logging
+import traceback
+from typing import Any, Callable
+
+from hfendpoints._hfendpoints.openai import (
+    Context,
+    ModelCard,
+    ModelInfo,
+    MultiTaskEndpoint,
+    RequestContext,
+    run,
+    run_unavailable,
+)
+
+
+def serve(endpoint_factory: Callable[[], Any], interface: str, port: int):
+    """
+    Create the endpoint through `endpoint_factory` and serve it on `interface:port`.
+    If the endpoint fails to be created (import error, missing weights, ...) the HTTP server is still started
+    in degraded mode: `/health` reports the endpoint as unavailable, `/info` exposes the kind and message of
+    the exception and every task route answers 503, instead of the process exiting. The traceback is only logged.
+    :param endpoint_factory: Callable creating the endpoint (and the underlying handler)
+    :param interface: Interface the endpoint will be listening to incoming requests
+    :param port: Port on the interface the endpoint will be listening to incoming requests
+    """
+    try:
+        endpoint = endpoint_factory()
+    except Exception as exc:
+        logging.getLogger(__name__).exception("Failed to create endpoint")
+        reason = "".join(traceback.format_exception_only(type(exc), exc)).strip()
+        run_unavailable(reason, interface, port)
+    else:
+        run(endpoint, interface, port)
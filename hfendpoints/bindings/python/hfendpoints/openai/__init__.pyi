@@ -1,3 +1,62 @@
+from typing import Any, Dict, List, Optional, Tuple
+
+class RequestContext:
+    """
+    Metadata of the request received, for logging and correlation purposes
+    """
+
+    @property
+    def request_id(self) -> str:
+        """
+        :return: (`str`) Request's id, as sent back in the `x-request-id` header
+        """
+        ...
+
+    @property
+    def received_at(self) -> float:
+        """
+        :return: (`float`) Seconds since the epoch at which the request was received, as `time.time()`
+        """
+        ...
+
+    @property
+    def headers(self) -> Dict[str, str]:
+        """
+        Headers of the request handed over to the handler: `user-agent`, `accept-language`, the `x-forwarded-*` ones,
+        `x-real-ip`, `traceparent` and `tracestate`. Credentials are never handed over
+        :return: (`Dict[str, str]`) Header values by lowercase name, repeated ones joined with commas
+        """
+        ...
+
+    @property
+    def remote_addr(self) -> Optional[Tuple[str, int]]:
+        """
+        :return: (`Optional[Tuple[str, int]]`) Host and port of the client, or of the proxy in front of the endpoint
+        """
+        ...
+
+    @property
+    def subject(self) -> Optional[str]:
+        """
+        :return: (`Optional[str]`) Subject of the token the request was authenticated with, if any
+        """
+        ...
+
+    @property
+    def tenant(self) -> Optional[str]:
+        """
+        :return: (`Optional[str]`) Tenant of the caller, read from the claim set through `HFENDPOINT_OIDC_TENANT_CLAIM`
+        """
+        ...
+
+    @property
+    def metadata(self) -> Optional[str]:
+        """
+        :return: (`Optional[str]`) JSON attached by the caller through the `x-metadata` header or the `metadata` field
+                 of the body, echoed back along the response
+        """
+        ...
+
 class Context:
     """ """
 
@@ -8,3 +67,164 @@ class Context:
         :return: (`str`) Request's id
         """
         ...
+
+    @property
+    def request(self) -> RequestContext:
+        """
+        Metadata of the current request
+        :return: (`RequestContext`) Request's id, reception time, headers and client address
+        """
+        ...
+
+    @property
+    def is_cancelled(self) -> bool:
+        """
+        Whether the outcome of the request is not awaited anymore, because the client went away
+        or the request exceeded `HFENDPOINT_REQUEST_TIMEOUT`. Long-running handlers may check it to abort early
+        :return: (`bool`) `True` once the request is cancelled
+        """
+        ...
+
+    async def checkpoint(self) -> bool:
+        """
+        Let other requests run if this one held its turn for longer than `HFENDPOINT_PREEMPTION_SLICE` seconds
+        while others are waiting, resuming once granted a turn back. Handlers streaming long generations
+        should await it regularly, i.e. between tokens. Returns immediately when preemption is disabled
+        :return: (`bool`) `True` if the request was paused
+        """
+        ...
+
+class ModelCard:
+    """
+    Model advertised on `/models`, provided to the endpoint through its `model` argument.
+    When omitted, the model referenced by the `MODEL_ID` environment variable is advertised
+    """
+
+    def __init__(
+        self,
+        id: str,
+        owned_by: Optional[str] = None,
+        created: Optional[int] = None,
+        revision: Optional[str] = None,
+    ): ...
+    @property
+    def id(self) -> str: ...
+    @property
+    def created(self) -> int: ...
+    @property
+    def owned_by(self) -> str: ...
+    @property
+    def revision(self) -> Optional[str]: ...
+
+class ModelInfo:
+    """
+    Model loaded by the handler, returned by its optional `info()` method. It completes the card advertised
+    on `/models` and `/info`, documents the OpenAPI specification and bounds the requests: languages, voices
+    and `max_completion_tokens` outside the reported ones are rejected. Constraints left unset are not enforced
+    """
+
+    def __init__(
+        self,
+        id: Optional[str] = None,
+        revision: Optional[str] = None,
+        task: Optional[str] = None,
+        context_length: Optional[int] = None,
+        max_output_tokens: Optional[int] = None,
+        languages: Optional[List[str]] = None,
+        voices: Optional[List[str]] = None,
+        dtype: Optional[str] = None,
+        device: Optional[str] = None,
+    ): ...
+    @property
+    def id(self) -> Optional[str]: ...
+    @property
+    def revision(self) -> Optional[str]: ...
+    @property
+    def task(self) -> Optional[str]: ...
+    @property
+    def context_length(self) -> Optional[int]: ...
+    @property
+    def max_output_tokens(self) -> Optional[int]: ...
+    @property
+    def languages(self) -> List[str]: ...
+    @property
+    def voices(self) -> List[str]: ...
+    @property
+    def dtype(self) -> Optional[str]: ...
+    @property
+    def device(self) -> Optional[str]: ...
+
+class MultiTaskEndpoint:
+    """
+    Endpoint serving the tasks of several endpoints on the same server, i.e. transcriptions and translations
+    for Whisper-like models. Each task keeps its own handler, workers and request queue
+    (`HFENDPOINT_QUEUE_NAME` suffixed with the position of the task in distributed mode)
+    """
+
+    def __init__(self, endpoints: List[object], model: Optional[ModelCard] = None):
+        """
+        :param endpoints: Endpoints whose tasks are served, i.e. `[AutomaticSpeechRecognitionEndpoint(handler), AudioTranslationEndpoint(handler)]`
+        :param model: Model card reported by the endpoint, the one of the first endpoint if omitted
+        """
+        ...
+
+def run(endpoint, interface: str, port: int) -> None:
+    """
+    Serve the provided endpoint on `interface:port`, blocking until the server stops.
+    When a listening socket is inherited through socket activation (`LISTEN_FDS`), it is used instead
+    On SIGTERM/SIGINT, in-flight requests are given `HFENDPOINT_SHUTDOWN_GRACE_PERIOD` seconds (30 by default) to complete
+    At most `HFENDPOINT_QUEUE_CAPACITY` requests (128 by default) are queued or processed at once, others are rejected with 429
+    At most `HFENDPOINT_MAX_CONCURRENT_REQUESTS` requests (512 by default) are processed at once, others are rejected
+    with 429, `/health`, `/metrics`, `/info` and `/admin` being served under a budget of their own so probes keep answering
+    `/health/ready` answers 503 while more than `HFENDPOINT_READY_QUEUE_THRESHOLD` requests (unset by default) are queued
+    Requests not completed within `HFENDPOINT_REQUEST_TIMEOUT` seconds (unset by default) fail with 504
+    Handlers defining `__call_batch__` are given up to `HFENDPOINT_MAX_BATCH_SIZE` queued requests (32 by default) per call
+    With `HFENDPOINT_PREEMPTION_SLICE` set, at most `HFENDPOINT_PREEMPTION_RUNNING` requests (1 by default) run at once
+    With `HFENDPOINT_USAGE_SINK` set (`stdout`, an `http(s)://` webhook or a file path), a JSON usage record is emitted per request
+    With `HFENDPOINT_JOURNAL_DIR` set, requests are journaled until answered, the ones interrupted by a crash reported failed on restart,
+    their payloads kept as received, or not at all, hashed or sampled as defined by `HFENDPOINT_PAYLOAD_CAPTURE` (`off`, `hashed`, `sampled:0.1`)
+    With `HFENDPOINT_JOBS_DIR` set, JSONL files of requests are accepted on `/api/v1/batches` and persisted in this directory,
+    their requests being replayed through the task routes while no other request is queued
+    With `HFENDPOINT_LOUDNESS_TARGET` set (in LUFS, i.e. `-23`), uploaded WAV files are normalized to this loudness
+    With `HFENDPOINT_AUDIO_PROMPT_LIMIT` set (in characters), longer transcription and translation prompts are truncated from the left,
+    the response carrying a `Warning` header, or rejected with 400 when `HFENDPOINT_AUDIO_PROMPT_OVERFLOW` is `reject`
+    With `HFENDPOINT_OIDC_ISSUER` set, tokens issued by this provider for one of the `HFENDPOINT_OIDC_AUDIENCE` are accepted as bearer tokens,
+    the caller being exposed through `RequestContext.subject` and `RequestContext.tenant` (read from `HFENDPOINT_OIDC_TENANT_CLAIM`)
+    Request bodies larger than `HFENDPOINT_BODY_LIMIT` bytes (unset by default) are rejected with 413
+    Settings may also be read from the TOML or YAML file at `HFENDPOINT_CONFIG`, see `load_config`
+    :param endpoint: Endpoint wrapping the handler to serve
+    :param interface: Interface the endpoint will be listening to incoming requests
+    :param port: Port on the interface the endpoint will be listening to incoming requests
+    """
+    ...
+
+def run_unavailable(reason: str, interface: str, port: int) -> None:
+    """
+    Serve a degraded endpoint on `interface:port` when the handler failed to initialize.
+    `/health` reports the endpoint unavailable, `/info` exposes the last line of `reason` and task routes answer 503
+    :param reason: Why the handler failed to initialize, i.e. the kind and message of the exception raised
+    :param interface: Interface the endpoint will be listening to incoming requests
+    :param port: Port on the interface the endpoint will be listening to incoming requests
+    """
+    ...
+
+def load_config() -> Dict[str, Any]:
+    """
+    Resolve the settings of the endpoint from the TOML or YAML file referenced by `HFENDPOINT_CONFIG`, if any,
+    each of them being overridden by its environment variable when set: `interface` (`INTERFACE`), `port` (`PORT`),
+    `body_limit` (`HFENDPOINT_BODY_LIMIT`), `queue_capacity` (`HFENDPOINT_QUEUE_CAPACITY`), `max_concurrent_requests`
+    (`HFENDPOINT_MAX_CONCURRENT_REQUESTS`), `request_timeout` (`HFENDPOINT_REQUEST_TIMEOUT`), `shutdown_grace_period`
+    (`HFENDPOINT_SHUTDOWN_GRACE_PERIOD`), `api_keys` (`HF_ENDPOINT_API_KEYS`) and `log_format` (`HFENDPOINT_LOG_FORMAT`, either `text` or `json`)
+    :return: The resolved settings, durations being expressed in seconds
+    :raises ValueError if the file cannot be read, is malformed or defines unknown settings
+    """
+    ...
+
+def load_snapshot(path: str) -> Dict[str, Optional[str]]:
+    """
+    Read the manifest at `path`, checking its signature against `HFENDPOINT_SNAPSHOT_KEY`
+    :param path: Path of the manifest, as exported through `GET /snapshot`
+    :return: The recorded environment variables, `None` for the ones which were not set
+    :raises ValueError if the manifest is malformed or its signature does not match
+    """
+    ...
@@ -1,6 +1,104 @@
 from enum import Enum
+from typing import List
 
 class TranscriptionResponseKind(Enum):
     TEXT = 1
     JSON = 2
     VERBOSE_JSON = 3
+    SRT = 4
+    VTT = 5
+
+class DecodedAudio:
+    """
+    Mono samples decoded from the uploaded audio, when built with the `audio-decode` feature.
+    Implements the buffer protocol over `float32` samples, `numpy.asarray(request.pcm)` does not copy them
+    """
+
+    @property
+    def sample_rate(self) -> int:
+        """Sample rate, in Hz, the audio was decoded at (`HFENDPOINT_AUDIO_SAMPLE_RATE`, 16000 by default)"""
+        ...
+
+    @property
+    def duration(self) -> float:
+        """Duration of the audio, in seconds"""
+        ...
+
+    def __len__(self) -> int: ...
+
+class SchemaError(ValueError):
+    """
+    Raised when building a transcription which would not be valid: a missing field, a segment or word ending before
+    it starts, or several segments sharing the same id
+    """
+
+class Segment:
+    @staticmethod
+    def builder() -> "SegmentBuilder": ...
+
+class Word:
+    @staticmethod
+    def builder() -> "WordBuilder": ...
+
+class VerboseTranscription:
+    @staticmethod
+    def builder() -> "VerboseTranscriptionBuilder": ...
+
+class SegmentBuilder:
+    """
+    Builds a `Segment`, `id`, `start`, `end`, `text` and `tokens` being required
+    """
+
+    def id(self, id: int) -> "SegmentBuilder": ...
+    def start(self, start: float) -> "SegmentBuilder": ...
+    def end(self, end: float) -> "SegmentBuilder": ...
+    def seek(self, seek: int) -> "SegmentBuilder": ...
+    def temperature(self, temperature: float) -> "SegmentBuilder": ...
+    def text(self, text: str) -> "SegmentBuilder": ...
+    def tokens(self, tokens: List[int]) -> "SegmentBuilder": ...
+    def avg_logprob(self, avg_logprob: float) -> "SegmentBuilder": ...
+    def compression_ratio(self, compression_ratio: float) -> "SegmentBuilder": ...
+    def no_speech_prob(self, no_speech_prob: float) -> "SegmentBuilder": ...
+    def language(self, language: str) -> "SegmentBuilder": ...
+    def channel(self, channel: str) -> "SegmentBuilder": ...
+    def build(self) -> Segment:
+        """
+        :raises SchemaError if a required field is not set or the segment ends before it starts
+        """
+        ...
+
+class WordBuilder:
+    """
+    Builds a `Word`, every field being required
+    """
+
+    def word(self, word: str) -> "WordBuilder": ...
+    def start(self, start: float) -> "WordBuilder": ...
+    def end(self, end: float) -> "WordBuilder": ...
+    def build(self) -> Word:
+        """
+        :raises SchemaError if a field is not set or the word ends before it starts
+        """
+        ...
+
+class VerboseTranscriptionBuilder:
+    """
+    Builds a `VerboseTranscription`, `text` being required
+    """
+
+    def text(self, text: str) -> "VerboseTranscriptionBuilder": ...
+    def duration(self, duration: float) -> "VerboseTranscriptionBuilder": ...
+    def language(self, language: str) -> "VerboseTranscriptionBuilder": ...
+    def segment(self, segment: Segment) -> "VerboseTranscriptionBuilder":
+        """Append `segment` to the segments of the transcription"""
+        ...
+    def segments(self, segments: List[Segment]) -> "VerboseTranscriptionBuilder": ...
+    def word(self, word: Word) -> "VerboseTranscriptionBuilder":
+        """Append `word` to the words of the transcription"""
+        ...
+    def words(self, words: List[Word]) -> "VerboseTranscriptionBuilder": ...
+    def build(self) -> VerboseTranscription:
+        """
+        :raises SchemaError if `text` is not set, a segment or word ends before it starts, or several segments share the same id
+        """
+        ...
@@ -0,0 +1,9 @@
+from ..._hfendpoints.openai.chat import (
+    ChatCompletionEndpoint,
+    ChatCompletionChoice,
+    ChatCompletionChunk,
+    ChatCompletionRequest,
+    ChatCompletionResponse,
+    ChatMessage,
+    Usage,
+)
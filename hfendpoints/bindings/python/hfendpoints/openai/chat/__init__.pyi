@@ -0,0 +1,73 @@
+from typing import List, Optional, Union
+
+from .. import ModelCard
+
+class ChatMessage:
+    @property
+    def role(self) -> str: ...
+    @property
+    def content(self) -> Optional[str]: ...
+    @property
+    def name(self) -> Optional[str]: ...
+
+class ChatCompletionRequest:
+    @property
+    def messages(self) -> List[ChatMessage]: ...
+    @property
+    def model(self) -> Optional[str]: ...
+    @property
+    def max_completion_tokens(self) -> Optional[int]: ...
+    @property
+    def temperature(self) -> Optional[float]: ...
+    @property
+    def top_p(self) -> Optional[float]: ...
+    @property
+    def stop(self) -> List[str]: ...
+    @property
+    def seed(self) -> Optional[int]: ...
+    @property
+    def stream(self) -> bool: ...
+
+class ChatCompletionChoice:
+    def __init__(self, index: int, content: str, finish_reason: str = "stop"): ...
+
+class Usage:
+    def __init__(self, prompt_tokens: int, completion_tokens: int): ...
+
+class ChatCompletionResponse:
+    def __init__(
+        self,
+        model: str,
+        choices: List[ChatCompletionChoice],
+        usage: Optional[Usage] = None,
+    ): ...
+
+class ChatCompletionChunk:
+    def __init__(self, index: int, delta: Union[str, bytes], finish_reason: Optional[str] = None):
+        """
+        Delta of one of the choices, yielded by handlers streaming the completion
+        :param index: Index of the choice
+        :param delta: Text generated since the previous chunk, bytes may end in the middle of a UTF-8 character
+        :param finish_reason: Reason the model stopped generating tokens, for the last chunk of the choice
+        """
+        ...
+
+class ChatCompletionEndpoint:
+    def __init__(
+        self,
+        handler,
+        model: Optional[ModelCard] = None,
+        workers: Optional[int] = None,
+        fallback=None,
+        fallback_after: Optional[float] = None,
+    ):
+        """
+        :param handler: Handler processing the requests, or a list of handler instances, one per worker
+        :param model: Model card reported by the endpoint
+        :param workers: Number of workers, each processing one request at a time from the shared queue.
+                        Defaults to `HFENDPOINT_WORKERS`; when unset, a single handler processes requests concurrently
+        :param fallback: Handler, usually serving a smaller model, the requests failed by `handler` are retried on.
+                         Responses report the model which served them through the `x-served-by` header
+        :param fallback_after: Seconds after which requests `handler` did not start answering are retried on `fallback`
+        """
+        ...
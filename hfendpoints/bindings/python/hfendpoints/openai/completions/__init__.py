@@ -0,0 +1,9 @@
+from ..._hfendpoints.openai.completions import (
+    CompletionEndpoint,
+    CompletionChoice,
+    CompletionChunk,
+    CompletionLogprobs,
+    CompletionRequest,
+    CompletionResponse,
+    Usage,
+)
@@ -0,0 +1,99 @@
+from typing import Dict, List, Optional, Union
+
+from .. import ModelCard
+
+class CompletionRequest:
+    @property
+    def prompt(self) -> List[str]: ...
+    @property
+    def model(self) -> Optional[str]: ...
+    @property
+    def suffix(self) -> Optional[str]: ...
+    @property
+    def max_tokens(self) -> Optional[int]: ...
+    @property
+    def temperature(self) -> Optional[float]: ...
+    @property
+    def top_p(self) -> Optional[float]: ...
+    @property
+    def stop(self) -> List[str]: ...
+    @property
+    def logprobs(self) -> Optional[int]: ...
+    @property
+    def seed(self) -> Optional[int]: ...
+    @property
+    def stream(self) -> bool: ...
+    @property
+    def user(self) -> Optional[str]: ...
+
+class CompletionLogprobs:
+    def __init__(
+        self,
+        tokens: List[str],
+        token_logprobs: List[float],
+        top_logprobs: Optional[List[Dict[str, float]]] = None,
+    ):
+        """
+        Log probabilities of the tokens generated for a choice, or since the previous chunk when streaming
+        :param tokens: Generated tokens, their offsets within the text being derived from their length
+        :param token_logprobs: Log probability of each token
+        :param top_logprobs: Most likely alternatives of each token, along with their log probability
+        """
+        ...
+
+class CompletionChoice:
+    def __init__(
+        self,
+        index: int,
+        text: str,
+        finish_reason: str = "stop",
+        logprobs: Optional[CompletionLogprobs] = None,
+    ): ...
+
+class Usage:
+    def __init__(self, prompt_tokens: int, completion_tokens: int): ...
+
+class CompletionResponse:
+    def __init__(
+        self,
+        model: str,
+        choices: List[CompletionChoice],
+        usage: Optional[Usage] = None,
+    ): ...
+
+class CompletionChunk:
+    def __init__(
+        self,
+        index: int,
+        delta: Union[str, bytes],
+        finish_reason: Optional[str] = None,
+        logprobs: Optional[CompletionLogprobs] = None,
+    ):
+        """
+        Delta of one of the choices, yielded by handlers streaming the completion
+        :param index: Index of the choice
+        :param delta: Text generated since the previous chunk, bytes may end in the middle of a UTF-8 character
+        :param finish_reason: Reason the model stopped generating tokens, for the last chunk of the choice
+        :param logprobs: Log probabilities of the tokens generated since the previous chunk
+        """
+        ...
+
+class CompletionEndpoint:
+    def __init__(
+        self,
+        handler,
+        model: Optional[ModelCard] = None,
+        workers: Optional[int] = None,
+        fallback=None,
+        fallback_after: Optional[float] = None,
+    ):
+        """
+        :param handler: Handler processing the requests, or a list of handler instances, one per worker
+        :param model: Model card reported by the endpoint
+        :param workers: Number of workers, each processing one request at a time from the shared queue.
+                        Defaults to `HFENDPOINT_WORKERS`; when unset, a single handler processes requests concurrently
+        :param fallback: Handler, usually serving a smaller model, the requests failed by `handler` are retried on.
+                         Responses report the model which served them through the `x-served-by` header
+        :param fallback_after: Seconds after which requests `handler` did not start answering are retried on `fallback`
+        """
+        ...
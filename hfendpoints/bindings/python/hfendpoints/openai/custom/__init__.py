@@ -0,0 +1,245 @@
+"""
+Tasks defined from Python rather than in Rust.
+
+The requests and responses of the task are declared as dataclasses (or pydantic models), from which the endpoint
+generates the route, the validation of the requests and responses, and their documentation on `/docs`:
+
+    @dataclass
+    class ClassificationRequest:
+        text: str
+        top_k: int = 3
+
+    @dataclass
+    class ClassificationResponse:
+        labels: List[str]
+        scores: List[float]
+
+    task = Task("/classify", ClassificationRequest, ClassificationResponse, summary="Classify a text")
+    run(CustomEndpoint(task, ClassificationHandler(model_id)), interface, port)
+
+Handlers receive an instance of the request type and return an instance of the response type (or a dict).
+They answer a single response, async generators are not supported.
+"""
+
+import dataclasses
+import inspect
+import json
+import types
+from collections.abc import Sequence
+from typing import Any, Dict, Optional, Union, get_args, get_origin, get_type_hints
+
+from ..._hfendpoints.openai import ModelCard
+from ..._hfendpoints.openai.custom import CustomEndpoint as _CustomEndpoint, TaskDefinition
+
+_SCALARS = {str: "string", bool: "boolean", int: "integer", float: "number"}
+
+
+def _optional_member(annotation) -> Optional[Any]:
+    """
+    Return `T` if `annotation` is `Optional[T]`, None otherwise
+    """
+    if get_origin(annotation) not in (Union, types.UnionType):
+        return None
+
+    members = [member for member in get_args(annotation) if member is not type(None)]
+    if len(members) != 1:
+        raise TypeError(f"Only unions with None (Optional) are supported, got {annotation}")
+    return members[0]
+
+
+def _json_default(value) -> Dict[str, Any]:
+    try:
+        json.dumps(value)
+    except (TypeError, ValueError):
+        return {}
+    return {"default": value}
+
+
+def _fields(model: type):
+    """
+    Iterate over the `(name, annotation, required, default, description)` of the fields of `model`
+    """
+    if dataclasses.is_dataclass(model):
+        hints = get_type_hints(model)
+        for field in dataclasses.fields(model):
+            if field.default is not dataclasses.MISSING:
+                default = _json_default(field.default)
+            elif field.default_factory is not dataclasses.MISSING:
+                default = _json_default(field.default_factory())
+            else:
+                default = None
+            yield field.name, hints[field.name], default is None, default or {}, field.metadata.get("description")
+    else:
+        for name, field in model.model_fields.items():
+            required = field.is_required()
+            default = {} if required else _json_default(field.get_default(call_default_factory=True))
+            yield name, field.annotation, required, default, field.description
+
+
+def _is_model(annotation) -> bool:
+    return inspect.isclass(annotation) and (
+        dataclasses.is_dataclass(annotation) or hasattr(annotation, "model_fields")
+    )
+
+
+def schema_of(annotation) -> Dict[str, Any]:
+    """
+    Describe `annotation` with the subset of JSON Schema understood by the endpoint
+    """
+    if annotation is Any:
+        return {}
+
+    if (member := _optional_member(annotation)) is not None:
+        return {**schema_of(member), "nullable": True}
+
+    if annotation in _SCALARS:
+        return {"type": _SCALARS[annotation]}
+
+    origin, args = get_origin(annotation), get_args(annotation)
+    if annotation in (list, tuple) or origin in (list, tuple, Sequence):
+        schema = {"type": "array"}
+        if args and args[0] is not Any:
+            schema["items"] = schema_of(args[0])
+        return schema
+
+    if annotation is dict or origin is dict:
+        return {"type": "object"}
+
+    if _is_model(annotation):
+        properties, required = {}, []
+        for name, field_annotation, is_required, default, description in _fields(annotation):
+            properties[name] = {**schema_of(field_annotation), **default}
+            if description:
+                properties[name]["description"] = description
+            if is_required:
+                required.append(name)
+
+        schema = {"type": "object", "properties": properties, "required": required}
+        # Dataclasses without docstring get their signature as documentation
+        description = inspect.getdoc(annotation)
+        if description and not description.startswith(f"{annotation.__name__}("):
+            schema["description"] = description
+        return schema
+
+    raise TypeError(f"{annotation} cannot be described as a JSON schema")
+
+
+def _decode(annotation, value):
+    """
+    Build an instance of `annotation` from the validated JSON `value`, recursing into nested models
+    """
+    if value is None:
+        return None
+
+    if (member := _optional_member(annotation)) is not None:
+        return _decode(member, value)
+
+    if hasattr(annotation, "model_validate"):
+        return annotation.model_validate(value)
+
+    if dataclasses.is_dataclass(annotation):
+        hints = get_type_hints(annotation)
+        return annotation(**{name: _decode(hints[name], item) for name, item in value.items()})
+
+    origin, args = get_origin(annotation), get_args(annotation)
+    if origin in (list, tuple, Sequence) and args:
+        items = [_decode(args[0], item) for item in value]
+        return tuple(items) if origin is tuple else items
+
+    return value
+
+
+def _encode(response):
+    """
+    Turn the response returned by the handler into JSON compatible values
+    """
+    if hasattr(response, "model_dump"):
+        return response.model_dump(mode="json")
+    if dataclasses.is_dataclass(response) and not inspect.isclass(response):
+        return dataclasses.asdict(response)
+    return response
+
+
+class Task:
+    """
+    Task served on `path` (relative to `/api/v1`), with requests and responses described by `request`
+    and `response`: dataclasses, pydantic models or JSON Schema documents (`dict`)
+    """
+
+    def __init__(self, path: str, request, response, summary: Optional[str] = None, description: Optional[str] = None):
+        self.request = request
+        self.response = response
+        self.definition = TaskDefinition(
+            path,
+            request if isinstance(request, dict) else schema_of(request),
+            response if isinstance(response, dict) else schema_of(response),
+            summary,
+            description,
+        )
+
+    @property
+    def path(self) -> str:
+        return self.definition.path
+
+    def decode(self, request: Dict[str, Any]):
+        """
+        Build the request handed to the handler from the validated JSON body
+        """
+        return request if isinstance(self.request, dict) else _decode(self.request, request)
+
+
+class _TypedHandler:
+    """
+    Hand the request to `handler` as an instance of the declared type, and its response back as JSON
+    """
+
+    def __init__(self, task: Task, handler):
+        if inspect.isasyncgenfunction(handler) or inspect.isasyncgenfunction(getattr(handler, "__call__", None)):
+            raise TypeError("Custom tasks answer a single response, async generators are not supported")
+
+        self.__protocol_version__ = getattr(handler, "__protocol_version__", 1)
+        self._task = task
+        self._handler = handler
+
+    def __call__(self, request, ctx):
+        return _encode(self._handler(self._task.decode(request), ctx))
+
+
+class _AsyncTypedHandler(_TypedHandler):
+    async def __call__(self, request, ctx):
+        return _encode(await self._handler(self._task.decode(request), ctx))
+
+
+def _typed(task: Task, handler) -> _TypedHandler:
+    # Plain `def` handlers keep running on a dedicated thread
+    if inspect.iscoroutinefunction(handler) or inspect.iscoroutinefunction(getattr(handler, "__call__", None)):
+        return _AsyncTypedHandler(task, handler)
+    return _TypedHandler(task, handler)
+
+
+class CustomEndpoint:
+    """
+    Endpoint serving `task`, to be provided to `hfendpoints.openai.run`
+    """
+
+    def __init__(self, task: Task, handler, model: Optional[ModelCard] = None, workers: Optional[int] = None):
+        """
+        :param task: Task served by the endpoint
+        :param handler: Handler processing the requests, or a list of handler instances, one per worker
+        :param model: Model card reported by the endpoint
+        :param workers: Number of workers, each processing one request at a time from the shared queue.
+                        Defaults to `HFENDPOINT_WORKERS`; when unset, a single handler processes requests concurrently
+        """
+        if isinstance(handler, (list, tuple)):
+            handler = [_typed(task, instance) for instance in handler]
+        else:
+            handler = _typed(task, handler)
+
+        self.task = task
+        self._endpoint = _CustomEndpoint(task.definition, handler, model, workers)
+
+    def _serve_(self, interface: str, port: int):
+        return self._endpoint._serve_(interface, port)
+
+    def _mount_(self, task: int):
+        return self._endpoint._mount_(task)
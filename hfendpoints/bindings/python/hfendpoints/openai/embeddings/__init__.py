@@ -0,0 +1,5 @@
+from ..._hfendpoints.openai.embeddings import (
+    EmbeddingEndpoint,
+    EmbeddingRequest,
+    EmbeddingResponse,
+)
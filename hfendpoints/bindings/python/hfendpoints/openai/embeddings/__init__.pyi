@@ -0,0 +1,47 @@
+from typing import List, Optional
+
+from .. import ModelCard
+
+class EmbeddingRequest:
+    @property
+    def input(self) -> List[str]: ...
+    @property
+    def model(self) -> Optional[str]: ...
+    @property
+    def encoding_format(self) -> str: ...
+    @property
+    def dimensions(self) -> Optional[int]: ...
+    @property
+    def quantization(self) -> Optional[str]:
+        """
+        Extension: quantization requested by the client (`int8`, `uint8` or `float16`), applied by the endpoint
+        on the floats returned by the handler
+        """
+        ...
+    @property
+    def user(self) -> Optional[str]: ...
+
+class EmbeddingResponse:
+    def __init__(
+        self, model: str, embeddings: List[List[float]], prompt_tokens: int
+    ): ...
+
+class EmbeddingEndpoint:
+    def __init__(
+        self,
+        handler,
+        model: Optional[ModelCard] = None,
+        workers: Optional[int] = None,
+        fallback=None,
+        fallback_after: Optional[float] = None,
+    ):
+        """
+        :param handler: Handler processing the requests, or a list of handler instances, one per worker
+        :param model: Model card reported by the endpoint
+        :param workers: Number of workers, each processing one request at a time from the shared queue.
+                        Defaults to `HFENDPOINT_WORKERS`; when unset, a single handler processes requests concurrently
+        :param fallback: Handler, usually serving a smaller model, the requests failed by `handler` are retried on.
+                         Responses report the model which served them through the `x-served-by` header
+        :param fallback_after: Seconds after which requests `handler` did not start answering are retried on `fallback`
+        """
+        ...
@@ -0,0 +1,10 @@
+from ..._hfendpoints.openai.images import (
+    ImageEditEndpoint,
+    ImageEditRequest,
+    ImageFile,
+    ImageGenerationEndpoint,
+    ImageGenerationRequest,
+    ImageGenerationResponse,
+    ImageVariationEndpoint,
+    ImageVariationRequest,
+)
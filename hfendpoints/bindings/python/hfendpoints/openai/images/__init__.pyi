@@ -0,0 +1,171 @@
+from typing import List, Optional, Union
+
+from .. import ModelCard
+
+class ImageFile:
+    """
+    Image uploaded along an edit or variation request, supporting the buffer protocol:
+    `memoryview(image)` exposes its content without copying it
+    """
+
+    def __len__(self) -> int: ...
+    @property
+    def content_type(self) -> str:
+        """
+        Media type of the image, i.e. `image/png`, sniffed from its content when not provided by the client
+        """
+        ...
+
+class ImageGenerationRequest:
+    @property
+    def prompt(self) -> str: ...
+    @property
+    def model(self) -> Optional[str]: ...
+    @property
+    def n(self) -> int: ...
+    @property
+    def size(self) -> str:
+        """
+        Size of the images, `auto` or `{width}x{height}` in pixels
+        """
+        ...
+    @property
+    def width(self) -> Optional[int]:
+        """
+        Width of the images in pixels, `None` when `size` is `auto`
+        """
+        ...
+    @property
+    def height(self) -> Optional[int]:
+        """
+        Height of the images in pixels, `None` when `size` is `auto`
+        """
+        ...
+    @property
+    def quality(self) -> str: ...
+    @property
+    def response_format(self) -> str: ...
+    @property
+    def user(self) -> Optional[str]: ...
+
+class ImageGenerationResponse:
+    """
+    Images generated by the handlers of generation, edit and variation requests
+    """
+
+    def __init__(
+        self, images: List[Union[bytes, str]], revised_prompt: Optional[str] = None
+    ):
+        """
+        :param images: Encoded content (i.e. PNG) of every generated image, or the URL it is available at.
+                       The endpoint renders them according to the `response_format` requested, images only
+                       available through their URL cannot be returned as `b64_json`
+        :param revised_prompt: Prompt the images were generated from, if the model revised the one provided
+        """
+        ...
+
+class ImageGenerationEndpoint:
+    def __init__(
+        self,
+        handler,
+        model: Optional[ModelCard] = None,
+        workers: Optional[int] = None,
+        fallback=None,
+        fallback_after: Optional[float] = None,
+    ):
+        """
+        :param handler: Handler processing the requests, or a list of handler instances, one per worker
+        :param model: Model card reported by the endpoint
+        :param workers: Number of workers, each processing one request at a time from the shared queue.
+                        Defaults to `HFENDPOINT_WORKERS`; when unset, a single handler processes requests concurrently
+        :param fallback: Handler, usually serving a smaller model, the requests failed by `handler` are retried on.
+                         Responses report the model which served them through the `x-served-by` header
+        :param fallback_after: Seconds after which requests `handler` did not start answering are retried on `fallback`
+        """
+        ...
+
+class ImageEditRequest:
+    @property
+    def image(self) -> ImageFile: ...
+    @property
+    def mask(self) -> Optional[ImageFile]:
+        """
+        Image whose fully transparent areas indicate where `image` should be edited
+        """
+        ...
+    @property
+    def prompt(self) -> str: ...
+    @property
+    def model(self) -> Optional[str]: ...
+    @property
+    def n(self) -> int: ...
+    @property
+    def size(self) -> str: ...
+    @property
+    def width(self) -> Optional[int]: ...
+    @property
+    def height(self) -> Optional[int]: ...
+    @property
+    def quality(self) -> str: ...
+    @property
+    def response_format(self) -> str: ...
+    @property
+    def user(self) -> Optional[str]: ...
+
+class ImageVariationRequest:
+    @property
+    def image(self) -> ImageFile: ...
+    @property
+    def model(self) -> Optional[str]: ...
+    @property
+    def n(self) -> int: ...
+    @property
+    def size(self) -> str: ...
+    @property
+    def width(self) -> Optional[int]: ...
+    @property
+    def height(self) -> Optional[int]: ...
+    @property
+    def response_format(self) -> str: ...
+    @property
+    def user(self) -> Optional[str]: ...
+
+class ImageEditEndpoint:
+    def __init__(
+        self,
+        handler,
+        model: Optional[ModelCard] = None,
+        workers: Optional[int] = None,
+        fallback=None,
+        fallback_after: Optional[float] = None,
+    ):
+        """
+        :param handler: Handler processing the requests, or a list of handler instances, one per worker
+        :param model: Model card reported by the endpoint
+        :param workers: Number of workers, each processing one request at a time from the shared queue.
+                        Defaults to `HFENDPOINT_WORKERS`; when unset, a single handler processes requests concurrently
+        :param fallback: Handler, usually serving a smaller model, the requests failed by `handler` are retried on.
+                         Responses report the model which served them through the `x-served-by` header
+        :param fallback_after: Seconds after which requests `handler` did not start answering are retried on `fallback`
+        """
+        ...
+
+class ImageVariationEndpoint:
+    def __init__(
+        self,
+        handler,
+        model: Optional[ModelCard] = None,
+        workers: Optional[int] = None,
+        fallback=None,
+        fallback_after: Optional[float] = None,
+    ):
+        """
+        :param handler: Handler processing the requests, or a list of handler instances, one per worker
+        :param model: Model card reported by the endpoint
+        :param workers: Number of workers, each processing one request at a time from the shared queue.
+                        Defaults to `HFENDPOINT_WORKERS`; when unset, a single handler processes requests concurrently
+        :param fallback: Handler, usually serving a smaller model, the requests failed by `handler` are retried on.
+                         Responses report the model which served them through the `x-served-by` header
+        :param fallback_after: Seconds after which requests `handler` did not start answering are retried on `fallback`
+        """
+        ...
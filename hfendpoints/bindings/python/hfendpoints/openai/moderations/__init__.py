@@ -0,0 +1,6 @@
+from ..._hfendpoints.openai.moderations import (
+    ModerationEndpoint,
+    ModerationRequest,
+    ModerationResponse,
+    ModerationResult,
+)
@@ -0,0 +1,55 @@
+from typing import Dict, List, Optional
+
+from .. import ModelCard
+
+class ModerationRequest:
+    @property
+    def input(self) -> List[str]: ...
+    @property
+    def model(self) -> Optional[str]: ...
+
+class ModerationResult:
+    def __init__(
+        self,
+        category_scores: Dict[str, float],
+        categories: Optional[Dict[str, bool]] = None,
+    ):
+        """
+        :param category_scores: Score predicted by the model for every category, i.e. `harassment` or `self-harm/intent`
+        :param categories: Whether each category is flagged, the categories left out being flagged when scored at least 0.5
+        """
+        ...
+    @property
+    def flagged(self) -> bool:
+        """
+        Whether any of the categories is flagged
+        """
+        ...
+
+class ModerationResponse:
+    def __init__(self, model: str, results: List[ModerationResult]):
+        """
+        :param model: Model which classified the inputs
+        :param results: Classification of every input, in the order of `ModerationRequest.input`
+        """
+        ...
+
+class ModerationEndpoint:
+    def __init__(
+        self,
+        handler,
+        model: Optional[ModelCard] = None,
+        workers: Optional[int] = None,
+        fallback=None,
+        fallback_after: Optional[float] = None,
+    ):
+        """
+        :param handler: Handler processing the requests, or a list of handler instances, one per worker
+        :param model: Model card reported by the endpoint
+        :param workers: Number of workers, each processing one request at a time from the shared queue.
+                        Defaults to `HFENDPOINT_WORKERS`; when unset, a single handler processes requests concurrently
+        :param fallback: Handler, usually serving a smaller model, the requests failed by `handler` are retried on.
+                         Responses report the model which served them through the `x-served-by` header
+        :param fallback_after: Seconds after which requests `handler` did not start answering are retried on `fallback`
+        """
+        ...
@@ -0,0 +1,5 @@
+from ..._hfendpoints.openai.rerank import (
+    RerankEndpoint,
+    RerankRequest,
+    RerankResponse,
+)
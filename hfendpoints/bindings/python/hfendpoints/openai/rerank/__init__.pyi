@@ -0,0 +1,43 @@
+from typing import List, Optional
+
+from .. import ModelCard
+
+class RerankRequest:
+    @property
+    def query(self) -> str: ...
+    @property
+    def documents(self) -> List[str]: ...
+    @property
+    def model(self) -> Optional[str]: ...
+    @property
+    def top_n(self) -> Optional[int]: ...
+    @property
+    def return_documents(self) -> bool: ...
+
+class RerankResponse:
+    def __init__(self, scores: List[float]):
+        """
+        :param scores: Relevance of every document to the query, in the order of `RerankRequest.documents`.
+                       Documents are ranked by the endpoint, the most relevant first
+        """
+        ...
+
+class RerankEndpoint:
+    def __init__(
+        self,
+        handler,
+        model: Optional[ModelCard] = None,
+        workers: Optional[int] = None,
+        fallback=None,
+        fallback_after: Optional[float] = None,
+    ):
+        """
+        :param handler: Handler processing the requests, or a list of handler instances, one per worker
+        :param model: Model card reported by the endpoint
+        :param workers: Number of workers, each processing one request at a time from the shared queue.
+                        Defaults to `HFENDPOINT_WORKERS`; when unset, a single handler processes requests concurrently
+        :param fallback: Handler, usually serving a smaller model, the requests failed by `handler` are retried on.
+                         Responses report the model which served them through the `x-served-by` header
+        :param fallback_after: Seconds after which requests `handler` did not start answering are retried on `fallback`
+        """
+        ...
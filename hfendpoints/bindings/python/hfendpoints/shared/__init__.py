@@ -0,0 +1 @@
+from .._hfendpoints.shared import get, get_or_init, register
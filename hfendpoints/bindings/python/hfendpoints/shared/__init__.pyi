@@ -0,0 +1,31 @@
+from typing import Any, Callable, TypeVar
+
+T = TypeVar("T")
+
+def get_or_init(name: str, factory: Callable[[], T]) -> T:
+    """
+    Retrieve the object shared as `name` across every invocation of the handler in this process,
+    calling `factory()` to create it on first use.
+    Concurrent callers wait for the ongoing `factory()` instead of loading the object again,
+    if `factory()` raises, the exception is propagated and the next caller retries.
+    :param name: Name the object is shared as
+    :param factory: Callable creating the object, i.e. loading the model weights
+    :return: The shared object
+    """
+    ...
+
+def register(name: str, value: Any) -> None:
+    """
+    Share an already created `value` as `name`, raising `ValueError` if the name is already taken
+    :param name: Name the object is shared as
+    :param value: Object to share
+    """
+    ...
+
+def get(name: str) -> Any:
+    """
+    Retrieve the object shared as `name`, raising `KeyError` if nothing was registered under this name
+    :param name: Name the object is shared as
+    :return: The shared object
+    """
+    ...
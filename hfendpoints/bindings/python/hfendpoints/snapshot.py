@@ -0,0 +1,97 @@
+import json
+import os
+from argparse import ArgumentParser, Namespace
+from typing import Optional
+from urllib.error import HTTPError, URLError
+from urllib.request import Request, urlopen
+
+# Path of the signed manifest the endpoint boots from
+SNAPSHOT_ENV = "HFENDPOINT_SNAPSHOT"
+
+# Key signing the exported manifests and verifying the ones the endpoint boots from
+SNAPSHOT_KEY_ENV = "HFENDPOINT_SNAPSHOT_KEY"
+
+
+def restore(path: Optional[str] = None) -> bool:
+    """
+    Apply the environment recorded in the manifest at `path` (`HFENDPOINT_SNAPSHOT` by default) to the
+    current process, unsetting the recorded variables the manifest does not define. The signature is
+    checked against `HFENDPOINT_SNAPSHOT_KEY` and the server refuses to start if the endpoint differs
+    from the manifest once booted
+    :param path: Path of the signed manifest, `HFENDPOINT_SNAPSHOT` if not provided
+    :return: `True` if a manifest was restored
+    :raises ValueError if the manifest cannot be read or its signature does not match
+    """
+    path = path or os.environ.get(SNAPSHOT_ENV)
+    if not path:
+        return False
+
+    from hfendpoints._hfendpoints.openai import load_snapshot
+
+    for name, value in load_snapshot(path).items():
+        if value is None:
+            os.environ.pop(name, None)
+        else:
+            os.environ[name] = value
+
+    # The server checks the endpoint against the manifest it booted from
+    os.environ[SNAPSHOT_ENV] = path
+    return True
+
+
+def export(url: str, api_key: Optional[str] = None) -> bytes:
+    """
+    Download the signed manifest of the endpoint listening at `url`
+    :param url: Base URL of the endpoint, i.e. `http://localhost:8000`
+    :param api_key: API key of the endpoint, if required
+    :return: The manifest, as served by the endpoint
+    """
+    headers = {"Authorization": f"Bearer {api_key}"} if api_key else {}
+    with urlopen(Request(f"{url.rstrip('/')}/snapshot", headers=headers), timeout=10) as response:
+        return response.read()
+
+
+def add_arguments(parser: ArgumentParser):
+    actions = parser.add_subparsers(dest="action", required=True)
+
+    export_parser = actions.add_parser("export", help="Download the signed manifest of a running endpoint")
+    export_parser.add_argument("--url", default="http://localhost:8000", help="Base URL of the endpoint")
+    export_parser.add_argument("--api-key", default=os.environ.get("OPENAI_API_KEY"), help="API key of the endpoint")
+    export_parser.add_argument("--output", "-o", default=None, help="File to write the manifest to, stdout if omitted")
+
+    verify_parser = actions.add_parser("verify", help="Check the signature of a manifest and print its environment")
+    verify_parser.add_argument("manifest", help="Path of the manifest")
+
+
+def run(args: Namespace) -> int:
+    """
+    Run the `hfendpoints snapshot` action, returning a non-zero exit code on failure
+    """
+    if args.action == "export":
+        try:
+            manifest = export(args.url, args.api_key)
+        except HTTPError as e:
+            hint = f", is {SNAPSHOT_KEY_ENV} set on the endpoint?" if e.code == 404 else ""
+            print(f"Failed to export the snapshot: {e.code} {e.reason}{hint}")
+            return 1
+        except URLError as e:
+            print(f"Failed to reach the endpoint at {args.url}: {e.reason}")
+            return 1
+
+        if args.output:
+            with open(args.output, "wb") as output:
+                output.write(manifest)
+        else:
+            print(manifest.decode())
+        return 0
+
+    from hfendpoints._hfendpoints.openai import load_snapshot
+
+    try:
+        environment = load_snapshot(args.manifest)
+    except ValueError as e:
+        print(f"Invalid snapshot: {e}")
+        return 1
+
+    print(json.dumps(environment, indent=2))
+    return 0
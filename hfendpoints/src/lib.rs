@@ -2,6 +2,8 @@
 mod python {
     use hfendpoints_audio as audio;
     use hfendpoints_binding_python::ImportablePyModuleBuilder;
+    use hfendpoints_client as client;
+    use hfendpoints_core::{HANDLER_PROTOCOL_VERSION, shared};
     use hfendpoints_openai as openai;
     use pyo3::prelude::*;
 
@@ -9,7 +11,20 @@ mod python {
 
     #[pymodule]
     pub fn _hfendpoints(py: Python, m: Bound<'_, PyModule>) -> PyResult<()> {
-        tracing_subscriber::fmt::init();
+        // Logs are written in the format configured through HFENDPOINT_LOG_FORMAT or the configuration
+        // file, an invalid file being reported once the endpoint is served
+        #[cfg(not(feature = "otel"))]
+        match openai::EndpointConfig::from_env()
+            .map(|config| config.log_format)
+            .unwrap_or_default()
+        {
+            openai::LogFormat::Text => tracing_subscriber::fmt::init(),
+            openai::LogFormat::Json => tracing_subscriber::fmt().json().init(),
+        }
+
+        // Spans are exported over OTLP as configured through the OTEL_* environment variables
+        #[cfg(feature = "otel")]
+        openai::init_tracing();
 
         let name = m.name()?.extract::<String>()?;
 
@@ -17,10 +32,13 @@ mod python {
         let pymodule_hfendpoints = ImportablePyModuleBuilder::from(m)
             .defaults()?
             .add_submodule(&audio::python::bind(py, &format!("{name}.audio"))?)?
+            .add_submodule(&client::python::bind(py, &format!("{name}.client"))?)?
             .add_submodule(&openai::python::bind(py, &format!("{name}.openai"))?)?
+            .add_submodule(&shared::python::bind(py, &format!("{name}.shared"))?)?
             .finish();
 
         pymodule_hfendpoints.add("__version__", __VERSION__)?;
+        pymodule_hfendpoints.add("__handler_protocol_version__", HANDLER_PROTOCOL_VERSION)?;
         Ok(())
     }
 }